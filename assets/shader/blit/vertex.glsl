@@ -6,7 +6,13 @@
 
 #version 440
 
+// Compiled a second time with UNIFORM_PARAMS defined for
+// adapters without push constants.
+#ifdef UNIFORM_PARAMS
+layout (set = 1, binding = 0) uniform Globals {
+#else
 layout (push_constant) uniform Globals {
+#endif
     mat4 uOrtho;
     vec2 uPos;
     vec2 uSize;
@@ -10,19 +10,25 @@ layout (location = 0) out vec4 oColor;
 layout (set = 0, binding = 0) uniform texture2DArray uBlockTextures;
 layout (set = 0, binding = 1) uniform sampler uBlockSampler;
 
-const vec4 fogColor = vec4(0.6, 0.7, 0.8, 1.0);
+// Changes with the time of day and the weather.
+layout (set = 0, binding = 2) uniform Lighting {
+    // The direction towards the sun or moon.
+    vec4 uLightDir;
+    vec4 uLightColor;
+    vec4 uAmbientColor;
+    vec4 uFogColor;
+};
+
 const float fogDensity = 0.005;
 
-const vec3 lightDir1 = vec3(1.0, 1.0, 0.3);
-const vec3 lightDir2 = vec3(-0.4, -0.7, -0.8);
-const vec3 lightColor = vec3(1.0, 0.8, 0.5);
+// Fill light from the opposite side of the sky.
+const vec3 fillDir = vec3(-0.4, -0.7, -0.8);
 
 void main() {
     // Shading
-    float ambient = 0.3;
     vec3 normal = normalize(iNormal);
-    float diff = max(dot(normal, lightDir1), 0.0) + max(dot(normal, lightDir2), 0.0) * 0.4;
-    vec4 shaded = vec4((ambient + diff) * lightColor, 1.0);
+    float diff = max(dot(normal, uLightDir.xyz), 0.0) + max(dot(normal, fillDir), 0.0) * 0.4;
+    vec4 shaded = vec4(uAmbientColor.rgb + diff * uLightColor.rgb, 1.0);
 
     // Fog
     float fogDepth = length(iViewPos);
@@ -31,7 +37,7 @@ void main() {
 
     vec4 col = shaded * texture(sampler2DArray(uBlockTextures, uBlockSampler), iTexCoord);
 
-    col = mix(col, fogColor, fogAmount);
+    col = mix(col, uFogColor, fogAmount);
 
     oColor = col;
 }
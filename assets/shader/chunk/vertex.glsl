@@ -9,11 +9,30 @@ layout (location = 1) out vec3 oViewPos;
 layout (location = 2) out vec3 oWorldPos;
 layout (location = 3) out vec3 oNormal;
 
+// Compiled a second time with UNIFORM_PARAMS defined for
+// adapters without push constants, and a third time with
+// INDIRECT_PARAMS defined for chunks drawn indirectly.
+#if defined(INDIRECT_PARAMS)
+layout (set = 1, binding = 0) uniform Camera {
+    mat4 uView;
+    mat4 uPerspective;
+};
+// Each chunk's draw starts at the instance with its transform.
+layout (set = 1, binding = 1) readonly buffer Transforms {
+    vec4 uTransforms[];
+};
+#define uTransform uTransforms[gl_InstanceIndex]
+#else
+#ifdef UNIFORM_PARAMS
+layout (set = 1, binding = 0) uniform Globals {
+#else
 layout (push_constant) uniform Globals {
+#endif
     vec4 uTransform;
     mat4 uView;
     mat4 uPerspective;
 };
+#endif
 
 void main() {
     oTexCoord = iTexCoord;
@@ -4,5 +4,8 @@
 #define BIOME_DESERT 3
 #define BIOME_FOREST 4
 #define BIOME_RIVER 5
+#define BIOME_MOUNTAINS 6
+#define BIOME_SWAMP 7
+#define BIOME_TUNDRA 8
 
-#define NUM_BIOMES 6
+#define NUM_BIOMES 9
@@ -5,3 +5,8 @@
 #define BLOCK_SAND 4
 #define BLOCK_MELIUM 5
 #define BLOCK_WATER 6
+#define BLOCK_GRAVEL 7
+// Ores are only placed by post-processing on the CPU.
+#define BLOCK_COAL_ORE 8
+#define BLOCK_IRON_ORE 9
+#define BLOCK_GOLD_ORE 10
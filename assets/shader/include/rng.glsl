@@ -11,3 +11,20 @@ uint random(uvec2 p) {
     h32 = PRIME32_3 * (h32 ^ (h32 >> 13));
     return h32 ^ (h32 >> 16);
 }
+
+// Salts for `deriveSeed`, one per independent random stream.
+// Must match the `SALT_*` constants in worldgen/src/noise.rs.
+#define SALT_ZOOM 0
+#define SALT_SMOOTH 1
+#define SALT_LAND 2
+#define SALT_CAVES 3
+#define SALT_TEMPERATURE 4
+#define SALT_HUMIDITY 5
+#define SALT_STRUCTURES 6
+#define SALT_ORES 7
+
+// Derives a 32-bit seed from the 64-bit world seed,
+// given as (low bits, high bits).
+uint deriveSeed(uvec2 seed, uint salt) {
+    return random(uvec2(random(seed), salt));
+}
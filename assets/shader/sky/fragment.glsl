@@ -0,0 +1,40 @@
+#version 440
+
+layout (location = 0) in vec3 iDirection;
+
+layout (location = 0) out vec4 oColor;
+
+layout (set = 0, binding = 0) uniform Sky {
+    mat4 uView;
+    mat4 uInverseProjection;
+    // The direction towards the sun, which may be below the horizon.
+    vec4 uSunDir;
+    vec4 uZenithColor;
+    vec4 uHorizonColor;
+    // The colors of the sun and moon discs, black while hidden.
+    vec4 uSunColor;
+    vec4 uMoonColor;
+};
+
+// The cosine of the angular radius of the sun and moon.
+const float discSize = 0.9995;
+
+void main() {
+    vec3 dir = normalize(iDirection);
+    float height = dir.y;
+
+    // Hazy at the horizon and clear overhead, darkening below the horizon.
+    vec3 color = mix(uHorizonColor.rgb, uZenithColor.rgb, smoothstep(0.0, 0.6, height));
+    color *= mix(0.6, 1.0, smoothstep(-0.4, 0.0, height));
+
+    // Sunlight scattered by the haze, strongest near the horizon.
+    float toSun = max(dot(dir, uSunDir.xyz), 0.0);
+    float haze = pow(1.0 - abs(height), 4.0);
+    color += uSunColor.rgb * (pow(toSun, 8.0) * 0.4 * haze + pow(toSun, 256.0) * 0.5);
+
+    float toMoon = max(dot(dir, -uSunDir.xyz), 0.0);
+    color += uSunColor.rgb * smoothstep(discSize, discSize + 0.0002, toSun);
+    color += uMoonColor.rgb * smoothstep(discSize, discSize + 0.0002, toMoon);
+
+    oColor = vec4(color, 1.0);
+}
@@ -0,0 +1,27 @@
+#version 440
+
+// Draws a triangle covering the screen and passes the direction
+// from the camera through each of its corners to the fragment shader.
+
+layout (location = 0) out vec3 oDirection;
+
+layout (set = 0, binding = 0) uniform Sky {
+    mat4 uView;
+    mat4 uInverseProjection;
+    vec4 uSunDir;
+    vec4 uZenithColor;
+    vec4 uHorizonColor;
+    vec4 uSunColor;
+    vec4 uMoonColor;
+};
+
+void main() {
+    vec2 pos = vec2(float((gl_VertexIndex << 1) & 2), float(gl_VertexIndex & 2)) * 2.0 - 1.0;
+    // The sky lies on the far plane.
+    gl_Position = vec4(pos, 1.0, 1.0);
+
+    vec4 viewDir = uInverseProjection * vec4(pos, 1.0, 1.0);
+    // Apart from its translation, the view matrix is a rotation,
+    // which its transpose undoes.
+    oDirection = transpose(mat3(uView)) * (viewDir.xyz / viewDir.w);
+}
@@ -0,0 +1,65 @@
+#version 440
+
+// Draws translucent blocks like water. Used with the chunk vertex shader.
+
+layout (location = 0) in vec3 iTexCoord;
+layout (location = 1) in vec3 iViewPos;
+layout (location = 2) in vec3 iWorldPos;
+layout (location = 3) in vec3 iNormal;
+
+layout (location = 0) out vec4 oColor;
+
+layout (set = 0, binding = 0) uniform texture2DArray uBlockTextures;
+layout (set = 0, binding = 1) uniform sampler uBlockSampler;
+
+// Changes with the time of day and the weather.
+layout (set = 0, binding = 2) uniform Lighting {
+    // The direction towards the sun or moon.
+    vec4 uLightDir;
+    vec4 uLightColor;
+    vec4 uAmbientColor;
+    vec4 uFogColor;
+    // Seconds of real time in X, wrapping around every 64 seconds.
+    vec4 uTime;
+};
+
+const float fogDensity = 0.005;
+
+// Fill light from the opposite side of the sky.
+const vec3 fillDir = vec3(-0.4, -0.7, -0.8);
+
+// The speeds in blocks per second at which two layers of the
+// texture flow. Each must flow a whole number of blocks in 64
+// seconds so the animation doesn't jump when the time wraps.
+const vec2 flow1 = vec2(1.0 / 16.0, 1.0 / 32.0);
+const vec2 flow2 = vec2(-1.0 / 32.0, 1.0 / 16.0);
+
+const float opacity = 0.7;
+
+void main() {
+    // Shading
+    vec3 normal = normalize(iNormal);
+    float diff = max(dot(normal, uLightDir.xyz), 0.0) + max(dot(normal, fillDir), 0.0) * 0.4;
+    vec4 shaded = vec4(uAmbientColor.rgb + diff * uLightColor.rgb, 1.0);
+
+    // Fog
+    float fogDepth = length(iViewPos);
+    #define LOG2 1.442695
+    float fogAmount = 1. - exp2(-fogDensity * fogDensity * fogDepth * fogDepth * LOG2);
+
+    // Two layers of the texture flowing across each other make ripples.
+    float time = uTime.x;
+    vec3 texCoord1 = vec3(iTexCoord.xy + flow1 * time, iTexCoord.z);
+    vec3 texCoord2 = vec3(iTexCoord.xy + flow2 * time, iTexCoord.z);
+    vec4 texel = mix(
+        texture(sampler2DArray(uBlockTextures, uBlockSampler), texCoord1),
+        texture(sampler2DArray(uBlockTextures, uBlockSampler), texCoord2),
+        0.5
+    );
+
+    vec4 col = shaded * texel;
+
+    col = mix(col, uFogColor, fogAmount);
+
+    oColor = vec4(col.rgb, opacity);
+}
@@ -7,6 +7,7 @@
 
 #version 450
 #include <noise.glsl>
+#include <rng.glsl>
 #include <biomes.glsl>
 
 #define DIM 32
@@ -20,7 +21,8 @@ layout (set = 0, binding = 0, r8ui) uniform readonly uimage2D uInputGrid;
 layout (set = 0, binding = 1, r8ui) uniform writeonly uimage2D uOutputGrid;
 
 layout (push_constant) uniform PushConstants {
-    uint uSeed;
+    // The world seed as (low bits, high bits).
+    uvec2 uSeed;
     ivec2 uOffset;
 };
 
@@ -34,7 +36,10 @@ void main() {
         result = value;
     } else {
         // Land: determine biome based on noise.
-        vec2 noiseInput = (uOffset + inCoords + uSeed) * 0.05;
+        // Offset noise by the seed, keeping coordinates
+        // small enough to be represented exactly.
+        float noiseOffset = float(deriveSeed(uSeed, SALT_LAND) % 4096);
+        vec2 noiseInput = (vec2(uOffset + inCoords) + noiseOffset) * 0.05;
         float noiseValue = simplexNoise2D(noiseInput);
 
         if (noiseValue < -0.5) {
@@ -17,7 +17,8 @@ layout (set = 0, binding = 0, r8ui) uniform readonly uimage2D uInputGrid;
 layout (set = 0, binding = 1, r8ui) uniform writeonly uimage2D uOutputGrid;
 
 layout (push_constant) uniform PushConstants {
-    uint uSeed;
+    // The world seed as (low bits, high bits).
+    uvec2 uSeed;
     ivec2 uOffset;
 };
 
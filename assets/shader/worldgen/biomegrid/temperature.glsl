@@ -0,0 +1,44 @@
+// Cools parts of the land. The coldest become tundra,
+// and hills in cool areas are raised into mountains.
+//
+// Output size: n (unchanged)
+
+#version 450
+#include <noise.glsl>
+#include <rng.glsl>
+#include <biomes.glsl>
+
+#define DIM 32
+
+layout (
+    local_size_x = DIM,
+    local_size_y = DIM
+) in;
+
+layout (set = 0, binding = 0, r8ui) uniform readonly uimage2D uInputGrid;
+layout (set = 0, binding = 1, r8ui) uniform writeonly uimage2D uOutputGrid;
+
+layout (push_constant) uniform PushConstants {
+    // The world seed as (low bits, high bits).
+    uvec2 uSeed;
+    ivec2 uOffset;
+};
+
+void main() {
+    ivec2 inCoords = ivec2(gl_LocalInvocationID.xy + gl_WorkGroupID.xy * DIM);
+    uint biome = imageLoad(uInputGrid, inCoords).x;
+
+    if (biome != BIOME_OCEAN) {
+        float noiseOffset = float(deriveSeed(uSeed, SALT_TEMPERATURE) % 4096);
+        vec2 noiseInput = (vec2(uOffset + inCoords) + noiseOffset) * 0.03;
+        float temperature = simplexNoise2D(noiseInput);
+
+        if (temperature < -0.45) {
+            biome = BIOME_TUNDRA;
+        } else if (temperature < -0.1 && biome == BIOME_HILLS) {
+            biome = BIOME_MOUNTAINS;
+        }
+    }
+
+    imageStore(uOutputGrid, inCoords, uvec4(biome, 0, 0, 0));
+}
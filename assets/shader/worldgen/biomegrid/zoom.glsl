@@ -18,7 +18,8 @@ layout (set = 0, binding = 0, r8ui) uniform readonly uimage2D uInputGrid;
 layout (set = 0, binding = 1, r8ui) uniform writeonly uimage2D uOutputGrid;
 
 layout (push_constant) uniform PushConstants {
-    uint uSeed;
+    // The world seed as (low bits, high bits).
+    uvec2 uSeed;
     ivec2 uOffset;
 };
 
@@ -36,7 +37,8 @@ void main() {
     uint bottomRight = imageLoad(uInputGrid, inBottomRight).x;
 
     ivec2 globalPos = outCoords + uOffset;
-    uint rand = random(uvec2(globalPos + uSeed)) % 4;
+    uint seed = deriveSeed(uSeed, SALT_ZOOM);
+    uint rand = random(uvec2(globalPos) + seed) % 4;
 
     uint[4] values = { topLeft, topRight, bottomLeft, bottomRight };
     uint value = values[rand];
@@ -4,6 +4,8 @@
 #include <biomes.glsl>
 
 #define REGION_DIM 256
+// Needs to match SEA_LEVEL in region.rs.
+#define SEA_LEVEL 64
 
 #extension GL_EXT_shader_8bit_storage : enable
 
@@ -18,6 +20,28 @@ layout (set = 0, binding = 0) writeonly restrict buffer Output {
 
 layout (set = 0, binding = 1, r8ui) uniform readonly restrict uimage2D uBiomeGrid;
 
+// The blocks making up the terrain of a biome. See composition.rs.
+struct Composition {
+    uint surface;
+    uint filler;
+    // The number of filler blocks below the surface.
+    uint depth;
+    uint underwater;
+};
+
+layout (set = 0, binding = 2) readonly restrict buffer CompositionTable {
+    // Indexed by biome ID.
+    Composition uComposition[];
+};
+
+layout (push_constant) uniform PushConstants {
+    // Position in blocks of the generated area
+    // within the world and the biome grid.
+    ivec2 uOffset;
+    // Width and depth of the output in blocks.
+    uint uOutputDim;
+};
+
 const float[NUM_BIOMES] cBiomeFrequencies = {
     0.0, // ocean
     0.005, // plains
@@ -25,8 +49,12 @@ const float[NUM_BIOMES] cBiomeFrequencies = {
     0.01, // desert
     0.011, // forest
     0.0, // river
+    0.008, // mountains
+    0.006, // swamp
+    0.007, // tundra
 };
 
+// Lower amplitudes make taller terrain.
 const float[NUM_BIOMES] cBiomeAmplitudes = {
     1.0, // ocean
     0.07, // plains
@@ -34,55 +62,75 @@ const float[NUM_BIOMES] cBiomeAmplitudes = {
     0.2, // desert
     0.15, // forest
     1.0, // river
+    0.01, // mountains
+    0.25, // swamp
+    0.06, // tundra
 };
 
+// Oceans and rivers lie below sea level, so
+// the sea fill pass floods them.
 const float[NUM_BIOMES] cBiomeMidpoints = {
-    64.0, // ocean
+    50.0, // ocean
     64.0, // plains
     75.0, // hills
     65.0, // desert
     66.0, // forest
-    64.0, // river
-};
-
-const uint[NUM_BIOMES] cBiomeBlocks = {
-    BLOCK_WATER, // ocean
-    BLOCK_GRASS, // plains
-    BLOCK_MELIUM, // hills
-    BLOCK_SAND, // desert
-    BLOCK_STONE, // forest
-    BLOCK_WATER, // river
+    58.0, // river
+    90.0, // mountains
+    63.0, // swamp
+    68.0, // tundra
 };
 
 shared uint biome;
 shared float frequency;
 shared float amplitude;
 shared float midpoint;
-shared uint biomeBlock;
-shared uint waterReplacementBlock;
 
 shared float[225] amplitudeSamples;
 shared float[225] midpointSamples;
 shared float[225] weights;
-shared uint[255] biomeBlocks;
+
+// Whether each block of the column is solid.
+shared bool[REGION_DIM] solid;
+
+// Returns the block `depth` blocks below the top of terrain whose top
+// block is at `surfaceY`. Mirrors Composition::block in composition.rs.
+uint composeBlock(Composition composition, uint depth, uint surfaceY) {
+    if (depth > composition.depth) {
+        return BLOCK_STONE;
+    } else if (surfaceY < SEA_LEVEL) {
+        return composition.underwater;
+    } else if (depth == 0) {
+        return composition.surface;
+    } else {
+        return composition.filler;
+    }
+}
+
+// Samples the biome grid. Columns outside the grid are ocean.
+uint sampleBiome(ivec2 pos) {
+    if (any(lessThan(pos, ivec2(0))) || any(greaterThanEqual(pos, imageSize(uBiomeGrid)))) {
+        return BIOME_OCEAN;
+    }
+    return imageLoad(uBiomeGrid, pos).x;
+}
 
 void main() {
-    uvec3 pos = gl_GlobalInvocationID;
+    uvec3 localPos = gl_GlobalInvocationID;
+    ivec3 pos = ivec3(localPos) + ivec3(uOffset.x, 0, uOffset.y);
 
     uint id = gl_LocalInvocationID.y;
     if (id < 225) {
         ivec2 offset = ivec2(id / 15, id % 15) - ivec2(7, 7);
         float weight = 10 / (length(vec2(offset)) + 1);
-        uint biomeSample = imageLoad(uBiomeGrid, ivec2(pos.xz) + offset).x;
+        uint biomeSample = sampleBiome(pos.xz + offset);
 
         amplitudeSamples[id] = cBiomeAmplitudes[biomeSample] * weight;
         midpointSamples[id] = cBiomeMidpoints[biomeSample] * weight;
         weights[id] = weight;
-        biomeBlocks[id] = cBiomeBlocks[biomeSample];
 
         if (offset == ivec2(0, 0)) {
             biome = biomeSample;
-            biomeBlock = cBiomeBlocks[biomeSample];
             frequency = cBiomeFrequencies[biomeSample];
         }
     }
@@ -91,44 +139,43 @@ void main() {
         float amp = 0;
         float mid = 0;
         float weightSum = 0;
-        waterReplacementBlock = biomeBlock;
         for (int i = 0; i < 225; i++) {
             amp += amplitudeSamples[i];
             mid += midpointSamples[i];
             weightSum += weights[i];
-
-            if (biomeBlock == BLOCK_WATER && biomeBlocks[i] != BLOCK_WATER) {
-                waterReplacementBlock = biomeBlocks[i];
-            }
         }
         amplitude = amp / weightSum;
         midpoint = mid / weightSum;
     }
     barrier();
 
-    float noiseValue1 = fbm3D(pos * frequency, 2, 2.0, 0.5);
-    float noiseValue2 = fbm3D(pos * frequency + vec3(0, 1000, 0), 2, 2.0, 0.5);
-    float choiceNoise = fbm3D(pos * 0.005, 2, 2.0, 0.5);
+    vec3 worldPos = vec3(pos);
+    float noiseValue1 = fbm3D(worldPos * frequency, 2, 2.0, 0.5);
+    float noiseValue2 = fbm3D(worldPos * frequency + vec3(0, 1000, 0), 2, 2.0, 0.5);
+    float choiceNoise = fbm3D(worldPos * 0.005, 2, 2.0, 0.5);
     float noiseValue = mix(noiseValue1, noiseValue2, choiceNoise);
 
-    float gradient = (pos.y - midpoint + 1) * amplitude;
+    float gradient = (worldPos.y - midpoint + 1) * amplitude;
 
     if (gradient < 0.0) {
         gradient *= 4.0;
     }
 
     float density = -abs(noiseValue) + gradient;
+    solid[id] = density < 0.0;
+    barrier();
 
-    uint block;
-    if (density < 0.0) {
-        if (pos.y >= 64) {
-            block = waterReplacementBlock;
-        } else {
-            block = biomeBlock;
+    // Layer the biome's blocks from the top of each stretch of solid
+    // blocks down. Only depths up to one past the filler matter.
+    uint block = BLOCK_AIR;
+    if (solid[id]) {
+        Composition composition = uComposition[biome];
+        uint depth = 0;
+        while (depth <= composition.depth && id + depth + 1 < REGION_DIM && solid[id + depth + 1]) {
+            depth++;
         }
-    } else {
-        block = BLOCK_AIR;
+        block = composeBlock(composition, depth, id + depth);
     }
 
-    uBlocks[pos.x * REGION_DIM * REGION_DIM + pos.z * REGION_DIM + pos.y] = uint8_t(block);
+    uBlocks[(localPos.x * uOutputDim + localPos.z) * REGION_DIM + localPos.y] = uint8_t(block);
 }
@@ -6,5 +6,26 @@ for shader in ${shaders[@]}; do
   rm -r assets/shader_compiled/${shader} || true
   mkdir -p assets/shader_compiled/${shader}
   glslc -fshader-stage=vertex assets/shader/${shader}/vertex.glsl -o assets/shader_compiled/${shader}/vertex.spv
+  glslc -fshader-stage=vertex -DUNIFORM_PARAMS assets/shader/${shader}/vertex.glsl -o assets/shader_compiled/${shader}/vertex_uniform.spv
   glslc -fshader-stage=fragment assets/shader/${shader}/fragment.glsl -o assets/shader_compiled/${shader}/fragment.spv
 done
+
+# Chunks drawn indirectly read their transforms from a storage buffer.
+glslc -fshader-stage=vertex -DINDIRECT_PARAMS assets/shader/chunk/vertex.glsl -o assets/shader_compiled/chunk/vertex_indirect.spv
+
+# The sky takes no draw parameters.
+rm -r assets/shader_compiled/sky || true
+mkdir -p assets/shader_compiled/sky
+glslc -fshader-stage=vertex assets/shader/sky/vertex.glsl -o assets/shader_compiled/sky/vertex.spv
+glslc -fshader-stage=fragment assets/shader/sky/fragment.glsl -o assets/shader_compiled/sky/fragment.spv
+
+# Translucent blocks use the chunk vertex shaders.
+rm -r assets/shader_compiled/water || true
+mkdir -p assets/shader_compiled/water
+glslc -fshader-stage=fragment assets/shader/water/fragment.glsl -o assets/shader_compiled/water/fragment.spv
+
+worldgen_shaders=("biomegrid/land" "biomegrid/temperature" "biomegrid/humidity" "biomegrid/rivers" "biomegrid/smooth" "biomegrid/zoom" "region/region")
+
+for shader in ${worldgen_shaders[@]}; do
+  glslc -fshader-stage=compute -I assets/shader/include assets/shader/worldgen/${shader}.glsl -o assets/shader/worldgen/${shader}.spv
+done
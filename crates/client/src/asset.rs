@@ -1,19 +1,21 @@
 use std::{
-    any::type_name,
-    any::type_name_of_val,
-    any::Any,
-    collections::HashMap,
-    fs,
-    marker::PhantomData,
-    ops::Deref,
+    any::type_name, any::type_name_of_val, any::Any, collections::HashMap, marker::PhantomData,
+    ops::Deref, sync::Arc,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs::{self, File},
     path::{Path, PathBuf},
-    sync::Arc,
 };
 
 use ahash::AHashMap;
 use anyhow::{anyhow, Context};
+#[cfg(not(target_arch = "wasm32"))]
+use memmap2::Mmap;
+#[cfg(not(target_arch = "wasm32"))]
 use path_slash::PathExt;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
 use walkdir::WalkDir;
 
 pub mod font;
@@ -26,8 +28,23 @@ impl<T> AssetKind for T where T: Any + Send + Sync {}
 
 pub trait AssetLoader: Send + Sync + 'static {
     fn load(&self, data: &[u8]) -> anyhow::Result<Box<dyn Any + Send + Sync>>;
+
+    /// Returns whether large files should be memory-mapped rather
+    /// than read into memory before they are passed to [`load`](AssetLoader::load).
+    ///
+    /// Loaders that copy or decode what they need out of `data`
+    /// should opt in: the file's pages are then read on demand and
+    /// never held in memory alongside the loaded asset.
+    fn prefers_mmap(&self) -> bool {
+        false
+    }
 }
 
+/// The minimum size of files memory-mapped for loaders that
+/// [prefer it](AssetLoader::prefers_mmap). Reading smaller
+/// files is faster than setting up a mapping.
+const MMAP_THRESHOLD: u64 = 256 * 1024;
+
 type DynAsset = Arc<dyn Any + Send + Sync>;
 
 /// A reference-counted handle to an asset of type `T`.
@@ -56,6 +73,12 @@ impl<T> Asset<T> {
     }
 }
 
+/// The file listing every asset file, one path relative to the asset
+/// root per line. Browsers can't list directories, so web builds
+/// fetch the assets named here. `web/build.sh` generates it.
+#[cfg(target_arch = "wasm32")]
+const MANIFEST_FILE: &str = "manifest.txt";
+
 /// The asset index file `index.yml`. Specifies which loader
 /// to use on a per-directory basis.
 #[derive(Debug, Serialize, Deserialize)]
@@ -82,10 +105,18 @@ pub enum AssetGetError {
     },
 }
 
+/// The loaded assets, keyed by their path relative to the asset root.
+///
+/// Assets come from an ordered list of roots: the base asset directory
+/// followed by any resource packs. A pack's assets override those loaded
+/// before at the same path, so packs can replace block textures and
+/// models without touching the base assets.
 #[derive(Default)]
 pub struct Assets {
     assets: AHashMap<String, DynAsset>,
     loaders: AHashMap<String, Box<dyn AssetLoader>>,
+    /// The groups of the [`AssetIndex`]es of all loaded roots.
+    groups: HashMap<String, Group>,
 }
 
 impl Assets {
@@ -105,13 +136,48 @@ impl Assets {
     /// The directory must contain `index.yml` which satisfies
     /// the [`AssetIndex`] format. This file specifies which loader
     /// to use for each file.
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn load_dir(&mut self, directory: impl AsRef<Path>) -> anyhow::Result<()> {
         let directory = directory.as_ref();
         let index = Self::load_index(directory)?;
-        self.load_assets(directory, &index)?;
+        self.groups.extend(index.groups);
+        self.load_assets(directory, false)
+    }
+
+    /// Loads a resource pack: a directory laid out like the asset
+    /// directory. Its assets override those loaded before at the same path.
+    ///
+    /// The pack may contain an `index.yml` to add asset directories.
+    /// Otherwise, it uses the directories indexed by the roots loaded
+    /// before, and may leave out any of them.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_pack(&mut self, directory: impl AsRef<Path>) -> anyhow::Result<()> {
+        let directory = directory.as_ref();
+        if directory.join("index.yml").exists() {
+            let index = Self::load_index(directory)?;
+            self.groups.extend(index.groups);
+        }
+        self.load_assets(directory, true)
+    }
+
+    /// Loads the asset directory `roots[0]` followed by the resource packs
+    /// in the rest of `roots`. Later packs take precedence over earlier ones.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_dirs(&mut self, roots: &[impl AsRef<Path>]) -> anyhow::Result<()> {
+        let (base, packs) = roots
+            .split_first()
+            .ok_or_else(|| anyhow!("no asset directories given"))?;
+        self.load_dir(base)?;
+        for pack in packs {
+            log::info!("Loading resource pack {}", pack.as_ref().display());
+            self.load_pack(pack).with_context(|| {
+                format!("failed to load resource pack '{}'", pack.as_ref().display())
+            })?;
+        }
         Ok(())
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
     fn load_index(directory: &Path) -> anyhow::Result<AssetIndex> {
         let path = directory.join("index.yml");
         let bytes = fs::read(&path)?;
@@ -119,9 +185,20 @@ impl Assets {
         Ok(index)
     }
 
-    fn load_assets(&mut self, directory: &Path, index: &AssetIndex) -> anyhow::Result<()> {
-        for (subdir, group) in &index.groups {
-            self.load_group(directory, Path::new(subdir), &group.loader)
+    /// Loads the indexed groups in `directory`. If `skip_missing`
+    /// is set, groups without a subdirectory are skipped.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_assets(&mut self, directory: &Path, skip_missing: bool) -> anyhow::Result<()> {
+        let groups: Vec<(String, String)> = self
+            .groups
+            .iter()
+            .map(|(subdir, group)| (subdir.clone(), group.loader.clone()))
+            .collect();
+        for (subdir, loader) in groups {
+            if skip_missing && !directory.join(&subdir).is_dir() {
+                continue;
+            }
+            self.load_group(directory, Path::new(&subdir), &loader)
                 .with_context(|| {
                     format!("failed to load asset directory '{}'", directory.display())
                 })?;
@@ -137,10 +214,14 @@ impl Assets {
     }
 
     fn insert_asset(&mut self, path: &str, asset: DynAsset) {
-        self.assets.insert(path.to_owned(), asset);
-        log::info!("Loaded {}", path);
+        if self.assets.insert(path.to_owned(), asset).is_some() {
+            log::info!("Overrode {}", path);
+        } else {
+            log::info!("Loaded {}", path);
+        }
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
     fn load_group(&mut self, directory: &Path, subdir: &Path, loader: &str) -> anyhow::Result<()> {
         let loader = self.find_loader(loader)?;
 
@@ -154,9 +235,7 @@ impl Assets {
             }
 
             let path = entry.path();
-            let bytes = fs::read(path)?;
-            let asset = loader
-                .load(&bytes)
+            let asset = load_file(loader, path, entry.metadata()?.len())
                 .with_context(|| format!("failed to load '{}'", path.display()))?;
             let path = path
                 .strip_prefix(directory)?
@@ -172,6 +251,42 @@ impl Assets {
         Ok(())
     }
 
+    /// Fetches and loads all assets served under `base_url`.
+    ///
+    /// Like [`Assets::load_dir`], but for web builds, which have no
+    /// file system. Besides `index.yml`, the server must provide
+    /// [`MANIFEST_FILE`] listing the asset files.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn load_url(&mut self, base_url: &str) -> anyhow::Result<()> {
+        let index: AssetIndex = serde_yaml::from_slice(
+            &crate::platform::fetch(&format!("{}/index.yml", base_url)).await?,
+        )?;
+        let manifest = crate::platform::fetch(&format!("{}/{}", base_url, MANIFEST_FILE)).await?;
+        let manifest = String::from_utf8(manifest).context("asset manifest is not UTF-8")?;
+
+        for path in manifest
+            .lines()
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+        {
+            let group = index
+                .groups
+                .iter()
+                .find(|(subdir, _)| path.starts_with(subdir.as_str()));
+            let loader = match group {
+                Some((_, group)) => self.find_loader(&group.loader)?,
+                None => continue,
+            };
+            let data = crate::platform::fetch(&format!("{}/{}", base_url, path)).await?;
+            let asset = loader
+                .load(&data)
+                .with_context(|| format!("failed to load '{}'", path))?;
+            self.insert_asset(path, asset.into());
+        }
+        self.groups.extend(index.groups);
+        Ok(())
+    }
+
     /// Gets the asset with the given path (relative to the asset directory)
     /// as a handle of type `T`. Returns an error if the asset does not exist
     /// or if its type is not `T`.
@@ -195,6 +310,8 @@ impl Assets {
     }
 
     /// Iterates over all assets matching the given prefix and type `T`.
+    /// An asset overridden by a resource pack is only yielded once,
+    /// as the pack's version.
     pub fn iter_prefixed<'a, T: AssetKind>(
         &'a self,
         prefix: &'a str,
@@ -209,6 +326,24 @@ impl Assets {
     }
 }
 
+/// Loads the file at `path`, which is `len` bytes long, using `loader`.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_file(
+    loader: &dyn AssetLoader,
+    path: &Path,
+    len: u64,
+) -> anyhow::Result<Box<dyn Any + Send + Sync>> {
+    if loader.prefers_mmap() && len >= MMAP_THRESHOLD {
+        let file = File::open(path)?;
+        // Safety: the mapping is dropped before this function returns,
+        // and asset files are not modified while the game loads them.
+        let map = unsafe { Mmap::map(&file)? };
+        loader.load(&map)
+    } else {
+        loader.load(&fs::read(path)?)
+    }
+}
+
 /// Asset loader for YAML files with format `T`.
 pub struct YamlLoader<T> {
     _marker: PhantomData<T>,
@@ -234,3 +369,51 @@ impl<T: DeserializeOwned + Any + Send + Sync> AssetLoader for YamlLoader<T> {
         Ok(Box::new(asset))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: PathBuf, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn packs_override_earlier_roots() {
+        let dir = std::env::temp_dir().join(format!("voltz-asset-test-{}", std::process::id()));
+        let (base, pack) = (dir.join("base"), dir.join("pack"));
+        write(
+            base.join("index.yml"),
+            "groups:\n  text/:\n    loader: Text\n",
+        );
+        write(base.join("text/a.yml"), "base a");
+        write(base.join("text/b.yml"), "base b");
+        write(pack.join("text/b.yml"), "pack b");
+        write(pack.join("text/c.yml"), "pack c");
+
+        let mut assets = Assets::new();
+        assets.add_loader("Text", YamlLoader::<String>::new());
+        assets.load_dirs(&[&base, &pack]).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        let text = |path: &str| assets.get::<String>(path).unwrap().to_string();
+        assert_eq!(text("text/a.yml"), "base a");
+        assert_eq!(text("text/b.yml"), "pack b");
+        assert_eq!(text("text/c.yml"), "pack c");
+
+        let mut prefixed: Vec<_> = assets
+            .iter_prefixed::<String>("text/")
+            .map(|(path, text)| (path.to_owned(), text.to_string()))
+            .collect();
+        prefixed.sort();
+        assert_eq!(
+            prefixed,
+            vec![
+                ("text/a.yml".to_owned(), "base a".to_owned()),
+                ("text/b.yml".to_owned(), "pack b".to_owned()),
+                ("text/c.yml".to_owned(), "pack c".to_owned()),
+            ]
+        );
+    }
+}
@@ -16,4 +16,8 @@ impl AssetLoader for FontLoader {
         let font = Font::from_bytes(data, FontSettings::default()).map_err(|e| anyhow!("{}", e))?;
         Ok(Box::new(font))
     }
+
+    fn prefers_mmap(&self) -> bool {
+        true
+    }
 }
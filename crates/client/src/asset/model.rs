@@ -25,6 +25,18 @@ pub struct YamlModel {
     pub prisms: Vec<Prism>,
 }
 
+impl YamlModel {
+    /// Returns the texture that best represents the model in item
+    /// icons, relative to `texture/block/`: the texture on its sides,
+    /// or on all faces, or on top. Inherited textures aren't considered.
+    pub fn icon_texture(&self) -> Option<&str> {
+        ["sides", "all", "top"]
+            .iter()
+            .find_map(|param| self.textures.get(*param))
+            .map(String::as_str)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextureParam {
     /// A default texture parameter to defer to if
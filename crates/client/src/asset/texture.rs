@@ -1,4 +1,6 @@
-use image::ImageFormat;
+use std::{any::Any, io::Read};
+
+use image::{codecs::png::PngDecoder, ColorType, ImageDecoder, ImageFormat};
 
 use super::AssetLoader;
 
@@ -33,14 +35,92 @@ impl PngLoader {
 }
 
 impl AssetLoader for PngLoader {
-    fn load(&self, data: &[u8]) -> anyhow::Result<Box<dyn std::any::Any + Send + Sync>> {
-        let image = image::load_from_memory_with_format(data, ImageFormat::Png)?.to_bgra8();
-
-        let texture = TextureAsset {
-            width: image.width(),
-            height: image.height(),
-            data: image.into_raw(),
-        };
-        Ok(Box::new(texture))
+    fn load(&self, data: &[u8]) -> anyhow::Result<Box<dyn Any + Send + Sync>> {
+        Ok(Box::new(decode_png(data)?))
+    }
+
+    fn prefers_mmap(&self) -> bool {
+        true
+    }
+}
+
+/// Decodes a PNG image into BGRA8.
+///
+/// 8-bit images are decoded a row at a time straight into the
+/// output, so no intermediate copy of the whole image is made.
+fn decode_png(data: &[u8]) -> anyhow::Result<TextureAsset> {
+    let decoder = PngDecoder::new(data)?;
+    let (width, height) = decoder.dimensions();
+    let channels = match decoder.color_type() {
+        ColorType::L8 => 1,
+        ColorType::La8 => 2,
+        ColorType::Rgb8 => 3,
+        ColorType::Rgba8 => 4,
+        // Other formats are rare enough that converting
+        // the decoded image as a whole is fine.
+        _ => {
+            let image = image::load_from_memory_with_format(data, ImageFormat::Png)?.to_bgra8();
+            return Ok(TextureAsset {
+                width: image.width(),
+                height: image.height(),
+                data: image.into_raw(),
+            });
+        }
+    };
+
+    let mut reader = decoder.into_reader()?;
+    let mut row = vec![0; width as usize * channels];
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    for _ in 0..height {
+        reader.read_exact(&mut row)?;
+        for pixel in row.chunks_exact(channels) {
+            let bgra = match *pixel {
+                [l] => [l, l, l, 255],
+                [l, a] => [l, l, l, a],
+                [r, g, b] => [b, g, r, 255],
+                [r, g, b, a] => [b, g, r, a],
+                _ => unreachable!("pixels have 1 to 4 channels"),
+            };
+            pixels.extend_from_slice(&bgra);
+        }
+    }
+
+    Ok(TextureAsset {
+        width,
+        height,
+        data: pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use image::codecs::png::PngEncoder;
+
+    use super::*;
+
+    #[test]
+    fn streaming_decode_matches_full_decode() {
+        let (width, height) = (5, 3);
+        for &(color_type, channels) in &[
+            (ColorType::L8, 1),
+            (ColorType::La8, 2),
+            (ColorType::Rgb8, 3),
+            (ColorType::Rgba8, 4),
+        ] {
+            let pixels: Vec<u8> = (0..width * height * channels)
+                .map(|i| (i * 37 % 256) as u8)
+                .collect();
+            let mut png = Vec::new();
+            PngEncoder::new(&mut png)
+                .encode(&pixels, width, height, color_type)
+                .unwrap();
+
+            let streamed = decode_png(&png).unwrap();
+            let full = image::load_from_memory_with_format(&png, ImageFormat::Png)
+                .unwrap()
+                .to_bgra8();
+            assert_eq!((streamed.width, streamed.height), full.dimensions());
+            assert_eq!(streamed.data, full.into_raw(), "{:?}", color_type);
+        }
     }
 }
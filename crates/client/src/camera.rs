@@ -1,13 +1,15 @@
 use crate::{event::MouseMoved, game::Game, PLAYER_BBOX};
 use bytemuck::{Pod, Zeroable};
-use common::{blocks, entity::Vel, BlockId, Orient, Pos, System, SystemExecutor};
+use common::{
+    entity::{player::eye_pos, Vel},
+    Orient, Pos, System, SystemExecutor,
+};
 use glam::{Mat4, Vec2, Vec3, Vec3A};
 use splines::{Interpolation, Key, Spline};
 use winit::event::VirtualKeyCode;
 
 const MOUSE_SENSITIVITY: f32 = 3.;
 const KEYBOARD_SENSITIVITY: f32 = 6.;
-const EYE_HEIGHT: f32 = 1.6;
 
 const JUMP_VEL_Y: f32 = 8.;
 
@@ -92,11 +94,12 @@ impl CameraSystem {
     fn tick_keyboard(&mut self, game: &mut Game) {
         self.tick_move(game);
         self.tick_jump(game);
+        self.tick_climb(game);
     }
 
     fn tick_move(&mut self, game: &mut Game) {
         let orient = game.player_ref().get::<Orient>().unwrap().0;
-        let forward = Vec3A::from(self.direction(orient));
+        let forward = Vec3A::from(direction(orient));
         let right = Vec3A::from(forward.cross(Vec3A::unit_y())).normalize();
 
         let mut vel = Vec3A::zero();
@@ -149,20 +152,28 @@ impl CameraSystem {
         };
         vel *= multiplier;
 
+        // Input moves the player in main-zone space, while collisions
+        // are resolved in the local space of the player's zone.
         let old_pos = game.player_ref().get::<Pos>().unwrap().0;
-        let new_pos = old_pos + vel;
-        let new_pos =
-            physics::collision::resolve_collisions(PLAYER_BBOX, old_pos, new_pos, |pos| {
-                game.main_zone().block(pos) != Some(BlockId::new(blocks::Air))
-            });
-        game.player_ref().get_mut::<Pos>().unwrap().0 = new_pos;
+        let world = game.world();
+        let new_pos = world
+            .to_zone(old_pos.zone, world.to_main(old_pos) + vel)
+            .unwrap_or_else(|| old_pos.offset(vel));
+        let zone = world.zone(old_pos.zone);
+        let new_local = physics::collision::resolve_collisions(
+            PLAYER_BBOX,
+            old_pos.local,
+            new_pos.local,
+            |pos| zone?.block(pos),
+        );
+        game.player_ref().get_mut::<Pos>().unwrap().0.local = new_local;
     }
 
     fn tick_jump(&mut self, game: &mut Game) {
+        let pos = game.player_ref().get::<Pos>().unwrap().0;
+        let zone = game.world().zone(pos.zone);
         if game.is_key_pressed(VirtualKeyCode::Space)
-            && physics::is_on_ground(game.player_ref().get::<Pos>().unwrap().0, |pos| {
-                game.main_zone().block(pos) != Some(BlockId::new(blocks::Air))
-            })
+            && physics::is_on_ground(pos, |pos| zone?.block(pos))
         {
             let vel = glam::vec3a(0., JUMP_VEL_Y, 0.);
             game.player_ref().get_mut::<Vel>().unwrap().0 = vel;
@@ -170,30 +181,44 @@ impl CameraSystem {
         }
     }
 
+    /// Climbs the ladder or other climbable block the player
+    /// is holding on to while they move forward.
+    fn tick_climb(&mut self, game: &mut Game) {
+        if !game.is_key_pressed(VirtualKeyCode::W) {
+            return;
+        }
+        let pos = game.player_ref().get::<Pos>().unwrap().0;
+        let zone = game.world().zone(pos.zone);
+        if physics::is_climbing(PLAYER_BBOX, pos.local, |pos| zone?.block(pos)) {
+            let mut vel = game.player_ref().get_mut::<Vel>().unwrap();
+            vel.0.y = vel.0.y.max(physics::CLIMB_SPEED);
+        }
+    }
+
     /// Returns the view-projection matrix that should be passed to shaders.
     fn matrices(&mut self, game: &mut Game, aspect_ratio: f32) -> Matrices {
         let pos = game.player_ref().get::<Pos>().unwrap().0;
         let orient = game.player_ref().get::<Orient>().unwrap().0;
 
-        let eye = pos + glam::vec3a(0., EYE_HEIGHT, 0.);
+        // Everything is rendered in main-zone space.
+        let eye = eye_pos(game.world().to_main(pos));
 
         // Determine center based on orient
-        let direction = self.direction(orient);
-        let center = Vec3::from(eye) + direction;
+        let center = Vec3::from(eye) + direction(orient);
 
         let view = Mat4::look_at_lh(eye.into(), center, Vec3::unit_y());
         let projection = Mat4::perspective_lh(70., aspect_ratio, 0.01, 1000.);
 
         Matrices { view, projection }
     }
+}
 
-    /// Determines the direction vector of the player.
-    fn direction(&self, orient: Vec2) -> Vec3 {
-        glam::vec3(
-            orient.x.to_radians().cos() * orient.y.to_radians().cos(),
-            orient.y.to_radians().sin(),
-            orient.x.to_radians().sin() * orient.y.to_radians().cos(),
-        )
-        .normalize()
-    }
+/// Determines the direction vector of the player.
+pub fn direction(orient: Vec2) -> Vec3 {
+    glam::vec3(
+        orient.x.to_radians().cos() * orient.y.to_radians().cos(),
+        orient.y.to_radians().sin(),
+        orient.x.to_radians().sin() * orient.y.to_radians().cos(),
+    )
+    .normalize()
 }
@@ -0,0 +1,197 @@
+//! The chat overlay.
+//!
+//! Pressing T opens the chat: a text field above a log of recent
+//! messages. Enter sends the typed message and Escape closes the chat.
+//! While the chat is open, Page Up and Page Down scroll through the log,
+//! and key presses don't move the player. While it is closed, new
+//! messages are shown for a few seconds.
+
+use std::{collections::VecDeque, ops::Range, time::Duration};
+
+use common::{System, SystemExecutor};
+use fontdue::Font;
+use glam::vec2;
+use protocol::packets::{
+    client::{ChatMessage, MAX_CHAT_MESSAGE_LENGTH},
+    ClientPacket,
+};
+use voltzui::{
+    widgets::{text_input, Panel, Text, TextInput},
+    Dimension,
+};
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    asset::{Asset, Assets},
+    event::{CharacterTyped, ChatReceived, KeyPressed},
+    game::Game,
+    platform::Instant,
+    ui::Length,
+};
+
+/// The key which opens the chat.
+const OPEN_KEY: VirtualKeyCode = VirtualKeyCode::T;
+/// The number of messages kept in the log.
+const HISTORY_LENGTH: usize = 100;
+/// The number of messages visible at once.
+const VISIBLE_MESSAGES: usize = 10;
+/// How long new messages are shown while the chat is closed.
+const MESSAGE_DURATION: Duration = Duration::from_secs(10);
+
+pub fn setup(systems: &mut SystemExecutor<Game>, assets: &Assets) -> anyhow::Result<()> {
+    let font = assets.get("font/Play-Regular.ttf")?;
+    systems.add(ChatSystem {
+        log: VecDeque::new(),
+        input: String::new(),
+        scroll: 0,
+        font,
+    });
+    Ok(())
+}
+
+struct ChatSystem {
+    /// Received messages and when they were received, oldest first.
+    log: VecDeque<(String, Instant)>,
+    /// The message being typed.
+    input: String,
+    /// The number of messages scrolled up from the newest one.
+    scroll: usize,
+    font: Asset<Font>,
+}
+
+impl ChatSystem {
+    fn receive_messages(&mut self, game: &Game) {
+        for chat in game.events().iter::<ChatReceived>() {
+            log::info!("<{}> {}", chat.sender, chat.message);
+            self.log.push_back((
+                format!("<{}> {}", chat.sender, chat.message),
+                Instant::now(),
+            ));
+            if self.log.len() > HISTORY_LENGTH {
+                self.log.pop_front();
+            }
+            // Keep the visible messages in place while scrolled up.
+            if self.scroll > 0 {
+                self.scroll = (self.scroll + 1).min(self.max_scroll());
+            }
+        }
+    }
+
+    fn handle_input(&mut self, game: &mut Game) {
+        let was_open = game.chat_open;
+        let keys: Vec<_> = game
+            .events()
+            .iter::<KeyPressed>()
+            .map(|pressed| pressed.key)
+            .collect();
+
+        // The character typed with the key that
+        // opened the chat isn't part of the message.
+        if was_open {
+            for typed in game.events().iter::<CharacterTyped>() {
+                text_input::type_char(&mut self.input, typed.c, MAX_CHAT_MESSAGE_LENGTH);
+            }
+        }
+
+        for key in keys {
+            match key {
+                OPEN_KEY if !game.chat_open => game.chat_open = true,
+                VirtualKeyCode::Return if game.chat_open => {
+                    self.send(game);
+                    self.close(game);
+                }
+                VirtualKeyCode::Escape if game.chat_open => self.close(game),
+                VirtualKeyCode::PageUp if game.chat_open => {
+                    self.scroll = (self.scroll + VISIBLE_MESSAGES / 2).min(self.max_scroll());
+                }
+                VirtualKeyCode::PageDown if game.chat_open => {
+                    self.scroll = self.scroll.saturating_sub(VISIBLE_MESSAGES / 2);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn send(&self, game: &Game) {
+        let message = self.input.trim();
+        if !message.is_empty() {
+            game.bridge().send(ClientPacket::ChatMessage(ChatMessage {
+                message: message.to_owned(),
+            }));
+        }
+    }
+
+    fn close(&mut self, game: &mut Game) {
+        game.chat_open = false;
+        self.input.clear();
+        self.scroll = 0;
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.log.len().saturating_sub(VISIBLE_MESSAGES)
+    }
+
+    fn build_ui(&self, game: &Game) {
+        let lines: Vec<&str> = self
+            .log
+            .range(visible_range(self.log.len(), self.scroll))
+            .filter(|(_, received)| game.chat_open || received.elapsed() < MESSAGE_DURATION)
+            .map(|(message, _)| message.as_str())
+            .collect();
+        if !game.chat_open && lines.is_empty() {
+            return;
+        }
+        let text = lines.join("\n");
+
+        // The chat is shown on the left, above server messages.
+        let window = game.window();
+        let window_size = window.inner_size().to_logical::<f32>(window.scale_factor());
+        let mut ui_store = game.ui_store();
+        let ui = ui_store.get(
+            "chat",
+            Length::Percent(50.),
+            Length::Percent(35.),
+            vec2(10., window_size.height * 0.35),
+        );
+        let font = self.font.as_arc();
+
+        let mut builder = ui.build();
+        builder.begin(Panel::new().with_style(|style| {
+            style.size.width = Dimension::Percent(1.);
+        }));
+        builder.push(Text::new(&text, font));
+        if game.chat_open {
+            builder.push(TextInput::new(&self.input, font).placeholder("Press Enter to send"));
+        }
+        builder.end();
+    }
+}
+
+impl System<Game> for ChatSystem {
+    fn run(&mut self, game: &mut Game) {
+        self.receive_messages(game);
+        self.handle_input(game);
+        self.build_ui(game);
+    }
+}
+
+/// Returns the range of messages visible in a log of `len`
+/// messages scrolled up by `scroll` messages.
+fn visible_range(len: usize, scroll: usize) -> Range<usize> {
+    let end = len - scroll.min(len);
+    end.saturating_sub(VISIBLE_MESSAGES)..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolling() {
+        assert_eq!(visible_range(0, 0), 0..0);
+        assert_eq!(visible_range(3, 0), 0..3);
+        assert_eq!(visible_range(3, 5), 0..0);
+        assert_eq!(visible_range(25, 0), 15..25);
+        assert_eq!(visible_range(25, 5), 10..20);
+    }
+}
@@ -0,0 +1,162 @@
+//! The on-disk cache of chunks received from servers.
+//!
+//! Servers supporting it send only the hash of each chunk; see
+//! [`protocol::chunk_cache`]. Chunks are cached in
+//! `chunks/<world id>/<x>_<y>_<z>.bin` below `VOLTZ_CACHE_DIR`, or
+//! `cache` if it is unset, with the server's block IDs. A cached chunk
+//! is loaded only if its hash matches, so stale or corrupted entries
+//! just cost a request.
+//!
+//! Entries are written on a background thread. Clients that can't cache
+//! chunks, including web builds, which have no file system, don't
+//! announce support for the feature.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+use common::{Chunk, ChunkPos};
+#[cfg(not(target_arch = "wasm32"))]
+use protocol::chunk_cache;
+
+/// The chunks cached for one world.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct ChunkCache {
+    dir: PathBuf,
+    /// Chunks to write on the background thread, which
+    /// stops once this is dropped.
+    writes: Sender<(PathBuf, Chunk)>,
+}
+
+/// Web builds can't cache chunks.
+#[cfg(target_arch = "wasm32")]
+pub enum ChunkCache {}
+
+#[cfg(target_arch = "wasm32")]
+impl ChunkCache {
+    pub fn is_available() -> bool {
+        false
+    }
+
+    pub fn load(&self, _pos: ChunkPos, _hash: u64) -> Option<Chunk> {
+        match *self {}
+    }
+
+    pub fn store(&self, _pos: ChunkPos, _chunk: Chunk) {
+        match *self {}
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ChunkCache {
+    /// Returns whether chunks can be cached, creating the
+    /// directory of the caches of all worlds if needed.
+    pub fn is_available() -> bool {
+        create_dir(&root_dir())
+    }
+
+    /// Opens the cache of the world with ID `world_id`, creating it
+    /// if needed. Returns `None` if its directory can't be created.
+    pub fn open(world_id: u64) -> Option<Self> {
+        let dir = root_dir().join(format!("{:016x}", world_id));
+        if !create_dir(&dir) {
+            return None;
+        }
+
+        let (writes, pending) = mpsc::channel::<(PathBuf, Chunk)>();
+        thread::Builder::new()
+            .name("chunk-cache".to_owned())
+            .spawn(move || {
+                for (path, chunk) in pending {
+                    if let Err(e) = write_entry(&path, chunk) {
+                        log::warn!("Failed to cache chunk in '{}': {}", path.display(), e);
+                    }
+                }
+            })
+            .expect("failed to spawn the chunk cache thread");
+        log::debug!("Caching chunks in '{}'", dir.display());
+        Some(Self { dir, writes })
+    }
+
+    /// Loads the cached chunk at `pos` if its hash is `hash`.
+    pub fn load(&self, pos: ChunkPos, hash: u64) -> Option<Chunk> {
+        let entry = fs::read(self.path(pos)).ok()?;
+        chunk_cache::decode_entry(&entry, hash)
+    }
+
+    /// Caches a chunk received from the server, replacing
+    /// the chunk cached at `pos`, if any.
+    pub fn store(&self, pos: ChunkPos, chunk: Chunk) {
+        self.writes.send((self.path(pos), chunk)).ok();
+    }
+
+    fn path(&self, pos: ChunkPos) -> PathBuf {
+        self.dir.join(format!("{}_{}_{}.bin", pos.x, pos.y, pos.z))
+    }
+}
+
+/// Returns the directory holding the caches of all worlds.
+#[cfg(not(target_arch = "wasm32"))]
+fn root_dir() -> PathBuf {
+    crate::platform::cache_dir().join("chunks")
+}
+
+/// Creates a cache directory, returning whether it exists.
+#[cfg(not(target_arch = "wasm32"))]
+fn create_dir(dir: &Path) -> bool {
+    match fs::create_dir_all(dir) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!(
+                "Not caching chunks since '{}' can't be created: {}",
+                dir.display(),
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Writes an entry next to its final path, so that an
+/// interrupted write never leaves a partial entry.
+#[cfg(not(target_arch = "wasm32"))]
+fn write_entry(path: &Path, chunk: Chunk) -> io::Result<()> {
+    let partial = path.with_extension("partial");
+    fs::write(&partial, chunk_cache::encode_entry(chunk))?;
+    fs::rename(&partial, path)
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::env;
+
+    use common::{blocks, BlockId};
+
+    use super::*;
+
+    #[test]
+    fn loads_chunks_with_matching_hashes() {
+        let dir = env::temp_dir().join(format!("voltz-chunk-cache-test-{}", std::process::id()));
+        let cache = ChunkCache {
+            dir: dir.clone(),
+            writes: mpsc::channel().0,
+        };
+        fs::create_dir_all(&dir).unwrap();
+        let pos = ChunkPos { x: -1, y: 2, z: 3 };
+        let mut chunk = Chunk::new();
+        chunk.set(0, 0, 0, BlockId::new(blocks::Stone));
+        let hash = chunk_cache::chunk_hash(&chunk);
+        assert!(cache.load(pos, hash).is_none());
+
+        write_entry(&cache.path(pos), chunk).unwrap();
+        let loaded = cache.load(pos, hash).unwrap();
+        assert!(loaded.get(0, 0, 0).is::<blocks::Stone>());
+        assert!(cache.load(pos, hash ^ 1).is_none());
+        assert!(cache.load(ChunkPos { x: 0, y: 0, z: 0 }, hash).is_none());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
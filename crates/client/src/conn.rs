@@ -1,13 +1,42 @@
+use ahash::AHashMap;
+use common::{
+    biome::ColumnBiomes,
+    edit::Digging,
+    entity::{
+        player::{Experience, Username},
+        FallingBlock, Health, ItemDrop, Vel, XpOrb,
+    },
+    item::Inventory,
+    Chunk, ChunkPos, Orient, Pos,
+};
+use glam::{Vec2, Vec3A};
+use hecs::Entity;
+use physics::FallDistance;
 use protocol::{
     bridge::ToServer,
-    packets::server::{LoadChunk, UnloadChunk},
-    packets::ServerPacket,
+    dictionary::BlockDictionary,
+    keepalive::{self, Keepalive},
+    packets::server::{
+        BlockUpdate, ChatMessage, CloseDialog, DespawnEntity, EntityDigProgress, EntityKind,
+        EntityPosition, LoadCachedChunk, LoadChunk, MeteorShower, MoveEntity, OpenDialog,
+        PlayerDied, Respawn, SetBlockDictionary, SetGameMode, SetInventory, SpawnEntity,
+        SpawnFallingBlock, SystemMessage, Teleport, TickRate, TimeUpdate, UnloadChunk,
+        UpdateGameRules, UpdateHealth, UpdateXp, ViewDistanceChanged, WeatherChange,
+    },
+    packets::{
+        client::RequestChunks, shared::Disconnect, ClientPacket, ServerPacket, SharedPacket,
+    },
     Bridge,
 };
 
 use crate::{
-    event::{ChunkLoaded, ChunkUnloaded},
+    chunk_cache::ChunkCache,
+    entity::Interpolation,
+    event::{
+        ChatReceived, ChunkLoaded, ChunkUnloaded, DialogClosed, DialogOpened, MessageReceived,
+    },
     game::Game,
+    platform::Instant,
 };
 
 /// Handles packets received from the server.
@@ -16,32 +45,277 @@ use crate::{
 /// running each tick. This on
 pub struct Connection {
     bridge: Bridge<ToServer>,
+    /// The dictionary used to decode chunk palettes, if the
+    /// server sent one.
+    dictionary: Option<BlockDictionary>,
+    /// Present if the server sends chunk hashes for us to look up.
+    chunk_cache: Option<ChunkCache>,
+    /// Chunks missing from the cache, to request
+    /// once the current packets are handled.
+    uncached: Vec<ChunkPos>,
+    /// Maps the server's entity IDs to our entities.
+    entities: AHashMap<u64, Entity>,
+    keepalive: Keepalive,
+    /// Whether the server has stopped answering pings.
+    timed_out: bool,
 }
 
 impl Connection {
-    pub fn new(bridge: Bridge<ToServer>) -> Self {
-        Self { bridge }
+    pub fn new(bridge: Bridge<ToServer>, chunk_cache: Option<ChunkCache>) -> Self {
+        Self {
+            bridge,
+            dictionary: None,
+            chunk_cache,
+            uncached: Vec::new(),
+            entities: AHashMap::new(),
+            keepalive: Keepalive::new(Instant::now()),
+            timed_out: false,
+        }
     }
 
     /// Handles all buffered packets and updates the game state accordingly.
-    pub fn handle_packets(&mut self, game: &mut Game) {
+    /// Returns the number of packets handled.
+    pub fn handle_packets(&mut self, game: &mut Game) -> usize {
+        let mut count = 0;
         for packet in self.bridge.flush_received() {
+            count += 1;
             match packet {
-                ServerPacket::Shared(_) => {}
-                ServerPacket::ServerInfo(_) | ServerPacket::JoinGame(_) => {
+                ServerPacket::Shared(packet) => self.handle_shared(game, packet),
+                ServerPacket::WorldgenProgress(_)
+                | ServerPacket::ServerInfo(_)
+                | ServerPacket::RegistrySync(_)
+                | ServerPacket::JoinGame(_) => {
                     log::warn!("Received login packet during game state?");
                 }
-                ServerPacket::LoadChunk(packet) => handle_load_chunk(game, packet),
+                ServerPacket::SetBlockDictionary(packet) => {
+                    self.handle_set_block_dictionary(packet)
+                }
+                ServerPacket::LoadChunk(packet) => self.handle_load_chunk(game, packet),
+                ServerPacket::LoadCachedChunk(packet) => {
+                    self.handle_load_cached_chunk(game, packet)
+                }
                 ServerPacket::UnloadChunk(packet) => handle_unload_chunk(game, packet),
+                ServerPacket::BlockUpdate(packet) => handle_block_update(game, packet),
+                ServerPacket::SpawnEntity(packet) => self.handle_spawn_entity(game, packet),
+                ServerPacket::SpawnFallingBlock(packet) => {
+                    self.handle_spawn_falling_block(game, packet)
+                }
+                ServerPacket::MoveEntity(packet) => self.handle_move_entity(game, packet),
+                ServerPacket::EntityPosition(packet) => self.handle_entity_position(game, packet),
+                ServerPacket::Teleport(packet) => handle_teleport(game, packet),
+                ServerPacket::DespawnEntity(packet) => self.handle_despawn_entity(game, packet),
+                ServerPacket::EntityDigProgress(packet) => {
+                    self.handle_entity_dig_progress(game, packet)
+                }
+                ServerPacket::WeatherChange(packet) => handle_weather_change(game, packet),
+                ServerPacket::TimeUpdate(packet) => handle_time_update(game, packet),
+                ServerPacket::MeteorShower(packet) => handle_meteor_shower(game, packet),
+                ServerPacket::SystemMessage(packet) => handle_system_message(game, packet),
+                ServerPacket::ChatMessage(packet) => handle_chat_message(game, packet),
+                ServerPacket::TickRate(packet) => handle_tick_rate(game, packet),
+                ServerPacket::ViewDistanceChanged(packet) => {
+                    handle_view_distance_changed(game, packet)
+                }
+                ServerPacket::OpenDialog(packet) => handle_open_dialog(game, packet),
+                ServerPacket::CloseDialog(packet) => handle_close_dialog(game, packet),
+                ServerPacket::UpdateXp(packet) => handle_update_xp(game, packet),
+                ServerPacket::UpdateHealth(packet) => handle_update_health(game, packet),
+                ServerPacket::SetInventory(packet) => handle_set_inventory(game, packet),
+                ServerPacket::PlayerDied(packet) => handle_player_died(game, packet),
+                ServerPacket::Respawn(packet) => handle_respawn(game, packet),
+                ServerPacket::SetGameMode(packet) => handle_set_game_mode(game, packet),
+                ServerPacket::UpdateGameRules(packet) => handle_update_game_rules(game, packet),
+            }
+        }
+        self.request_uncached();
+        self.keep_alive(game);
+        count
+    }
+
+    fn handle_shared(&mut self, game: &mut Game, packet: SharedPacket) {
+        match packet {
+            SharedPacket::Disconnect(Disconnect { reason }) => {
+                let reason = reason.unwrap_or_else(|| "no reason given".to_owned());
+                log::warn!("Disconnected by the server: {}", reason);
+                game.events().push(MessageReceived {
+                    message: format!("Disconnected: {}", reason),
+                });
+            }
+            SharedPacket::Ping(ping) => {
+                self.bridge
+                    .send(ClientPacket::Shared(SharedPacket::Pong(keepalive::answer(
+                        ping,
+                    ))));
+            }
+            SharedPacket::Pong(pong) => self.keepalive.on_pong(pong, Instant::now()),
+        }
+    }
+
+    /// Pings the server and reports when it stops answering.
+    fn keep_alive(&mut self, game: &mut Game) {
+        let now = Instant::now();
+        if self.keepalive.is_timed_out(now) {
+            if !self.timed_out {
+                self.timed_out = true;
+                log::error!("The server stopped responding");
+                game.events().push(MessageReceived {
+                    message: "The server stopped responding.".to_owned(),
+                });
+            }
+        } else if let Some(ping) = self.keepalive.poll(now) {
+            self.bridge
+                .send(ClientPacket::Shared(SharedPacket::Ping(ping)));
+        }
+        game.debug_data.rtt = self.keepalive.rtt();
+    }
+
+    fn handle_set_block_dictionary(&mut self, packet: SetBlockDictionary) {
+        log::debug!(
+            "Using block dictionary with {} entries",
+            packet.dictionary.entries().len()
+        );
+        self.dictionary = Some(packet.dictionary);
+    }
+
+    fn handle_load_chunk(&self, game: &mut Game, packet: LoadChunk) {
+        let chunk = match &self.dictionary {
+            Some(dictionary) => dictionary.decode(packet.chunk),
+            None => packet.chunk.into_full(),
+        };
+        if let (Some(cache), Some(chunk)) = (&self.chunk_cache, &chunk) {
+            cache.store(packet.pos, chunk.clone());
+        }
+        load_chunk(game, packet.pos, chunk, packet.biomes);
+        log::trace!("Received and loaded chunk {:?}", packet.pos);
+    }
+
+    fn handle_load_cached_chunk(&mut self, game: &mut Game, packet: LoadCachedChunk) {
+        let cached = self
+            .chunk_cache
+            .as_ref()
+            .and_then(|cache| cache.load(packet.pos, packet.hash));
+        match cached {
+            Some(chunk) => {
+                load_chunk(game, packet.pos, Some(chunk), packet.biomes);
+                log::trace!("Loaded chunk {:?} from the cache", packet.pos);
+            }
+            None => self.uncached.push(packet.pos),
+        }
+    }
+
+    /// Asks the server for the chunks missing from the cache.
+    fn request_uncached(&mut self) {
+        if self.uncached.is_empty() {
+            return;
+        }
+        log::trace!("Requesting {} uncached chunks", self.uncached.len());
+        let positions = std::mem::take(&mut self.uncached);
+        self.bridge
+            .send(ClientPacket::RequestChunks(RequestChunks { positions }));
+    }
+
+    fn handle_spawn_entity(&mut self, game: &mut Game, packet: SpawnEntity) {
+        let entity = match packet.kind {
+            EntityKind::Player { username } => game.ecs_mut().spawn((
+                Pos(packet.pos),
+                Orient(packet.orient),
+                Interpolation::at(packet.pos, packet.orient),
+                Username(username),
+            )),
+            EntityKind::XpOrb { value } => game.ecs_mut().spawn((
+                Pos(packet.pos),
+                Interpolation::at(packet.pos, Vec2::zero()),
+                XpOrb { value },
+            )),
+            EntityKind::ItemDrop { stack } => {
+                if !stack.is_valid() {
+                    log::warn!("Received invalid item drop {:?}", stack);
+                    return;
+                }
+                game.ecs_mut().spawn((
+                    Pos(packet.pos),
+                    Interpolation::at(packet.pos, Vec2::zero()),
+                    ItemDrop(stack),
+                ))
+            }
+        };
+        if let Some(old) = self.entities.insert(packet.entity, entity) {
+            game.ecs_mut().despawn(old).ok();
+        }
+    }
+
+    fn handle_spawn_falling_block(&mut self, game: &mut Game, packet: SpawnFallingBlock) {
+        let block = match game.registry.to_local(packet.block) {
+            Some(block) => block,
+            None => {
+                log::warn!("Received invalid falling block {:?}", packet.block);
+                return;
+            }
+        };
+        let entity = game.ecs_mut().spawn((
+            Pos(packet.pos),
+            Interpolation::at(packet.pos, Vec2::zero()),
+            FallingBlock(block),
+        ));
+        if let Some(old) = self.entities.insert(packet.entity, entity) {
+            game.ecs_mut().despawn(old).ok();
+        }
+    }
+
+    fn handle_move_entity(&self, game: &mut Game, packet: MoveEntity) {
+        if let Some(&entity) = self.entities.get(&packet.entity) {
+            if let Ok(mut interpolation) = game.ecs().get_mut::<Interpolation>(entity) {
+                interpolation.push(packet.pos, None);
+            }
+        }
+    }
+
+    fn handle_entity_position(&self, game: &mut Game, packet: EntityPosition) {
+        if let Some(&entity) = self.entities.get(&packet.entity) {
+            if let Ok(mut interpolation) = game.ecs().get_mut::<Interpolation>(entity) {
+                interpolation.push(packet.pos, Some(packet.orient));
+            }
+        }
+    }
+
+    fn handle_despawn_entity(&mut self, game: &mut Game, packet: DespawnEntity) {
+        if let Some(entity) = self.entities.remove(&packet.entity) {
+            game.ecs_mut().despawn(entity).ok();
+        }
+    }
+
+    /// Keeps the block other players dig in their [`Digging`]
+    /// component, so the renderer can draw its cracks.
+    fn handle_entity_dig_progress(&self, game: &mut Game, packet: EntityDigProgress) {
+        let entity = match self.entities.get(&packet.entity) {
+            Some(&entity) => entity,
+            None => return,
+        };
+        match packet.digging {
+            Some(digging) => {
+                game.ecs_mut().insert_one(entity, digging).ok();
+            }
+            None => {
+                game.ecs_mut().remove_one::<Digging>(entity).ok();
             }
         }
     }
 }
 
-fn handle_load_chunk(game: &mut Game, packet: LoadChunk) {
-    game.main_zone_mut().insert(packet.pos, packet.chunk);
-    game.events().push(ChunkLoaded { pos: packet.pos });
-    log::trace!("Received and loaded chunk {:?}", packet.pos);
+/// Loads a chunk received from the server, translating its block IDs.
+/// `chunk` is `None` if the server sent a malformed chunk.
+fn load_chunk(game: &mut Game, pos: ChunkPos, chunk: Option<Chunk>, biomes: ColumnBiomes) {
+    let chunk = match chunk.and_then(|chunk| game.registry.chunk_to_local(chunk)) {
+        Some(chunk) => chunk,
+        None => {
+            log::warn!("Received malformed chunk {:?}", pos);
+            return;
+        }
+    };
+    let zone = game.main_zone_mut();
+    zone.insert(pos, chunk);
+    zone.set_column_biomes(pos.x, pos.z, biomes);
+    game.events().push(ChunkLoaded { pos });
 }
 
 fn handle_unload_chunk(game: &mut Game, packet: UnloadChunk) {
@@ -49,3 +323,138 @@ fn handle_unload_chunk(game: &mut Game, packet: UnloadChunk) {
     game.events().push(ChunkUnloaded { pos: packet.pos });
     log::trace!("Unloaded chunk {:?} (existed: {})", packet.pos, existed);
 }
+
+fn handle_teleport(game: &mut Game, packet: Teleport) {
+    log::debug!("Teleported to {:?}", packet.pos);
+    let player = game.player_ref();
+    player.get_mut::<Pos>().unwrap().0 = packet.pos;
+    player.get_mut::<Vel>().unwrap().0 = Vec3A::zero();
+    *player.get_mut::<FallDistance>().unwrap() = FallDistance::default();
+}
+
+fn handle_player_died(game: &mut Game, packet: PlayerDied) {
+    log::info!("Died: {}", packet.cause);
+    game.death = Some(packet.cause);
+}
+
+fn handle_respawn(game: &mut Game, packet: Respawn) {
+    log::debug!("Respawned at {:?}", packet.pos);
+    game.death = None;
+    let player = game.player_ref();
+    player.get_mut::<Pos>().unwrap().0 = packet.pos;
+    player.get_mut::<Vel>().unwrap().0 = Vec3A::zero();
+    *player.get_mut::<FallDistance>().unwrap() = FallDistance::default();
+}
+
+fn handle_set_game_mode(game: &mut Game, packet: SetGameMode) {
+    if packet.reach.is_finite() && packet.reach >= 0. {
+        log::debug!("Game mode is {} with reach {}", packet.mode, packet.reach);
+        game.game_mode = packet.mode;
+        game.reach = packet.reach;
+    } else {
+        log::warn!("Received invalid reach {}", packet.reach);
+    }
+}
+
+fn handle_update_game_rules(game: &mut Game, packet: UpdateGameRules) {
+    for (rule, value) in packet.rules {
+        match game.rules.set(rule, value) {
+            Ok(()) => log::debug!("Game rule {} is {}", rule, value),
+            Err(e) => log::warn!("Received invalid game rule: {}", e),
+        }
+    }
+}
+
+fn handle_weather_change(game: &mut Game, packet: WeatherChange) {
+    log::debug!("Weather changed to {:?}", packet.weather);
+    game.weather = packet.weather;
+}
+
+fn handle_time_update(game: &mut Game, packet: TimeUpdate) {
+    if packet.time.is_finite() && packet.rate.is_finite() && packet.rate >= 0. {
+        game.clock.update(packet.time, packet.rate);
+    } else {
+        log::warn!(
+            "Received invalid time {} passing at rate {}",
+            packet.time,
+            packet.rate
+        );
+    }
+}
+
+fn handle_meteor_shower(game: &mut Game, packet: MeteorShower) {
+    game.meteor_shower = packet.active;
+}
+
+fn handle_system_message(game: &mut Game, packet: SystemMessage) {
+    game.events().push(MessageReceived {
+        message: packet.message,
+    });
+}
+
+fn handle_chat_message(game: &mut Game, packet: ChatMessage) {
+    game.events().push(ChatReceived {
+        sender: packet.sender,
+        message: packet.message,
+    });
+}
+
+fn handle_update_xp(game: &mut Game, packet: UpdateXp) {
+    game.experience = Experience::new(packet.total);
+}
+
+fn handle_update_health(game: &mut Game, packet: UpdateHealth) {
+    game.health = Health {
+        current: packet.health.min(packet.max_health),
+        max: packet.max_health,
+    };
+}
+
+fn handle_set_inventory(game: &mut Game, packet: SetInventory) {
+    match Inventory::from_slots(packet.slots, packet.selected as usize) {
+        Some(inventory) => game.inventory = inventory,
+        None => log::warn!("Received invalid inventory"),
+    }
+}
+
+fn handle_tick_rate(game: &mut Game, packet: TickRate) {
+    if packet.tick_length.is_finite() && packet.tick_length > 0. {
+        log::debug!("Server ticks every {}s", packet.tick_length);
+        game.server_tick_length = packet.tick_length;
+    } else {
+        log::warn!("Received invalid tick length {}", packet.tick_length);
+    }
+}
+
+fn handle_view_distance_changed(game: &mut Game, packet: ViewDistanceChanged) {
+    log::debug!("Server view distance is {} chunks", packet.view_distance);
+    game.server_view_distance = Some(packet.view_distance);
+}
+
+fn handle_block_update(game: &mut Game, packet: BlockUpdate) {
+    let block = match game.registry.to_local(packet.block) {
+        Some(block) => block,
+        None => {
+            log::warn!(
+                "Received invalid block {:?} at {:?}",
+                packet.block,
+                packet.pos
+            );
+            return;
+        }
+    };
+    // Updates to chunks that are not loaded are ignored. This
+    // also replaces any block the player predicted at `pos`.
+    if game.set_block(packet.pos, block).is_ok() {
+        log::trace!("Set block {:?} to {:?}", packet.pos, block);
+    }
+}
+
+fn handle_open_dialog(game: &mut Game, packet: OpenDialog) {
+    log::trace!("Opening dialog {} ('{}')", packet.id, packet.title);
+    game.events().push(DialogOpened { dialog: packet });
+}
+
+fn handle_close_dialog(game: &mut Game, packet: CloseDialog) {
+    game.events().push(DialogClosed { id: packet.id });
+}
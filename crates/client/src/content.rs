@@ -0,0 +1,131 @@
+//! Validation of loaded content.
+//!
+//! Blocks, block models and block textures refer to each other by name.
+//! [`validate`] cross-checks them right after assets are loaded and
+//! reports every problem at once, rather than the renderer failing on the
+//! first one or drawing blocks with the unknown model at mesh time.
+
+use std::fmt::{self, Display};
+
+use anyhow::bail;
+
+use crate::{asset::Assets, renderer};
+
+/// The problems found with the loaded content.
+#[derive(Debug, Default)]
+pub struct Report {
+    problems: Vec<String>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a problem.
+    pub fn add(&mut self, problem: impl Into<String>) {
+        self.problems.push(problem.into());
+    }
+
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Returns an error listing every problem, if there are any.
+    pub fn into_result(self) -> anyhow::Result<()> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            bail!("{}", self)
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "found {} problem(s) with the loaded content:",
+            self.problems.len()
+        )?;
+        for problem in &self.problems {
+            write!(f, "\n  * {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the block registry, block models and
+/// block textures in `assets` agree with each other.
+pub fn validate(assets: &Assets) -> Report {
+    let mut report = Report::new();
+    renderer::validate_assets(assets, &mut report);
+    if !report.is_empty() {
+        log::error!("{}", report);
+    }
+    report
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::*;
+    use crate::{
+        asset::{model::YamlModel, YamlLoader},
+        asset_loaders,
+    };
+
+    #[test]
+    fn bundled_assets_are_valid() {
+        let mut assets = asset_loaders();
+        assets.load_dir("../../assets").unwrap();
+        let report = validate(&assets);
+        assert!(report.is_empty(), "{}", report);
+    }
+
+    fn write(path: PathBuf, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let dir = std::env::temp_dir().join(format!("voltz-content-test-{}", std::process::id()));
+        write(
+            dir.join("index.yml"),
+            "groups:\n  model/:\n    loader: YamlModel\n",
+        );
+        write(
+            dir.join("model/block/stone.yml"),
+            "inherits: cube\ntextures:\n  all: stone.png\n",
+        );
+        write(
+            dir.join("model/block/dirt.yml"),
+            "inherits: missing\ntextures:\n  all: dirt.png\n",
+        );
+
+        let mut assets = Assets::new();
+        assets.add_loader("YamlModel", YamlLoader::<YamlModel>::new());
+        assets.load_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        let report = validate(&assets);
+        let has = |text: &str| {
+            report
+                .problems()
+                .iter()
+                .any(|problem| problem.contains(text))
+        };
+        assert!(has("missing block texture 'outline.png'"));
+        assert!(has("missing parent model 'cube'"));
+        assert!(has("missing parent model 'missing'"));
+        assert!(has("block 'grass' has no model 'model/block/grass.yml'"));
+        assert!(has("missing fallback model 'model/block/unknown.yml'"));
+        assert!(report.into_result().is_err());
+    }
+}
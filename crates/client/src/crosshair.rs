@@ -0,0 +1,182 @@
+//! The crosshair, whose icon shows what the player
+//! can do with the block they are looking at.
+
+use std::{
+    f32::consts::{FRAC_PI_2, TAU},
+    panic::Location,
+};
+
+use common::SystemExecutor;
+use glam::{vec2, Vec2};
+use utils::{Color, Rect};
+use voltzui::{
+    canvas::{LineCap, Paint, Stroke},
+    widget::ChangeList,
+    Canvas, Path, Style, Theme, WidgetData, WidgetState,
+};
+
+use crate::{game::Game, ui::Length};
+
+/// The width and height of the crosshair in logical pixels.
+const SIZE: f32 = 32.;
+/// The length of each arm of the plus sign.
+const ARM_LENGTH: f32 = 6.;
+/// The radius of the interact and digging rings.
+const RING_RADIUS: f32 = 10.;
+const LINE_WIDTH: f32 = 2.;
+/// The number of line segments in a full digging ring.
+const RING_SEGMENTS: u32 = 48;
+
+/// The crosshair icon. Set each frame by the
+/// [`interaction`](crate::interaction) system.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Crosshair {
+    /// A plus sign.
+    Default,
+    /// The targeted block can be used, like a door.
+    Interact,
+    /// The targeted block is being dug. Shows a ring filled
+    /// to the given fraction, from 0 to 1.
+    Digging(f32),
+    /// The player is looking at a block beyond their reach.
+    OutOfReach,
+}
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(crosshair_system);
+}
+
+fn crosshair_system(game: &mut Game) {
+    let window = game.window();
+    let window_size = window.inner_size().to_logical::<f32>(window.scale_factor());
+    let pos = (vec2(window_size.width, window_size.height) - Vec2::splat(SIZE)) / 2.;
+    let crosshair = game.crosshair;
+
+    let mut ui_store = game.ui_store();
+    let ui = ui_store.get(
+        "crosshair",
+        Length::LogicalPixels(SIZE),
+        Length::LogicalPixels(SIZE),
+        pos,
+    );
+    ui.build().push(CrosshairWidget::new(crosshair));
+}
+
+/// Draws a [`Crosshair`].
+struct CrosshairWidget {
+    crosshair: Crosshair,
+    location: &'static Location<'static>,
+}
+
+impl CrosshairWidget {
+    #[track_caller]
+    fn new(crosshair: Crosshair) -> Self {
+        Self {
+            crosshair,
+            location: Location::caller(),
+        }
+    }
+}
+
+impl WidgetData for CrosshairWidget {
+    type State = Crosshair;
+
+    fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    fn into_state(self) -> Self::State {
+        self.crosshair
+    }
+
+    fn apply_changes(&self, state: &Self::State, changes: &mut ChangeList<Self::State>) {
+        let _ = (state, changes);
+    }
+}
+
+impl WidgetState for Crosshair {
+    fn style(&self, _theme: &Theme) -> Style {
+        Style::default()
+    }
+
+    fn is_leaf(&self) -> bool {
+        true
+    }
+
+    fn compute_size(
+        &mut self,
+        _max_width: Option<f32>,
+        _max_height: Option<f32>,
+        _theme: &Theme,
+    ) -> Vec2 {
+        Vec2::splat(SIZE)
+    }
+
+    fn draw(&mut self, bounds: Rect, cv: &mut Canvas, _theme: &Theme) {
+        let center = bounds.pos + bounds.size / 2.;
+        let color = Color::rgba(1., 1., 1., 0.9);
+        match *self {
+            Crosshair::Default => stroke(cv, &plus(center), color),
+            Crosshair::Interact => {
+                cv.fill_path(
+                    &Path::circle(center, LINE_WIDTH),
+                    &Paint::new().shade_solid(color),
+                );
+                stroke(cv, &Path::circle(center, RING_RADIUS), color);
+            }
+            Crosshair::Digging(progress) => {
+                stroke(cv, &plus(center), color);
+                stroke(
+                    cv,
+                    &Path::circle(center, RING_RADIUS),
+                    Color::rgba(1., 1., 1., 0.3),
+                );
+                if progress > 0. {
+                    stroke(cv, &arc(center, progress.min(1.)), color);
+                }
+            }
+            Crosshair::OutOfReach => {
+                let d = ARM_LENGTH / 2f32.sqrt();
+                let cross = Path::builder()
+                    .move_to(center - vec2(d, d))
+                    .line_to(center + vec2(d, d))
+                    .move_to(center + vec2(-d, d))
+                    .line_to(center + vec2(d, -d))
+                    .finish();
+                stroke(cv, &cross, Color::rgba(0.6, 0.6, 0.6, 0.6));
+            }
+        }
+    }
+}
+
+fn stroke(cv: &mut Canvas, path: &Path, color: Color) {
+    cv.stroke_path(
+        path,
+        &Paint::new().shade_solid(color),
+        &Stroke::new().width(LINE_WIDTH).line_cap(LineCap::Round),
+    );
+}
+
+fn plus(center: Vec2) -> Path {
+    Path::builder()
+        .move_to(center - vec2(ARM_LENGTH, 0.))
+        .line_to(center + vec2(ARM_LENGTH, 0.))
+        .move_to(center - vec2(0., ARM_LENGTH))
+        .line_to(center + vec2(0., ARM_LENGTH))
+        .finish()
+}
+
+/// Returns an arc of the ring starting at the top
+/// and going clockwise for `fraction` of a turn.
+fn arc(center: Vec2, fraction: f32) -> Path {
+    let segments = ((RING_SEGMENTS as f32 * fraction).ceil() as u32).max(1);
+    let point = |i: u32| {
+        let angle = TAU * fraction * i as f32 / segments as f32 - FRAC_PI_2;
+        center + vec2(angle.cos(), angle.sin()) * RING_RADIUS
+    };
+    let mut builder = Path::builder().move_to(point(0));
+    for i in 1..=segments {
+        builder = builder.line_to(point(i));
+    }
+    builder.finish()
+}
@@ -0,0 +1,128 @@
+//! The death screen.
+//!
+//! Shown from when the server reports that the player died until it
+//! confirms that they respawned. The cursor is released meanwhile so
+//! the screen's buttons can be clicked; they can also be focused with
+//! Tab and pressed with Enter. Respawning asks the server to respawn
+//! the player, and quitting closes the game.
+
+use common::{System, SystemExecutor};
+use fontdue::Font;
+use glam::Vec2;
+use protocol::packets::{client::Respawn, ClientPacket};
+use utils::Color;
+use voltzui::{
+    widgets::{Button, Container, Panel, Text},
+    AlignItems, Dimension, JustifyContent, Message,
+};
+
+use crate::{
+    asset::{Asset, Assets},
+    game::Game,
+    ui::Length,
+};
+
+/// Width of the death screen panel in logical pixels.
+const PANEL_WIDTH: f32 = 400.;
+/// Color of the death screen heading.
+const HEADING_COLOR: Color = Color {
+    r: 0.9,
+    g: 0.2,
+    b: 0.2,
+    a: 1.,
+};
+
+const RESPAWN_BUTTON: &str = "respawn";
+const QUIT_BUTTON: &str = "quit";
+
+pub fn setup(systems: &mut SystemExecutor<Game>, assets: &Assets) -> anyhow::Result<()> {
+    let font = assets.get("font/Play-Regular.ttf")?;
+    systems.add(DeathSystem {
+        respawning: false,
+        font,
+    });
+    Ok(())
+}
+
+struct DeathSystem {
+    /// Whether we asked to respawn and are waiting for the server.
+    respawning: bool,
+    font: Asset<Font>,
+}
+
+impl DeathSystem {
+    fn handle_clicks(&mut self, game: &Game) {
+        let messages = game
+            .ui_store()
+            .get(
+                "death",
+                Length::Percent(100.),
+                Length::Percent(100.),
+                Vec2::zero(),
+            )
+            .take_messages();
+        for message in messages {
+            match message {
+                Message::Clicked(RESPAWN_BUTTON) if !self.respawning => {
+                    self.respawning = true;
+                    game.bridge().send(ClientPacket::Respawn(Respawn));
+                }
+                Message::Clicked(QUIT_BUTTON) => game.close(),
+                _ => {}
+            }
+        }
+    }
+
+    fn build_ui(&self, game: &Game, cause: &str) {
+        let mut ui_store = game.ui_store();
+        let ui = ui_store.get(
+            "death",
+            Length::Percent(100.),
+            Length::Percent(100.),
+            Vec2::zero(),
+        );
+        let heading_size = ui.theme().heading_font_size;
+        let font = self.font.as_arc();
+
+        let mut builder = ui.build();
+        builder
+            .begin(Container::column().with_style(|style| {
+                style.size.width = Dimension::Percent(1.);
+                style.size.height = Dimension::Percent(1.);
+                style.justify_content = JustifyContent::Center;
+                style.align_items = AlignItems::Center;
+            }))
+            .begin(Panel::new().with_style(|style| {
+                style.size.width = Dimension::Points(PANEL_WIDTH);
+            }))
+            .push(
+                Text::themed("You died")
+                    .size(heading_size)
+                    .color(HEADING_COLOR),
+            )
+            .push(Text::themed(cause));
+        if self.respawning {
+            builder.push(Text::themed("Respawning..."));
+        } else {
+            builder.push(Button::new(RESPAWN_BUTTON, "Respawn", font));
+        }
+        builder
+            .push(Button::new(QUIT_BUTTON, "Quit", font))
+            .end()
+            .end();
+    }
+}
+
+impl System<Game> for DeathSystem {
+    fn run(&mut self, game: &mut Game) {
+        let cause = match &game.death {
+            Some(cause) => cause.clone(),
+            None => {
+                self.respawning = false;
+                return;
+            }
+        };
+        self.handle_clicks(game);
+        self.build_ui(game, &cause);
+    }
+}
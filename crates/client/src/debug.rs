@@ -1,9 +1,19 @@
 //! The debug screen (F3)
-
-use common::{event::EventBus, Orient, Pos, System, SystemExecutor};
+//!
+//! Besides performance data, it lists the kinds of packets most recently
+//! exchanged with the server, from the bridge's
+//! [statistics](protocol::stats).
+//!
+//! When the client runs under RenderDoc with `VOLTZ_RENDERDOC` set,
+//! pressing [`CAPTURE_KEY`] while the debug screen is open captures
+//! the next frame.
+
+use std::{collections::VecDeque, time::Duration};
+
+use common::{event::EventBus, Orient, System, SystemExecutor};
 use fontdue::Font;
 use glam::Vec2;
-use protocol::PROTOCOL_VERSION;
+use protocol::{packets::kind::Direction, PROTOCOL_VERSION};
 use voltzui::widgets::Text;
 use winit::event::VirtualKeyCode;
 
@@ -15,10 +25,55 @@ use crate::{
     ALLOCATOR,
 };
 
+/// The number of packet kinds listed on the debug screen.
+const MAX_PACKET_KINDS: usize = 8;
+
+/// Captures the next frame with RenderDoc while the debug screen is open.
+pub const CAPTURE_KEY: VirtualKeyCode = VirtualKeyCode::F9;
+
 #[derive(Default)]
 pub struct DebugData {
-    pub adapter: Option<wgpu::AdapterInfo>,
+    /// The name and graphics backend of the adapter.
+    pub adapter: Option<(String, &'static str)>,
     pub render_chunks: usize,
+    /// The bytes used by chunk meshes and the
+    /// total size of the buffers holding them.
+    pub chunk_memory: (u64, u64),
+    /// The number of chunk meshing tasks that finished this frame.
+    pub meshes_completed: usize,
+    /// The number of packets handled this frame.
+    pub packets_processed: usize,
+    /// The round-trip time to the server, measured
+    /// by the last answered ping.
+    pub rtt: Option<Duration>,
+    pub render_timings: RenderTimings,
+    /// Summaries of recent lag spikes, oldest first.
+    /// See the [`diagnostics`](crate::diagnostics) module.
+    pub lag_spikes: VecDeque<String>,
+    /// Whether RenderDoc frame captures are available.
+    pub capture_available: bool,
+    /// Set to capture the next rendered frame with RenderDoc.
+    /// The renderer clears it.
+    pub capture_requested: bool,
+    /// The number of frames captured.
+    pub captures: u32,
+}
+
+/// How long each stage of rendering took this frame.
+///
+/// wgpu does not expose GPU timestamps, so these are CPU
+/// times. When the GPU falls behind, the time shows up in
+/// `acquire`, which waits for the next swapchain image.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RenderTimings {
+    /// Preparing buffers for the frame, including uploads.
+    pub prepare: Duration,
+    /// Waiting for the next swapchain image.
+    pub acquire: Duration,
+    /// Recording render passes.
+    pub encode: Duration,
+    /// Submitting commands to the GPU.
+    pub submit: Duration,
 }
 
 pub fn setup(systems: &mut SystemExecutor<Game>, assets: &Assets) -> anyhow::Result<()> {
@@ -36,19 +91,25 @@ struct DebugSystem {
 }
 
 impl DebugSystem {
-    fn update_enabled(&mut self, events: &mut EventBus) {
+    /// Toggles the debug screen. Returns whether a
+    /// frame capture was requested while it is open.
+    fn handle_keys(&mut self, events: &mut EventBus) -> bool {
+        let mut capture = false;
         for key_pressed in events.iter::<KeyPressed>() {
             if key_pressed.key == VirtualKeyCode::F3 {
                 self.enabled = !self.enabled;
+            } else if key_pressed.key == CAPTURE_KEY && self.enabled {
+                capture = true;
             }
         }
+        capture
     }
 
     fn text(&self, game: &Game) -> String {
         let version = env!("CARGO_PKG_VERSION");
         let protocol = PROTOCOL_VERSION;
 
-        let pos = game.player_ref().get::<Pos>().unwrap().0;
+        let pos = game.player_pos();
         let [posx, posy, posz] = [pos.x, pos.y, pos.z];
         let orient = game.player_ref().get::<Orient>().unwrap().0;
         let [orientx, orienty] = [orient.x, orient.y];
@@ -59,45 +120,111 @@ impl DebugSystem {
             .debug_data
             .adapter
             .as_ref()
-            .map(|info| {
-                let backend = match info.backend {
-                    wgpu::Backend::Empty => "Empty",
-                    wgpu::Backend::Vulkan => "Vulkan",
-                    wgpu::Backend::Metal => "Metal",
-                    wgpu::Backend::Dx12 => "DirectX 12",
-                    wgpu::Backend::Dx11 => "DirectX 11",
-                    wgpu::Backend::Gl => "OpenGL",
-                    wgpu::Backend::BrowserWebGpu => "WebGPU",
-                };
-                (info.name.as_str(), backend)
-            })
+            .map(|(name, backend)| (name.as_str(), *backend))
             .unwrap_or_else(|| ("unknown", "Unknown"));
 
         let dt = game.dt() * 1000.;
 
+        let lag_spikes = if game.debug_data.lag_spikes.is_empty() {
+            "None".to_owned()
+        } else {
+            game.debug_data
+                .lag_spikes
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+
+        let rtt = match game.debug_data.rtt {
+            Some(rtt) => format!("{:.2}ms", rtt.as_secs_f64() * 1000.),
+            None => "unknown".to_owned(),
+        };
+
+        let packets = Self::packets(game);
+
+        let capture = if game.debug_data.capture_available {
+            format!(
+                "press {:?} to capture ({} taken)",
+                CAPTURE_KEY, game.debug_data.captures
+            )
+        } else {
+            "unavailable".to_owned()
+        };
+
+        let target_mode = format!("{:?}", game.target_mode);
+
         let loaded_chunks = game.main_zone().len();
+        let view_distance = match game.server_view_distance {
+            Some(distance) if distance != game.settings.view_distance => {
+                format!("{} (server: {})", game.settings.view_distance, distance)
+            }
+            _ => game.settings.view_distance.to_string(),
+        };
         let render_chunks = game.debug_data.render_chunks;
+        let (used, total) = game.debug_data.chunk_memory;
+        let chunk_memory = format!(
+            "{} of {}",
+            utils::format_bytes(used),
+            utils::format_bytes(total)
+        );
 
         indoc::formatdoc! {"
             Voltz v{version}, protocol {protocol}
             X: {posx:.2}, Y: {posy:.2}, Z: {posz:.2}
             Yaw: {orientx:.2}, Pitch: {orienty:.2}
+            Targeting: {target_mode}
 
             Adapter: {adapter}
             Backend: {backend}
+            RenderDoc: {capture}
 
+            View distance: {view_distance}
             Chunks loaded: {loaded_chunks}
             Chunks rendering: {render_chunks}
+            Chunk meshes: {chunk_memory}
             Used memory: {memory}
 
             Frame time: {dt:.2}ms
+            Ping: {rtt}
+            Lag spikes:
+            {lag_spikes}
+
+            Packets:
+            {packets}
         "}
     }
+
+    /// Lists the most recently seen kinds of packets.
+    fn packets(game: &Game) -> String {
+        let seen = game.bridge().stats().seen();
+        if seen.is_empty() {
+            return "None".to_owned();
+        }
+        seen.iter()
+            .take(MAX_PACKET_KINDS)
+            .map(|(kind, stats)| {
+                let count = match kind.direction() {
+                    Direction::ToServer => format!("{} sent", stats.sent),
+                    Direction::ToClient => format!("{} received", stats.received),
+                    Direction::Both => {
+                        format!("{} sent, {} received", stats.sent, stats.received)
+                    }
+                };
+                let last_seen = stats.last_seen().unwrap().elapsed();
+                format!("{}: {}, {:.1}s ago", kind, count, last_seen.as_secs_f32())
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
 }
 
 impl System<Game> for DebugSystem {
     fn run(&mut self, game: &mut Game) {
-        self.update_enabled(&mut *game.events());
+        let capture = self.handle_keys(&mut *game.events());
+        if capture && game.debug_data.capture_available {
+            game.debug_data.capture_requested = true;
+        }
 
         if self.enabled {
             let mut ui_store = game.ui_store();
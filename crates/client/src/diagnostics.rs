@@ -0,0 +1,174 @@
+//! Lag spike diagnostics.
+//!
+//! When a frame takes longer than a threshold, [`LagSpikeMonitor`]
+//! captures a breakdown of where the time went: how long each system
+//! and each stage of rendering took, how many chunk meshes and packets
+//! were handled, and how many allocations were made. The breakdown is
+//! appended to `diagnostics/lag_spikes.log`, and a summary is added to
+//! the lag spike history on the debug screen (F3).
+//!
+//! If event tracing is enabled with `VOLTZ_TRACE_EVENTS`, the report
+//! also lists the events pushed during the last few frames.
+//!
+//! The threshold defaults to 50 milliseconds and can be set
+//! with `VOLTZ_LAG_THRESHOLD_MS`.
+
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use common::SystemExecutor;
+
+use crate::{game::Game, ALLOCATOR};
+
+const DEFAULT_THRESHOLD: Duration = Duration::from_millis(50);
+/// The number of lag spikes kept in the debug screen history.
+const HISTORY_LENGTH: usize = 5;
+/// The number of slowest systems listed in a summary.
+const SUMMARY_SYSTEMS: usize = 2;
+/// The number of frames of traced events included in a report.
+const TRACE_FRAMES: usize = 3;
+
+/// Captures a breakdown of frames that take too long.
+pub struct LagSpikeMonitor {
+    threshold: Duration,
+    path: PathBuf,
+    /// The allocation count at the start of the frame.
+    frame_allocations: usize,
+}
+
+impl LagSpikeMonitor {
+    /// Creates a monitor using the threshold from
+    /// `VOLTZ_LAG_THRESHOLD_MS`, if it is set.
+    pub fn from_env() -> Self {
+        let threshold = match env::var("VOLTZ_LAG_THRESHOLD_MS") {
+            Ok(value) => match value.parse() {
+                Ok(millis) => Duration::from_millis(millis),
+                Err(_) => {
+                    log::error!("Ignoring invalid VOLTZ_LAG_THRESHOLD_MS '{}'", value);
+                    DEFAULT_THRESHOLD
+                }
+            },
+            Err(_) => DEFAULT_THRESHOLD,
+        };
+        Self {
+            threshold,
+            path: PathBuf::from("diagnostics").join("lag_spikes.log"),
+            frame_allocations: ALLOCATOR.allocations(),
+        }
+    }
+
+    /// Called at the end of each frame, which took `elapsed`.
+    /// Captures a breakdown of the frame if it was a lag spike.
+    pub fn end_frame(
+        &mut self,
+        elapsed: Duration,
+        systems: &SystemExecutor<Game>,
+        game: &mut Game,
+    ) {
+        let allocations = ALLOCATOR.allocations();
+        let frame_allocations = allocations.wrapping_sub(self.frame_allocations);
+        self.frame_allocations = allocations;
+
+        if elapsed < self.threshold {
+            return;
+        }
+
+        let mut slowest: Vec<_> = systems.timings().collect();
+        slowest.sort_by_key(|&(_, time)| std::cmp::Reverse(time));
+        let summary = format!(
+            "{:.1}ms: {}",
+            millis(elapsed),
+            slowest
+                .iter()
+                .take(SUMMARY_SYSTEMS)
+                .map(|(name, time)| format!("{} {:.1}ms", short_name(name), millis(*time)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        log::warn!("Lag spike: {}", summary);
+
+        if let Err(e) = self.write_report(elapsed, systems, game, frame_allocations) {
+            log::error!("Failed to write lag spike report: {:#}", e);
+        }
+
+        let history = &mut game.debug_data.lag_spikes;
+        if history.len() == HISTORY_LENGTH {
+            history.pop_front();
+        }
+        history.push_back(summary);
+    }
+
+    /// Appends the breakdown of a frame to the report file.
+    fn write_report(
+        &self,
+        elapsed: Duration,
+        systems: &SystemExecutor<Game>,
+        game: &Game,
+        allocations: usize,
+    ) -> anyhow::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        writeln!(
+            file,
+            "Frame took {:.2}ms (at {}s since the Unix epoch)",
+            millis(elapsed),
+            timestamp
+        )?;
+        writeln!(file, "Systems:")?;
+        for (name, time) in systems.timings() {
+            writeln!(file, "  {:>8.2}ms  {}", millis(time), name)?;
+        }
+
+        let debug = &game.debug_data;
+        let render = debug.render_timings;
+        writeln!(file, "Rendering:")?;
+        for &(stage, time) in &[
+            ("prepare", render.prepare),
+            ("acquire", render.acquire),
+            ("encode", render.encode),
+            ("submit", render.submit),
+        ] {
+            writeln!(file, "  {:>8.2}ms  {}", millis(time), stage)?;
+        }
+        writeln!(file, "Meshes completed: {}", debug.meshes_completed)?;
+        writeln!(file, "Packets processed: {}", debug.packets_processed)?;
+        writeln!(
+            file,
+            "Allocations: {} ({} in use)",
+            allocations,
+            utils::format_bytes(ALLOCATOR.allocated() as u64)
+        )?;
+        let events = game.events();
+        if events.is_tracing() {
+            writeln!(file, "Events:")?;
+            write!(file, "{}", events.dump(TRACE_FRAMES))?;
+        }
+        writeln!(file)?;
+        Ok(())
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.
+}
+
+/// Strips the module path from a system's type name.
+fn short_name(name: &str) -> &str {
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
@@ -0,0 +1,146 @@
+//! Modal dialogs opened by the server.
+//!
+//! Buttons are chosen with the number keys; Escape
+//! dismisses the dialog.
+
+use common::{System, SystemExecutor};
+use fontdue::Font;
+use glam::Vec2;
+use protocol::packets::{client::DialogResponse, server::OpenDialog, ClientPacket};
+use voltzui::{
+    widgets::{Container, Panel, Text},
+    AlignItems, Dimension, JustifyContent,
+};
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    asset::{Asset, Assets},
+    event::{DialogClosed, DialogOpened, KeyPressed},
+    game::Game,
+    ui::Length,
+};
+
+/// Width of a dialog in logical pixels.
+const DIALOG_WIDTH: f32 = 400.;
+
+const NUMBER_KEYS: [VirtualKeyCode; 9] = [
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+
+pub fn setup(systems: &mut SystemExecutor<Game>, assets: &Assets) -> anyhow::Result<()> {
+    let font = assets.get("font/Play-Regular.ttf")?;
+    systems.add(DialogSystem {
+        dialogs: Vec::new(),
+        font,
+    });
+    Ok(())
+}
+
+struct DialogSystem {
+    /// Open dialogs. The last one is displayed.
+    dialogs: Vec<OpenDialog>,
+    font: Asset<Font>,
+}
+
+impl DialogSystem {
+    fn update_dialogs(&mut self, game: &Game) {
+        let mut events = game.events();
+        for event in events.iter::<DialogOpened>() {
+            self.dialogs.push(event.dialog.clone());
+        }
+        for event in events.iter::<DialogClosed>() {
+            self.dialogs.retain(|dialog| dialog.id != event.id);
+        }
+    }
+
+    /// Determines the player's response to the displayed dialog, if any.
+    /// `Some(None)` means the dialog was dismissed.
+    fn response(&self, game: &Game) -> Option<Option<u32>> {
+        // Keys typed into the chat don't answer dialogs.
+        if game.chat_open {
+            return None;
+        }
+        let dialog = self.dialogs.last()?;
+        for key_pressed in game.events().iter::<KeyPressed>() {
+            if key_pressed.key == VirtualKeyCode::Escape {
+                return Some(None);
+            }
+            if let Some(index) = NUMBER_KEYS.iter().position(|&k| k == key_pressed.key) {
+                if let Some(button) = dialog.buttons.get(index) {
+                    return Some(Some(button.id));
+                }
+            }
+        }
+        None
+    }
+
+    fn build_ui(&self, game: &Game, dialog: &OpenDialog) {
+        let mut ui_store = game.ui_store();
+        let ui = ui_store.get(
+            "dialog",
+            Length::Percent(100.),
+            Length::Percent(100.),
+            Vec2::zero(),
+        );
+        let heading_size = ui.theme().heading_font_size;
+        let font = self.font.as_arc();
+
+        ui.build()
+            .begin(Container::column().with_style(|style| {
+                style.size.width = Dimension::Percent(1.);
+                style.size.height = Dimension::Percent(1.);
+                style.justify_content = JustifyContent::Center;
+                style.align_items = AlignItems::Center;
+            }))
+            .begin(Panel::new().with_style(|style| {
+                style.size.width = Dimension::Points(DIALOG_WIDTH);
+            }))
+            .push(Text::new(&dialog.title, font).size(heading_size))
+            .push(Text::new(&dialog.text, font))
+            .push(Text::new(&button_labels(dialog), font))
+            .end()
+            .end();
+    }
+}
+
+/// Formats the dialog's buttons with the number
+/// keys which choose them, e.g. `[1] Yes    [2] No`.
+fn button_labels(dialog: &OpenDialog) -> String {
+    dialog
+        .buttons
+        .iter()
+        .zip(1..=NUMBER_KEYS.len())
+        .map(|(button, number)| format!("[{}] {}", number, button.label))
+        .collect::<Vec<_>>()
+        .join("    ")
+}
+
+impl System<Game> for DialogSystem {
+    fn run(&mut self, game: &mut Game) {
+        self.update_dialogs(game);
+        // Set before responding so that systems running later
+        // this frame ignore the key that answered the dialog.
+        game.dialog_open = !self.dialogs.is_empty();
+
+        if let Some(button) = self.response(game) {
+            let dialog = self.dialogs.pop().expect("no dialog");
+            game.bridge()
+                .send(ClientPacket::DialogResponse(DialogResponse {
+                    dialog: dialog.id,
+                    button,
+                }));
+        }
+
+        if let Some(dialog) = self.dialogs.last() {
+            self.build_ui(game, dialog);
+        }
+    }
+}
@@ -1,18 +1,193 @@
 //! Systems for miscallaneous entity functionality.
 
-use common::{blocks, entity::Vel, BlockId, Pos, SystemExecutor};
-use physics::Aabb;
+use std::f32::consts::{PI, TAU};
+
+use common::{entity::Vel, Orient, Pos, SystemExecutor, WorldPos};
+use glam::Vec2;
+use physics::{Aabb, FallDistance};
+use protocol::packets::{client::Landed, ClientPacket};
 
 use crate::game::Game;
 
+/// The longest time in seconds an entity keeps moving
+/// past its latest snapshot when the next one is late.
+const MAX_EXTRAPOLATION: f32 = 0.1;
+
+/// Component for entities moved by the server.
+///
+/// Buffers the last two snapshots of the entity's position and
+/// orientation sent by the server, and shows the entity moving from
+/// the previous snapshot to the latest over the time between server
+/// ticks. If the next snapshot is late, the entity keeps moving in the
+/// same direction for at most [`MAX_EXTRAPOLATION`] seconds. The entity
+/// turns the short way around, e.g. through 180° rather than 0° when its
+/// yaw goes from 170° to -170°.
+/// An entity that moved to another zone jumps to its new position.
+#[derive(Copy, Clone, Debug)]
+pub struct Interpolation {
+    previous: Snapshot,
+    latest: Snapshot,
+    /// Seconds since the latest snapshot was received.
+    elapsed: f32,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Snapshot {
+    pos: WorldPos,
+    orient: Vec2,
+}
+
+impl Interpolation {
+    /// Creates an `Interpolation` for an entity at rest.
+    pub fn at(pos: WorldPos, orient: Vec2) -> Self {
+        let snapshot = Snapshot { pos, orient };
+        Self {
+            previous: snapshot,
+            latest: snapshot,
+            elapsed: 0.,
+        }
+    }
+
+    /// Adds a snapshot received from the server. Entities
+    /// without an orientation pass `None`.
+    pub fn push(&mut self, pos: WorldPos, orient: Option<Vec2>) {
+        self.previous = self.latest;
+        self.latest = Snapshot {
+            pos,
+            orient: orient.unwrap_or(self.latest.orient),
+        };
+        self.elapsed = 0.;
+    }
+
+    /// Advances time by `dt` seconds and returns the entity's
+    /// position and orientation, given the time between server ticks.
+    fn advance(&mut self, dt: f32, tick_length: f32) -> (WorldPos, Vec2) {
+        self.elapsed += dt;
+        let max = 1. + MAX_EXTRAPOLATION / tick_length;
+        let t = (self.elapsed / tick_length).min(max);
+        let (previous, latest) = (self.previous.pos, self.latest.pos);
+        let pos = if previous.zone == latest.zone {
+            WorldPos {
+                zone: latest.zone,
+                local: previous.local.lerp(latest.local, t),
+            }
+        } else {
+            latest
+        };
+        (
+            pos,
+            lerp_orient(self.previous.orient, self.latest.orient, t),
+        )
+    }
+}
+
+/// Interpolates between two orientations,
+/// turning the yaw the short way around.
+fn lerp_orient(from: Vec2, to: Vec2, t: f32) -> Vec2 {
+    let yaw_difference = (to.x - from.x + PI).rem_euclid(TAU) - PI;
+    Vec2::new(from.x + yaw_difference * t, from.y + (to.y - from.y) * t)
+}
+
 pub fn setup(systems: &mut SystemExecutor<Game>) {
     systems.add(physics_system);
+    systems.add(interpolation_system);
 }
 
 fn physics_system(game: &mut Game) {
-    for (_, (pos, vel, &bounds)) in game.ecs().query::<(&mut Pos, &mut Vel, &Aabb)>().iter() {
+    for (entity, (pos, vel, &bounds, fall)) in game
+        .ecs()
+        .query::<(&mut Pos, &mut Vel, &Aabb, Option<&mut FallDistance>)>()
+        .iter()
+    {
+        // The camera stays where the player died until they respawn.
+        if entity == game.player() && game.death.is_some() {
+            continue;
+        }
+        let zone = game.world().zone(pos.0.zone);
+        let old_pos = pos.0;
         physics::do_tick(bounds, &mut pos.0, &mut vel.0, game.dt(), |pos| {
-            game.main_zone().block(pos) != Some(BlockId::new(blocks::Air))
+            zone?.block(pos)
         });
+        // Only the player's falls are tracked, and the
+        // server decides how much each landing hurts.
+        if let Some(fall) = fall {
+            if let Some(distance) =
+                physics::track_fall(fall, bounds, old_pos, pos.0, |pos| zone?.block(pos))
+            {
+                game.bridge()
+                    .send(ClientPacket::Landed(Landed { distance }));
+            }
+        }
+    }
+}
+
+fn interpolation_system(game: &mut Game) {
+    for (_, (pos, orient, interpolation)) in game
+        .ecs()
+        .query::<(&mut Pos, Option<&mut Orient>, &mut Interpolation)>()
+        .iter()
+    {
+        let (new_pos, new_orient) = interpolation.advance(game.dt(), game.server_tick_length);
+        pos.0 = new_pos;
+        if let Some(orient) = orient {
+            orient.0 = new_orient;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{world::ZoneTransform, World};
+    use glam::{vec3a, Vec3A};
+
+    use super::*;
+
+    #[test]
+    fn interpolates_between_snapshots() {
+        let mut interpolation = Interpolation::at(WorldPos::main(Vec3A::zero()), Vec2::zero());
+        interpolation.push(WorldPos::main(vec3a(1., 0., 0.)), Some(Vec2::new(1., 0.)));
+
+        let (pos, orient) = interpolation.advance(0.025, 0.05);
+        assert!((pos.local.x - 0.5).abs() < 1e-5);
+        assert!((orient.x - 0.5).abs() < 1e-5);
+
+        let (pos, _) = interpolation.advance(0.025, 0.05);
+        assert!((pos.local.x - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn turns_the_short_way_around() {
+        let from = Vec2::new(170f32.to_radians(), 0.);
+        let to = Vec2::new(-170f32.to_radians(), 0.);
+        let mut interpolation = Interpolation::at(WorldPos::main(Vec3A::zero()), from);
+        interpolation.push(WorldPos::main(Vec3A::zero()), Some(to));
+
+        let (_, orient) = interpolation.advance(0.025, 0.05);
+        assert!((orient.x.to_degrees() - 180.).abs() < 1e-3, "{}", orient.x);
+    }
+
+    #[test]
+    fn extrapolation_is_capped() {
+        let mut interpolation = Interpolation::at(WorldPos::main(Vec3A::zero()), Vec2::zero());
+        interpolation.push(WorldPos::main(vec3a(1., 0., 0.)), None);
+
+        // 0.1 seconds of extrapolation is two more ticks of movement.
+        let (pos, _) = interpolation.advance(10., 0.05);
+        assert!((pos.local.x - 3.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn jumps_between_zones() {
+        let mut world = World::new(());
+        let ship = world.add_zone((), ZoneTransform::default());
+        let mut interpolation = Interpolation::at(WorldPos::main(Vec3A::zero()), Vec2::zero());
+        let on_ship = WorldPos {
+            zone: ship,
+            local: vec3a(4., 0., 0.),
+        };
+        interpolation.push(on_ship, None);
+
+        let (pos, _) = interpolation.advance(0.025, 0.05);
+        assert_eq!(pos, on_ship);
     }
 }
@@ -1,5 +1,6 @@
 use common::ChunkPos;
-use winit::event::VirtualKeyCode;
+use protocol::packets::server::OpenDialog;
+use winit::event::{MouseButton, VirtualKeyCode};
 
 /// A chunk has been loaded.
 #[derive(Copy, Clone, Debug)]
@@ -7,6 +8,12 @@ pub struct ChunkLoaded {
     pub pos: ChunkPos,
 }
 
+/// A block in a loaded chunk has changed.
+#[derive(Copy, Clone, Debug)]
+pub struct ChunkModified {
+    pub pos: ChunkPos,
+}
+
 /// A chunk has been unloaded.
 #[derive(Copy, Clone, Debug)]
 pub struct ChunkUnloaded {
@@ -25,6 +32,18 @@ pub struct KeyReleased {
     pub key: VirtualKeyCode,
 }
 
+/// A character has been typed.
+#[derive(Copy, Clone, Debug)]
+pub struct CharacterTyped {
+    pub c: char,
+}
+
+/// A mouse button has been pressed.
+#[derive(Copy, Clone, Debug)]
+pub struct MousePressed {
+    pub button: MouseButton,
+}
+
 /// The mouse has moved.
 #[derive(Copy, Clone, Debug)]
 pub struct MouseMoved {
@@ -38,3 +57,28 @@ pub struct WindowResized {
     pub new_width: u32,
     pub new_height: u32,
 }
+
+/// The server opened a dialog.
+#[derive(Clone, Debug)]
+pub struct DialogOpened {
+    pub dialog: OpenDialog,
+}
+
+/// The server closed a dialog.
+#[derive(Copy, Clone, Debug)]
+pub struct DialogClosed {
+    pub id: u32,
+}
+
+/// The server sent a message to display.
+#[derive(Clone, Debug)]
+pub struct MessageReceived {
+    pub message: String,
+}
+
+/// A player sent a chat message.
+#[derive(Clone, Debug)]
+pub struct ChatReceived {
+    pub sender: String,
+    pub message: String,
+}
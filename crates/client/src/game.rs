@@ -1,15 +1,40 @@
-use std::cell::{Cell, RefCell, RefMut};
+use std::{
+    cell::{Cell, RefCell, RefMut},
+    sync::Arc,
+};
 
 use ahash::AHashSet;
 use bumpalo::Bump;
-use common::{event::EventBus, world::SparseZone, World};
+use common::{
+    chunk::CHUNK_DIM,
+    edit::{Digging, Reach},
+    entity::{
+        player::{Experience, GameMode, MAX_HEALTH},
+        Health,
+    },
+    event::EventBus,
+    game_rules::GameRules,
+    item::Inventory,
+    weather::Weather,
+    world::{BlockOutOfBounds, SparseZone},
+    BlockId, BlockPos, ChunkPos, Pos, World,
+};
+use glam::Vec3A;
 use hecs::{DynamicBundle, Entity, EntityRef};
-use protocol::{bridge::ToServer, Bridge};
+use physics::collision::RaycastMode;
+use protocol::{bridge::ToServer, registry::RegistryMap, Bridge};
 use rand::{Rng, SeedableRng};
 use rand_pcg::Pcg64Mcg;
-use winit::{dpi::PhysicalPosition, event::VirtualKeyCode, window::Window};
+use winit::{
+    dpi::PhysicalPosition,
+    event::{ModifiersState, MouseButton, VirtualKeyCode},
+    window::Window,
+};
 
-use crate::{camera::Matrices, debug::DebugData, ui::UiStore};
+use crate::{
+    camera::Matrices, crosshair::Crosshair, debug::DebugData, event::ChunkModified, meteor::Meteor,
+    settings::Settings, time::Clock, ui::UiStore, weather::Particle,
+};
 
 /// Uberstruct containing the game state. Includes zones, entities,
 /// blocks, etc.
@@ -44,11 +69,13 @@ pub struct Game {
     /// Time in seconds since the previous frame.
     dt: f32,
 
-    /// The window.
-    window: Window,
+    /// The window, which outlives the game.
+    window: Arc<Window>,
 
     /// The set of pressed keys.
     pressed_keys: AHashSet<VirtualKeyCode>,
+    /// The set of pressed mouse buttons.
+    pressed_buttons: AHashSet<MouseButton>,
 
     /// UIs to render this frame.
     ui_store: RefCell<UiStore>,
@@ -60,7 +87,76 @@ pub struct Game {
 
     pub debug_data: DebugData,
 
+    /// The player's settings.
+    pub settings: Settings,
+
+    /// Translates block IDs to and from the server's.
+    pub registry: RegistryMap,
+
+    /// Whether the chat is open. While it is, typed keys
+    /// go to the chat and don't count as pressed.
+    pub chat_open: bool,
+    /// Whether a server dialog is displayed. While one
+    /// is, number keys choose its buttons.
+    pub dialog_open: bool,
+
+    /// The block the player is looking at, if any is in reach.
+    pub targeted_block: Option<BlockPos>,
+    /// The block the player is digging, if any.
+    pub digging: Option<Digging>,
+    /// Which blocks the player can target and
+    /// which they target through.
+    pub target_mode: RaycastMode,
+    /// The crosshair icon, which depends on the targeted block.
+    pub crosshair: Crosshair,
+
     pub mouse_pos: PhysicalPosition<f64>,
+    /// The modifier keys held down.
+    pub modifiers: ModifiersState,
+
+    /// The weather, as last sent by the server.
+    pub weather: Weather,
+    /// The world's time, kept in sync with the server.
+    pub clock: Clock,
+    /// Particles of rain or snow falling around the player.
+    pub precipitation: Vec<Particle>,
+
+    /// Whether the server started a meteor shower.
+    pub meteor_shower: bool,
+    /// Meteors visible in the sky.
+    pub meteors: Vec<Meteor>,
+
+    /// The number of seconds between server ticks,
+    /// as last sent by the server.
+    pub server_tick_length: f32,
+    /// The view distance the server sends chunks within,
+    /// as last sent by the server. May be less than the
+    /// distance in the settings while the server is busy.
+    pub server_view_distance: Option<u32>,
+
+    /// The player's experience, as last sent by the server.
+    pub experience: Experience,
+    /// The player's health, as last sent by the server.
+    pub health: Health,
+    /// The player's inventory, as last sent by the server
+    /// apart from the selected slot, which the client changes.
+    pub inventory: Inventory,
+
+    /// The player's game mode, as last sent by the server.
+    pub game_mode: GameMode,
+    /// How far the player can reach to edit blocks,
+    /// as last sent by the server.
+    pub reach: f32,
+
+    /// The game rules which affect prediction, as last sent by
+    /// the server. Other rules keep their default values.
+    pub rules: GameRules,
+
+    /// The cause of the player's death if they died and the server
+    /// hasn't confirmed that they respawned. While set, the player
+    /// can't move, key presses don't count as pressed, and the
+    /// cursor is released.
+    pub death: Option<String>,
 }
 
 impl Game {
@@ -72,7 +168,7 @@ impl Game {
     pub fn new(
         bridge: Bridge<ToServer>,
         player_components: impl DynamicBundle,
-        window: Window,
+        window: Arc<Window>,
         bump: Bump,
     ) -> Self {
         let mut ecs = hecs::World::new();
@@ -102,11 +198,35 @@ impl Game {
             dt: 0.,
             window,
             pressed_keys,
+            pressed_buttons: AHashSet::new(),
             ui_store,
             matrices,
             closed: Cell::new(false),
             debug_data: Default::default(),
+            settings: Settings::default(),
+            registry: RegistryMap::identity(),
+            chat_open: false,
+            dialog_open: false,
+            targeted_block: None,
+            digging: None,
+            target_mode: RaycastMode::default(),
+            crosshair: Crosshair::Default,
             mouse_pos,
+            modifiers: ModifiersState::empty(),
+            weather: Weather::Clear,
+            clock: Clock::default(),
+            precipitation: Vec::new(),
+            meteor_shower: false,
+            meteors: Vec::new(),
+            server_tick_length: 1. / 20.,
+            server_view_distance: None,
+            experience: Experience::default(),
+            health: Health::full(MAX_HEALTH),
+            inventory: Inventory::new(),
+            game_mode: GameMode::default(),
+            reach: Reach::default().survival,
+            rules: GameRules::new(),
+            death: None,
         }
     }
 
@@ -132,6 +252,12 @@ impl Game {
         self.ecs.entity(self.player).expect("player despawned")
     }
 
+    /// Gets the position of the player in main-zone space.
+    pub fn player_pos(&self) -> Vec3A {
+        self.world
+            .to_main(self.player_ref().get::<Pos>().unwrap().0)
+    }
+
     /// Gets the event bus for queuing and processing events.
     pub fn events(&self) -> RefMut<EventBus> {
         self.events.borrow_mut()
@@ -175,6 +301,38 @@ impl Game {
         self.world_mut().main_zone_mut()
     }
 
+    /// Sets a block in the main zone and marks the chunks it borders
+    /// as modified. Returns an error if the block's chunk is not loaded.
+    pub fn set_block(&mut self, pos: BlockPos, block: BlockId) -> Result<(), BlockOutOfBounds> {
+        self.main_zone_mut().set_block(pos, block)?;
+
+        // Neighboring chunks are re-meshed too, since
+        // the block's faces may have been culled there.
+        let chunk = pos.chunk();
+        let (x, y, z) = pos.chunk_local();
+        let edge = |local: usize| match local {
+            0 => -1,
+            l if l == CHUNK_DIM - 1 => 1,
+            _ => 0,
+        };
+        let mut events = self.events.borrow_mut();
+        events.push(ChunkModified { pos: chunk });
+        let offsets = [(edge(x), 0, 0), (0, edge(y), 0), (0, 0, edge(z))];
+        for &(dx, dy, dz) in &offsets {
+            if (dx, dy, dz) != (0, 0, 0) {
+                let neighbor = ChunkPos {
+                    x: chunk.x + dx,
+                    y: chunk.y + dy,
+                    z: chunk.z + dz,
+                };
+                if self.world.main_zone().chunk(neighbor).is_some() {
+                    events.push(ChunkModified { pos: neighbor });
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Gets the bridge for sending packets to the server.
     pub fn bridge(&self) -> &Bridge<ToServer> {
         &self.bridge
@@ -193,10 +351,6 @@ impl Game {
         &self.window
     }
 
-    pub fn window_mut(&mut self) -> &mut Window {
-        &mut self.window
-    }
-
     pub fn insert_pressed_key(&mut self, key: VirtualKeyCode) {
         self.pressed_keys.insert(key);
     }
@@ -206,7 +360,25 @@ impl Game {
     }
 
     pub fn is_key_pressed(&self, key: VirtualKeyCode) -> bool {
-        self.pressed_keys.contains(&key)
+        !self.chat_open && self.death.is_none() && self.pressed_keys.contains(&key)
+    }
+
+    /// Whether the cursor is grabbed to turn the camera. It's released
+    /// on the death screen so that the screen's buttons can be clicked.
+    pub fn is_cursor_grabbed(&self) -> bool {
+        self.death.is_none()
+    }
+
+    pub fn insert_pressed_button(&mut self, button: MouseButton) {
+        self.pressed_buttons.insert(button);
+    }
+
+    pub fn remove_pressed_button(&mut self, button: MouseButton) {
+        self.pressed_buttons.remove(&button);
+    }
+
+    pub fn is_button_pressed(&self, button: MouseButton) -> bool {
+        self.pressed_buttons.contains(&button)
     }
 
     pub fn ui_store(&self) -> RefMut<UiStore> {
@@ -221,6 +393,7 @@ impl Game {
         self.matrices = matrices;
     }
 
+    /// Asks the client to leave the world, ending the session.
     pub fn close(&self) {
         self.closed.set(true);
     }
@@ -0,0 +1,84 @@
+//! The health bar, shown above the hotbar.
+//!
+//! Shows the player's health out of their maximum,
+//! as last sent by the server.
+
+use common::{System, SystemExecutor};
+use fontdue::Font;
+use glam::vec2;
+use utils::Color;
+use voltzui::{
+    widgets::{Container, Rectangle, Text},
+    AlignItems, Dimension,
+};
+
+use crate::{
+    asset::{Asset, Assets},
+    game::Game,
+    ui::Length,
+};
+
+/// Width of the bar in logical pixels.
+const BAR_WIDTH: f32 = 200.;
+/// Height of the bar in logical pixels.
+const BAR_HEIGHT: f32 = 8.;
+/// Height of the area holding the bar and the health.
+const HUD_HEIGHT: f32 = 30.;
+/// Distance from the bottom of the window to the bottom of
+/// the bar, leaving room for the hotbar and experience bar.
+const BOTTOM_OFFSET: f32 = 110.;
+const HEALTH_COLOR: Color = Color {
+    r: 0.9,
+    g: 0.15,
+    b: 0.15,
+    a: 1.,
+};
+
+pub fn setup(systems: &mut SystemExecutor<Game>, assets: &Assets) -> anyhow::Result<()> {
+    let font = assets.get("font/Play-Regular.ttf")?;
+    systems.add(HealthBarSystem { font });
+    Ok(())
+}
+
+struct HealthBarSystem {
+    font: Asset<Font>,
+}
+
+impl System<Game> for HealthBarSystem {
+    fn run(&mut self, game: &mut Game) {
+        let health = game.health;
+        let text = format!("{} / {}", health.current, health.max);
+        let fraction = if health.max == 0 {
+            0.
+        } else {
+            (health.current as f32 / health.max as f32).min(1.)
+        };
+        let filled = BAR_WIDTH * fraction;
+
+        let window = game.window();
+        let window_size = window.inner_size().to_logical::<f32>(window.scale_factor());
+        let mut ui_store = game.ui_store();
+        let ui = ui_store.get(
+            "health_bar",
+            Length::Percent(100.),
+            Length::LogicalPixels(HUD_HEIGHT),
+            vec2(0., window_size.height - BOTTOM_OFFSET - HUD_HEIGHT),
+        );
+        let font = self.font.as_arc();
+
+        ui.build()
+            .begin(Container::column().with_style(|style| {
+                style.size.width = Dimension::Percent(1.);
+                style.align_items = AlignItems::Center;
+            }))
+            .push(Text::new(&text, font).size(14.).color(HEALTH_COLOR))
+            .begin(Container::row())
+            .push(Rectangle::new(vec2(filled, BAR_HEIGHT), HEALTH_COLOR))
+            .push(Rectangle::new(
+                vec2(BAR_WIDTH - filled, BAR_HEIGHT),
+                Color::rgba(0., 0., 0., 0.5),
+            ))
+            .end()
+            .end();
+    }
+}
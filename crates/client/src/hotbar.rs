@@ -0,0 +1,213 @@
+//! The hotbar, shown above the experience bar.
+//!
+//! Shows the first slots of the player's inventory, as last sent by
+//! the server, with an icon and count for each stack and a mark under
+//! the selected slot, which slides to a newly selected slot. The number
+//! keys select a slot, which the server is told with `SelectSlot`. Right
+//! clicking places the block of the selected item; see the
+//! [`interaction`](crate::interaction) module.
+//!
+//! An item's icon is the [icon texture](YamlModel::icon_texture)
+//! of its block's model.
+
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use anyhow::Context;
+use common::{
+    item::{self, ItemId, HOTBAR_SIZE},
+    System, SystemExecutor,
+};
+use fontdue::Font;
+use glam::vec2;
+use protocol::packets::{client::SelectSlot, ClientPacket};
+use utils::Color;
+use voltzui::{
+    canvas::FilterQuality,
+    widgets::{Container, Image, Rectangle, Text},
+    AlignItems, Dimension, Easing, Texture,
+};
+use winit::event::VirtualKeyCode;
+
+use crate::{
+    asset::{model::YamlModel, texture::TextureAsset, Asset, Assets},
+    event::KeyPressed,
+    game::Game,
+    ui::{self, Length},
+};
+
+/// The keys selecting each hotbar slot.
+const SLOT_KEYS: [VirtualKeyCode; HOTBAR_SIZE] = [
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+/// Width and height of item icons in logical pixels.
+const ICON_SIZE: f32 = 32.;
+/// Width of each slot in logical pixels.
+const SLOT_WIDTH: f32 = 44.;
+/// Height of the mark under the selected slot.
+const MARK_HEIGHT: f32 = 3.;
+/// Seconds the mark takes to slide to a newly selected slot.
+const MARK_SLIDE_DURATION: f32 = 0.1;
+/// Height of the hotbar in logical pixels.
+const HOTBAR_HEIGHT: f32 = 60.;
+/// Distance from the bottom of the window to the bottom of the
+/// hotbar, leaving room for the experience bar.
+const BOTTOM_OFFSET: f32 = 50.;
+const EMPTY_SLOT_COLOR: Color = Color {
+    r: 0.,
+    g: 0.,
+    b: 0.,
+    a: 0.4,
+};
+const MARK_COLOR: Color = Color {
+    r: 1.,
+    g: 1.,
+    b: 1.,
+    a: 0.9,
+};
+
+/// The texture shown for items whose block has no model.
+const UNKNOWN_TEXTURE: &str = "texture/block/unknown.png";
+
+pub fn setup(systems: &mut SystemExecutor<Game>, assets: &Assets) -> anyhow::Result<()> {
+    let font = assets.get("font/Play-Regular.ttf")?;
+    let mut icons = AHashMap::new();
+    for item in item::all_items() {
+        icons.insert(item, load_icon(assets, item)?);
+    }
+    systems.add(HotbarSystem { font, icons });
+    Ok(())
+}
+
+fn load_icon(assets: &Assets, item: ItemId) -> anyhow::Result<Arc<Texture>> {
+    let path = item
+        .descriptor()
+        .block()
+        .and_then(|block| block_texture(assets, block.descriptor().slug()))
+        .unwrap_or_else(|| UNKNOWN_TEXTURE.to_owned());
+    let texture = assets.get::<TextureAsset>(&path).with_context(|| {
+        format!(
+            "failed to load the icon of item '{}'",
+            item.descriptor().slug()
+        )
+    })?;
+    Ok(Arc::new(ui::ui_texture(&texture)))
+}
+
+/// Returns the path of the icon texture
+/// of the block model named `model`, if it has one.
+fn block_texture(assets: &Assets, model: &str) -> Option<String> {
+    let model = assets
+        .get::<YamlModel>(&format!("model/block/{}.yml", model))
+        .ok()?;
+    model
+        .icon_texture()
+        .map(|name| format!("texture/block/{}", name))
+}
+
+/// Selects a hotbar slot and tells the server.
+pub fn select(game: &mut Game, slot: usize) {
+    if slot == game.inventory.selected() || !game.inventory.select(slot) {
+        return;
+    }
+    game.bridge()
+        .send(ClientPacket::SelectSlot(SelectSlot { slot: slot as u32 }));
+}
+
+struct HotbarSystem {
+    font: Asset<Font>,
+    icons: AHashMap<ItemId, Arc<Texture>>,
+}
+
+impl System<Game> for HotbarSystem {
+    fn run(&mut self, game: &mut Game) {
+        self.handle_input(game);
+        self.build_ui(game);
+    }
+}
+
+impl HotbarSystem {
+    fn handle_input(&mut self, game: &mut Game) {
+        // Keys typed into the chat or choosing buttons
+        // of a dialog or the death screen don't select slots.
+        if game.chat_open || game.dialog_open || game.death.is_some() {
+            return;
+        }
+        let slot = game
+            .events()
+            .iter::<KeyPressed>()
+            .filter_map(|pressed| SLOT_KEYS.iter().position(|&key| key == pressed.key))
+            .last();
+        if let Some(slot) = slot {
+            select(game, slot);
+        }
+    }
+
+    fn build_ui(&mut self, game: &mut Game) {
+        let window = game.window();
+        let window_size = window.inner_size().to_logical::<f32>(window.scale_factor());
+        let hotbar = game.inventory.hotbar().to_vec();
+        let selected = game.inventory.selected();
+        let mut ui_store = game.ui_store();
+        let ui = ui_store.get(
+            "hotbar",
+            Length::Percent(100.),
+            Length::LogicalPixels(HOTBAR_HEIGHT),
+            vec2(0., window_size.height - BOTTOM_OFFSET - HOTBAR_HEIGHT),
+        );
+        let font = self.font.as_arc();
+
+        let mut builder = ui.build();
+        let mark_offset = builder.animate(
+            selected as f32 * SLOT_WIDTH + (SLOT_WIDTH - ICON_SIZE) / 2.,
+            MARK_SLIDE_DURATION,
+            Easing::EaseOut,
+        );
+        builder.begin(Container::column().with_style(|style| {
+            style.size.width = Dimension::Percent(1.);
+            style.align_items = AlignItems::Center;
+        }));
+        builder.begin(Container::row());
+        for stack in &hotbar {
+            builder.begin(Container::column().with_style(|style| {
+                style.size.width = Dimension::Points(SLOT_WIDTH);
+                style.align_items = AlignItems::Center;
+            }));
+            match stack.and_then(|stack| self.icons.get(&stack.item)) {
+                Some(icon) => builder.push(
+                    Image::new(icon)
+                        .size(vec2(ICON_SIZE, ICON_SIZE))
+                        .filter_quality(FilterQuality::Nearest),
+                ),
+                None => builder.push(Rectangle::new(vec2(ICON_SIZE, ICON_SIZE), EMPTY_SLOT_COLOR)),
+            };
+            let count = match stack {
+                Some(stack) if stack.count > 1 => stack.count.to_string(),
+                _ => String::new(),
+            };
+            builder.push(Text::new(&count, font).size(14.));
+            builder.end();
+        }
+        builder.end();
+
+        // The mark is offset from the start of the slots by a transparent spacer.
+        builder.begin(Container::row().with_style(|style| {
+            style.size.width = Dimension::Points(hotbar.len() as f32 * SLOT_WIDTH);
+        }));
+        builder.push(Rectangle::new(
+            vec2(mark_offset, MARK_HEIGHT),
+            Color::rgba(0., 0., 0., 0.),
+        ));
+        builder.push(Rectangle::new(vec2(ICON_SIZE, MARK_HEIGHT), MARK_COLOR));
+        builder.end();
+        builder.end();
+    }
+}
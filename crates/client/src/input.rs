@@ -1,12 +1,20 @@
 //! Takes `winit` input and writes it to the event bus.
+//!
+//! Pointer input is first fed to the UIs. Mouse buttons
+//! handled by a widget are not written to the event bus,
+//! so clicking the UI doesn't also act on the world.
+//! Likewise, Tab and Shift-Tab move focus between widgets
+//! and Enter activates the focused one, unless the chat is open.
 
+use glam::vec2;
+use voltzui::{Event, PointerButton};
 use winit::{
     dpi::PhysicalPosition,
-    event::{ElementState, WindowEvent},
+    event::{ElementState, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent},
 };
 
 use crate::{
-    event::{KeyPressed, KeyReleased, MouseMoved, WindowResized},
+    event::{CharacterTyped, KeyPressed, KeyReleased, MouseMoved, MousePressed, WindowResized},
     game::Game,
 };
 
@@ -19,8 +27,14 @@ pub fn handle_event(event: &WindowEvent, game: &mut Game) {
         WindowEvent::KeyboardInput { input, .. } => match input.state {
             ElementState::Pressed => {
                 if let Some(key) = input.virtual_keycode {
-                    game.events().push(KeyPressed { key });
-                    game.insert_pressed_key(key);
+                    // Keys typed into the chat don't navigate the UI.
+                    let handled = !game.chat_open
+                        && ui_event(key, game.modifiers)
+                            .map_or(false, |event| game.ui_store().handle_event(event));
+                    if !handled {
+                        game.events().push(KeyPressed { key });
+                        game.insert_pressed_key(key);
+                    }
                 }
             }
             ElementState::Released => {
@@ -30,7 +44,39 @@ pub fn handle_event(event: &WindowEvent, game: &mut Game) {
                 }
             }
         },
+        WindowEvent::ModifiersChanged(modifiers) => game.modifiers = *modifiers,
+        WindowEvent::ReceivedCharacter(c) => game.events().push(CharacterTyped { c: *c }),
+        WindowEvent::MouseInput { state, button, .. } => match state {
+            ElementState::Pressed => {
+                let handled = pointer_button(*button).map_or(false, |button| {
+                    game.ui_store()
+                        .handle_event(Event::PointerPressed { button })
+                });
+                if !handled {
+                    game.events().push(MousePressed { button: *button });
+                    game.insert_pressed_button(*button);
+                }
+            }
+            ElementState::Released => {
+                if let Some(button) = pointer_button(*button) {
+                    game.ui_store()
+                        .handle_event(Event::PointerReleased { button });
+                }
+                game.remove_pressed_button(*button);
+            }
+        },
+        WindowEvent::CursorLeft { .. } => {
+            game.ui_store().handle_event(Event::PointerLeft);
+        }
         WindowEvent::CursorMoved { position, .. } => {
+            game.ui_store().handle_event(Event::PointerMoved {
+                pos: vec2(position.x as f32, position.y as f32),
+            });
+            // A released cursor points at the UI instead of turning the camera.
+            if !game.is_cursor_grabbed() {
+                game.mouse_pos = *position;
+                return;
+            }
             let size = game.window().inner_size();
             game.events().push(MouseMoved {
                 xrel: ((position.x - game.mouse_pos.x) / size.width as f64) * 1000.,
@@ -38,10 +84,87 @@ pub fn handle_event(event: &WindowEvent, game: &mut Game) {
             });
             let mouse_pos = PhysicalPosition::new(size.width as f64 / 2., size.height as f64 / 2.);
             game.mouse_pos = mouse_pos;
-            if let Err(e) = game.window_mut().set_cursor_position(mouse_pos) {
+            if let Err(e) = game.window().set_cursor_position(mouse_pos) {
                 log::error!("Failed to set cursor position: {:?}", e);
             }
         }
         _ => (),
     }
 }
+
+/// Maps the keys which navigate the UI to UI events.
+fn ui_event(key: VirtualKeyCode, modifiers: ModifiersState) -> Option<Event> {
+    match key {
+        VirtualKeyCode::Tab if modifiers.shift() => Some(Event::FocusPrevious),
+        VirtualKeyCode::Tab => Some(Event::FocusNext),
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Some(Event::Activate),
+        _ => None,
+    }
+}
+
+fn pointer_button(button: MouseButton) -> Option<PointerButton> {
+    match button {
+        MouseButton::Left => Some(PointerButton::Left),
+        MouseButton::Right => Some(PointerButton::Right),
+        MouseButton::Middle => Some(PointerButton::Middle),
+        MouseButton::Other(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use fontdue::Font;
+    use glam::Vec2;
+    use voltzui::{
+        widgets::{Button, Container},
+        Message,
+    };
+
+    use crate::ui::{Length, UiStore};
+
+    use super::*;
+
+    #[test]
+    fn keys_navigate_uis() {
+        let font = Arc::new(
+            Font::from_bytes(
+                &include_bytes!("../../../assets/font/Play-Regular.ttf")[..],
+                Default::default(),
+            )
+            .unwrap(),
+        );
+        let mut uis = UiStore::default();
+        let menu = |uis: &mut UiStore| {
+            uis.get(
+                "menu",
+                Length::Percent(100.),
+                Length::Percent(100.),
+                Vec2::zero(),
+            )
+        };
+        menu(&mut uis)
+            .build()
+            .begin(Container::column())
+            .push(Button::new("play", "Play", &font))
+            .push(Button::new("quit", "Quit", &font))
+            .end();
+
+        let mut press = |key, modifiers| {
+            ui_event(key, modifiers).map_or(false, |event| uis.handle_event(event))
+        };
+        // Nothing is focused yet.
+        assert!(!press(VirtualKeyCode::Return, ModifiersState::empty()));
+        assert!(press(VirtualKeyCode::Tab, ModifiersState::empty()));
+        assert!(press(VirtualKeyCode::Tab, ModifiersState::empty()));
+        assert!(press(VirtualKeyCode::Tab, ModifiersState::SHIFT));
+        assert!(press(VirtualKeyCode::Return, ModifiersState::empty()));
+        assert!(!press(VirtualKeyCode::Key1, ModifiersState::empty()));
+
+        assert_eq!(
+            menu(&mut uis).take_messages(),
+            vec![Message::Clicked("play")]
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! Breaking and placing blocks.
+//!
+//! Each frame, a ray cast from the player's eyes finds the targeted
+//! block, which the renderer outlines. Holding the left button digs the
+//! targeted block, breaking it after a time that depends on its hardness.
+//! Right clicking uses it if it can be used, like a door, and otherwise
+//! places the block of the item selected in the [hotbar](crate::hotbar)
+//! against the targeted face, turned by [`edit::orient`] so that ladders
+//! face away from the wall they hang on. The crosshair shows which
+//! of these apply. Blocks are in reach when [`edit::is_in_reach`]
+//! allows it for the reach the server sent, so the crosshair
+//! shows exactly which blocks the server lets the player edit.
+//!
+//! The ray stops at blocks according to the [targeting mode](physics::collision::RaycastMode),
+//! which [`TARGET_MODE_KEY`] cycles through: the player can target fluids,
+//! blocks behind fluids, or only blocks entities collide with, seeing past
+//! wires and open doors.
+//!
+//! The block being dug is kept in [`Game::digging`] for the renderer
+//! to draw its cracks, and its crack stage is sent to the server with
+//! `DigProgress` whenever it changes, so other players see it too.
+//!
+//! Edits are predicted: they apply locally at once and are sent to the
+//! server. The server answers each edit with a `BlockUpdate`, which
+//! replaces the prediction and undoes it if the edit was rejected.
+
+use common::{
+    edit::{self, BlockEdit, Digging},
+    entity::player,
+    BlockId, BlockPos, Orient, System, SystemExecutor,
+};
+use glam::Vec3A;
+use physics::collision::{block_bounds, raytrace_in_zone};
+use protocol::packets::{
+    client::{BreakBlock, DigProgress, PlaceBlock, UseBlock},
+    ClientPacket,
+};
+use winit::event::{MouseButton, VirtualKeyCode};
+
+use crate::{
+    camera,
+    crosshair::Crosshair,
+    event::{KeyPressed, MousePressed},
+    game::Game,
+    PLAYER_BBOX,
+};
+
+/// The maximum distance from the player's eyes to a block the
+/// crosshair shows as out of reach, beyond the player's reach.
+const SIGHT: f32 = 16.;
+/// The number of seconds it takes to break
+/// a block with a hardness of 1.
+const BREAK_TIME: f32 = 0.75;
+/// The key to switch to the next targeting mode.
+const TARGET_MODE_KEY: VirtualKeyCode = VirtualKeyCode::LAlt;
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(InteractionSystem { digging: None });
+}
+
+/// A block the player is looking at.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Target {
+    pos: BlockPos,
+    /// The block on the other side of the face
+    /// the player is looking at.
+    adjacent: BlockPos,
+    /// Whether the player can reach the block.
+    in_reach: bool,
+}
+
+struct InteractionSystem {
+    /// The block being dug and the fraction of it dug so far.
+    digging: Option<(BlockPos, f32)>,
+}
+
+impl System<Game> for InteractionSystem {
+    fn run(&mut self, game: &mut Game) {
+        self.interact(game);
+
+        let digging = self
+            .digging
+            .map(|(pos, progress)| Digging::new(pos, progress));
+        if digging != game.digging {
+            game.digging = digging;
+            game.bridge()
+                .send(ClientPacket::DigProgress(DigProgress { digging }));
+        }
+    }
+}
+
+impl InteractionSystem {
+    /// Targets, digs, uses, and places blocks
+    /// depending on the buttons the player presses.
+    fn interact(&mut self, game: &mut Game) {
+        switch_target_mode(game);
+        if game.death.is_some() {
+            self.digging = None;
+            game.targeted_block = None;
+            game.crosshair = Crosshair::Default;
+            return;
+        }
+
+        let target = find_target(game);
+        game.targeted_block = target
+            .filter(|target| target.in_reach)
+            .map(|target| target.pos);
+        let target = match target {
+            Some(target) if target.in_reach => target,
+            Some(_) => {
+                self.digging = None;
+                game.crosshair = Crosshair::OutOfReach;
+                return;
+            }
+            None => {
+                self.digging = None;
+                game.crosshair = Crosshair::Default;
+                return;
+            }
+        };
+
+        self.dig(game, target.pos);
+
+        let right_clicked = game
+            .events()
+            .iter::<MousePressed>()
+            .any(|event| event.button == MouseButton::Right);
+        if right_clicked {
+            // Use the targeted block if possible,
+            // and otherwise place a block against it.
+            if !predict(game, BlockEdit::Use, target.pos) {
+                let selected = game
+                    .inventory
+                    .selected_stack()
+                    .and_then(|stack| stack.item.descriptor().block())
+                    .and_then(|block| edit::orient(block, target.adjacent, target.pos));
+                if let Some(block) = selected {
+                    predict(game, BlockEdit::Place(block), target.adjacent);
+                }
+            }
+        }
+
+        game.crosshair = match self.digging {
+            Some((_, progress)) => Crosshair::Digging(progress),
+            None if is_usable(game, target.pos) => Crosshair::Interact,
+            None => Crosshair::Default,
+        };
+    }
+
+    /// Digs the block at `pos` while the left button is held,
+    /// breaking it once it has been dug for long enough.
+    fn dig(&mut self, game: &mut Game, pos: BlockPos) {
+        let block = match game.main_zone().block(pos) {
+            Some(block) if game.is_button_pressed(MouseButton::Left) => block,
+            _ => {
+                self.digging = None;
+                return;
+            }
+        };
+
+        // Digging starts over when the player looks at another block.
+        let progress = match self.digging {
+            Some((digging, progress)) if digging == pos => progress,
+            _ => 0.,
+        };
+        let break_time = block.hardness() * BREAK_TIME;
+        let progress = if break_time > 0. {
+            progress + game.dt() / break_time
+        } else {
+            1.
+        };
+
+        if progress >= 1. {
+            predict(game, BlockEdit::Break, pos);
+            self.digging = None;
+        } else {
+            self.digging = Some((pos, progress));
+        }
+    }
+}
+
+/// Returns whether the block at `pos` can be used, like a door.
+fn is_usable(game: &Game, pos: BlockPos) -> bool {
+    BlockEdit::Use
+        .apply(pos, |pos| game.main_zone().block(pos))
+        .is_some()
+}
+
+/// Applies an edit locally and sends it to the server.
+/// Returns whether the edit was allowed.
+fn predict(game: &mut Game, edit: BlockEdit, pos: BlockPos) -> bool {
+    if let BlockEdit::Place(block) = edit {
+        if game.registry.to_remote(block).is_none() {
+            log::debug!("The server doesn't have {:?}; not placing it", block);
+            return false;
+        }
+    }
+    let changes = match edit.apply(pos, |pos| game.main_zone().block(pos)) {
+        Some(changes) => changes,
+        None => return false,
+    };
+
+    // The player's position is the bottom center of their bounds.
+    let center_offset = glam::vec3a(PLAYER_BBOX.half_width(), 0., PLAYER_BBOX.half_depth());
+    let player_bounds = PLAYER_BBOX + (game.player_pos() - center_offset);
+    let blocks_player = changes.iter().any(|&(pos, block)| {
+        block_bounds(pos, Some(block)).map_or(false, |bounds| bounds.intersects(player_bounds))
+    });
+    if blocks_player {
+        // Don't put blocks inside the player.
+        return false;
+    }
+
+    for (pos, block) in changes {
+        game.set_block(pos, block).ok();
+    }
+
+    let packet = match edit {
+        BlockEdit::Place(block) => ClientPacket::PlaceBlock(PlaceBlock {
+            pos,
+            block: game.registry.to_remote(block).expect("checked above"),
+        }),
+        BlockEdit::Break => ClientPacket::BreakBlock(BreakBlock { pos }),
+        BlockEdit::Use => ClientPacket::UseBlock(UseBlock { pos }),
+    };
+    game.bridge().send(packet);
+    true
+}
+
+/// Finds the block the player is looking at, if any is within sight.
+fn find_target(game: &Game) -> Option<Target> {
+    let pos = game.player_pos();
+    let orient = game.player_ref().get::<Orient>().unwrap().0;
+    let eye = player::eye_pos(pos);
+    let direction = Vec3A::from(camera::direction(orient));
+
+    let sight = game.reach + SIGHT;
+    let impact = raytrace_in_zone(eye, direction, sight * sight, game.target_mode, |pos| {
+        game.main_zone().block(pos)
+    })?;
+
+    let point = eye + direction * impact.distance;
+    Some(Target {
+        pos: impact.pos,
+        adjacent: adjacent_to_face(impact.pos, point),
+        in_reach: edit::is_in_reach(eye, impact.pos, game.reach),
+    })
+}
+
+/// Switches to the next targeting mode when [`TARGET_MODE_KEY`] is pressed.
+fn switch_target_mode(game: &mut Game) {
+    // Keys typed into the chat don't switch modes.
+    if game.chat_open {
+        return;
+    }
+    let presses = game
+        .events()
+        .iter::<KeyPressed>()
+        .filter(|pressed| pressed.key == TARGET_MODE_KEY)
+        .count();
+    for _ in 0..presses {
+        game.target_mode = game.target_mode.next();
+        log::info!("Targeting mode: {:?}", game.target_mode);
+    }
+}
+
+/// Returns the block sharing the face of `block`
+/// closest to `point`, a point on its surface.
+fn adjacent_to_face(block: BlockPos, point: Vec3A) -> BlockPos {
+    let local = point - block.center();
+    let abs = local.abs();
+    if abs.x >= abs.y && abs.x >= abs.z {
+        block.offset(local.x.signum() as i32, 0, 0)
+    } else if abs.y >= abs.z {
+        block.offset(0, local.y.signum() as i32, 0)
+    } else {
+        block.offset(0, 0, local.z.signum() as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::vec3a;
+
+    use super::*;
+
+    #[test]
+    fn faces() {
+        let block = BlockPos { x: 2, y: -3, z: 5 };
+        assert_eq!(
+            adjacent_to_face(block, vec3a(2.5, -2., 5.3)),
+            BlockPos { x: 2, y: -2, z: 5 }
+        );
+        assert_eq!(
+            adjacent_to_face(block, vec3a(2., -2.5, 5.6)),
+            BlockPos { x: 1, y: -3, z: 5 }
+        );
+        assert_eq!(
+            adjacent_to_face(block, vec3a(2.1, -2.9, 6.)),
+            BlockPos { x: 2, y: -3, z: 6 }
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! The loading screen displayed while logging in.
+//!
+//! Shows the progress of world generation
+//! reported by the server.
+
+use std::sync::Arc;
+
+use fontdue::Font;
+use glam::{vec2, Vec2};
+use protocol::packets::server::WorldgenProgress;
+use utils::Color;
+use voltzui::{
+    widgets::{Container, Panel, Rectangle, Text},
+    AlignItems, Dimension, JustifyContent, Theme,
+};
+use winit::window::Window;
+
+use crate::{
+    asset::{Asset, Assets},
+    renderer::Renderer,
+    ui::{Length, UiStore},
+};
+
+/// Width of the loading screen panel in logical pixels.
+const PANEL_WIDTH: f32 = 400.;
+/// Height of the progress bar in logical pixels.
+const PROGRESS_BAR_HEIGHT: f32 = 8.;
+
+pub struct LoadingScreen {
+    ui_store: UiStore,
+    font: Asset<Font>,
+    /// The most recent progress sent by the server.
+    progress: Option<WorldgenProgress>,
+}
+
+impl LoadingScreen {
+    pub fn new(assets: &Assets, theme: Arc<Theme>) -> anyhow::Result<Self> {
+        let font = assets.get("font/Play-Regular.ttf")?;
+        let mut ui_store = UiStore::default();
+        ui_store.set_theme(theme);
+        Ok(Self {
+            ui_store,
+            font,
+            progress: None,
+        })
+    }
+
+    pub fn set_progress(&mut self, progress: WorldgenProgress) {
+        self.progress = Some(progress);
+    }
+
+    /// Renders the loading screen to the window.
+    pub fn render(&mut self, renderer: &mut Renderer, window: &Window) {
+        self.build_ui();
+        renderer.render_ui_only(window, &mut self.ui_store);
+    }
+
+    fn build_ui(&mut self) {
+        let (status, percent) = match &self.progress {
+            Some(progress) => (
+                format!("{}... {:.0}%", progress.stage, progress.percent),
+                progress.percent,
+            ),
+            None => ("Connecting...".to_owned(), 0.),
+        };
+        let filled = PANEL_WIDTH * (percent / 100.).max(0.).min(1.);
+
+        let ui = self.ui_store.get(
+            "loading",
+            Length::Percent(100.),
+            Length::Percent(100.),
+            Vec2::zero(),
+        );
+        let theme = Arc::clone(ui.theme());
+        let font = self.font.as_arc();
+
+        ui.build()
+            .begin(Container::column().with_style(|style| {
+                style.size.width = Dimension::Percent(1.);
+                style.size.height = Dimension::Percent(1.);
+                style.justify_content = JustifyContent::Center;
+                style.align_items = AlignItems::Center;
+            }))
+            .begin(Panel::new().with_style(|style| {
+                style.size.width = Dimension::Points(PANEL_WIDTH + theme.padding * 2.);
+            }))
+            .push(Text::new("Loading World", font).size(theme.heading_font_size))
+            .push(Text::new(&status, font))
+            .begin(Container::row())
+            .push(Rectangle::new(
+                vec2(filled, PROGRESS_BAR_HEIGHT),
+                theme.text_color,
+            ))
+            .push(Rectangle::new(
+                vec2(PANEL_WIDTH - filled, PROGRESS_BAR_HEIGHT),
+                Color::rgba(1., 1., 1., 0.2),
+            ))
+            .end()
+            .end()
+            .end();
+    }
+}
@@ -1,29 +1,28 @@
 #![feature(type_name_of_val, allocator_api, format_args_capture)]
 #![allow(dead_code)]
 
-use std::{alloc::System, sync::Arc, thread, time::Instant};
+use std::{alloc::System, mem, sync::Arc, time::Duration};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{env, path::PathBuf};
 
-use anyhow::{bail, Context};
+use anyhow::Context;
 use asset::{
     font::FontLoader, model::YamlModel, shader::SpirvLoader, texture::PngLoader, Assets, YamlLoader,
 };
-use bumpalo::Bump;
-use common::{entity::Vel, Orient, Pos, SystemExecutor};
-use conn::Connection;
+use common::SystemExecutor;
 use game::Game;
 use glam::Vec3A;
 use physics::Aabb;
-use protocol::{
-    bridge::{self, ToServer},
-    packets::client::ClientInfo,
-    packets::ClientPacket,
-    packets::ServerPacket,
-    Bridge, PROTOCOL_VERSION,
-};
+use platform::Instant;
 use renderer::Renderer;
-use server::Server;
-use simple_logger::SimpleLogger;
+#[cfg(not(target_arch = "wasm32"))]
+use server::command::Console;
+use session::{Login, LoginEvent, Session};
+use settings::Settings;
 use utils::TrackAllocator;
+use voltzui::Theme;
+#[cfg(not(target_arch = "wasm32"))]
+use winit::platform::run_return::EventLoopExtRunReturn;
 use winit::{
     dpi::LogicalSize,
     event::{Event, WindowEvent},
@@ -36,113 +35,326 @@ const PLAYER_BBOX: Aabb = Aabb {
     max: glam::const_vec3a!([0.5, 2., 0.5]),
 };
 
+/// The directory the singleplayer world is saved to.
+#[cfg(not(target_arch = "wasm32"))]
+const SAVE_DIR: &str = "world";
+
 mod asset;
 mod camera;
+mod chat;
+mod chunk_cache;
 mod conn;
+mod content;
+mod crosshair;
+mod death;
 mod debug;
+mod diagnostics;
+mod dialog;
 mod entity;
 mod event;
 mod game;
+mod health_bar;
+mod hotbar;
 mod input;
+mod interaction;
+mod loading;
+mod messages;
+mod meteor;
+mod platform;
 mod renderer;
+#[cfg(not(target_arch = "wasm32"))]
+mod resource_pack;
+mod session;
+mod settings;
+mod time;
 mod ui;
 mod update_server;
+mod weather;
+mod xp_bar;
 
 #[global_allocator]
 pub static ALLOCATOR: TrackAllocator<System> = TrackAllocator::new(System);
 
 pub struct Client {
     assets: Assets,
+    window: Arc<Window>,
+    renderer: Renderer,
+    theme: Arc<Theme>,
+    settings: Settings,
+    /// Reads commands for integrated servers from stdin.
+    #[cfg(not(target_arch = "wasm32"))]
+    console: Console,
+    /// The resource pack of the current server, mounted
+    /// over the packs in `VOLTZ_RESOURCE_PACKS`.
+    #[cfg(not(target_arch = "wasm32"))]
+    server_pack: Option<PathBuf>,
+
+    screen: Screen,
+    /// When the last frame ended.
+    last_frame: Instant,
+}
 
-    systems: SystemExecutor<Game>,
-
-    game: Game,
-
-    conn: Connection,
+/// What the client is doing.
+enum Screen {
+    LoggingIn(Login),
+    Playing(Session),
+    /// The client is about to exit.
+    Closed,
 }
 
 impl Client {
-    pub fn run(mut self, event_loop: EventLoop<()>) -> anyhow::Result<()> {
-        let mut previous = Instant::now();
+    /// Creates the client, initializing rendering to `window`.
+    pub async fn new(assets: Assets, window: Window) -> anyhow::Result<Self> {
+        let renderer = Renderer::new(&window, &assets)
+            .await
+            .context("failed to intiailize wgpu renderer")?;
+        let theme = Arc::new(ui::load_theme(&assets).context("failed to load UI theme")?);
+        Ok(Self {
+            assets,
+            window: Arc::new(window),
+            renderer,
+            theme,
+            settings: Settings::from_env(),
+            #[cfg(not(target_arch = "wasm32"))]
+            console: Console::stdin(),
+            #[cfg(not(target_arch = "wasm32"))]
+            server_pack: None,
+            screen: Screen::Closed,
+            last_frame: Instant::now(),
+        })
+    }
+
+    /// Runs the client until the window is closed or the player
+    /// leaves the world.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run(mut self, event_loop: &mut EventLoop<()>) -> anyhow::Result<()> {
+        let mut result = Ok(());
+        event_loop.run_return(|event, _, control_flow| {
+            if let Err(e) = self.handle_event(event, control_flow) {
+                result = Err(e);
+            }
+        });
+        result
+    }
+
+    /// Runs the client until the window is closed or the player
+    /// leaves the world. The browser owns the event loop, so
+    /// this never returns.
+    #[cfg(target_arch = "wasm32")]
+    pub fn run(mut self, event_loop: EventLoop<()>) -> ! {
         event_loop.run(move |event, _, control_flow| {
-            *control_flow = ControlFlow::Poll;
-            match event {
-                Event::WindowEvent {
-                    event: WindowEvent::CloseRequested,
-                    ..
-                } => {
-                    *control_flow = ControlFlow::Exit;
+            if let Err(e) = self.handle_event(event, control_flow) {
+                log::error!("{:?}", e);
+            }
+        })
+    }
+
+    /// Handles an event of the event loop. A frame
+    /// failing leaves the world and returns the error.
+    fn handle_event(
+        &mut self,
+        event: Event<'_, ()>,
+        control_flow: &mut ControlFlow,
+    ) -> anyhow::Result<()> {
+        *control_flow = ControlFlow::Poll;
+        let mut result = Ok(());
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => self.leave(),
+            Event::MainEventsCleared => {
+                if let Err(e) = self.frame(self.last_frame.elapsed()) {
+                    result = Err(e);
+                    self.leave();
+                }
+                self.last_frame = Instant::now();
+            }
+            Event::WindowEvent { event, .. } => {
+                if let Screen::Playing(session) = &mut self.screen {
+                    input::handle_event(&event, session.game_mut());
                 }
-                Event::MainEventsCleared => {
-                    self.tick();
-                    let elapsed = previous.elapsed();
-                    self.game.set_dt(elapsed.as_secs_f32());
+            }
+            _ => (),
+        }
+        if let Screen::Closed = self.screen {
+            *control_flow = ControlFlow::Exit;
+        }
+        result
+    }
 
-                    if elapsed.as_secs_f64() >= (1. / 60.) {
-                        log::warn!("Frame took too long: {:?}", elapsed);
-                    }
+    /// Starts logging in to the singleplayer world,
+    /// leaving the current world if there is one.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn play_singleplayer(&mut self) -> anyhow::Result<()> {
+        self.leave();
+        self.mount_server_pack(None)?;
+        let login = Login::singleplayer(
+            &self.assets,
+            &self.renderer,
+            Arc::clone(&self.theme),
+            PathBuf::from(SAVE_DIR),
+            self.console.clone(),
+            self.settings,
+        )?;
+        self.screen = Screen::LoggingIn(login);
+        Ok(())
+    }
 
-                    previous = Instant::now();
+    /// Reloads the assets with the resource pack in `pack` mounted over
+    /// the local ones, or with none if `None`, then rebuilds the block
+    /// textures and models. Does nothing if `pack` is already mounted.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn mount_server_pack(&mut self, pack: Option<PathBuf>) -> anyhow::Result<()> {
+        if pack == self.server_pack {
+            return Ok(());
+        }
+        let mut roots = asset_roots();
+        roots.extend(pack.clone());
+        let mut assets = asset_loaders();
+        assets
+            .load_dirs(&roots)
+            .context("failed to load the server's resource pack")?;
+        content::validate(&assets)
+            .into_result()
+            .context("the server's resource pack is invalid")?;
+        self.renderer.reload_assets(&assets)?;
+        self.assets = assets;
+        self.server_pack = pack;
+        Ok(())
+    }
 
-                    self.game.window_mut().set_cursor_visible(false);
-                    if let Err(e) = self.game.window_mut().set_cursor_grab(true) {
-                        log::error!("Failed to grab cursor: {:?}", e);
+    /// Runs a frame. The previous frame took `previous`.
+    fn frame(&mut self, previous: Duration) -> anyhow::Result<()> {
+        match mem::replace(&mut self.screen, Screen::Closed) {
+            Screen::LoggingIn(mut login) => match login.poll() {
+                #[cfg(not(target_arch = "wasm32"))]
+                Ok(Some(LoginEvent::MountPack(pack))) => {
+                    if let Err(e) = self.mount_server_pack(Some(pack)) {
+                        login.abort();
+                        return Err(e);
                     }
+                    login.pack_mounted();
+                    self.screen = Screen::LoggingIn(login);
+                }
+                Ok(Some(LoginEvent::Join(join_game))) => {
+                    let session = login.into_session(
+                        join_game,
+                        &self.assets,
+                        &mut self.renderer,
+                        Arc::clone(&self.window),
+                        Arc::clone(&self.theme),
+                    )?;
+                    self.screen = Screen::Playing(session);
+                }
+                Ok(None) => {
+                    login.render(&mut self.renderer, &self.window);
+                    self.screen = Screen::LoggingIn(login);
+                }
+                Err(e) => {
+                    login.abort();
+                    return Err(e.context("failed to log in"));
+                }
+            },
+            Screen::Playing(mut session) => {
+                session.end_frame(previous);
+                session.frame(&mut self.renderer);
+                if session.game().should_close() {
+                    // There is no main menu yet, so leaving the world quits.
+                    session.end(&mut self.renderer);
+                } else {
+                    self.screen = Screen::Playing(session);
                 }
-                Event::WindowEvent { event, .. } => input::handle_event(&event, &mut self.game),
-                _ => (),
             }
-        });
+            Screen::Closed => {}
+        }
+        Ok(())
     }
 
-    fn tick(&mut self) {
-        self.game.events().set_system(0);
-        self.conn.handle_packets(&mut self.game);
-
-        self.systems.run(&mut self.game, |game, system| {
-            game.events().set_system(system + 1)
-        });
-
-        self.game.bump_mut().reset();
+    /// Leaves the world or stops logging in, if the client is doing either.
+    fn leave(&mut self) {
+        match mem::replace(&mut self.screen, Screen::Closed) {
+            Screen::LoggingIn(login) => login.abort(),
+            Screen::Playing(session) => session.end(&mut self.renderer),
+            Screen::Closed => {}
+        }
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
 fn main() -> anyhow::Result<()> {
-    SimpleLogger::new()
-        .with_level(log::LevelFilter::Debug)
-        .init()?;
-    let assets = load_assets()?;
-    let (window, event_loop) = init_window()?;
-    let renderer = Renderer::new(&window, &assets).context("failed to intiailize wgpu renderer")?;
-
-    let bridge = launch_server(&renderer)?;
-    let (pos, orient, vel) = log_in(&bridge).context("failed to connect to integrated server")?;
-    let conn = Connection::new(bridge.clone());
-    let mut game = Game::new(bridge, (pos, orient, vel, PLAYER_BBOX), window, Bump::new());
-
-    let mut systems = setup(&assets)?;
-    renderer.setup(&mut systems, &mut game);
+    platform::init_logging()?;
+    let mut assets = asset_loaders();
+    assets
+        .load_dirs(&asset_roots())
+        .context("failed to load assets")?;
+    content::validate(&assets).into_result()?;
+    let (window, mut event_loop) = init_window()?;
+
+    let mut client = futures_executor::block_on(Client::new(assets, window))?;
+    client.play_singleplayer()?;
+    client.run(&mut event_loop)
+}
 
-    let client = Client {
-        assets,
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    if let Err(e) = platform::init_logging() {
+        panic!("failed to initialize logging: {:?}", e);
+    }
+    wasm_bindgen_futures::spawn_local(async {
+        if let Err(e) = run_web().await {
+            log::error!("{:?}", e);
+        }
+    });
+}
 
-        game,
-        conn,
+/// Starts the client in a browser, adding its canvas to the page.
+#[cfg(target_arch = "wasm32")]
+async fn run_web() -> anyhow::Result<()> {
+    use anyhow::anyhow;
+    use winit::platform::web::WindowExtWebSys;
 
-        systems,
-    };
+    let mut assets = asset_loaders();
+    assets
+        .load_url("assets")
+        .await
+        .context("failed to load assets")?;
+    content::validate(&assets).into_result()?;
+    let (window, event_loop) = init_window()?;
+    web_sys::window()
+        .and_then(|page| page.document())
+        .and_then(|document| document.body())
+        .and_then(|body| body.append_child(&window.canvas()).ok())
+        .ok_or_else(|| anyhow!("failed to add the canvas to the page"))?;
+
+    let client = Client::new(assets, window).await?;
+    // There is no network transport yet, so there is nothing to join.
+    log::warn!("Web builds can't host worlds; singleplayer requires a native build");
     client.run(event_loop)
 }
 
-fn load_assets() -> anyhow::Result<Assets> {
+/// Returns the asset directory followed by the resource packs listed in
+/// `VOLTZ_RESOURCE_PACKS`, which is formatted like `PATH`. Later packs
+/// override earlier ones.
+#[cfg(not(target_arch = "wasm32"))]
+fn asset_roots() -> Vec<PathBuf> {
+    let mut roots = vec![PathBuf::from("assets")];
+    if let Some(packs) = env::var_os("VOLTZ_RESOURCE_PACKS") {
+        roots.extend(env::split_paths(&packs).filter(|pack| !pack.as_os_str().is_empty()));
+    }
+    roots
+}
+
+/// Creates an `Assets` with the client's loaders registered.
+fn asset_loaders() -> Assets {
     let mut assets = Assets::new();
     assets
         .add_loader("YamlModel", YamlLoader::<YamlModel>::new())
         .add_loader("Png", PngLoader::new())
         .add_loader("Spirv", SpirvLoader::new())
-        .add_loader("Font", FontLoader::new());
-    assets.load_dir("assets").context("failed to load assets")?;
-    Ok(assets)
+        .add_loader("Font", FontLoader::new())
+        .add_loader("YamlTheme", YamlLoader::<Theme>::new());
+    assets
 }
 
 fn init_window() -> anyhow::Result<(Window, EventLoop<()>)> {
@@ -159,65 +371,27 @@ fn init_window() -> anyhow::Result<(Window, EventLoop<()>)> {
     Ok((window, event_loop))
 }
 
-fn launch_server(renderer: &Renderer) -> anyhow::Result<Bridge<ToServer>> {
-    let (client_bridge, server_bridge) = bridge::singleplayer();
-
-    let conn = server::Connection::new(server_bridge);
-
-    let device = Arc::clone(renderer.device_arc());
-    let queue = Arc::clone(renderer.queue_arc());
-
-    thread::Builder::new()
-        .name("integrated-server".to_owned())
-        .spawn(move || {
-            let mut server = Server::new(vec![conn], &device, &queue);
-            server.run();
-        })?;
-
-    Ok(client_bridge)
-}
-
-fn log_in(bridge: &Bridge<ToServer>) -> anyhow::Result<(Pos, Orient, Vel)> {
-    log::info!("Connecting to server");
-    bridge.send(ClientPacket::ClientInfo(ClientInfo {
-        protocol_version: PROTOCOL_VERSION,
-        implementation: format!("voltz-client:{}", env!("CARGO_PKG_VERSION")),
-        username: "caelunshun".to_owned(),
-    }));
-
-    let server_info = match bridge.wait_received() {
-        Some(ServerPacket::ServerInfo(info)) => info,
-        Some(_) => bail!("invalid packet received during login state"),
-        None => bail!("disconnected"),
-    };
-
-    log::info!(
-        "Connected to server '{}' implementing protocol {}.",
-        server_info.implementation,
-        server_info.protocol_version
-    );
-
-    let join_game = match bridge.wait_received() {
-        Some(ServerPacket::JoinGame(join_game)) => join_game,
-        Some(_) => bail!("invalid packet received during login state"),
-        None => bail!("disconnected"),
-    };
-
-    log::info!("Received JoinGame: {:?}", join_game);
-    Ok((
-        Pos(join_game.pos),
-        Orient(join_game.orient),
-        Vel(join_game.vel),
-    ))
-}
-
+/// Creates the systems of a session.
 fn setup(assets: &Assets) -> anyhow::Result<SystemExecutor<Game>> {
     let mut systems = SystemExecutor::new();
 
     camera::setup(&mut systems);
+    interaction::setup(&mut systems);
+    crosshair::setup(&mut systems);
     entity::setup(&mut systems);
+    time::setup(&mut systems);
+    weather::setup(&mut systems);
+    meteor::setup(&mut systems);
     debug::setup(&mut systems, assets)?;
+    dialog::setup(&mut systems, assets)?;
+    death::setup(&mut systems, assets)?;
+    messages::setup(&mut systems, assets)?;
+    chat::setup(&mut systems, assets)?;
+    xp_bar::setup(&mut systems, assets)?;
+    hotbar::setup(&mut systems, assets)?;
+    health_bar::setup(&mut systems, assets)?;
     update_server::setup(&mut systems);
+    settings::setup(&mut systems);
 
     Ok(systems)
 }
@@ -0,0 +1,73 @@
+//! Messages sent by the server, displayed in
+//! the corner of the screen for a few seconds.
+
+use std::time::Duration;
+
+use common::{System, SystemExecutor};
+use fontdue::Font;
+use glam::vec2;
+use voltzui::widgets::Text;
+
+use crate::{
+    asset::{Asset, Assets},
+    event::MessageReceived,
+    game::Game,
+    platform::Instant,
+    ui::Length,
+};
+
+/// How long a message is displayed.
+const MESSAGE_DURATION: Duration = Duration::from_secs(10);
+/// The maximum number of messages displayed at once.
+/// Older messages are hidden first.
+const MAX_MESSAGES: usize = 5;
+
+pub fn setup(systems: &mut SystemExecutor<Game>, assets: &Assets) -> anyhow::Result<()> {
+    let font = assets.get("font/Play-Regular.ttf")?;
+    systems.add(MessageSystem {
+        messages: Vec::new(),
+        font,
+    });
+    Ok(())
+}
+
+struct MessageSystem {
+    /// Displayed messages and when they were received, oldest first.
+    messages: Vec<(String, Instant)>,
+    font: Asset<Font>,
+}
+
+impl System<Game> for MessageSystem {
+    fn run(&mut self, game: &mut Game) {
+        for event in game.events().iter::<MessageReceived>() {
+            log::info!("[Server] {}", event.message);
+            self.messages.push((event.message.clone(), Instant::now()));
+        }
+        self.messages
+            .retain(|(_, received)| received.elapsed() < MESSAGE_DURATION);
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.drain(..self.messages.len() - MAX_MESSAGES);
+        }
+
+        if self.messages.is_empty() {
+            return;
+        }
+        let text = self
+            .messages
+            .iter()
+            .map(|(message, _)| message.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        // Messages are shown in the bottom left, clear of the debug screen.
+        let window = game.window();
+        let window_size = window.inner_size().to_logical::<f32>(window.scale_factor());
+        let mut ui_store = game.ui_store();
+        let ui = ui_store.get(
+            "messages",
+            Length::Percent(50.),
+            Length::Percent(30.),
+            vec2(10., window_size.height * 0.7),
+        );
+        ui.build().push(Text::new(&text, self.font.as_arc()));
+    }
+}
@@ -0,0 +1,84 @@
+//! Meteor showers, started and stopped by the server
+//! with the `MeteorShower` packet.
+//!
+//! Meteors are purely visual: they streak across the sky
+//! high above the player and burn up before reaching the ground.
+//! The renderer draws each meteor with a trail and lights up
+//! the sky while meteors are visible.
+
+use std::f32::consts::PI;
+
+use common::{System, SystemExecutor};
+use glam::{vec3a, Vec3A};
+use rand::Rng;
+
+use crate::game::Game;
+
+/// Meteors spawned per second during a shower.
+const SPAWN_RATE: f32 = 1.5;
+/// The range of horizontal distances from
+/// the player at which meteors spawn.
+const MIN_DISTANCE: f32 = 40.;
+const MAX_DISTANCE: f32 = 120.;
+/// The range of heights above the player at which meteors spawn.
+const MIN_HEIGHT: f32 = 80.;
+const MAX_HEIGHT: f32 = 120.;
+const HORIZONTAL_SPEED: f32 = 50.;
+const VERTICAL_SPEED: f32 = 25.;
+/// How many seconds a meteor is visible before burning up.
+pub const LIFETIME: f32 = 1.5;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Meteor {
+    pub pos: Vec3A,
+    pub vel: Vec3A,
+    /// Seconds since the meteor spawned.
+    pub age: f32,
+}
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(MeteorSystem { spawn_debt: 0. });
+}
+
+/// System to spawn and move meteors.
+struct MeteorSystem {
+    /// Meteors left over from previous frames,
+    /// as spawn counts are fractional.
+    spawn_debt: f32,
+}
+
+impl System<Game> for MeteorSystem {
+    fn run(&mut self, game: &mut Game) {
+        let dt = game.dt();
+        if game.meteor_shower {
+            self.spawn_debt += SPAWN_RATE * dt;
+            while self.spawn_debt >= 1. {
+                self.spawn_debt -= 1.;
+                let player_pos = game.player_pos();
+                let meteor = spawn_meteor(&mut *game.rng(), player_pos);
+                game.meteors.push(meteor);
+            }
+        }
+
+        game.meteors.retain(|meteor| meteor.age < LIFETIME);
+        for meteor in &mut game.meteors {
+            meteor.pos += meteor.vel * dt;
+            meteor.age += dt;
+        }
+    }
+}
+
+fn spawn_meteor(rng: &mut impl Rng, player_pos: Vec3A) -> Meteor {
+    let angle = rng.gen_range(0., 2. * PI);
+    let distance = rng.gen_range(MIN_DISTANCE, MAX_DISTANCE);
+    let height = rng.gen_range(MIN_HEIGHT, MAX_HEIGHT);
+    let pos = player_pos + vec3a(angle.cos() * distance, height, angle.sin() * distance);
+
+    let direction = rng.gen_range(0., 2. * PI);
+    let vel = vec3a(
+        direction.cos() * HORIZONTAL_SPEED,
+        -VERTICAL_SPEED,
+        direction.sin() * HORIZONTAL_SPEED,
+    );
+    Meteor { pos, vel, age: 0. }
+}
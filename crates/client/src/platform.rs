@@ -0,0 +1,73 @@
+//! Differences between native and web builds.
+//!
+//! The client builds for `wasm32-unknown-unknown`, running in the
+//! browser on WebGPU. Browsers don't give it threads, a file system,
+//! or `std::time`, so code needing them goes through this module.
+//!
+//! Web builds can't host worlds: the integrated server only
+//! runs in native builds.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{env, path::PathBuf};
+
+pub use instant::Instant;
+
+/// Returns the directory holding the client's caches:
+/// `VOLTZ_CACHE_DIR`, or `cache` if it is unset.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn cache_dir() -> PathBuf {
+    env::var_os("VOLTZ_CACHE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("cache"))
+}
+
+/// Runs `task` in the background. Native builds run it on the
+/// rayon thread pool. Web builds have no threads, so it runs
+/// before this returns.
+pub fn spawn(task: impl FnOnce() + Send + 'static) {
+    #[cfg(not(target_arch = "wasm32"))]
+    rayon::spawn(task);
+    #[cfg(target_arch = "wasm32")]
+    task();
+}
+
+/// Sends log messages to standard output, or to
+/// the browser console in web builds.
+pub fn init_logging() -> anyhow::Result<()> {
+    #[cfg(not(target_arch = "wasm32"))]
+    simple_logger::SimpleLogger::new()
+        .with_level(log::LevelFilter::Debug)
+        .init()?;
+    #[cfg(target_arch = "wasm32")]
+    {
+        std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+        console_log::init_with_level(log::Level::Debug)?;
+    }
+    Ok(())
+}
+
+/// Fetches the file at `url`, relative to the page.
+#[cfg(target_arch = "wasm32")]
+pub async fn fetch(url: &str) -> anyhow::Result<Vec<u8>> {
+    use anyhow::anyhow;
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen_futures::JsFuture;
+
+    let window = web_sys::window().ok_or_else(|| anyhow!("no browser window"))?;
+    let response = JsFuture::from(window.fetch_with_str(url))
+        .await
+        .map_err(|e| anyhow!("failed to fetch '{}': {:?}", url, e))?;
+    let response: web_sys::Response = response
+        .dyn_into()
+        .map_err(|_| anyhow!("fetch of '{}' did not return a response", url))?;
+    if !response.ok() {
+        anyhow::bail!("failed to fetch '{}': status {}", url, response.status());
+    }
+    let buffer = response
+        .array_buffer()
+        .map_err(|e| anyhow!("failed to read '{}': {:?}", url, e))?;
+    let buffer = JsFuture::from(buffer)
+        .await
+        .map_err(|e| anyhow!("failed to read '{}': {:?}", url, e))?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
@@ -1,23 +1,211 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::mem;
 use std::sync::Arc;
 
 use anyhow::{anyhow, Context};
-use common::{System, SystemExecutor};
-use futures_executor::block_on;
 use present::Presenter;
-use winit::window::Window;
+use winit::{dpi::PhysicalSize, window::Window};
 
-use crate::{asset::Assets, game::Game};
+use crate::{asset::Assets, game::Game, platform::Instant, ui::UiStore};
 
-use self::{chunk::ChunkRenderer, ui::UiRenderer};
+#[cfg(not(target_arch = "wasm32"))]
+use self::capture::FrameCapture;
+use self::{
+    chunk::ChunkRenderer,
+    sky::{Sky, SkyRenderer},
+    ui::UiRenderer,
+};
 
+pub use self::chunk::validate_assets;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod capture;
 mod chunk;
 mod present;
+mod sky;
 mod ui;
 mod utils;
 
 const SC_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
 const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24Plus;
+/// The number of MSAA samples used on capable adapters.
 const SAMPLE_COUNT: u32 = 2;
+/// The size of the largest draw parameters passed to
+/// shaders: the transform and camera matrices of chunks.
+const MAX_DRAW_PARAMS_SIZE: u32 = 144;
+const CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.1,
+    g: 0.2,
+    b: 0.4,
+    a: 1.0,
+};
+
+/// Optional GPU capabilities. The renderer falls back to
+/// slower or plainer rendering on adapters lacking them.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// Whether draw parameters are passed in push constants rather
+    /// than uniform buffers. See [`utils::DrawParams`].
+    pub push_constants: bool,
+    /// The number of MSAA samples of the 3D pass. 1 disables MSAA.
+    pub sample_count: u32,
+    /// Whether chunks are drawn indirectly, reading their
+    /// transforms from a storage buffer. Each chunk's draw starts at
+    /// the instance with its transform, so this needs indirect draws
+    /// with a non-zero first instance.
+    pub indirect_chunks: bool,
+    /// Whether a batch of indirect draws takes a single call.
+    pub multi_draw_indirect: bool,
+    /// The limits to request for the device.
+    pub limits: wgpu::Limits,
+}
+
+impl Capabilities {
+    /// Determines the capabilities to use on an adapter with the given
+    /// `features` and `limits`. `software` is set for CPU renderers, and
+    /// `indirect_first_instance` for adapters whose indirect draws may
+    /// start at a non-zero instance.
+    pub fn probe(
+        features: wgpu::Features,
+        limits: &wgpu::Limits,
+        software: bool,
+        indirect_first_instance: bool,
+    ) -> Self {
+        let push_constants = features.contains(wgpu::Features::PUSH_CONSTANTS)
+            && limits.max_push_constant_size >= MAX_DRAW_PARAMS_SIZE;
+
+        // Requesting more than the adapter supports fails,
+        // so weak adapters get their own limits.
+        let defaults = wgpu::Limits::default();
+        let mut limits = min_limits(&defaults, limits);
+        let low_end = limits != defaults;
+        if push_constants {
+            limits.max_push_constant_size = MAX_DRAW_PARAMS_SIZE;
+        }
+
+        // MSAA is too expensive for software renderers and
+        // adapters which can't even meet the default limits.
+        let sample_count = if low_end || software { 1 } else { SAMPLE_COUNT };
+
+        let indirect_chunks = indirect_first_instance
+            && limits.max_storage_buffers_per_shader_stage >= 1
+            && limits.max_bind_groups >= 2;
+        let multi_draw_indirect =
+            indirect_chunks && features.contains(wgpu::Features::MULTI_DRAW_INDIRECT);
+
+        Self {
+            push_constants,
+            sample_count,
+            indirect_chunks,
+            multi_draw_indirect,
+            limits,
+        }
+    }
+
+    /// Returns the features to request for the device.
+    pub fn features(&self) -> wgpu::Features {
+        let mut features = wgpu::Features::empty();
+        if self.push_constants {
+            features |= wgpu::Features::PUSH_CONSTANTS;
+        }
+        if self.multi_draw_indirect {
+            features |= wgpu::Features::MULTI_DRAW_INDIRECT;
+        }
+        features
+    }
+}
+
+/// Logs the selected adapter and returns whether it renders on
+/// the CPU. Browsers don't describe their adapters, so web builds
+/// assume hardware rendering.
+fn is_software_adapter(adapter: &wgpu::Adapter) -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let info = adapter.get_info();
+        log::info!("Selected adapter: {:#?}", info);
+        info.device_type == wgpu::DeviceType::Cpu
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = adapter;
+        log::info!("Selected a WebGPU adapter");
+        false
+    }
+}
+
+/// Returns whether indirect draws on `adapter` may start at a non-zero
+/// instance. Vulkan and WebGPU make this an optional feature, which
+/// wgpu doesn't report, so only backends which always allow it count.
+fn supports_indirect_first_instance(adapter: &wgpu::Adapter) -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        matches!(
+            adapter.get_info().backend,
+            wgpu::Backend::Metal | wgpu::Backend::Dx12
+        )
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = adapter;
+        false
+    }
+}
+
+/// Returns the name and backend of `adapter`
+/// to show on the debug screen.
+fn describe_adapter(adapter: &wgpu::Adapter) -> (String, &'static str) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let info = adapter.get_info();
+        let backend = match info.backend {
+            wgpu::Backend::Empty => "Empty",
+            wgpu::Backend::Vulkan => "Vulkan",
+            wgpu::Backend::Metal => "Metal",
+            wgpu::Backend::Dx12 => "DirectX 12",
+            wgpu::Backend::Dx11 => "DirectX 11",
+            wgpu::Backend::Gl => "OpenGL",
+            wgpu::Backend::BrowserWebGpu => "WebGPU",
+        };
+        (info.name, backend)
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = adapter;
+        ("browser".to_owned(), "WebGPU")
+    }
+}
+
+/// Returns the lower of each of the limits in `a` and `b`.
+fn min_limits(a: &wgpu::Limits, b: &wgpu::Limits) -> wgpu::Limits {
+    wgpu::Limits {
+        max_bind_groups: a.max_bind_groups.min(b.max_bind_groups),
+        max_dynamic_uniform_buffers_per_pipeline_layout: a
+            .max_dynamic_uniform_buffers_per_pipeline_layout
+            .min(b.max_dynamic_uniform_buffers_per_pipeline_layout),
+        max_dynamic_storage_buffers_per_pipeline_layout: a
+            .max_dynamic_storage_buffers_per_pipeline_layout
+            .min(b.max_dynamic_storage_buffers_per_pipeline_layout),
+        max_sampled_textures_per_shader_stage: a
+            .max_sampled_textures_per_shader_stage
+            .min(b.max_sampled_textures_per_shader_stage),
+        max_samplers_per_shader_stage: a
+            .max_samplers_per_shader_stage
+            .min(b.max_samplers_per_shader_stage),
+        max_storage_buffers_per_shader_stage: a
+            .max_storage_buffers_per_shader_stage
+            .min(b.max_storage_buffers_per_shader_stage),
+        max_storage_textures_per_shader_stage: a
+            .max_storage_textures_per_shader_stage
+            .min(b.max_storage_textures_per_shader_stage),
+        max_uniform_buffers_per_shader_stage: a
+            .max_uniform_buffers_per_shader_stage
+            .min(b.max_uniform_buffers_per_shader_stage),
+        max_uniform_buffer_binding_size: a
+            .max_uniform_buffer_binding_size
+            .min(b.max_uniform_buffer_binding_size),
+        max_push_constant_size: a.max_push_constant_size.min(b.max_push_constant_size),
+    }
+}
 
 #[derive(Debug)]
 pub struct Resources {
@@ -25,6 +213,7 @@ pub struct Resources {
     device: Arc<wgpu::Device>,
     queue: Arc<wgpu::Queue>,
     surface: wgpu::Surface,
+    capabilities: Capabilities,
 }
 
 impl Resources {
@@ -32,6 +221,10 @@ impl Resources {
         &self.adapter
     }
 
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
     pub fn device(&self) -> &wgpu::Device {
         &self.device
     }
@@ -47,14 +240,24 @@ impl Resources {
 
 pub struct Renderer {
     resources: Arc<Resources>,
+    sky_renderer: SkyRenderer,
     chunk_renderer: ChunkRenderer,
     ui_renderer: UiRenderer,
     presenter: Presenter,
+    /// Present if RenderDoc captures are enabled.
+    #[cfg(not(target_arch = "wasm32"))]
+    capture: Option<FrameCapture>,
 }
 
 impl Renderer {
-    pub fn new(window: &Window, assets: &Assets) -> anyhow::Result<Self> {
+    /// Initializes rendering to `window`. Waits for the adapter
+    /// and device, which in web builds requires yielding to the browser.
+    pub async fn new(window: &Window, assets: &Assets) -> anyhow::Result<Self> {
+        // RenderDoc must be connected before the device is created.
+        #[cfg(not(target_arch = "wasm32"))]
+        let capture = FrameCapture::from_env();
         let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+        #[cfg(not(target_arch = "wasm32"))]
         log::info!(
             "Available adapters: {:#?}",
             instance
@@ -62,29 +265,44 @@ impl Renderer {
                 .map(|adapter| adapter.get_info())
                 .collect::<Vec<_>>()
         );
-        let surface = block_on(async {
-            // SAFETY: a wgpu surface can be created with a winit window.
-            unsafe { instance.create_surface(window) }
-        });
-        let adapter = block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            compatible_surface: Some(&surface),
-        }))
-        .ok_or_else(|| anyhow!("failed to select a suitable adapter"))?;
-        log::info!("Selected adapter: {:#?}", adapter.get_info());
-
-        let (device, queue) = block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                features: wgpu::Features::PUSH_CONSTANTS,
-                limits: wgpu::Limits {
-                    max_push_constant_size: 256,
-                    ..Default::default()
+        // SAFETY: a wgpu surface can be created with a winit window.
+        let surface = unsafe { instance.create_surface(window) };
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                compatible_surface: Some(&surface),
+            })
+            .await
+            .ok_or_else(|| anyhow!("failed to select a suitable adapter"))?;
+        let software = is_software_adapter(&adapter);
+
+        let capabilities = Capabilities::probe(
+            adapter.features(),
+            &adapter.limits(),
+            software,
+            supports_indirect_first_instance(&adapter),
+        );
+        if !capabilities.push_constants {
+            log::warn!(
+                "The adapter lacks push constants; passing draw parameters in uniform buffers"
+            );
+        }
+        if capabilities.sample_count < SAMPLE_COUNT {
+            log::warn!("Disabling MSAA on a low-end adapter");
+        }
+        log::info!("Renderer capabilities: {:#?}", capabilities);
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: capabilities.features(),
+                    limits: capabilities.limits.clone(),
+                    shader_validation: true,
                 },
-                shader_validation: true,
-            },
-            None,
-        ))
-        .context("failed to create device")?;
+                None,
+            )
+            .await
+            .context("failed to create device")?;
 
         log::info!("Device limits: {:#?}", device.limits());
 
@@ -93,15 +311,11 @@ impl Renderer {
             device: Arc::new(device),
             queue: Arc::new(queue),
             surface,
+            capabilities,
         });
 
         let size = window.inner_size();
-        let presenter = Presenter::new(
-            resources.device(),
-            resources.surface(),
-            size.width,
-            size.height,
-        );
+        let presenter = Presenter::new(&resources, size.width, size.height);
 
         let mut init_encoder =
             resources
@@ -110,6 +324,8 @@ impl Renderer {
                     label: Some("init_encoder"),
                 });
 
+        let sky_renderer =
+            SkyRenderer::new(&resources, assets).context("failed to initialize sky renderer")?;
         let chunk_renderer = ChunkRenderer::new(&resources, assets, &mut init_encoder)
             .context("failed to initialize chunk renderer")?;
         let ui_renderer =
@@ -117,19 +333,53 @@ impl Renderer {
 
         resources.queue().submit(vec![init_encoder.finish()]);
 
+        // The browser polls the device in web builds.
+        #[cfg(not(target_arch = "wasm32"))]
         common::gpu::launch_poll_thread(&resources.device);
 
         Ok(Self {
             resources,
+            sky_renderer,
             chunk_renderer,
             ui_renderer,
             presenter,
+            #[cfg(not(target_arch = "wasm32"))]
+            capture,
         })
     }
 
-    pub fn setup(self, systems: &mut SystemExecutor<Game>, game: &mut Game) {
-        game.debug_data.adapter = Some(self.resources.adapter().get_info());
-        systems.add(self);
+    /// Prepares to render a newly joined world.
+    pub fn start_session(&mut self, game: &mut Game) {
+        game.debug_data.adapter = Some(describe_adapter(self.resources.adapter()));
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            game.debug_data.capture_available = self.capture.is_some();
+        }
+    }
+
+    /// Rebuilds the block textures and models from `assets`, such
+    /// as after mounting a server's resource pack. Call between sessions.
+    pub fn reload_assets(&mut self, assets: &Assets) -> anyhow::Result<()> {
+        let mut encoder =
+            self.resources
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("reload_encoder"),
+                });
+        self.chunk_renderer = ChunkRenderer::new(&self.resources, assets, &mut encoder)
+            .context("failed to initialize chunk renderer")?;
+        self.resources.queue().submit(vec![encoder.finish()]);
+        Ok(())
+    }
+
+    /// Drops the GPU state of the world that was left,
+    /// such as chunk meshes.
+    pub fn end_session(&mut self) {
+        self.chunk_renderer.clear_world();
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        self.resources.capabilities()
     }
 
     pub fn device_arc(&self) -> &Arc<wgpu::Device> {
@@ -140,27 +390,85 @@ impl Renderer {
         &self.resources.queue
     }
 
+    /// Renders a frame containing only the UIs in `ui_store`.
+    /// Used for screens displayed before the game starts.
+    pub fn render_ui_only(&mut self, window: &Window, ui_store: &mut UiStore) {
+        let size = window.inner_size();
+        self.resize_if_needed(size);
+
+        let mut uis = Vec::new();
+        ui_store.finish_frame(&mut uis);
+        self.ui_renderer.prepare_uis(&self.resources, size, uis);
+
+        let mut encoder =
+            self.resources
+                .device()
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("render_ui_only"),
+                });
+
+        let frame = self
+            .presenter
+            .swapchain()
+            .get_current_frame()
+            .expect("failed to get next output frame");
+
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: &frame.output.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(CLEAR_COLOR),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            self.ui_renderer.do_render(&self.resources, &mut pass);
+        }
+
+        self.resources.queue().submit(vec![encoder.finish()]);
+    }
+
+    fn resize_if_needed(&mut self, size: PhysicalSize<u32>) {
+        if size.width != self.presenter.width() || size.height != self.presenter.height() {
+            self.on_resize(size.width, size.height);
+        }
+    }
+
     fn on_resize(&mut self, new_width: u32, new_height: u32) {
-        self.presenter = Presenter::new(
-            self.resources.device(),
-            self.resources.surface(),
-            new_width,
-            new_height,
-        );
+        self.presenter = Presenter::new(&self.resources, new_width, new_height);
     }
 
-    /// Renders a frame.
-    fn render(&mut self, game: &mut Game) {
-        self.prep_render(game);
-        self.do_render(game);
+    /// Renders a frame of the game.
+    pub fn render(&mut self, game: &mut Game) {
+        self.resize_if_needed(game.window().inner_size());
+        let start = Instant::now();
+        let sky = Sky::new(game);
+        self.prep_render(game, &sky);
+        game.debug_data.render_timings.prepare = start.elapsed();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if mem::take(&mut game.debug_data.capture_requested) && self.capture.is_some() {
+            self.capture.as_mut().unwrap().start();
+            self.do_render(game, &sky);
+            // The frame was presented when `do_render` dropped it.
+            self.capture.as_mut().unwrap().end();
+            game.debug_data.captures += 1;
+            log::info!("Captured a frame with RenderDoc");
+            return;
+        }
+        self.do_render(game, &sky);
     }
 
-    fn prep_render(&mut self, game: &mut Game) {
-        self.chunk_renderer.prep_render(&self.resources, game);
+    fn prep_render(&mut self, game: &mut Game, sky: &Sky) {
+        self.sky_renderer.prep_render(&self.resources, game, sky);
+        self.chunk_renderer.prep_render(&self.resources, game, sky);
         self.ui_renderer.prep_render(&self.resources, game);
     }
 
-    fn do_render(&mut self, game: &mut Game) {
+    fn do_render(&mut self, game: &mut Game, sky: &Sky) {
         let mut encoder =
             self.resources
                 .device()
@@ -168,24 +476,27 @@ impl Renderer {
                     label: Some("render_frame"),
                 });
 
+        let start = Instant::now();
         let frame = self
             .presenter
             .swapchain()
             .get_current_frame()
             .expect("failed to get next output frame");
+        game.debug_data.render_timings.acquire = start.elapsed();
 
+        let start = Instant::now();
         {
+            // Without MSAA, the 3D pass draws to the frame directly.
+            let (attachment, resolve_target) = match self.presenter.sample_buffer() {
+                Some(sample_buffer) => (sample_buffer, Some(&frame.output.view)),
+                None => (&frame.output.view, None),
+            };
             let mut pass_3d = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                    attachment: self.presenter.sample_buffer(),
-                    resolve_target: Some(&frame.output.view),
+                    attachment,
+                    resolve_target,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.4,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(sky.color),
                         store: true,
                     },
                 }],
@@ -199,7 +510,37 @@ impl Renderer {
                 }),
             });
 
-            self.chunk_renderer.do_render(&mut pass_3d, game);
+            self.sky_renderer.do_render(&mut pass_3d);
+            self.chunk_renderer
+                .do_render(&self.resources, &mut pass_3d, game);
+        }
+        {
+            // Translucent blocks blend over everything drawn in the
+            // 3D pass, so they get a pass of their own after it.
+            let (attachment, resolve_target) = match self.presenter.sample_buffer() {
+                Some(sample_buffer) => (sample_buffer, Some(&frame.output.view)),
+                None => (&frame.output.view, None),
+            };
+            let mut pass_translucent = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachmentDescriptor {
+                    attachment: self.presenter.depth_buffer(),
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: false,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            self.chunk_renderer
+                .do_render_translucent(&self.resources, &mut pass_translucent, game);
         }
         {
             let mut pass_2d = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -213,20 +554,93 @@ impl Renderer {
                 }],
                 depth_stencil_attachment: None,
             });
-            self.ui_renderer.do_render(&mut pass_2d);
+            self.ui_renderer.do_render(&self.resources, &mut pass_2d);
         }
+        let commands = encoder.finish();
+        game.debug_data.render_timings.encode = start.elapsed();
 
-        self.resources.queue().submit(vec![encoder.finish()]);
+        let start = Instant::now();
+        self.resources.queue().submit(vec![commands]);
+        game.debug_data.render_timings.submit = start.elapsed();
     }
 }
 
-impl System<Game> for Renderer {
-    fn run(&mut self, game: &mut Game) {
-        let size = game.window().inner_size();
-        if size.width != self.presenter.width() || size.height != self.presenter.height() {
-            self.on_resize(size.width, size.height);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_back_without_push_constants() {
+        let limits = wgpu::Limits {
+            max_push_constant_size: 256,
+            ..Default::default()
+        };
+        let capabilities = Capabilities::probe(wgpu::Features::empty(), &limits, false, true);
+        assert!(!capabilities.push_constants);
+        assert_eq!(capabilities.features(), wgpu::Features::empty());
+        assert_eq!(capabilities.limits, wgpu::Limits::default());
+        assert_eq!(capabilities.sample_count, SAMPLE_COUNT);
+
+        let capabilities =
+            Capabilities::probe(wgpu::Features::PUSH_CONSTANTS, &limits, false, true);
+        assert!(capabilities.push_constants);
+        assert_eq!(
+            capabilities.limits.max_push_constant_size,
+            MAX_DRAW_PARAMS_SIZE
+        );
+    }
 
-        self.render(game);
+    #[test]
+    fn draws_chunks_indirectly_when_possible() {
+        let limits = wgpu::Limits::default();
+        let capabilities = Capabilities::probe(wgpu::Features::empty(), &limits, false, true);
+        assert!(capabilities.indirect_chunks);
+        assert!(!capabilities.multi_draw_indirect);
+
+        let features = wgpu::Features::MULTI_DRAW_INDIRECT;
+        let capabilities = Capabilities::probe(features, &limits, false, true);
+        assert!(capabilities.multi_draw_indirect);
+        assert_eq!(capabilities.features(), features);
+
+        let limits = wgpu::Limits {
+            max_storage_buffers_per_shader_stage: 0,
+            ..Default::default()
+        };
+        let capabilities =
+            Capabilities::probe(wgpu::Features::MULTI_DRAW_INDIRECT, &limits, false, true);
+        assert!(!capabilities.indirect_chunks);
+        assert!(!capabilities.multi_draw_indirect);
+
+        // Without a non-zero first instance, chunks
+        // can't find their transforms.
+        let capabilities = Capabilities::probe(
+            wgpu::Features::MULTI_DRAW_INDIRECT,
+            &wgpu::Limits::default(),
+            false,
+            false,
+        );
+        assert!(!capabilities.indirect_chunks);
+        assert!(!capabilities.multi_draw_indirect);
+        assert_eq!(capabilities.features(), wgpu::Features::empty());
+    }
+
+    #[test]
+    fn degrades_on_weak_adapters() {
+        let limits = wgpu::Limits {
+            max_bind_groups: 2,
+            max_uniform_buffer_binding_size: 4096,
+            ..Default::default()
+        };
+        let capabilities = Capabilities::probe(wgpu::Features::empty(), &limits, false, true);
+        assert_eq!(capabilities.limits, limits);
+        assert_eq!(capabilities.sample_count, 1);
+
+        let capabilities = Capabilities::probe(
+            wgpu::Features::empty(),
+            &wgpu::Limits::default(),
+            true,
+            true,
+        );
+        assert_eq!(capabilities.sample_count, 1);
     }
 }
@@ -0,0 +1,42 @@
+//! Single-frame RenderDoc captures, for debugging the render passes.
+//!
+//! Launch the client from RenderDoc with `VOLTZ_RENDERDOC` set, open
+//! the debug screen (F3), and press [`CAPTURE_KEY`](crate::debug::CAPTURE_KEY)
+//! to capture the next frame, including the chunk and UI passes.
+
+use std::{env, ptr};
+
+use renderdoc::{RenderDoc, V100};
+
+/// A connection to RenderDoc.
+pub struct FrameCapture {
+    renderdoc: RenderDoc<V100>,
+}
+
+impl FrameCapture {
+    /// Connects to RenderDoc if `VOLTZ_RENDERDOC` is set. Returns `None`
+    /// if it isn't or the client wasn't launched from RenderDoc.
+    pub fn from_env() -> Option<Self> {
+        env::var_os("VOLTZ_RENDERDOC")?;
+        match RenderDoc::new() {
+            Ok(renderdoc) => {
+                log::info!("RenderDoc frame captures are enabled");
+                Some(Self { renderdoc })
+            }
+            Err(e) => {
+                log::error!("Failed to connect to RenderDoc: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Starts capturing the GPU work of the current frame.
+    pub fn start(&mut self) {
+        self.renderdoc.start_frame_capture(ptr::null(), ptr::null());
+    }
+
+    /// Finishes the capture started by [`start`](Self::start).
+    pub fn end(&mut self) {
+        self.renderdoc.end_frame_capture(ptr::null(), ptr::null());
+    }
+}
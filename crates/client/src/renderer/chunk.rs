@@ -1,28 +1,47 @@
-use std::{mem::size_of, sync::Arc};
+use std::{iter, mem::size_of, num::NonZeroU64, ops::Range, sync::Arc};
 
 use ahash::{AHashMap, AHashSet};
 use anyhow::{bail, Context};
-use common::{chunk::CHUNK_DIM, ChunkPos, Pos};
-use glam::{vec4, Mat4, Vec4};
-use mesher::{ChunkMesher, GpuMesh};
+use arena::{ArenaMesh, MeshArena};
+use common::{
+    chunk::CHUNK_DIM,
+    edit::{Digging, DIG_STAGES},
+    entity::{player::Username, FallingBlock, ItemDrop, XpOrb},
+    item::{self, ItemId},
+    weather::Precipitation,
+    BlockId, BlockPos, ChunkPos, Pos,
+};
+use glam::{vec3, vec3a, vec4, Mat4, Vec3, Vec3A, Vec4};
+use indirect::IndirectDrawer;
+use mesher::{neighbor_positions, ChunkMesher, ChunkVertices, GpuMesh};
 
 use crate::{
-    asset::{shader::ShaderAsset, texture::TextureAsset, Assets},
-    event::{ChunkLoaded, ChunkUnloaded},
+    asset::{model::YamlModel, shader::ShaderAsset, texture::TextureAsset, Assets},
+    camera::Matrices,
+    content::Report,
+    event::{ChunkLoaded, ChunkModified, ChunkUnloaded},
     game::Game,
 };
 
 use self::{cull::Culler, mesher::RawVertex};
 
-use super::{utils::TextureArray, Resources, DEPTH_FORMAT, SAMPLE_COUNT, SC_FORMAT};
+use super::{
+    sky::Sky,
+    utils::{DrawParams, MipmapGenerator, TextureArray},
+    Resources, DEPTH_FORMAT, SC_FORMAT,
+};
 
+mod arena;
 mod cull;
+mod indirect;
 mod mesher;
 
 /// The chunk renderer. Responsible for
-/// 1) Maintaining a mesh for each chunk to be rendered.
+/// 1) Maintaining a mesh for each chunk to be rendered, suballocated
+/// from shared vertex buffers.
 /// 2) Maintaining a texture array containing block textures.
-/// 3) Rendering each visible chunk.
+/// 3) Rendering each visible chunk. Translucent blocks, like water,
+/// are drawn in a pass of their own after everything else.
 pub struct ChunkRenderer {
     block_textures: TextureArray,
     /// Maps block slug => texture index into `block_textures`.
@@ -33,11 +52,60 @@ pub struct ChunkRenderer {
     mesher: ChunkMesher,
     culler: Culler,
 
-    chunks: AHashMap<ChunkPos, GpuMesh>,
-    pending_meshes: AHashSet<ChunkPos>,
+    chunks: AHashMap<ChunkPos, ArenaMesh>,
+    arena: MeshArena,
+    /// Meshes of the translucent blocks in each chunk.
+    translucent: AHashMap<ChunkPos, GpuMesh>,
+    /// Chunks with translucent meshes visible in the current
+    /// frame, sorted from farthest to nearest.
+    visible_translucent: Vec<ChunkPos>,
+    /// Draws chunks indirectly if the adapter allows.
+    indirect: Option<IndirectDrawer>,
+    /// Outlines the block targeted by the player.
+    outline: GpuMesh,
+    /// Meshes of single blocks, used to draw falling blocks.
+    block_meshes: AHashMap<BlockId, Option<GpuMesh>>,
+    /// Rain, snow, and meteors, rebuilt each frame.
+    particles: Option<GpuMesh>,
+    rain_texture: u32,
+    snow_texture: u32,
+    meteor_texture: u32,
+    /// Placeholder models of other players, rebuilt each frame.
+    players: Option<GpuMesh>,
+    player_texture: u32,
+    /// Experience orbs, rebuilt each frame.
+    orbs: Option<GpuMesh>,
+    orb_texture: u32,
+    /// Dropped items, rebuilt each frame.
+    drops: Option<GpuMesh>,
+    /// The texture drawn on dropped items of each kind.
+    item_textures: AHashMap<ItemId, u32>,
+    /// Maps chunks being meshed to the version of their latest
+    /// meshing task. Meshes from older tasks are discarded.
+    pending_meshes: AHashMap<ChunkPos, u64>,
+    next_mesh_version: u64,
+
+    /// Blob shadows under entities, rebuilt each frame.
+    shadows: Option<GpuMesh>,
+    shadow_texture: u32,
+    /// Cracks on the blocks being dug, rebuilt each frame.
+    cracks: Option<GpuMesh>,
+    /// The texture of each crack stage.
+    crack_textures: Vec<u32>,
 
     pipeline: wgpu::RenderPipeline,
+    /// Draws flat, translucent meshes like shadows on top
+    /// of other geometry.
+    decal_pipeline: wgpu::RenderPipeline,
+    /// Draws translucent blocks with animated textures.
+    translucent_pipeline: wgpu::RenderPipeline,
     bind_group: wgpu::BindGroup,
+    /// The [`Lighting`] of the current frame.
+    lighting: wgpu::Buffer,
+    params: DrawParams,
+    /// Seconds of real time passed, wrapping around
+    /// after [`ANIMATION_PERIOD`]. Animates water.
+    animation_time: f32,
 }
 
 impl ChunkRenderer {
@@ -53,6 +121,30 @@ impl ChunkRenderer {
             block_texture_indexes.get(texture_name).copied()
         })
         .context("failed to initialize chunk mesher")?;
+        let texture = |name: &str| {
+            block_texture_indexes
+                .get(name)
+                .copied()
+                .with_context(|| format!("missing block texture '{}'", name))
+        };
+        let outline = mesher.outline_mesh(texture(OUTLINE_TEXTURE)?);
+        let rain_texture = texture(RAIN_TEXTURE)?;
+        let snow_texture = texture(SNOW_TEXTURE)?;
+        let meteor_texture = texture(METEOR_TEXTURE)?;
+        let shadow_texture = texture(SHADOW_TEXTURE)?;
+        let crack_textures = (0..DIG_STAGES)
+            .map(|stage| texture(&crack_texture(stage)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let player_texture = texture(PLAYER_TEXTURE)?;
+        let orb_texture = texture(ORB_TEXTURE)?;
+        let unknown_texture = texture(UNKNOWN_TEXTURE)?;
+        let mut item_textures = AHashMap::new();
+        for item in item::all_items() {
+            let texture = item_texture(assets, item)
+                .and_then(|name| block_texture_indexes.get(&name).copied())
+                .unwrap_or(unknown_texture);
+            item_textures.insert(item, texture);
+        }
 
         let block_sampler = resources.device().create_sampler(&wgpu::SamplerDescriptor {
             label: Some("block_sampler"),
@@ -87,23 +179,23 @@ impl ChunkRenderer {
                             ty: wgpu::BindingType::Sampler { comparison: false },
                             count: None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStage::FRAGMENT,
+                            ty: wgpu::BindingType::UniformBuffer {
+                                dynamic: false,
+                                min_binding_size: NonZeroU64::new(size_of::<Lighting>() as u64),
+                            },
+                            count: None,
+                        },
                     ],
                 });
 
-        let pipeline_layout =
-            resources
-                .device()
-                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: Some("chunk_pipeline_layout"),
-                    bind_group_layouts: &[&bg_layout],
-                    push_constant_ranges: &[wgpu::PushConstantRange {
-                        stages: wgpu::ShaderStage::VERTEX,
-                        range: 0..(size_of::<Mat4>() as u32 * 2 + size_of::<Vec4>() as u32),
-                    }],
-                });
+        let params = DrawParams::new::<Params>(resources, "chunk");
+        let pipeline_layout = params.create_pipeline_layout(resources.device(), &[&bg_layout]);
         let vertex = resources.device().create_shader_module(
             assets
-                .get::<ShaderAsset>("shader_compiled/chunk/vertex.spv")?
+                .get::<ShaderAsset>(&params.vertex_shader("chunk"))?
                 .to_source(),
         );
         let fragment = resources.device().create_shader_module(
@@ -111,49 +203,45 @@ impl ChunkRenderer {
                 .get::<ShaderAsset>("shader_compiled/chunk/fragment.spv")?
                 .to_source(),
         );
-        let pipeline = resources
-            .device()
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("chunk_pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex_stage: wgpu::ProgrammableStageDescriptor {
-                    module: &vertex,
-                    entry_point: "main",
-                },
-                fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-                    module: &fragment,
-                    entry_point: "main",
-                }),
-                rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: wgpu::CullMode::None,
-                    ..Default::default()
-                }),
-                primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-                color_states: &[wgpu::ColorStateDescriptor {
-                    format: SC_FORMAT,
-                    color_blend: wgpu::BlendDescriptor::REPLACE,
-                    alpha_blend: wgpu::BlendDescriptor::REPLACE,
-                    write_mask: wgpu::ColorWrite::ALL,
-                }],
-                depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
-                    format: DEPTH_FORMAT,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilStateDescriptor::default(),
-                }),
-                vertex_state: wgpu::VertexStateDescriptor {
-                    index_format: wgpu::IndexFormat::Uint16,
-                    vertex_buffers: &[wgpu::VertexBufferDescriptor {
-                        stride: size_of::<RawVertex>() as _,
-                        step_mode: wgpu::InputStepMode::Vertex,
-                        attributes: &wgpu::vertex_attr_array![0 => Float3, 1 => Float3, 2 => Float3],
-                    }],
-                },
-                sample_count: SAMPLE_COUNT,
-                sample_mask: !0,
-                alpha_to_coverage_enabled: false,
-            });
+        let translucent_fragment = resources.device().create_shader_module(
+            assets
+                .get::<ShaderAsset>("shader_compiled/water/fragment.spv")?
+                .to_source(),
+        );
+        let pipeline = create_pipeline(
+            resources,
+            &pipeline_layout,
+            &vertex,
+            &fragment,
+            PipelineKind::Opaque,
+        );
+        let decal_pipeline = create_pipeline(
+            resources,
+            &pipeline_layout,
+            &vertex,
+            &fragment,
+            PipelineKind::Decal,
+        );
+        let translucent_pipeline = create_pipeline(
+            resources,
+            &pipeline_layout,
+            &vertex,
+            &translucent_fragment,
+            PipelineKind::Translucent,
+        );
+        let indirect = if resources.capabilities().indirect_chunks {
+            Some(IndirectDrawer::new(
+                resources, assets, &bg_layout, &fragment,
+            )?)
+        } else {
+            None
+        };
+        let lighting = resources.device().create_buffer(&wgpu::BufferDescriptor {
+            label: Some("chunk_lighting"),
+            size: size_of::<Lighting>() as u64,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
         let bind_group = resources
             .device()
             .create_bind_group(&wgpu::BindGroupDescriptor {
@@ -170,6 +258,10 @@ impl ChunkRenderer {
                         binding: 1,
                         resource: wgpu::BindingResource::Sampler(&block_sampler),
                     },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Buffer(lighting.slice(..)),
+                    },
                 ],
             });
 
@@ -180,117 +272,817 @@ impl ChunkRenderer {
             mesher,
             culler: Culler::new(),
             chunks: AHashMap::new(),
-            pending_meshes: AHashSet::new(),
+            arena: MeshArena::new(resources),
+            translucent: AHashMap::new(),
+            visible_translucent: Vec::new(),
+            indirect,
+            outline,
+            block_meshes: AHashMap::new(),
+            particles: None,
+            rain_texture,
+            snow_texture,
+            meteor_texture,
+            players: None,
+            player_texture,
+            orbs: None,
+            orb_texture,
+            drops: None,
+            item_textures,
+            shadows: None,
+            shadow_texture,
+            cracks: None,
+            crack_textures,
+            pending_meshes: AHashMap::new(),
+            next_mesh_version: 0,
             pipeline,
+            decal_pipeline,
+            translucent_pipeline,
             bind_group,
+            lighting,
+            params,
+            animation_time: 0.,
         })
     }
 
-    pub fn prep_render(&mut self, resources: &Resources, game: &mut Game) {
+    /// Drops the meshes of chunks and entities. Meshing and culling
+    /// tasks still running for the old world are discarded.
+    pub fn clear_world(&mut self) {
+        self.culler = Culler::new();
+        self.chunks.clear();
+        self.arena.clear();
+        self.translucent.clear();
+        self.visible_translucent.clear();
+        self.pending_meshes.clear();
+        self.particles = None;
+        self.players = None;
+        self.orbs = None;
+        self.drops = None;
+        self.shadows = None;
+        self.cracks = None;
+    }
+
+    pub fn prep_render(&mut self, resources: &Resources, game: &mut Game, sky: &Sky) {
+        self.animation_time = (self.animation_time + game.dt()) % ANIMATION_PERIOD;
+        let lighting = Lighting::new(sky, self.animation_time);
+        resources
+            .queue()
+            .write_buffer(&self.lighting, 0, bytemuck::bytes_of(&lighting));
         self.update_chunk_meshes(resources, game);
+        self.update_block_meshes(game);
+        self.update_particle_mesh(game);
+        self.update_player_mesh(game);
+        self.update_orb_mesh(game);
+        self.update_drop_mesh(game);
+        self.update_shadow_mesh(game);
+        self.update_crack_mesh(game);
+
+        // Chunks, translucent meshes, falling blocks, and the entity, particle,
+        // crack, and outline meshes. Chunks drawn indirectly don't need parameter slots.
+        let falling_blocks = game.ecs().query::<&FallingBlock>().iter().count();
+        let chunks = if self.indirect.is_some() {
+            0
+        } else {
+            self.chunks.len()
+        };
+        let draws = chunks + self.translucent.len() + falling_blocks + 7;
+        self.params.reserve(resources.device(), draws as u32);
+    }
+
+    fn update_player_mesh(&mut self, game: &Game) {
+        let texture = self.player_texture;
+        // Remote players are positioned by the minimum
+        // corner of their bounding box, like our own.
+        let players = game
+            .ecs()
+            .query::<(&Pos, &Username)>()
+            .iter()
+            .flat_map(|(_, (pos, _))| {
+                let pos = Vec3::from(game.world().to_main(pos.0));
+                let head_offset = (PLAYER_BODY_SIZE.x - PLAYER_HEAD_SIZE) / 2.;
+                let body = (pos, PLAYER_BODY_SIZE, texture);
+                let head = (
+                    pos + vec3(head_offset, PLAYER_BODY_SIZE.y, head_offset),
+                    Vec3::splat(PLAYER_HEAD_SIZE),
+                    texture,
+                );
+                iter::once(body).chain(iter::once(head))
+            })
+            .collect::<Vec<_>>();
+        self.players = self.mesher.cuboids_mesh("players", players);
+    }
+
+    fn update_orb_mesh(&mut self, game: &Game) {
+        let texture = self.orb_texture;
+        let orbs = game
+            .ecs()
+            .query::<(&Pos, &XpOrb)>()
+            .iter()
+            .map(|(_, (pos, _))| {
+                (
+                    Vec3::from(game.world().to_main(pos.0)),
+                    Vec3::splat(ORB_SIZE),
+                    texture,
+                )
+            })
+            .collect::<Vec<_>>();
+        self.orbs = self.mesher.cuboids_mesh("orbs", orbs);
+    }
+
+    fn update_drop_mesh(&mut self, game: &Game) {
+        let textures = &self.item_textures;
+        let drops = game
+            .ecs()
+            .query::<(&Pos, &ItemDrop)>()
+            .iter()
+            .filter_map(|(_, (pos, drop))| {
+                let texture = *textures.get(&drop.0.item)?;
+                Some((
+                    Vec3::from(game.world().to_main(pos.0)),
+                    Vec3::splat(DROP_SIZE),
+                    texture,
+                ))
+            })
+            .collect::<Vec<_>>();
+        self.drops = self.mesher.cuboids_mesh("drops", drops);
+    }
+
+    fn update_shadow_mesh(&mut self, game: &Game) {
+        let player_pos = game.player_pos();
+        let falling_blocks = game
+            .ecs()
+            .query::<(&Pos, &FallingBlock)>()
+            .iter()
+            .map(|(_, (pos, _))| (game.world().to_main(pos.0), FALLING_BLOCK_SHADOW_SIZE))
+            .collect::<Vec<_>>();
+        let players = game
+            .ecs()
+            .query::<(&Pos, &Username)>()
+            .iter()
+            .map(|(_, (pos, _))| {
+                let center = game.world().to_main(pos.0)
+                    + vec3a(PLAYER_BODY_SIZE.x, 0., PLAYER_BODY_SIZE.z) / 2.;
+                (center, PLAYER_SHADOW_SIZE)
+            })
+            .collect::<Vec<_>>();
+        let orbs = game
+            .ecs()
+            .query::<(&Pos, &XpOrb)>()
+            .iter()
+            .map(|(_, (pos, _))| {
+                let center = game.world().to_main(pos.0) + vec3a(ORB_SIZE, 0., ORB_SIZE) / 2.;
+                (center, ORB_SHADOW_SIZE)
+            })
+            .collect::<Vec<_>>();
+        let drops = game
+            .ecs()
+            .query::<(&Pos, &ItemDrop)>()
+            .iter()
+            .map(|(_, (pos, _))| {
+                let center = game.world().to_main(pos.0) + vec3a(DROP_SIZE, 0., DROP_SIZE) / 2.;
+                (center, DROP_SHADOW_SIZE)
+            })
+            .collect::<Vec<_>>();
+        let shadows = falling_blocks
+            .into_iter()
+            .chain(players)
+            .chain(orbs)
+            .chain(drops)
+            .filter(|(pos, _)| pos.distance_squared(player_pos) <= SHADOW_DISTANCE.powi(2))
+            .filter_map(|(pos, size)| {
+                let ground = ground_height(game, pos)?;
+                // Shadows shrink as entities rise above the ground.
+                let height = pos.y - ground;
+                let half_width = size * (1. - height / SHADOW_MAX_HEIGHT as f32).max(0.);
+                let center = vec3(pos.x, ground + SHADOW_OFFSET, pos.z);
+                Some((center, half_width))
+            })
+            .collect::<Vec<_>>();
+        self.shadows = self
+            .mesher
+            .decals_mesh("shadows", shadows, self.shadow_texture);
+    }
+
+    fn update_crack_mesh(&mut self, game: &Game) {
+        let textures = &self.crack_textures;
+        // Our own digging and that of other players.
+        let remote = game
+            .ecs()
+            .query::<&Digging>()
+            .iter()
+            .map(|(_, &digging)| digging)
+            .collect::<Vec<_>>();
+        let cracks = game
+            .digging
+            .into_iter()
+            .chain(remote)
+            .filter_map(|digging| {
+                let texture = *textures.get(digging.stage as usize)?;
+                // The decal pipeline's depth bias keeps the cracks
+                // in front of the block's faces.
+                let offset = Vec3::from(digging.pos.min_corner());
+                Some((offset, Vec3::one(), texture))
+            })
+            .collect::<Vec<_>>();
+        self.cracks = self.mesher.cuboids_mesh("cracks", cracks);
+    }
+
+    fn update_particle_mesh(&mut self, game: &Game) {
+        let (rain_texture, snow_texture) = (self.rain_texture, self.snow_texture);
+        let precipitation = game.precipitation.iter().map(|particle| {
+            let (size, texture) = match particle.kind {
+                Precipitation::Snow => (vec3(0.1, 0.1, 0.1), snow_texture),
+                _ => (vec3(0.03, 0.6, 0.03), rain_texture),
+            };
+            // Particles are positioned by their bottom center.
+            let offset = Vec3::from(particle.pos) - vec3(size.x / 2., 0., size.z / 2.);
+            (offset, size, texture)
+        });
+
+        // Meteors are drawn as a head followed by a
+        // trail of smaller cubes along their path.
+        let meteor_texture = self.meteor_texture;
+        let meteors = game.meteors.iter().flat_map(|meteor| {
+            (0..METEOR_TRAIL_LENGTH).map(move |i| {
+                let size = Vec3::splat(METEOR_SIZE * (1. - i as f32 / METEOR_TRAIL_LENGTH as f32));
+                let center = meteor.pos - meteor.vel * (i as f32 * METEOR_TRAIL_SPACING);
+                (Vec3::from(center) - size / 2., size, meteor_texture)
+            })
+        });
+
+        self.particles = self
+            .mesher
+            .cuboids_mesh("particles", precipitation.chain(meteors));
+    }
+
+    fn update_block_meshes(&mut self, game: &Game) {
+        for (_, falling) in game.ecs().query::<&FallingBlock>().iter() {
+            let mesher = &self.mesher;
+            self.block_meshes
+                .entry(falling.0)
+                .or_insert_with(|| mesher.block_mesh(falling.0));
+        }
     }
 
     fn update_chunk_meshes(&mut self, _resources: &Resources, game: &mut Game) {
+        // Chunks are collected into a set first, so a chunk loaded
+        // or modified several times in a frame is only meshed once.
+        let mut to_mesh = hashbrown::HashSet::new_in(game.bump());
         for event in game.events().iter::<ChunkLoaded>() {
             if let Some(chunk) = game.main_zone().chunk(event.pos) {
                 log::trace!("Spawning cull task for {:?}", event.pos);
                 self.culler.on_chunk_loaded(event.pos, chunk);
-                self.mesher.spawn(event.pos, chunk.clone());
-                log::trace!("Spawning mesher task for {:?}", event.pos);
-                self.pending_meshes.insert(event.pos);
+                to_mesh.insert(event.pos);
+                // Faces of loaded neighbors against
+                // this chunk may now be hidden.
+                for &neighbor in &neighbor_positions(event.pos) {
+                    if self.chunks.contains_key(&neighbor)
+                        || self.pending_meshes.contains_key(&neighbor)
+                    {
+                        to_mesh.insert(neighbor);
+                    }
+                }
+            }
+        }
+
+        let mut modified = hashbrown::HashSet::new_in(game.bump());
+        modified.extend(game.events().iter::<ChunkModified>().map(|event| event.pos));
+        for pos in modified {
+            if to_mesh.contains(&pos) {
+                continue;
+            }
+            if let Some(chunk) = game.main_zone().chunk(pos) {
+                self.culler.on_chunk_modified(pos, chunk);
+                to_mesh.insert(pos);
+            }
+        }
+
+        for pos in to_mesh {
+            if let Some(chunk) = game.main_zone().chunk(pos) {
+                let version = self.next_mesh_version;
+                self.next_mesh_version += 1;
+                let mut neighbors = [None, None, None, None, None, None];
+                for (neighbor, &neighbor_pos) in neighbors.iter_mut().zip(&neighbor_positions(pos))
+                {
+                    *neighbor = game.main_zone().chunk(neighbor_pos).cloned();
+                }
+                self.mesher.spawn(pos, version, chunk.clone(), neighbors);
+                log::trace!("Spawning mesher task for {:?}", pos);
+                self.pending_meshes.insert(pos, version);
             }
         }
 
         for event in game.events().iter::<ChunkUnloaded>() {
-            self.chunks.remove(&event.pos);
+            if let Some(mesh) = self.chunks.remove(&event.pos) {
+                self.arena.free(mesh);
+            }
+            self.translucent.remove(&event.pos);
             self.pending_meshes.remove(&event.pos);
             self.culler.on_chunk_unloaded(event.pos);
 
             log::trace!("Dropping chunk mesh for {:?}", event.pos);
         }
 
-        for (pos, mesh) in self.mesher.iter_finished() {
-            let was_pending = self.pending_meshes.remove(&pos);
-            let mesh = match mesh {
-                Some(mesh) => mesh,
-                None => continue,
-            };
-            if was_pending {
-                self.chunks.insert(pos, mesh);
-
-                log::trace!(
-                    "Loaded mesh for {:?}. Total chunks in renderer: {}",
-                    pos,
-                    self.chunks.len()
-                );
+        game.debug_data.meshes_completed = 0;
+        for (pos, version, mesh) in self.mesher.iter_finished() {
+            game.debug_data.meshes_completed += 1;
+            let is_latest = self.pending_meshes.get(&pos) == Some(&version);
+            if !is_latest {
+                continue;
+            }
+            self.pending_meshes.remove(&pos);
+            if let Some(old) = self.chunks.remove(&pos) {
+                self.arena.free(old);
             }
+            // Empty chunks have no mesh.
+            let ChunkVertices {
+                opaque,
+                translucent,
+            } = mesh;
+            if let Some(vertices) = opaque {
+                self.chunks.insert(pos, self.arena.upload(&vertices));
+            }
+            match translucent {
+                Some(vertices) => {
+                    let mesh = self.mesher.upload_vertices("translucent_chunk", &vertices);
+                    self.translucent.insert(pos, mesh);
+                }
+                None => {
+                    self.translucent.remove(&pos);
+                }
+            }
+
+            log::trace!(
+                "Loaded mesh for {:?}. Total chunks in renderer: {}",
+                pos,
+                self.chunks.len()
+            );
         }
     }
 
-    pub fn do_render<'a>(&'a mut self, pass: &mut wgpu::RenderPass<'a>, game: &mut Game) {
+    pub fn do_render<'a>(
+        &'a mut self,
+        resources: &Resources,
+        pass: &mut wgpu::RenderPass<'a>,
+        game: &mut Game,
+    ) {
         pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, &self.bind_group, &[]);
 
-        let matrices = game.matrices();
+        let drawer = MeshDrawer {
+            params: &self.params,
+            queue: resources.queue(),
+            matrices: game.matrices(),
+        };
 
-        let pos = *game.player_ref().get::<Pos>().unwrap();
-        let player_chunk = ChunkPos::from_pos(pos);
+        let player_chunk = ChunkPos::from_pos(game.player_pos());
 
         #[cfg(debug_assertions)]
         let visible = {
             // Culling disabled in debug mode - it's too slow.
-            self.chunks.keys().copied()
+            let translucent_only = self
+                .translucent
+                .keys()
+                .filter(|pos| !self.chunks.contains_key(pos));
+            self.chunks.keys().chain(translucent_only).copied()
         };
         #[cfg(not(debug_assertions))]
         let visible = {
             self.culler.update(player_chunk, game.bump());
             self.culler.visible_chunks()
         };
+        let eye = game.player_pos();
+        let distance = |pos: &ChunkPos| {
+            let center = chunk_transform(*pos).truncate() + Vec3::splat(CHUNK_DIM as f32 / 2.);
+            Vec3A::from(center).distance_squared(eye)
+        };
 
-        let mut count = 0;
-        for pos in visible {
-            let mesh = match self.chunks.get(&pos) {
-                Some(m) => m,
-                None => continue,
-            };
-            pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        // Chunks are drawn grouped by pool, so that each
+        // pool's vertex buffer is bound only once.
+        let count = {
+            let mut visible_chunks = Vec::new_in(game.bump());
+            visible_chunks.extend(visible);
 
-            #[derive(Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
-            #[repr(C)]
-            struct PushConstants {
-                transform: Vec4,
-                view: Mat4,
-                projection: Mat4,
-            }
-            let transform = vec4(
-                (pos.x * CHUNK_DIM as i32) as f32,
-                (pos.y * CHUNK_DIM as i32) as f32,
-                (pos.z * CHUNK_DIM as i32) as f32,
-                0.,
+            // Translucent meshes are drawn back to front, so
+            // nearer water blends over water behind it.
+            let translucent = &self.translucent;
+            self.visible_translucent.clear();
+            self.visible_translucent.extend(
+                visible_chunks
+                    .iter()
+                    .copied()
+                    .filter(|pos| translucent.contains_key(pos)),
             );
-            let push_constants = PushConstants {
-                transform,
-                view: matrices.view,
-                projection: matrices.projection,
-            };
-            pass.set_push_constants(
-                wgpu::ShaderStage::VERTEX,
-                0,
-                bytemuck::cast_slice(&[push_constants]),
+            self.visible_translucent
+                .sort_by(|a, b| distance(b).partial_cmp(&distance(a)).unwrap());
+
+            let mut meshes = Vec::new_in(game.bump());
+            meshes.extend(
+                visible_chunks
+                    .iter()
+                    .filter_map(|&pos| Some((pos, self.chunks.get(&pos)?))),
             );
+            meshes.sort_by_key(|(_, mesh)| mesh.pool());
+            match self.indirect.as_mut() {
+                Some(indirect) => {
+                    indirect.prepare(resources, &drawer.matrices, &meshes);
+                    let indirect: &'a IndirectDrawer = indirect;
+                    indirect.draw(pass, &self.arena);
+                    pass.set_pipeline(&self.pipeline);
+                }
+                None => {
+                    let mut bound_pool = None;
+                    for &(pos, mesh) in &meshes {
+                        if bound_pool != Some(mesh.pool()) {
+                            pass.set_vertex_buffer(0, self.arena.buffer(mesh.pool()).slice(..));
+                            bound_pool = Some(mesh.pool());
+                        }
+                        drawer.draw_range(pass, mesh.vertices(), chunk_transform(pos));
+                    }
+                }
+            }
+            meshes.len()
+        };
+        game.debug_data.render_chunks = count;
+        game.debug_data.chunk_memory = self.arena.usage();
+
+        for (_, (pos, falling)) in game.ecs().query::<(&Pos, &FallingBlock)>().iter() {
+            if let Some(Some(mesh)) = self.block_meshes.get(&falling.0) {
+                // Falling blocks are positioned by the center of their bottom face.
+                let pos = game.world().to_main(pos.0);
+                let transform = vec4(pos.x - 0.5, pos.y, pos.z - 0.5, 0.);
+                drawer.draw(pass, mesh, transform);
+            }
+        }
+
+        if let Some(mesh) = &self.players {
+            drawer.draw(pass, mesh, Vec4::zero());
+        }
+
+        if let Some(mesh) = &self.orbs {
+            drawer.draw(pass, mesh, Vec4::zero());
+        }
+
+        if let Some(mesh) = &self.drops {
+            drawer.draw(pass, mesh, Vec4::zero());
+        }
+
+        if self.shadows.is_some() || self.cracks.is_some() {
+            pass.set_pipeline(&self.decal_pipeline);
+            for mesh in self.shadows.iter().chain(&self.cracks) {
+                drawer.draw(pass, mesh, Vec4::zero());
+            }
+            pass.set_pipeline(&self.pipeline);
+        }
+
+        if let Some(mesh) = &self.particles {
+            drawer.draw(pass, mesh, Vec4::zero());
+        }
+
+        if let Some(target) = game.targeted_block {
+            let min = target.min_corner();
+            let transform = vec4(min.x, min.y, min.z, 0.);
+            drawer.draw(pass, &self.outline, transform);
+        }
+    }
+
+    /// Draws the translucent blocks of the chunks visible in the last
+    /// call to [`do_render`](Self::do_render), over the geometry it drew.
+    pub fn do_render_translucent<'a>(
+        &'a self,
+        resources: &Resources,
+        pass: &mut wgpu::RenderPass<'a>,
+        game: &Game,
+    ) {
+        if self.visible_translucent.is_empty() {
+            return;
+        }
+        pass.set_pipeline(&self.translucent_pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+
+        let drawer = MeshDrawer {
+            params: &self.params,
+            queue: resources.queue(),
+            matrices: game.matrices(),
+        };
+        for pos in &self.visible_translucent {
+            if let Some(mesh) = self.translucent.get(pos) {
+                drawer.draw(pass, mesh, chunk_transform(*pos));
+            }
+        }
+    }
+}
+
+/// Returns the offset of a chunk's mesh in the world.
+fn chunk_transform(pos: ChunkPos) -> Vec4 {
+    let min = pos.block_min().min_corner();
+    vec4(min.x, min.y, min.z, 0.)
+}
+
+/// The kinds of geometry drawn by the chunk renderer's pipelines.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum PipelineKind {
+    Opaque,
+    /// Flat meshes on top of other geometry, like shadows. They blend
+    /// with the geometry below them and are biased toward the camera
+    /// so they don't z-fight with it.
+    Decal,
+    /// Translucent blocks, which blend with the geometry behind them.
+    Translucent,
+}
+
+/// Creates a pipeline for the chunk vertex shader. Only opaque
+/// geometry writes to the depth buffer.
+fn create_pipeline(
+    resources: &Resources,
+    layout: &wgpu::PipelineLayout,
+    vertex: &wgpu::ShaderModule,
+    fragment: &wgpu::ShaderModule,
+    kind: PipelineKind,
+) -> wgpu::RenderPipeline {
+    let (color_blend, alpha_blend) = if kind != PipelineKind::Opaque {
+        (
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        )
+    } else {
+        (
+            wgpu::BlendDescriptor::REPLACE,
+            wgpu::BlendDescriptor::REPLACE,
+        )
+    };
+    let (depth_bias, depth_bias_slope_scale) = if kind == PipelineKind::Decal {
+        (-4, -1.)
+    } else {
+        (0, 0.)
+    };
+    let device = resources.device();
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(match kind {
+            PipelineKind::Opaque => "chunk_pipeline",
+            PipelineKind::Decal => "chunk_decal_pipeline",
+            PipelineKind::Translucent => "chunk_translucent_pipeline",
+        }),
+        layout: Some(layout),
+        vertex_stage: wgpu::ProgrammableStageDescriptor {
+            module: vertex,
+            entry_point: "main",
+        },
+        fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+            module: fragment,
+            entry_point: "main",
+        }),
+        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: wgpu::CullMode::None,
+            depth_bias,
+            depth_bias_slope_scale,
+            ..Default::default()
+        }),
+        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+        color_states: &[wgpu::ColorStateDescriptor {
+            format: SC_FORMAT,
+            color_blend,
+            alpha_blend,
+            write_mask: wgpu::ColorWrite::ALL,
+        }],
+        depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: kind == PipelineKind::Opaque,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilStateDescriptor::default(),
+        }),
+        vertex_state: wgpu::VertexStateDescriptor {
+            index_format: wgpu::IndexFormat::Uint16,
+            vertex_buffers: &[wgpu::VertexBufferDescriptor {
+                stride: size_of::<RawVertex>() as _,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float3, 1 => Float3, 2 => Float3],
+            }],
+        },
+        sample_count: resources.capabilities().sample_count,
+        sample_mask: !0,
+        alpha_to_coverage_enabled: false,
+    })
+}
+
+/// Finds the top of the first solid block below `pos`, looking
+/// at most [`SHADOW_MAX_HEIGHT`] blocks down.
+fn ground_height(game: &Game, pos: Vec3A) -> Option<f32> {
+    let start = BlockPos::from_pos(pos);
+    (0..=SHADOW_MAX_HEIGHT)
+        .map(|dy| start.offset(0, -dy, 0))
+        .find_map(|block_pos| {
+            let collision_box = game.main_zone().block(block_pos)?.collision_box()?;
+            let top = block_pos.y as f32 + collision_box.max[1];
+            // Skip blocks the entity is inside of.
+            if top <= pos.y + SHADOW_OFFSET {
+                Some(top)
+            } else {
+                None
+            }
+        })
+}
+
+#[derive(Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
+#[repr(C)]
+struct Params {
+    transform: Vec4,
+    view: Mat4,
+    projection: Mat4,
+}
+
+/// The `Lighting` uniform block of the fragment shaders.
+#[derive(Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
+#[repr(C)]
+struct Lighting {
+    light_dir: Vec4,
+    light_color: Vec4,
+    ambient_color: Vec4,
+    fog_color: Vec4,
+    /// The animation time in X. Only the water shader reads it.
+    time: Vec4,
+}
+
+impl Lighting {
+    fn new(sky: &Sky, animation_time: f32) -> Self {
+        Self {
+            light_dir: sky.light_dir.extend(0.),
+            light_color: sky.light_color.extend(0.),
+            ambient_color: sky.ambient_color.extend(0.),
+            fog_color: sky.fog_color.extend(1.),
+            time: vec4(animation_time, 0., 0., 0.),
+        }
+    }
+}
 
+/// Draws meshes with the camera's matrices.
+struct MeshDrawer<'a, 'q> {
+    params: &'a DrawParams,
+    queue: &'q wgpu::Queue,
+    matrices: Matrices,
+}
+
+impl<'a, 'q> MeshDrawer<'a, 'q> {
+    /// Draws a mesh translated by `transform`.
+    fn draw(&self, pass: &mut wgpu::RenderPass<'a>, mesh: &'a GpuMesh, transform: Vec4) {
+        if self.set_params(pass, transform) {
+            pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
             pass.draw(0..mesh.vertex_count, 0..1);
-            count += 1;
         }
-        game.debug_data.render_chunks = count;
     }
+
+    /// Draws `vertices` of the bound vertex buffer translated by `transform`.
+    fn draw_range(&self, pass: &mut wgpu::RenderPass<'a>, vertices: Range<u32>, transform: Vec4) {
+        if self.set_params(pass, transform) {
+            pass.draw(vertices, 0..1);
+        }
+    }
+
+    fn set_params(&self, pass: &mut wgpu::RenderPass<'a>, transform: Vec4) -> bool {
+        let params = Params {
+            transform,
+            view: self.matrices.view,
+            projection: self.matrices.projection,
+        };
+        self.params.set(self.queue, pass, &params)
+    }
+}
+
+/// The texture used to outline the targeted block.
+const OUTLINE_TEXTURE: &str = "outline.png";
+/// The textures of rain and snow particles.
+const RAIN_TEXTURE: &str = "rain.png";
+const SNOW_TEXTURE: &str = "snow.png";
+const METEOR_TEXTURE: &str = "meteor.png";
+/// The texture of blob shadows: a dark circle that fades out.
+const SHADOW_TEXTURE: &str = "shadow.png";
+
+/// The texture of placeholder player models.
+const PLAYER_TEXTURE: &str = "player.png";
+
+/// The size of a placeholder player's body and head. Together
+/// they fill the player's bounding box.
+const PLAYER_BODY_SIZE: Vec3 = glam::const_vec3!([0.5, 1.6, 0.5]);
+const PLAYER_HEAD_SIZE: f32 = 0.4;
+
+/// The texture of experience orbs.
+const ORB_TEXTURE: &str = "xp_orb.png";
+/// The size of an experience orb. Orbs are positioned
+/// by the minimum corner of their bounding box.
+const ORB_SIZE: f32 = 0.25;
+
+/// The texture of items whose block has no icon texture.
+const UNKNOWN_TEXTURE: &str = "unknown.png";
+/// The size of a dropped item. Drops are positioned
+/// by the minimum corner of their bounding box.
+const DROP_SIZE: f32 = 0.25;
+
+/// The half-width of a falling block's shadow when it is on the ground.
+const FALLING_BLOCK_SHADOW_SIZE: f32 = 0.65;
+/// The half-width of a player's shadow when they are on the ground.
+const PLAYER_SHADOW_SIZE: f32 = 0.45;
+/// The half-width of an experience orb's shadow when it is on the ground.
+const ORB_SHADOW_SIZE: f32 = 0.2;
+/// The half-width of a dropped item's shadow when it is on the ground.
+const DROP_SHADOW_SIZE: f32 = 0.2;
+/// The number of blocks an entity can be above the
+/// ground before its shadow disappears.
+const SHADOW_MAX_HEIGHT: i32 = 8;
+/// Entities farther than this from the player cast no shadow.
+/// Fog would show the edges of more distant shadows.
+const SHADOW_DISTANCE: f32 = 32.;
+/// The height of shadows above the ground.
+const SHADOW_OFFSET: f32 = 1. / 256.;
+
+/// Returns the name of the texture drawn on blocks
+/// being dug at a crack stage. Later stages are
+/// redder and more cracked.
+fn crack_texture(stage: u8) -> String {
+    format!("crack_{}.png", stage)
 }
 
+/// The number of seconds after which animations repeat. Animated
+/// textures must scroll a whole number of times in this period.
+const ANIMATION_PERIOD: f32 = 64.;
+
+/// The size of a meteor's head in blocks.
+const METEOR_SIZE: f32 = 1.5;
+/// The number of cubes drawn for each meteor, including its head.
+const METEOR_TRAIL_LENGTH: u32 = 8;
+/// The time in seconds between a meteor passing
+/// successive cubes in its trail.
+const METEOR_TRAIL_SPACING: f32 = 0.02;
+
 /// A fixed dimension used for block textures. Block textures
 /// must match this dimension exactly.
 const BLOCK_TEXTURE_DIM: u32 = 64;
 const MIP_LEVELS: u32 = 7;
 
+/// The textures the chunk renderer draws other than those of block models.
+const REQUIRED_TEXTURES: [&str; 7] = [
+    OUTLINE_TEXTURE,
+    RAIN_TEXTURE,
+    SNOW_TEXTURE,
+    METEOR_TEXTURE,
+    SHADOW_TEXTURE,
+    PLAYER_TEXTURE,
+    ORB_TEXTURE,
+];
+
+/// Checks that the block textures and models in `assets`
+/// agree with each other and with the block registry.
+/// Problems are added to `report`.
+pub fn validate_assets(assets: &Assets, report: &mut Report) {
+    let prefix = "texture/block/";
+    let mut textures = AHashSet::new();
+    for (name, texture) in assets.iter_prefixed::<TextureAsset>(prefix) {
+        let name = name.strip_prefix(prefix).expect("prefix");
+        if texture.width() != BLOCK_TEXTURE_DIM || texture.height() != BLOCK_TEXTURE_DIM {
+            report.add(format!(
+                "block texture '{}' is {}x{}, but must be {}x{}",
+                name,
+                texture.width(),
+                texture.height(),
+                BLOCK_TEXTURE_DIM,
+                BLOCK_TEXTURE_DIM
+            ));
+        }
+        textures.insert(name);
+    }
+
+    let required = REQUIRED_TEXTURES
+        .iter()
+        .chain(iter::once(&UNKNOWN_TEXTURE))
+        .map(|&texture| texture.to_owned())
+        .chain((0..DIG_STAGES).map(crack_texture));
+    for texture in required {
+        if !textures.contains(texture.as_str()) {
+            report.add(format!("missing block texture '{}'", texture));
+        }
+    }
+
+    mesher::validate_models(assets, |texture| textures.contains(texture), report);
+}
+
+/// Returns the name of the texture drawn on an
+/// item, relative to `texture/block/`, if it has one.
+fn item_texture(assets: &Assets, item: ItemId) -> Option<String> {
+    let block = item.descriptor().block()?;
+    let model = assets
+        .get::<YamlModel>(&format!("model/block/{}.yml", block.descriptor().slug()))
+        .ok()?;
+    model.icon_texture().map(str::to_owned)
+}
+
 fn create_block_textures(
     resources: &Arc<Resources>,
     assets: &Assets,
@@ -308,10 +1100,11 @@ fn create_block_textures(
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Bgra8UnormSrgb,
-            usage: wgpu::TextureUsage::SAMPLED,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::OUTPUT_ATTACHMENT,
         },
         resources,
     );
+    let mut mipmaps = MipmapGenerator::new(resources, assets)?;
     let mut indexes = AHashMap::new();
 
     let prefix = "texture/block/";
@@ -330,7 +1123,7 @@ fn create_block_textures(
         }
 
         let data = texture.data();
-        let index = textures.add_mipmapped(data, resources.queue(), encoder)?;
+        let index = textures.add_mipmapped(data, resources.queue(), encoder, &mut mipmaps);
         indexes.insert(name.to_owned(), index);
 
         log::info!("Uploaded block texture '{}'", name);
@@ -0,0 +1,248 @@
+//! Suballocation of chunk meshes from large, shared vertex buffers.
+//!
+//! Giving each chunk mesh its own buffer fragments GPU memory and forces a
+//! vertex buffer bind per draw. Instead, a [`MeshArena`] owns a few pools,
+//! each a single large buffer, and places each mesh in a free range of one
+//! of them. Ranges are returned to their pool when a chunk is remeshed or
+//! unloaded. The renderer groups draws by pool, so it binds each
+//! pool's buffer once per frame.
+
+use std::{mem::size_of, ops::Range, sync::Arc};
+
+use crate::renderer::Resources;
+
+use super::mesher::RawVertex;
+
+/// The number of vertices in each pool. Meshes larger
+/// than this get a pool of their own.
+const POOL_VERTICES: u32 = 1 << 19;
+
+/// Pools of vertex buffers holding chunk meshes.
+pub struct MeshArena {
+    resources: Arc<Resources>,
+    pools: Vec<Pool>,
+}
+
+struct Pool {
+    buffer: wgpu::Buffer,
+    free: FreeList,
+}
+
+/// A mesh stored in a [`MeshArena`]. Must be returned
+/// with [`MeshArena::free`] once no longer drawn.
+#[derive(Debug)]
+pub struct ArenaMesh {
+    pool: usize,
+    vertices: Range<u32>,
+}
+
+impl ArenaMesh {
+    /// The index of the pool holding the mesh.
+    pub fn pool(&self) -> usize {
+        self.pool
+    }
+
+    /// The range of the mesh's vertices in its pool.
+    pub fn vertices(&self) -> Range<u32> {
+        self.vertices.clone()
+    }
+}
+
+impl MeshArena {
+    pub fn new(resources: &Arc<Resources>) -> Self {
+        Self {
+            resources: Arc::clone(resources),
+            pools: Vec::new(),
+        }
+    }
+
+    /// Uploads a mesh, creating a new pool if none has room for it.
+    pub fn upload(&mut self, vertices: &[RawVertex]) -> ArenaMesh {
+        let len = vertices.len() as u32;
+        let found = self
+            .pools
+            .iter_mut()
+            .enumerate()
+            .find_map(|(index, pool)| Some((index, pool.free.allocate(len)?)));
+        let (pool, range) = match found {
+            Some(found) => found,
+            None => {
+                let mut pool = self.create_pool(len.max(POOL_VERTICES));
+                let range = pool.free.allocate(len).expect("new pool fits the mesh");
+                self.pools.push(pool);
+                (self.pools.len() - 1, range)
+            }
+        };
+
+        let offset = range.start as u64 * size_of::<RawVertex>() as u64;
+        self.resources.queue().write_buffer(
+            &self.pools[pool].buffer,
+            offset,
+            bytemuck::cast_slice(vertices),
+        );
+        ArenaMesh {
+            pool,
+            vertices: range,
+        }
+    }
+
+    /// Returns the space used by `mesh` to its pool.
+    pub fn free(&mut self, mesh: ArenaMesh) {
+        self.pools[mesh.pool].free.free(mesh.vertices);
+    }
+
+    /// Frees all meshes. The pools are kept for reuse.
+    pub fn clear(&mut self) {
+        for pool in &mut self.pools {
+            pool.free.clear();
+        }
+    }
+
+    /// Gets the vertex buffer of a pool.
+    pub fn buffer(&self, pool: usize) -> &wgpu::Buffer {
+        &self.pools[pool].buffer
+    }
+
+    /// Returns the number of bytes used by meshes
+    /// and the total size of all pools.
+    pub fn usage(&self) -> (u64, u64) {
+        let vertex_size = size_of::<RawVertex>() as u64;
+        self.pools.iter().fold((0, 0), |(used, total), pool| {
+            let capacity = pool.free.capacity() as u64;
+            let free = pool.free.free_len() as u64;
+            (
+                used + (capacity - free) * vertex_size,
+                total + capacity * vertex_size,
+            )
+        })
+    }
+
+    fn create_pool(&self, vertices: u32) -> Pool {
+        log::debug!(
+            "Creating chunk mesh pool {} with room for {} vertices",
+            self.pools.len(),
+            vertices
+        );
+        let buffer = self
+            .resources
+            .device()
+            .create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!("chunk_pool_{}", self.pools.len())),
+                size: vertices as u64 * size_of::<RawVertex>() as u64,
+                usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            });
+        Pool {
+            buffer,
+            free: FreeList::new(vertices),
+        }
+    }
+}
+
+/// Tracks the free ranges of a pool. Allocation is first-fit,
+/// and adjacent free ranges are merged.
+#[derive(Debug)]
+struct FreeList {
+    capacity: u32,
+    /// Sorted, disjoint, and never adjacent to one another.
+    free: Vec<Range<u32>>,
+}
+
+impl FreeList {
+    fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            free: vec![0..capacity],
+        }
+    }
+
+    fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Returns the total length of the free ranges.
+    fn free_len(&self) -> u32 {
+        self.free.iter().map(|range| range.end - range.start).sum()
+    }
+
+    /// Allocates a range of length `len`, or returns
+    /// `None` if no free range is large enough.
+    fn allocate(&mut self, len: u32) -> Option<Range<u32>> {
+        let index = self
+            .free
+            .iter()
+            .position(|range| range.end - range.start >= len)?;
+        let range = &mut self.free[index];
+        let start = range.start;
+        range.start += len;
+        if range.start == range.end {
+            self.free.remove(index);
+        }
+        Some(start..start + len)
+    }
+
+    /// Returns an allocated range.
+    fn free(&mut self, range: Range<u32>) {
+        if range.start == range.end {
+            return;
+        }
+        let index = self
+            .free
+            .iter()
+            .position(|free| free.start > range.start)
+            .unwrap_or_else(|| self.free.len());
+        debug_assert!(index == 0 || self.free[index - 1].end <= range.start);
+        debug_assert!(index == self.free.len() || range.end <= self.free[index].start);
+
+        let merges_before = index > 0 && self.free[index - 1].end == range.start;
+        let merges_after = index < self.free.len() && self.free[index].start == range.end;
+        match (merges_before, merges_after) {
+            (true, true) => {
+                let after = self.free.remove(index);
+                self.free[index - 1].end = after.end;
+            }
+            (true, false) => self.free[index - 1].end = range.end,
+            (false, true) => self.free[index].start = range.start,
+            (false, false) => self.free.insert(index, range),
+        }
+    }
+
+    /// Frees all ranges.
+    fn clear(&mut self) {
+        self.free = vec![0..self.capacity];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocates_first_fit() {
+        let mut list = FreeList::new(100);
+        assert_eq!(list.allocate(30), Some(0..30));
+        assert_eq!(list.allocate(30), Some(30..60));
+        assert_eq!(list.allocate(50), None);
+        assert_eq!(list.allocate(40), Some(60..100));
+        assert_eq!(list.free_len(), 0);
+        assert_eq!(list.allocate(1), None);
+    }
+
+    #[test]
+    fn freed_ranges_merge() {
+        let mut list = FreeList::new(100);
+        let a = list.allocate(20).unwrap();
+        let b = list.allocate(20).unwrap();
+        let c = list.allocate(20).unwrap();
+
+        list.free(a);
+        list.free(c);
+        assert_eq!(list.free, vec![0..20, 40..100]);
+        // 80 vertices are free, but too fragmented for 70 until b is freed
+        assert_eq!(list.allocate(70), None);
+
+        list.free(b);
+        assert_eq!(list.free, vec![0..100]);
+        assert_eq!(list.allocate(100), Some(0..100));
+    }
+}
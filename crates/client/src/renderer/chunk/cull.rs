@@ -12,6 +12,8 @@ use common::{
 use crossbeam_queue::SegQueue;
 use utils::BitSet;
 
+use crate::platform;
+
 /// Algorithm to skip rendering chunks which are occluded
 /// by other chunks.
 ///
@@ -28,7 +30,12 @@ pub struct Culler {
     chunks_updated: bool,
     previous_root: ChunkPos,
     visible: AHashSet<ChunkPos>,
-    task_queue: Arc<SegQueue<(ChunkPos, ChunkVisibility)>>,
+    /// Maps chunks whose visibility is being computed to the
+    /// version of their latest task. Results of older tasks
+    /// are discarded.
+    pending: AHashMap<ChunkPos, u64>,
+    next_version: u64,
+    task_queue: Arc<SegQueue<(ChunkPos, u64, ChunkVisibility)>>,
 }
 
 impl Culler {
@@ -39,23 +46,39 @@ impl Culler {
     pub fn on_chunk_loaded(&mut self, pos: ChunkPos, chunk: &Chunk) {
         if chunk.is_empty() {
             self.chunks.insert(pos, full_visibility());
+            self.pending.remove(&pos);
             self.chunks_updated = true;
         } else {
+            let version = self.next_version;
+            self.next_version += 1;
+            self.pending.insert(pos, version);
+
             let chunk = chunk.clone();
             let task_queue = Arc::clone(&self.task_queue);
-            rayon::spawn(move || {
+            platform::spawn(move || {
                 utils::THREAD_BUMP.with(|bump| {
                     let mut bump = bump.borrow_mut();
                     let vis = compute_visibility(&chunk, &*bump);
                     bump.reset();
-                    task_queue.push((pos, vis));
+                    task_queue.push((pos, version, vis));
                 });
             });
         }
     }
 
+    /// Invalidates the visibility of a chunk whose blocks changed.
+    ///
+    /// Until its new visibility is computed, the chunk is treated
+    /// as transparent, so chunks behind an opening are not culled.
+    pub fn on_chunk_modified(&mut self, pos: ChunkPos, chunk: &Chunk) {
+        self.chunks.insert(pos, full_visibility());
+        self.chunks_updated = true;
+        self.on_chunk_loaded(pos, chunk);
+    }
+
     pub fn on_chunk_unloaded(&mut self, pos: ChunkPos) {
         self.chunks.remove(&pos);
+        self.pending.remove(&pos);
         self.chunks_updated = true;
         log::trace!("Removed visibility for {:?}", pos);
     }
@@ -73,7 +96,11 @@ impl Culler {
     }
 
     fn poll_tasks(&mut self) {
-        while let Some((pos, vis)) = self.task_queue.pop() {
+        while let Some((pos, version, vis)) = self.task_queue.pop() {
+            if self.pending.get(&pos) != Some(&version) {
+                continue;
+            }
+            self.pending.remove(&pos);
             self.chunks.insert(pos, vis);
             self.chunks_updated = true;
             log::trace!("Computed visibility for {:?}", pos);
@@ -0,0 +1,226 @@
+//! Indirect drawing of chunk meshes.
+//!
+//! Drawing chunks one by one costs a parameter upload and a draw call
+//! each, which adds up at high view distances. Instead, each frame the
+//! transforms of the visible chunks are written to a storage buffer and
+//! their draws to an indirect buffer. The draw of the `i`th chunk starts
+//! at instance `i`, which the `vertex_indirect` shader variant uses to
+//! find its transform. Draws are batched by [arena](super::arena) pool,
+//! so that a pool takes a single `multi_draw_indirect` call on
+//! adapters supporting it, and one `draw_indirect` per chunk elsewhere.
+//! Adapters which can't start indirect draws at a non-zero instance
+//! draw chunks directly instead; see
+//! [`Capabilities`](crate::renderer::Capabilities::indirect_chunks).
+
+use std::{mem::size_of, num::NonZeroU64};
+
+use common::ChunkPos;
+use glam::Vec4;
+
+use crate::{
+    asset::{shader::ShaderAsset, Assets},
+    camera::Matrices,
+    renderer::Resources,
+};
+
+use super::{
+    arena::{ArenaMesh, MeshArena},
+    chunk_transform, create_pipeline,
+};
+
+/// The number of draws the buffers initially have room for.
+const INITIAL_CAPACITY: u32 = 256;
+
+/// The arguments of a single indirect draw.
+#[derive(Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
+#[repr(C)]
+struct DrawIndirect {
+    vertex_count: u32,
+    instance_count: u32,
+    base_vertex: u32,
+    base_instance: u32,
+}
+
+/// Draws the visible chunks of a frame indirectly.
+pub struct IndirectDrawer {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    camera: wgpu::Buffer,
+    transforms: wgpu::Buffer,
+    draws: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// The number of draws the buffers have room for.
+    capacity: u32,
+    multi_draw: bool,
+    /// The draws of the current frame as (pool, first draw, draw count).
+    batches: Vec<(usize, u32, u32)>,
+}
+
+impl IndirectDrawer {
+    /// Creates the drawer. `chunk_layout` is the layout of the
+    /// block textures' bind group, which stays at set 0.
+    pub fn new(
+        resources: &Resources,
+        assets: &Assets,
+        chunk_layout: &wgpu::BindGroupLayout,
+        fragment: &wgpu::ShaderModule,
+    ) -> anyhow::Result<Self> {
+        let device = resources.device();
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("chunk_indirect_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStage::VERTEX,
+                    ty: wgpu::BindingType::UniformBuffer {
+                        dynamic: false,
+                        min_binding_size: NonZeroU64::new(size_of::<Matrices>() as u64),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStage::VERTEX,
+                    ty: wgpu::BindingType::StorageBuffer {
+                        dynamic: false,
+                        min_binding_size: NonZeroU64::new(size_of::<Vec4>() as u64),
+                        readonly: true,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("chunk_indirect"),
+            bind_group_layouts: &[chunk_layout, &layout],
+            push_constant_ranges: &[],
+        });
+        let vertex = device.create_shader_module(
+            assets
+                .get::<ShaderAsset>("shader_compiled/chunk/vertex_indirect.spv")?
+                .to_source(),
+        );
+        let pipeline = create_pipeline(resources, &pipeline_layout, &vertex, fragment, false);
+
+        let camera = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("chunk_indirect_camera"),
+            size: size_of::<Matrices>() as u64,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (transforms, draws) = create_buffers(device, INITIAL_CAPACITY);
+        let bind_group = create_bind_group(device, &layout, &camera, &transforms);
+
+        Ok(Self {
+            layout,
+            pipeline,
+            camera,
+            transforms,
+            draws,
+            bind_group,
+            capacity: INITIAL_CAPACITY,
+            multi_draw: resources.capabilities().multi_draw_indirect,
+            batches: Vec::new(),
+        })
+    }
+
+    /// Writes the draws of `meshes`, which must be sorted by pool.
+    pub fn prepare(
+        &mut self,
+        resources: &Resources,
+        matrices: &Matrices,
+        meshes: &[(ChunkPos, &ArenaMesh)],
+    ) {
+        let len = meshes.len() as u32;
+        if len > self.capacity {
+            let device = resources.device();
+            self.capacity = len.next_power_of_two();
+            let (transforms, draws) = create_buffers(device, self.capacity);
+            self.bind_group = create_bind_group(device, &self.layout, &self.camera, &transforms);
+            self.transforms = transforms;
+            self.draws = draws;
+        }
+
+        self.batches.clear();
+        let mut transforms = Vec::with_capacity(meshes.len());
+        let mut draws = Vec::with_capacity(meshes.len());
+        for (i, (pos, mesh)) in meshes.iter().enumerate() {
+            let i = i as u32;
+            match self.batches.last_mut() {
+                Some((pool, _, count)) if *pool == mesh.pool() => *count += 1,
+                _ => self.batches.push((mesh.pool(), i, 1)),
+            }
+            transforms.push(chunk_transform(*pos));
+            let vertices = mesh.vertices();
+            draws.push(DrawIndirect {
+                vertex_count: vertices.end - vertices.start,
+                instance_count: 1,
+                base_vertex: vertices.start,
+                base_instance: i,
+            });
+        }
+
+        let queue = resources.queue();
+        queue.write_buffer(&self.camera, 0, bytemuck::bytes_of(matrices));
+        if !draws.is_empty() {
+            queue.write_buffer(&self.transforms, 0, bytemuck::cast_slice(&transforms));
+            queue.write_buffer(&self.draws, 0, bytemuck::cast_slice(&draws));
+        }
+    }
+
+    /// Records the draws written by the last [`prepare`](Self::prepare).
+    /// Leaves the indirect pipeline bound.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, arena: &'a MeshArena) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(1, &self.bind_group, &[]);
+        let stride = size_of::<DrawIndirect>() as u64;
+        for &(pool, first, count) in &self.batches {
+            pass.set_vertex_buffer(0, arena.buffer(pool).slice(..));
+            if self.multi_draw {
+                pass.multi_draw_indirect(&self.draws, first as u64 * stride, count);
+            } else {
+                for draw in first..first + count {
+                    pass.draw_indirect(&self.draws, draw as u64 * stride);
+                }
+            }
+        }
+    }
+}
+
+fn create_buffers(device: &wgpu::Device, capacity: u32) -> (wgpu::Buffer, wgpu::Buffer) {
+    let transforms = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("chunk_transforms"),
+        size: capacity as u64 * size_of::<Vec4>() as u64,
+        usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let draws = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("chunk_draws"),
+        size: capacity as u64 * size_of::<DrawIndirect>() as u64,
+        usage: wgpu::BufferUsage::INDIRECT | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    (transforms, draws)
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    camera: &wgpu::Buffer,
+    transforms: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("chunk_indirect"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(camera.slice(..)),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer(transforms.slice(..)),
+            },
+        ],
+    })
+}
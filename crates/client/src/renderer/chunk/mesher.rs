@@ -1,12 +1,16 @@
 use std::{iter, ops::Deref, sync::Arc};
 
-use ahash::AHashMap;
-use common::{Chunk, ChunkPos};
+use ahash::{AHashMap, AHashSet};
+use bumpalo::Bump;
+use common::{block, BlockId, Chunk, ChunkPos};
 use crossbeam_queue::SegQueue;
+use glam::Vec3;
 use wgpu::util::DeviceExt;
 
 use crate::{
     asset::{model::YamlModel, Asset, Assets},
+    content::Report,
+    platform,
     renderer::Resources,
 };
 
@@ -17,6 +21,33 @@ mod compile;
 
 pub use algo::RawVertex;
 
+/// Returns the positions of the six chunks adjacent to `pos`
+/// in the order [`ChunkMesher::spawn`] expects them:
+/// +Y, -Y, +X, -X, +Z, then -Z.
+pub fn neighbor_positions(pos: ChunkPos) -> [ChunkPos; 6] {
+    let offset = |x, y, z| ChunkPos {
+        x: pos.x + x,
+        y: pos.y + y,
+        z: pos.z + z,
+    };
+    [
+        offset(0, 1, 0),
+        offset(0, -1, 0),
+        offset(1, 0, 0),
+        offset(-1, 0, 0),
+        offset(0, 0, 1),
+        offset(0, 0, -1),
+    ]
+}
+
+/// The vertices of a meshed chunk. Empty meshes are `None`.
+#[derive(Debug)]
+pub struct ChunkVertices {
+    pub opaque: Option<Vec<RawVertex>>,
+    /// Faces of translucent blocks, like water.
+    pub translucent: Option<Vec<RawVertex>>,
+}
+
 /// A mesh uploaded to the GPU.
 #[derive(Debug)]
 pub struct GpuMesh {
@@ -30,7 +61,10 @@ pub struct GpuMesh {
 ///
 /// Meshing is offloaded to the Rayon thread pool to increase throughput.
 /// Request that a chunk be meshed via `spawn()`, and poll for completed
-/// meshing tasks using `iter_finished()`.
+/// meshing tasks using `iter_finished()`. Chunk meshes are returned
+/// as vertices for the renderer to place in its
+/// [`MeshArena`](super::arena::MeshArena); other meshes, including
+/// those of translucent blocks, get buffers of their own.
 ///
 /// This struct stores immutable state internally: it contains the compiled
 /// block models.
@@ -44,22 +78,7 @@ impl ChunkMesher {
         resources: &Arc<Resources>,
         get_texture_index: impl Fn(&str) -> Option<u32>,
     ) -> anyhow::Result<Self> {
-        let prefix = "model/block/";
-
-        let models: AHashMap<String, Asset<YamlModel>> = assets
-            .iter_prefixed::<YamlModel>(prefix)
-            .map(|(name, model)| {
-                (
-                    name.strip_prefix(prefix)
-                        .expect("prefix")
-                        .strip_suffix(".yml")
-                        .expect("suffix")
-                        .to_owned(),
-                    model,
-                )
-            })
-            .collect();
-
+        let models = block_models(assets);
         let models = compile::compile(
             models.keys().map(String::as_str),
             |model| models.get(model).map(Asset::deref).map(YamlModel::clone),
@@ -74,34 +93,175 @@ impl ChunkMesher {
     }
 
     /// Spawns a meshing task. The generated mesh will be
-    /// returned from [`iter_finished`] at some point in the future.
-    pub fn spawn(&self, pos: ChunkPos, chunk: Chunk) {
+    /// returned from [`iter_finished`] at some point in the future,
+    /// along with `version`. Tasks may finish in any order.
+    ///
+    /// `neighbors` contains the loaded chunks at the positions returned
+    /// by [`neighbor_positions`]. Faces hidden by blocks in them are culled.
+    pub fn spawn(&self, pos: ChunkPos, version: u64, chunk: Chunk, neighbors: [Option<Chunk>; 6]) {
         let mesher = Arc::clone(&self.0);
-        rayon::spawn(move || {
+        platform::spawn(move || {
             utils::THREAD_BUMP.with(|bump| {
                 let mut bump = bump.borrow_mut();
                 {
-                    let mesh = algo::mesh(&mesher.models, &chunk, &bump);
-                    let gpu_mesh = if mesh.vertices.is_empty() {
-                        None
-                    } else {
-                        let label = format!("chunk_mesh_{:?}", pos);
-                        Some(mesher.upload(&label, &mesh))
+                    let mut neighbor_refs = [None; 6];
+                    for (neighbor_ref, neighbor) in neighbor_refs.iter_mut().zip(&neighbors) {
+                        *neighbor_ref = neighbor.as_ref();
+                    }
+                    let mesh = algo::mesh(&mesher.models, &chunk, neighbor_refs, &bump);
+                    let to_vec = |mesh: &algo::Mesh| {
+                        if mesh.vertices.is_empty() {
+                            None
+                        } else {
+                            Some(mesh.vertices.to_vec())
+                        }
+                    };
+                    let vertices = ChunkVertices {
+                        opaque: to_vec(&mesh.opaque),
+                        translucent: to_vec(&mesh.translucent),
                     };
 
-                    mesher.completed.push((pos, gpu_mesh));
+                    mesher.completed.push((pos, version, vertices));
                 }
                 bump.reset();
             });
         });
     }
 
+    /// Creates a mesh outlining the edges of the block at
+    /// the origin with the given texture.
+    pub fn outline_mesh(&self, texture: u32) -> GpuMesh {
+        let bump = Bump::new();
+        let mesh = algo::outline(texture, &bump);
+        self.0.upload("block_outline", &mesh)
+    }
+
+    /// Uploads the vertices of a chunk's mesh to a buffer of its own.
+    pub fn upload_vertices(&self, label: &str, vertices: &[RawVertex]) -> GpuMesh {
+        self.0.upload_vertices(label, vertices)
+    }
+
+    /// Creates a mesh of a single block at the origin, or `None`
+    /// if the block has no opaque faces, like air and water.
+    pub fn block_mesh(&self, block: BlockId) -> Option<GpuMesh> {
+        let mut chunk = Chunk::new();
+        chunk.set(0, 0, 0, block);
+        let bump = Bump::new();
+        let mesh = algo::mesh(&self.0.models, &chunk, [None; 6], &bump).opaque;
+        if mesh.vertices.is_empty() {
+            None
+        } else {
+            let label = format!("block_mesh_{}", block.descriptor().slug());
+            Some(self.0.upload(&label, &mesh))
+        }
+    }
+
+    /// Creates a mesh of cuboids given the offset, size, and
+    /// texture of each, or `None` if there are no cuboids.
+    pub fn cuboids_mesh(
+        &self,
+        label: &str,
+        cuboids: impl IntoIterator<Item = (Vec3, Vec3, u32)>,
+    ) -> Option<GpuMesh> {
+        let bump = Bump::new();
+        let mesh = algo::cuboids(cuboids, &bump);
+        if mesh.vertices.is_empty() {
+            None
+        } else {
+            Some(self.0.upload(label, &mesh))
+        }
+    }
+
+    /// Creates a mesh of flat squares given the center and half-width
+    /// of each, or `None` if there are no squares.
+    pub fn decals_mesh(
+        &self,
+        label: &str,
+        decals: impl IntoIterator<Item = (Vec3, f32)>,
+        texture: u32,
+    ) -> Option<GpuMesh> {
+        let bump = Bump::new();
+        let mesh = algo::decals(decals, texture, &bump);
+        if mesh.vertices.is_empty() {
+            None
+        } else {
+            Some(self.0.upload(label, &mesh))
+        }
+    }
+
     /// Returns an iterator over meshes which have completed.
-    pub fn iter_finished<'a>(&'a self) -> impl Iterator<Item = (ChunkPos, Option<GpuMesh>)> + 'a {
+    pub fn iter_finished<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (ChunkPos, u64, ChunkVertices)> + 'a {
         iter::from_fn(move || self.0.completed.pop())
     }
 }
 
+/// The prefix of the paths of block models.
+const MODEL_PREFIX: &str = "model/block/";
+
+/// Returns the block models in `assets`, keyed by name.
+fn block_models(assets: &Assets) -> AHashMap<String, Asset<YamlModel>> {
+    assets
+        .iter_prefixed::<YamlModel>(MODEL_PREFIX)
+        .map(|(name, model)| {
+            (
+                name.strip_prefix(MODEL_PREFIX)
+                    .expect("prefix")
+                    .strip_suffix(".yml")
+                    .expect("suffix")
+                    .to_owned(),
+                model,
+            )
+        })
+        .collect()
+}
+
+/// Checks that every block state has a model and that every model
+/// compiles using the block textures for which `has_texture` returns
+/// `true`. Problems are added to `report`.
+pub fn validate_models(assets: &Assets, has_texture: impl Fn(&str) -> bool, report: &mut Report) {
+    let models = block_models(assets);
+    let get_model = |name: &str| models.get(name).map(Asset::deref).map(YamlModel::clone);
+    let get_texture_index = |texture: &str| if has_texture(texture) { Some(0) } else { None };
+
+    // Compile models one at a time to find every broken one.
+    let mut names: Vec<&str> = models.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    for name in names {
+        if let Err(e) = compile::compile(iter::once(name), &get_model, &get_texture_index) {
+            report.add(format!("{:#}", e));
+        }
+    }
+
+    let mut checked = AHashSet::new();
+    for state in block::all_states() {
+        let name = algo::model_name(state);
+        if !checked.insert(name) {
+            continue;
+        }
+        let slug = state.descriptor().slug();
+        match models.get(name) {
+            None => report.add(format!(
+                "block '{}' has no model '{}{}.yml'",
+                slug, MODEL_PREFIX, name
+            )),
+            Some(model) if model.is_abstract => {
+                report.add(format!("model '{}' of block '{}' is abstract", name, slug))
+            }
+            Some(_) => {}
+        }
+    }
+
+    if !models.contains_key(algo::UNKNOWN_MODEL) {
+        report.add(format!(
+            "missing fallback model '{}{}.yml'",
+            MODEL_PREFIX,
+            algo::UNKNOWN_MODEL
+        ));
+    }
+}
+
 #[derive(Debug)]
 struct Mesher {
     /// The compiled block models. This maps block slug
@@ -113,13 +273,18 @@ struct Mesher {
 
     resources: Arc<Resources>,
 
-    /// Completed meshes.
-    completed: SegQueue<(ChunkPos, Option<GpuMesh>)>,
+    /// Vertices of completed chunk meshes.
+    completed: SegQueue<(ChunkPos, u64, ChunkVertices)>,
 }
 
 impl Mesher {
     pub fn upload(&self, label: &str, mesh: &algo::Mesh) -> GpuMesh {
-        let vertices: &[u8] = bytemuck::cast_slice(mesh.vertices.as_slice());
+        self.upload_vertices(label, &mesh.vertices)
+    }
+
+    pub fn upload_vertices(&self, label: &str, vertices: &[RawVertex]) -> GpuMesh {
+        let vertex_count = vertices.len() as u32;
+        let vertices: &[u8] = bytemuck::cast_slice(vertices);
         let vertex_buffer =
             self.resources
                 .device()
@@ -131,7 +296,7 @@ impl Mesher {
 
         GpuMesh {
             vertex_buffer,
-            vertex_count: mesh.vertices.len() as u32,
+            vertex_count,
         }
     }
 }
@@ -2,7 +2,13 @@
 
 use ahash::AHashMap;
 use bumpalo::Bump;
-use common::{chunk::CHUNK_DIM, chunk::CHUNK_VOLUME, Chunk};
+use common::{
+    block::Facing,
+    blocks::{Door, Lamp, Trapdoor, Wire},
+    chunk::CHUNK_DIM,
+    chunk::CHUNK_VOLUME,
+    BlockId, Chunk,
+};
 use glam::{Vec2, Vec3, Vec3Swizzles};
 use utils::BitSet;
 
@@ -14,16 +20,37 @@ pub struct Mesh<'bump> {
     pub vertices: Vec<RawVertex, &'bump Bump>,
 }
 
+/// The meshes of a chunk. Translucent blocks, like water, are
+/// meshed separately so they can be drawn after everything else.
+#[derive(Debug)]
+pub struct ChunkMesh<'bump> {
+    pub opaque: Mesh<'bump>,
+    pub translucent: Mesh<'bump>,
+}
+
 impl Mesh<'_> {
-    pub fn push_prism(&mut self, prism: &Prism, offset: Vec3) {
+    pub fn push_prism(&mut self, prism: &Prism, offset: Vec3, visible: [bool; 6]) {
         // TODO: figure out how to move this into a function.
         let offset = offset + vec3(prism.offset);
         let size = vec3(prism.extent);
 
-        self.push_cube(offset, size, prism.textures);
+        self.push_cube_faces(offset, size, prism.textures, visible);
     }
 
     pub fn push_cube(&mut self, offset: Vec3, size: Vec3, textures: [u32; 6]) {
+        self.push_cube_faces(offset, size, textures, [true; 6]);
+    }
+
+    /// Pushes the faces of a cuboid for which `visible` is true.
+    /// Both `textures` and `visible` are indexed by face
+    /// like the textures of a [`Prism`].
+    pub fn push_cube_faces(
+        &mut self,
+        offset: Vec3,
+        size: Vec3,
+        textures: [u32; 6],
+        visible: [bool; 6],
+    ) {
         let x0y0z0 = offset;
         let x1y0z0 = offset + size * glam::vec3(1., 0., 0.);
         let x1y0z1 = offset + size * glam::vec3(1., 0., 1.);
@@ -104,8 +131,12 @@ impl Mesh<'_> {
                 textures[4] as f32,
             ),
         ];
-        for &quad in &quads {
-            self.push_quad(quad);
+        // The face index of each quad.
+        let faces = [1, 0, 3, 2, 5, 4];
+        for (&quad, &face) in quads.iter().zip(&faces) {
+            if visible[face] {
+                self.push_quad(quad);
+            }
         }
     }
 
@@ -156,6 +187,16 @@ struct State<'a> {
     bump: &'a Bump,
 
     mesh: Mesh<'a>,
+    translucent: Mesh<'a>,
+
+    /// The face textures of each palette entry whose model is
+    /// a full, opaque cube, or `None` for other models.
+    cube_textures: Vec<Option<[u32; 6]>, &'a Bump>,
+
+    /// The loaded chunks adjacent to this one, indexed by face
+    /// like the textures of a [`Prism`], along with which
+    /// of their palette entries are full, opaque cubes.
+    neighbors: [Option<(&'a Chunk, Vec<bool, &'a Bump>)>; 6],
 
     /// The blocks which still have to be processed.
     /// Ordered the same way as `Chunk::indexes()`.
@@ -164,9 +205,171 @@ struct State<'a> {
 
 impl<'a> State<'a> {
     pub fn mark_finished(&mut self, pos: [usize; 3]) {
-        let index = pos[1] * CHUNK_DIM * CHUNK_DIM + pos[2] * CHUNK_DIM + pos[0];
-        self.remaining.remove(index as usize);
+        self.remaining.remove(index(pos));
+    }
+
+    /// Returns whether `face` of the block at `pos` is hidden
+    /// because the adjacent block is a full, opaque cube.
+    ///
+    /// Faces on the chunk's boundary are checked against the
+    /// neighboring chunk. If it is not loaded, they are visible.
+    fn is_hidden(&self, pos: [usize; 3], face: usize) -> bool {
+        let (axis, positive) = face_direction(face);
+        let mut adjacent = pos;
+        let on_boundary = if positive {
+            pos[axis] == CHUNK_DIM - 1
+        } else {
+            pos[axis] == 0
+        };
+        if !on_boundary {
+            if positive {
+                adjacent[axis] += 1;
+            } else {
+                adjacent[axis] -= 1;
+            }
+            let palette_index = self
+                .chunk
+                .indexes()
+                .get(index(adjacent))
+                .expect("out of bounds");
+            return self.cube_textures[palette_index as usize].is_some();
+        }
+
+        match &self.neighbors[face] {
+            Some((chunk, full_cubes)) => {
+                adjacent[axis] = if positive { 0 } else { CHUNK_DIM - 1 };
+                let palette_index = chunk.indexes().get(index(adjacent)).expect("out of bounds");
+                full_cubes[palette_index as usize]
+            }
+            None => false,
+        }
     }
+
+    /// Returns the block adjacent to `face` of the block at `pos`, or
+    /// `None` if it is in a neighboring chunk that is not loaded.
+    fn adjacent_block(&self, pos: [usize; 3], face: usize) -> Option<BlockId> {
+        let (axis, positive) = face_direction(face);
+        let mut adjacent = pos;
+        let chunk = if positive && pos[axis] == CHUNK_DIM - 1 {
+            adjacent[axis] = 0;
+            self.neighbors[face].as_ref()?.0
+        } else if !positive && pos[axis] == 0 {
+            adjacent[axis] = CHUNK_DIM - 1;
+            self.neighbors[face].as_ref()?.0
+        } else {
+            if positive {
+                adjacent[axis] += 1;
+            } else {
+                adjacent[axis] -= 1;
+            }
+            self.chunk
+        };
+        Some(chunk.get(adjacent[0], adjacent[1], adjacent[2]))
+    }
+
+    /// Returns which faces of the cuboid from `min` to `max` are
+    /// visible: those not hidden for every block they cover.
+    fn visible_faces(&self, min: [usize; 3], max: [usize; 3]) -> [bool; 6] {
+        let mut visible = [false; 6];
+        for (face, visible) in visible.iter_mut().enumerate() {
+            let (axis, positive) = face_direction(face);
+            let mut slab_min = min;
+            let mut slab_max = max;
+            if positive {
+                slab_min[axis] = max[axis];
+            } else {
+                slab_max[axis] = min[axis];
+            }
+
+            *visible = (slab_min[0]..=slab_max[0]).any(|x| {
+                (slab_min[1]..=slab_max[1])
+                    .any(|y| (slab_min[2]..=slab_max[2]).any(|z| !self.is_hidden([x, y, z], face)))
+            });
+        }
+        visible
+    }
+
+    /// Returns whether the cuboid from `min` to `max` can grow by one
+    /// block in the positive direction along `axis`, updating `textures`
+    /// with the cuboid's new face in that direction if so.
+    ///
+    /// Blocks may join the cuboid if they are full cubes that have not been
+    /// meshed and each of their faces on the boundary of the grown cuboid has
+    /// the texture of that face of the cuboid. Blocks of different kinds can
+    /// therefore be merged if they look the same where they are visible.
+    fn try_extend(
+        &self,
+        min: [usize; 3],
+        max: [usize; 3],
+        axis: usize,
+        textures: &mut [u32; 6],
+    ) -> bool {
+        if max[axis] + 1 >= CHUNK_DIM {
+            return false;
+        }
+
+        let indexes = self.chunk.indexes();
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let mut new_face = None;
+        for i in min[a]..=max[a] {
+            for j in min[b]..=max[b] {
+                let mut pos = [0; 3];
+                pos[axis] = max[axis] + 1;
+                pos[a] = i;
+                pos[b] = j;
+
+                if !self.remaining.contains(index(pos)) {
+                    return false;
+                }
+                let palette_index = indexes.get(index(pos)).expect("out of bounds") as usize;
+                let block_textures = match self.cube_textures[palette_index] {
+                    Some(textures) => textures,
+                    None => return false,
+                };
+
+                // The face in the direction of growth is
+                // exposed for every block in the new slab.
+                let face = block_textures[FACES[axis].0];
+                if *new_face.get_or_insert(face) != face {
+                    return false;
+                }
+                // Faces along the other axes are exposed
+                // for blocks on the cuboid's boundary.
+                for &other in &[a, b] {
+                    let (positive, negative) = FACES[other];
+                    if (pos[other] == max[other] && block_textures[positive] != textures[positive])
+                        || (pos[other] == min[other]
+                            && block_textures[negative] != textures[negative])
+                    {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        textures[FACES[axis].0] = new_face.expect("slab is empty");
+        true
+    }
+}
+
+/// The indexes of the positive and negative faces along each
+/// axis into the textures of a [`Prism`].
+const FACES: [(usize, usize); 3] = [(2, 3), (0, 1), (4, 5)];
+
+/// Returns the axis a face points along and whether
+/// it points in the positive direction.
+fn face_direction(face: usize) -> (usize, bool) {
+    let axis = FACES
+        .iter()
+        .position(|&(positive, negative)| face == positive || face == negative)
+        .expect("invalid face");
+    (axis, face == FACES[axis].0)
+}
+
+/// Returns the index of a block in a chunk, ordered
+/// the same way as `Chunk::indexes()`.
+fn index(pos: [usize; 3]) -> usize {
+    pos[1] * CHUNK_DIM * CHUNK_DIM + pos[2] * CHUNK_DIM + pos[0]
 }
 
 /// Gets the function used to mesh a given block
@@ -184,30 +387,79 @@ impl<'a> State<'a> {
 /// block is meshed using a greedy meshing algorithm. An empty
 /// model uses a no-op function, and a complex model uses
 /// a naive implementation which copies the model's vertices
-/// into the mesh.
+/// into the mesh. The model is first turned to match the
+/// block's orientation. Translucent blocks go in their own mesh.
 
 // TODO: use Box<T, &Bump> once https://github.com/rust-lang/rust/issues/78459 is fixed.
 
 fn mesh_function<'a, 'bump>(
     model: &'a CompiledModel,
-    palette_index: usize,
+    block: BlockId,
     _bump: &'bump Bump,
 ) -> Box<dyn FnMut(&mut State, [usize; 3]) + 'a> {
+    let quarter_turns = quarter_turns(block);
     if model.prisms.is_empty() {
         Box::new(mesh_noop)
+    } else if block.is_translucent() {
+        Box::new(move |state, pos| mesh_translucent(state, pos, block, &model.prisms))
     } else if is_full_cube(model) {
-        Box::new(move |state, pos| mesh_greedy(state, pos, palette_index, &model.prisms[0]))
-    } else {
+        let textures = model.prisms[0].rotated(quarter_turns).textures;
+        Box::new(move |state, pos| mesh_greedy(state, pos, textures))
+    } else if quarter_turns == 0 {
         Box::new(move |state, pos| mesh_naive(state, pos, &model.prisms))
+    } else {
+        let prisms: Vec<Prism> = model
+            .prisms
+            .iter()
+            .map(|prism| prism.rotated(quarter_turns))
+            .collect();
+        Box::new(move |state, pos| mesh_naive(state, pos, &prisms))
     }
 }
 
+/// Returns the name of the model used for a block state.
+///
+/// Most blocks use the model named after their slug. Blocks
+/// whose appearance depends on their state use a model per variant.
+pub(super) fn model_name(block: BlockId) -> &'static str {
+    if let Some(wire) = block.cast::<Wire>() {
+        if wire.power > 0 {
+            return "wire_powered";
+        }
+    } else if let Some(lamp) = block.cast::<Lamp>() {
+        if lamp.lit {
+            return "lamp_lit";
+        }
+    } else if let Some(door) = block.cast::<Door>() {
+        if door.open {
+            return "door_open";
+        }
+    } else if let Some(trapdoor) = block.cast::<Trapdoor>() {
+        if trapdoor.open {
+            return "trapdoor_open";
+        }
+    }
+    block.descriptor().slug()
+}
+
+/// Returns the number of quarter turns by which the model of a
+/// block is rotated. Models of oriented blocks face +Z.
+fn quarter_turns(block: BlockId) -> u8 {
+    block.facing().map_or(0, Facing::quarter_turns)
+}
+
 fn is_full_cube(model: &CompiledModel) -> bool {
     model.prisms.len() == 1
         && model.prisms[0].extent == [64, 64, 64]
         && model.prisms[0].offset == [0, 0, 0]
 }
 
+/// Returns whether a block hides the faces of its neighbors,
+/// i.e. whether its model is a full cube that is not translucent.
+fn is_opaque_cube(model: &CompiledModel, block: BlockId) -> bool {
+    is_full_cube(model) && !block.is_translucent()
+}
+
 /// Mesher function which just clears the block from
 /// the `remaining` set. Effectively a no-op.
 fn mesh_noop(state: &mut State, pos: [usize; 3]) {
@@ -222,91 +474,154 @@ fn mesh_naive(state: &mut State, pos: [usize; 3], prisms: &[Prism]) {
     let offset = Vec3::new(pos[0] as f32, pos[1] as f32, pos[2] as f32);
 
     for prism in prisms {
-        state.mesh.push_prism(prism, offset);
+        // Only faces on the block's boundary can be hidden by a neighbor.
+        let mut visible = [true; 6];
+        for (face, visible) in visible.iter_mut().enumerate() {
+            let (axis, positive) = face_direction(face);
+            let on_boundary = if positive {
+                prism.offset[axis] + prism.extent[axis] == 64
+            } else {
+                prism.offset[axis] == 0
+            };
+            *visible = !on_boundary || !state.is_hidden(pos, face);
+        }
+        state.mesh.push_prism(prism, offset, visible);
     }
 
     state.mark_finished(pos);
 }
 
-/// Mesh function which uses a greedy algorithm
-/// to mesh as many blocks as possible with a single prism.
-///
-/// Only works on full cubes (1x1x1) for now.
-fn mesh_greedy(state: &mut State, pos: [usize; 3], palette_index: usize, prism: &Prism) {
-    // Extend the block in the X, then the Z, then the Y axes.
-    fn index(x: usize, y: usize, z: usize) -> usize {
-        y * CHUNK_DIM * CHUNK_DIM + z * CHUNK_DIM + x
-    }
-
-    let indexes = state.chunk.indexes();
+/// Mesher function for translucent blocks, which copies their
+/// prisms into the translucent mesh. Besides faces hidden by full
+/// cubes, faces against the same block are culled, so only the
+/// surface of a body of water is drawn.
+fn mesh_translucent(state: &mut State, pos: [usize; 3], block: BlockId, prisms: &[Prism]) {
+    let offset = Vec3::new(pos[0] as f32, pos[1] as f32, pos[2] as f32);
 
-    // X
-    let mut x = pos[0];
-    while x + 1 < 16 {
-        let block = indexes.get(index(x + 1, pos[1], pos[2])).unwrap() as usize;
-        if block != palette_index {
-            break;
+    for prism in prisms {
+        let mut visible = [true; 6];
+        for (face, visible) in visible.iter_mut().enumerate() {
+            let (axis, positive) = face_direction(face);
+            let on_boundary = if positive {
+                prism.offset[axis] + prism.extent[axis] == 64
+            } else {
+                prism.offset[axis] == 0
+            };
+            *visible = !on_boundary
+                || !(state.is_hidden(pos, face) || state.adjacent_block(pos, face) == Some(block));
         }
-        x += 1;
+        state.translucent.push_prism(prism, offset, visible);
     }
 
-    // Z
-    let mut z = pos[2];
-    while z + 1 < 16 {
-        let matches = (pos[0]..=x)
-            .all(|x| indexes.get(index(x, pos[1], z + 1)).unwrap() as usize == palette_index);
-        if matches {
-            z += 1;
-        } else {
-            break;
-        }
-    }
+    state.mark_finished(pos);
+}
 
-    // Y
-    let mut y = pos[1];
-    while y + 1 < 16 {
-        let matches = (pos[0]..=x)
-            .flat_map(|x| (pos[2]..=z).map(move |z| (x, z)))
-            .all(|(x, z)| indexes.get(index(x, y + 1, z)).unwrap() as usize == palette_index);
-        if matches {
-            y += 1;
-        } else {
-            break;
+/// Mesh function which uses a greedy algorithm
+/// to mesh as many blocks as possible with a single prism.
+///
+/// Only works on full cubes (1x1x1) for now. Adjacent blocks are
+/// merged even if their kinds differ, as long as their visible
+/// faces have the same textures. See [`State::try_extend`].
+fn mesh_greedy(state: &mut State, pos: [usize; 3], textures: [u32; 6]) {
+    // The textures of the cuboid's faces. Faces in the directions
+    // of growth change as blocks are added.
+    let mut textures = textures;
+    let min = pos;
+    let mut max = pos;
+
+    // Extend the cuboid in the X, then the Z, then the Y axes.
+    for &axis in &[0, 2, 1] {
+        while state.try_extend(min, max, axis, &mut textures) {
+            max[axis] += 1;
         }
     }
 
     // Push final prism to the mesh.
-    let offset = Vec3::new(pos[0] as f32, pos[1] as f32, pos[2] as f32);
+    let offset = Vec3::new(min[0] as f32, min[1] as f32, min[2] as f32);
     let size = Vec3::new(
-        (x - pos[0] + 1) as f32,
-        (y - pos[1] + 1) as f32,
-        (z - pos[2] + 1) as f32,
+        (max[0] - min[0] + 1) as f32,
+        (max[1] - min[1] + 1) as f32,
+        (max[2] - min[2] + 1) as f32,
     );
-    state.mesh.push_cube(offset, size, prism.textures);
+    let visible = state.visible_faces(min, max);
+    state.mesh.push_cube_faces(offset, size, textures, visible);
 
     // Mark processed blocks as finished.
-    for y in pos[1]..=y {
-        for z in pos[2]..=z {
-            for x in pos[0]..=x {
+    for y in min[1]..=max[1] {
+        for z in min[2]..=max[2] {
+            for x in min[0]..=max[0] {
                 state.mark_finished([x, y, z]);
             }
         }
     }
 }
 
-/// Meshes a chunk: converts a volume of blocks to a [`Mesh`].
+/// The model of blocks without one of their own.
+pub(super) const UNKNOWN_MODEL: &str = "unknown";
+
+/// Gets the model of a block.
+fn model<'a>(models: &'a AHashMap<String, CompiledModel>, block: BlockId) -> &'a CompiledModel {
+    models
+        .get(model_name(block))
+        .unwrap_or_else(|| models.get(UNKNOWN_MODEL).expect("missing unknown model"))
+}
+
+/// Meshes a chunk: converts a volume of blocks to a [`ChunkMesh`].
+///
+/// `neighbors` contains the chunks adjacent to `chunk`, indexed by
+/// face like the textures of a [`Prism`]: +Y, -Y, +X, -X, +Z, then -Z.
+/// Faces against full, opaque cubes are culled, including those on
+/// the chunk's boundary if the neighbor on that side is known.
 pub(super) fn mesh<'bump>(
     models: &AHashMap<String, CompiledModel>,
     chunk: &'bump Chunk,
+    neighbors: [Option<&'bump Chunk>; 6],
     bump: &'bump Bump,
-) -> Mesh<'bump> {
+) -> ChunkMesh<'bump> {
     let mesh = Mesh {
         vertices: Vec::new_in(bump),
     };
+    let translucent = Mesh {
+        vertices: Vec::new_in(bump),
+    };
     if chunk.is_empty() {
         // Fast path: the chunk is completely air,
         // so return an empty mesh.
-        return mesh;
+        return ChunkMesh {
+            opaque: mesh,
+            translucent,
+        };
+    }
+
+    let mut palette_models = Vec::new_in(bump);
+    palette_models.extend(chunk.palette().iter().map(|&block| model(models, block)));
+
+    let mut cube_textures = Vec::new_in(bump);
+    cube_textures.extend(
+        palette_models
+            .iter()
+            .zip(chunk.palette())
+            .map(|(model, &block)| {
+                if is_opaque_cube(model, block) {
+                    Some(model.prisms[0].rotated(quarter_turns(block)).textures)
+                } else {
+                    None
+                }
+            }),
+    );
+
+    let mut neighbor_cubes = [None, None, None, None, None, None];
+    for (neighbor, cubes) in neighbors.iter().zip(&mut neighbor_cubes) {
+        if let Some(neighbor) = *neighbor {
+            let mut full_cubes = Vec::new_in(bump);
+            full_cubes.extend(
+                neighbor
+                    .palette()
+                    .iter()
+                    .map(|&block| is_opaque_cube(model(models, block), block)),
+            );
+            *cubes = Some((neighbor, full_cubes));
+        }
     }
 
     let mut remaining = BitSet::new_in(CHUNK_VOLUME, bump);
@@ -315,22 +630,18 @@ pub(super) fn mesh<'bump>(
         chunk,
         bump,
         mesh,
+        translucent,
+        cube_textures,
+        neighbors: neighbor_cubes,
         remaining,
     };
 
     let mut mesh_fns = Vec::new_in(bump);
     mesh_fns.extend(
-        chunk
-            .palette()
+        palette_models
             .iter()
-            .copied()
-            .enumerate()
-            .map(|(i, block)| {
-                let model = models
-                    .get(block.descriptor().slug())
-                    .unwrap_or_else(|| models.get("unknown").expect("missing unknown model"));
-                mesh_function(model, i, bump)
-            }),
+            .zip(chunk.palette())
+            .map(|(&model, &block)| mesh_function(model, block, bump)),
     );
 
     let indexes = chunk.indexes();
@@ -346,7 +657,71 @@ pub(super) fn mesh<'bump>(
         mesh(&mut state, [x, y, z]);
     }
 
-    state.mesh
+    ChunkMesh {
+        opaque: state.mesh,
+        translucent: state.translucent,
+    }
+}
+
+/// Creates a mesh outlining the edges of the block at the origin.
+pub fn outline(texture: u32, bump: &Bump) -> Mesh {
+    const THICKNESS: f32 = 1. / 64.;
+    // Extends the outline slightly past the block's
+    // faces to avoid z-fighting with them.
+    const MARGIN: f32 = 1. / 256.;
+
+    let mut mesh = Mesh {
+        vertices: Vec::new_in(bump),
+    };
+    let min = -MARGIN;
+    let length = 1. + 2. * MARGIN;
+    // Four edges parallel to each axis.
+    for axis in 0..3 {
+        for &(a, b) in &[(0., 0.), (1., 0.), (0., 1.), (1., 1.)] {
+            let mut offset = [min; 3];
+            let mut size = [THICKNESS; 3];
+            size[axis] = length;
+            offset[(axis + 1) % 3] += a * (length - THICKNESS);
+            offset[(axis + 2) % 3] += b * (length - THICKNESS);
+            mesh.push_cube(Vec3::from(offset), Vec3::from(size), [texture; 6]);
+        }
+    }
+    mesh
+}
+
+/// Creates a mesh of cuboids given the offset,
+/// size, and texture of each.
+pub fn cuboids(cuboids: impl IntoIterator<Item = (Vec3, Vec3, u32)>, bump: &Bump) -> Mesh {
+    let mut mesh = Mesh {
+        vertices: Vec::new_in(bump),
+    };
+    for (offset, size, texture) in cuboids {
+        mesh.push_cube(offset, size, [texture; 6]);
+    }
+    mesh
+}
+
+/// Creates a mesh of flat, upward-facing squares given the center
+/// and half-width of each. The texture is stretched across each square.
+pub fn decals(decals: impl IntoIterator<Item = (Vec3, f32)>, texture: u32, bump: &Bump) -> Mesh {
+    let mut mesh = Mesh {
+        vertices: Vec::new_in(bump),
+    };
+    let texture = texture as f32;
+    for (center, half_width) in decals {
+        let vertex = |x: f32, z: f32| RawVertex {
+            pos: center + glam::vec3(x, 0., z) * half_width,
+            texcoord: glam::vec3((x + 1.) / 2., (z + 1.) / 2., texture),
+            normal: Vec3::unit_y(),
+        };
+        mesh.push_quad([
+            vertex(-1., -1.),
+            vertex(-1., 1.),
+            vertex(1., 1.),
+            vertex(1., -1.),
+        ]);
+    }
+    mesh
 }
 
 #[cfg(test)]
@@ -382,10 +757,196 @@ mod tests {
 
         let bump = Bump::new();
         let start = Instant::now();
-        let mesh = mesh(&models, &chunk, &bump);
+        let mesh = mesh(&models, &chunk, [None; 6], &bump);
         println!("Took {:?}", start.elapsed());
         /*let obj = mesh.to_obj();
         fs::write("mesh.obj", obj.as_bytes()).unwrap();*/
         let _ = mesh;
     }
+
+    fn cube(textures: [u32; 6]) -> CompiledModel {
+        CompiledModel {
+            prisms: vec![Prism {
+                offset: [0, 0, 0],
+                extent: [64, 64, 64],
+                textures,
+            }],
+        }
+    }
+
+    /// The number of vertices in a mesh of `n` faces.
+    fn face_vertices(n: usize) -> usize {
+        n * 6
+    }
+
+    #[test]
+    fn merges_blocks_with_matching_textures() {
+        let mut models = AHashMap::new();
+        models.insert("unknown".to_owned(), cube([0; 6]));
+        models.insert("air".to_owned(), CompiledModel { prisms: vec![] });
+        models.insert("stone".to_owned(), cube([1; 6]));
+        models.insert("dirt".to_owned(), cube([1; 6]));
+        // Top, bottom, then sides.
+        models.insert("grass".to_owned(), cube([2, 1, 3, 3, 3, 3]));
+        let bump = Bump::new();
+
+        // Stone and dirt look the same, so a layer
+        // of both is a single cuboid.
+        let mut chunk = Chunk::new();
+        for x in 0..16 {
+            for z in 0..16 {
+                let block = if (x + z) % 2 == 0 {
+                    BlockId::new(blocks::Stone)
+                } else {
+                    BlockId::new(blocks::Dirt)
+                };
+                chunk.set(x, 0, z, block);
+            }
+        }
+        assert_eq!(
+            mesh(&models, &chunk, [None; 6], &bump)
+                .opaque
+                .vertices
+                .len(),
+            face_vertices(6)
+        );
+
+        // Grass has different sides, so it is not merged with
+        // the layer below. The faces between them are hidden.
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set(x, 1, z, BlockId::new(blocks::Grass));
+            }
+        }
+        assert_eq!(
+            mesh(&models, &chunk, [None; 6], &bump)
+                .opaque
+                .vertices
+                .len(),
+            face_vertices(10)
+        );
+    }
+
+    #[test]
+    fn culls_faces_against_neighbors() {
+        let mut models = AHashMap::new();
+        models.insert("unknown".to_owned(), cube([0; 6]));
+        models.insert("air".to_owned(), CompiledModel { prisms: vec![] });
+        let bump = Bump::new();
+
+        let mut solid = Chunk::new();
+        for x in 0..16 {
+            for y in 0..16 {
+                for z in 0..16 {
+                    solid.set(x, y, z, BlockId::new(blocks::Stone));
+                }
+            }
+        }
+        let air = Chunk::new();
+
+        // Without neighbors, the boundary is visible.
+        assert_eq!(
+            mesh(&models, &solid, [None; 6], &bump)
+                .opaque
+                .vertices
+                .len(),
+            face_vertices(6)
+        );
+        // Buried chunks have no faces at all.
+        assert!(mesh(&models, &solid, [Some(&solid); 6], &bump)
+            .opaque
+            .vertices
+            .is_empty());
+        // Only the top is exposed to the air above.
+        let mut neighbors = [Some(&solid); 6];
+        neighbors[0] = Some(&air);
+        assert_eq!(
+            mesh(&models, &solid, neighbors, &bump)
+                .opaque
+                .vertices
+                .len(),
+            face_vertices(1)
+        );
+
+        // A hole on the neighbor's boundary exposes the
+        // face of every block in the cuboid against it.
+        let mut holey = solid.clone();
+        holey.set(0, 5, 5, BlockId::new(blocks::Air));
+        let mut neighbors = [Some(&solid); 6];
+        neighbors[2] = Some(&holey);
+        assert_eq!(
+            mesh(&models, &solid, neighbors, &bump)
+                .opaque
+                .vertices
+                .len(),
+            face_vertices(1)
+        );
+    }
+
+    #[test]
+    fn turns_oriented_blocks() {
+        let mut models = AHashMap::new();
+        models.insert("unknown".to_owned(), cube([0; 6]));
+        models.insert("air".to_owned(), CompiledModel { prisms: vec![] });
+        // Modeled facing +Z, against the -Z side of the block.
+        models.insert(
+            "ladder".to_owned(),
+            CompiledModel {
+                prisms: vec![Prism {
+                    offset: [0, 0, 0],
+                    extent: [64, 64, 8],
+                    textures: [1; 6],
+                }],
+            },
+        );
+        let bump = Bump::new();
+
+        let mut chunk = Chunk::new();
+        chunk.set(
+            5,
+            5,
+            5,
+            BlockId::new(blocks::Ladder {
+                facing: Facing::NegX,
+            }),
+        );
+        let mesh = mesh(&models, &chunk, [None; 6], &bump).opaque;
+        assert_eq!(mesh.vertices.len(), face_vertices(6));
+        // Facing -X, the ladder hangs on the +X side of its block.
+        for vertex in mesh.vertices.iter() {
+            assert!(vertex.pos.x >= 5.875 && vertex.pos.x <= 6.);
+            assert!(vertex.pos.z >= 5. && vertex.pos.z <= 6.);
+        }
+    }
+
+    #[test]
+    fn meshes_water_separately() {
+        let mut models = AHashMap::new();
+        models.insert("unknown".to_owned(), cube([0; 6]));
+        models.insert("air".to_owned(), CompiledModel { prisms: vec![] });
+        models.insert("water".to_owned(), cube([1; 6]));
+        let bump = Bump::new();
+
+        // A pool of water on a stone floor.
+        let mut chunk = Chunk::new();
+        for x in 0..16 {
+            for z in 0..16 {
+                chunk.set(x, 0, z, BlockId::new(blocks::Stone));
+                for y in 1..4 {
+                    chunk.set(x, y, z, BlockId::new(blocks::Water));
+                }
+            }
+        }
+        let mesh = mesh(&models, &chunk, [None; 6], &bump);
+        // The floor is visible through the water.
+        assert_eq!(mesh.opaque.vertices.len(), face_vertices(6));
+        // Faces between water blocks are hidden, leaving
+        // the top and the sides on the chunk's boundary.
+        let columns = 16 * 16;
+        let sides = 4 * 16 * 3;
+        assert_eq!(
+            mesh.translucent.vertices.len(),
+            face_vertices(columns + sides)
+        );
+    }
 }
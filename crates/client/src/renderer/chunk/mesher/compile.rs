@@ -16,7 +16,7 @@ pub struct CompiledModel {
     pub prisms: Vec<Prism>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Prism {
     /// Offset in stops from the block origin of the minimum coordinate.
     pub offset: [u8; 3],
@@ -27,6 +27,36 @@ pub struct Prism {
     pub textures: [u32; 6],
 }
 
+impl Prism {
+    /// Turns this prism about the vertical axis through the center
+    /// of the block, along with its faces. Each quarter turn takes
+    /// +Z to +X, like [`CollisionBox::rotated`](common::block::CollisionBox::rotated).
+    pub fn rotated(&self, quarter_turns: u8) -> Prism {
+        let mut rotated = self.clone();
+        for _ in 0..quarter_turns % 4 {
+            let Prism {
+                offset,
+                extent,
+                textures,
+            } = rotated;
+            rotated = Prism {
+                offset: [offset[2], offset[1], 64 - offset[0] - extent[0]],
+                extent: [extent[2], extent[1], extent[0]],
+                // +Z faces become +X faces, +X faces become -Z faces, and so on.
+                textures: [
+                    textures[0],
+                    textures[1],
+                    textures[4],
+                    textures[5],
+                    textures[3],
+                    textures[2],
+                ],
+            };
+        }
+        rotated
+    }
+}
+
 /// Compiler state to convert `YamlModel`s to `CompiledModel`s.
 struct Compiler;
 
@@ -150,3 +180,31 @@ pub fn compile<'a>(
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_prisms() {
+        // A slab against the -Z side of the block.
+        let prism = Prism {
+            offset: [0, 0, 0],
+            extent: [64, 64, 8],
+            textures: [0, 1, 2, 3, 4, 5],
+        };
+        let turned = prism.rotated(1);
+        assert_eq!(turned.offset, [0, 0, 0]);
+        assert_eq!(turned.extent, [8, 64, 64]);
+        assert_eq!(turned.textures, [0, 1, 4, 5, 3, 2]);
+
+        let turned = prism.rotated(2);
+        assert_eq!(turned.offset, [0, 0, 56]);
+        assert_eq!(turned.extent, [64, 64, 8]);
+        assert_eq!(turned.textures, [0, 1, 3, 2, 5, 4]);
+
+        let turned = prism.rotated(3);
+        assert_eq!(turned.offset, [56, 0, 0]);
+        assert_eq!(prism.rotated(4).textures, prism.textures);
+    }
+}
@@ -1,17 +1,19 @@
-use super::{DEPTH_FORMAT, SAMPLE_COUNT, SC_FORMAT};
+use super::{Resources, DEPTH_FORMAT, SC_FORMAT};
 
 #[derive(Debug)]
 pub struct Presenter {
     sc_desc: wgpu::SwapChainDescriptor,
     sc: wgpu::SwapChain,
-    sample_buffer: wgpu::Texture,
-    sample_buffer_view: wgpu::TextureView,
+    /// Absent if MSAA is disabled.
+    sample_buffer: Option<(wgpu::Texture, wgpu::TextureView)>,
     depth_buffer: wgpu::Texture,
     depth_buffer_view: wgpu::TextureView,
 }
 
 impl Presenter {
-    pub fn new(device: &wgpu::Device, surface: &wgpu::Surface, width: u32, height: u32) -> Self {
+    pub fn new(resources: &Resources, width: u32, height: u32) -> Self {
+        let device = resources.device();
+        let sample_count = resources.capabilities().sample_count;
         let sc_desc = wgpu::SwapChainDescriptor {
             usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
             format: SC_FORMAT,
@@ -19,22 +21,27 @@ impl Presenter {
             height,
             present_mode: wgpu::PresentMode::Immediate,
         };
-        let sc = device.create_swap_chain(&surface, &sc_desc);
+        let sc = device.create_swap_chain(resources.surface(), &sc_desc);
 
-        let sample_buffer = device.create_texture(&wgpu::TextureDescriptor {
-            label: Some("sample_texture"),
-            size: wgpu::Extent3d {
-                width,
-                height,
-                depth: 1,
-            },
-            mip_level_count: 1,
-            sample_count: SAMPLE_COUNT,
-            dimension: wgpu::TextureDimension::D2,
-            format: SC_FORMAT,
-            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
-        });
-        let sample_buffer_view = sample_buffer.create_view(&Default::default());
+        let sample_buffer = if sample_count > 1 {
+            let sample_buffer = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("sample_texture"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: SC_FORMAT,
+                usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
+            });
+            let view = sample_buffer.create_view(&Default::default());
+            Some((sample_buffer, view))
+        } else {
+            None
+        };
 
         let depth_buffer = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("depth_texture"),
@@ -44,7 +51,7 @@ impl Presenter {
                 depth: 1,
             },
             mip_level_count: 1,
-            sample_count: SAMPLE_COUNT,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: DEPTH_FORMAT,
             usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::SAMPLED,
@@ -55,7 +62,6 @@ impl Presenter {
             sc_desc,
             sc,
             sample_buffer,
-            sample_buffer_view,
             depth_buffer,
             depth_buffer_view,
         }
@@ -73,8 +79,9 @@ impl Presenter {
         &mut self.sc
     }
 
-    pub fn sample_buffer(&self) -> &wgpu::TextureView {
-        &self.sample_buffer_view
+    /// Returns the multisampled color buffer, if MSAA is enabled.
+    pub fn sample_buffer(&self) -> Option<&wgpu::TextureView> {
+        self.sample_buffer.as_ref().map(|(_, view)| view)
     }
 
     pub fn depth_buffer(&self) -> &wgpu::TextureView {
@@ -0,0 +1,289 @@
+//! The sky: a gradient from the haze at the horizon up to the zenith,
+//! with the sun and moon, drawn behind the world. Its colors and the
+//! light cast on the world follow the time of day and the weather.
+
+use std::{mem::size_of, num::NonZeroU64};
+
+use common::{time, weather::Weather};
+use glam::{const_vec3, Mat4, Vec3, Vec3A, Vec4};
+
+use crate::{
+    asset::{shader::ShaderAsset, Assets},
+    game::Game,
+};
+
+use super::{Resources, CLEAR_COLOR, DEPTH_FORMAT, SC_FORMAT};
+
+/// The color of the sky at night.
+const NIGHT_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.01,
+    g: 0.015,
+    b: 0.04,
+    a: 1.0,
+};
+/// The color distant geometry fades into during the day.
+const FOG_COLOR: Vec3 = const_vec3!([0.6, 0.7, 0.8]);
+/// The colors of sunlight and moonlight.
+const SUN_COLOR: Vec3 = const_vec3!([1.0, 0.8, 0.5]);
+const MOON_COLOR: Vec3 = const_vec3!([0.2, 0.25, 0.4]);
+/// The ambient light during the day and at night.
+const DAY_AMBIENT: Vec3 = const_vec3!([0.3, 0.24, 0.15]);
+const NIGHT_AMBIENT: Vec3 = const_vec3!([0.12, 0.14, 0.2]);
+/// The color of the moon's disc on a clear night.
+const MOON_DISC_COLOR: Vec3 = const_vec3!([0.8, 0.85, 0.9]);
+
+/// The sky and the light cast on the world in a frame,
+/// which change with the time of day and the weather.
+#[derive(Copy, Clone, Debug)]
+pub struct Sky {
+    /// The color of the sky overhead, which the 3D pass is cleared to.
+    pub color: wgpu::Color,
+    /// The direction towards the sun by day and the moon by night.
+    pub light_dir: Vec3A,
+    pub light_color: Vec3,
+    pub ambient_color: Vec3,
+    /// The color of distant geometry and the sky at the horizon.
+    pub fog_color: Vec3,
+    /// The direction towards the sun, which may be below the horizon.
+    pub sun_dir: Vec3A,
+    /// The colors of the sun and moon discs, black while hidden.
+    pub sun_disc: Vec3,
+    pub moon_disc: Vec3,
+}
+
+impl Sky {
+    pub fn new(game: &Game) -> Self {
+        let time_of_day = game.clock.time_of_day();
+        let daylight = time::daylight(time_of_day);
+        let brightness = weather_brightness(game.weather);
+
+        let sun = time::sun_direction(time_of_day);
+        let light_dir = if sun.y >= 0. { sun } else { -sun };
+        // Direct light fades out as the sun or moon nears the horizon,
+        // so the switch between them is not visible.
+        let strength = (light_dir.y * 4.).min(1.);
+        let light_color = MOON_COLOR.lerp(SUN_COLOR, daylight) * strength * brightness;
+        let ambient_color = NIGHT_AMBIENT.lerp(DAY_AMBIENT, daylight) * brightness;
+        let fog_color = FOG_COLOR * (0.1 + 0.9 * daylight) * brightness;
+
+        // Clouds all but hide the sun and moon.
+        let visibility = if game.weather == Weather::Clear {
+            1.
+        } else {
+            0.15
+        };
+        let sun_height = ((sun.y + 0.05) * 10.).max(0.).min(1.);
+        let sun_disc = SUN_COLOR * sun_height * visibility;
+        let moon_disc = MOON_DISC_COLOR * (1. - daylight) * visibility;
+
+        Self {
+            color: sky_color(game.weather, game.meteors.len(), daylight),
+            light_dir,
+            light_color,
+            ambient_color,
+            fog_color,
+            sun_dir: sun,
+            sun_disc,
+            moon_disc,
+        }
+    }
+}
+
+/// Returns how much the weather dims the sky.
+fn weather_brightness(weather: Weather) -> f32 {
+    match weather {
+        Weather::Clear => 1.,
+        Weather::Rain => 0.6,
+        Weather::Thunder => 0.35,
+    }
+}
+
+/// Returns the color of the sky, which darkens at night, glows
+/// at dawn and dusk, is overcast in rain and thunderstorms, and
+/// is lit up by visible meteors. `daylight` is as returned by
+/// [`time::daylight`].
+fn sky_color(weather: Weather, meteors: usize, daylight: f32) -> wgpu::Color {
+    let brightness = weather_brightness(weather) as f64;
+    let saturation = if weather == Weather::Clear { 1. } else { 0.5 };
+    let daylight = daylight as f64;
+    // Twilight is strongest halfway between day and night.
+    let twilight = daylight * (1. - daylight) * 4.;
+    // Each meteor adds a warm glow, up to a limit.
+    let glow = (meteors as f64 * 0.02).min(0.1);
+
+    let base = |day: f64, night: f64, dusk: f64| night + (day - night) * daylight + dusk * twilight;
+    let r = base(CLEAR_COLOR.r, NIGHT_COLOR.r, 0.3);
+    let g = base(CLEAR_COLOR.g, NIGHT_COLOR.g, 0.1);
+    let b = base(CLEAR_COLOR.b, NIGHT_COLOR.b, 0.);
+    let gray = (r + g + b) / 3.;
+    let channel = |value: f64| (gray + (value - gray) * saturation) * brightness;
+    wgpu::Color {
+        r: channel(r) + glow,
+        g: channel(g) + glow * 0.6,
+        b: channel(b) + glow * 0.2,
+        a: 1.0,
+    }
+}
+
+/// Draws the sky as a triangle covering the screen at the far
+/// plane, before anything else in the 3D pass.
+pub struct SkyRenderer {
+    pipeline: wgpu::RenderPipeline,
+    uniforms: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl SkyRenderer {
+    pub fn new(resources: &Resources, assets: &Assets) -> anyhow::Result<Self> {
+        let device = resources.device();
+        let bg_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sky_bg_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: NonZeroU64::new(size_of::<Uniforms>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sky_pipeline_layout"),
+            bind_group_layouts: &[&bg_layout],
+            push_constant_ranges: &[],
+        });
+
+        let vertex = device.create_shader_module(
+            assets
+                .get::<ShaderAsset>("shader_compiled/sky/vertex.spv")?
+                .to_source(),
+        );
+        let fragment = device.create_shader_module(
+            assets
+                .get::<ShaderAsset>("shader_compiled/sky/fragment.spv")?
+                .to_source(),
+        );
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("sky_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex_stage: wgpu::ProgrammableStageDescriptor {
+                module: &vertex,
+                entry_point: "main",
+            },
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                module: &fragment,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor::default()),
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            color_states: &[wgpu::ColorStateDescriptor {
+                format: SC_FORMAT,
+                color_blend: wgpu::BlendDescriptor::REPLACE,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+            // The sky is behind everything, so it neither
+            // tests against nor writes to the depth buffer.
+            depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilStateDescriptor::default(),
+            }),
+            vertex_state: wgpu::VertexStateDescriptor {
+                index_format: wgpu::IndexFormat::Uint16,
+                vertex_buffers: &[],
+            },
+            sample_count: resources.capabilities().sample_count,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("sky_uniforms"),
+            size: size_of::<Uniforms>() as u64,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sky_bg"),
+            layout: &bg_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(uniforms.slice(..)),
+            }],
+        });
+
+        Ok(Self {
+            pipeline,
+            uniforms,
+            bind_group,
+        })
+    }
+
+    pub fn prep_render(&mut self, resources: &Resources, game: &Game, sky: &Sky) {
+        let matrices = game.matrices();
+        let color =
+            |color: wgpu::Color| Vec4::new(color.r as f32, color.g as f32, color.b as f32, 1.);
+        let uniforms = Uniforms {
+            view: matrices.view,
+            inverse_projection: matrices.projection.inverse(),
+            sun_dir: sky.sun_dir.extend(0.),
+            zenith_color: color(sky.color),
+            horizon_color: sky.fog_color.extend(1.),
+            sun_color: sky.sun_disc.extend(1.),
+            moon_color: sky.moon_disc.extend(1.),
+        };
+        resources
+            .queue()
+            .write_buffer(&self.uniforms, 0, bytemuck::bytes_of(&uniforms));
+    }
+
+    pub fn do_render<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+}
+
+/// The `Sky` uniform block of the sky shaders.
+#[derive(Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
+#[repr(C)]
+struct Uniforms {
+    view: Mat4,
+    inverse_projection: Mat4,
+    sun_dir: Vec4,
+    zenith_color: Vec4,
+    horizon_color: Vec4,
+    sun_color: Vec4,
+    moon_color: Vec4,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sky_darkens_at_night() {
+        let noon = time::daylight(time::NOON);
+        let midnight = time::daylight(time::MIDNIGHT);
+        let sunset = time::daylight(time::SUNSET);
+        let brightness = |color: wgpu::Color| color.r + color.g + color.b;
+
+        let day = sky_color(Weather::Clear, 0, noon);
+        assert!((day.b - CLEAR_COLOR.b).abs() < 1e-6);
+        let night = sky_color(Weather::Clear, 0, midnight);
+        assert!(brightness(night) < brightness(day) / 4.);
+        // Dusk glows red
+        let dusk = sky_color(Weather::Clear, 0, sunset);
+        assert!(dusk.r > day.r && dusk.r > night.r);
+        assert!(brightness(sky_color(Weather::Rain, 0, noon)) < brightness(day));
+    }
+
+    #[test]
+    fn uniforms_match_shader_layout() {
+        // Two matrices and five vectors, as declared in the shaders.
+        assert_eq!(size_of::<Uniforms>(), 208);
+    }
+}
@@ -1,43 +1,61 @@
-use std::mem::size_of;
+use std::alloc::Allocator;
 
 use ahash::AHashMap;
 use glam::{vec2, Mat4, Vec2};
 use utils::Color;
 use voltzui::Canvas;
+use winit::dpi::PhysicalSize;
 
 use crate::{
     asset::{shader::ShaderAsset, Assets},
     game::Game,
+    ui::UiRenderData,
 };
 
-use super::{Resources, SC_FORMAT};
+use super::{utils::DrawParams, Resources, SC_FORMAT};
 
 #[derive(Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
 #[repr(C)]
-struct PushConstants {
+struct Params {
     ortho: Mat4,
     pos: Vec2,
     size: Vec2,
 }
 
 struct Bundle {
-    push_constants: PushConstants,
+    /// The name of the UI, used as the key into the canvas cache.
+    name: &'static str,
+    params: Params,
+}
+
+/// A canvas plus its GPU texture, persisted across frames
+/// for a single UI.
+struct CachedUi {
+    canvas: Canvas,
+    texture: wgpu::Texture,
     bind_group: wgpu::BindGroup,
 }
 
 /// Renderer which blits rendered `voltzui::Ui` canvases
 /// to the present surface.
+///
+/// Each UI gets its own canvas and texture, keyed by the UI's
+/// name. These are reused on subsequent frames and only
+/// redrawn when the UI changes or is resized. Cached canvases
+/// for UIs which are no longer displayed are evicted.
 pub struct UiRenderer {
     pipeline: wgpu::RenderPipeline,
+    params: DrawParams,
     bg_layout: wgpu::BindGroupLayout,
     sampler: wgpu::Sampler,
-    canvas_cache: AHashMap<(u32, u32), Canvas>,
+    canvas_cache: AHashMap<&'static str, CachedUi>,
     /// Cached for current frame.
     bundles: Vec<Bundle>,
 }
 
 impl UiRenderer {
     pub fn new(resources: &Resources, assets: &Assets) -> anyhow::Result<Self> {
+        let params = DrawParams::new::<Params>(resources, "ui_blit");
         let bg_layout =
             resources
                 .device()
@@ -64,7 +82,7 @@ impl UiRenderer {
                 });
 
         let vertex_stage = assets
-            .get::<ShaderAsset>("shader_compiled/blit/vertex.spv")?
+            .get::<ShaderAsset>(&params.vertex_shader("blit"))?
             .to_source();
         let fragment_stage = assets
             .get::<ShaderAsset>("shader_compiled/blit/fragment.spv")?
@@ -73,17 +91,7 @@ impl UiRenderer {
         let vertex_stage = resources.device().create_shader_module(vertex_stage);
         let fragment_stage = resources.device().create_shader_module(fragment_stage);
 
-        let pipeline_layout =
-            resources
-                .device()
-                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                    label: Some("ui_blit"),
-                    bind_group_layouts: &[&bg_layout],
-                    push_constant_ranges: &[wgpu::PushConstantRange {
-                        stages: wgpu::ShaderStage::VERTEX,
-                        range: 0..(size_of::<Vec2>() * 2 + size_of::<Mat4>()) as u32,
-                    }],
-                });
+        let pipeline_layout = params.create_pipeline_layout(resources.device(), &[&bg_layout]);
         let pipeline = resources
             .device()
             .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -136,6 +144,7 @@ impl UiRenderer {
         Ok(Self {
             bg_layout,
             pipeline,
+            params,
             sampler,
             canvas_cache: AHashMap::new(),
             bundles: Vec::new(),
@@ -144,95 +153,135 @@ impl UiRenderer {
 
     pub fn prep_render(&mut self, resources: &Resources, game: &mut Game) {
         let size = game.window().inner_size();
-        let ortho = Mat4::orthographic_lh(0., size.width as f32, size.height as f32, 0., 0., 1.);
-
         let mut uis = Vec::new_in(game.bump());
         let mut store = game.ui_store();
         store.finish_frame(&mut uis);
+        self.prepare_uis(resources, size, uis);
+    }
+
+    /// Redraws and uploads the canvases of UIs
+    /// to be rendered this frame.
+    pub fn prepare_uis<A: Allocator>(
+        &mut self,
+        resources: &Resources,
+        size: PhysicalSize<u32>,
+        uis: Vec<UiRenderData, A>,
+    ) {
+        let ortho = Mat4::orthographic_lh(0., size.width as f32, size.height as f32, 0., 0., 1.);
+
+        // Evict canvases of UIs which are no longer displayed.
+        self.canvas_cache
+            .retain(|name, _| uis.iter().any(|ui| ui.name == *name));
 
         self.bundles.clear();
+        self.params.reserve(resources.device(), uis.len() as u32);
         for ui in uis {
             let width = ui.width.resolve(size.width as f32) as u32;
             let height = ui.height.resolve(size.height as f32) as u32;
-            let canvas = self
-                .canvas_cache
-                .entry((width, height))
-                .or_insert_with(|| Canvas::new(width, height, 1.));
 
-            canvas.clear(Color::rgba(0., 0., 0., 0.));
-            ui.ui.render(canvas);
+            let resized = match self.canvas_cache.get(ui.name) {
+                Some(cached) => {
+                    cached.canvas.pixel_width() != width || cached.canvas.pixel_height() != height
+                }
+                None => true,
+            };
+            if resized {
+                let cached = self.create_cached_ui(resources, width, height);
+                self.canvas_cache.insert(ui.name, cached);
+            }
+            let cached = self.canvas_cache.get_mut(ui.name).expect("inserted above");
+
+            if resized || ui.ui.needs_redraw() {
+                cached.canvas.clear(Color::rgba(0., 0., 0., 0.));
+                ui.ui.render(&mut cached.canvas);
+                upload_canvas(resources, &cached.canvas, &cached.texture);
+            }
 
-            let size = wgpu::Extent3d {
+            self.bundles.push(Bundle {
+                name: ui.name,
+                params: Params {
+                    ortho,
+                    pos: ui.pos,
+                    size: vec2(cached.canvas.width(), cached.canvas.height()),
+                },
+            });
+        }
+    }
+
+    fn create_cached_ui(&self, resources: &Resources, width: u32, height: u32) -> CachedUi {
+        let canvas = Canvas::new(width, height, 1.);
+        let texture = resources.device().create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
                 width: canvas.pixel_width(),
                 height: canvas.pixel_height(),
                 depth: 1,
-            };
-            let texture = resources.device().create_texture(&wgpu::TextureDescriptor {
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+        let bind_group = resources
+            .device()
+            .create_bind_group(&wgpu::BindGroupDescriptor {
                 label: None,
-                size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+                layout: &self.bg_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(
+                            &texture.create_view(&Default::default()),
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
             });
-            resources.queue().write_texture(
-                wgpu::TextureCopyView {
-                    texture: &texture,
-                    mip_level: 0,
-                    origin: wgpu::Origin3d::ZERO,
-                },
-                canvas.data(),
-                wgpu::TextureDataLayout {
-                    offset: 0,
-                    bytes_per_row: 4 * canvas.pixel_width(),
-                    rows_per_image: canvas.pixel_height(),
-                },
-                size,
-            );
-
-            let bind_group = resources
-                .device()
-                .create_bind_group(&wgpu::BindGroupDescriptor {
-                    label: None,
-                    layout: &self.bg_layout,
-                    entries: &[
-                        wgpu::BindGroupEntry {
-                            binding: 0,
-                            resource: wgpu::BindingResource::TextureView(
-                                &texture.create_view(&Default::default()),
-                            ),
-                        },
-                        wgpu::BindGroupEntry {
-                            binding: 1,
-                            resource: wgpu::BindingResource::Sampler(&self.sampler),
-                        },
-                    ],
-                });
 
-            let bundle = Bundle {
-                push_constants: PushConstants {
-                    ortho,
-                    pos: ui.pos,
-                    size: vec2(canvas.width(), canvas.height()),
-                },
-                bind_group,
-            };
-            self.bundles.push(bundle);
+        CachedUi {
+            canvas,
+            texture,
+            bind_group,
         }
     }
 
-    pub fn do_render<'a>(&'a mut self, pass: &mut wgpu::RenderPass<'a>) {
+    pub fn do_render<'a>(&'a mut self, resources: &Resources, pass: &mut wgpu::RenderPass<'a>) {
         pass.set_pipeline(&self.pipeline);
 
         for bundle in &self.bundles {
-            pass.set_bind_group(0, &bundle.bind_group, &[]);
-            pass.set_push_constants(
-                wgpu::ShaderStage::VERTEX,
-                0,
-                bytemuck::cast_slice(&[bundle.push_constants]),
-            );
-            pass.draw(0..6, 0..1);
+            let cached = match self.canvas_cache.get(bundle.name) {
+                Some(c) => c,
+                None => continue,
+            };
+            pass.set_bind_group(0, &cached.bind_group, &[]);
+            if self.params.set(resources.queue(), pass, &bundle.params) {
+                pass.draw(0..6, 0..1);
+            }
         }
     }
 }
+
+fn upload_canvas(resources: &Resources, canvas: &Canvas, texture: &wgpu::Texture) {
+    resources.queue().write_texture(
+        wgpu::TextureCopyView {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        canvas.data(),
+        wgpu::TextureDataLayout {
+            offset: 0,
+            bytes_per_row: 4 * canvas.pixel_width(),
+            rows_per_image: canvas.pixel_height(),
+        },
+        wgpu::Extent3d {
+            width: canvas.pixel_width(),
+            height: canvas.pixel_height(),
+            depth: 1,
+        },
+    );
+}
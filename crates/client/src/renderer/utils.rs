@@ -1,7 +1,11 @@
 //! Assorted rendering utilities.
 
+pub mod draw_params;
+pub mod mipmap;
 pub mod scaler;
 pub mod texture_array;
 
+pub use draw_params::DrawParams;
+pub use mipmap::MipmapGenerator;
 pub use scaler::TextureScaler;
 pub use texture_array::TextureArray;
@@ -0,0 +1,201 @@
+use std::{cell::Cell, mem::size_of, num::NonZeroU64};
+
+use crate::renderer::Resources;
+
+/// Dynamic offsets into uniform buffers must be aligned to this.
+const SLOT_ALIGNMENT: u32 = wgpu::BIND_BUFFER_ALIGNMENT as u32;
+
+/// Passes the parameters of each draw, such as transforms,
+/// to a pipeline's vertex shader.
+///
+/// When the device supports push constants, parameters are pushed
+/// with the draw. Otherwise each draw's parameters are written to
+/// their own slot of a uniform buffer, which is bound at set 1 with
+/// a dynamic offset. That requires the `vertex_uniform.spv` variant
+/// of the vertex shader; see [`DrawParams::vertex_shader`].
+pub struct DrawParams {
+    label: &'static str,
+    /// The size of each draw's parameters in bytes.
+    size: u32,
+    /// Present if push constants are unavailable.
+    uniform: Option<UniformParams>,
+}
+
+struct UniformParams {
+    layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    /// The number of slots in `buffer`.
+    capacity: u32,
+    /// The next unused slot.
+    next: Cell<u32>,
+}
+
+impl DrawParams {
+    /// Creates parameters of type `T` for a pipeline.
+    pub fn new<T: bytemuck::Pod>(resources: &Resources, label: &'static str) -> Self {
+        let size = size_of::<T>() as u32;
+        let uniform = if resources.capabilities().push_constants {
+            None
+        } else {
+            let layout =
+                resources
+                    .device()
+                    .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                        label: Some(label),
+                        entries: &[wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStage::VERTEX,
+                            ty: wgpu::BindingType::UniformBuffer {
+                                dynamic: true,
+                                min_binding_size: NonZeroU64::new(size as u64),
+                            },
+                            count: None,
+                        }],
+                    });
+            let (buffer, bind_group) = create_buffer(resources.device(), &layout, label, size, 1);
+            Some(UniformParams {
+                layout,
+                buffer,
+                bind_group,
+                capacity: 1,
+                next: Cell::new(0),
+            })
+        };
+        Self {
+            label,
+            size,
+            uniform,
+        }
+    }
+
+    /// Returns the asset path of the vertex shader of `shader`
+    /// which receives parameters the way this device supports.
+    pub fn vertex_shader(&self, shader: &str) -> String {
+        let variant = if self.uniform.is_some() {
+            "vertex_uniform"
+        } else {
+            "vertex"
+        };
+        format!("shader_compiled/{}/{}.spv", shader, variant)
+    }
+
+    /// Creates a pipeline layout with `bind_group_layouts`
+    /// followed by whatever passing the parameters requires.
+    pub fn create_pipeline_layout(
+        &self,
+        device: &wgpu::Device,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+    ) -> wgpu::PipelineLayout {
+        let mut layouts = bind_group_layouts.to_vec();
+        let mut push_constant_ranges = Vec::new();
+        match &self.uniform {
+            Some(uniform) => layouts.push(&uniform.layout),
+            None => push_constant_ranges.push(wgpu::PushConstantRange {
+                stages: wgpu::ShaderStage::VERTEX,
+                range: 0..self.size,
+            }),
+        }
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(self.label),
+            bind_group_layouts: &layouts,
+            push_constant_ranges: &push_constant_ranges,
+        })
+    }
+
+    /// Prepares for up to `draws` calls to [`DrawParams::set`] whose
+    /// commands are submitted together. Slots set before are reused.
+    pub fn reserve(&mut self, device: &wgpu::Device, draws: u32) {
+        let label = self.label;
+        let size = self.size;
+        if let Some(uniform) = &mut self.uniform {
+            uniform.next.set(0);
+            if draws > uniform.capacity {
+                let capacity = draws.next_power_of_two();
+                let (buffer, bind_group) =
+                    create_buffer(device, &uniform.layout, label, size, capacity);
+                uniform.buffer = buffer;
+                uniform.bind_group = bind_group;
+                uniform.capacity = capacity;
+            }
+        }
+    }
+
+    /// Sets the parameters of the next draw in `pass`.
+    ///
+    /// Returns `false` if all reserved slots are used, in which
+    /// case the draw must be skipped.
+    #[must_use]
+    pub fn set<'a, T: bytemuck::Pod>(
+        &'a self,
+        queue: &wgpu::Queue,
+        pass: &mut wgpu::RenderPass<'a>,
+        params: &T,
+    ) -> bool {
+        debug_assert_eq!(size_of::<T>() as u32, self.size);
+        let uniform = match &self.uniform {
+            Some(uniform) => uniform,
+            None => {
+                pass.set_push_constants(
+                    wgpu::ShaderStage::VERTEX,
+                    0,
+                    bytemuck::cast_slice(std::slice::from_ref(params)),
+                );
+                return true;
+            }
+        };
+
+        let slot = uniform.next.get();
+        if slot >= uniform.capacity {
+            log::warn!("Out of '{}' draw parameter slots", self.label);
+            return false;
+        }
+        uniform.next.set(slot + 1);
+
+        let offset = slot * slot_size(self.size);
+        queue.write_buffer(&uniform.buffer, offset as u64, bytemuck::bytes_of(params));
+        pass.set_bind_group(1, &uniform.bind_group, &[offset]);
+        true
+    }
+}
+
+/// Returns the stride of slots holding parameters of `size` bytes.
+fn slot_size(size: u32) -> u32 {
+    (size + SLOT_ALIGNMENT - 1) / SLOT_ALIGNMENT * SLOT_ALIGNMENT
+}
+
+fn create_buffer(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    label: &str,
+    size: u32,
+    capacity: u32,
+) -> (wgpu::Buffer, wgpu::BindGroup) {
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: (slot_size(size) * capacity) as u64,
+        usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some(label),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(buffer.slice(..size as u64)),
+        }],
+    });
+    (buffer, bind_group)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slots_are_aligned() {
+        assert_eq!(slot_size(80), 256);
+        assert_eq!(slot_size(256), 256);
+        assert_eq!(slot_size(300), 512);
+    }
+}
@@ -0,0 +1,189 @@
+use std::num::NonZeroU32;
+
+use glam::{vec2, Mat4, Vec2};
+
+use crate::{
+    asset::{shader::ShaderAsset, Assets},
+    renderer::Resources,
+};
+
+use super::DrawParams;
+
+/// Generates mipmaps on the GPU.
+///
+/// Each mip level is rendered from the one above it by drawing
+/// it at half size with linear filtering, using the UI blit
+/// shaders. Textures must have the `Bgra8UnormSrgb` format and
+/// the `SAMPLED` and `OUTPUT_ATTACHMENT` usages.
+pub struct MipmapGenerator {
+    bg_layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    sampler: wgpu::Sampler,
+    params: DrawParams,
+}
+
+#[derive(Copy, Clone, bytemuck::Zeroable, bytemuck::Pod)]
+#[repr(C)]
+struct Params {
+    ortho: Mat4,
+    pos: Vec2,
+    size: Vec2,
+}
+
+impl MipmapGenerator {
+    pub fn new(resources: &Resources, assets: &Assets) -> anyhow::Result<Self> {
+        let device = resources.device();
+        let params = DrawParams::new::<Params>(resources, "mipmap");
+        let bg_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("mipmap_source"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    ty: wgpu::BindingType::SampledTexture {
+                        dimension: wgpu::TextureViewDimension::D2,
+                        component_type: wgpu::TextureComponentType::Float,
+                        multisampled: false,
+                    },
+                    count: None,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    ty: wgpu::BindingType::Sampler { comparison: false },
+                    count: None,
+                    visibility: wgpu::ShaderStage::FRAGMENT,
+                },
+            ],
+        });
+
+        let vertex_stage = assets
+            .get::<ShaderAsset>(&params.vertex_shader("blit"))?
+            .to_source();
+        let fragment_stage = assets
+            .get::<ShaderAsset>("shader_compiled/blit/fragment.spv")?
+            .to_source();
+        let vertex_stage = device.create_shader_module(vertex_stage);
+        let fragment_stage = device.create_shader_module(fragment_stage);
+
+        let pipeline_layout = params.create_pipeline_layout(device, &[&bg_layout]);
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("mipmap"),
+            layout: Some(&pipeline_layout),
+            vertex_stage: wgpu::ProgrammableStageDescriptor {
+                module: &vertex_stage,
+                entry_point: "main",
+            },
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
+                module: &fragment_stage,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor::default()),
+            primitive_topology: wgpu::PrimitiveTopology::TriangleList,
+            color_states: &[wgpu::ColorStateDescriptor {
+                format: wgpu::TextureFormat::Bgra8UnormSrgb,
+                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                color_blend: wgpu::BlendDescriptor::REPLACE,
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+            depth_stencil_state: None,
+            vertex_state: wgpu::VertexStateDescriptor {
+                index_format: wgpu::IndexFormat::Uint16,
+                vertex_buffers: &[],
+            },
+            sample_count: 1,
+            sample_mask: !0,
+            alpha_to_coverage_enabled: false,
+        });
+
+        // Linear filtering averages the 2x2 texels
+        // covering each texel of the next level.
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("mipmap_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: 0.,
+            lod_max_clamp: 100.,
+            compare: None,
+            anisotropy_clamp: None,
+        });
+
+        Ok(Self {
+            bg_layout,
+            pipeline,
+            sampler,
+            params,
+        })
+    }
+
+    /// Records passes to generate mip levels `1..num_levels` of
+    /// `array_layer` in `texture` from mip level 0.
+    pub fn generate(
+        &mut self,
+        resources: &Resources,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        array_layer: u32,
+        num_levels: u32,
+    ) {
+        let view = |mip_level| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("mipmap_level"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_mip_level: mip_level,
+                level_count: NonZeroU32::new(1),
+                base_array_layer: array_layer,
+                array_layer_count: NonZeroU32::new(1),
+                ..Default::default()
+            })
+        };
+        let params = Params {
+            ortho: Mat4::orthographic_lh(0., 1., 1., 0., 0., 1.),
+            pos: Vec2::zero(),
+            size: vec2(1., 1.),
+        };
+
+        // Every level is drawn with the same parameters, so slots still
+        // pending from the previous call can be overwritten.
+        let device = resources.device();
+        self.params.reserve(device, num_levels);
+        for level in 1..num_levels {
+            let source = view(level - 1);
+            let target = view(level);
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("mipmap_source"),
+                layout: &self.bg_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&source),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: &target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            if self.params.set(resources.queue(), &mut pass, &params) {
+                pass.draw(0..6, 0..1);
+            }
+        }
+    }
+}
@@ -1,12 +1,10 @@
-use std::borrow::Cow;
-
 use anyhow::{anyhow, bail};
 pub use tiny_skia::FilterQuality;
 use tiny_skia::{Canvas, Pixmap, PixmapPaint};
 
 /// Performs scaling (upsampling or downsampling)
-/// on textures. Also able to generate mipmaps with
-/// high quality cubic filtering.
+/// on textures on the CPU. Mipmaps are generated
+/// on the GPU by a [`MipmapGenerator`](super::MipmapGenerator).
 pub struct TextureScaler;
 
 impl TextureScaler {
@@ -51,63 +49,6 @@ impl TextureScaler {
 
         Ok(output.pixmap.take())
     }
-
-    /// Generates mipmaps and writes them to the given GPU texture.
-    ///
-    /// Mip level 0 is taken from `texture`. This function will write
-    /// mipmap levels `0..num_levels` to the `target`. Uses bicubic
-    /// filtering for maximum quality mipmaps.
-    pub fn generate_mipmaps(
-        &mut self,
-        texture: &[u8],
-        width: u32,
-        height: u32,
-        num_levels: u32,
-        target: &wgpu::Texture,
-        array_layer: u32,
-        queue: &wgpu::Queue,
-    ) -> anyhow::Result<()> {
-        for level in 0..num_levels {
-            let mip_width = width / 2u32.pow(level);
-            let mip_height = height / 2u32.pow(level);
-            let data = if level == 0 {
-                Cow::Borrowed(texture)
-            } else {
-                Cow::Owned(self.scale(
-                    texture,
-                    width,
-                    height,
-                    mip_width,
-                    mip_height,
-                    FilterQuality::Bicubic,
-                )?)
-            };
-            queue.write_texture(
-                wgpu::TextureCopyView {
-                    texture: target,
-                    mip_level: level,
-                    origin: wgpu::Origin3d {
-                        x: 0,
-                        y: 0,
-                        z: array_layer,
-                    },
-                },
-                &data,
-                wgpu::TextureDataLayout {
-                    offset: 0,
-                    bytes_per_row: 4 * mip_width,
-                    rows_per_image: mip_height,
-                },
-                wgpu::Extent3d {
-                    width: mip_width,
-                    height: mip_height,
-                    depth: 1,
-                },
-            );
-        }
-
-        Ok(())
-    }
 }
 
 #[cfg(test)]
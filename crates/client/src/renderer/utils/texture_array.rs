@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::renderer::Resources;
 
-use super::TextureScaler;
+use super::MipmapGenerator;
 
 pub type Index = u32;
 
@@ -57,24 +57,26 @@ impl TextureArray {
         index
     }
 
-    /// Adds a texture to the array, generating mipmaps using a [`TextureScaler`](super::TextureScaler).
+    /// Adds a texture to the array, generating its mipmaps on the GPU.
+    ///
+    /// The array must have been created with the `OUTPUT_ATTACHMENT` usage.
     pub fn add_mipmapped(
         &mut self,
         texture: &[u8],
         queue: &wgpu::Queue,
         encoder: &mut wgpu::CommandEncoder,
-    ) -> anyhow::Result<Index> {
+        mipmaps: &mut MipmapGenerator,
+    ) -> Index {
         let index = self.allocate_index(encoder);
-        TextureScaler::new().generate_mipmaps(
-            texture,
-            self.desc.size.width,
-            self.desc.size.height,
-            self.desc.mip_level_count,
+        self.upload_texture(texture, queue, index);
+        mipmaps.generate(
+            &self.resources,
+            encoder,
             &self.texture,
             index,
-            queue,
-        )?;
-        Ok(index)
+            self.desc.mip_level_count,
+        );
+        index
     }
 
     fn upload_texture(&self, texture: &[u8], queue: &wgpu::Queue, index: Index) {
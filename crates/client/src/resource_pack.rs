@@ -0,0 +1,214 @@
+//! Downloading the resource packs required by servers.
+//!
+//! A server names its pack in `ServerInfo`; see
+//! [`protocol::resource_pack`]. The client downloads the archive on a
+//! background thread while the loading screen shows the progress, then
+//! [mounts](crate::Client) it over the local asset directories.
+//!
+//! Packs are cached, unpacked, in `resource_packs/<hash>` below
+//! `VOLTZ_CACHE_DIR`, or `cache` if it is unset. An archive is only
+//! unpacked there once its hash has been verified, so a cached pack is
+//! used without downloading or checking it again.
+
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+use anyhow::{anyhow, bail, Context};
+use protocol::resource_pack::{PackHash, ResourcePack};
+
+/// The largest archive we download, in bytes.
+/// Guards against servers filling the disk.
+const MAX_PACK_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Returns the directory holding cached resource packs.
+pub fn cache_dir() -> PathBuf {
+    crate::platform::cache_dir().join("resource_packs")
+}
+
+/// A resource pack being fetched on a background thread.
+pub struct PackDownload {
+    /// The bits of the percentage downloaded, as an `f32`.
+    percent: Arc<AtomicU32>,
+    result: Receiver<anyhow::Result<PathBuf>>,
+}
+
+impl PackDownload {
+    /// Starts fetching `pack` into the cache at `cache_dir`.
+    pub fn start(cache_dir: PathBuf, pack: ResourcePack) -> Self {
+        let percent = Arc::new(AtomicU32::new(0f32.to_bits()));
+        let (sender, result) = mpsc::channel();
+        let thread_percent = Arc::clone(&percent);
+        thread::Builder::new()
+            .name("resource-pack-download".to_owned())
+            .spawn(move || {
+                let result = fetch(&cache_dir, &pack, |percent| {
+                    thread_percent.store(percent.to_bits(), Ordering::Relaxed)
+                });
+                sender.send(result).ok();
+            })
+            .expect("failed to spawn download thread");
+        Self { percent, result }
+    }
+
+    /// Returns how much of the archive has been downloaded, from 0 to 100.
+    pub fn percent(&self) -> f32 {
+        f32::from_bits(self.percent.load(Ordering::Relaxed))
+    }
+
+    /// Returns the directory of the unpacked pack once it has been
+    /// fetched, or `None` while the download is in progress.
+    pub fn poll(&self) -> Option<anyhow::Result<PathBuf>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(anyhow!("the download thread panicked"))),
+        }
+    }
+}
+
+/// Returns the directory of `pack` in the cache at `cache_dir`,
+/// downloading and unpacking the pack there unless it is cached.
+/// Calls `on_progress` with the percentage downloaded.
+pub fn fetch(
+    cache_dir: &Path,
+    pack: &ResourcePack,
+    on_progress: impl FnMut(f32),
+) -> anyhow::Result<PathBuf> {
+    let dir = cache_dir.join(pack.hash.to_hex());
+    if dir.is_dir() {
+        log::info!("Using cached resource pack {}", pack.hash);
+        return Ok(dir);
+    }
+
+    log::info!("Downloading resource pack from {}", pack.url);
+    let data = download(&pack.url, on_progress)
+        .with_context(|| format!("failed to download '{}'", pack.url))?;
+    let hash = PackHash::of(&data);
+    if hash != pack.hash {
+        bail!(
+            "the resource pack's hash is {}, but the server expects {}",
+            hash,
+            pack.hash
+        );
+    }
+
+    // Unpack next to the final directory, so that an
+    // interrupted unpack never looks like a cached pack.
+    let partial = cache_dir.join(format!("{}.partial", pack.hash.to_hex()));
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+    fs::create_dir_all(&partial)
+        .with_context(|| format!("failed to create '{}'", partial.display()))?;
+    tar::Archive::new(data.as_slice())
+        .unpack(&partial)
+        .context("failed to unpack the resource pack")?;
+    fs::rename(&partial, &dir)?;
+    Ok(dir)
+}
+
+fn download(url: &str, mut on_progress: impl FnMut(f32)) -> anyhow::Result<Vec<u8>> {
+    if let Some(path) = url.strip_prefix("file://") {
+        let data = fs::read(path)?;
+        on_progress(100.);
+        return Ok(data);
+    }
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        bail!("unsupported URL scheme");
+    }
+
+    let response = ureq::get(url).timeout_connect(10_000).call();
+    if let Some(e) = response.synthetic_error() {
+        bail!("{}", e);
+    }
+    if !response.ok() {
+        bail!(
+            "the server answered {} {}",
+            response.status(),
+            response.status_text()
+        );
+    }
+    let len = response
+        .header("Content-Length")
+        .and_then(|len| len.parse::<u64>().ok());
+    if len.map_or(false, |len| len > MAX_PACK_SIZE) {
+        bail!("the pack is larger than {} bytes", MAX_PACK_SIZE);
+    }
+
+    let mut reader = response.into_reader().take(MAX_PACK_SIZE + 1);
+    let mut data = Vec::with_capacity(len.unwrap_or(0) as usize);
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        data.extend_from_slice(&buffer[..read]);
+        if let Some(len) = len {
+            on_progress((data.len() as f32 / len as f32 * 100.).min(100.));
+        }
+    }
+    if data.len() as u64 > MAX_PACK_SIZE {
+        bail!("the pack is larger than {} bytes", MAX_PACK_SIZE);
+    }
+    on_progress(100.);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn archive() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let data = b"texture";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "texture/block/custom.png", &data[..])
+            .unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn fetches_verified_packs_into_cache() {
+        let dir = env::temp_dir().join(format!("voltz-pack-test-{}", std::process::id()));
+        let cache = dir.join("cache");
+        let archive_path = dir.join("pack.tar");
+        fs::create_dir_all(&dir).unwrap();
+        let data = archive();
+        fs::write(&archive_path, &data).unwrap();
+
+        let mut pack = ResourcePack {
+            url: format!("file://{}", archive_path.display()),
+            hash: PackHash::of(b"something else"),
+        };
+        assert!(fetch(&cache, &pack, |_| ()).is_err());
+        assert!(!cache.join(pack.hash.to_hex()).exists());
+
+        pack.hash = PackHash::of(&data);
+        let unpacked = fetch(&cache, &pack, |_| ()).unwrap();
+        assert_eq!(
+            fs::read(unpacked.join("texture/block/custom.png")).unwrap(),
+            b"texture"
+        );
+
+        // Cached packs aren't downloaded again
+        fs::remove_file(&archive_path).unwrap();
+        assert_eq!(fetch(&cache, &pack, |_| ()).unwrap(), unpacked);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
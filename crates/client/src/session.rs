@@ -0,0 +1,400 @@
+//! Sessions: the time spent connected to one world.
+//!
+//! Everything tied to a world lives in a [`Session`]: the [`Game`], the
+//! [`Connection`], the systems, and the integrated server hosting
+//! singleplayer worlds. Before a session starts, a [`Login`] shows the
+//! loading screen until the server lets the player join.
+//!
+//! If the server requires a resource pack, the login downloads it and
+//! hands it to the client to mount before the player joins; see the
+//! [`resource_pack`](crate::resource_pack) module.
+//!
+//! The window, renderer, and assets outlive sessions. Ending a session
+//! disconnects from the server, stops the integrated server (which saves
+//! the world), and clears the renderer's world state, so the client can
+//! join another world without restarting.
+//!
+//! Integrated servers only run in native builds; see the
+//! [`platform`](crate::platform) module.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    path::PathBuf,
+    thread::{self, JoinHandle},
+};
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{bail, Context};
+use bumpalo::Bump;
+#[cfg(not(target_arch = "wasm32"))]
+use common::block;
+use common::{entity::Vel, Orient, Pos, SystemExecutor};
+use physics::FallDistance;
+#[cfg(not(target_arch = "wasm32"))]
+use protocol::{
+    bridge,
+    features::Features,
+    packets::{
+        client::{ClientInfo, ResourcePackLoaded},
+        server::WorldgenProgress,
+    },
+};
+use protocol::{
+    bridge::ToServer,
+    packets::{server::JoinGame, shared::Disconnect, ClientPacket, ServerPacket, SharedPacket},
+    registry::RegistryMap,
+    resource_pack::ResourcePack,
+    Bridge, PROTOCOL_VERSION,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use server::{command::Console, Server};
+use voltzui::Theme;
+use winit::window::Window;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::resource_pack::{self, PackDownload};
+use crate::{
+    asset::Assets, chunk_cache::ChunkCache, conn::Connection, diagnostics::LagSpikeMonitor,
+    game::Game, loading::LoadingScreen, renderer::Renderer, settings::Settings, PLAYER_BBOX,
+};
+
+/// A server running on a thread of the client.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct IntegratedServer {
+    console: Console,
+    thread: JoinHandle<()>,
+}
+
+/// Web builds can't run an integrated server.
+#[cfg(target_arch = "wasm32")]
+pub enum IntegratedServer {}
+
+#[cfg(target_arch = "wasm32")]
+impl IntegratedServer {
+    pub fn stop(self) {
+        match self {}
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl IntegratedServer {
+    /// Launches a server for the world saved in `save_dir`, returning it
+    /// along with the bridge to its only client. The server accepts
+    /// commands from `console`.
+    pub fn launch(
+        renderer: &Renderer,
+        save_dir: PathBuf,
+        console: Console,
+    ) -> anyhow::Result<(Self, Bridge<ToServer>)> {
+        let (client_bridge, server_bridge) = bridge::singleplayer();
+        let conn = server::Connection::new(server_bridge);
+        // World generation shaders need push constants.
+        let backend = if renderer.capabilities().push_constants {
+            server::Backend::Gpu {
+                device: Arc::clone(renderer.device_arc()),
+                queue: Arc::clone(renderer.queue_arc()),
+            }
+        } else {
+            log::warn!("Generating the world on the CPU since the adapter lacks push constants");
+            server::Backend::Cpu
+        };
+
+        let server_console = console.clone();
+        let thread = thread::Builder::new()
+            .name("integrated-server".to_owned())
+            .spawn(move || {
+                let mut server = Server::singleplayer(conn, backend, save_dir);
+                server.set_console(server_console);
+                server.run();
+            })?;
+        Ok((Self { console, thread }, client_bridge))
+    }
+
+    /// Stops the server and waits until it has saved the world.
+    pub fn stop(self) {
+        log::info!("Stopping the integrated server");
+        self.console.send("stop");
+        if self.thread.join().is_err() {
+            log::error!("The integrated server panicked");
+        }
+    }
+}
+
+/// Logging in to a world. Shows the loading
+/// screen until the player joins the game.
+pub struct Login {
+    bridge: Bridge<ToServer>,
+    server: Option<IntegratedServer>,
+    loading_screen: LoadingScreen,
+    received_server_info: bool,
+    /// Translates block IDs if the server's registry differs from ours.
+    registry: RegistryMap,
+    /// Present if the server sends chunk hashes for us to look up.
+    chunk_cache: Option<ChunkCache>,
+    settings: Settings,
+    /// The server's resource pack while it downloads.
+    #[cfg(not(target_arch = "wasm32"))]
+    pack_download: Option<PackDownload>,
+}
+
+/// Progress made by a [`Login`] that the client must act on.
+pub enum LoginEvent {
+    /// The server's resource pack was fetched to this directory. The
+    /// client should mount it, then call [`Login::pack_mounted`].
+    #[cfg(not(target_arch = "wasm32"))]
+    MountPack(PathBuf),
+    /// The player may join.
+    Join(JoinGame),
+}
+
+impl Login {
+    /// Starts an integrated server for the world in
+    /// `save_dir` and logs in to it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn singleplayer(
+        assets: &Assets,
+        renderer: &Renderer,
+        theme: Arc<Theme>,
+        save_dir: PathBuf,
+        console: Console,
+        settings: Settings,
+    ) -> anyhow::Result<Self> {
+        let (server, bridge) = IntegratedServer::launch(renderer, save_dir, console)?;
+        // Without a cache, the server's chunk hashes would
+        // only cost a request for each chunk.
+        let mut features = Features::SUPPORTED;
+        if !ChunkCache::is_available() {
+            features = features.difference(Features::CHUNK_CACHE);
+        }
+        log::info!("Connecting to server");
+        bridge.send(ClientPacket::ClientInfo(ClientInfo {
+            protocol_version: PROTOCOL_VERSION,
+            implementation: format!("voltz-client:{}", env!("CARGO_PKG_VERSION")),
+            username: "caelunshun".to_owned(),
+            features,
+            view_distance: settings.view_distance,
+            registry_digest: block::registry_digest(),
+            // The bridge to an integrated server can't be lost.
+            resume_token: None,
+        }));
+
+        Ok(Self {
+            bridge,
+            server: Some(server),
+            loading_screen: LoadingScreen::new(assets, theme)?,
+            received_server_info: false,
+            registry: RegistryMap::identity(),
+            chunk_cache: None,
+            settings,
+            pack_download: None,
+        })
+    }
+
+    /// Handles packets received during the login state and checks on
+    /// the resource pack download. Returns what the client should do next.
+    pub fn poll(&mut self) -> anyhow::Result<Option<LoginEvent>> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(download) = &self.pack_download {
+            match download.poll() {
+                Some(result) => {
+                    self.pack_download = None;
+                    let dir = result.context("failed to fetch the server's resource pack")?;
+                    return Ok(Some(LoginEvent::MountPack(dir)));
+                }
+                None => self.loading_screen.set_progress(WorldgenProgress {
+                    stage: "Downloading resource pack".to_owned(),
+                    percent: download.percent(),
+                }),
+            }
+        }
+
+        for packet in self.bridge.flush_received() {
+            match packet {
+                ServerPacket::WorldgenProgress(progress) => {
+                    self.loading_screen.set_progress(progress)
+                }
+                ServerPacket::ServerInfo(server_info) if !self.received_server_info => {
+                    if server_info.protocol_version != PROTOCOL_VERSION {
+                        bail!(
+                            "server '{}' implements protocol version {}, but we implement version {}",
+                            server_info.implementation,
+                            server_info.protocol_version,
+                            PROTOCOL_VERSION
+                        );
+                    }
+                    log::info!(
+                        "Connected to server '{}' implementing protocol {}.",
+                        server_info.implementation,
+                        server_info.protocol_version
+                    );
+                    log::debug!("Enabled protocol features: {:?}", server_info.features);
+                    self.received_server_info = true;
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if server_info.features.contains(Features::CHUNK_CACHE) {
+                        self.chunk_cache = ChunkCache::open(server_info.world_id);
+                    }
+                    if let Some(pack) = server_info.resource_pack {
+                        self.start_download(pack)?;
+                    }
+                }
+                ServerPacket::RegistrySync(sync) if self.received_server_info => {
+                    self.registry = RegistryMap::new(&sync.blocks)
+                        .context("the server's block registry is incompatible")?;
+                    log::info!("Server's block registry differs; translating block IDs");
+                }
+                ServerPacket::Shared(SharedPacket::Disconnect(disconnect)) => bail!(
+                    "the server disconnected us: {}",
+                    disconnect.reason.as_deref().unwrap_or("no reason given")
+                ),
+                ServerPacket::JoinGame(join_game) if self.received_server_info => {
+                    // Packets after `JoinGame` are left for the game's `Connection`.
+                    log::info!("Received JoinGame: {:?}", join_game);
+                    return Ok(Some(LoginEvent::Join(join_game)));
+                }
+                _ => bail!("invalid packet received during login state"),
+            }
+        }
+
+        if self.bridge.is_disconnected() {
+            bail!("disconnected");
+        }
+        Ok(None)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn start_download(&mut self, pack: ResourcePack) -> anyhow::Result<()> {
+        self.pack_download = Some(PackDownload::start(resource_pack::cache_dir(), pack));
+        Ok(())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn start_download(&mut self, pack: ResourcePack) -> anyhow::Result<()> {
+        bail!(
+            "the server requires the resource pack {}, which web builds can't load",
+            pack.url
+        )
+    }
+
+    /// Tells the server that its resource pack is mounted,
+    /// after which it lets the player join.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pack_mounted(&self) {
+        self.bridge
+            .send(ClientPacket::ResourcePackLoaded(ResourcePackLoaded));
+    }
+
+    pub fn render(&mut self, renderer: &mut Renderer, window: &Window) {
+        self.loading_screen.render(renderer, window);
+    }
+
+    /// Starts the session once the server sent `JoinGame`.
+    pub fn into_session(
+        self,
+        join_game: JoinGame,
+        assets: &Assets,
+        renderer: &mut Renderer,
+        window: Arc<Window>,
+        theme: Arc<Theme>,
+    ) -> anyhow::Result<Session> {
+        let player = (
+            Pos(join_game.pos),
+            Orient(join_game.orient),
+            Vel(join_game.vel),
+            PLAYER_BBOX,
+            FallDistance::default(),
+        );
+        let mut game = Game::new(self.bridge.clone(), player, window, Bump::new());
+        game.ui_store().set_theme(theme);
+        game.settings = self.settings;
+        game.registry = self.registry;
+        game.events().enable_tracing_from_env();
+
+        let systems = crate::setup(assets)?;
+        renderer.start_session(&mut game);
+
+        Ok(Session {
+            conn: Connection::new(self.bridge.clone(), self.chunk_cache),
+            game,
+            systems,
+            server: self.server,
+            lag_spikes: LagSpikeMonitor::from_env(),
+        })
+    }
+
+    /// Gives up logging in, stopping the integrated server.
+    pub fn abort(self) {
+        if let Some(server) = self.server {
+            server.stop();
+        }
+    }
+}
+
+/// Playing in a world.
+pub struct Session {
+    game: Game,
+    conn: Connection,
+    systems: SystemExecutor<Game>,
+    /// Present in singleplayer.
+    server: Option<IntegratedServer>,
+    lag_spikes: LagSpikeMonitor,
+}
+
+impl Session {
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn game_mut(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    /// Runs a frame: handles packets, runs the systems, and renders.
+    pub fn frame(&mut self, renderer: &mut Renderer) {
+        self.game.events().begin_tick();
+        self.game.events().set_system(0);
+        self.game.debug_data.packets_processed = self.conn.handle_packets(&mut self.game);
+        self.game.ui_store().advance(self.game.dt());
+
+        self.systems.run(&mut self.game, |game, system| {
+            game.events().set_system(system + 1)
+        });
+        renderer.render(&mut self.game);
+
+        self.game.bump_mut().reset();
+    }
+
+    /// Called at the end of each frame, which took `elapsed`.
+    pub fn end_frame(&mut self, elapsed: Duration) {
+        self.game.set_dt(elapsed.as_secs_f32());
+        self.lag_spikes
+            .end_frame(elapsed, &self.systems, &mut self.game);
+
+        let grabbed = self.game.is_cursor_grabbed();
+        let window = self.game.window();
+        window.set_cursor_visible(!grabbed);
+        if let Err(e) = window.set_cursor_grab(grabbed) {
+            log::error!("Failed to grab cursor: {:?}", e);
+        }
+    }
+
+    /// Leaves the world. Blocks until the integrated
+    /// server, if any, has saved it.
+    pub fn end(self, renderer: &mut Renderer) {
+        log::info!("Leaving the world");
+        self.game
+            .bridge()
+            .send(ClientPacket::Shared(SharedPacket::Disconnect(Disconnect {
+                reason: Some("Left the game".to_owned()),
+            })));
+        if let Some(server) = self.server {
+            server.stop();
+        }
+        renderer.end_session();
+
+        let window = self.game.window();
+        window.set_cursor_visible(true);
+        if let Err(e) = window.set_cursor_grab(false) {
+            log::error!("Failed to release cursor: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Player settings.
+//!
+//! Settings start out from the environment and can be changed in game.
+//! The view distance, the number of chunks the server sends in each
+//! direction, is read from `VOLTZ_VIEW_DISTANCE` and raised or lowered
+//! with the `=` and `-` keys. Changes reach the server in `UpdateSettings`,
+//! and it loads or unloads chunks to match. The server may clamp the
+//! distance to its own maximum.
+
+use std::env;
+
+use common::{System, SystemExecutor};
+use protocol::packets::{client::UpdateSettings, ClientPacket};
+use winit::event::VirtualKeyCode;
+
+use crate::{event::KeyPressed, game::Game};
+
+/// The view distance unless `VOLTZ_VIEW_DISTANCE` is set.
+pub const DEFAULT_VIEW_DISTANCE: u32 = 8;
+/// The range of view distances the keys choose from.
+const MIN_VIEW_DISTANCE: u32 = 2;
+const MAX_VIEW_DISTANCE: u32 = 32;
+
+const FARTHER_KEY: VirtualKeyCode = VirtualKeyCode::Equals;
+const NEARER_KEY: VirtualKeyCode = VirtualKeyCode::Minus;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Settings {
+    /// The number of chunks visible in each direction.
+    pub view_distance: u32,
+}
+
+impl Settings {
+    /// Reads the settings from the environment.
+    pub fn from_env() -> Self {
+        let view_distance = match env::var("VOLTZ_VIEW_DISTANCE") {
+            Ok(value) => match value.parse::<u32>() {
+                Ok(distance) => distance.max(MIN_VIEW_DISTANCE).min(MAX_VIEW_DISTANCE),
+                Err(_) => {
+                    log::error!("Ignoring invalid VOLTZ_VIEW_DISTANCE '{}'", value);
+                    DEFAULT_VIEW_DISTANCE
+                }
+            },
+            Err(_) => DEFAULT_VIEW_DISTANCE,
+        };
+        Self { view_distance }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            view_distance: DEFAULT_VIEW_DISTANCE,
+        }
+    }
+}
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(SettingsSystem);
+}
+
+/// Changes the view distance and tells the server.
+pub fn set_view_distance(game: &mut Game, view_distance: u32) {
+    let view_distance = view_distance.max(MIN_VIEW_DISTANCE).min(MAX_VIEW_DISTANCE);
+    if view_distance == game.settings.view_distance {
+        return;
+    }
+    log::info!("Setting view distance to {} chunks", view_distance);
+    game.settings.view_distance = view_distance;
+    game.bridge()
+        .send(ClientPacket::UpdateSettings(UpdateSettings {
+            view_distance,
+        }));
+}
+
+/// System to change settings with key presses.
+struct SettingsSystem;
+
+impl System<Game> for SettingsSystem {
+    fn run(&mut self, game: &mut Game) {
+        // Keys typed into the chat don't change settings.
+        if game.chat_open {
+            return;
+        }
+        let keys: Vec<_> = game
+            .events()
+            .iter::<KeyPressed>()
+            .map(|pressed| pressed.key)
+            .collect();
+        for key in keys {
+            let view_distance = game.settings.view_distance;
+            match key {
+                FARTHER_KEY => set_view_distance(game, view_distance + 1),
+                NEARER_KEY => set_view_distance(game, view_distance.saturating_sub(1)),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! The time of day.
+//!
+//! The server sends the world's time with the `TimeUpdate` packet every
+//! few seconds. In between, [`ClockSystem`] advances the time each frame
+//! at the rate the server gave, so the sun moves smoothly. Small
+//! differences from the server's time are caught up gradually rather
+//! than jumped over; large ones, e.g. from the time being set, are not.
+//!
+//! The renderer lights the world and colors the sky
+//! according to the time.
+
+use common::{time, System, SystemExecutor};
+
+use crate::game::Game;
+
+/// The largest difference from the server's time, in seconds
+/// of game time, that is caught up gradually.
+const MAX_CORRECTION: f64 = 10.;
+/// The number of seconds over which differences are caught up.
+const CORRECTION_TIME: f32 = 1.;
+
+/// The world's time as seen by the client.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Clock {
+    /// The current time in seconds of game time.
+    time: f64,
+    /// The seconds of game time that pass per real second.
+    rate: f32,
+    /// The difference from the server's time left to catch up.
+    correction: f64,
+}
+
+impl Clock {
+    /// Gets the current time in seconds of game time.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Gets the current [time of day](time::time_of_day).
+    pub fn time_of_day(&self) -> f32 {
+        time::time_of_day(self.time)
+    }
+
+    /// Handles a time update from the server.
+    pub fn update(&mut self, time: f64, rate: f32) {
+        self.rate = rate;
+        let difference = time - self.time;
+        if difference.abs() > MAX_CORRECTION {
+            self.time = time;
+            self.correction = 0.;
+        } else {
+            self.correction = difference;
+        }
+    }
+
+    /// Advances the time by `dt` seconds of real time.
+    pub fn advance(&mut self, dt: f32) {
+        let catch_up = self.correction * (dt / CORRECTION_TIME).min(1.) as f64;
+        self.correction -= catch_up;
+        self.time += self.rate as f64 * dt as f64 + catch_up;
+    }
+}
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(ClockSystem);
+}
+
+/// System to advance the time between updates.
+struct ClockSystem;
+
+impl System<Game> for ClockSystem {
+    fn run(&mut self, game: &mut Game) {
+        let dt = game.dt();
+        game.clock.advance(dt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catches_up_with_the_server() {
+        let mut clock = Clock::default();
+        clock.update(100., 2.);
+        assert_eq!(clock.time(), 100.);
+        clock.advance(0.5);
+        assert_eq!(clock.time(), 101.);
+
+        // Small differences are caught up over time
+        clock.update(103., 2.);
+        clock.advance(0.5);
+        assert!(clock.time() > 102. && clock.time() < 104.);
+        clock.advance(1.);
+        assert!((clock.time() - 106.).abs() < 1e-9);
+
+        // Paused time stays put
+        clock.update(clock.time(), 0.);
+        clock.advance(1.);
+        assert!((clock.time() - 106.).abs() < 1e-9);
+    }
+}
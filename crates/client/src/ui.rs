@@ -2,11 +2,18 @@
 //! of UIs to be positioned on the screen
 //! and rendered.
 
-use std::alloc::Allocator;
+use std::{alloc::Allocator, sync::Arc};
 
 use ahash::AHashMap;
+use anyhow::Context;
+use fontdue::Font;
 use glam::Vec2;
-use voltzui::Ui;
+use voltzui::{theme::Insets, Event, NinePatch, Texture, Theme, Ui};
+
+use crate::asset::{texture::TextureAsset, Assets};
+
+/// Path of the theme asset applied to all UIs.
+pub const THEME_PATH: &str = "theme/default.yml";
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Length {
@@ -34,16 +41,28 @@ impl Length {
 #[derive(Default)]
 pub struct UiStore {
     uis: AHashMap<&'static str, StoredUi>,
+    theme: Arc<Theme>,
 }
 
 impl UiStore {
+    /// Sets the theme of all current and future UIs.
+    pub fn set_theme(&mut self, theme: Arc<Theme>) {
+        for stored in self.uis.values_mut() {
+            stored.ui.set_theme(Arc::clone(&theme));
+        }
+        self.theme = theme;
+    }
+
     /// Gets a UI with the given name, dimensions, and position.
     /// Position is measured in logical pixels.
     pub fn get(&mut self, name: &'static str, width: Length, height: Length, pos: Vec2) -> &mut Ui {
+        let theme = &self.theme;
         let stored = self.uis.entry(name).or_insert_with(|| {
             log::debug!("Creating UI '{}'", name);
+            let mut ui = Ui::new();
+            ui.set_theme(Arc::clone(theme));
             StoredUi {
-                ui: Ui::new(),
+                ui,
                 width,
                 height,
                 pos,
@@ -57,6 +76,31 @@ impl UiStore {
         &mut stored.ui
     }
 
+    /// Feeds input to every UI, returning whether a widget handled
+    /// it. Each UI keeps its own keyboard focus. Pointer positions
+    /// are relative to the window; each UI receives them relative
+    /// to its own position.
+    pub fn handle_event(&mut self, event: Event) -> bool {
+        let mut handled = false;
+        for stored in self.uis.values_mut() {
+            let event = match event {
+                Event::PointerMoved { pos } => Event::PointerMoved {
+                    pos: pos - stored.pos,
+                },
+                event => event,
+            };
+            handled |= stored.ui.handle_event(event);
+        }
+        handled
+    }
+
+    /// Advances the animations of all UIs by `dt` seconds.
+    pub fn advance(&mut self, dt: f32) {
+        for stored in self.uis.values_mut() {
+            stored.ui.advance(dt);
+        }
+    }
+
     /// Finishes the current frame, removing any UIs
     /// which were not accessed. Writes UI render data
     /// to `output`.
@@ -71,8 +115,9 @@ impl UiStore {
             true
         });
 
-        for stored in self.uis.values_mut() {
+        for (&name, stored) in self.uis.iter_mut() {
             output.push(UiRenderData {
+                name,
                 ui: &mut stored.ui,
                 width: stored.width,
                 height: stored.height,
@@ -84,6 +129,8 @@ impl UiStore {
 
 /// A UI to be rendered.
 pub struct UiRenderData<'a> {
+    /// The unique name of the UI, as passed to `UiStore::get()`.
+    pub name: &'static str,
     pub ui: &'a mut Ui,
     pub width: Length,
     pub height: Length,
@@ -98,3 +145,53 @@ struct StoredUi {
     /// Whether the UI has been accessed this tick
     accessed: bool,
 }
+
+/// Loads the UI theme from the assets, resolving
+/// its font and panel image if it has them.
+pub fn load_theme(assets: &Assets) -> anyhow::Result<Theme> {
+    let mut theme = Theme::clone(&assets.get::<Theme>(THEME_PATH)?);
+    if let Some(path) = &theme.font {
+        let font = assets
+            .get::<Font>(path)
+            .with_context(|| format!("failed to load theme font '{}'", path))?;
+        theme.default_font = Some(Arc::clone(font.as_arc()));
+    }
+    if let Some(path) = &theme.panel_image {
+        let texture = assets
+            .get::<TextureAsset>(path)
+            .with_context(|| format!("failed to load theme panel image '{}'", path))?;
+        theme.panel_nine_patch = Some(Arc::new(nine_patch_from_texture(
+            &texture,
+            theme.panel_insets,
+        )?));
+    }
+    Ok(theme)
+}
+
+fn nine_patch_from_texture(texture: &TextureAsset, insets: Insets) -> anyhow::Result<NinePatch> {
+    if insets.left + insets.right > texture.width() || insets.top + insets.bottom > texture.height()
+    {
+        anyhow::bail!("panel insets are larger than the panel image");
+    }
+
+    Ok(NinePatch::from_rgba(
+        &to_rgba(texture),
+        texture.width(),
+        texture.height(),
+        insets,
+    ))
+}
+
+/// Converts a texture asset into a texture for UI images.
+pub fn ui_texture(texture: &TextureAsset) -> Texture {
+    Texture::from_rgba(&to_rgba(texture), texture.width(), texture.height())
+}
+
+/// Textures are stored in BGRA; the UI expects RGBA.
+fn to_rgba(texture: &TextureAsset) -> Vec<u8> {
+    let mut rgba = texture.data().to_vec();
+    for pixel in rgba.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+    rgba
+}
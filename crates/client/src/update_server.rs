@@ -1,7 +1,7 @@
 //! Systems that notify the server of client actions.
 
-use common::{Orient, Pos, System, SystemExecutor};
-use glam::{Vec2, Vec3A};
+use common::{Orient, Pos, System, SystemExecutor, WorldPos};
+use glam::Vec2;
 use protocol::packets::{client::UpdatePosition, ClientPacket};
 
 use crate::game::Game;
@@ -13,7 +13,7 @@ pub fn setup(systems: &mut SystemExecutor<Game>) {
 /// Notifies the server of changes in position and orientation.
 #[derive(Default)]
 struct NotifyMovement {
-    old_state: Option<(Vec3A, Vec2)>,
+    old_state: Option<(WorldPos, Vec2)>,
 }
 
 impl System<Game> for NotifyMovement {
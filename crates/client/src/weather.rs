@@ -0,0 +1,137 @@
+//! Rain and snow.
+//!
+//! The server tells the client the weather with the `WeatherChange`
+//! packet. [`PrecipitationSystem`] spawns rain or snow particles above
+//! the player and lets them fall until they hit the ground, and the
+//! chunk renderer draws them.
+//!
+//! Particles only spawn in columns open to the sky, so it
+//! does not rain indoors.
+
+use common::{
+    weather::{self, Precipitation, Weather},
+    BlockPos, System, SystemExecutor,
+};
+use glam::{vec3a, Vec3A};
+use rand::Rng;
+
+use crate::game::Game;
+
+/// The horizontal distance from the player
+/// within which particles spawn.
+const RADIUS: f32 = 24.;
+/// How far above the player particles spawn.
+const SPAWN_HEIGHT: f32 = 20.;
+/// How far above the spawn height to look for roofs.
+/// A roof above this height does not stop precipitation.
+const ROOF_SEARCH_HEIGHT: i32 = 48;
+/// How far below the player to look for the ground.
+const GROUND_SEARCH_DEPTH: i32 = 32;
+
+/// Particles spawned per second in the rain.
+/// Doubled in thunderstorms.
+const SPAWN_RATE: f32 = 800.;
+const MAX_PARTICLES: usize = 4096;
+
+const RAIN_SPEED: f32 = 16.;
+const SNOW_SPEED: f32 = 2.;
+
+/// A particle of rain or snow.
+#[derive(Copy, Clone, Debug)]
+pub struct Particle {
+    pub pos: Vec3A,
+    /// Either `Rain` or `Snow`.
+    pub kind: Precipitation,
+    /// The height at which the particle hits the ground.
+    floor: f32,
+}
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(PrecipitationSystem { spawn_debt: 0. });
+}
+
+/// System to spawn and move precipitation particles.
+struct PrecipitationSystem {
+    /// Particles left over from previous frames,
+    /// as spawn counts are fractional.
+    spawn_debt: f32,
+}
+
+impl System<Game> for PrecipitationSystem {
+    fn run(&mut self, game: &mut Game) {
+        let player_pos = game.player_pos();
+        let dt = game.dt();
+
+        let rate = match game.weather {
+            Weather::Clear => 0.,
+            Weather::Rain => SPAWN_RATE,
+            Weather::Thunder => SPAWN_RATE * 2.,
+        };
+        self.spawn_debt += rate * dt;
+        while self.spawn_debt >= 1. {
+            self.spawn_debt -= 1.;
+            if game.precipitation.len() >= MAX_PARTICLES {
+                continue;
+            }
+            if let Some(particle) = spawn_particle(game, player_pos) {
+                game.precipitation.push(particle);
+            }
+        }
+
+        let max_distance_squared = (RADIUS * 2.) * (RADIUS * 2.);
+        game.precipitation.retain(|particle| {
+            let offset = particle.pos - player_pos;
+            particle.pos.y > particle.floor
+                && offset.x * offset.x + offset.z * offset.z <= max_distance_squared
+        });
+        for particle in &mut game.precipitation {
+            let speed = match particle.kind {
+                Precipitation::Snow => SNOW_SPEED,
+                _ => RAIN_SPEED,
+            };
+            particle.pos.y -= speed * dt;
+        }
+    }
+}
+
+/// Spawns a particle above a random position near the player, or returns
+/// `None` if nothing falls there: the column is covered, not loaded,
+/// or in a biome without precipitation.
+fn spawn_particle(game: &Game, player_pos: Vec3A) -> Option<Particle> {
+    let (dx, dz) = {
+        let mut rng = game.rng();
+        (
+            rng.gen_range(-RADIUS, RADIUS),
+            rng.gen_range(-RADIUS, RADIUS),
+        )
+    };
+    let pos = vec3a(
+        player_pos.x + dx,
+        player_pos.y + SPAWN_HEIGHT,
+        player_pos.z + dz,
+    );
+    let column = BlockPos::from_pos(pos);
+
+    let zone = game.main_zone();
+    let (surface_pos, _) = weather::surface(
+        column.x,
+        column.z,
+        column.y + ROOF_SEARCH_HEIGHT,
+        BlockPos::from_pos(player_pos).y - GROUND_SEARCH_DEPTH,
+        |pos| zone.block(pos),
+    )?;
+    if surface_pos.y >= column.y {
+        // Covered by a roof.
+        return None;
+    }
+
+    let kind = weather::precipitation(game.weather, zone.biome_at(surface_pos), surface_pos.y);
+    if kind == Precipitation::None {
+        return None;
+    }
+    Some(Particle {
+        pos,
+        kind,
+        floor: (surface_pos.y + 1) as f32,
+    })
+}
@@ -0,0 +1,77 @@
+//! The experience bar, shown at the bottom of the screen.
+//!
+//! Shows the player's level and their progress toward
+//! the next one, as last sent by the server.
+
+use common::{System, SystemExecutor};
+use fontdue::Font;
+use glam::vec2;
+use utils::Color;
+use voltzui::{
+    widgets::{Container, Rectangle, Text},
+    AlignItems, Dimension,
+};
+
+use crate::{
+    asset::{Asset, Assets},
+    game::Game,
+    ui::Length,
+};
+
+/// Width of the bar in logical pixels.
+const BAR_WIDTH: f32 = 360.;
+/// Height of the bar in logical pixels.
+const BAR_HEIGHT: f32 = 6.;
+/// Height of the area holding the bar and level,
+/// measured from the bottom of the window.
+const HUD_HEIGHT: f32 = 50.;
+/// Color of the level and the filled part of the bar.
+const XP_COLOR: Color = Color {
+    r: 0.5,
+    g: 1.,
+    b: 0.3,
+    a: 1.,
+};
+
+pub fn setup(systems: &mut SystemExecutor<Game>, assets: &Assets) -> anyhow::Result<()> {
+    let font = assets.get("font/Play-Regular.ttf")?;
+    systems.add(XpBarSystem { font });
+    Ok(())
+}
+
+struct XpBarSystem {
+    font: Asset<Font>,
+}
+
+impl System<Game> for XpBarSystem {
+    fn run(&mut self, game: &mut Game) {
+        let level = game.experience.level().to_string();
+        let filled = BAR_WIDTH * game.experience.progress();
+
+        let window = game.window();
+        let window_size = window.inner_size().to_logical::<f32>(window.scale_factor());
+        let mut ui_store = game.ui_store();
+        let ui = ui_store.get(
+            "xp_bar",
+            Length::Percent(100.),
+            Length::LogicalPixels(HUD_HEIGHT),
+            vec2(0., window_size.height - HUD_HEIGHT),
+        );
+        let font = self.font.as_arc();
+
+        ui.build()
+            .begin(Container::column().with_style(|style| {
+                style.size.width = Dimension::Percent(1.);
+                style.align_items = AlignItems::Center;
+            }))
+            .push(Text::new(&level, font).color(XP_COLOR))
+            .begin(Container::row())
+            .push(Rectangle::new(vec2(filled, BAR_HEIGHT), XP_COLOR))
+            .push(Rectangle::new(
+                vec2(BAR_WIDTH - filled, BAR_HEIGHT),
+                Color::rgba(0., 0., 0., 0.5),
+            ))
+            .end()
+            .end();
+    }
+}
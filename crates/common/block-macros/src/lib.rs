@@ -14,6 +14,18 @@ use syn::{
 struct Descriptor {
     slug: String,
     display_name: String,
+    #[darling(default)]
+    solid: Option<bool>,
+    #[darling(default)]
+    hardness: Option<f32>,
+    #[darling(default)]
+    friction: Option<f32>,
+    #[darling(default)]
+    climbable: Option<bool>,
+    #[darling(default)]
+    translucent: Option<bool>,
+    #[darling(default)]
+    collision_box: Option<String>,
 }
 
 /// Used to create a struct representing a block plus
@@ -36,6 +48,18 @@ struct Descriptor {
 /// For enum or bool properties, this is not necessary.
 ///
 /// Block properties must implement the `BlockProperty` trait.
+///
+/// # Descriptor
+/// The `#[block(...)]` attribute sets the block's descriptor. `slug` and
+/// `display_name` are required. The physical properties are optional and
+/// default to those of a full, solid block:
+/// * `solid = false`: entities pass through the block.
+/// * `hardness = 0.5`: how long the block takes to break.
+/// * `friction = 1.0`: how quickly entities on top of the block slow down.
+/// * `climbable = true`: entities can climb the block, like a ladder.
+/// * `translucent = true`: the block is drawn see-through, like water.
+/// * `collision_box = "0 0 0 1 0.5 1"`: the minimum and maximum corners
+/// of the part of the block entities collide with, in block space.
 #[proc_macro_derive(Block, attributes(range, block))]
 #[proc_macro_error]
 pub fn block(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
@@ -182,7 +206,7 @@ fn generate_block_impl(
     let map_prop_to_int: Vec<TokenStream> = generate_map_prop_to_int(properties);
     let map_int_to_prop: Vec<TokenStream> = generate_map_int_to_prop(properties);
 
-    let Descriptor { slug, display_name } = descriptor;
+    let descriptor_impl = generate_descriptor(descriptor);
 
     quote! {
         #[allow(non_upper_case_globals)]
@@ -196,6 +220,9 @@ fn generate_block_impl(
             }
 
             fn from_state_id(state: u32) -> Option<Self> {
+                if state >= <Self as crate::block::Block>::num_states() {
+                    return None;
+                }
                 let unpacked = #packer.unpack(state);
 
                 Some(Self {
@@ -205,13 +232,75 @@ fn generate_block_impl(
                 })
             }
 
+            fn num_states() -> u32 {
+                let num_possible_values: [u32; #num_properties] = [#(#num_possible_values),*];
+                num_possible_values.iter().product()
+            }
+
             fn descriptor() -> crate::block::BlockDescriptor {
-                crate::block::BlockDescriptor::new(#slug, #display_name)
+                #descriptor_impl
             }
         }
     }
 }
 
+fn generate_descriptor(descriptor: &Descriptor) -> TokenStream {
+    let Descriptor {
+        slug, display_name, ..
+    } = descriptor;
+    let mut result = quote! {
+        crate::block::BlockDescriptor::new(#slug, #display_name)
+    };
+    if let Some(solid) = descriptor.solid {
+        result = quote! { #result.with_solid(#solid) };
+    }
+    if let Some(hardness) = descriptor.hardness {
+        result = quote! { #result.with_hardness(#hardness) };
+    }
+    if let Some(friction) = descriptor.friction {
+        result = quote! { #result.with_friction(#friction) };
+    }
+    if let Some(climbable) = descriptor.climbable {
+        result = quote! { #result.with_climbable(#climbable) };
+    }
+    if let Some(translucent) = descriptor.translucent {
+        result = quote! { #result.with_translucent(#translucent) };
+    }
+    if let Some(collision_box) = &descriptor.collision_box {
+        let (min, max) = parse_collision_box(collision_box);
+        result = quote! {
+            #result.with_collision_box(crate::block::CollisionBox {
+                min: [#(#min),*],
+                max: [#(#max),*],
+            })
+        };
+    }
+    result
+}
+
+/// Parses a collision box of the form `"min_x min_y min_z max_x max_y max_z"`.
+fn parse_collision_box(collision_box: &str) -> (Vec<f32>, Vec<f32>) {
+    let coords: Vec<f32> = collision_box
+        .split_whitespace()
+        .map(|coord| match coord.parse() {
+            Ok(coord) if (0.0..=1.0).contains(&coord) => coord,
+            _ => abort_call_site!(
+                "collision box coordinate '{}' must be a number from 0 to 1",
+                coord
+            ),
+        })
+        .collect();
+    if coords.len() != 6 {
+        abort_call_site!(
+            "collision box must have six coordinates: the minimum and maximum corners"
+        );
+    }
+    if (0..3).any(|i| coords[i] >= coords[i + 3]) {
+        abort_call_site!("collision box minimum must be less than its maximum");
+    }
+    (coords[..3].to_vec(), coords[3..].to_vec())
+}
+
 fn generate_num_possible_values(properties: &Properties) -> Vec<TokenStream> {
     properties
         .iter()
@@ -1,3 +1,9 @@
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::CHUNK_DIM;
+
 /// A biome. Defines the overall look of an area of the world.
 ///
 /// Biomes are defined by a set of properties stored in this struct.
@@ -6,16 +12,39 @@
 pub struct Biome {
     slug: &'static str,
     display_name: &'static str,
+    id: u8,
 }
 
 #[allow(non_upper_case_globals)]
 impl Biome {
-    // Biome constants.
-    pub const Ocean: &'static Biome = &Biome::new("ocean", "Ocean");
-    pub const Plains: &'static Biome = &Biome::new("plains", "Plains");
+    // Biome constants. IDs need to match the biome
+    // definitions in shader/include/biomes.glsl.
+    pub const Ocean: &'static Biome = &Biome::new("ocean", "Ocean", 0);
+    pub const Plains: &'static Biome = &Biome::new("plains", "Plains", 1);
+    pub const Hills: &'static Biome = &Biome::new("hills", "Hills", 2);
+    pub const Desert: &'static Biome = &Biome::new("desert", "Desert", 3);
+    pub const Forest: &'static Biome = &Biome::new("forest", "Forest", 4);
+    pub const River: &'static Biome = &Biome::new("river", "River", 5);
+    pub const Mountains: &'static Biome = &Biome::new("mountains", "Mountains", 6);
+    pub const Swamp: &'static Biome = &Biome::new("swamp", "Swamp", 7);
+    pub const Tundra: &'static Biome = &Biome::new("tundra", "Tundra", 8);
+
+    const fn new(slug: &'static str, display_name: &'static str, id: u8) -> Self {
+        Self {
+            slug,
+            display_name,
+            id,
+        }
+    }
 
-    const fn new(slug: &'static str, display_name: &'static str) -> Self {
-        Self { slug, display_name }
+    /// Returns all biomes, ordered by ID.
+    pub fn all() -> &'static [&'static Biome] {
+        ALL
+    }
+
+    /// Gets the biome with the given ID.
+    pub fn from_id(id: u8) -> Option<&'static Biome> {
+        ALL.get(id as usize).copied()
     }
 
     pub fn slug(&self) -> &str {
@@ -25,4 +54,119 @@ impl Biome {
     pub fn display_name(&self) -> &str {
         self.display_name
     }
+
+    /// The biome's ID, as output by the world generator.
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+}
+
+static ALL: &[&Biome] = &[
+    Biome::Ocean,
+    Biome::Plains,
+    Biome::Hills,
+    Biome::Desert,
+    Biome::Forest,
+    Biome::River,
+    Biome::Mountains,
+    Biome::Swamp,
+    Biome::Tundra,
+];
+
+/// Length in blocks of the sides of the square
+/// cells [`ColumnBiomes`] stores a biome for.
+pub const BIOME_CELL_DIM: usize = 4;
+const CELLS_PER_SIDE: usize = CHUNK_DIM / BIOME_CELL_DIM;
+const CELLS: usize = CELLS_PER_SIDE * CELLS_PER_SIDE;
+
+/// The biomes of a chunk column, recorded during world generation.
+///
+/// Stores one biome per [`BIOME_CELL_DIM`]-wide square of block
+/// columns, which is fine enough for gameplay and keeps the data
+/// small enough to send along with every chunk. The default is
+/// all ocean, like terrain outside the world generator's biome grid.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "[u8; CELLS]", into = "[u8; CELLS]")]
+pub struct ColumnBiomes([u8; CELLS]);
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown biome ID {0}")]
+pub struct UnknownBiome(u8);
+
+impl ColumnBiomes {
+    /// Creates column biomes by evaluating `f` for each cell. `f` is
+    /// called with the chunk-local X and Z of the block column near
+    /// the middle of the cell.
+    pub fn from_fn(mut f: impl FnMut(usize, usize) -> &'static Biome) -> Self {
+        let mut ids = [0; CELLS];
+        for (index, id) in ids.iter_mut().enumerate() {
+            let x = index % CELLS_PER_SIDE * BIOME_CELL_DIM + BIOME_CELL_DIM / 2;
+            let z = index / CELLS_PER_SIDE * BIOME_CELL_DIM + BIOME_CELL_DIM / 2;
+            *id = f(x, z).id;
+        }
+        Self(ids)
+    }
+
+    /// Gets the biome at the given chunk-local X and Z coordinates.
+    pub fn get(&self, x: usize, z: usize) -> &'static Biome {
+        let index = z / BIOME_CELL_DIM * CELLS_PER_SIDE + x / BIOME_CELL_DIM;
+        Biome::from_id(self.0[index]).expect("validated biome ID")
+    }
+
+    /// Returns the biome IDs of the cells, ordered by Z then X.
+    pub fn ids(&self) -> [u8; CELLS] {
+        self.0
+    }
+}
+
+impl TryFrom<[u8; CELLS]> for ColumnBiomes {
+    type Error = UnknownBiome;
+
+    fn try_from(ids: [u8; CELLS]) -> Result<Self, Self::Error> {
+        match ids.iter().find(|&&id| Biome::from_id(id).is_none()) {
+            Some(&id) => Err(UnknownBiome(id)),
+            None => Ok(Self(ids)),
+        }
+    }
+}
+
+impl From<ColumnBiomes> for [u8; CELLS] {
+    fn from(biomes: ColumnBiomes) -> Self {
+        biomes.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_index_all_biomes() {
+        for (id, biome) in Biome::all().iter().enumerate() {
+            assert_eq!(biome.id() as usize, id);
+            assert_eq!(Biome::from_id(id as u8), Some(*biome));
+        }
+        assert_eq!(Biome::from_id(Biome::all().len() as u8), None);
+    }
+
+    #[test]
+    fn column_biomes_cover_cells() {
+        let biomes = ColumnBiomes::from_fn(|x, z| {
+            if x < 8 && z >= 12 {
+                Biome::Desert
+            } else {
+                Biome::Forest
+            }
+        });
+        assert_eq!(biomes.get(0, 12), Biome::Desert);
+        assert_eq!(biomes.get(7, 15), Biome::Desert);
+        assert_eq!(biomes.get(8, 12), Biome::Forest);
+        assert_eq!(biomes.get(0, 11), Biome::Forest);
+        assert_eq!(ColumnBiomes::default().get(5, 5), Biome::Ocean);
+
+        let mut ids = biomes.ids();
+        assert!(ColumnBiomes::try_from(ids).is_ok());
+        ids[3] = 200;
+        assert!(ColumnBiomes::try_from(ids).is_err());
+    }
 }
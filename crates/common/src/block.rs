@@ -18,6 +18,8 @@ struct Registry {
     kind_to_type: Vec<TypeId>,
     /// Maps BlockId.kind to BlockDescriptor.
     kind_to_descriptor: Vec<BlockDescriptor>,
+    /// Maps BlockId.kind to the number of states of that kind.
+    kind_to_num_states: Vec<u32>,
 
     /// The next BlockId.kind to allocate.
     next_kind: u32,
@@ -36,6 +38,8 @@ impl Registry {
 
         self.kind_to_descriptor.push(T::descriptor());
 
+        self.kind_to_num_states.push(T::num_states());
+
         self
     }
 
@@ -50,6 +54,10 @@ impl Registry {
     pub fn descriptor_of(&self, kind: u32) -> Option<BlockDescriptor> {
         self.kind_to_descriptor.get(kind as usize).copied()
     }
+
+    pub fn num_states_of(&self, kind: u32) -> Option<u32> {
+        self.kind_to_num_states.get(kind as usize).copied()
+    }
 }
 
 /// The global block registry.
@@ -64,11 +72,97 @@ static REGISTRY: Lazy<Registry> = Lazy::new(|| {
         .register::<Grass>()
         .register::<Melium>()
         .register::<Sand>()
-        .register::<Water>();
+        .register::<Water>()
+        .register::<Wire>()
+        .register::<SignalSource>()
+        .register::<Lamp>()
+        .register::<Door>()
+        .register::<Trapdoor>()
+        .register::<Gravel>()
+        .register::<Snow>()
+        .register::<Log>()
+        .register::<Leaves>()
+        .register::<Sapling>()
+        .register::<Ladder>()
+        .register::<CoalOre>()
+        .register::<IronOre>()
+        .register::<GoldOre>();
 
     registry
 });
 
+/// Computes a digest of the block registry. Peers whose digests are
+/// equal have the same block kind and state IDs, so block IDs can be
+/// exchanged between them directly.
+///
+/// The digest is stable across processes and platforms.
+pub fn registry_digest() -> u64 {
+    // FNV-1a over the slugs and state counts in kind order.
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    let mut hash = OFFSET_BASIS;
+    let kinds = REGISTRY
+        .kind_to_descriptor
+        .iter()
+        .zip(&REGISTRY.kind_to_num_states);
+    for (descriptor, num_states) in kinds {
+        let num_states = num_states.to_le_bytes();
+        let bytes = descriptor
+            .slug()
+            .as_bytes()
+            .iter()
+            .chain(&[0])
+            .chain(&num_states);
+        for &byte in bytes {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    hash
+}
+
+/// Returns the default state of a registered block kind: the state
+/// with ID 0, whose properties all have their first value.
+pub fn default_state(kind: u32) -> Option<BlockId> {
+    if kind < REGISTRY.next_kind {
+        Some(BlockId::from_raw_parts(kind, 0))
+    } else {
+        None
+    }
+}
+
+/// Iterates over the [default state](default_state) of
+/// each registered block kind, in kind order.
+pub fn default_states() -> impl Iterator<Item = BlockId> {
+    (0..REGISTRY.next_kind).filter_map(default_state)
+}
+
+/// Iterates over every state of each registered
+/// block kind, in kind and then state order.
+pub fn all_states() -> impl Iterator<Item = BlockId> {
+    REGISTRY
+        .kind_to_num_states
+        .iter()
+        .enumerate()
+        .flat_map(|(kind, &num_states)| {
+            (0..num_states).map(move |state| BlockId::from_raw_parts(kind as u32, state))
+        })
+}
+
+/// Returns the kind ID of the registered block with the given slug.
+pub fn kind_by_slug(slug: &str) -> Option<u32> {
+    REGISTRY
+        .kind_to_descriptor
+        .iter()
+        .position(|descriptor| descriptor.slug() == slug)
+        .map(|kind| kind as u32)
+}
+
+/// Returns the number of states of a registered block kind.
+pub fn num_states(kind: u32) -> Option<u32> {
+    REGISTRY.num_states_of(kind)
+}
+
 /// ID of a block state.
 ///
 /// This struct can be thought of as a `Box<dyn Block>`, except
@@ -120,6 +214,15 @@ impl BlockId {
         Self { kind, state }
     }
 
+    /// Returns whether this ID refers to a registered block kind
+    /// and a valid state of that kind. Other methods panic on
+    /// invalid IDs, so check IDs received from untrusted peers.
+    pub fn is_valid(self) -> bool {
+        REGISTRY
+            .num_states_of(self.kind)
+            .map_or(false, |num_states| self.state < num_states)
+    }
+
     /// Returns the descriptor of this block, which provides
     /// e.g. slug and display name.
     pub fn descriptor(self) -> BlockDescriptor {
@@ -146,6 +249,74 @@ impl BlockId {
         self.cast::<T>().is_some()
     }
 
+    /// Returns whether entities collide with this block.
+    ///
+    /// This is the descriptor's `solid` property, except that open
+    /// doors and trapdoors are not solid.
+    pub fn is_solid(self) -> bool {
+        if let Some(door) = self.cast::<blocks::Door>() {
+            !door.open
+        } else if let Some(trapdoor) = self.cast::<blocks::Trapdoor>() {
+            !trapdoor.open
+        } else {
+            self.descriptor().is_solid()
+        }
+    }
+
+    /// Returns how long this block takes to break.
+    pub fn hardness(self) -> f32 {
+        self.descriptor().hardness()
+    }
+
+    /// Returns the friction of this block's surface.
+    pub fn friction(self) -> f32 {
+        self.descriptor().friction()
+    }
+
+    /// Returns whether entities can climb this block.
+    pub fn is_climbable(self) -> bool {
+        self.descriptor().is_climbable()
+    }
+
+    /// Returns whether this block is drawn see-through.
+    pub fn is_translucent(self) -> bool {
+        self.descriptor().is_translucent()
+    }
+
+    /// Returns the direction this block faces, or `None`
+    /// if it looks the same from every side.
+    pub fn facing(self) -> Option<Facing> {
+        self.cast::<blocks::Ladder>().map(|ladder| ladder.facing)
+    }
+
+    /// Returns the part of this block's space that entities
+    /// collide with, or `None` if this block is not solid.
+    ///
+    /// The descriptor's collision box is that of the block facing
+    /// [`Facing::PosZ`], and is turned to match other facings.
+    pub fn collision_box(self) -> Option<CollisionBox> {
+        if !self.is_solid() {
+            return None;
+        }
+        let collision_box = self.descriptor().collision_box();
+        Some(match self.facing() {
+            Some(facing) => collision_box.rotated(facing.quarter_turns()),
+            None => collision_box,
+        })
+    }
+
+    /// Returns whether this block is a fluid, which
+    /// entities swim in and don't fall through.
+    pub fn is_fluid(self) -> bool {
+        self.is::<blocks::Water>()
+    }
+
+    /// Returns whether this block blocks light, which is true of
+    /// solid blocks filling their whole space other than water.
+    pub fn is_opaque(self) -> bool {
+        self.collision_box() == Some(CollisionBox::FULL) && !self.is_fluid()
+    }
+
     /// Returns the numeric ID of this block's kind.
     pub fn kind(self) -> u32 {
         self.kind
@@ -171,35 +342,228 @@ pub trait Block: Any + Sized {
     /// from `Self::state_id()`.
     fn from_state_id(id: u32) -> Option<Self>;
 
+    /// Gets the number of states of this block kind. Valid
+    /// state IDs are in the range `0..num_states()`.
+    fn num_states() -> u32;
+
     /// Gets the BlockDescriptor for this block kind.
     fn descriptor() -> BlockDescriptor;
 }
 
 /// A descriptor that exists for every block kind. Provides
-/// information such as slug and display name.
+/// information such as slug and display name, plus the
+/// physical properties of the block.
 #[derive(Debug, Copy, Clone)]
 pub struct BlockDescriptor {
     slug: &'static str,
     display_name: &'static str,
+    solid: bool,
+    hardness: f32,
+    friction: f32,
+    climbable: bool,
+    translucent: bool,
+    collision_box: CollisionBox,
 }
 
 impl BlockDescriptor {
+    /// Creates a descriptor for a full, solid block with
+    /// the default hardness and friction.
     pub fn new(slug: &'static str, display_name: &'static str) -> Self {
-        Self { slug, display_name }
+        Self {
+            slug,
+            display_name,
+            solid: true,
+            hardness: 1.,
+            friction: 1.,
+            climbable: false,
+            translucent: false,
+            collision_box: CollisionBox::FULL,
+        }
+    }
+
+    pub fn with_solid(mut self, solid: bool) -> Self {
+        self.solid = solid;
+        self
+    }
+
+    pub fn with_hardness(mut self, hardness: f32) -> Self {
+        self.hardness = hardness;
+        self
+    }
+
+    pub fn with_friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+
+    pub fn with_climbable(mut self, climbable: bool) -> Self {
+        self.climbable = climbable;
+        self
+    }
+
+    pub fn with_translucent(mut self, translucent: bool) -> Self {
+        self.translucent = translucent;
+        self
+    }
+
+    pub fn with_collision_box(mut self, collision_box: CollisionBox) -> Self {
+        self.collision_box = collision_box;
+        self
     }
 
     /// Returns the block's slug, for example "dirt." This slug is
     /// stable and can be used for serialization to disk. (The properties
     /// map of the block returned by `BlockId::to_properties()` must be serialized
     /// as well for properties to persist.)
-    pub fn slug(&self) -> &str {
+    pub fn slug(&self) -> &'static str {
         self.slug
     }
 
     /// Returns the block's display name which can be displayed to the user.
-    pub fn display_name(&self) -> &str {
+    pub fn display_name(&self) -> &'static str {
         self.display_name
     }
+
+    /// Returns whether entities collide with blocks of this kind.
+    /// Some states may differ; see `BlockId::is_solid()`.
+    pub fn is_solid(&self) -> bool {
+        self.solid
+    }
+
+    /// Returns how long the block takes to break. A stone block
+    /// has a hardness of 1, and air a hardness of 0.
+    pub fn hardness(&self) -> f32 {
+        self.hardness
+    }
+
+    /// Returns the friction of the block's surface, relative to
+    /// ordinary ground. Entities standing on a block with zero
+    /// friction slide freely.
+    pub fn friction(&self) -> f32 {
+        self.friction
+    }
+
+    /// Returns whether entities can climb the block, like a ladder.
+    /// Entities in a climbable block fall slowly and climb up it
+    /// when moving against it.
+    pub fn is_climbable(&self) -> bool {
+        self.climbable
+    }
+
+    /// Returns whether the block is drawn see-through, like water.
+    /// Clients draw translucent blocks after all other geometry.
+    pub fn is_translucent(&self) -> bool {
+        self.translucent
+    }
+
+    /// Returns the part of the block's space that entities collide
+    /// with when it is solid.
+    pub fn collision_box(&self) -> CollisionBox {
+        self.collision_box
+    }
+}
+
+/// The part of a block's space that entities collide with. Coordinates
+/// are relative to the block's minimum corner, from 0 to 1.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CollisionBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl CollisionBox {
+    /// A box filling the whole block.
+    pub const FULL: CollisionBox = CollisionBox {
+        min: [0., 0., 0.],
+        max: [1., 1., 1.],
+    };
+
+    /// Turns this box about the vertical axis through the center of
+    /// the block. Each quarter turn takes +Z to +X.
+    pub fn rotated(self, quarter_turns: u8) -> Self {
+        let mut rotated = self;
+        for _ in 0..quarter_turns % 4 {
+            let CollisionBox { min, max } = rotated;
+            rotated = CollisionBox {
+                min: [min[2], min[1], 1. - max[0]],
+                max: [max[2], max[1], 1. - min[0]],
+            };
+        }
+        rotated
+    }
+}
+
+/// A horizontal direction, such as the one an oriented block faces.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Facing {
+    PosX,
+    NegX,
+    PosZ,
+    NegZ,
+}
+
+impl Facing {
+    /// Returns the direction of the given offset
+    /// along the X or Z axis, if it is one.
+    pub fn from_offset(x: i32, y: i32, z: i32) -> Option<Self> {
+        match (x, y, z) {
+            (1, 0, 0) => Some(Facing::PosX),
+            (-1, 0, 0) => Some(Facing::NegX),
+            (0, 0, 1) => Some(Facing::PosZ),
+            (0, 0, -1) => Some(Facing::NegZ),
+            _ => None,
+        }
+    }
+
+    /// Returns the offset to the adjacent block in this direction.
+    pub fn offset(self) -> [i32; 3] {
+        match self {
+            Facing::PosX => [1, 0, 0],
+            Facing::NegX => [-1, 0, 0],
+            Facing::PosZ => [0, 0, 1],
+            Facing::NegZ => [0, 0, -1],
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Facing::PosX => Facing::NegX,
+            Facing::NegX => Facing::PosX,
+            Facing::PosZ => Facing::NegZ,
+            Facing::NegZ => Facing::PosZ,
+        }
+    }
+
+    /// Returns the number of quarter turns about the vertical
+    /// axis from +Z to this direction, where each turn takes
+    /// +Z to +X. Oriented blocks are modeled facing +Z and
+    /// turned by this amount.
+    pub fn quarter_turns(self) -> u8 {
+        match self {
+            Facing::PosZ => 0,
+            Facing::PosX => 1,
+            Facing::NegZ => 2,
+            Facing::NegX => 3,
+        }
+    }
+}
+
+impl BlockProperty for Facing {
+    const NUM_POSSIBLE_VALUES: u32 = 4;
+
+    fn to_int(self) -> u32 {
+        self.quarter_turns() as u32
+    }
+
+    fn from_int(int: u32) -> Option<Self> {
+        match int {
+            0 => Some(Facing::PosZ),
+            1 => Some(Facing::PosX),
+            2 => Some(Facing::NegZ),
+            3 => Some(Facing::NegX),
+            _ => None,
+        }
+    }
 }
 
 /// A type which can be used as a block property.
@@ -306,6 +670,87 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn registry_digest_is_stable() {
+        assert_eq!(registry_digest(), registry_digest());
+        assert_eq!(default_states().next(), Some(BlockId::new(blocks::Air)));
+    }
+
+    #[test]
+    fn physical_properties() {
+        let air = BlockId::new(blocks::Air);
+        assert!(!air.is_solid());
+        assert_eq!(air.hardness(), 0.);
+        assert_eq!(air.collision_box(), None);
+
+        let stone = BlockId::new(blocks::Stone);
+        assert!(stone.is_solid());
+        assert_eq!(stone.hardness(), 1.);
+        assert_eq!(stone.friction(), 1.);
+        assert_eq!(stone.collision_box(), Some(CollisionBox::FULL));
+
+        let trapdoor = BlockId::new(blocks::Trapdoor {
+            open: false,
+            powered: false,
+        });
+        assert_eq!(
+            trapdoor.collision_box(),
+            Some(CollisionBox {
+                min: [0., 0., 0.],
+                max: [1., 0.1875, 1.],
+            })
+        );
+        let open = BlockId::new(blocks::Trapdoor {
+            open: true,
+            powered: false,
+        });
+        assert!(!open.is_solid());
+        assert_eq!(open.collision_box(), None);
+
+        assert!(stone.is_opaque());
+        assert!(!air.is_opaque());
+        assert!(!trapdoor.is_opaque());
+        assert!(!BlockId::new(blocks::Water).is_opaque());
+        assert!(BlockId::new(blocks::Water).is_translucent());
+        assert!(!stone.is_translucent());
+    }
+
+    #[test]
+    fn oriented_blocks_turn() {
+        let ladder = |facing| BlockId::new(blocks::Ladder { facing });
+        assert!(ladder(Facing::PosZ).is_climbable());
+        assert!(!BlockId::new(blocks::Stone).is_climbable());
+        assert_eq!(BlockId::new(blocks::Stone).facing(), None);
+
+        // Ladders hug the wall behind them.
+        let thin = |min: [f32; 3], max: [f32; 3]| Some(CollisionBox { min, max });
+        assert_eq!(
+            ladder(Facing::PosZ).collision_box(),
+            thin([0., 0., 0.], [1., 1., 0.125])
+        );
+        assert_eq!(
+            ladder(Facing::PosX).collision_box(),
+            thin([0., 0., 0.], [0.125, 1., 1.])
+        );
+        assert_eq!(
+            ladder(Facing::NegZ).collision_box(),
+            thin([0., 0., 0.875], [1., 1., 1.])
+        );
+        assert_eq!(
+            ladder(Facing::NegX).collision_box(),
+            thin([0.875, 0., 0.], [1., 1., 1.])
+        );
+        assert!(!ladder(Facing::NegX).is_opaque());
+
+        for &facing in &[Facing::PosX, Facing::NegX, Facing::PosZ, Facing::NegZ] {
+            let [x, y, z] = facing.offset();
+            assert_eq!(Facing::from_offset(x, y, z), Some(facing));
+            assert_eq!(Facing::from_int(facing.to_int()), Some(facing));
+            assert_ne!(facing.opposite(), facing);
+        }
+        assert_eq!(Facing::from_offset(0, 1, 0), None);
+    }
+
     #[test]
     fn property_packer_zero_size() {
         let packer = PropertyPacker::new([]);
@@ -376,4 +821,31 @@ mod tests {
         assert!(BlockId::from_raw_parts(0, 0).is::<blocks::Air>());
         assert!(BlockId::from_raw_parts(1, 0).is::<blocks::Dirt>());
     }
+
+    #[test]
+    fn block_id_validity() {
+        assert!(BlockId::new(blocks::Stone).is_valid());
+        assert!(!BlockId::from_raw_parts(u32::MAX, 0).is_valid());
+        assert!(!BlockId::from_raw_parts(0, 1).is_valid());
+        assert!(blocks::Air::from_state_id(1).is_none());
+
+        let wire = BlockId::new(blocks::Wire { power: 15 });
+        assert!(wire.is_valid());
+        assert_eq!(wire.cast::<blocks::Wire>().unwrap().power, 15);
+        assert!(!BlockId::from_raw_parts(wire.kind(), 16).is_valid());
+    }
+
+    #[test]
+    fn all_states_are_valid() {
+        let states: Vec<BlockId> = all_states().collect();
+        assert!(states.iter().all(|block| block.is_valid()));
+        assert!(states.contains(&BlockId::new(blocks::Lamp { lit: true })));
+        assert_eq!(
+            states
+                .iter()
+                .filter(|block| block.is::<blocks::Wire>())
+                .count(),
+            16
+        );
+    }
 }
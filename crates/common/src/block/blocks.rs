@@ -2,12 +2,14 @@
 
 use block_macros::Block;
 
+use crate::{block::Facing, BlockPos};
+
 #[derive(Block)]
-#[block(slug = "air", display_name = "Air")]
+#[block(slug = "air", display_name = "Air", solid = false, hardness = 0.0)]
 pub struct Air;
 
 #[derive(Block)]
-#[block(slug = "dirt", display_name = "Dirt")]
+#[block(slug = "dirt", display_name = "Dirt", hardness = 0.5)]
 pub struct Dirt;
 
 #[derive(Block)]
@@ -15,17 +17,150 @@ pub struct Dirt;
 pub struct Stone;
 
 #[derive(Block)]
-#[block(slug = "grass", display_name = "Grass")]
+#[block(slug = "grass", display_name = "Grass", hardness = 0.6)]
 pub struct Grass;
 
 #[derive(Block)]
-#[block(slug = "melium", display_name = "Melium")]
+#[block(slug = "melium", display_name = "Melium", hardness = 2.0)]
 pub struct Melium;
 
 #[derive(Block)]
-#[block(slug = "sand", display_name = "Sand")]
+#[block(slug = "sand", display_name = "Sand", hardness = 0.5, friction = 1.5)]
 pub struct Sand;
 
 #[derive(Block)]
-#[block(slug = "water", display_name = "Water")]
+#[block(
+    slug = "gravel",
+    display_name = "Gravel",
+    hardness = 0.6,
+    friction = 1.2
+)]
+pub struct Gravel;
+
+#[derive(Block)]
+#[block(slug = "water", display_name = "Water", translucent = true)]
 pub struct Water;
+
+/// Carries a signal to adjacent blocks. Loses one
+/// level of power per block.
+#[derive(Block)]
+#[block(slug = "wire", display_name = "Wire", solid = false, hardness = 0.0)]
+pub struct Wire {
+    #[range(0..=15)]
+    pub power: u32,
+}
+
+/// Powers adjacent blocks at full strength.
+#[derive(Block)]
+#[block(slug = "signal_source", display_name = "Signal Source", hardness = 0.5)]
+pub struct SignalSource;
+
+/// Lights up when powered.
+#[derive(Block)]
+#[block(slug = "lamp", display_name = "Lamp", hardness = 0.3)]
+pub struct Lamp {
+    pub lit: bool,
+}
+
+/// Opens when powered or used. A door is two blocks
+/// tall, with one block for each half.
+#[derive(Block)]
+#[block(
+    slug = "door",
+    display_name = "Door",
+    hardness = 0.8,
+    collision_box = "0 0 0 1 1 0.1875"
+)]
+pub struct Door {
+    pub open: bool,
+    pub upper: bool,
+    /// Whether either half of the door is powered. A
+    /// door opens or closes when this changes.
+    pub powered: bool,
+}
+
+impl Door {
+    /// Returns the position of the other half of this
+    /// door, given the position of this half.
+    pub fn other_half(&self, pos: BlockPos) -> BlockPos {
+        if self.upper {
+            pos.offset(0, -1, 0)
+        } else {
+            pos.offset(0, 1, 0)
+        }
+    }
+}
+
+/// A hatch that opens when powered or used.
+#[derive(Block)]
+#[block(
+    slug = "trapdoor",
+    display_name = "Trapdoor",
+    hardness = 0.8,
+    collision_box = "0 0 0 1 0.1875 1"
+)]
+pub struct Trapdoor {
+    pub open: bool,
+    pub powered: bool,
+}
+
+/// A thin layer of snow. Accumulates on the
+/// ground when it snows. Entities sink through it.
+#[derive(Block)]
+#[block(slug = "snow", display_name = "Snow", solid = false, hardness = 0.1)]
+pub struct Snow;
+
+/// Ore found in veins underground.
+#[derive(Block)]
+#[block(slug = "coal_ore", display_name = "Coal Ore", hardness = 1.5)]
+pub struct CoalOre;
+
+#[derive(Block)]
+#[block(slug = "iron_ore", display_name = "Iron Ore", hardness = 2.0)]
+pub struct IronOre;
+
+#[derive(Block)]
+#[block(slug = "gold_ore", display_name = "Gold Ore", hardness = 2.5)]
+pub struct GoldOre;
+
+/// The trunk of a tree.
+#[derive(Block)]
+#[block(slug = "log", display_name = "Log", hardness = 0.8)]
+pub struct Log;
+
+#[derive(Block)]
+#[block(slug = "leaves", display_name = "Leaves", hardness = 0.2)]
+pub struct Leaves;
+
+/// Grows into a tree after some time.
+#[derive(Block)]
+#[block(
+    slug = "sapling",
+    display_name = "Sapling",
+    solid = false,
+    hardness = 0.0
+)]
+pub struct Sapling;
+
+/// Lets entities climb up and down. A ladder hangs on the
+/// block behind it, opposite the direction it faces.
+#[derive(Block)]
+#[block(
+    slug = "ladder",
+    display_name = "Ladder",
+    hardness = 0.4,
+    climbable = true,
+    collision_box = "0 0 0 1 1 0.125"
+)]
+pub struct Ladder {
+    pub facing: Facing,
+}
+
+impl Ladder {
+    /// Returns the position of the block this
+    /// ladder hangs on, given its position.
+    pub fn wall(&self, pos: BlockPos) -> BlockPos {
+        let [x, y, z] = self.facing.opposite().offset();
+        pos.offset(x, y, z)
+    }
+}
@@ -1,9 +1,10 @@
 //! Data structure for compactly storing blocks in the world.
 
+use glam::Vec3A;
 use serde::{Deserialize, Serialize};
 use utils::PackedArray;
 
-use crate::{blocks, BlockId, Pos};
+use crate::{blocks, BlockId, BlockPos};
 
 /// The dimensions of a chunk (cube).
 pub const CHUNK_DIM: usize = 16;
@@ -27,15 +28,25 @@ impl ChunkPos {
         (other.x - self.x) + (other.y - self.y) + (other.z - self.z)
     }
 
-    /// Gets the position of the chunk containing the given `Pos`.
-    pub fn from_pos(pos: Pos) -> Self {
-        let pos = pos.0;
+    /// Gets the position of the chunk containing the given position,
+    /// rounding down like [`BlockPos::from_pos`].
+    pub fn from_pos(pos: Vec3A) -> Self {
+        BlockPos::from_pos(pos).chunk()
+    }
 
-        let x = (pos.x.floor() as i32).div_euclid(CHUNK_DIM as i32);
-        let y = (pos.y.floor() as i32).div_euclid(CHUNK_DIM as i32);
-        let z = (pos.z.floor() as i32).div_euclid(CHUNK_DIM as i32);
+    /// Returns the block in this chunk with the lowest coordinates.
+    pub fn block_min(self) -> BlockPos {
+        BlockPos {
+            x: self.x * CHUNK_DIM as i32,
+            y: self.y * CHUNK_DIM as i32,
+            z: self.z * CHUNK_DIM as i32,
+        }
+    }
 
-        Self { x, y, z }
+    /// Returns the block in this chunk with the highest coordinates.
+    pub fn block_max(self) -> BlockPos {
+        let last = CHUNK_DIM as i32 - 1;
+        self.block_min().offset(last, last, last)
     }
 }
 
@@ -75,6 +86,42 @@ impl Chunk {
         }
     }
 
+    /// Creates a chunk from its packed indexes and palette,
+    /// as returned by [`into_parts()`](Self::into_parts).
+    ///
+    /// Returns `None` if the indexes do not have one entry per
+    /// block or if any index is out of bounds of the palette.
+    pub fn from_parts(indexes: PackedArray, palette: Vec<BlockId>) -> Option<Self> {
+        if indexes.len() != CHUNK_VOLUME
+            || palette.is_empty()
+            || indexes.iter().any(|index| index as usize >= palette.len())
+        {
+            return None;
+        }
+        Some(Self { indexes, palette })
+    }
+
+    /// Creates a chunk from its palette and the palette index of each
+    /// block, ordered like [`indexes()`](Self::indexes). Packs the indexes
+    /// with as few bits as the palette allows.
+    ///
+    /// Returns `None` under the same conditions as [`from_parts()`](Self::from_parts).
+    pub fn from_palette_indexes(
+        palette: Vec<BlockId>,
+        indexes: impl IntoIterator<Item = u64>,
+    ) -> Option<Self> {
+        let mut bits_per_block = INITIAL_BITS_PER_BLOCK;
+        while palette.len() > 1 << bits_per_block {
+            bits_per_block += 1;
+        }
+        Self::from_parts(PackedArray::from_iter(indexes, bits_per_block), palette)
+    }
+
+    /// Decomposes this chunk into its packed indexes and palette.
+    pub fn into_parts(self) -> (PackedArray, Vec<BlockId>) {
+        (self.indexes, self.palette)
+    }
+
     /// Gets the block at the given position within this chunk.
     ///
     /// # Panics
@@ -184,4 +231,14 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn chunk_parts_roundtrip() {
+        let mut chunk = Chunk::new();
+        chunk.set(1, 2, 3, BlockId::new(blocks::Stone));
+        let (indexes, palette) = chunk.into_parts();
+        assert!(Chunk::from_parts(indexes.clone(), vec![BlockId::new(blocks::Air)]).is_none());
+        let chunk = Chunk::from_parts(indexes, palette).unwrap();
+        assert!(chunk.get(1, 2, 3).is::<blocks::Stone>());
+    }
 }
@@ -0,0 +1,361 @@
+//! Rules for block edits made by players.
+//!
+//! The server uses these rules to validate edits, and
+//! the client uses them to predict the outcome of its own.
+//! Both use [`is_in_reach`] to decide which blocks a player can
+//! reach, so the client only targets blocks the server lets it edit.
+
+use glam::Vec3A;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::Facing,
+    blocks::{Air, Door, Ladder, Trapdoor},
+    entity::player::GameMode,
+    BlockId, BlockPos,
+};
+
+/// How far players can reach to edit blocks in each game mode,
+/// in blocks from their eyes to the nearest point of the block.
+///
+/// The server is configured with these distances and sends
+/// each player the one for their game mode.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reach {
+    pub survival: f32,
+    pub creative: f32,
+}
+
+impl Default for Reach {
+    fn default() -> Self {
+        Self {
+            survival: 5.,
+            creative: 8.,
+        }
+    }
+}
+
+impl Reach {
+    /// Returns the reach of players in `mode`.
+    pub fn for_mode(self, mode: GameMode) -> f32 {
+        match mode {
+            GameMode::Survival => self.survival,
+            GameMode::Creative => self.creative,
+        }
+    }
+}
+
+/// Returns whether a player whose eyes are at `eye` can
+/// edit the block at `pos` with the given reach.
+pub fn is_in_reach(eye: Vec3A, pos: BlockPos, reach: f32) -> bool {
+    let min = pos.min_corner();
+    let nearest = eye.max(min).min(min + Vec3A::one());
+    (nearest - eye).length_squared() <= reach * reach
+}
+
+/// Orients `block` to be placed at `pos` against the face of the
+/// block at `against`, so that ladders face away from the wall they
+/// are placed on. Returns `None` if `block` can't be placed against
+/// that face, like a ladder against a floor.
+pub fn orient(block: BlockId, pos: BlockPos, against: BlockPos) -> Option<BlockId> {
+    match block.cast::<Ladder>() {
+        Some(_) => {
+            let facing =
+                Facing::from_offset(pos.x - against.x, pos.y - against.y, pos.z - against.z)?;
+            Some(BlockId::new(Ladder { facing }))
+        }
+        None => Some(block),
+    }
+}
+
+/// An edit requested by a player.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlockEdit {
+    /// Place a block where there is air.
+    Place(BlockId),
+    /// Replace a block with air.
+    Break,
+    /// Interact with a block, e.g. to open a door.
+    Use,
+}
+
+impl BlockEdit {
+    /// Computes the blocks changed by applying this edit at `pos`.
+    /// `block_at` should return the current block at a position,
+    /// or `None` if it is not known.
+    ///
+    /// Edits to multi-block structures like doors change every
+    /// block in the structure. Returns `None` if the edit is
+    /// not allowed.
+    pub fn apply(
+        self,
+        pos: BlockPos,
+        mut block_at: impl FnMut(BlockPos) -> Option<BlockId>,
+    ) -> Option<Vec<(BlockPos, BlockId)>> {
+        let current = block_at(pos)?;
+        match self {
+            BlockEdit::Place(block) => {
+                if !block.is_valid() || block.is::<Air>() || !current.is::<Air>() {
+                    return None;
+                }
+                if let Some(ladder) = block.cast::<Ladder>() {
+                    // Ladders hang on a full block behind them.
+                    if !block_at(ladder.wall(pos))?.is_opaque() {
+                        return None;
+                    }
+                }
+
+                match block.cast::<Door>() {
+                    Some(door) => {
+                        // Doors are placed by their lower half, which
+                        // must stand on a solid block with air above it.
+                        let upper = door.other_half(pos);
+                        if door.upper
+                            || !block_at(pos.offset(0, -1, 0))?.is_solid()
+                            || !block_at(upper)?.is::<Air>()
+                        {
+                            return None;
+                        }
+                        Some(vec![
+                            (pos, block),
+                            (
+                                upper,
+                                BlockId::new(Door {
+                                    upper: true,
+                                    ..door
+                                }),
+                            ),
+                        ])
+                    }
+                    None => Some(vec![(pos, block)]),
+                }
+            }
+            BlockEdit::Break => {
+                if current.is::<Air>() {
+                    return None;
+                }
+
+                let air = BlockId::new(Air);
+                let mut changes = vec![(pos, air)];
+                if let Some(door) = current.cast::<Door>() {
+                    let other = door.other_half(pos);
+                    if block_at(other).map_or(false, |block| block.is::<Door>()) {
+                        changes.push((other, air));
+                    }
+                }
+                Some(changes)
+            }
+            BlockEdit::Use => {
+                if let Some(door) = current.cast::<Door>() {
+                    let open = !door.open;
+                    let other = door.other_half(pos);
+                    let mut changes = vec![(pos, BlockId::new(Door { open, ..door }))];
+                    if let Some(other_door) = block_at(other).and_then(|block| block.cast::<Door>())
+                    {
+                        changes.push((other, BlockId::new(Door { open, ..other_door })));
+                    }
+                    Some(changes)
+                } else {
+                    let trapdoor = current.cast::<Trapdoor>()?;
+                    Some(vec![(
+                        pos,
+                        BlockId::new(Trapdoor {
+                            open: !trapdoor.open,
+                            ..trapdoor
+                        }),
+                    )])
+                }
+            }
+        }
+    }
+
+    /// Returns every position an edit at `pos` could change.
+    ///
+    /// When an edit is rejected, these blocks are resent to
+    /// the player so any prediction of the edit is undone.
+    pub fn affected_positions(pos: BlockPos) -> [BlockPos; 3] {
+        // Doors are the only multi-block structure, and
+        // they extend one block up or down.
+        [pos, pos.offset(0, 1, 0), pos.offset(0, -1, 0)]
+    }
+}
+
+/// The number of crack stages a block goes through while it is dug.
+pub const DIG_STAGES: u8 = 8;
+
+/// A block being dug by a player, and how far along it is.
+///
+/// Players send the stage to the server as it changes, which shares
+/// it with other players so they can draw the cracks too.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Digging {
+    pub pos: BlockPos,
+    /// The crack stage, less than [`DIG_STAGES`].
+    pub stage: u8,
+}
+
+impl Digging {
+    /// Returns the state of digging the block at `pos` after
+    /// `progress` of it was dug, from 0 to 1.
+    pub fn new(pos: BlockPos, progress: f32) -> Self {
+        let stage = (progress.max(0.) * DIG_STAGES as f32) as u8;
+        Self {
+            pos,
+            stage: stage.min(DIG_STAGES - 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ahash::AHashMap;
+
+    use super::*;
+    use crate::blocks::Stone;
+
+    fn pos(y: i32) -> BlockPos {
+        BlockPos { x: 0, y, z: 0 }
+    }
+
+    fn door(open: bool, upper: bool) -> BlockId {
+        BlockId::new(Door {
+            open,
+            upper,
+            powered: false,
+        })
+    }
+
+    /// Returns a column with stone at y = 0 and air above it.
+    fn column() -> AHashMap<BlockPos, BlockId> {
+        let mut blocks: AHashMap<_, _> = (1..4).map(|y| (pos(y), BlockId::new(Air))).collect();
+        blocks.insert(pos(0), BlockId::new(Stone));
+        blocks
+    }
+
+    #[test]
+    fn reach_is_measured_to_the_nearest_point() {
+        let eye = glam::vec3a(0.5, 1.6, 0.5);
+        // The nearest face of this block is 4.5 blocks away,
+        // but its center is 5 blocks away.
+        let block = BlockPos { x: 5, y: 1, z: 0 };
+        assert!(is_in_reach(eye, block, 4.5));
+        assert!(!is_in_reach(eye, block, 4.4));
+        // Inside the block.
+        assert!(is_in_reach(eye, pos(1), 0.));
+
+        let reach = Reach::default();
+        assert!(reach.for_mode(GameMode::Creative) > reach.for_mode(GameMode::Survival));
+    }
+
+    #[test]
+    fn place_requires_air() {
+        let blocks = column();
+        let stone = BlockId::new(Stone);
+        let block_at = |pos| blocks.get(&pos).copied();
+        assert_eq!(
+            BlockEdit::Place(stone).apply(pos(1), block_at),
+            Some(vec![(pos(1), stone)])
+        );
+        assert_eq!(BlockEdit::Place(stone).apply(pos(0), block_at), None);
+        assert_eq!(
+            BlockEdit::Place(BlockId::new(Air)).apply(pos(1), block_at),
+            None
+        );
+        assert_eq!(
+            BlockEdit::Place(BlockId::from_raw_parts(u32::MAX, 0)).apply(pos(1), block_at),
+            None
+        );
+    }
+
+    #[test]
+    fn break_requires_solid() {
+        let blocks = column();
+        let block_at = |pos| blocks.get(&pos).copied();
+        assert_eq!(
+            BlockEdit::Break.apply(pos(0), block_at),
+            Some(vec![(pos(0), BlockId::new(Air))])
+        );
+        assert_eq!(BlockEdit::Break.apply(pos(1), block_at), None);
+    }
+
+    #[test]
+    fn doors_need_ground_and_headroom() {
+        let mut blocks = column();
+        let block_at = |pos| blocks.get(&pos).copied();
+        let place = BlockEdit::Place(door(false, false));
+        assert_eq!(
+            place.apply(pos(1), block_at),
+            Some(vec![
+                (pos(1), door(false, false)),
+                (pos(2), door(false, true))
+            ])
+        );
+        assert_eq!(place.apply(pos(2), block_at), None);
+        assert_eq!(
+            BlockEdit::Place(door(false, true)).apply(pos(1), block_at),
+            None
+        );
+
+        blocks.insert(pos(2), BlockId::new(Stone));
+        assert_eq!(place.apply(pos(1), |pos| blocks.get(&pos).copied()), None);
+    }
+
+    #[test]
+    fn door_halves_are_linked() {
+        let mut blocks = column();
+        blocks.insert(pos(1), door(false, false));
+        blocks.insert(pos(2), door(false, true));
+        let block_at = |pos| blocks.get(&pos).copied();
+
+        let air = BlockId::new(Air);
+        assert_eq!(
+            BlockEdit::Break.apply(pos(2), block_at),
+            Some(vec![(pos(2), air), (pos(1), air)])
+        );
+        assert_eq!(
+            BlockEdit::Use.apply(pos(1), block_at),
+            Some(vec![
+                (pos(1), door(true, false)),
+                (pos(2), door(true, true))
+            ])
+        );
+        assert_eq!(BlockEdit::Use.apply(pos(0), block_at), None);
+    }
+
+    #[test]
+    fn ladders_hang_on_walls() {
+        let mut blocks = column();
+        blocks.insert(BlockPos { x: -1, y: 1, z: 0 }, BlockId::new(Stone));
+        let block_at = |pos| blocks.get(&pos).copied();
+        let wall = BlockPos { x: -1, y: 1, z: 0 };
+        let ladder = |facing| BlockId::new(Ladder { facing });
+
+        // Placed against the wall, the ladder faces away from it.
+        let placed = orient(ladder(Facing::PosZ), pos(1), wall);
+        assert_eq!(placed, Some(ladder(Facing::PosX)));
+        assert_eq!(
+            BlockEdit::Place(placed.unwrap()).apply(pos(1), block_at),
+            Some(vec![(pos(1), ladder(Facing::PosX))])
+        );
+        // Not against floors or thin air.
+        assert_eq!(orient(ladder(Facing::PosZ), pos(1), pos(0)), None);
+        assert_eq!(
+            BlockEdit::Place(ladder(Facing::NegX)).apply(pos(1), block_at),
+            None
+        );
+        // Other blocks aren't turned.
+        let stone = BlockId::new(Stone);
+        assert_eq!(orient(stone, pos(1), pos(0)), Some(stone));
+    }
+
+    #[test]
+    fn dig_stages() {
+        let stage = |progress| Digging::new(pos(0), progress).stage;
+        assert_eq!(stage(0.), 0);
+        assert_eq!(stage(0.2), 1);
+        assert_eq!(stage(0.99), DIG_STAGES - 1);
+        // Out of range progress is clamped.
+        assert_eq!(stage(-1.), 0);
+        assert_eq!(stage(1.5), DIG_STAGES - 1);
+    }
+}
@@ -4,6 +4,8 @@
 use glam::{Vec2, Vec3A};
 use hecs::Bundle;
 
+use crate::{item::ItemStack, BlockId, WorldPos};
+
 pub mod player;
 
 /// The "base" bundle of components for an entity. All non-block
@@ -14,11 +16,11 @@ pub struct BaseBundle {
     pub orient: Orient,
 }
 
-/// The position of an entity. This is
+/// The position of an entity in its zone. This is
 /// the center of the bottom of its bounding box.
 /// _Mandatory_ for all non-block entities.
 #[derive(Copy, Clone, Debug)]
-pub struct Pos(pub Vec3A);
+pub struct Pos(pub WorldPos);
 
 /// The orientation of an entity.
 ///
@@ -32,3 +34,47 @@ pub struct Orient(pub Vec2);
 /// per second.
 #[derive(Default, Copy, Clone, Debug)]
 pub struct Vel(pub Vec3A);
+
+/// A block falling under gravity, like sand that lost its support.
+/// Turns back into a block when it lands.
+#[derive(Copy, Clone, Debug)]
+pub struct FallingBlock(pub BlockId);
+
+/// A stack of items lying in the world, picked
+/// up by players who touch it.
+#[derive(Copy, Clone, Debug)]
+pub struct ItemDrop(pub ItemStack);
+
+/// An orb of experience points, collected by
+/// players who come close to it.
+#[derive(Copy, Clone, Debug)]
+pub struct XpOrb {
+    /// The number of experience points the orb is worth.
+    pub value: u32,
+}
+
+/// The health of an entity that can be hurt.
+/// The entity dies when its health reaches zero.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Health {
+    /// Creates full health.
+    pub fn full(max: u32) -> Self {
+        Self { current: max, max }
+    }
+
+    /// Takes away up to `amount` health. Returns
+    /// whether the entity has no health left.
+    pub fn damage(&mut self, amount: u32) -> bool {
+        self.current = self.current.saturating_sub(amount);
+        self.is_dead()
+    }
+
+    pub fn is_dead(self) -> bool {
+        self.current == 0
+    }
+}
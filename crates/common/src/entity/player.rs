@@ -1,14 +1,106 @@
+use std::{fmt, str::FromStr};
+
+use glam::Vec3A;
+use serde::{Deserialize, Serialize};
+
 use crate::ChunkPos;
 
 use super::BaseBundle;
 
+/// The height of a player's eyes above their position.
+pub const EYE_HEIGHT: f32 = 1.6;
+/// The [health](super::Health) of a player who just joined or respawned.
+pub const MAX_HEALTH: u32 = 20;
+
 /// Base components required for all players.
 pub type PlayerBundle = BaseBundle;
 
+/// Determines the position of a player's eyes.
+pub fn eye_pos(pos: Vec3A) -> Vec3A {
+    pos + glam::vec3a(0., EYE_HEIGHT, 0.)
+}
+
 /// A player's username.
 #[derive(Debug)]
 pub struct Username(pub String);
 
+/// A player's game mode. For now, game modes
+/// only differ in how far players can [reach](crate::edit::Reach).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    Survival,
+    Creative,
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Survival
+    }
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            GameMode::Survival => "survival",
+            GameMode::Creative => "creative",
+        })
+    }
+}
+
+impl FromStr for GameMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "survival" => Ok(GameMode::Survival),
+            "creative" => Ok(GameMode::Creative),
+            _ => anyhow::bail!("unknown game mode '{}'", s),
+        }
+    }
+}
+
+/// A player's experience points, gained by collecting
+/// [`XpOrb`](super::XpOrb)s. The total determines the player's level.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Experience {
+    pub total: u32,
+}
+
+impl Experience {
+    pub fn new(total: u32) -> Self {
+        Self { total }
+    }
+
+    /// Returns the number of points needed to
+    /// advance from `level` to the next level.
+    pub fn points_for_level(level: u32) -> u32 {
+        7 + 2 * level
+    }
+
+    /// Returns the player's level, starting at 0.
+    pub fn level(self) -> u32 {
+        self.level_and_remainder().0
+    }
+
+    /// Returns how far the player is from their level to the
+    /// next one, from 0 (just reached it) to 1 (exclusive).
+    pub fn progress(self) -> f32 {
+        let (level, remainder) = self.level_and_remainder();
+        remainder as f32 / Self::points_for_level(level) as f32
+    }
+
+    /// Returns the level and the points gained since reaching it.
+    fn level_and_remainder(self) -> (u32, u32) {
+        let mut level = 0;
+        let mut remainder = self.total;
+        while remainder >= Self::points_for_level(level) {
+            remainder -= Self::points_for_level(level);
+            level += 1;
+        }
+        (level, remainder)
+    }
+}
+
 /// A view, encapsulating the set of chunks visible to a player.
 ///
 /// A player's view is defined as a cube with the center equal
@@ -110,3 +202,27 @@ impl View {
         self.center.z + self.distance
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_mode_names() {
+        for &mode in &[GameMode::Survival, GameMode::Creative] {
+            assert_eq!(mode.to_string().parse::<GameMode>().unwrap(), mode);
+        }
+        assert!("spectator".parse::<GameMode>().is_err());
+    }
+
+    #[test]
+    fn experience_levels() {
+        assert_eq!(Experience::new(0).level(), 0);
+        assert_eq!(Experience::new(6).level(), 0);
+        assert_eq!(Experience::new(7).level(), 1);
+        assert_eq!(Experience::new(7).progress(), 0.);
+        // Level 1 takes 9 more points.
+        assert_eq!(Experience::new(16).level(), 2);
+        assert!((Experience::new(11).progress() - 4. / 9.).abs() < 1e-6);
+    }
+}
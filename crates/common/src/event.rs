@@ -1,6 +1,8 @@
 use std::{
-    any::{Any, TypeId},
+    any::{self, Any, TypeId},
     collections::VecDeque,
+    env,
+    fmt::Write,
 };
 
 use ahash::AHashMap;
@@ -23,10 +25,19 @@ use ahash::AHashMap;
 /// were added. Each event is associated with the _system index_
 /// it was invoked by. When that system runs again, the bus assumes
 /// all other systems have observed those events are therefore drops them.
+///
+/// # Tracing
+/// Because events are dropped based on system order, a system
+/// added in the wrong place silently misses events. To debug such
+/// problems, [`EventBus::enable_tracing`] makes the bus record which
+/// events each system pushed during the last few ticks, and
+/// [`EventBus::dump`] prints that record.
 #[derive(Default)]
 pub struct EventBus {
     seats: AHashMap<TypeId, Box<dyn ErasedSeat>>,
     system: usize,
+    /// Present if tracing is enabled.
+    trace: Option<Trace>,
 }
 
 impl EventBus {
@@ -34,6 +45,50 @@ impl EventBus {
         Self::default()
     }
 
+    /// Enables tracing if `VOLTZ_TRACE_EVENTS` is set to
+    /// the number of ticks to keep.
+    pub fn enable_tracing_from_env(&mut self) {
+        let value = match env::var("VOLTZ_TRACE_EVENTS") {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+        match value.parse() {
+            Ok(ticks) => {
+                log::info!("Tracing events of the last {} ticks", ticks);
+                self.enable_tracing(ticks);
+            }
+            Err(_) => log::error!("Ignoring invalid VOLTZ_TRACE_EVENTS '{}'", value),
+        }
+    }
+
+    /// Starts recording the events pushed in each tick,
+    /// keeping the record of the last `ticks` ticks.
+    pub fn enable_tracing(&mut self, ticks: usize) {
+        self.trace = Some(Trace::new(ticks));
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    /// Marks the start of a new tick in the trace.
+    /// Does nothing if tracing is disabled.
+    pub fn begin_tick(&mut self) {
+        if let Some(trace) = &mut self.trace {
+            trace.begin_tick();
+        }
+    }
+
+    /// Prints the events pushed during the last `ticks` ticks,
+    /// grouped by tick and producing system index. Returns an
+    /// empty string if tracing is disabled.
+    pub fn dump(&self, ticks: usize) -> String {
+        match &self.trace {
+            Some(trace) => trace.dump(ticks),
+            None => String::new(),
+        }
+    }
+
     pub fn set_system(&mut self, system: usize) {
         self.system = system;
         for seat in self.seats.values_mut() {
@@ -46,6 +101,9 @@ impl EventBus {
         T: 'static,
     {
         let system = self.system;
+        if let Some(trace) = &mut self.trace {
+            trace.record(system, any::type_name::<T>());
+        }
         let seat = self.seat::<T>();
         seat.push(event, system);
     }
@@ -71,6 +129,90 @@ impl EventBus {
     }
 }
 
+/// The events pushed during recent ticks.
+struct Trace {
+    /// The recorded ticks, oldest first.
+    ticks: VecDeque<TracedTick>,
+    capacity: usize,
+    next_tick: u64,
+}
+
+/// The events pushed during one tick, in the order
+/// each (system, event type) pair first occurred.
+struct TracedTick {
+    tick: u64,
+    events: Vec<TracedEvents>,
+}
+
+struct TracedEvents {
+    system: usize,
+    type_name: &'static str,
+    count: usize,
+}
+
+impl Trace {
+    fn new(capacity: usize) -> Self {
+        Self {
+            ticks: VecDeque::with_capacity(capacity),
+            capacity,
+            next_tick: 0,
+        }
+    }
+
+    fn begin_tick(&mut self) {
+        if self.ticks.len() == self.capacity {
+            self.ticks.pop_front();
+        }
+        if self.capacity > 0 {
+            self.ticks.push_back(TracedTick {
+                tick: self.next_tick,
+                events: Vec::new(),
+            });
+        }
+        self.next_tick += 1;
+    }
+
+    fn record(&mut self, system: usize, type_name: &'static str) {
+        let tick = match self.ticks.back_mut() {
+            Some(tick) => tick,
+            // Events pushed before the first tick aren't recorded.
+            None => return,
+        };
+        match tick
+            .events
+            .iter_mut()
+            .find(|events| events.system == system && events.type_name == type_name)
+        {
+            Some(events) => events.count += 1,
+            None => tick.events.push(TracedEvents {
+                system,
+                type_name,
+                count: 1,
+            }),
+        }
+    }
+
+    fn dump(&self, ticks: usize) -> String {
+        let mut output = String::new();
+        let skip = self.ticks.len().saturating_sub(ticks);
+        for tick in self.ticks.iter().skip(skip) {
+            writeln!(output, "Tick {}:", tick.tick).unwrap();
+            if tick.events.is_empty() {
+                writeln!(output, "  (no events)").unwrap();
+            }
+            for events in &tick.events {
+                writeln!(
+                    output,
+                    "  system {:>3}  {:>5}x {}",
+                    events.system, events.count, events.type_name
+                )
+                .unwrap();
+            }
+        }
+        output
+    }
+}
+
 trait ErasedSeat {
     fn as_any_mut(&mut self) -> &mut dyn Any;
     fn advance_to(&mut self, system_index: usize);
@@ -161,4 +303,34 @@ mod tests {
         bus.set_system(1);
         assert_eq!(bus.iter::<i32>().count(), 0);
     }
+
+    #[test]
+    fn tracing() {
+        let mut bus = EventBus::new();
+        assert_eq!(bus.dump(10), "");
+        bus.enable_tracing(2);
+
+        for tick in 0..3 {
+            bus.begin_tick();
+            bus.set_system(0);
+            bus.push(tick);
+            bus.set_system(2);
+            bus.push(tick);
+            bus.push(tick);
+            bus.push("event");
+        }
+
+        assert_eq!(
+            bus.dump(10),
+            "Tick 1:\n  \
+             system   0      1x i32\n  \
+             system   2      2x i32\n  \
+             system   2      1x &str\n\
+             Tick 2:\n  \
+             system   0      1x i32\n  \
+             system   2      2x i32\n  \
+             system   2      1x &str\n"
+        );
+        assert!(bus.dump(1).starts_with("Tick 2:"));
+    }
 }
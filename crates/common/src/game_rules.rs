@@ -0,0 +1,271 @@
+//! Game rules: per-world settings which change how the game plays.
+//!
+//! The server keeps a world's [`GameRules`] in its save and lets
+//! operators change them. Rules which [affect what clients
+//! predict](GameRule::is_synced) are also sent to clients.
+
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+/// A game rule.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum GameRule {
+    /// Whether players keep their inventory when they die.
+    KeepInventory,
+    /// Whether the time of day advances.
+    DoDaylightCycle,
+    /// Whether the weather changes on its own.
+    DoWeatherCycle,
+    /// Whether players take damage from falling.
+    FallDamage,
+    /// Whether mined Melium drops experience orbs.
+    DoXpDrops,
+    /// Whether blocks broken by players drop items.
+    DoTileDrops,
+    /// The number of random ticks in each chunk per tick.
+    RandomTickSpeed,
+}
+
+impl GameRule {
+    /// Every game rule.
+    pub const ALL: [GameRule; 7] = [
+        GameRule::KeepInventory,
+        GameRule::DoDaylightCycle,
+        GameRule::DoWeatherCycle,
+        GameRule::FallDamage,
+        GameRule::DoXpDrops,
+        GameRule::DoTileDrops,
+        GameRule::RandomTickSpeed,
+    ];
+
+    /// Returns the name used for the rule in
+    /// commands and saves, such as `keepInventory`.
+    pub fn name(self) -> &'static str {
+        match self {
+            GameRule::KeepInventory => "keepInventory",
+            GameRule::DoDaylightCycle => "doDaylightCycle",
+            GameRule::DoWeatherCycle => "doWeatherCycle",
+            GameRule::FallDamage => "fallDamage",
+            GameRule::DoXpDrops => "doXpDrops",
+            GameRule::DoTileDrops => "doTileDrops",
+            GameRule::RandomTickSpeed => "randomTickSpeed",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|rule| rule.name() == name)
+    }
+
+    /// Returns the value of the rule in new worlds.
+    pub fn default_value(self) -> RuleValue {
+        match self {
+            GameRule::KeepInventory => RuleValue::Bool(false),
+            GameRule::DoDaylightCycle => RuleValue::Bool(true),
+            GameRule::DoWeatherCycle => RuleValue::Bool(true),
+            GameRule::FallDamage => RuleValue::Bool(true),
+            GameRule::DoXpDrops => RuleValue::Bool(true),
+            GameRule::DoTileDrops => RuleValue::Bool(true),
+            GameRule::RandomTickSpeed => RuleValue::Int(3),
+        }
+    }
+
+    /// Returns whether clients are told the rule's value
+    /// because they use it to predict the game.
+    pub fn is_synced(self) -> bool {
+        matches!(self, GameRule::DoDaylightCycle | GameRule::FallDamage)
+    }
+}
+
+impl fmt::Display for GameRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The value of a game rule.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleValue {
+    Bool(bool),
+    /// A non-negative integer.
+    Int(i32),
+}
+
+impl RuleValue {
+    /// Parses a value of the same type as this one.
+    pub fn parse_same_type(self, s: &str) -> anyhow::Result<RuleValue> {
+        match self {
+            RuleValue::Bool(_) => match s {
+                "true" => Ok(RuleValue::Bool(true)),
+                "false" => Ok(RuleValue::Bool(false)),
+                _ => bail!("'{}' is not true or false", s),
+            },
+            RuleValue::Int(_) => {
+                let value: i32 = s
+                    .parse()
+                    .with_context(|| format!("'{}' is not an integer", s))?;
+                if value < 0 {
+                    bail!("the value can't be negative");
+                }
+                Ok(RuleValue::Int(value))
+            }
+        }
+    }
+
+    fn has_same_type(self, other: RuleValue) -> bool {
+        matches!(
+            (self, other),
+            (RuleValue::Bool(_), RuleValue::Bool(_)) | (RuleValue::Int(_), RuleValue::Int(_))
+        )
+    }
+}
+
+impl fmt::Display for RuleValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleValue::Bool(value) => write!(f, "{}", value),
+            RuleValue::Int(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// The values of all game rules.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameRules {
+    /// Rules which don't have their default value.
+    changed: BTreeMap<GameRule, RuleValue>,
+}
+
+impl GameRules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, rule: GameRule) -> RuleValue {
+        self.changed
+            .get(&rule)
+            .copied()
+            .unwrap_or_else(|| rule.default_value())
+    }
+
+    /// Gets the value of a boolean rule.
+    ///
+    /// # Panics
+    /// Panics if the rule is not boolean.
+    pub fn get_bool(&self, rule: GameRule) -> bool {
+        match self.get(rule) {
+            RuleValue::Bool(value) => value,
+            value => panic!("{} is not a boolean rule (value: {})", rule, value),
+        }
+    }
+
+    /// Gets the value of an integer rule.
+    ///
+    /// # Panics
+    /// Panics if the rule is not an integer.
+    pub fn get_int(&self, rule: GameRule) -> i32 {
+        match self.get(rule) {
+            RuleValue::Int(value) => value,
+            value => panic!("{} is not an integer rule (value: {})", rule, value),
+        }
+    }
+
+    /// Sets a rule. Fails if the value has the wrong type.
+    pub fn set(&mut self, rule: GameRule, value: RuleValue) -> anyhow::Result<()> {
+        let default = rule.default_value();
+        if !value.has_same_type(default) {
+            bail!("{} can't be set to {}", rule, value);
+        }
+        if value == default {
+            self.changed.remove(&rule);
+        } else {
+            self.changed.insert(rule, value);
+        }
+        Ok(())
+    }
+
+    /// Iterates over all rules and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (GameRule, RuleValue)> + '_ {
+        GameRule::ALL
+            .iter()
+            .map(move |&rule| (rule, self.get(rule)))
+    }
+
+    /// Returns the rules which differ from their defaults by name,
+    /// which unlike the [`GameRule`] variants stay the same as
+    /// rules are added.
+    pub fn to_named(&self) -> Vec<(String, RuleValue)> {
+        self.changed
+            .iter()
+            .map(|(rule, &value)| (rule.name().to_owned(), value))
+            .collect()
+    }
+
+    /// Creates rules from their values by name. Returns the
+    /// rules along with the names of any unknown or invalid rules,
+    /// which are left at their defaults.
+    pub fn from_named(named: Vec<(String, RuleValue)>) -> (Self, Vec<String>) {
+        let mut rules = Self::new();
+        let mut invalid = Vec::new();
+        for (name, value) in named {
+            let valid =
+                GameRule::from_name(&name).map_or(false, |rule| rules.set(rule, value).is_ok());
+            if !valid {
+                invalid.push(name);
+            }
+        }
+        (rules, invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        for &rule in &GameRule::ALL {
+            assert_eq!(GameRule::from_name(rule.name()), Some(rule));
+        }
+        assert_eq!(GameRule::from_name("mobGriefing"), None);
+    }
+
+    #[test]
+    fn values_keep_their_type() {
+        let mut rules = GameRules::new();
+        assert!(rules.get_bool(GameRule::FallDamage));
+        rules
+            .set(GameRule::FallDamage, RuleValue::Bool(false))
+            .unwrap();
+        assert!(!rules.get_bool(GameRule::FallDamage));
+        assert!(rules.set(GameRule::FallDamage, RuleValue::Int(1)).is_err());
+
+        let value = rules.get(GameRule::RandomTickSpeed);
+        assert_eq!(value.parse_same_type("10").unwrap(), RuleValue::Int(10));
+        assert!(value.parse_same_type("-1").is_err());
+        assert!(value.parse_same_type("true").is_err());
+    }
+
+    #[test]
+    fn named_round_trip() {
+        let mut rules = GameRules::new();
+        rules
+            .set(GameRule::KeepInventory, RuleValue::Bool(true))
+            .unwrap();
+        rules
+            .set(GameRule::RandomTickSpeed, RuleValue::Int(0))
+            .unwrap();
+
+        let mut named = rules.to_named();
+        assert_eq!(named.len(), 2);
+        named.push(("removedRule".to_owned(), RuleValue::Bool(true)));
+        named.push(("fallDamage".to_owned(), RuleValue::Int(2)));
+        let (loaded, invalid) = GameRules::from_named(named);
+        assert_eq!(loaded, rules);
+        assert_eq!(
+            invalid,
+            vec!["removedRule".to_owned(), "fallDamage".to_owned()]
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Heightmaps of chunk columns.
+//!
+//! A [`Heightmap`] records the highest non-air block of each block column
+//! in a chunk column. Zones keep one for each of their chunk columns and
+//! update it as blocks and chunks change, so finding the top of a column,
+//! like the ground to place something on, doesn't scan down through
+//! the air above it.
+
+use crate::{blocks::Air, chunk::CHUNK_DIM, BlockId, BlockPos, Chunk};
+
+const COLUMNS: usize = CHUNK_DIM * CHUNK_DIM;
+
+/// The heights of the highest non-air blocks in the
+/// block columns of a chunk column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Heightmap {
+    /// The height of each block column, ordered
+    /// by Z then X. `None` for columns of air.
+    heights: [Option<i32>; COLUMNS],
+}
+
+impl Default for Heightmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Heightmap {
+    /// Creates a heightmap of a chunk column of air.
+    pub fn new() -> Self {
+        Self {
+            heights: [None; COLUMNS],
+        }
+    }
+
+    /// Gets the Y coordinate of the highest non-air block at the given
+    /// chunk-local X and Z coordinates, or `None` if the column is all air.
+    pub fn get(&self, x: usize, z: usize) -> Option<i32> {
+        self.heights[z * CHUNK_DIM + x]
+    }
+
+    /// Sets the height of the block column at the
+    /// given chunk-local X and Z coordinates.
+    pub fn set(&mut self, x: usize, z: usize, height: Option<i32>) {
+        self.heights[z * CHUNK_DIM + x] = height;
+    }
+
+    /// Updates the height of the block column at the given chunk-local
+    /// X and Z coordinates after its block at `y` was set to `block`.
+    ///
+    /// Returns `false` if that was the top block and it was replaced
+    /// with air. The new top is then somewhere below, and the
+    /// caller should find it and [`set`](Self::set) it.
+    pub fn update(&mut self, x: usize, z: usize, y: i32, block: BlockId) -> bool {
+        let height = self.get(x, z);
+        if block.is::<Air>() {
+            height != Some(y)
+        } else {
+            if height.map_or(true, |height| y > height) {
+                self.set(x, z, Some(y));
+            }
+            true
+        }
+    }
+
+    /// Returns the chunk-local X and Z coordinates of the block columns
+    /// whose top could have changed when the blocks at or below `y`
+    /// changed: those that are all air or whose top is at or below `y`.
+    pub fn columns_at_or_below(&self, y: i32) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.heights
+            .iter()
+            .enumerate()
+            .filter(move |(_, height)| height.map_or(true, |height| height <= y))
+            .map(|(index, _)| (index % CHUNK_DIM, index / CHUNK_DIM))
+    }
+}
+
+/// Finds the Y coordinate of the highest non-air block at or below
+/// `from_y` in the block column at `(x, z)`. `chunks` are the loaded
+/// chunks of its chunk column with their Y coordinates, from top
+/// to bottom. Chunks of air are skipped without looking at their blocks.
+pub(crate) fn scan_column<'a>(
+    x: i32,
+    z: i32,
+    from_y: i32,
+    chunks: impl IntoIterator<Item = (i32, &'a Chunk)>,
+) -> Option<i32> {
+    let (local_x, _, local_z) = BlockPos { x, y: 0, z }.chunk_local();
+    for (chunk_y, chunk) in chunks {
+        let bottom = chunk_y * CHUNK_DIM as i32;
+        if from_y < bottom || chunk.is_empty() {
+            continue;
+        }
+        let top = ((from_y - bottom) as usize).min(CHUNK_DIM - 1);
+        let highest = (0..=top)
+            .rev()
+            .find(|&local_y| !chunk.get(local_x, local_y, local_z).is::<Air>());
+        if let Some(local_y) = highest {
+            return Some(bottom + local_y as i32);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::Stone;
+
+    #[test]
+    fn updates_follow_block_changes() {
+        let mut heightmap = Heightmap::new();
+        let stone = BlockId::new(Stone);
+        let air = BlockId::new(Air);
+
+        assert!(heightmap.update(3, 4, 10, stone));
+        assert_eq!(heightmap.get(3, 4), Some(10));
+        assert_eq!(heightmap.get(4, 3), None);
+        // Blocks below the top and air above it change nothing.
+        assert!(heightmap.update(3, 4, 5, stone));
+        assert!(heightmap.update(3, 4, 12, air));
+        assert_eq!(heightmap.get(3, 4), Some(10));
+        // Removing the top needs a scan.
+        assert!(!heightmap.update(3, 4, 10, air));
+
+        heightmap.set(3, 4, Some(20));
+        let columns: Vec<_> = heightmap.columns_at_or_below(15).collect();
+        assert_eq!(columns.len(), COLUMNS - 1);
+        assert!(!columns.contains(&(3, 4)));
+        assert!(columns.contains(&(4, 3)));
+    }
+
+    #[test]
+    fn scans_columns_from_the_top() {
+        let mut lower = Chunk::new();
+        lower.set(1, 2, 3, BlockId::new(Stone));
+        lower.set(1, 9, 3, BlockId::new(Stone));
+        let upper = Chunk::new();
+        let chunks = [(1, &upper), (0, &lower)];
+
+        assert_eq!(scan_column(1, 3, 31, chunks.iter().copied()), Some(9));
+        assert_eq!(scan_column(1, 3, 8, chunks.iter().copied()), Some(2));
+        assert_eq!(scan_column(1, 3, 1, chunks.iter().copied()), None);
+        assert_eq!(scan_column(17, -13, 31, chunks.iter().copied()), Some(9));
+        assert_eq!(scan_column(2, 3, 31, chunks.iter().copied()), None);
+    }
+}
@@ -0,0 +1,343 @@
+//! Items: the things players carry in their [`Inventory`].
+//!
+//! Like block kinds, item kinds are listed in a global registry and
+//! referred to by numeric [`ItemId`]s, which are only meaningful between
+//! peers with the same registry. For now, every item places a block.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    block::{Block, Facing},
+    blocks, BlockId,
+};
+
+/// The number of items in a full stack.
+pub const MAX_STACK: u32 = 64;
+/// The number of slots in an inventory.
+pub const INVENTORY_SIZE: usize = 36;
+/// The number of slots in the hotbar, which
+/// are the first slots of the inventory.
+pub const HOTBAR_SIZE: usize = 9;
+
+/// Describes an item kind.
+#[derive(Copy, Clone, Debug)]
+pub struct ItemDescriptor {
+    slug: &'static str,
+    display_name: &'static str,
+    max_stack: u32,
+    block: Option<BlockId>,
+}
+
+impl ItemDescriptor {
+    pub fn slug(&self) -> &'static str {
+        self.slug
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        self.display_name
+    }
+
+    /// Returns the number of items that fit in one slot.
+    pub fn max_stack(&self) -> u32 {
+        self.max_stack
+    }
+
+    /// Returns the block placed by this item, if any.
+    pub fn block(&self) -> Option<BlockId> {
+        self.block
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    items: Vec<ItemDescriptor>,
+}
+
+impl Registry {
+    /// Registers an item which places `block`, named after it.
+    fn register_block(&mut self, block: impl Block) -> &mut Self {
+        let block = BlockId::new(block);
+        let descriptor = block.descriptor();
+        self.items.push(ItemDescriptor {
+            slug: descriptor.slug(),
+            display_name: descriptor.display_name(),
+            max_stack: MAX_STACK,
+            block: Some(block),
+        });
+        self
+    }
+}
+
+/// The global item registry.
+static REGISTRY: Lazy<Registry> = Lazy::new(|| {
+    let mut registry = Registry::default();
+
+    use blocks::*;
+    registry
+        .register_block(Dirt)
+        .register_block(Stone)
+        .register_block(Grass)
+        .register_block(Melium)
+        .register_block(Sand)
+        .register_block(Gravel)
+        .register_block(Log)
+        .register_block(Leaves)
+        .register_block(Sapling)
+        .register_block(Lamp { lit: false })
+        .register_block(Door {
+            open: false,
+            upper: false,
+            powered: false,
+        })
+        .register_block(Trapdoor {
+            open: false,
+            powered: false,
+        })
+        .register_block(Ladder {
+            facing: Facing::PosZ,
+        })
+        .register_block(Wire { power: 0 })
+        .register_block(SignalSource)
+        .register_block(CoalOre)
+        .register_block(IronOre)
+        .register_block(GoldOre);
+
+    registry
+});
+
+/// Iterates over every registered item.
+pub fn all_items() -> impl Iterator<Item = ItemId> {
+    (0..REGISTRY.items.len() as u32).map(ItemId)
+}
+
+/// ID of an item kind.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ItemId(u32);
+
+impl ItemId {
+    /// Creates an item ID from its raw value, which may be invalid.
+    pub fn from_raw(id: u32) -> Self {
+        Self(id)
+    }
+
+    /// Gets the item with the given slug.
+    pub fn from_slug(slug: &str) -> Option<Self> {
+        all_items().find(|item| item.descriptor().slug() == slug)
+    }
+
+    /// Gets the item which places blocks of the same kind as `block`.
+    pub fn for_block(block: BlockId) -> Option<Self> {
+        all_items().find(|item| {
+            item.descriptor()
+                .block()
+                .map_or(false, |placed| placed.kind() == block.kind())
+        })
+    }
+
+    /// Returns whether this ID refers to a registered item. Other
+    /// methods panic on invalid IDs, so check IDs received from peers.
+    pub fn is_valid(self) -> bool {
+        (self.0 as usize) < REGISTRY.items.len()
+    }
+
+    /// Returns the descriptor of this item.
+    pub fn descriptor(self) -> ItemDescriptor {
+        *REGISTRY
+            .items
+            .get(self.0 as usize)
+            .expect("item has not been registered with the item registry")
+    }
+
+    pub fn raw(self) -> u32 {
+        self.0
+    }
+}
+
+/// A number of items of one kind, held in a single slot.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: ItemId,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(item: ItemId, count: u32) -> Self {
+        Self { item, count }
+    }
+
+    /// Returns whether the item is registered and the count
+    /// is between 1 and the item's maximum stack size.
+    pub fn is_valid(self) -> bool {
+        self.item.is_valid() && self.count > 0 && self.count <= self.item.descriptor().max_stack()
+    }
+}
+
+/// Component holding the items a player carries.
+///
+/// The first [`HOTBAR_SIZE`] slots form the hotbar. One of them is
+/// selected, and right clicking places the block of the item in it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+    selected: usize,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Inventory {
+    /// Creates an empty inventory with the first slot selected.
+    pub fn new() -> Self {
+        Self {
+            slots: vec![None; INVENTORY_SIZE],
+            selected: 0,
+        }
+    }
+
+    /// Creates an inventory from its slots and the index of the
+    /// selected hotbar slot. Returns `None` if there aren't
+    /// [`INVENTORY_SIZE`] slots, a stack is invalid, or the
+    /// selected slot isn't in the hotbar.
+    pub fn from_slots(slots: Vec<Option<ItemStack>>, selected: usize) -> Option<Self> {
+        let valid = slots.len() == INVENTORY_SIZE
+            && selected < HOTBAR_SIZE
+            && slots.iter().flatten().all(|stack| stack.is_valid());
+        if valid {
+            Some(Self { slots, selected })
+        } else {
+            None
+        }
+    }
+
+    pub fn slots(&self) -> &[Option<ItemStack>] {
+        &self.slots
+    }
+
+    pub fn hotbar(&self) -> &[Option<ItemStack>] {
+        &self.slots[..HOTBAR_SIZE]
+    }
+
+    /// Gets the stack in a slot.
+    ///
+    /// # Panics
+    /// Panics if `slot >= INVENTORY_SIZE`.
+    pub fn slot(&self, slot: usize) -> Option<ItemStack> {
+        self.slots[slot]
+    }
+
+    /// Replaces the stack in a slot.
+    ///
+    /// # Panics
+    /// Panics if `slot >= INVENTORY_SIZE`.
+    pub fn set_slot(&mut self, slot: usize, stack: Option<ItemStack>) {
+        self.slots[slot] = stack;
+    }
+
+    /// Returns the index of the selected hotbar slot.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Selects a hotbar slot. Returns `false` and
+    /// does nothing if the slot isn't in the hotbar.
+    pub fn select(&mut self, slot: usize) -> bool {
+        if slot < HOTBAR_SIZE {
+            self.selected = slot;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Gets the stack in the selected hotbar slot.
+    pub fn selected_stack(&self) -> Option<ItemStack> {
+        self.slots[self.selected]
+    }
+
+    /// Adds items, first to the stacks of the same item and then to
+    /// empty slots. Returns the number of items that didn't fit.
+    pub fn add(&mut self, stack: ItemStack) -> u32 {
+        let max_stack = stack.item.descriptor().max_stack();
+        let mut remaining = stack.count;
+        for slot in self.slots.iter_mut().flatten() {
+            if remaining == 0 {
+                break;
+            }
+            if slot.item == stack.item {
+                let added = remaining.min(max_stack - slot.count.min(max_stack));
+                slot.count += added;
+                remaining -= added;
+            }
+        }
+        for slot in &mut self.slots {
+            if remaining == 0 {
+                break;
+            }
+            if slot.is_none() {
+                let added = remaining.min(max_stack);
+                *slot = Some(ItemStack::new(stack.item, added));
+                remaining -= added;
+            }
+        }
+        remaining
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_items() {
+        let stone = ItemId::from_slug("stone").unwrap();
+        assert_eq!(
+            stone.descriptor().block(),
+            Some(BlockId::new(blocks::Stone))
+        );
+        let open_door = BlockId::new(blocks::Door {
+            open: true,
+            upper: false,
+            powered: false,
+        });
+        assert_eq!(ItemId::for_block(open_door), ItemId::from_slug("door"));
+        assert_eq!(ItemId::for_block(BlockId::new(blocks::Air)), None);
+        assert!(all_items().all(ItemId::is_valid));
+        assert!(!ItemId::from_raw(u32::MAX).is_valid());
+    }
+
+    #[test]
+    fn adding_fills_stacks_then_empty_slots() {
+        let stone = ItemId::from_slug("stone").unwrap();
+        let dirt = ItemId::from_slug("dirt").unwrap();
+        let mut inventory = Inventory::new();
+        inventory.set_slot(0, Some(ItemStack::new(dirt, 1)));
+        inventory.set_slot(1, Some(ItemStack::new(stone, MAX_STACK - 2)));
+
+        assert_eq!(inventory.add(ItemStack::new(stone, 10)), 0);
+        assert_eq!(inventory.slot(1), Some(ItemStack::new(stone, MAX_STACK)));
+        assert_eq!(inventory.slot(2), Some(ItemStack::new(stone, 8)));
+
+        let mut full = Inventory::new();
+        let overflow = full.add(ItemStack::new(dirt, MAX_STACK * INVENTORY_SIZE as u32 + 5));
+        assert_eq!(overflow, 5);
+    }
+
+    #[test]
+    fn validates_slots() {
+        let stone = ItemId::from_slug("stone").unwrap();
+        let mut slots = vec![None; INVENTORY_SIZE];
+        assert!(Inventory::from_slots(slots.clone(), HOTBAR_SIZE).is_none());
+        slots[3] = Some(ItemStack::new(stone, 0));
+        assert!(Inventory::from_slots(slots.clone(), 0).is_none());
+        slots[3] = Some(ItemStack::new(stone, 5));
+        let mut inventory = Inventory::from_slots(slots, 3).unwrap();
+        assert_eq!(inventory.selected_stack(), Some(ItemStack::new(stone, 5)));
+        assert!(!inventory.select(HOTBAR_SIZE));
+        assert!(inventory.select(0));
+        assert_eq!(inventory.selected_stack(), None);
+    }
+}
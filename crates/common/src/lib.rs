@@ -4,14 +4,20 @@
 pub mod biome;
 pub mod block;
 pub mod chunk;
+pub mod edit;
 pub mod entity;
 pub mod event;
+pub mod game_rules;
 pub mod gpu;
+pub mod heightmap;
+pub mod item;
 pub mod system;
+pub mod time;
+pub mod weather;
 pub mod world;
 
 pub use block::{blocks, BlockId};
 pub use chunk::{Chunk, ChunkPos};
 pub use entity::{Orient, Pos};
 pub use system::{System, SystemExecutor};
-pub use world::{BlockPos, World, Zone};
+pub use world::{BlockPos, World, WorldPos, Zone, ZoneId};
@@ -1,4 +1,6 @@
-use std::time::Instant;
+use std::time::Duration;
+
+use instant::Instant;
 
 /// A simple system executor.
 ///
@@ -19,6 +21,8 @@ use std::time::Instant;
 /// cost.
 pub struct SystemExecutor<S> {
     systems: Vec<Box<dyn System<S>>>,
+    /// How long each system took the last time it ran.
+    timings: Vec<Duration>,
 }
 
 impl<S> SystemExecutor<S>
@@ -29,6 +33,7 @@ where
     pub fn new() -> Self {
         Self {
             systems: Vec::new(),
+            timings: Vec::new(),
         }
     }
 
@@ -36,6 +41,7 @@ where
     /// for method chaining.
     pub fn add(&mut self, system: impl System<S>) -> &mut Self {
         self.systems.push(Box::new(system));
+        self.timings.push(Duration::default());
         self
     }
 
@@ -55,8 +61,18 @@ where
             if elapsed.as_secs_f64() >= 0.01 {
                 log::debug!("{} took {:?}", system.name(), elapsed);
             }
+            self.timings[i] = elapsed;
         }
     }
+
+    /// Returns the name of each system and how long it
+    /// took the last time the systems ran, in order.
+    pub fn timings(&self) -> impl Iterator<Item = (&'static str, Duration)> + '_ {
+        self.systems
+            .iter()
+            .zip(&self.timings)
+            .map(|(system, &elapsed)| (system.name(), elapsed))
+    }
 }
 
 /// A system that can be added to a [`SystemExecutor`].
@@ -0,0 +1,70 @@
+//! The time of day, shared between client and server.
+//!
+//! The world's time is the number of seconds of game time since it was
+//! created. Only its position within a [day](DAY_LENGTH) matters for
+//! gameplay and rendering: a day starts at sunrise, the sun is highest
+//! at a quarter of the way through, and sets halfway through.
+
+use glam::{vec3a, Vec3A};
+
+/// The length of a day in seconds of game time.
+pub const DAY_LENGTH: f64 = 20. * 60.;
+
+/// The time of day at which the sun rises.
+pub const SUNRISE: f32 = 0.;
+/// The time of day at which the sun is highest.
+pub const NOON: f32 = 0.25;
+/// The time of day at which the sun sets.
+pub const SUNSET: f32 = 0.5;
+/// The time of day at which the sun is lowest.
+pub const MIDNIGHT: f32 = 0.75;
+
+/// How far the sun's path is tilted towards +Z so that
+/// it never lights faces from straight above.
+const SUN_TILT: f32 = 0.3;
+
+/// Returns the time of day, between 0 and 1, of the world
+/// time `time`.
+pub fn time_of_day(time: f64) -> f32 {
+    (time / DAY_LENGTH).rem_euclid(1.) as f32
+}
+
+/// Returns the direction towards the sun at `time_of_day`.
+/// The sun rises in the +X direction and sets in the -X direction.
+pub fn sun_direction(time_of_day: f32) -> Vec3A {
+    let angle = time_of_day * std::f32::consts::TAU;
+    vec3a(angle.cos(), angle.sin(), SUN_TILT).normalize()
+}
+
+/// Returns how bright it is at `time_of_day`, from 0
+/// at night to 1 during the day. Brightness changes
+/// smoothly through dawn and dusk.
+pub fn daylight(time_of_day: f32) -> f32 {
+    // The sun's height, from -1 to 1.
+    let height = (time_of_day * std::f32::consts::TAU).sin();
+    // Twilight lasts while the sun is just below or above the horizon.
+    let t = ((height + 0.1) / 0.3).max(0.).min(1.);
+    t * t * (3. - 2. * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_follows_the_day() {
+        assert_eq!(time_of_day(0.), SUNRISE);
+        assert!((time_of_day(DAY_LENGTH * 2.25) - NOON).abs() < 1e-6);
+        assert!((time_of_day(-DAY_LENGTH / 4.) - MIDNIGHT).abs() < 1e-6);
+
+        assert!(sun_direction(NOON).y > 0.9);
+        assert!(sun_direction(MIDNIGHT).y < -0.9);
+        assert!(sun_direction(SUNRISE).x > 0.9);
+        assert!(sun_direction(SUNSET).x < -0.9);
+
+        assert_eq!(daylight(NOON), 1.);
+        assert_eq!(daylight(MIDNIGHT), 0.);
+        let dawn = daylight(SUNRISE);
+        assert!(dawn > 0. && dawn < 1.);
+    }
+}
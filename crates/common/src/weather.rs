@@ -0,0 +1,112 @@
+//! Weather shared between client and server.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{biome::Biome, blocks::Air, BlockId, BlockPos};
+
+/// The weather across the whole world.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Weather {
+    Clear,
+    Rain,
+    /// Heavy rain.
+    Thunder,
+}
+
+/// What falls from the sky onto a column.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Precipitation {
+    None,
+    Rain,
+    Snow,
+}
+
+/// The height above which precipitation falls as snow.
+pub const SNOW_LINE: i32 = 96;
+
+/// Returns the precipitation falling onto a column given the weather,
+/// the column's biome, if known, and the height of its [surface](surface).
+///
+/// Deserts stay dry and it always snows in tundras. Elsewhere,
+/// it snows above the [`SNOW_LINE`] and rains below it.
+pub fn precipitation(weather: Weather, biome: Option<&Biome>, surface_y: i32) -> Precipitation {
+    if weather == Weather::Clear || biome == Some(Biome::Desert) {
+        Precipitation::None
+    } else if biome == Some(Biome::Tundra) || surface_y >= SNOW_LINE {
+        Precipitation::Snow
+    } else {
+        Precipitation::Rain
+    }
+}
+
+/// Finds the highest non-air block in the column at `(x, z)`
+/// between `bottom_y` and `top_y`, inclusive. This is the block
+/// precipitation lands on.
+///
+/// `block_at` should return the block at a position, or `None`
+/// if it is not known, in which case so is the surface.
+pub fn surface(
+    x: i32,
+    z: i32,
+    top_y: i32,
+    bottom_y: i32,
+    block_at: impl Fn(BlockPos) -> Option<BlockId>,
+) -> Option<(BlockPos, BlockId)> {
+    for y in (bottom_y..=top_y).rev() {
+        let pos = BlockPos { x, y, z };
+        let block = block_at(pos)?;
+        if !block.is::<Air>() {
+            return Some((pos, block));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::Grass;
+
+    #[test]
+    fn precipitation_depends_on_biome() {
+        let plains = Some(Biome::Plains);
+        assert_eq!(
+            precipitation(Weather::Clear, plains, 64),
+            Precipitation::None
+        );
+        assert_eq!(
+            precipitation(Weather::Rain, plains, 64),
+            Precipitation::Rain
+        );
+        assert_eq!(
+            precipitation(Weather::Thunder, plains, SNOW_LINE),
+            Precipitation::Snow
+        );
+        assert_eq!(
+            precipitation(Weather::Rain, Some(Biome::Desert), SNOW_LINE),
+            Precipitation::None
+        );
+        assert_eq!(
+            precipitation(Weather::Rain, Some(Biome::Tundra), 64),
+            Precipitation::Snow
+        );
+        assert_eq!(precipitation(Weather::Rain, None, 64), Precipitation::Rain);
+    }
+
+    #[test]
+    fn surface_is_highest_block() {
+        let block_at = |pos: BlockPos| {
+            if pos.y <= 10 {
+                Some(BlockId::new(Grass))
+            } else {
+                Some(BlockId::new(Air))
+            }
+        };
+        assert_eq!(
+            surface(0, 0, 20, 0, block_at),
+            Some((BlockPos { x: 0, y: 10, z: 0 }, BlockId::new(Grass)))
+        );
+        assert_eq!(surface(0, 0, 20, 11, block_at), None);
+        assert_eq!(surface(0, 0, 20, 0, |_| None), None);
+    }
+}
@@ -1,13 +1,24 @@
 //! Data structure for accessing blocks in the world.
 
-use crate::{chunk::CHUNK_DIM, BlockId, Chunk, ChunkPos};
+use std::collections::BTreeSet;
+
+use crate::{
+    biome::{Biome, ColumnBiomes},
+    blocks,
+    chunk::CHUNK_DIM,
+    heightmap::{self, Heightmap},
+    BlockId, Chunk, ChunkPos,
+};
 use ahash::AHashMap;
-use glam::Vec3A;
+use glam::{vec3a, Quat, Vec3, Vec3A};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 /// Position of a block within a zone. Measured in blocks.
-#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
 pub struct BlockPos {
     pub x: i32,
     pub y: i32,
@@ -33,6 +44,10 @@ impl BlockPos {
     }
 
     /// Determines the block containing the given position.
+    ///
+    /// Block `n` spans `n` inclusive to `n + 1` exclusive on each
+    /// axis, so coordinates are rounded down: `-0.5` is in block
+    /// `-1`, not in block `0` as casting with `as` would have it.
     pub fn from_pos(pos: Vec3A) -> Self {
         Self {
             x: pos.x.floor() as i32,
@@ -40,6 +55,44 @@ impl BlockPos {
             z: pos.z.floor() as i32,
         }
     }
+
+    /// Returns the position of the corner of this
+    /// block with the lowest coordinates.
+    pub fn min_corner(self) -> Vec3A {
+        vec3a(self.x as f32, self.y as f32, self.z as f32)
+    }
+
+    /// Returns the position of the center of this block.
+    pub fn center(self) -> Vec3A {
+        self.min_corner() + Vec3A::splat(0.5)
+    }
+
+    /// Returns the position of the center of this block's bottom
+    /// face, where entities standing on the block below are.
+    pub fn bottom_center(self) -> Vec3A {
+        self.min_corner() + vec3a(0.5, 0., 0.5)
+    }
+
+    /// Returns this position offset by the given number of blocks.
+    pub fn offset(self, x: i32, y: i32, z: i32) -> Self {
+        Self {
+            x: self.x + x,
+            y: self.y + y,
+            z: self.z + z,
+        }
+    }
+
+    /// Returns the six blocks sharing a face with this block.
+    pub fn adjacent(self) -> [BlockPos; 6] {
+        [
+            self.offset(0, 1, 0),
+            self.offset(0, -1, 0),
+            self.offset(1, 0, 0),
+            self.offset(-1, 0, 0),
+            self.offset(0, 0, 1),
+            self.offset(0, 0, -1),
+        ]
+    }
 }
 
 /// A zone in the world.
@@ -55,6 +108,37 @@ pub struct Zone {
     chunks: Vec<Chunk>,
     min: ChunkPos,
     max: ChunkPos,
+    /// The heightmap of each chunk column, ordered by X
+    /// then Z. Updated when blocks and chunks change.
+    heightmaps: Vec<Heightmap>,
+    /// The biomes of each chunk column, ordered by X then Z,
+    /// or `None` for columns the world generator didn't record.
+    biomes: Vec<Option<ColumnBiomes>>,
+}
+
+/// The top of a column of blocks in a [`Zone`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ColumnSummary {
+    /// The highest block that isn't air.
+    pub top: BlockId,
+    /// The Y coordinate of `top`.
+    pub height: i32,
+    /// The column's biome. If no biome is stored for the column, this
+    /// is guessed from the top block: water is ocean, all else plains.
+    pub biome: &'static Biome,
+}
+
+impl ColumnSummary {
+    fn new(top: BlockId, height: i32, biome: Option<&'static Biome>) -> Self {
+        let biome = biome.unwrap_or_else(|| {
+            if top.is::<blocks::Water>() {
+                Biome::Ocean
+            } else {
+                Biome::Plains
+            }
+        });
+        Self { top, height, biome }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -73,10 +157,35 @@ impl Zone {
         Some(&self.chunks[index])
     }
 
-    /// Mutably gets the chunk at `pos`.
-    pub fn chunk_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
-        let index = self.chunk_index(pos)?;
-        Some(&mut self.chunks[index])
+    /// Replaces the chunk at `pos`. Returns an error
+    /// if `pos` is outside this zone.
+    pub fn set_chunk(&mut self, pos: ChunkPos, chunk: Chunk) -> Result<(), ChunkOutOfBounds> {
+        let index = self
+            .chunk_index(pos)
+            .ok_or_else(|| ChunkOutOfBounds(pos, self.min, self.max))?;
+        self.chunks[index] = chunk;
+        self.refresh_heightmap(pos);
+        Ok(())
+    }
+
+    /// Updates the heightmap of a chunk's column after the chunk changed.
+    fn refresh_heightmap(&mut self, pos: ChunkPos) {
+        let index = match self.column_index(pos.x, pos.z) {
+            Some(index) => index,
+            None => return,
+        };
+        // Columns with a top above the chunk keep it.
+        let (min, top) = (pos.block_min(), pos.block_max().y);
+        let heights: Vec<_> = self.heightmaps[index]
+            .columns_at_or_below(top)
+            .map(|(x, z)| {
+                let height = self.scan_column(min.x + x as i32, min.z + z as i32, top);
+                (x, z, height)
+            })
+            .collect();
+        for (x, z, height) in heights {
+            self.heightmaps[index].set(x, z, height);
+        }
     }
 
     /// Gets the block at `pos`, or `None` if `pos` is outside
@@ -90,11 +199,102 @@ impl Zone {
     /// Sets the block at `pos`. Returns an error if `pos`
     /// is outside this zone.
     pub fn set_block(&mut self, pos: BlockPos, block: BlockId) -> Result<(), BlockOutOfBounds> {
-        let chunk = self
-            .chunk_mut(pos.chunk())
+        let index = self
+            .chunk_index(pos.chunk())
             .ok_or_else(|| BlockOutOfBounds(pos))?;
         let (x, y, z) = pos.chunk_local();
-        chunk.set(x, y, z, block);
+        self.chunks[index].set(x, y, z, block);
+
+        let chunk = pos.chunk();
+        let column = self
+            .column_index(chunk.x, chunk.z)
+            .expect("chunk is in bounds");
+        if !self.heightmaps[column].update(x, z, pos.y, block) {
+            // The top was removed; the new one is somewhere below.
+            let height = self.scan_column(pos.x, pos.z, pos.y - 1);
+            self.heightmaps[column].set(x, z, height);
+        }
+        Ok(())
+    }
+
+    /// Gets the position of the highest non-air block in the column
+    /// at `(x, z)`, or `None` if the column is outside this zone or
+    /// contains only air. Read from the column's heightmap.
+    pub fn highest_block_at(&self, x: i32, z: i32) -> Option<BlockPos> {
+        let chunk = BlockPos { x, y: 0, z }.chunk();
+        let (local_x, _, local_z) = BlockPos { x, y: 0, z }.chunk_local();
+        let y = self.heightmap(chunk.x, chunk.z)?.get(local_x, local_z)?;
+        Some(BlockPos { x, y, z })
+    }
+
+    /// Gets the heightmap of the chunk column at
+    /// `(x, z)`, measured in chunks.
+    pub fn heightmap(&self, x: i32, z: i32) -> Option<&Heightmap> {
+        let index = self.column_index(x, z)?;
+        Some(&self.heightmaps[index])
+    }
+
+    /// Summarizes the column of blocks at `(x, z)`: its highest
+    /// non-air block, that block's height, and its biome.
+    /// Returns `None` if the column is outside this zone or
+    /// contains only air.
+    ///
+    /// The height comes from the column's heightmap, so maps
+    /// and the like can call this for every column they draw.
+    pub fn column_summary(&self, x: i32, z: i32) -> Option<ColumnSummary> {
+        let pos = self.highest_block_at(x, z)?;
+        let top = self.block(pos)?;
+        Some(ColumnSummary::new(top, pos.y, self.biome_at(pos)))
+    }
+
+    /// Finds the highest non-air block at or below `from_y` in the
+    /// column at `(x, z)` by looking at its blocks.
+    fn scan_column(&self, x: i32, z: i32, from_y: i32) -> Option<i32> {
+        let column = BlockPos { x, y: 0, z }.chunk();
+        let chunks = (self.min.y..=self.max.y).rev().filter_map(|y| {
+            let chunk = self.chunk(ChunkPos { y, ..column })?;
+            Some((y, chunk))
+        });
+        heightmap::scan_column(x, z, from_y, chunks)
+    }
+
+    /// Gets the biome at `pos`, or `None` if `pos` is outside this
+    /// zone or no biomes are stored for its chunk column. Biomes
+    /// don't vary with height, so the Y coordinate is ignored.
+    pub fn biome_at(&self, pos: BlockPos) -> Option<&'static Biome> {
+        let chunk = pos.chunk();
+        let biomes = self.column_biomes(chunk.x, chunk.z)?;
+        let (x, _, z) = pos.chunk_local();
+        Some(biomes.get(x, z))
+    }
+
+    /// Gets the biomes of the chunk column at `(x, z)`, measured in
+    /// chunks, if the world generator recorded them.
+    pub fn column_biomes(&self, x: i32, z: i32) -> Option<ColumnBiomes> {
+        let index = self.column_index(x, z)?;
+        self.biomes[index]
+    }
+
+    /// Sets the biomes of the chunk column at `(x, z)`, measured in
+    /// chunks. Returns an error if the column is outside this zone.
+    pub fn set_column_biomes(
+        &mut self,
+        x: i32,
+        z: i32,
+        biomes: ColumnBiomes,
+    ) -> Result<(), ChunkOutOfBounds> {
+        let index = self.column_index(x, z).ok_or_else(|| {
+            ChunkOutOfBounds(
+                ChunkPos {
+                    x,
+                    y: self.min.y,
+                    z,
+                },
+                self.min,
+                self.max,
+            )
+        })?;
+        self.biomes[index] = Some(biomes);
         Ok(())
     }
 
@@ -134,15 +334,6 @@ impl Zone {
         })
     }
 
-    pub fn chunks_mut<'a>(&'a mut self) -> impl Iterator<Item = (ChunkPos, &'a mut Chunk)> + 'a {
-        let min = self.min;
-        let dim = self.dim();
-        self.chunks.iter_mut().enumerate().map(move |(i, chunk)| {
-            let pos = reverse_chunk_index(min, dim, i);
-            (pos, chunk)
-        })
-    }
-
     pub fn par_chunks<'a>(
         &'a self,
     ) -> impl IndexedParallelIterator<Item = (ChunkPos, &'a Chunk)> + 'a {
@@ -152,18 +343,12 @@ impl Zone {
         })
     }
 
-    pub fn par_chunks_mut<'a>(
-        &'a mut self,
-    ) -> impl IndexedParallelIterator<Item = (ChunkPos, &'a mut Chunk)> + 'a {
-        let min = self.min;
-        let dim = self.dim();
-        self.chunks
-            .par_iter_mut()
-            .enumerate()
-            .map(move |(i, chunk)| {
-                let pos = reverse_chunk_index(min, dim, i);
-                (pos, chunk)
-            })
+    fn column_index(&self, x: i32, z: i32) -> Option<usize> {
+        if x < self.min.x || x > self.max.x || z < self.min.z || z > self.max.z {
+            None
+        } else {
+            Some((x - self.min.x) as usize * self.z_dim() + (z - self.min.z) as usize)
+        }
     }
 
     fn chunk_index(&self, pos: ChunkPos) -> Option<usize> {
@@ -206,6 +391,7 @@ pub struct ZoneBuilder {
     min: ChunkPos,
     max: ChunkPos,
     chunks: AHashMap<ChunkPos, Chunk>,
+    biomes: AHashMap<(i32, i32), ColumnBiomes>,
 }
 
 impl ZoneBuilder {
@@ -218,6 +404,7 @@ impl ZoneBuilder {
             min,
             max,
             chunks: AHashMap::new(),
+            biomes: AHashMap::new(),
         }
     }
 
@@ -261,6 +448,31 @@ impl ZoneBuilder {
         Ok(())
     }
 
+    /// Sets the biomes of the chunk column at `(x, z)`, measured
+    /// in chunks. Returns an error if the column is not within the
+    /// bounds of the zone being built.
+    pub fn set_column_biomes(
+        &mut self,
+        x: i32,
+        z: i32,
+        biomes: ColumnBiomes,
+    ) -> Result<(), ChunkOutOfBounds> {
+        if x < self.min.x || x > self.max.x || z < self.min.z || z > self.max.z {
+            return Err(ChunkOutOfBounds(
+                ChunkPos {
+                    x,
+                    y: self.min.y,
+                    z,
+                },
+                self.min,
+                self.max,
+            ));
+        }
+
+        self.biomes.insert((x, z), biomes);
+        Ok(())
+    }
+
     /// Determines whether the zone is complete, i.e. whether
     /// all chunks within the bounds have been added via calls
     /// to `add_chunk()`. If this returns `true`, then calling
@@ -313,11 +525,30 @@ impl ZoneBuilder {
             }
         }
 
-        Ok(Zone {
+        let mut biomes = Vec::new();
+        for x in self.min.x..=self.max.x {
+            for z in self.min.z..=self.max.z {
+                biomes.push(self.biomes.remove(&(x, z)));
+            }
+        }
+
+        let mut zone = Zone {
             min: self.min,
             max: self.max,
             chunks,
-        })
+            heightmaps: vec![Heightmap::new(); biomes.len()],
+            biomes,
+        };
+        for x in zone.min.x..=zone.max.x {
+            for z in zone.min.z..=zone.max.z {
+                zone.refresh_heightmap(ChunkPos {
+                    x,
+                    y: zone.max.y,
+                    z,
+                });
+            }
+        }
+        Ok(zone)
     }
 }
 
@@ -330,6 +561,18 @@ impl ZoneBuilder {
 #[derive(Default)]
 pub struct SparseZone {
     chunks: AHashMap<ChunkPos, Chunk>,
+    /// The chunk columns with loaded chunks, by column X and Z.
+    columns: AHashMap<(i32, i32), SparseColumn>,
+}
+
+/// A chunk column of a [`SparseZone`].
+#[derive(Default)]
+struct SparseColumn {
+    /// The Y coordinates of the loaded chunks in the column.
+    chunks: BTreeSet<i32>,
+    biomes: Option<ColumnBiomes>,
+    /// The heights of the highest known blocks of the column.
+    heightmap: Heightmap,
 }
 
 impl SparseZone {
@@ -348,20 +591,114 @@ impl SparseZone {
         self.chunks.get(&pos)
     }
 
-    /// Mutably gets the chunk at `pos`.
-    pub fn chunk_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
-        self.chunks.get_mut(&pos)
+    /// Returns an iterator over the chunks in this zone, yielded
+    /// in arbitrary order.
+    pub fn chunks(&self) -> impl Iterator<Item = (ChunkPos, &Chunk)> + '_ {
+        self.chunks.iter().map(|(&pos, chunk)| (pos, chunk))
     }
 
     /// Inserts a new chunk. If a chunk at `pos` already exists,
     /// it is replaced.
     pub fn insert(&mut self, pos: ChunkPos, chunk: Chunk) {
         self.chunks.insert(pos, chunk);
+        self.columns
+            .entry((pos.x, pos.z))
+            .or_default()
+            .chunks
+            .insert(pos.y);
+        self.refresh_heightmap(pos);
     }
 
-    /// Removes the chunk at `pos`, returning it.
+    /// Removes the chunk at `pos`, returning it. The biomes
+    /// of its column are removed along with the column's last chunk.
     pub fn remove(&mut self, pos: ChunkPos) -> Option<Chunk> {
-        self.chunks.remove(&pos)
+        let chunk = self.chunks.remove(&pos)?;
+        if let Some(column) = self.columns.get_mut(&(pos.x, pos.z)) {
+            column.chunks.remove(&pos.y);
+            if column.chunks.is_empty() {
+                self.columns.remove(&(pos.x, pos.z));
+            } else {
+                self.refresh_heightmap(pos);
+            }
+        }
+        Some(chunk)
+    }
+
+    /// Updates the heightmap of a chunk's column after
+    /// the chunk was inserted or removed.
+    fn refresh_heightmap(&mut self, pos: ChunkPos) {
+        let column = match self.columns.get(&(pos.x, pos.z)) {
+            Some(column) => column,
+            None => return,
+        };
+        // Columns with a top above the chunk keep it.
+        let (min, top) = (pos.block_min(), pos.block_max().y);
+        let heights: Vec<_> = column
+            .heightmap
+            .columns_at_or_below(top)
+            .map(|(x, z)| {
+                let height = self.scan_column(min.x + x as i32, min.z + z as i32, top);
+                (x, z, height)
+            })
+            .collect();
+        let heightmap = &mut self
+            .columns
+            .get_mut(&(pos.x, pos.z))
+            .expect("column exists")
+            .heightmap;
+        for (x, z, height) in heights {
+            heightmap.set(x, z, height);
+        }
+    }
+
+    /// Finds the highest non-air block at or below `from_y` in the
+    /// column at `(x, z)` by looking at the blocks of its loaded chunks.
+    fn scan_column(&self, x: i32, z: i32, from_y: i32) -> Option<i32> {
+        let column = BlockPos { x, y: 0, z }.chunk();
+        let chunks = self
+            .columns
+            .get(&(column.x, column.z))
+            .into_iter()
+            .flat_map(|column| column.chunks.iter().rev())
+            .filter_map(|&y| {
+                let chunk = self.chunk(ChunkPos { y, ..column })?;
+                Some((y, chunk))
+            });
+        heightmap::scan_column(x, z, from_y, chunks)
+    }
+
+    /// Gets the position of the highest non-air block in the column at
+    /// `(x, z)` among the loaded chunks, or `None` if no chunk of the
+    /// column is loaded or they contain only air. Read from the
+    /// column's heightmap.
+    pub fn highest_block_at(&self, x: i32, z: i32) -> Option<BlockPos> {
+        let chunk = BlockPos { x, y: 0, z }.chunk();
+        let (local_x, _, local_z) = BlockPos { x, y: 0, z }.chunk_local();
+        let y = self.heightmap(chunk.x, chunk.z)?.get(local_x, local_z)?;
+        Some(BlockPos { x, y, z })
+    }
+
+    /// Gets the heightmap of the loaded chunks of the chunk
+    /// column at `(x, z)`, measured in chunks.
+    pub fn heightmap(&self, x: i32, z: i32) -> Option<&Heightmap> {
+        Some(&self.columns.get(&(x, z))?.heightmap)
+    }
+
+    /// Sets the biomes of the chunk column at `(x, z)`, measured in
+    /// chunks. Does nothing if no chunk in the column is loaded.
+    pub fn set_column_biomes(&mut self, x: i32, z: i32, biomes: ColumnBiomes) {
+        if let Some(column) = self.columns.get_mut(&(x, z)) {
+            column.biomes = Some(biomes);
+        }
+    }
+
+    /// Gets the biome at `pos`, or `None` if the
+    /// biomes of the block's chunk column are not known.
+    pub fn biome_at(&self, pos: BlockPos) -> Option<&'static Biome> {
+        let chunk = pos.chunk();
+        let biomes = self.columns.get(&(chunk.x, chunk.z))?.biomes?;
+        let (x, _, z) = pos.chunk_local();
+        Some(biomes.get(x, z))
     }
 
     /// Gets the block at `pos`, or `None` if the block's
@@ -375,18 +712,104 @@ impl SparseZone {
     /// Sets the block at `pos`. Returns an error if the
     /// block's chunk is not loaded.
     pub fn set_block(&mut self, pos: BlockPos, block: BlockId) -> Result<(), BlockOutOfBounds> {
+        let chunk_pos = pos.chunk();
         let chunk = self
-            .chunk_mut(pos.chunk())
+            .chunks
+            .get_mut(&chunk_pos)
             .ok_or_else(|| BlockOutOfBounds(pos))?;
         let (x, y, z) = pos.chunk_local();
         chunk.set(x, y, z, block);
+
+        let column = self
+            .columns
+            .get_mut(&(chunk_pos.x, chunk_pos.z))
+            .expect("loaded chunks have a column");
+        if !column.heightmap.update(x, z, pos.y, block) {
+            // The top was removed; the new one is somewhere below.
+            let height = self.scan_column(pos.x, pos.z, pos.y - 1);
+            self.columns
+                .get_mut(&(chunk_pos.x, chunk_pos.z))
+                .expect("loaded chunks have a column")
+                .heightmap
+                .set(x, z, height);
+        }
         Ok(())
     }
 }
 
 /// Unique, persistent ID of a `Zone`.
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-pub struct ZoneId(Uuid);
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ZoneId(u128);
+
+impl ZoneId {
+    /// The ID of the main zone of every world, so
+    /// that the client and server agree on it.
+    pub const MAIN: ZoneId = ZoneId(0);
+}
+
+/// Places a zone in the space of the main zone.
+///
+/// A position local to the zone is rotated by `rotation`, then
+/// moved by `translation`, to give the position in main-zone space.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ZoneTransform {
+    pub translation: Vec3A,
+    pub rotation: Quat,
+}
+
+impl Default for ZoneTransform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3A::zero(),
+            rotation: Quat::identity(),
+        }
+    }
+}
+
+impl ZoneTransform {
+    /// Converts a position local to the zone to main-zone space.
+    pub fn local_to_main(self, local: Vec3A) -> Vec3A {
+        Vec3A::from(self.rotation * Vec3::from(local)) + self.translation
+    }
+
+    /// Converts a position in main-zone space to the zone's local space.
+    pub fn main_to_local(self, main: Vec3A) -> Vec3A {
+        Vec3A::from(self.rotation.conjugate() * Vec3::from(main - self.translation))
+    }
+}
+
+/// A position in the local space of a zone. Measured in blocks.
+///
+/// Positions in different zones can't be compared directly;
+/// convert them to main-zone space with [`World::to_main`] first.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorldPos {
+    pub zone: ZoneId,
+    pub local: Vec3A,
+}
+
+impl WorldPos {
+    /// Creates a position in the main zone.
+    pub fn main(local: Vec3A) -> Self {
+        Self {
+            zone: ZoneId::MAIN,
+            local,
+        }
+    }
+
+    /// Returns the block containing this position, in the zone's local space.
+    pub fn block(self) -> BlockPos {
+        BlockPos::from_pos(self.local)
+    }
+
+    /// Returns this position moved by `offset` in the zone's local space.
+    pub fn offset(self, offset: Vec3A) -> Self {
+        Self {
+            zone: self.zone,
+            local: self.local + offset,
+        }
+    }
+}
 
 /// A world, containing one or more `Zone`s of the given
 /// type. `Z` should be either [`Zone`] or [`SparseZone`].
@@ -394,70 +817,191 @@ pub struct ZoneId(Uuid);
 /// # Main zone
 /// The main zone is the zone containing "the ground,"
 /// or most of the world. Other zones correspond
-/// to e.g. ships.
+/// to e.g. ships, and are placed in the main zone's
+/// space by their [`ZoneTransform`]s.
 pub struct World<Z> {
-    zones: AHashMap<ZoneId, Z>,
-    main_zone: ZoneId,
+    zones: AHashMap<ZoneId, (Z, ZoneTransform)>,
 }
 
 impl<Z> World<Z> {
     /// Creates a `World` with the given main zone.
     pub fn new(main_zone: Z) -> Self {
         let mut zones = AHashMap::new();
-        let main_zone_id = Self::create_zone_id();
-        zones.insert(main_zone_id, main_zone);
-
-        Self {
-            zones,
-            main_zone: main_zone_id,
-        }
+        zones.insert(ZoneId::MAIN, (main_zone, ZoneTransform::default()));
+        Self { zones }
     }
 
     /// Gets the `Zone` with the given ID.
     pub fn zone(&self, id: ZoneId) -> Option<&Z> {
-        self.zones.get(&id)
+        self.zones.get(&id).map(|(zone, _)| zone)
     }
 
     /// Mutably gets the `Zone` with the given ID.
     pub fn zone_mut(&mut self, id: ZoneId) -> Option<&mut Z> {
-        self.zones.get_mut(&id)
+        self.zones.get_mut(&id).map(|(zone, _)| zone)
     }
 
     /// Gets the main zone.
     pub fn main_zone(&self) -> &Z {
-        self.zone(self.main_zone).expect("missing main zone")
+        self.zone(ZoneId::MAIN).expect("missing main zone")
     }
 
     /// Gets the main zone.
     pub fn main_zone_mut(&mut self) -> &mut Z {
-        self.zone_mut(self.main_zone).expect("missing main zone")
+        self.zone_mut(ZoneId::MAIN).expect("missing main zone")
     }
 
-    /// Inserts a new zone into this world.
+    /// Inserts a new zone into this world, placed by `transform`.
     /// Returns the ID of this zone.
-    pub fn add_zone(&mut self, zone: Z) -> ZoneId {
+    pub fn add_zone(&mut self, zone: Z, transform: ZoneTransform) -> ZoneId {
         let id = Self::create_zone_id();
-        self.zones.insert(id, zone);
+        self.zones.insert(id, (zone, transform));
         id
     }
 
     /// Removes a zone from this world.
     /// Returns the removed zone.
+    ///
+    /// # Panics
+    /// Panics if `id` is the main zone.
     pub fn remove_zone(&mut self, id: ZoneId) -> Option<Z> {
-        self.zones.remove(&id)
+        assert_ne!(id, ZoneId::MAIN, "the main zone can't be removed");
+        self.zones.remove(&id).map(|(zone, _)| zone)
+    }
+
+    /// Gets the transform of a zone. The main zone's
+    /// transform is always the identity.
+    pub fn transform(&self, id: ZoneId) -> Option<ZoneTransform> {
+        self.zones.get(&id).map(|(_, transform)| *transform)
+    }
+
+    /// Moves a zone. Returns `false` if there is no such zone.
+    ///
+    /// # Panics
+    /// Panics if `id` is the main zone, which defines main-zone space.
+    pub fn set_transform(&mut self, id: ZoneId, transform: ZoneTransform) -> bool {
+        assert_ne!(id, ZoneId::MAIN, "the main zone can't be moved");
+        match self.zones.get_mut(&id) {
+            Some((_, current)) => {
+                *current = transform;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Converts a position to main-zone space. Positions in
+    /// zones that no longer exist are kept as they are.
+    pub fn to_main(&self, pos: WorldPos) -> Vec3A {
+        match self.transform(pos.zone) {
+            Some(transform) => transform.local_to_main(pos.local),
+            None => pos.local,
+        }
+    }
+
+    /// Converts a position in main-zone space to the local space of `zone`.
+    /// Returns `None` if there is no such zone.
+    pub fn to_zone(&self, zone: ZoneId, main: Vec3A) -> Option<WorldPos> {
+        let transform = self.transform(zone)?;
+        Some(WorldPos {
+            zone,
+            local: transform.main_to_local(main),
+        })
+    }
+
+    /// Converts a position to the local space of another zone.
+    /// Returns `None` if there is no such zone.
+    pub fn convert(&self, pos: WorldPos, zone: ZoneId) -> Option<WorldPos> {
+        if pos.zone == zone {
+            return Some(pos);
+        }
+        self.to_zone(zone, self.to_main(pos))
     }
 
     fn create_zone_id() -> ZoneId {
-        ZoneId(Uuid::new_v4())
+        ZoneId(Uuid::new_v4().as_u128())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use glam::vec3a;
+
     use crate::blocks;
 
     use super::*;
 
+    #[test]
+    fn floors_positions_to_blocks() {
+        assert_eq!(
+            BlockPos::from_pos(vec3a(0.5, 15.99, 16.)),
+            BlockPos { x: 0, y: 15, z: 16 }
+        );
+        // Negative coordinates round down too.
+        assert_eq!(
+            BlockPos::from_pos(vec3a(-0.5, -1., -16.01)),
+            BlockPos {
+                x: -1,
+                y: -1,
+                z: -17
+            }
+        );
+
+        let pos = BlockPos { x: -3, y: 0, z: 7 };
+        assert_eq!(pos.min_corner(), vec3a(-3., 0., 7.));
+        assert_eq!(pos.center(), vec3a(-2.5, 0.5, 7.5));
+        assert_eq!(pos.bottom_center(), vec3a(-2.5, 0., 7.5));
+        assert_eq!(BlockPos::from_pos(pos.min_corner()), pos);
+        assert_eq!(BlockPos::from_pos(pos.center()), pos);
+    }
+
+    #[test]
+    fn adjacent_blocks() {
+        let pos = BlockPos { x: 4, y: -2, z: 9 };
+        let adjacent = pos.adjacent();
+        for other in &adjacent {
+            let distance =
+                (other.x - pos.x).abs() + (other.y - pos.y).abs() + (other.z - pos.z).abs();
+            assert_eq!(distance, 1);
+        }
+        for (i, a) in adjacent.iter().enumerate() {
+            assert!(!adjacent[i + 1..].contains(a));
+        }
+    }
+
+    #[test]
+    fn converts_positions_between_zones() {
+        let mut world = World::new(());
+        let main = WorldPos::main(vec3a(1., 2., 3.));
+        assert_eq!(world.to_main(main), main.local);
+
+        let ship = world.add_zone(
+            (),
+            ZoneTransform {
+                translation: vec3a(10., 0., 0.),
+                rotation: Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            },
+        );
+        let on_ship = world.convert(main, ship).unwrap();
+        assert_eq!(on_ship.zone, ship);
+        assert!(world.to_main(on_ship).abs_diff_eq(main.local, 1e-4));
+
+        let deck = WorldPos {
+            zone: ship,
+            local: vec3a(1., 0., 0.),
+        };
+        // Rotating 90° around Y turns +X into -Z.
+        assert!(world.to_main(deck).abs_diff_eq(vec3a(10., 0., -1.), 1e-4));
+        assert!(world
+            .convert(deck, ZoneId::MAIN)
+            .unwrap()
+            .local
+            .abs_diff_eq(vec3a(10., 0., -1.), 1e-4));
+
+        world.remove_zone(ship);
+        assert!(world.convert(main, ship).is_none());
+    }
+
     #[test]
     fn block_to_chunk() {
         assert_eq!(
@@ -487,6 +1031,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn chunk_block_bounds() {
+        let chunk = ChunkPos { x: -1, y: 0, z: 2 };
+        assert_eq!(
+            chunk.block_min(),
+            BlockPos {
+                x: -16,
+                y: 0,
+                z: 32
+            }
+        );
+        assert_eq!(
+            chunk.block_max(),
+            BlockPos {
+                x: -1,
+                y: 15,
+                z: 47
+            }
+        );
+        assert_eq!(chunk.block_min().chunk(), chunk);
+        assert_eq!(chunk.block_max().chunk(), chunk);
+
+        assert_eq!(
+            ChunkPos::from_pos(vec3a(-0.01, 0., 47.99)),
+            ChunkPos { x: -1, y: 0, z: 2 }
+        );
+        assert_eq!(
+            ChunkPos::from_pos(vec3a(-16., -16.01, 48.)),
+            ChunkPos { x: -1, y: -2, z: 3 }
+        );
+    }
+
     #[test]
     fn block_chunk_local_pos() {
         assert_eq!(BlockPos { x: 0, y: 0, z: 0 }.chunk_local(), (0, 0, 0));
@@ -537,4 +1113,185 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn column_summaries_follow_edits() {
+        let mut builder =
+            Zone::builder(ChunkPos { x: 0, y: 0, z: 0 }, ChunkPos { x: 0, y: 1, z: 0 });
+        builder
+            .add_chunk(ChunkPos { x: 0, y: 0, z: 0 }, Chunk::new())
+            .unwrap();
+        builder
+            .add_chunk(ChunkPos { x: 0, y: 1, z: 0 }, Chunk::new())
+            .unwrap();
+        let mut zone = builder.build().ok().unwrap();
+        let stone = BlockId::new(blocks::Stone);
+        let water = BlockId::new(blocks::Water);
+
+        assert_eq!(zone.column_summary(3, 4), None);
+        assert_eq!(zone.column_summary(16, 4), None);
+        zone.set_block(BlockPos { x: 3, y: 5, z: 4 }, stone)
+            .unwrap();
+        let summary = zone.column_summary(3, 4).unwrap();
+        assert_eq!((summary.top, summary.height), (stone, 5));
+        assert_eq!(summary.biome, Biome::Plains);
+
+        // Raising the column
+        zone.set_block(BlockPos { x: 3, y: 20, z: 4 }, water)
+            .unwrap();
+        let summary = zone.column_summary(3, 4).unwrap();
+        assert_eq!((summary.top, summary.height), (water, 20));
+        assert_eq!(summary.biome, Biome::Ocean);
+
+        // Lowering it
+        zone.set_block(BlockPos { x: 3, y: 20, z: 4 }, BlockId::new(blocks::Air))
+            .unwrap();
+        assert_eq!(zone.column_summary(3, 4).unwrap().height, 5);
+
+        // Replacing a whole chunk
+        zone.set_chunk(ChunkPos { x: 0, y: 0, z: 0 }, Chunk::new())
+            .unwrap();
+        assert_eq!(zone.column_summary(3, 4), None);
+    }
+
+    #[test]
+    fn stores_column_biomes() {
+        let mut builder = Zone::builder(
+            ChunkPos { x: -1, y: 0, z: 0 },
+            ChunkPos { x: 0, y: 0, z: 0 },
+        );
+        for x in -1..=0 {
+            builder
+                .add_chunk(ChunkPos { x, y: 0, z: 0 }, Chunk::new())
+                .unwrap();
+        }
+        let desert = ColumnBiomes::from_fn(|_, _| Biome::Desert);
+        builder.set_column_biomes(-1, 0, desert).unwrap();
+        assert!(builder.set_column_biomes(1, 0, desert).is_err());
+        let mut zone = builder.build().ok().unwrap();
+
+        assert_eq!(
+            zone.biome_at(BlockPos { x: -3, y: 9, z: 2 }),
+            Some(Biome::Desert)
+        );
+        assert_eq!(zone.biome_at(BlockPos { x: 3, y: 9, z: 2 }), None);
+        assert_eq!(zone.biome_at(BlockPos { x: -3, y: 9, z: 20 }), None);
+
+        // Stored biomes replace the guess in column summaries.
+        let water = BlockId::new(blocks::Water);
+        zone.set_block(BlockPos { x: -3, y: 5, z: 2 }, water)
+            .unwrap();
+        assert_eq!(zone.column_summary(-3, 2).unwrap().biome, Biome::Desert);
+        zone.set_column_biomes(-1, 0, ColumnBiomes::from_fn(|_, _| Biome::Swamp))
+            .unwrap();
+        assert_eq!(zone.column_summary(-3, 2).unwrap().biome, Biome::Swamp);
+    }
+
+    #[test]
+    fn sparse_zone_drops_biomes_with_last_chunk() {
+        let mut zone = SparseZone::new();
+        let forest = ColumnBiomes::from_fn(|_, _| Biome::Forest);
+        let pos = BlockPos {
+            x: 20,
+            y: 40,
+            z: -5,
+        };
+        zone.set_column_biomes(1, -1, forest);
+        assert_eq!(zone.biome_at(pos), None);
+
+        zone.insert(ChunkPos { x: 1, y: 0, z: -1 }, Chunk::new());
+        zone.insert(ChunkPos { x: 1, y: 1, z: -1 }, Chunk::new());
+        zone.set_column_biomes(1, -1, forest);
+        assert_eq!(zone.biome_at(pos), Some(Biome::Forest));
+
+        zone.remove(ChunkPos { x: 1, y: 0, z: -1 });
+        assert_eq!(zone.biome_at(pos), Some(Biome::Forest));
+        zone.remove(ChunkPos { x: 1, y: 1, z: -1 });
+        assert_eq!(zone.biome_at(pos), None);
+    }
+
+    #[test]
+    fn zones_build_heightmaps() {
+        let mut chunk = Chunk::new();
+        chunk.set(2, 7, 3, BlockId::new(blocks::Stone));
+        let mut builder =
+            Zone::builder(ChunkPos { x: 0, y: 0, z: 0 }, ChunkPos { x: 0, y: 1, z: 0 });
+        builder
+            .add_chunk(ChunkPos { x: 0, y: 0, z: 0 }, chunk)
+            .unwrap();
+        builder
+            .add_chunk(ChunkPos { x: 0, y: 1, z: 0 }, Chunk::new())
+            .unwrap();
+        let mut zone = builder.build().ok().unwrap();
+
+        assert_eq!(
+            zone.highest_block_at(2, 3),
+            Some(BlockPos { x: 2, y: 7, z: 3 })
+        );
+        assert_eq!(zone.highest_block_at(3, 2), None);
+        assert_eq!(zone.highest_block_at(2, 19), None);
+
+        // A chunk above the top raises it.
+        let mut upper = Chunk::new();
+        upper.set(2, 1, 3, BlockId::new(blocks::Dirt));
+        zone.set_chunk(ChunkPos { x: 0, y: 1, z: 0 }, upper)
+            .unwrap();
+        assert_eq!(zone.heightmap(0, 0).unwrap().get(2, 3), Some(17));
+        zone.set_chunk(ChunkPos { x: 0, y: 1, z: 0 }, Chunk::new())
+            .unwrap();
+        assert_eq!(zone.heightmap(0, 0).unwrap().get(2, 3), Some(7));
+        assert!(zone
+            .set_chunk(ChunkPos { x: 0, y: 2, z: 0 }, Chunk::new())
+            .is_err());
+    }
+
+    #[test]
+    fn sparse_zone_heightmaps_follow_loaded_chunks() {
+        let mut zone = SparseZone::new();
+        let stone = BlockId::new(blocks::Stone);
+        let mut lower = Chunk::new();
+        lower.set(5, 10, 6, stone);
+        let mut upper = Chunk::new();
+        upper.set(5, 0, 6, stone);
+
+        zone.insert(ChunkPos { x: -1, y: 0, z: 0 }, lower);
+        assert_eq!(
+            zone.highest_block_at(-11, 6),
+            Some(BlockPos {
+                x: -11,
+                y: 10,
+                z: 6
+            })
+        );
+        zone.insert(ChunkPos { x: -1, y: 2, z: 0 }, upper);
+        assert_eq!(zone.highest_block_at(-11, 6).unwrap().y, 32);
+
+        // Edits raise and lower the top.
+        zone.set_block(
+            BlockPos {
+                x: -11,
+                y: 40,
+                z: 6,
+            },
+            stone,
+        )
+        .unwrap();
+        assert_eq!(zone.highest_block_at(-11, 6).unwrap().y, 40);
+        zone.set_block(
+            BlockPos {
+                x: -11,
+                y: 40,
+                z: 6,
+            },
+            BlockId::new(blocks::Air),
+        )
+        .unwrap();
+        assert_eq!(zone.highest_block_at(-11, 6).unwrap().y, 32);
+
+        // Unloading the upper chunk uncovers the lower one.
+        zone.remove(ChunkPos { x: -1, y: 2, z: 0 });
+        assert_eq!(zone.highest_block_at(-11, 6).unwrap().y, 10);
+        zone.remove(ChunkPos { x: -1, y: 0, z: 0 });
+        assert_eq!(zone.highest_block_at(-11, 6), None);
+    }
 }
@@ -2,7 +2,7 @@
 
 use std::{cmp::Ordering, f32::INFINITY, mem::swap, ops::Add};
 
-use common::BlockPos;
+use common::{block::CollisionBox, blocks, BlockId, BlockPos};
 use glam::{vec3a, Vec3A};
 
 /// An axis-aligned bounding box.
@@ -13,6 +13,15 @@ pub struct Aabb {
 }
 
 impl Aabb {
+    /// Returns the bounds of the full block at `pos`.
+    pub fn from_block(pos: BlockPos) -> Self {
+        let min = pos.min_corner();
+        Self {
+            min,
+            max: min + Vec3A::one(),
+        }
+    }
+
     pub fn half_width(self) -> f32 {
         (self.max.x - self.min.x) / 2.
     }
@@ -37,6 +46,12 @@ impl Aabb {
             .map(|(x, y, z)| BlockPos { x, y, z })
     }
 
+    /// Returns whether this AABB overlaps `other`. AABBs
+    /// that only touch do not overlap.
+    pub fn intersects(self, other: Aabb) -> bool {
+        self.min.cmplt(other.max).all() && other.min.cmplt(self.max).all()
+    }
+
     pub fn corners(self) -> [Vec3A; 8] {
         let dist = self.max - self.min;
         let bottom = [
@@ -120,6 +135,21 @@ impl Add<Vec3A> for Aabb {
     }
 }
 
+/// Returns the world-space bounds of the part of a block that
+/// entities collide with, or `None` if the block is not solid.
+/// Unloaded blocks (`None`) are full, solid blocks.
+pub fn block_bounds(pos: BlockPos, block: Option<BlockId>) -> Option<Aabb> {
+    let collision_box = match block {
+        Some(block) => block.collision_box()?,
+        None => CollisionBox::FULL,
+    };
+    let offset = pos.min_corner();
+    Some(Aabb {
+        min: Vec3A::from(collision_box.min) + offset,
+        max: Vec3A::from(collision_box.max) + offset,
+    })
+}
+
 /// Return value from [`collide_with_zone`]. Contains
 /// a collision vector for each of the six faces of the AABB.
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -227,21 +257,67 @@ fn collision_along_axis(
     Some(None)
 }
 
+/// The block a ray hit.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct RayImpact {
+    pub pos: BlockPos,
     pub distance: f32,
 }
 
-/// Ray traces into a zone to determine the first
-/// block impacted by the given ray. Returns `None`
-/// if the raytrace travels `max_distance_squared` without
-/// encountering a block. Otherwise, returns `Some(distance)` with the
-/// distance from `origin` to the block.
+/// Which blocks stop a ray cast by [`raytrace_in_zone`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RaycastMode {
+    /// Stop at any block other than air, including fluids.
+    StopAtFluids,
+    /// Pass through fluids, stopping at any other block.
+    IgnoreFluids,
+    /// Pass through fluids and blocks entities don't
+    /// collide with, like wires and open doors.
+    CollidingOnly,
+}
+
+impl Default for RaycastMode {
+    fn default() -> Self {
+        RaycastMode::StopAtFluids
+    }
+}
+
+impl RaycastMode {
+    /// Returns whether a ray cast in this mode stops at `block`.
+    pub fn stops_at(self, block: BlockId) -> bool {
+        match self {
+            RaycastMode::StopAtFluids => !block.is::<blocks::Air>(),
+            RaycastMode::IgnoreFluids => !block.is::<blocks::Air>() && !block.is_fluid(),
+            RaycastMode::CollidingOnly => block.collision_box().is_some() && !block.is_fluid(),
+        }
+    }
+
+    /// Returns the mode after this one, wrapping
+    /// around to the first after the last.
+    pub fn next(self) -> Self {
+        match self {
+            RaycastMode::StopAtFluids => RaycastMode::IgnoreFluids,
+            RaycastMode::IgnoreFluids => RaycastMode::CollidingOnly,
+            RaycastMode::CollidingOnly => RaycastMode::StopAtFluids,
+        }
+    }
+}
+
+/// Ray traces into a zone to determine the first block
+/// impacted by the given ray that stops it according to
+/// `mode`. `block_at` should return the block of the zone
+/// at a position, or `None` if it is not loaded; rays pass
+/// through unloaded blocks.
+///
+/// Returns `None` if the raytrace travels `max_distance_squared`
+/// without being stopped. Otherwise, returns the position of
+/// the block and the distance from `origin` to it.
 pub fn raytrace_in_zone(
     origin: Vec3A,
     dir: Vec3A,
     max_distance_squared: f32,
-    mut is_solid: impl FnMut(BlockPos) -> bool,
+    mode: RaycastMode,
+    mut block_at: impl FnMut(BlockPos) -> Option<BlockId>,
 ) -> Option<RayImpact> {
     if dir == vec3a(0.0, 0.0, 0.0) {
         return None;
@@ -274,7 +350,7 @@ pub fn raytrace_in_zone(
         Ordering::Less => {
             step.x = -1.;
             delta.x = (1.0 / direction.x).abs();
-            next.x = ((origin.x - (origin.x - 1.0).ceil()) / direction.x).abs();
+            next.x = ((origin.x - origin.x.floor()) / direction.x).abs(); // Brings X position to its block's lower face
         }
         _ => (),
     }
@@ -288,7 +364,7 @@ pub fn raytrace_in_zone(
         Ordering::Less => {
             step.y = -1.;
             delta.y = (1.0 / direction.y).abs();
-            next.y = ((origin.y - (origin.y - 1.0).ceil()) / direction.y).abs();
+            next.y = ((origin.y - origin.y.floor()) / direction.y).abs();
         }
         _ => (),
     }
@@ -302,7 +378,7 @@ pub fn raytrace_in_zone(
         Ordering::Less => {
             step.z = -1.;
             delta.z = (1.0 / direction.z).abs();
-            next.z = ((origin.z - (origin.z - 1.0).ceil()) / direction.z).abs();
+            next.z = ((origin.z - origin.z.floor()) / direction.z).abs();
         }
         _ => (),
     }
@@ -310,18 +386,14 @@ pub fn raytrace_in_zone(
     let mut current_pos = BlockPos::from_pos(origin);
 
     while dist_traveled.length_squared() < max_distance_squared {
-        if is_solid(current_pos) {
+        if block_at(current_pos).map_or(false, |block| mode.stops_at(block)) {
             // Calculate world-space position of impact.
-            let bounds = Aabb {
-                min: Vec3A::zero(),
-                max: vec3a(1., 1., 1.),
-            } + vec3a(
-                current_pos.x as f32,
-                current_pos.y as f32,
-                current_pos.z as f32,
-            );
+            let bounds = Aabb::from_block(current_pos);
             if let Some(distance) = bounds.toi_with_ray(origin, dir) {
-                return Some(RayImpact { distance });
+                return Some(RayImpact {
+                    pos: current_pos,
+                    distance,
+                });
             }
         }
 
@@ -355,11 +427,13 @@ pub fn raytrace_in_zone(
 /// * The target position of the bounding box
 /// returns a new target position accounting for
 /// collisions on the path between the two position.
+///
+/// `block_at` is used as in [`crate::do_tick`].
 pub fn resolve_collisions(
     bounds: Aabb,
     start: Vec3A,
     end: Vec3A,
-    mut is_solid: impl FnMut(BlockPos) -> bool,
+    mut block_at: impl FnMut(BlockPos) -> Option<BlockId>,
 ) -> Vec3A {
     // Work with bounding box origin instead of center bottom.
     let center_offset = vec3a(bounds.half_width(), 0., bounds.half_depth());
@@ -369,18 +443,37 @@ pub fn resolve_collisions(
     let mut pos = end;
     let vel = end - start;
 
+    // Returns the bounds of the blocks that `moved` overlaps.
+    let mut colliding = |moved: Aabb| -> Vec<Aabb> {
+        moved
+            .blocks()
+            .filter_map(|pos| block_bounds(pos, block_at(pos)))
+            .filter(|block| block.intersects(moved))
+            .collect()
+    };
+
     let moved_down = bounds + start + vec3a(0., vel.y, 0.);
-    if moved_down.blocks().any(|pos| is_solid(pos)) {
-        pos.y = start.y.floor();
+    let below = colliding(moved_down);
+    if !below.is_empty() {
+        // Land on top of the highest block.
+        let top = below
+            .iter()
+            .map(|block| block.max.y)
+            .fold(f32::NEG_INFINITY, f32::max);
+        pos.y = if vel.y < 0. {
+            top.min(start.y)
+        } else {
+            start.y
+        };
     }
 
     let moved_forward = bounds + start + vec3a(0., 0., vel.z);
-    if moved_forward.blocks().any(|pos| is_solid(pos)) {
+    if !colliding(moved_forward).is_empty() {
         pos.z = start.z;
     }
 
     let moved_right = bounds + start + vec3a(vel.x, 0., 0.);
-    if moved_right.blocks().any(|pos| is_solid(pos)) {
+    if !colliding(moved_right).is_empty() {
         pos.x = start.x;
     }
 
@@ -422,6 +515,8 @@ pub fn resolve_collisions(
 
 #[cfg(test)]
 mod tests {
+    use common::blocks;
+
     use super::*;
 
     #[test]
@@ -484,15 +579,129 @@ mod tests {
         assert_eq!(toi, Some(99.));
     }
 
+    #[test]
+    fn land_on_collision_box() {
+        let bounds = Aabb {
+            min: Vec3A::zero(),
+            max: vec3a(0.5, 1.5, 0.5),
+        };
+        let block_at = |pos: BlockPos| {
+            Some(if pos.y == 0 {
+                BlockId::new(blocks::Trapdoor {
+                    open: false,
+                    powered: false,
+                })
+            } else {
+                BlockId::new(blocks::Air)
+            })
+        };
+
+        let pos = resolve_collisions(bounds, vec3a(0.5, 0.3, 0.5), vec3a(0.5, 0.1, 0.5), block_at);
+        assert_eq!(pos, vec3a(0.5, 0.1875, 0.5));
+        assert!(crate::is_on_ground(pos, block_at));
+        assert!(!crate::is_on_ground(vec3a(0.5, 0.5, 0.5), block_at));
+    }
+
     #[test]
     fn raytrace_empty() {
-        let impact = raytrace_in_zone(Vec3A::zero(), Vec3A::unit_y(), 100., |_| false);
+        let air = |_| Some(BlockId::new(blocks::Air));
+        let mode = RaycastMode::default();
+        let impact = raytrace_in_zone(Vec3A::zero(), Vec3A::unit_y(), 100., mode, air);
+        assert_eq!(impact, None);
+        let impact = raytrace_in_zone(Vec3A::zero(), Vec3A::unit_y(), 100., mode, |_| None);
         assert_eq!(impact, None);
     }
 
     #[test]
     fn raytrace_to_block() {
-        let impact = raytrace_in_zone(vec3a(0.5, 0., 0.5), Vec3A::unit_y(), 100., |pos| pos.y == 2);
-        assert_eq!(impact, Some(RayImpact { distance: 2. }));
+        let block_at = |pos: BlockPos| {
+            Some(if pos.y == 2 {
+                BlockId::new(blocks::Stone)
+            } else {
+                BlockId::new(blocks::Air)
+            })
+        };
+        let impact = raytrace_in_zone(
+            vec3a(0.5, 0., 0.5),
+            Vec3A::unit_y(),
+            100.,
+            RaycastMode::default(),
+            block_at,
+        );
+        assert_eq!(
+            impact,
+            Some(RayImpact {
+                pos: BlockPos { x: 0, y: 2, z: 0 },
+                distance: 2.
+            })
+        );
+    }
+
+    #[test]
+    fn raytrace_from_block_faces() {
+        // Leaving a block through the face the ray starts on, toward
+        // negative X, enters the next block at once. The ray rises into
+        // the blocks above shortly after, so stepping late misses stone.
+        let block_at = |pos: BlockPos| {
+            Some(if pos == (BlockPos { x: -2, y: 0, z: 0 }) {
+                BlockId::new(blocks::Stone)
+            } else {
+                BlockId::new(blocks::Air)
+            })
+        };
+        let impact = raytrace_in_zone(
+            vec3a(-1., 0.9, 0.5),
+            vec3a(-1., 0.5, 0.),
+            100.,
+            RaycastMode::default(),
+            block_at,
+        );
+        assert_eq!(
+            impact.map(|impact| impact.pos),
+            Some(BlockPos { x: -2, y: 0, z: 0 })
+        );
+    }
+
+    #[test]
+    fn block_aabbs() {
+        let pos = BlockPos {
+            x: -1,
+            y: 2,
+            z: -16,
+        };
+        let bounds = Aabb::from_block(pos);
+        assert_eq!(bounds.min, vec3a(-1., 2., -16.));
+        assert_eq!(bounds.max, vec3a(0., 3., -15.));
+        assert_eq!(bounds.blocks().collect::<Vec<_>>(), vec![pos]);
+        assert_eq!(
+            block_bounds(pos, Some(BlockId::new(blocks::Stone))),
+            Some(bounds)
+        );
+    }
+
+    #[test]
+    fn raytrace_modes() {
+        // Water, then a wire, then stone above.
+        let block_at = |pos: BlockPos| {
+            Some(match pos.y {
+                1 => BlockId::new(blocks::Water),
+                2 => BlockId::new(blocks::Wire { power: 0 }),
+                3 => BlockId::new(blocks::Stone),
+                _ => BlockId::new(blocks::Air),
+            })
+        };
+        let hit = |mode| {
+            raytrace_in_zone(vec3a(0.5, 0.5, 0.5), Vec3A::unit_y(), 100., mode, block_at)
+                .map(|impact| impact.pos.y)
+        };
+        assert_eq!(hit(RaycastMode::StopAtFluids), Some(1));
+        assert_eq!(hit(RaycastMode::IgnoreFluids), Some(2));
+        assert_eq!(hit(RaycastMode::CollidingOnly), Some(3));
+
+        let mut mode = RaycastMode::default();
+        for _ in 0..3 {
+            mode = mode.next();
+        }
+        assert_eq!(mode, RaycastMode::default());
     }
 }
@@ -3,42 +3,272 @@
 pub mod collision;
 
 pub use collision::Aabb;
-use common::BlockPos;
+use common::{BlockId, BlockPos, WorldPos};
 use glam::{vec3a, Vec3A};
 
+/// How far above the ground an entity can be while
+/// still standing on it.
+const GROUND_TOLERANCE: f32 = 0.05;
+
+/// The fastest an entity on a climbable block can fall,
+/// in blocks per second.
+const MAX_CLIMBING_FALL_SPEED: f32 = 2.;
+/// The speed at which entities climb, in blocks per second.
+pub const CLIMB_SPEED: f32 = 3.;
+/// How far beyond an entity's bounds climbable
+/// blocks can be for it to hold on to them.
+const CLIMB_REACH: f32 = 0.1;
+
+/// How far an entity has fallen since it last stood on the
+/// ground or was in a fluid. Updated by [`track_fall`].
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct FallDistance(pub f32);
+
 /// Ticks an entity for physics.
+///
+/// The entity is simulated in the local space of its zone, so `vel` is
+/// local to the zone and `block_at` should return the block of that zone
+/// at a position, or `None` if it is not loaded. Unloaded blocks are
+/// treated as full, solid blocks.
+///
+/// Returns the speed in blocks per second at which the entity
+/// landed on the ground, or `None` if it didn't land this tick.
 pub fn do_tick(
     bounds: Aabb,
-    pos: &mut Vec3A,
+    pos: &mut WorldPos,
     vel: &mut Vec3A,
     dt: f32,
-    mut is_solid: impl FnMut(BlockPos) -> bool,
-) {
+    mut block_at: impl FnMut(BlockPos) -> Option<BlockId>,
+) -> Option<f32> {
+    let pos = &mut pos.local;
+    let was_on_ground = ground_below(*pos, &mut block_at).is_some();
+
     let drag_factor = 0.6676f32;
     *vel *= drag_factor.powf(dt);
+    let falling_speed = -vel.y;
 
     let new_pos = *pos + *vel * dt;
-    let new_pos = collision::resolve_collisions(bounds, *pos, new_pos, &mut is_solid);
+    let new_pos = collision::resolve_collisions(bounds, *pos, new_pos, &mut block_at);
     *pos = new_pos;
 
-    let on_ground = is_on_ground(*pos, &mut is_solid);
+    let ground = ground_below(*pos, &mut block_at);
 
     let gravity = -24.0f32;
-    if !on_ground {
+    if ground.is_none() {
         vel.y += gravity * dt;
     }
+    // Entities hold on to ladders and the like, so they fall slowly.
+    if is_climbing(bounds, *pos, &mut block_at) {
+        vel.y = vel.y.max(-MAX_CLIMBING_FALL_SPEED);
+    }
 
+    // Friction is relative to ordinary ground, which has a factor of 0.05.
     let friction_factor = 0.05f32;
-    if on_ground {
-        *vel *= friction_factor.powf(dt);
+    if let Some(ground) = ground {
+        let friction = ground.map_or(1., BlockId::friction);
+        *vel *= friction_factor.powf(dt * friction);
+    }
+
+    if ground.is_some() && !was_on_ground && falling_speed > 0. {
+        Some(falling_speed)
+    } else {
+        None
     }
 }
 
-/// Determines if an entity is standing on the ground.
-pub fn is_on_ground(pos: Vec3A, mut is_solid: impl FnMut(BlockPos) -> bool) -> bool {
-    if pos.y % 1.0 <= 0.05 {
-        is_solid(BlockPos::from_pos(pos - vec3a(0., 1., 0.)))
+/// Updates the distance an entity with the given bounds has fallen after
+/// a [tick](do_tick) moved it from `old_pos` to `pos`. See [`do_tick`]
+/// for `block_at`.
+///
+/// Only downward movement counts, so the distance of a jump is measured
+/// from its peak. The distance is reset when the entity stands on the
+/// ground, is in or on a fluid, holds on to a climbable block, or
+/// changes zones. Returns the distance
+/// fallen if the entity landed on the ground this tick.
+pub fn track_fall(
+    fall: &mut FallDistance,
+    bounds: Aabb,
+    old_pos: WorldPos,
+    pos: WorldPos,
+    mut block_at: impl FnMut(BlockPos) -> Option<BlockId>,
+) -> Option<f32> {
+    // Fluids are solid, so entities may stand on them as well as in them.
+    let ground = ground_below(pos.local, &mut block_at);
+    let in_fluid = block_at(BlockPos::from_pos(pos.local)).map_or(false, BlockId::is_fluid)
+        || matches!(ground, Some(Some(block)) if block.is_fluid());
+    let climbing = is_climbing(bounds, pos.local, &mut block_at);
+    if old_pos.zone != pos.zone || in_fluid || climbing {
+        fall.0 = 0.;
+        return None;
+    }
+
+    fall.0 += (old_pos.local.y - pos.local.y).max(0.);
+    if ground.is_some() {
+        let distance = std::mem::take(&mut fall.0);
+        Some(distance).filter(|&distance| distance > 0.)
     } else {
-        false
+        None
+    }
+}
+
+/// Determines if an entity with the given bounds whose bottom center
+/// is at `pos` is holding on to a climbable block, like a ladder.
+/// See [`do_tick`] for `block_at`.
+pub fn is_climbing(
+    bounds: Aabb,
+    pos: Vec3A,
+    mut block_at: impl FnMut(BlockPos) -> Option<BlockId>,
+) -> bool {
+    let center_offset = vec3a(bounds.half_width(), 0., bounds.half_depth());
+    let reach = vec3a(CLIMB_REACH, 0., CLIMB_REACH);
+    let bounds = Aabb {
+        min: bounds.min - center_offset - reach,
+        max: bounds.max - center_offset + reach,
+    } + pos;
+    bounds
+        .blocks()
+        .any(|block_pos| block_at(block_pos).map_or(false, BlockId::is_climbable))
+}
+
+/// Determines if an entity is standing on the ground
+/// of its zone. See [`do_tick`] for `block_at`.
+pub fn is_on_ground(pos: WorldPos, block_at: impl FnMut(BlockPos) -> Option<BlockId>) -> bool {
+    ground_below(pos.local, block_at).is_some()
+}
+
+/// Finds the block an entity is standing on. Returns `Some(None)`
+/// if the entity is standing on an unloaded block.
+fn ground_below(
+    pos: Vec3A,
+    mut block_at: impl FnMut(BlockPos) -> Option<BlockId>,
+) -> Option<Option<BlockId>> {
+    let block_pos = BlockPos::from_pos(pos - vec3a(0., GROUND_TOLERANCE, 0.));
+    let block = block_at(block_pos);
+    let bounds = collision::block_bounds(block_pos, block)?;
+    let height = pos.y - bounds.max.y;
+    if (-GROUND_TOLERANCE..=GROUND_TOLERANCE).contains(&height) {
+        Some(block)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::blocks;
+    use glam::vec3a;
+
+    use super::*;
+
+    #[test]
+    fn reports_landing_speed() {
+        let bounds = Aabb {
+            min: Vec3A::zero(),
+            max: vec3a(0.5, 2., 0.5),
+        };
+        let floor = |pos: BlockPos| {
+            Some(if pos.y < 0 {
+                BlockId::new(blocks::Stone)
+            } else {
+                BlockId::new(blocks::Air)
+            })
+        };
+        let mut pos = WorldPos::main(vec3a(0.5, 10., 0.5));
+        let mut vel = Vec3A::zero();
+        let mut landings = Vec::new();
+        for _ in 0..100 {
+            if let Some(speed) = do_tick(bounds, &mut pos, &mut vel, 0.05, floor) {
+                landings.push(speed);
+            }
+        }
+        assert_eq!(landings.len(), 1);
+        assert!(landings[0] > 10.);
+        assert!(pos.local.y.abs() < GROUND_TOLERANCE);
+        assert!(is_on_ground(pos, floor));
+    }
+
+    #[test]
+    fn tracks_fall_distance() {
+        let bounds = Aabb {
+            min: Vec3A::zero(),
+            max: vec3a(0.5, 2., 0.5),
+        };
+        // Stone below y = 0, with a pool of water at x >= 10.
+        let world = |pos: BlockPos| {
+            Some(if pos.y < 0 {
+                BlockId::new(blocks::Stone)
+            } else if pos.y < 2 && pos.x >= 10 {
+                BlockId::new(blocks::Water)
+            } else {
+                BlockId::new(blocks::Air)
+            })
+        };
+        let fall = |start: Vec3A, vel: Vec3A| {
+            let mut pos = WorldPos::main(start);
+            let mut vel = vel;
+            let mut fall = FallDistance::default();
+            let mut landings = Vec::new();
+            for _ in 0..100 {
+                let old_pos = pos;
+                do_tick(bounds, &mut pos, &mut vel, 0.05, world);
+                landings.extend(track_fall(&mut fall, bounds, old_pos, pos, world));
+            }
+            assert_eq!(fall, FallDistance::default());
+            landings
+        };
+
+        let landings = fall(vec3a(0.5, 10., 0.5), Vec3A::zero());
+        assert_eq!(landings.len(), 1);
+        assert!((landings[0] - 10.).abs() < GROUND_TOLERANCE);
+
+        // Jumps are measured from their peak
+        let landings = fall(vec3a(0.5, 0., 0.5), vec3a(0., 8., 0.));
+        assert_eq!(landings.len(), 1);
+        assert!(landings[0] > 0.5 && landings[0] < 2.);
+
+        // Water breaks falls
+        assert!(fall(vec3a(10.5, 10., 0.5), Vec3A::zero()).is_empty());
+    }
+
+    #[test]
+    fn ladders_break_falls() {
+        let bounds = Aabb {
+            min: Vec3A::zero(),
+            max: vec3a(0.5, 2., 0.5),
+        };
+        // A ladder at x = 0 from the ground up to y = 20,
+        // hanging on a wall at x = -1.
+        let world = |pos: BlockPos| {
+            Some(if pos.y < 0 || pos.x < 0 {
+                BlockId::new(blocks::Stone)
+            } else if pos.x == 0 && pos.z == 0 && pos.y < 20 {
+                BlockId::new(blocks::Ladder {
+                    facing: common::block::Facing::PosX,
+                })
+            } else {
+                BlockId::new(blocks::Air)
+            })
+        };
+        assert!(is_climbing(bounds, vec3a(0.5, 5., 0.5), world));
+        assert!(is_climbing(bounds, vec3a(1.2, 5., 0.5), world));
+        assert!(!is_climbing(bounds, vec3a(3.5, 5., 0.5), world));
+
+        let mut pos = WorldPos::main(vec3a(0.5, 15., 0.5));
+        let mut vel = Vec3A::zero();
+        let mut fall = FallDistance::default();
+        let mut landings = Vec::new();
+        for _ in 0..200 {
+            let old_pos = pos;
+            if let Some(speed) = do_tick(bounds, &mut pos, &mut vel, 0.05, world) {
+                landings.push(speed);
+            }
+            assert!(vel.y >= -MAX_CLIMBING_FALL_SPEED);
+            assert_eq!(track_fall(&mut fall, bounds, old_pos, pos, world), None);
+        }
+        // The entity slid down the ladder and landed gently.
+        assert!(pos.local.y.abs() < GROUND_TOLERANCE);
+        assert!(landings
+            .iter()
+            .all(|&speed| speed <= MAX_CLIMBING_FALL_SPEED));
     }
 }
@@ -0,0 +1,76 @@
+//! Benchmarks compressing and decompressing chunk data, and
+//! prints the size reduction on representative chunks.
+
+use common::{blocks, chunk::CHUNK_DIM, BlockId, Chunk};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use protocol::dictionary::{BlockDictionary, ChunkData};
+
+/// Layered terrain with a surface of grass and sand.
+fn terrain() -> Chunk {
+    let mut chunk = Chunk::new();
+    for x in 0..CHUNK_DIM {
+        for z in 0..CHUNK_DIM {
+            for y in 0..10 {
+                chunk.set(x, y, z, BlockId::new(blocks::Stone));
+            }
+            for y in 10..13 {
+                chunk.set(x, y, z, BlockId::new(blocks::Dirt));
+            }
+            let surface = if (x * 7 + z * 3) % 5 == 0 {
+                BlockId::new(blocks::Sand)
+            } else {
+                BlockId::new(blocks::Grass)
+            };
+            chunk.set(x, 13, z, surface);
+        }
+    }
+    chunk
+}
+
+/// Stone riddled with a noisy pattern of ores and caves,
+/// close to the worst case for compression.
+fn underground() -> Chunk {
+    let mut chunk = Chunk::new();
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    for x in 0..CHUNK_DIM {
+        for y in 0..CHUNK_DIM {
+            for z in 0..CHUNK_DIM {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let block = match state % 8 {
+                    0 => BlockId::new(blocks::Air),
+                    1 => BlockId::new(blocks::Melium),
+                    2 => BlockId::new(blocks::Gravel),
+                    _ => BlockId::new(blocks::Stone),
+                };
+                chunk.set(x, y, z, block);
+            }
+        }
+    }
+    chunk
+}
+
+fn bench(c: &mut Criterion) {
+    let dictionary = BlockDictionary::from_registry();
+    for (name, chunk) in [("terrain", terrain()), ("underground", underground())].iter() {
+        let data = dictionary.encode(chunk.clone());
+        let compressed = data.clone().compress();
+        println!(
+            "{}: {} bytes uncompressed, {} bytes compressed",
+            name,
+            data.encoded_size(),
+            compressed.encoded_size()
+        );
+
+        c.bench_function(&format!("compress {}", name), |b| {
+            b.iter(|| black_box(data.clone()).compress())
+        });
+        c.bench_function(&format!("decompress {}", name), |b| {
+            b.iter(|| black_box(compressed.clone()).decompress())
+        });
+    }
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -1,12 +1,15 @@
-use std::{fmt::Debug, iter};
+use std::{fmt::Debug, iter, sync::Arc};
 
 use flume::{Receiver, Sender};
 
-use crate::packets::{client::ClientPacket, server::ServerPacket};
+use crate::{
+    packets::{client::ClientPacket, server::ServerPacket, Packet},
+    stats::PacketStats,
+};
 
 pub trait Side {
-    type SendPacket: Send + Debug + 'static;
-    type RecvPacket: Send + Debug + 'static;
+    type SendPacket: Packet + Send + Debug + 'static;
+    type RecvPacket: Packet + Send + Debug + 'static;
 }
 
 #[derive(Clone)]
@@ -32,10 +35,13 @@ impl Side for ToClient {
 /// the client and server run in the same process and different threads, so they
 /// communicate via a channel. Whereas in multiplayer, client and server communicate
 /// over the network via QUIC.
+///
+/// Each bridge keeps [statistics](crate::stats) of its traffic.
 #[derive(Debug)]
 pub struct Bridge<S: Side> {
     sender: Sender<S::SendPacket>,
     receiver: Receiver<S::RecvPacket>,
+    stats: Arc<PacketStats>,
 }
 
 impl<S> Clone for Bridge<S>
@@ -46,6 +52,7 @@ where
         Self {
             sender: self.sender.clone(),
             receiver: self.receiver.clone(),
+            stats: Arc::clone(&self.stats),
         }
     }
 }
@@ -59,10 +66,12 @@ pub fn singleplayer() -> (Bridge<ToServer>, Bridge<ToClient>) {
         Bridge {
             sender: client_sender,
             receiver: server_receiver,
+            stats: Arc::new(PacketStats::default()),
         },
         Bridge {
             sender: server_sender,
             receiver: client_receiver,
+            stats: Arc::new(PacketStats::default()),
         },
     )
 }
@@ -74,20 +83,34 @@ where
     /// Returns an iterator over buffered packets.
     pub fn flush_received(&self) -> impl Iterator<Item = S::RecvPacket> {
         let receiver = self.receiver.clone();
-        iter::from_fn(move || receiver.try_recv().ok())
+        let stats = Arc::clone(&self.stats);
+        iter::from_fn(move || {
+            let packet = receiver.try_recv().ok()?;
+            stats.record_received(packet.kind());
+            Some(packet)
+        })
     }
 
     /// Waits for the next packet to be received.
     pub fn wait_received(&self) -> Option<S::RecvPacket> {
-        self.receiver.recv().ok()
+        let packet = self.receiver.recv().ok()?;
+        self.stats.record_received(packet.kind());
+        Some(packet)
     }
 
     /// Sends a packet to the peer.
     pub fn send(&self, packet: S::SendPacket) {
         log::trace!("Sending {:?}", packet);
+        self.stats.record_sent(packet.kind());
         let _ = self.sender.send(packet);
     }
 
+    /// Returns the statistics of the packets sent and
+    /// received through this bridge and its clones.
+    pub fn stats(&self) -> &PacketStats {
+        &self.stats
+    }
+
     /// Returns whether an error has occurred resulting in a
     /// disconnection from the peer.
     pub fn is_disconnected(&self) -> bool {
@@ -0,0 +1,87 @@
+//! Caching chunks on the client across connections.
+//!
+//! Rejoining a world means receiving every chunk in view again, though
+//! most haven't changed since the last visit. If both peers enable
+//! [`Features::CHUNK_CACHE`](crate::features::Features::CHUNK_CACHE),
+//! the server sends [`LoadCachedChunk`](crate::packets::server::LoadCachedChunk)
+//! with the [hash](chunk_hash) of each chunk instead of `LoadChunk`. A
+//! client holding a chunk with that hash for the same world loads its
+//! copy; it asks for the others with
+//! [`RequestChunks`](crate::packets::client::RequestChunks), which the
+//! server answers with `LoadChunk`.
+//!
+//! Worlds are told apart by the `world_id` in `ServerInfo`. Hashes are
+//! computed over chunks with the server's block IDs, so clients cache
+//! chunks before [translating](crate::registry) them.
+
+use common::Chunk;
+use sha2::{Digest, Sha256};
+
+use crate::dictionary::ChunkData;
+
+/// Returns the hash identifying the contents of a chunk.
+pub fn chunk_hash(chunk: &Chunk) -> u64 {
+    let encoded = bincode::serialize(chunk).expect("chunks are serializable");
+    first_u64(&Sha256::digest(&encoded))
+}
+
+/// Derives the ID sent in `ServerInfo` from a world's
+/// seed, so that clients can't learn the seed.
+pub fn world_id(seed: u64) -> u64 {
+    first_u64(&Sha256::digest(&seed.to_le_bytes()))
+}
+
+fn first_u64(digest: &[u8]) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Encodes a chunk to be stored in a cache.
+pub fn encode_entry(chunk: Chunk) -> Vec<u8> {
+    let data = ChunkData::Full(chunk);
+    // Web builds can't compress chunk data.
+    #[cfg(not(target_arch = "wasm32"))]
+    let data = data.compress();
+    bincode::serialize(&data).expect("chunk data is serializable")
+}
+
+/// Decodes a chunk stored with [`encode_entry`], returning it only if its
+/// hash is `expected_hash`. Returns `None` if the entry is malformed,
+/// so a corrupted cache only costs a request.
+pub fn decode_entry(entry: &[u8], expected_hash: u64) -> Option<Chunk> {
+    let data: ChunkData = bincode::deserialize(entry).ok()?;
+    let chunk = data.into_full()?;
+    if chunk_hash(&chunk) == expected_hash {
+        Some(chunk)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{blocks, BlockId};
+
+    use super::*;
+
+    #[test]
+    fn entries_match_hashes() {
+        let mut chunk = Chunk::new();
+        chunk.set(1, 2, 3, BlockId::new(blocks::Stone));
+        let hash = chunk_hash(&chunk);
+        assert_ne!(hash, chunk_hash(&Chunk::new()));
+
+        let entry = encode_entry(chunk.clone());
+        let decoded = decode_entry(&entry, hash).unwrap();
+        assert!(decoded.get(1, 2, 3).is::<blocks::Stone>());
+        assert!(decode_entry(&entry, hash ^ 1).is_none());
+        assert!(decode_entry(&entry[..entry.len() / 2], hash).is_none());
+    }
+
+    #[test]
+    fn world_ids_differ() {
+        assert_eq!(world_id(6256), world_id(6256));
+        assert_ne!(world_id(6256), world_id(6257));
+    }
+}
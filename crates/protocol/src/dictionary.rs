@@ -0,0 +1,261 @@
+//! Shared block-state dictionaries for compact chunk palettes.
+//!
+//! Chunk palettes normally contain full [`BlockId`]s. When both peers have
+//! the same block registry, the server sends a [`BlockDictionary`] at join
+//! time, after which chunk palettes are sent as small indexes into the
+//! dictionary. Chunks containing a block state missing from the dictionary,
+//! and all chunks sent to clients with a different registry, fall back
+//! to full palettes.
+//!
+//! Either form of [`ChunkData`] may additionally be compressed with zstd,
+//! which the server announces in `ServerInfo`. Decoding decompresses
+//! chunk data transparently. zstd doesn't build for wasm32, so web
+//! builds can't compress chunk data and don't support the
+//! [feature](crate::features::Features::COMPRESSED_CHUNKS).
+
+use std::collections::HashMap;
+
+use common::{block, BlockId, Chunk};
+use serde::{Deserialize, Serialize};
+use utils::PackedArray;
+
+/// The zstd level used to compress chunk data. Chunks
+/// compress well at low levels, which are much faster.
+#[cfg(not(target_arch = "wasm32"))]
+pub const COMPRESSION_LEVEL: i32 = 3;
+
+/// A table of block states shared by both peers.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "Vec<BlockId>", into = "Vec<BlockId>")]
+pub struct BlockDictionary {
+    entries: Vec<BlockId>,
+    indexes: HashMap<BlockId, u16>,
+}
+
+impl BlockDictionary {
+    /// Creates a dictionary from its entries. Entries after
+    /// the first `u16::MAX + 1` are ignored.
+    pub fn new(entries: Vec<BlockId>) -> Self {
+        let mut entries = entries;
+        entries.truncate(u16::MAX as usize + 1);
+        let indexes = entries
+            .iter()
+            .enumerate()
+            .map(|(i, &block)| (block, i as u16))
+            .collect();
+        Self { entries, indexes }
+    }
+
+    /// Creates a dictionary containing every
+    /// state of each block in the registry.
+    pub fn from_registry() -> Self {
+        Self::new(block::all_states().collect())
+    }
+
+    pub fn entries(&self) -> &[BlockId] {
+        &self.entries
+    }
+
+    pub fn index_of(&self, block: BlockId) -> Option<u16> {
+        self.indexes.get(&block).copied()
+    }
+
+    pub fn get(&self, index: u16) -> Option<BlockId> {
+        self.entries.get(index as usize).copied()
+    }
+
+    /// Encodes a chunk, using dictionary indexes for its palette
+    /// if every block in the palette is in the dictionary.
+    pub fn encode(&self, chunk: Chunk) -> ChunkData {
+        let palette: Option<Vec<u16>> = chunk
+            .palette()
+            .iter()
+            .map(|&block| self.index_of(block))
+            .collect();
+        match palette {
+            Some(palette) => ChunkData::Dictionary {
+                indexes: chunk.into_parts().0,
+                palette,
+            },
+            None => ChunkData::Full(chunk),
+        }
+    }
+
+    /// Decodes a chunk. Returns `None` if the chunk data is
+    /// malformed or references entries not in the dictionary.
+    pub fn decode(&self, data: ChunkData) -> Option<Chunk> {
+        match data.decompress()? {
+            ChunkData::Full(chunk) => Some(chunk),
+            ChunkData::Dictionary { indexes, palette } => {
+                let palette = palette
+                    .into_iter()
+                    .map(|index| self.get(index))
+                    .collect::<Option<_>>()?;
+                Chunk::from_parts(indexes, palette)
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            ChunkData::Compressed(_) => None,
+        }
+    }
+}
+
+impl From<Vec<BlockId>> for BlockDictionary {
+    fn from(entries: Vec<BlockId>) -> Self {
+        Self::new(entries)
+    }
+}
+
+impl From<BlockDictionary> for Vec<BlockId> {
+    fn from(dictionary: BlockDictionary) -> Self {
+        dictionary.entries
+    }
+}
+
+/// The blocks of a chunk as sent over the network.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ChunkData {
+    /// The chunk with its full palette.
+    Full(Chunk),
+    /// The chunk's packed indexes and a palette of
+    /// indexes into the connection's [`BlockDictionary`].
+    Dictionary {
+        indexes: PackedArray,
+        palette: Vec<u16>,
+    },
+    /// Either of the above, bincode-encoded
+    /// and compressed with zstd.
+    #[cfg(not(target_arch = "wasm32"))]
+    Compressed(Vec<u8>),
+}
+
+impl ChunkData {
+    /// Decodes chunk data without a dictionary. Returns `None`
+    /// if the data was encoded with a dictionary or is malformed.
+    pub fn into_full(self) -> Option<Chunk> {
+        match self.decompress()? {
+            ChunkData::Full(chunk) => Some(chunk),
+            ChunkData::Dictionary { .. } => None,
+            #[cfg(not(target_arch = "wasm32"))]
+            ChunkData::Compressed(_) => None,
+        }
+    }
+
+    /// Compresses the data. Data that is already compressed is unchanged.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn compress(self) -> Self {
+        if let ChunkData::Compressed(_) = self {
+            return self;
+        }
+        let encoded = bincode::serialize(&self).expect("chunk data is serializable");
+        let compressed = zstd::encode_all(encoded.as_slice(), COMPRESSION_LEVEL)
+            .expect("compressing to memory can't fail");
+        ChunkData::Compressed(compressed)
+    }
+
+    /// Decompresses the data if it is compressed. Returns
+    /// `None` if the compressed data is malformed.
+    pub fn decompress(self) -> Option<Self> {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            ChunkData::Compressed(compressed) => {
+                let encoded = zstd::decode_all(compressed.as_slice()).ok()?;
+                bincode::deserialize(&encoded).ok()
+            }
+            data => Some(data),
+        }
+    }
+
+    /// Returns the number of bytes the data takes up in a packet.
+    pub fn encoded_size(&self) -> usize {
+        bincode::serialized_size(self).expect("chunk data is serializable") as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{blocks, chunk::CHUNK_DIM};
+
+    use super::*;
+
+    #[test]
+    fn dictionary_roundtrip() {
+        let dictionary = BlockDictionary::from_registry();
+        let mut chunk = Chunk::new();
+        chunk.set(0, 0, 0, BlockId::new(blocks::Dirt));
+
+        let data = dictionary.encode(chunk);
+        assert!(matches!(data, ChunkData::Dictionary { .. }));
+        let chunk = dictionary.decode(data).unwrap();
+        assert!(chunk.get(0, 0, 0).is::<blocks::Dirt>());
+        assert!(chunk.get(1, 0, 0).is::<blocks::Air>());
+    }
+
+    #[test]
+    fn fallback_to_full_palette() {
+        let dictionary = BlockDictionary::new(vec![BlockId::new(blocks::Air)]);
+        let mut chunk = Chunk::new();
+        chunk.set(0, 0, 0, BlockId::new(blocks::Stone));
+
+        let data = dictionary.encode(chunk);
+        assert!(matches!(data, ChunkData::Full(_)));
+        assert!(dictionary.decode(data).is_some());
+    }
+
+    /// A chunk of terrain: stone, then dirt, then a layer of grass with
+    /// some sand, then air.
+    fn terrain_chunk() -> Chunk {
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_DIM {
+            for z in 0..CHUNK_DIM {
+                for y in 0..10 {
+                    chunk.set(x, y, z, BlockId::new(blocks::Stone));
+                }
+                for y in 10..13 {
+                    chunk.set(x, y, z, BlockId::new(blocks::Dirt));
+                }
+                let surface = if (x * 7 + z * 3) % 5 == 0 {
+                    BlockId::new(blocks::Sand)
+                } else {
+                    BlockId::new(blocks::Grass)
+                };
+                chunk.set(x, 13, z, surface);
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn compression_roundtrip() {
+        let dictionary = BlockDictionary::from_registry();
+        let chunk = terrain_chunk();
+
+        let data = dictionary.encode(chunk.clone()).compress();
+        assert!(matches!(data, ChunkData::Compressed(_)));
+        let decoded = dictionary.decode(data).unwrap();
+        assert!(decoded.get(3, 5, 3).is::<blocks::Stone>());
+        assert!(decoded.get(3, 14, 3).is::<blocks::Air>());
+
+        let data = ChunkData::Full(chunk).compress();
+        assert!(data.into_full().unwrap().get(3, 11, 3).is::<blocks::Dirt>());
+    }
+
+    #[test]
+    fn compression_shrinks_terrain() {
+        let dictionary = BlockDictionary::from_registry();
+        let data = dictionary.encode(terrain_chunk());
+        let uncompressed = data.encoded_size();
+        let compressed = data.compress().encoded_size();
+        assert!(
+            compressed * 4 < uncompressed,
+            "compressed to {} of {} bytes",
+            compressed,
+            uncompressed
+        );
+    }
+
+    #[test]
+    fn malformed_compressed_data() {
+        let data = ChunkData::Compressed(vec![1, 2, 3]);
+        assert!(data.into_full().is_none());
+    }
+}
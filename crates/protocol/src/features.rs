@@ -0,0 +1,105 @@
+//! Optional protocol features.
+//!
+//! Some parts of the protocol are optional, so a peer can skip
+//! implementing them and a server can turn them off. In the login
+//! state, the client lists the [`Features`] it supports in `ClientInfo`,
+//! and the server answers in `ServerInfo` with the features enabled
+//! for the connection: those both peers support and the server has
+//! turned on. Peers ignore feature bits they don't know, so adding a
+//! feature doesn't require a new [protocol version](crate::PROTOCOL_VERSION).
+
+use std::ops::{BitAnd, BitOr};
+
+use serde::{Deserialize, Serialize};
+
+/// A set of optional protocol features.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Features(u32);
+
+impl Features {
+    /// No features.
+    pub const NONE: Features = Features(0);
+    /// The chunk data in `LoadChunk` may be
+    /// [compressed](crate::dictionary::ChunkData::Compressed).
+    pub const COMPRESSED_CHUNKS: Features = Features(1 << 0);
+    /// The server may send chunk hashes for the client to
+    /// load from its [cache](crate::chunk_cache).
+    pub const CHUNK_CACHE: Features = Features(1 << 1);
+
+    /// All features implemented by this crate.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub const SUPPORTED: Features =
+        Features(Features::COMPRESSED_CHUNKS.0 | Features::CHUNK_CACHE.0);
+    /// All features implemented by this crate. Web builds
+    /// can't [decompress](crate::dictionary) chunk data.
+    #[cfg(target_arch = "wasm32")]
+    pub const SUPPORTED: Features = Features::CHUNK_CACHE;
+
+    /// Creates a set from its bits. Bits of unknown features
+    /// are kept, so they can be passed on.
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns whether all features in `other` are in this set.
+    pub fn contains(self, other: Features) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the features in both sets.
+    pub fn intersection(self, other: Features) -> Features {
+        Features(self.0 & other.0)
+    }
+
+    /// Returns the features in this set but not in `other`.
+    pub fn difference(self, other: Features) -> Features {
+        Features(self.0 & !other.0)
+    }
+}
+
+impl BitOr for Features {
+    type Output = Features;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Features(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for Features {
+    type Output = Features;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.intersection(rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiation() {
+        let unknown = Features::from_bits(1 << 31);
+        let client = Features::SUPPORTED | unknown;
+        assert!(client.contains(Features::COMPRESSED_CHUNKS));
+        assert!(client.contains(Features::CHUNK_CACHE));
+        assert!(client.contains(Features::NONE));
+
+        // A server with compression turned off enables
+        // nothing, and never enables unknown features.
+        let enabled = client & Features::SUPPORTED & Features::NONE;
+        assert!(!enabled.contains(Features::COMPRESSED_CHUNKS));
+        let enabled = client & Features::SUPPORTED & Features::COMPRESSED_CHUNKS;
+        assert_eq!(enabled, Features::COMPRESSED_CHUNKS);
+        assert!(!(client & Features::SUPPORTED).contains(unknown));
+
+        // A client without a chunk cache doesn't announce it.
+        let client = Features::SUPPORTED.difference(Features::CHUNK_CACHE);
+        assert!(!client.contains(Features::CHUNK_CACHE));
+        assert!(client.contains(Features::COMPRESSED_CHUNKS));
+    }
+}
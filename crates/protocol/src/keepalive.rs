@@ -0,0 +1,118 @@
+//! Detection of dead connections.
+//!
+//! In the game state, each peer sends a [`Ping`] every [`PING_INTERVAL`],
+//! and the other answers with a [`Pong`] carrying the same ID. A peer
+//! which hears no answer for [`TIMEOUT`] considers the connection dead.
+//! [`Keepalive`] tracks this state for one side of a connection.
+
+use std::time::Duration;
+
+use instant::Instant;
+
+use crate::packets::shared::{Ping, Pong};
+
+/// The time between pings.
+pub const PING_INTERVAL: Duration = Duration::from_secs(2);
+/// The time without an answer to a ping after
+/// which the connection is considered dead.
+pub const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The pings sent on one side of a connection
+/// and the answers to them.
+#[derive(Debug)]
+pub struct Keepalive {
+    next_id: u64,
+    /// The ID and send time of the ping awaiting an answer.
+    pending: Option<(u64, Instant)>,
+    last_ping: Instant,
+    last_answer: Instant,
+    rtt: Option<Duration>,
+}
+
+impl Keepalive {
+    /// Creates a `Keepalive` for a connection
+    /// which was alive at `now`.
+    pub fn new(now: Instant) -> Self {
+        Self {
+            next_id: 0,
+            pending: None,
+            last_ping: now,
+            last_answer: now,
+            rtt: None,
+        }
+    }
+
+    /// Returns a ping to send if one is due. A new ping is
+    /// only sent once the last one has been answered.
+    pub fn poll(&mut self, now: Instant) -> Option<Ping> {
+        if self.pending.is_some() || now.duration_since(self.last_ping) < PING_INTERVAL {
+            return None;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending = Some((id, now));
+        self.last_ping = now;
+        Some(Ping { id })
+    }
+
+    /// Handles an answer to a ping. Answers to
+    /// unknown pings are ignored.
+    pub fn on_pong(&mut self, pong: Pong, now: Instant) {
+        if let Some((id, sent)) = self.pending {
+            if id == pong.id {
+                self.pending = None;
+                self.last_answer = now;
+                self.rtt = Some(now.duration_since(sent));
+            }
+        }
+    }
+
+    /// Returns whether the peer has failed to answer for [`TIMEOUT`].
+    pub fn is_timed_out(&self, now: Instant) -> bool {
+        now.duration_since(self.last_answer) >= TIMEOUT
+    }
+
+    /// Returns the round-trip time of the last answered ping.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+}
+
+/// Returns the answer to a ping.
+pub fn answer(ping: Ping) -> Pong {
+    Pong { id: ping.id }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_rtt() {
+        let start = Instant::now();
+        let mut keepalive = Keepalive::new(start);
+        assert!(keepalive.poll(start).is_none());
+
+        let sent = start + PING_INTERVAL;
+        let ping = keepalive.poll(sent).unwrap();
+        // No new ping until this one is answered.
+        assert!(keepalive.poll(sent + PING_INTERVAL).is_none());
+
+        keepalive.on_pong(Pong { id: ping.id + 1 }, sent);
+        assert_eq!(keepalive.rtt(), None);
+        keepalive.on_pong(answer(ping), sent + Duration::from_millis(40));
+        assert_eq!(keepalive.rtt(), Some(Duration::from_millis(40)));
+    }
+
+    #[test]
+    fn times_out_without_answers() {
+        let start = Instant::now();
+        let mut keepalive = Keepalive::new(start);
+        let ping = keepalive.poll(start + PING_INTERVAL).unwrap();
+        assert!(!keepalive.is_timed_out(start + TIMEOUT / 2));
+        assert!(keepalive.is_timed_out(start + TIMEOUT));
+
+        keepalive.on_pong(answer(ping), start + TIMEOUT);
+        assert!(!keepalive.is_timed_out(start + TIMEOUT));
+    }
+}
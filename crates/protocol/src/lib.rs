@@ -25,19 +25,46 @@
 //! The initial stream of events looks like this:
 //! * Client connects to server.
 //! * Client sends [`ClientInfo`](packets::client::ClientInfo).
-//! * Server sends [`ServerInfo`](packets::server::ServerInfo).
+//! * Server sends [`ServerInfo`](packets::server::ServerInfo), or disconnects the
+//! client if it implements a different [`PROTOCOL_VERSION`]. The two packets also
+//! negotiate optional [features](features::Features).
+//! * If `ServerInfo` names a [resource pack](resource_pack), client loads it
+//! and sends [`ResourcePackLoaded`](packets::client::ResourcePackLoaded).
+//! * If the client's block registry digest differs from the server's, server
+//! sends [`RegistrySync`](packets::server::RegistrySync), which the client
+//! uses to [translate](registry) block IDs.
 //! * Server sends [`JoinGame`](packets::server::JoinGame). State switches to `Game`.
+//! * If the client's block registry digest matches the server's, server sends
+//! [`SetBlockDictionary`](packets::server::SetBlockDictionary).
 //! * Server sends local chunks, entities, etc. and continues sending these
-//! as the client moves.
+//! as the client moves. Clients may [cache](chunk_cache) chunks to
+//! avoid receiving them again.
+//! * Both peers periodically send [`Ping`](packets::shared::Ping), which the
+//! other answers with [`Pong`](packets::shared::Pong). See the [`keepalive`] module.
 //! * Either peer disconnects and sends [`Disconnect`](packets::shared::Disconnect)
 //! before doing so.
+//!
+//! A connection that dies without a `Disconnect` can be resumed: the client
+//! connects again and sends the session token from `JoinGame` in its
+//! `ClientInfo`. The server then sends `ServerInfo` and `JoinGame` as usual,
+//! followed by the packets it held back while the client was gone and
+//! the chunks in the player's view.
 
 /// Current protocol version. Increment when a new release is made
-/// with a change in the protocol.
+/// with a change in the protocol. Peers only connect if they
+/// implement the same version.
 pub const PROTOCOL_VERSION: u32 = 0;
 
 pub mod bridge;
+pub mod chunk_cache;
+pub mod dictionary;
+pub mod features;
+pub mod keepalive;
 pub mod packets;
+pub mod registry;
+pub mod resource_pack;
+pub mod stats;
+pub mod trust;
 
 #[doc(inline)]
 pub use bridge::Bridge;
@@ -3,8 +3,14 @@
 //! peer which sends them.
 
 pub mod client;
+pub mod kind;
 pub mod server;
 pub mod shared;
 
 #[doc(inline)]
-pub use self::{client::ClientPacket, server::ServerPacket, shared::SharedPacket};
+pub use self::{
+    client::ClientPacket,
+    kind::{Packet, PacketKind},
+    server::ServerPacket,
+    shared::SharedPacket,
+};
@@ -1,16 +1,30 @@
 //! Packets sent by the client.
 
-use glam::{Vec2, Vec3A};
+use common::{edit::Digging, BlockId, BlockPos, ChunkPos, WorldPos};
+use glam::Vec2;
 use serde::{Deserialize, Serialize};
 
 use super::shared::SharedPacket;
+use crate::features::Features;
 
 /// The union of all possible packets sent by the client.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ClientPacket {
     Shared(SharedPacket),
     ClientInfo(ClientInfo),
+    ResourcePackLoaded(ResourcePackLoaded),
     UpdatePosition(UpdatePosition),
+    DialogResponse(DialogResponse),
+    PlaceBlock(PlaceBlock),
+    BreakBlock(BreakBlock),
+    UseBlock(UseBlock),
+    DigProgress(DigProgress),
+    ChatMessage(ChatMessage),
+    Respawn(Respawn),
+    UpdateSettings(UpdateSettings),
+    SelectSlot(SelectSlot),
+    RequestChunks(RequestChunks),
+    Landed(Landed),
 }
 
 /// Login state: initial data sent by the client.
@@ -23,13 +37,142 @@ pub struct ClientInfo {
 
     /// The player's username.
     pub username: String,
+
+    /// The optional features the client supports.
+    pub features: Features,
+
+    /// The number of chunks the player would like to see in each
+    /// direction. The server may clamp it to its own limits.
+    pub view_distance: u32,
+
+    /// The client's [`registry_digest`](common::block::registry_digest).
+    /// Determines whether a shared block dictionary can be used.
+    pub registry_digest: u64,
+
+    /// The session token from the [`JoinGame`](super::server::JoinGame)
+    /// of a connection that was lost. If the server still holds that
+    /// player, the client resumes playing as them.
+    pub resume_token: Option<u64>,
 }
 
+/// Login state: the client has loaded the resource
+/// pack required in `ServerInfo` and is ready to join.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResourcePackLoaded;
+
 /// Updates the client's position on the server.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UpdatePosition {
     /// The new position.
-    pub new_pos: Vec3A,
+    pub new_pos: WorldPos,
     /// The new orientation.
     pub new_orient: Vec2,
 }
+
+/// Reports the player's response to a dialog
+/// opened with `OpenDialog`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DialogResponse {
+    /// The ID of the dialog.
+    pub dialog: u32,
+    /// The ID of the chosen button, or `None`
+    /// if the player dismissed the dialog.
+    pub button: Option<u32>,
+}
+
+/// Requests that a block be placed.
+///
+/// The server answers with `BlockUpdate`, containing either
+/// the placed block or the unchanged block if the placement
+/// was rejected.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlaceBlock {
+    /// The position to place the block at. Must contain air.
+    pub pos: BlockPos,
+    /// The block to place.
+    pub block: BlockId,
+}
+
+/// Requests that a block be broken, replacing it with air.
+///
+/// The server answers with `BlockUpdate` like for `PlaceBlock`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BreakBlock {
+    /// The position of the block to break.
+    pub pos: BlockPos,
+}
+
+/// Requests that a block be used, e.g. to open or close a door.
+///
+/// The server answers with `BlockUpdate` like for `PlaceBlock`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UseBlock {
+    /// The position of the block to use.
+    pub pos: BlockPos,
+}
+
+/// The player started or stopped digging a block,
+/// or the crack stage of the block they dig changed.
+///
+/// Sent only when it changes. The server shares it with
+/// other players with `EntityDigProgress`. Blocks are
+/// still broken with `BreakBlock`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DigProgress {
+    /// The block being dug, or `None` if the player stopped digging.
+    pub digging: Option<Digging>,
+}
+
+/// A chat message typed by the player.
+///
+/// The server broadcasts it to all players with `ChatMessage`.
+/// Messages longer than [`MAX_CHAT_MESSAGE_LENGTH`] characters
+/// are truncated.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub message: String,
+}
+
+/// The maximum number of characters in a chat message.
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 256;
+
+/// Asks to respawn after the server sent `PlayerDied`.
+///
+/// The server answers with `Respawn`. Ignored if the player is alive.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Respawn;
+
+/// The player changed settings that affect the server.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateSettings {
+    /// The new view distance, as in `ClientInfo`. The server
+    /// loads and unloads chunks to match.
+    pub view_distance: u32,
+}
+
+/// The player selected a slot of their hotbar.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SelectSlot {
+    /// The index of the slot, less than `HOTBAR_SIZE`.
+    pub slot: u32,
+}
+
+/// Asks for chunks the server sent with `LoadCachedChunk` that
+/// aren't in the client's [cache](crate::chunk_cache).
+///
+/// The server answers with `LoadChunk` for each chunk still in the
+/// player's view. Others are ignored.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequestChunks {
+    pub positions: Vec<ChunkPos>,
+}
+
+/// The player landed on the ground after falling.
+///
+/// The server hurts the player depending on the distance.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Landed {
+    /// The distance fallen in blocks, as tracked
+    /// by `physics::track_fall`.
+    pub distance: f32,
+}
@@ -0,0 +1,237 @@
+//! The registry of packet kinds.
+//!
+//! Each variant of the packet enums is a _kind_ of packet with a stable
+//! numeric ID, a name, and the [`Direction`] it flows in. The registry is
+//! generated from the lists of variants below, and the match mapping
+//! packets to their kinds is exhaustive, so adding a packet without
+//! registering it fails to compile. IDs are assigned in list order, shared
+//! packets first, so they only change when packets are added or removed.
+//!
+//! Kinds are used for diagnostics, such as the
+//! [traffic statistics](crate::stats) kept by each `Bridge`.
+
+use std::fmt::{self, Display};
+
+use super::{ClientPacket, ServerPacket, SharedPacket};
+
+/// The peer a kind of packet is sent to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// Sent by both peers.
+    Both,
+    /// Sent by the client.
+    ToServer,
+    /// Sent by the server.
+    ToClient,
+}
+
+/// A kind of packet.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PacketKind(u16);
+
+/// A packet enum whose variants are registered as kinds.
+pub trait Packet {
+    /// Returns the kind of this packet.
+    fn kind(&self) -> PacketKind;
+}
+
+/// The kinds of one packet enum, with consecutive IDs.
+struct Group {
+    first: u16,
+    names: &'static [&'static str],
+    direction: Direction,
+}
+
+impl Group {
+    const fn end(&self) -> u16 {
+        self.first + self.names.len() as u16
+    }
+}
+
+macro_rules! packet_kinds {
+    (
+        $module:ident: $packet:ident => $direction:ident,
+        first = $first:expr,
+        $(shared = $shared:ident,)?
+        [$($variant:ident),* $(,)?]
+    ) => {
+        mod $module {
+            use super::{Direction, Group};
+
+            pub(super) enum Index {
+                $($variant),*
+            }
+
+            pub(super) const GROUP: Group = Group {
+                first: $first,
+                names: &[$(stringify!($variant)),*],
+                direction: Direction::$direction,
+            };
+        }
+
+        impl Packet for $packet {
+            fn kind(&self) -> PacketKind {
+                match self {
+                    $($packet::$shared(packet) => packet.kind(),)?
+                    $($packet::$variant(_) => {
+                        PacketKind($module::GROUP.first + $module::Index::$variant as u16)
+                    })*
+                }
+            }
+        }
+    };
+}
+
+packet_kinds! {
+    shared: SharedPacket => Both,
+    first = 0,
+    [Disconnect, Ping, Pong]
+}
+
+packet_kinds! {
+    client: ClientPacket => ToServer,
+    first = shared::GROUP.end(),
+    shared = Shared,
+    [
+        ClientInfo,
+        ResourcePackLoaded,
+        UpdatePosition,
+        DialogResponse,
+        PlaceBlock,
+        BreakBlock,
+        UseBlock,
+        DigProgress,
+        ChatMessage,
+        Respawn,
+        UpdateSettings,
+        SelectSlot,
+        RequestChunks,
+        Landed,
+    ]
+}
+
+packet_kinds! {
+    server: ServerPacket => ToClient,
+    first = client::GROUP.end(),
+    shared = Shared,
+    [
+        WorldgenProgress,
+        ServerInfo,
+        RegistrySync,
+        JoinGame,
+        SetBlockDictionary,
+        LoadChunk,
+        LoadCachedChunk,
+        UnloadChunk,
+        BlockUpdate,
+        SpawnEntity,
+        SpawnFallingBlock,
+        MoveEntity,
+        EntityPosition,
+        Teleport,
+        DespawnEntity,
+        EntityDigProgress,
+        WeatherChange,
+        TimeUpdate,
+        MeteorShower,
+        SystemMessage,
+        ChatMessage,
+        UpdateXp,
+        UpdateHealth,
+        SetInventory,
+        PlayerDied,
+        Respawn,
+        SetGameMode,
+        UpdateGameRules,
+        TickRate,
+        ViewDistanceChanged,
+        OpenDialog,
+        CloseDialog,
+    ]
+}
+
+const GROUPS: [Group; 3] = [shared::GROUP, client::GROUP, server::GROUP];
+
+/// The number of packet kinds.
+pub const COUNT: usize = server::GROUP.end() as usize;
+
+impl PacketKind {
+    /// Returns all packet kinds in order of their IDs.
+    pub fn all() -> impl Iterator<Item = PacketKind> {
+        (0..COUNT as u16).map(PacketKind)
+    }
+
+    /// Returns the kind with the given ID, if any.
+    pub fn from_id(id: u16) -> Option<Self> {
+        if (id as usize) < COUNT {
+            Some(PacketKind(id))
+        } else {
+            None
+        }
+    }
+
+    pub fn id(self) -> u16 {
+        self.0
+    }
+
+    /// Returns the name of the packet, which is the name of its variant.
+    /// Names are only unique among kinds with the same direction.
+    pub fn name(self) -> &'static str {
+        let group = self.group();
+        group.names[(self.0 - group.first) as usize]
+    }
+
+    pub fn direction(self) -> Direction {
+        self.group().direction
+    }
+
+    fn group(self) -> &'static Group {
+        GROUPS
+            .iter()
+            .find(|group| self.0 < group.end())
+            .expect("packet kinds are always registered")
+    }
+}
+
+impl Display for PacketKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::packets::{client::Landed, server::TickRate, shared::Ping};
+
+    use super::*;
+
+    #[test]
+    fn kinds_have_names_and_directions() {
+        let ping = ServerPacket::Shared(SharedPacket::Ping(Ping { id: 5 })).kind();
+        assert_eq!(ping.name(), "Ping");
+        assert_eq!(ping.direction(), Direction::Both);
+        assert_eq!(
+            ping,
+            ClientPacket::Shared(SharedPacket::Ping(Ping { id: 5 })).kind()
+        );
+
+        let landed = ClientPacket::Landed(Landed { distance: 20. }).kind();
+        assert_eq!(landed.name(), "Landed");
+        assert_eq!(landed.direction(), Direction::ToServer);
+
+        let tick_rate = ServerPacket::TickRate(TickRate { tick_length: 0.05 }).kind();
+        assert_eq!(tick_rate.name(), "TickRate");
+        assert_eq!(tick_rate.direction(), Direction::ToClient);
+    }
+
+    #[test]
+    fn ids_are_unique_and_dense() {
+        let kinds: Vec<PacketKind> = PacketKind::all().collect();
+        assert_eq!(kinds.len(), COUNT);
+        for (i, kind) in kinds.iter().enumerate() {
+            assert_eq!(kind.id() as usize, i);
+            assert_eq!(PacketKind::from_id(kind.id()), Some(*kind));
+        }
+        assert_eq!(PacketKind::from_id(COUNT as u16), None);
+    }
+}
@@ -1,22 +1,80 @@
 //! Packets sent by the server.
 
-use common::{Chunk, ChunkPos};
+use common::{
+    biome::ColumnBiomes,
+    edit::Digging,
+    entity::player::GameMode,
+    game_rules::{GameRule, RuleValue},
+    item::ItemStack,
+    weather::Weather,
+    BlockId, BlockPos, ChunkPos, WorldPos,
+};
 use derivative::Derivative;
 use glam::{Vec2, Vec3A};
 use serde::{Deserialize, Serialize};
 
 use super::shared::SharedPacket;
+use crate::{
+    dictionary::{BlockDictionary, ChunkData},
+    features::Features,
+    registry::BlockKind,
+    resource_pack::ResourcePack,
+};
 
 /// The union of all possible packets sent by the server.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ServerPacket {
     Shared(SharedPacket),
 
+    WorldgenProgress(WorldgenProgress),
     ServerInfo(ServerInfo),
+    RegistrySync(RegistrySync),
     JoinGame(JoinGame),
+    SetBlockDictionary(SetBlockDictionary),
 
     LoadChunk(LoadChunk),
+    LoadCachedChunk(LoadCachedChunk),
     UnloadChunk(UnloadChunk),
+    BlockUpdate(BlockUpdate),
+
+    SpawnEntity(SpawnEntity),
+    SpawnFallingBlock(SpawnFallingBlock),
+    MoveEntity(MoveEntity),
+    EntityPosition(EntityPosition),
+    Teleport(Teleport),
+    DespawnEntity(DespawnEntity),
+    EntityDigProgress(EntityDigProgress),
+
+    WeatherChange(WeatherChange),
+    TimeUpdate(TimeUpdate),
+    MeteorShower(MeteorShower),
+    SystemMessage(SystemMessage),
+    ChatMessage(ChatMessage),
+    UpdateXp(UpdateXp),
+    UpdateHealth(UpdateHealth),
+    SetInventory(SetInventory),
+    PlayerDied(PlayerDied),
+    Respawn(Respawn),
+    SetGameMode(SetGameMode),
+    UpdateGameRules(UpdateGameRules),
+    TickRate(TickRate),
+    ViewDistanceChanged(ViewDistanceChanged),
+
+    OpenDialog(OpenDialog),
+    CloseDialog(CloseDialog),
+}
+
+/// Login phase: progress of world generation.
+///
+/// Sent any number of times before `ServerInfo` while the
+/// server generates the world, so the client can display
+/// a loading screen.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorldgenProgress {
+    /// A description of the current stage of generation.
+    pub stage: String,
+    /// Progress of world generation, from 0 to 100.
+    pub percent: f32,
 }
 
 /// Login phase: the server's properties.
@@ -26,6 +84,26 @@ pub struct ServerInfo {
     pub protocol_version: u32,
     /// An arbitrary name for the server.
     pub implementation: String,
+    /// The optional features enabled for this connection,
+    /// a subset of those listed in `ClientInfo`.
+    pub features: Features,
+    /// Identifies the world, so that clients keep the
+    /// [chunks they cache](crate::chunk_cache) apart.
+    pub world_id: u64,
+    /// A resource pack the client must load before joining. If
+    /// present, the server waits for `ResourcePackLoaded` before
+    /// sending `JoinGame`.
+    pub resource_pack: Option<ResourcePack>,
+}
+
+/// Login phase: the server's block registry.
+///
+/// Sent before `JoinGame` if the client's registry digest differs
+/// from the server's. See the [`registry`](crate::registry) module.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RegistrySync {
+    /// The server's block kinds, in kind order.
+    pub blocks: Vec<BlockKind>,
 }
 
 /// Login phase: the player's initial state. Switches
@@ -33,11 +111,24 @@ pub struct ServerInfo {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JoinGame {
     /// The player's initial position.
-    pub pos: Vec3A,
+    pub pos: WorldPos,
     /// The player's initial orientation.
     pub orient: Vec2,
     /// The player's initial velocity.
     pub vel: Vec3A,
+    /// Identifies the player's session, so that a client which
+    /// loses its connection can resume it. Must be kept secret.
+    pub session_token: u64,
+}
+
+/// Sets the block dictionary used to decode chunk
+/// palettes for the rest of the connection.
+///
+/// Sent after `JoinGame` if the client's registry digest matches
+/// the server's. Otherwise, chunks are always sent with full palettes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetBlockDictionary {
+    pub dictionary: BlockDictionary,
 }
 
 /// Loads a chunk on the client.
@@ -50,9 +141,28 @@ pub struct JoinGame {
 pub struct LoadChunk {
     /// The position of the chunk.
     pub pos: ChunkPos,
-    /// The chunk.
+    /// The chunk, possibly encoded with the block dictionary.
     #[derivative(Debug = "ignore")]
-    pub chunk: Chunk,
+    pub chunk: ChunkData,
+    /// The biomes of the chunk's column.
+    #[derivative(Debug = "ignore")]
+    pub biomes: ColumnBiomes,
+}
+
+/// Loads a chunk from the client's [cache](crate::chunk_cache).
+///
+/// Sent instead of `LoadChunk` if the chunk cache feature is enabled.
+/// If the client has no cached chunk with this hash, it asks for
+/// the chunk with `RequestChunks`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoadCachedChunk {
+    /// The position of the chunk.
+    pub pos: ChunkPos,
+    /// The [hash](crate::chunk_cache::chunk_hash) of the chunk.
+    pub hash: u64,
+    /// The biomes of the chunk's column. Sent here rather
+    /// than cached, since they aren't part of the hash.
+    pub biomes: ColumnBiomes,
 }
 
 /// Unloads a chunk on the client.
@@ -63,3 +173,292 @@ pub struct UnloadChunk {
     /// The position of the chunk to unload.
     pub pos: ChunkPos,
 }
+
+/// Sets a single block on the client.
+///
+/// Sent to each player whose view contains the block's chunk
+/// whenever the block changes, including in response to the
+/// player's own `PlaceBlock` or `BreakBlock`. If the server rejects
+/// an edit, it sends the unchanged block to the player who made it.
+///
+/// Does nothing if the chunk is not loaded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlockUpdate {
+    /// The position of the block.
+    pub pos: BlockPos,
+    /// The new block.
+    pub block: BlockId,
+}
+
+/// Spawns an entity other than a falling block,
+/// such as another player.
+///
+/// Sent to each player when the entity enters their view.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpawnEntity {
+    /// The server's ID for the entity, used by
+    /// `EntityPosition`, `MoveEntity`, and `DespawnEntity`.
+    pub entity: u64,
+    /// The position of the entity's feet.
+    pub pos: WorldPos,
+    pub orient: Vec2,
+    pub kind: EntityKind,
+}
+
+/// The kind of an entity spawned with `SpawnEntity`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum EntityKind {
+    Player {
+        username: String,
+    },
+    /// An experience orb. Moved with `MoveEntity`.
+    XpOrb {
+        value: u32,
+    },
+    /// A dropped stack of items. Moved with `MoveEntity`.
+    ItemDrop {
+        stack: ItemStack,
+    },
+}
+
+/// Spawns a falling block entity, such as sand
+/// whose support was removed.
+///
+/// Sent to each player whose view contains the entity.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpawnFallingBlock {
+    /// The server's ID for the entity, used by
+    /// `MoveEntity` and `DespawnEntity`.
+    pub entity: u64,
+    /// The position of the center of the block's bottom face.
+    pub pos: WorldPos,
+    /// The falling block.
+    pub block: BlockId,
+}
+
+/// Moves an entity.
+///
+/// Sent each tick the entity moves to each player whose
+/// view contains it. Does nothing if the entity is unknown.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoveEntity {
+    pub entity: u64,
+    pub pos: WorldPos,
+}
+
+/// Moves and rotates an entity spawned with `SpawnEntity`.
+///
+/// Sent each tick the entity moves or turns, and on the tick it
+/// stops, to each player whose view contains it. Does nothing if
+/// the entity is unknown.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EntityPosition {
+    pub entity: u64,
+    pub pos: WorldPos,
+    pub orient: Vec2,
+}
+
+/// Moves the player to `pos` and stops them.
+///
+/// The client owns its player's position, so this is the only way
+/// for the server to move the player, e.g. with the `tp` command.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Teleport {
+    pub pos: WorldPos,
+}
+
+/// Removes an entity.
+///
+/// Sent when an entity is destroyed or leaves a player's view.
+/// May be sent for entities the client does not know
+/// about, which it should ignore.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DespawnEntity {
+    pub entity: u64,
+}
+
+/// Another player started or stopped digging a block,
+/// or the crack stage of the block they dig changed.
+///
+/// Sent to each player whose view contains the block, as reported
+/// by the digger with `DigProgress`. Does nothing if the entity
+/// is unknown.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EntityDigProgress {
+    pub entity: u64,
+    /// The block being dug, or `None` if the player stopped digging.
+    pub digging: Option<Digging>,
+}
+
+/// Sets the weather.
+///
+/// Sent to all players when the weather changes
+/// and to each player when they join.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeatherChange {
+    pub weather: Weather,
+}
+
+/// Sets the world's time, in seconds of game time.
+///
+/// Sent to each player when they join and periodically
+/// afterwards, as well as to all players when the time is set or
+/// changes speed. Clients advance the time themselves between updates.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimeUpdate {
+    pub time: f64,
+    /// The seconds of game time that pass per real second.
+    /// Zero while the daylight cycle is paused.
+    pub rate: f32,
+}
+
+/// Starts or stops a meteor shower, which is purely visual.
+///
+/// Sent to all players when a shower starts or
+/// stops and to each player when they join.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MeteorShower {
+    pub active: bool,
+}
+
+/// A message from the server to display to the player.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SystemMessage {
+    pub message: String,
+}
+
+/// A chat message sent by a player.
+///
+/// Sent to all players, including the sender.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    /// The username of the player who sent the message.
+    pub sender: String,
+    pub message: String,
+}
+
+/// Sets the player's total experience points.
+///
+/// Sent when the player joins and whenever they collect an orb.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateXp {
+    pub total: u32,
+}
+
+/// Sets the player's health.
+///
+/// Sent when the player joins, respawns, or is hurt.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateHealth {
+    pub health: u32,
+    pub max_health: u32,
+}
+
+/// Sets the contents of the player's inventory
+/// and the selected hotbar slot.
+///
+/// Sent when the player joins and whenever the inventory changes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetInventory {
+    /// The stack in each slot, `INVENTORY_SIZE` in all.
+    pub slots: Vec<Option<ItemStack>>,
+    pub selected: u32,
+}
+
+/// The player died.
+///
+/// The player can't move or edit blocks until they ask to
+/// respawn with the client's `Respawn` and the server answers
+/// with `Respawn`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlayerDied {
+    /// Describes how the player died, e.g. "Killed by an operator".
+    pub cause: String,
+}
+
+/// The player respawned at `pos` after dying. Also stops the player.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Respawn {
+    pub pos: WorldPos,
+}
+
+/// Sets the player's game mode and how far they can reach
+/// to edit blocks, as checked by `common::edit::is_in_reach`.
+///
+/// Sent when the player joins and whenever their game mode changes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetGameMode {
+    pub mode: GameMode,
+    /// The player's reach in blocks.
+    pub reach: f32,
+}
+
+/// Sets the values of the game rules which clients use to
+/// predict the game, i.e. those for which `GameRule::is_synced`
+/// returns true. Rules not included keep their values.
+///
+/// Sent when the player joins and whenever a synced rule changes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpdateGameRules {
+    pub rules: Vec<(GameRule, RuleValue)>,
+}
+
+/// Sets the real time between server ticks, which changes
+/// when the tick rate is configured or in slow motion.
+/// Clients interpolate entity movement over this time.
+///
+/// Sent to all players when it changes
+/// and to each player when they join.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TickRate {
+    /// The number of seconds between ticks.
+    pub tick_length: f32,
+}
+
+/// Sets the view distance the server sends the player chunks within.
+///
+/// This is the distance the client asked for, clamped to the server's
+/// limits. The server lowers its limit temporarily while it is under
+/// load, so the distance can change without the client asking.
+///
+/// Sent to each player when they join and whenever it changes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ViewDistanceChanged {
+    /// The view distance in chunks.
+    pub view_distance: u32,
+}
+
+/// Opens a modal dialog on the client.
+///
+/// The client responds with `DialogResponse` when
+/// the player chooses a button or dismisses the dialog.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenDialog {
+    /// The ID of this dialog, unique for the connection.
+    pub id: u32,
+    /// The title displayed at the top of the dialog.
+    pub title: String,
+    /// The body text of the dialog.
+    pub text: String,
+    /// The buttons the player can choose from, in display order.
+    pub buttons: Vec<DialogButton>,
+}
+
+/// A button in a dialog.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DialogButton {
+    /// The ID reported back to the server if
+    /// this button is chosen.
+    pub id: u32,
+    /// The button's text.
+    pub label: String,
+}
+
+/// Closes a dialog on the client without a response.
+///
+/// Does nothing if the dialog is not open.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CloseDialog {
+    /// The ID of the dialog to close.
+    pub id: u32,
+}
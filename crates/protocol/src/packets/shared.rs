@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Serialize, Deserialize)]
 pub enum SharedPacket {
     Disconnect(Disconnect),
+    Ping(Ping),
+    Pong(Pong),
 }
 
 /// Informs the peer that the connection is terminated.
@@ -14,3 +16,20 @@ pub struct Disconnect {
     /// An optional reason for the disconnect.
     pub reason: Option<String>,
 }
+
+/// Checks that the peer is still responding. The peer
+/// answers with a `Pong` carrying the same ID.
+///
+/// Sent periodically by both peers in the game state.
+/// See the [`keepalive`](crate::keepalive) module.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Ping {
+    pub id: u64,
+}
+
+/// Answers a `Ping`.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Pong {
+    /// The ID of the ping.
+    pub id: u64,
+}
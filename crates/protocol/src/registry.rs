@@ -0,0 +1,237 @@
+//! Translating block IDs between peers with different block registries.
+//!
+//! A [`BlockId`] is an index into the block registry, so peers whose
+//! registries list different blocks, or the same blocks in another order,
+//! disagree on what an ID means. If the client's registry digest differs
+//! from its own, the server sends its registry in
+//! [`RegistrySync`](crate::packets::server::RegistrySync) before
+//! `JoinGame`: the slug and number of states of each block kind, in kind
+//! order. The client builds a [`RegistryMap`] from it, matching kinds by
+//! slug, and translates block IDs received from and sent to the server.
+//!
+//! The client refuses to join if the server has a block it doesn't know
+//! at all. Blocks only the client knows are fine, since the server never
+//! sends them. If a kind has fewer states locally than on the server,
+//! the extra states become the kind's
+//! [default state](common::block::default_state).
+//!
+//! Biome IDs are fixed by the world generator, and item IDs aren't
+//! translated yet, so the block registry is the only one synchronized.
+//! Peers with different item registries will disagree about inventories.
+
+use std::fmt;
+
+use common::{block, BlockId, Chunk};
+use serde::{Deserialize, Serialize};
+
+/// A block kind as listed in `RegistrySync`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockKind {
+    pub slug: String,
+    pub num_states: u32,
+}
+
+/// Lists the kinds of our block registry, in kind order.
+pub fn local_blocks() -> Vec<BlockKind> {
+    block::default_states()
+        .map(|block| BlockKind {
+            slug: block.descriptor().slug().to_owned(),
+            num_states: block::num_states(block.kind()).expect("registered"),
+        })
+        .collect()
+}
+
+/// Returned when the server has blocks we don't know.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownBlocks(pub Vec<String>);
+
+impl fmt::Display for UnknownBlocks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown blocks: {}", self.0.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownBlocks {}
+
+/// Translates block IDs between the server's registry and ours.
+#[derive(Debug, Clone)]
+pub struct RegistryMap {
+    /// Our kind and the server's number of states for each server kind.
+    to_local: Vec<(u32, u32)>,
+    /// The server's kind for each of our kinds, if it has it.
+    to_remote: Vec<Option<u32>>,
+    identity: bool,
+}
+
+impl RegistryMap {
+    /// Creates a map for a server whose registry equals ours.
+    pub fn identity() -> Self {
+        Self::new(&local_blocks()).expect("our own blocks are known")
+    }
+
+    /// Creates a map from the server's block kinds. Fails if
+    /// any of them is missing from our registry.
+    pub fn new(remote: &[BlockKind]) -> Result<Self, UnknownBlocks> {
+        let num_local_kinds = block::default_states().count();
+        let mut to_local = Vec::with_capacity(remote.len());
+        let mut to_remote = vec![None; num_local_kinds];
+        let mut unknown = Vec::new();
+        for (remote_kind, kind) in remote.iter().enumerate() {
+            let local_kind = match block::kind_by_slug(&kind.slug) {
+                Some(local_kind) => local_kind,
+                None => {
+                    unknown.push(kind.slug.clone());
+                    continue;
+                }
+            };
+            let local_states = block::num_states(local_kind).expect("registered");
+            if local_states != kind.num_states {
+                log::warn!(
+                    "Block '{}' has {} states, but the server's has {}",
+                    kind.slug,
+                    local_states,
+                    kind.num_states
+                );
+            }
+            to_local.push((local_kind, kind.num_states));
+            to_remote[local_kind as usize] = Some(remote_kind as u32);
+        }
+        if !unknown.is_empty() {
+            return Err(UnknownBlocks(unknown));
+        }
+
+        let identity = remote.len() == num_local_kinds
+            && to_local
+                .iter()
+                .enumerate()
+                .all(|(remote_kind, &(local_kind, num_states))| {
+                    remote_kind as u32 == local_kind
+                        && block::num_states(local_kind) == Some(num_states)
+                });
+        Ok(Self {
+            to_local,
+            to_remote,
+            identity,
+        })
+    }
+
+    /// Returns whether block IDs are the same for both peers.
+    pub fn is_identity(&self) -> bool {
+        self.identity
+    }
+
+    /// Translates a block ID received from the server. Returns
+    /// `None` if it is invalid in the server's registry.
+    pub fn to_local(&self, block: BlockId) -> Option<BlockId> {
+        let &(kind, num_states) = self.to_local.get(block.kind() as usize)?;
+        if block.state() >= num_states {
+            return None;
+        }
+        let local = BlockId::from_raw_parts(kind, block.state());
+        if local.is_valid() {
+            Some(local)
+        } else {
+            block::default_state(kind)
+        }
+    }
+
+    /// Translates a block ID to send to the server. Returns
+    /// `None` if the server doesn't have the block.
+    pub fn to_remote(&self, block: BlockId) -> Option<BlockId> {
+        let kind = (*self.to_remote.get(block.kind() as usize)?)?;
+        let num_states = self.to_local[kind as usize].1;
+        if block.state() < num_states {
+            Some(BlockId::from_raw_parts(kind, block.state()))
+        } else {
+            None
+        }
+    }
+
+    /// Translates the palette of a chunk received from the server.
+    /// Returns `None` if the palette contains invalid blocks.
+    pub fn chunk_to_local(&self, chunk: Chunk) -> Option<Chunk> {
+        if self.identity {
+            return Some(chunk);
+        }
+        let (indexes, palette) = chunk.into_parts();
+        let palette = palette
+            .into_iter()
+            .map(|block| self.to_local(block))
+            .collect::<Option<_>>()?;
+        Chunk::from_parts(indexes, palette)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::blocks;
+
+    use super::*;
+
+    #[test]
+    fn identical_registries() {
+        let map = RegistryMap::identity();
+        assert!(map.is_identity());
+        let stone = BlockId::new(blocks::Stone);
+        assert_eq!(map.to_local(stone), Some(stone));
+        assert_eq!(map.to_remote(stone), Some(stone));
+    }
+
+    #[test]
+    fn remaps_reordered_registries() {
+        // The server lacks dirt and lists the remaining blocks in reverse.
+        let dirt = BlockId::new(blocks::Dirt);
+        let mut remote = local_blocks();
+        remote.retain(|kind| kind.slug != dirt.descriptor().slug());
+        remote.reverse();
+        let map = RegistryMap::new(&remote).unwrap();
+        assert!(!map.is_identity());
+
+        let door = BlockId::new(blocks::Door {
+            open: true,
+            upper: true,
+            powered: false,
+        });
+        let remote_door = map.to_remote(door).unwrap();
+        assert_ne!(remote_door.kind(), door.kind());
+        assert_eq!(remote_door.state(), door.state());
+        assert_eq!(map.to_local(remote_door), Some(door));
+        assert_eq!(map.to_remote(dirt), None);
+
+        let invalid = BlockId::from_raw_parts(remote_door.kind(), u32::MAX);
+        assert_eq!(map.to_local(invalid), None);
+        let invalid = BlockId::from_raw_parts(remote.len() as u32, 0);
+        assert_eq!(map.to_local(invalid), None);
+    }
+
+    #[test]
+    fn extra_states_become_the_default() {
+        let door = BlockId::new(blocks::Door {
+            open: false,
+            upper: false,
+            powered: false,
+        });
+        let mut remote = local_blocks();
+        remote[door.kind() as usize].num_states *= 2;
+        let map = RegistryMap::new(&remote).unwrap();
+        assert!(!map.is_identity());
+
+        let num_states = block::num_states(door.kind()).unwrap();
+        let extra = BlockId::from_raw_parts(door.kind(), num_states);
+        assert_eq!(map.to_local(extra), block::default_state(door.kind()));
+        assert_eq!(block::default_state(door.kind()), Some(door));
+    }
+
+    #[test]
+    fn rejects_unknown_blocks() {
+        let mut remote = local_blocks();
+        remote.push(BlockKind {
+            slug: "marble".to_owned(),
+            num_states: 1,
+        });
+        assert_eq!(
+            RegistryMap::new(&remote).unwrap_err(),
+            UnknownBlocks(vec!["marble".to_owned()])
+        );
+    }
+}
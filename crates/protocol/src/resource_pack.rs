@@ -0,0 +1,68 @@
+//! Resource packs required by servers.
+//!
+//! A server may ship custom textures and models by requiring a resource
+//! pack: a tar archive laid out like the client's asset directory. It
+//! advertises the archive's URL and SHA-256 hash in `ServerInfo`. The
+//! client downloads the archive, rejects it unless the hash matches, and
+//! loads it over its own assets before answering with `ResourcePackLoaded`.
+//! The hash also names the pack in the client's cache, so each pack is
+//! only downloaded once.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A resource pack that clients must load to join.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourcePack {
+    /// Where to download the archive. Either an `http://`,
+    /// `https://`, or `file://` URL.
+    pub url: String,
+    /// The hash of the archive.
+    pub hash: PackHash,
+}
+
+/// The SHA-256 hash of a resource pack archive.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PackHash([u8; 32]);
+
+impl PackHash {
+    /// Hashes the archive `data`.
+    pub fn of(data: &[u8]) -> Self {
+        let mut hash = [0; 32];
+        hash.copy_from_slice(&Sha256::digest(data));
+        Self(hash)
+    }
+
+    /// Formats the hash as lowercase hexadecimal.
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+impl fmt::Debug for PackHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PackHash({})", self.to_hex())
+    }
+}
+
+impl fmt::Display for PackHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_archives() {
+        assert_eq!(
+            PackHash::of(b"abc").to_hex(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_ne!(PackHash::of(b"abc"), PackHash::of(b"abd"));
+    }
+}
@@ -0,0 +1,114 @@
+//! Per-kind packet traffic statistics.
+//!
+//! Every [`Bridge`](crate::Bridge) counts the packets it sends and
+//! receives by [kind](crate::packets::kind) and remembers when it last saw
+//! each kind, so developers can see exactly what traffic flows while
+//! reproducing a bug. Clones of a bridge share its statistics.
+
+use std::{cmp::Reverse, sync::Mutex};
+
+use instant::Instant;
+
+use crate::packets::{kind, PacketKind};
+
+/// The traffic of one kind of packet.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KindStats {
+    pub sent: u64,
+    pub received: u64,
+    pub last_sent: Option<Instant>,
+    pub last_received: Option<Instant>,
+}
+
+impl KindStats {
+    /// Returns when a packet of this kind was last sent or received.
+    pub fn last_seen(&self) -> Option<Instant> {
+        match (self.last_sent, self.last_received) {
+            (Some(sent), Some(received)) => Some(sent.max(received)),
+            (sent, received) => sent.or(received),
+        }
+    }
+}
+
+/// The traffic of a bridge.
+#[derive(Debug)]
+pub struct PacketStats {
+    kinds: Mutex<Vec<KindStats>>,
+}
+
+impl Default for PacketStats {
+    fn default() -> Self {
+        Self {
+            kinds: Mutex::new(vec![KindStats::default(); kind::COUNT]),
+        }
+    }
+}
+
+impl PacketStats {
+    pub(crate) fn record_sent(&self, kind: PacketKind) {
+        let mut kinds = self.kinds.lock().unwrap();
+        let stats = &mut kinds[kind.id() as usize];
+        stats.sent += 1;
+        stats.last_sent = Some(Instant::now());
+    }
+
+    pub(crate) fn record_received(&self, kind: PacketKind) {
+        let mut kinds = self.kinds.lock().unwrap();
+        let stats = &mut kinds[kind.id() as usize];
+        stats.received += 1;
+        stats.last_received = Some(Instant::now());
+    }
+
+    /// Returns the traffic of one kind of packet.
+    pub fn get(&self, kind: PacketKind) -> KindStats {
+        self.kinds.lock().unwrap()[kind.id() as usize]
+    }
+
+    /// Returns the traffic of every kind of packet that was sent or
+    /// received at least once, most recently seen first.
+    pub fn seen(&self) -> Vec<(PacketKind, KindStats)> {
+        let kinds = self.kinds.lock().unwrap();
+        let mut seen: Vec<_> = PacketKind::all()
+            .zip(kinds.iter().copied())
+            .filter(|(_, stats)| stats.last_seen().is_some())
+            .collect();
+        seen.sort_by_key(|(_, stats)| Reverse(stats.last_seen()));
+        seen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::packets::{
+        client::Landed,
+        shared::{Ping, SharedPacket},
+        ClientPacket, Packet,
+    };
+
+    use super::*;
+
+    #[test]
+    fn counts_packets_by_kind() {
+        let stats = PacketStats::default();
+        let ping = SharedPacket::Ping(Ping { id: 0 }).kind();
+        let landed = ClientPacket::Landed(Landed { distance: 15. }).kind();
+        assert!(stats.seen().is_empty());
+
+        stats.record_sent(ping);
+        stats.record_sent(ping);
+        stats.record_received(ping);
+        thread::sleep(Duration::from_millis(1));
+        stats.record_sent(landed);
+
+        let ping_stats = stats.get(ping);
+        assert_eq!((ping_stats.sent, ping_stats.received), (2, 1));
+        assert!(ping_stats.last_received.is_some());
+        assert_eq!(stats.get(landed).sent, 1);
+        assert!(stats.get(landed).last_received.is_none());
+
+        let seen: Vec<PacketKind> = stats.seen().into_iter().map(|(kind, _)| kind).collect();
+        assert_eq!(seen, vec![landed, ping]);
+    }
+}
@@ -0,0 +1,233 @@
+//! Pinned fingerprints of self-signed server certificates.
+//!
+//! [`KnownServers`] records the [`Fingerprint`] of the certificate a server
+//! presented the first time it was seen, so that later certificates can be
+//! checked against it. Nothing checks certificates with it yet: there is no
+//! network transport, only the in-memory singleplayer bridge.
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs, io,
+    path::Path,
+    str::FromStr,
+};
+
+/// The SHA-256 digest of a DER-encoded certificate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Fingerprint([u8; 32]);
+
+impl Fingerprint {
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+/// Formats as colon-separated uppercase hex, e.g. `AB:CD:...`,
+/// for display to the player.
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i != 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses hex with or without colon separators.
+impl FromStr for Fingerprint {
+    type Err = ParseFingerprintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits: Vec<u8> = s.bytes().filter(|&b| b != b':').collect();
+        if digits.len() != 64 {
+            return Err(ParseFingerprintError);
+        }
+
+        let mut bytes = [0; 32];
+        for (byte, pair) in bytes.iter_mut().zip(digits.chunks_exact(2)) {
+            let pair = std::str::from_utf8(pair).map_err(|_| ParseFingerprintError)?;
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| ParseFingerprintError)?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseFingerprintError;
+
+impl Display for ParseFingerprintError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("malformed certificate fingerprint")
+    }
+}
+
+impl Error for ParseFingerprintError {}
+
+/// The result of checking a certificate against [`KnownServers`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrustDecision {
+    /// The certificate matches the pinned fingerprint.
+    Trusted,
+    /// No certificate is pinned for the server. The player
+    /// should be shown the fingerprint and asked to confirm it.
+    Unknown,
+    /// A different certificate is pinned for the server. The connection
+    /// must be aborted: either the server changed its certificate or the
+    /// connection is being intercepted.
+    Mismatch { pinned: Fingerprint },
+}
+
+/// Pinned certificate fingerprints, keyed by server address.
+///
+/// Stored as a text file with one `address fingerprint` pair per line.
+/// Empty lines and lines starting with `#` are ignored.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct KnownServers {
+    pins: BTreeMap<String, Fingerprint>,
+}
+
+impl KnownServers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads known servers from a file. Returns an empty
+    /// set of known servers if the file does not exist.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let contents = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(e),
+        };
+        contents
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Saves known servers to a file, replacing its contents.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Checks a certificate presented by the server at `address`.
+    pub fn check(&self, address: &str, fingerprint: Fingerprint) -> TrustDecision {
+        match self.pins.get(address) {
+            Some(&pinned) if pinned == fingerprint => TrustDecision::Trusted,
+            Some(&pinned) => TrustDecision::Mismatch { pinned },
+            None => TrustDecision::Unknown,
+        }
+    }
+
+    /// Pins a fingerprint for a server, returning the
+    /// previously pinned fingerprint.
+    pub fn pin(
+        &mut self,
+        address: impl Into<String>,
+        fingerprint: Fingerprint,
+    ) -> Option<Fingerprint> {
+        self.pins.insert(address.into(), fingerprint)
+    }
+
+    /// Removes the pinned fingerprint for a server, allowing
+    /// it to present a new certificate.
+    pub fn unpin(&mut self, address: &str) -> Option<Fingerprint> {
+        self.pins.remove(address)
+    }
+
+    /// Iterates over pinned servers and their fingerprints.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Fingerprint)> + '_ {
+        self.pins
+            .iter()
+            .map(|(address, &fingerprint)| (address.as_str(), fingerprint))
+    }
+}
+
+impl Display for KnownServers {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (address, fingerprint) in self.iter() {
+            writeln!(f, "{} {}", address, fingerprint)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for KnownServers {
+    type Err = ParseFingerprintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut known = Self::new();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let address = parts.next().ok_or(ParseFingerprintError)?;
+            let fingerprint = parts.next().ok_or(ParseFingerprintError)?.parse()?;
+            if parts.next().is_some() {
+                return Err(ParseFingerprintError);
+            }
+            known.pin(address, fingerprint);
+        }
+        Ok(known)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint(byte: u8) -> Fingerprint {
+        Fingerprint::from_bytes([byte; 32])
+    }
+
+    #[test]
+    fn fingerprint_roundtrip() {
+        let fingerprint = Fingerprint::from_bytes([0xAB; 32]);
+        let s = fingerprint.to_string();
+        assert!(s.starts_with("AB:AB:"));
+        assert_eq!(s.parse::<Fingerprint>().unwrap(), fingerprint);
+        assert_eq!(
+            s.replace(':', "").parse::<Fingerprint>().unwrap(),
+            fingerprint
+        );
+        assert!("AB:CD".parse::<Fingerprint>().is_err());
+    }
+
+    #[test]
+    fn trust_on_first_use() {
+        let mut known = KnownServers::new();
+        assert_eq!(
+            known.check("example.com:4433", fingerprint(1)),
+            TrustDecision::Unknown
+        );
+        known.pin("example.com:4433", fingerprint(1));
+        assert_eq!(
+            known.check("example.com:4433", fingerprint(1)),
+            TrustDecision::Trusted
+        );
+        assert_eq!(
+            known.check("example.com:4433", fingerprint(2)),
+            TrustDecision::Mismatch {
+                pinned: fingerprint(1)
+            }
+        );
+    }
+
+    #[test]
+    fn known_servers_roundtrip() {
+        let mut known = KnownServers::new();
+        known.pin("a:1", fingerprint(1));
+        known.pin("b:2", fingerprint(2));
+        let parsed: KnownServers = format!("# comment\n\n{}", known).parse().unwrap();
+        assert_eq!(parsed, known);
+    }
+}
@@ -0,0 +1,390 @@
+//! Backups of the world save.
+//!
+//! A backup is a copy of the [save](crate::save) directory in the
+//! save's backup directory, e.g. `world-backups/backup-1612345678` for
+//! a save in `world`, named after the Unix time it was taken. Files
+//! which the server only ever replaces, such as region files, are
+//! hard-linked into the backup, so a backup takes little time and
+//! space. The history logs, which are appended to, are copied.
+//!
+//! Backups are taken on the world save thread right after a save, so
+//! they never contain half-written regions. They are taken every
+//! `VOLTZ_BACKUP_INTERVAL_MINS` minutes of game time if that variable
+//! is set, and by the `backup now` console [command](crate::command).
+//! Only the newest `VOLTZ_BACKUP_KEEP` backups are kept, [`DEFAULT_KEEP`]
+//! by default. `backup list` lists them.
+//!
+//! To restore a backup, stop the server and replace
+//! the save directory with a copy of the backup.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::{bail, Context};
+
+use crate::{
+    command::{self, Command, CommandRegistry},
+    event::BackupRequested,
+    game::Game,
+    history,
+    save::WorldSave,
+};
+
+/// The number of backups kept unless `VOLTZ_BACKUP_KEEP` is set.
+pub const DEFAULT_KEEP: usize = 5;
+/// The prefix of backup directory names.
+const PREFIX: &str = "backup-";
+/// The suffix of backups that are still being written.
+const PARTIAL_SUFFIX: &str = ".partial";
+
+/// When backups are taken and how many are kept.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BackupSettings {
+    /// The game seconds between periodic backups. `None`
+    /// if backups are only taken on request.
+    pub interval: Option<u64>,
+    /// The number of backups kept. Older ones are deleted.
+    pub keep: usize,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            interval: None,
+            keep: DEFAULT_KEEP,
+        }
+    }
+}
+
+impl BackupSettings {
+    /// Reads the settings from `VOLTZ_BACKUP_INTERVAL_MINS`
+    /// and `VOLTZ_BACKUP_KEEP`.
+    pub fn from_env() -> Self {
+        let mut settings = Self::default();
+        if let Ok(minutes) = env::var("VOLTZ_BACKUP_INTERVAL_MINS") {
+            match minutes.parse::<u64>() {
+                Ok(minutes) if minutes > 0 => settings.interval = Some(minutes * 60),
+                _ => log::warn!("Ignoring invalid VOLTZ_BACKUP_INTERVAL_MINS '{}'", minutes),
+            }
+        }
+        if let Ok(keep) = env::var("VOLTZ_BACKUP_KEEP") {
+            match keep.parse() {
+                Ok(keep) if keep > 0 => settings.keep = keep,
+                _ => log::warn!("Ignoring invalid VOLTZ_BACKUP_KEEP '{}'", keep),
+            }
+        }
+        settings
+    }
+}
+
+/// A backup in the backup directory.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Backup {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    /// Distinguishes backups taken in the same second.
+    pub number: u32,
+    pub path: PathBuf,
+}
+
+/// What went into a new backup.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BackupStats {
+    pub files: usize,
+    /// The number of files that were hard-linked rather than copied.
+    pub linked: usize,
+    /// The total size of the files.
+    pub bytes: u64,
+}
+
+/// The backups of a world save.
+#[derive(Clone, Debug)]
+pub struct Backups {
+    save_dir: PathBuf,
+    dir: PathBuf,
+    settings: BackupSettings,
+}
+
+impl Backups {
+    pub fn new(save: &WorldSave, settings: BackupSettings) -> Self {
+        Self {
+            save_dir: save.dir().to_owned(),
+            dir: save.backups_dir(),
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> BackupSettings {
+        self.settings
+    }
+
+    /// Backs up the save, then deletes the oldest backups beyond the
+    /// number to keep. Logs the result, since this runs in the background.
+    pub fn create_and_prune(&self) {
+        let start = Instant::now();
+        match self.create(history::unix_time()) {
+            Ok((backup, stats)) => log::info!(
+                "Backed up the world to {} in {:?} ({} files, {} hard-linked, {})",
+                backup.path.display(),
+                start.elapsed(),
+                stats.files,
+                stats.linked,
+                utils::format_bytes(stats.bytes)
+            ),
+            Err(e) => {
+                log::error!("Failed to back up the world: {:#}", e);
+                return;
+            }
+        }
+        match self.prune() {
+            Ok(0) => {}
+            Ok(deleted) => log::info!("Deleted {} old backups", deleted),
+            Err(e) => log::error!("Failed to delete old backups: {:#}", e),
+        }
+    }
+
+    /// Backs up the save, naming the backup after `time`.
+    pub fn create(&self, time: u64) -> anyhow::Result<(Backup, BackupStats)> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create {}", self.dir.display()))?;
+        let number = self
+            .list()?
+            .iter()
+            .filter(|backup| backup.time == time)
+            .map(|backup| backup.number + 1)
+            .max()
+            .unwrap_or(0);
+        let name = backup_name(time, number);
+
+        // Written under another name so that an interrupted
+        // backup isn't mistaken for a complete one.
+        let partial = self.dir.join(format!("{}{}", name, PARTIAL_SUFFIX));
+        if partial.exists() {
+            fs::remove_dir_all(&partial)?;
+        }
+        let mut stats = BackupStats::default();
+        self.copy_dir(&self.save_dir, &partial, false, &mut stats)
+            .with_context(|| format!("failed to write {}", partial.display()))?;
+        let path = self.dir.join(name);
+        fs::rename(&partial, &path)?;
+
+        Ok((Backup { time, number, path }, stats))
+    }
+
+    /// Lists the complete backups, oldest first.
+    pub fn list(&self) -> anyhow::Result<Vec<Backup>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut backups = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let parsed = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(parse_backup_name);
+            if let Some((time, number)) = parsed {
+                backups.push(Backup { time, number, path });
+            }
+        }
+        backups.sort_by_key(|backup| (backup.time, backup.number));
+        Ok(backups)
+    }
+
+    /// Deletes the oldest backups beyond the number to keep, along with
+    /// backups that were interrupted. Returns the number of backups deleted.
+    pub fn prune(&self) -> anyhow::Result<usize> {
+        let backups = self.list()?;
+        let excess = backups.len().saturating_sub(self.settings.keep);
+        for backup in &backups[..excess] {
+            fs::remove_dir_all(&backup.path)
+                .with_context(|| format!("failed to delete {}", backup.path.display()))?;
+        }
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let interrupted = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| {
+                    name.starts_with(PREFIX) && name.ends_with(PARTIAL_SUFFIX)
+                });
+            if interrupted {
+                fs::remove_dir_all(&path)?;
+            }
+        }
+        Ok(excess)
+    }
+
+    /// Copies the contents of `from` into the new directory `to`.
+    /// Files are hard-linked unless `appended` is set or linking fails.
+    fn copy_dir(
+        &self,
+        from: &Path,
+        to: &Path,
+        appended: bool,
+        stats: &mut BackupStats,
+    ) -> io::Result<()> {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            let path = entry.path();
+            // Backups may be inside saves without a name to derive the backup directory from.
+            if path == self.dir {
+                continue;
+            }
+            let target = to.join(entry.file_name());
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                let appended = appended || path == self.save_dir.join("history");
+                self.copy_dir(&path, &target, appended, stats)?;
+            } else if file_type.is_file() {
+                // Left behind by a write that is still in progress or was interrupted.
+                if path
+                    .extension()
+                    .map_or(false, |extension| extension == "tmp")
+                {
+                    continue;
+                }
+                if !appended && fs::hard_link(&path, &target).is_ok() {
+                    stats.linked += 1;
+                } else {
+                    fs::copy(&path, &target)?;
+                }
+                stats.files += 1;
+                stats.bytes += entry.metadata()?.len();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn backup_name(time: u64, number: u32) -> String {
+    if number == 0 {
+        format!("{}{}", PREFIX, time)
+    } else {
+        format!("{}{}-{}", PREFIX, time, number)
+    }
+}
+
+/// Parses the time and number from the name of a complete backup.
+fn parse_backup_name(name: &str) -> Option<(u64, u32)> {
+    let name = name.strip_prefix(PREFIX)?;
+    let mut parts = name.splitn(2, '-');
+    let time = parts.next()?.parse().ok()?;
+    let number = match parts.next() {
+        Some(number) => number.parse().ok()?,
+        None => 0,
+    };
+    Some((time, number))
+}
+
+/// Registers the `backup` command, which
+/// fails unless the world has `backups`.
+pub fn register_commands(commands: &mut CommandRegistry, backups: Option<Backups>) {
+    commands.register(BackupCommand { backups });
+}
+
+struct BackupCommand {
+    backups: Option<Backups>,
+}
+
+impl Command for BackupCommand {
+    fn name(&self) -> &str {
+        "backup"
+    }
+
+    fn usage(&self) -> &str {
+        "now | list"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        let backups = match &self.backups {
+            Some(backups) => backups,
+            None => bail!("this world is not saved, so it can't be backed up"),
+        };
+        match args {
+            ["now"] => {
+                game.events().push(BackupRequested);
+                Ok("Saving and backing up the world".to_owned())
+            }
+            ["list"] => {
+                let list = backups.list()?;
+                if list.is_empty() {
+                    return Ok("There are no backups".to_owned());
+                }
+                let now = history::unix_time();
+                let mut message = format!("{} backups, newest last:", list.len());
+                for backup in &list {
+                    message.push_str(&format!(
+                        "\n  {} ({})",
+                        backup.path.display(),
+                        history::format_age(now.saturating_sub(backup.time))
+                    ));
+                }
+                Ok(message)
+            }
+            _ => bail!("usage: {}", command::usage(self)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_backup_names() {
+        assert_eq!(parse_backup_name(&backup_name(1600, 0)), Some((1600, 0)));
+        assert_eq!(parse_backup_name(&backup_name(1600, 2)), Some((1600, 2)));
+        assert_eq!(parse_backup_name("backup-1600.partial"), None);
+        assert_eq!(parse_backup_name("notes.txt"), None);
+    }
+
+    #[test]
+    fn backups_are_snapshots() {
+        let dir = std::env::temp_dir().join(format!("voltz-backup-test-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        let save = WorldSave::new(dir.join("world"));
+        let backups = Backups::new(
+            &save,
+            BackupSettings {
+                interval: None,
+                keep: 2,
+            },
+        );
+        fs::create_dir_all(save.history_dir()).unwrap();
+        fs::write(save.dir().join("level.bin"), "level").unwrap();
+        fs::write(save.history_dir().join("events.log"), "join\n").unwrap();
+
+        let (first, stats) = backups.create(100).unwrap();
+        assert_eq!(stats.files, 2);
+        assert_eq!(first.path, dir.join("world-backups").join("backup-100"));
+
+        // Saves replace files, and history is appended to.
+        fs::write(save.dir().join("level.tmp"), "new level").unwrap();
+        fs::rename(save.dir().join("level.tmp"), save.dir().join("level.bin")).unwrap();
+        fs::write(save.history_dir().join("events.log"), "join\nleave\n").unwrap();
+        assert_eq!(fs::read(first.path.join("level.bin")).unwrap(), b"level");
+        assert_eq!(
+            fs::read(first.path.join("history").join("events.log")).unwrap(),
+            b"join\n"
+        );
+
+        let (second, _) = backups.create(100).unwrap();
+        assert_eq!(second.number, 1);
+        backups.create(200).unwrap();
+        assert_eq!(backups.prune().unwrap(), 1);
+        let times: Vec<_> = backups
+            .list()
+            .unwrap()
+            .iter()
+            .map(|backup| (backup.time, backup.number))
+            .collect();
+        assert_eq!(times, vec![(100, 1), (200, 0)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
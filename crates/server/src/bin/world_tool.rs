@@ -0,0 +1,246 @@
+//! Maintenance tool for world saves.
+//!
+//! # Usage
+//! `voltz-world-tool <command> <save directory>`, where `command` is one of:
+//! * `verify`: checks the level data and each region file, including the
+//!   checksum and chunks of each column. Exits with an error if any
+//!   problems are found.
+//! * `defrag`: rewrites region files with their columns in order and
+//!   without unused bytes. Files with corrupt columns are skipped.
+//! * `prune`: removes columns identical to what the world generator
+//!   produces for the save's seed. They are generated again when the
+//!   world is loaded, so this only saves space. Pass `--cpu` to
+//!   generate on the CPU. Like the server, it uses the biome sequence
+//!   in `VOLTZ_BIOME_SEQUENCE` and the composition table in
+//!   `VOLTZ_COMPOSITION_TABLE` if set, and refuses to prune saves
+//!   generated with another biome sequence.
+//! * `stats`: prints the size of the save and the blocks it contains.
+//!
+//! Run it while no server is using the save.
+
+use std::{collections::BTreeMap, env, fs, process};
+
+use anyhow::{bail, Context};
+use common::{block, chunk::CHUNK_VOLUME, Chunk};
+use server::save::{self, RegionPos, SavedColumn, WorldSave};
+use worldgen::{Backend, BiomeSequence, CompositionTable, WorldGenerator};
+
+const USAGE: &str = "usage: voltz-world-tool <verify|defrag|prune|stats> <save directory> [--cpu]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.as_slice() {
+        [command, dir, flags @ ..] => {
+            let save = WorldSave::new(dir);
+            let cpu = flags.iter().any(|flag| flag == "--cpu");
+            match command.as_str() {
+                "verify" => verify(&save),
+                "defrag" => defrag(&save),
+                "prune" => prune(&save, cpu),
+                "stats" => stats(&save),
+                _ => Err(anyhow::anyhow!("unknown command '{}'\n{}", command, USAGE)),
+            }
+        }
+        _ => Err(anyhow::anyhow!(USAGE)),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {:#}", e);
+        process::exit(1);
+    }
+}
+
+/// Reads and inspects each region file, calling `f` with the
+/// region, its file size, and the result of inspecting it.
+fn for_each_region(
+    save: &WorldSave,
+    mut f: impl FnMut(RegionPos, usize, anyhow::Result<save::RegionReport>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    if !save.exists() {
+        bail!("no world is saved in {}", save.dir().display());
+    }
+    let mut files = save.region_files()?;
+    files.sort_by_key(|&(region, _)| (region.x, region.z));
+    for (region, path) in files {
+        let file = fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?;
+        f(region, file.len(), save::inspect_region(region, &file))?;
+    }
+    Ok(())
+}
+
+fn verify(save: &WorldSave) -> anyhow::Result<()> {
+    let mut problems = 0;
+    match save.read_level() {
+        Ok(level) => {
+            if level.registry_digest != block::registry_digest() {
+                println!("level: written with a different block registry");
+                problems += 1;
+            }
+        }
+        Err(e) => {
+            println!("level: {:#}", e);
+            problems += 1;
+        }
+    }
+
+    let mut columns = 0;
+    for_each_region(save, |region, _, report| {
+        let name = region.file_name();
+        match report {
+            Ok(report) => {
+                columns += report.columns.len();
+                for problem in &report.problems {
+                    println!("{}: {}", name, problem);
+                }
+                problems += report.problems.len();
+            }
+            Err(e) => {
+                println!("{}: {:#}", name, e);
+                problems += 1;
+            }
+        }
+        Ok(())
+    })?;
+
+    println!("Verified {} columns; found {} problems", columns, problems);
+    if problems > 0 {
+        bail!("the save is damaged");
+    }
+    Ok(())
+}
+
+fn defrag(save: &WorldSave) -> anyhow::Result<()> {
+    let (mut before, mut after) = (0, 0);
+    for_each_region(save, |region, size, report| {
+        let name = region.file_name();
+        let report = match report {
+            Ok(report) if report.problems.is_empty() => report,
+            _ => {
+                println!("{}: skipped because it is damaged; run verify", name);
+                return Ok(());
+            }
+        };
+
+        let mut columns = report.columns;
+        columns.sort_by_key(|column| (column.pos.x, column.pos.z));
+        let new_size = save::encode_region(region, &columns)?.len();
+        if report.unused_bytes > 0 || new_size < size {
+            save.write_region(region, &columns)?;
+            println!("{}: {} => {} bytes", name, size, new_size);
+            before += size;
+            after += new_size;
+        }
+        Ok(())
+    })?;
+
+    println!("Saved {} bytes", before.saturating_sub(after));
+    Ok(())
+}
+
+fn prune(save: &WorldSave, cpu: bool) -> anyhow::Result<()> {
+    let level = save.read_level()?;
+    let sequence = BiomeSequence::from_env();
+    if level.has_other_biome_sequence(&sequence) {
+        // Pruned columns would come back generated with another sequence.
+        bail!("the save was generated with a different biome sequence");
+    }
+    let backend = if cpu { Backend::Cpu } else { Backend::detect() };
+    let generator = WorldGenerator::new(backend)
+        .with_biome_sequence(sequence)
+        .with_composition(CompositionTable::from_env());
+
+    let mut pruned = 0;
+    for_each_region(save, |region, _, report| {
+        let name = region.file_name();
+        let report = match report {
+            Ok(report) if report.problems.is_empty() => report,
+            _ => {
+                println!("{}: skipped because it is damaged; run verify", name);
+                return Ok(());
+            }
+        };
+
+        let count = report.columns.len();
+        let modified: Vec<SavedColumn> = report
+            .columns
+            .into_iter()
+            .filter(|column| {
+                let generated = generator.generate_chunk_column(level.seed, column.pos);
+                !same_chunks(&column.chunks, &generated.chunks[..])
+            })
+            .collect();
+        if modified.len() == count {
+            return Ok(());
+        }
+
+        pruned += count - modified.len();
+        if modified.is_empty() {
+            save.remove_region(region)?;
+        } else {
+            save.write_region(region, &modified)?;
+        }
+        println!(
+            "{}: pruned {} of {} columns",
+            name,
+            count - modified.len(),
+            count
+        );
+        Ok(())
+    })?;
+
+    println!("Pruned {} columns", pruned);
+    Ok(())
+}
+
+/// Returns whether two lists of chunks contain the same blocks.
+/// Their palettes may differ.
+fn same_chunks(a: &[Chunk], b: &[Chunk]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(a, b)| (0..CHUNK_VOLUME).all(|i| block_at(a, i) == block_at(b, i)))
+}
+
+fn block_at(chunk: &Chunk, index: usize) -> block::BlockId {
+    let palette_index = chunk.indexes().get(index).expect("out of bounds") as usize;
+    chunk.palette()[palette_index]
+}
+
+fn stats(save: &WorldSave) -> anyhow::Result<()> {
+    let (mut regions, mut columns, mut chunks, mut empty_chunks, mut bytes) = (0, 0, 0, 0, 0);
+    let mut blocks: BTreeMap<&str, u64> = BTreeMap::new();
+    for_each_region(save, |_, size, report| {
+        let report = report?;
+        regions += 1;
+        bytes += size;
+        columns += report.columns.len();
+        for chunk in report.columns.iter().flat_map(|column| &column.chunks) {
+            chunks += 1;
+            if chunk.is_empty() {
+                empty_chunks += 1;
+            }
+            for i in 0..CHUNK_VOLUME {
+                let slug = block_at(chunk, i).descriptor().slug();
+                *blocks.entry(slug).or_default() += 1;
+            }
+        }
+        Ok(())
+    })?;
+
+    let level = save.read_level()?;
+    println!("Seed: {}", level.seed);
+    println!("Regions: {} ({} bytes)", regions, bytes);
+    println!("Columns: {}", columns);
+    println!("Chunks: {} ({} empty)", chunks, empty_chunks);
+    if chunks > 0 {
+        println!("Bytes per chunk: {:.1}", bytes as f64 / chunks as f64);
+    }
+
+    let mut blocks: Vec<_> = blocks.into_iter().collect();
+    blocks.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    println!("Blocks:");
+    for (slug, count) in blocks {
+        println!("  {}: {}", slug, count);
+    }
+    Ok(())
+}
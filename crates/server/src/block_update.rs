@@ -0,0 +1,186 @@
+//! Scheduled block updates.
+//!
+//! Changing a block with [`Game::set_block`] schedules an update for the
+//! block and the six blocks next to it. Systems may also schedule updates
+//! directly with [`Game::schedule_block_update`]. Each tick, up to
+//! [`UPDATES_PER_TICK`] scheduled updates are pushed as [`BlockUpdateDue`]
+//! events, oldest first, for systems that react to a block's surroundings.
+//! Updates beyond the budget wait for later ticks, so long chains of
+//! updates are spread out instead of stalling a single tick.
+//!
+//! Systems can also delay work at a block with
+//! [`Game::schedule_block_tick`], which pushes a [`BlockTickDue`]
+//! event once the given number of ticks has passed.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, VecDeque},
+};
+
+use common::{BlockPos, System, SystemExecutor};
+use hashbrown::HashSet;
+
+use crate::{
+    event::{BlockTickDue, BlockUpdateDue},
+    game::Game,
+};
+
+/// The maximum number of block updates processed per tick.
+pub const UPDATES_PER_TICK: usize = 1024;
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(BlockUpdateSystem);
+}
+
+/// A first-in, first-out queue of block positions awaiting an update.
+/// A position is queued at most once at a time.
+#[derive(Debug, Default)]
+pub struct BlockUpdateQueue {
+    queue: VecDeque<BlockPos>,
+    queued: HashSet<BlockPos>,
+}
+
+impl BlockUpdateQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedules an update at `pos`. Does nothing if
+    /// an update is already scheduled there.
+    pub fn schedule(&mut self, pos: BlockPos) {
+        if self.queued.insert(pos) {
+            self.queue.push_back(pos);
+        }
+    }
+
+    /// Removes the oldest scheduled update.
+    pub fn pop(&mut self) -> Option<BlockPos> {
+        let pos = self.queue.pop_front()?;
+        self.queued.remove(&pos);
+        Some(pos)
+    }
+
+    /// Returns the number of scheduled updates.
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/// Block ticks scheduled for later game ticks.
+/// A position has at most one scheduled tick at a time.
+#[derive(Debug, Default)]
+pub struct BlockTickQueue {
+    /// Scheduled ticks as `(due tick, position)`, earliest first.
+    heap: BinaryHeap<Reverse<(u64, BlockPos)>>,
+    scheduled: HashSet<BlockPos>,
+}
+
+impl BlockTickQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedules a tick at `pos` for the game tick `due`. Does
+    /// nothing if a tick is already scheduled there.
+    pub fn schedule(&mut self, pos: BlockPos, due: u64) {
+        if self.scheduled.insert(pos) {
+            self.heap.push(Reverse((due, pos)));
+        }
+    }
+
+    /// Returns whether a tick is scheduled at `pos`.
+    pub fn is_scheduled(&self, pos: BlockPos) -> bool {
+        self.scheduled.contains(&pos)
+    }
+
+    /// Removes the earliest scheduled tick if it is due at `now`.
+    pub fn pop_due(&mut self, now: u64) -> Option<BlockPos> {
+        match self.heap.peek() {
+            Some(&Reverse((due, _))) if due <= now => {
+                let Reverse((_, pos)) = self.heap.pop()?;
+                self.scheduled.remove(&pos);
+                Some(pos)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// System to emit [`BlockUpdateDue`] events for
+/// scheduled updates within the per-tick budget,
+/// and [`BlockTickDue`] events for due ticks.
+struct BlockUpdateSystem;
+
+impl System<Game> for BlockUpdateSystem {
+    fn run(&mut self, game: &mut Game) {
+        let mut due = Vec::new();
+        let queue = game.block_updates_mut();
+        while due.len() < UPDATES_PER_TICK {
+            match queue.pop() {
+                Some(pos) => due.push(pos),
+                None => break,
+            }
+        }
+        if !queue.is_empty() {
+            log::trace!("{} block updates deferred to later ticks", queue.len());
+        }
+
+        let mut ticks = Vec::new();
+        let now = game.tick();
+        while let Some(pos) = game.block_ticks_mut().pop_due(now) {
+            ticks.push(pos);
+        }
+
+        let mut events = game.events();
+        for pos in due {
+            events.push(BlockUpdateDue { pos });
+        }
+        for pos in ticks {
+            events.push(BlockTickDue { pos });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queue_is_fifo_without_duplicates() {
+        let a = BlockPos { x: 0, y: 0, z: 0 };
+        let b = BlockPos { x: 1, y: 0, z: 0 };
+        let mut queue = BlockUpdateQueue::new();
+        queue.schedule(a);
+        queue.schedule(b);
+        queue.schedule(a);
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(queue.pop(), Some(a));
+        // Popped positions can be scheduled again.
+        queue.schedule(a);
+        assert_eq!(queue.pop(), Some(b));
+        assert_eq!(queue.pop(), Some(a));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn ticks_are_popped_when_due() {
+        let a = BlockPos { x: 0, y: 0, z: 0 };
+        let b = BlockPos { x: 1, y: 0, z: 0 };
+        let mut queue = BlockTickQueue::new();
+        queue.schedule(a, 10);
+        queue.schedule(b, 5);
+        queue.schedule(a, 1);
+        assert!(queue.is_scheduled(a));
+
+        assert_eq!(queue.pop_due(4), None);
+        assert_eq!(queue.pop_due(7), Some(b));
+        assert_eq!(queue.pop_due(7), None);
+        assert_eq!(queue.pop_due(10), Some(a));
+        assert!(!queue.is_scheduled(a));
+    }
+}
@@ -0,0 +1,75 @@
+//! Chat between players.
+//!
+//! Players send `ChatMessage`s, which the server cleans up with
+//! [`sanitize`] and broadcasts to all players along with the
+//! sender's username.
+
+use common::{entity::player::Username, System, SystemExecutor};
+use protocol::packets::{
+    client::MAX_CHAT_MESSAGE_LENGTH,
+    server::{self, ServerPacket},
+};
+
+use crate::{event::ChatReceived, game::Game, Mailbox};
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(ChatSystem);
+}
+
+/// System to broadcast chat messages.
+struct ChatSystem;
+
+impl System<Game> for ChatSystem {
+    fn run(&mut self, game: &mut Game) {
+        for chat in game.events().iter::<ChatReceived>() {
+            let sender = match game.ecs().get::<Username>(chat.player) {
+                Ok(username) => username.0.clone(),
+                // The player left during this tick.
+                Err(_) => continue,
+            };
+            log::info!("<{}> {}", sender, chat.message);
+
+            for (_, mailbox) in game.ecs().query::<&Mailbox>().iter() {
+                mailbox.send(ServerPacket::ChatMessage(server::ChatMessage {
+                    sender: sender.clone(),
+                    message: chat.message.clone(),
+                }));
+            }
+        }
+    }
+}
+
+/// Cleans up a chat message sent by a player: removes control
+/// characters, trims whitespace, and truncates it to
+/// [`MAX_CHAT_MESSAGE_LENGTH`] characters. Returns `None` if
+/// nothing is left.
+pub fn sanitize(message: &str) -> Option<String> {
+    let message: String = message
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>()
+        .trim()
+        .chars()
+        .take(MAX_CHAT_MESSAGE_LENGTH)
+        .collect();
+    if message.is_empty() {
+        None
+    } else {
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitizes_messages() {
+        assert_eq!(sanitize("  hello\u{7}  ").as_deref(), Some("hello"));
+        assert_eq!(sanitize(" \n\t "), None);
+
+        let long = "é".repeat(MAX_CHAT_MESSAGE_LENGTH + 10);
+        let sanitized = sanitize(&long).unwrap();
+        assert_eq!(sanitized.chars().count(), MAX_CHAT_MESSAGE_LENGTH);
+    }
+}
@@ -0,0 +1,572 @@
+//! Console commands for administering the server at runtime.
+//!
+//! A [`Console`] supplies command lines, either read from stdin or sent
+//! through a channel. Each tick, the server runs the lines received
+//! since the last tick through its [`CommandRegistry`] and logs the
+//! results. A leading slash is optional, so `/kick alice` and `kick alice`
+//! are the same command.
+//!
+//! Wherever a command takes a player, a [selector](crate::selector) such
+//! as `@p` may be given instead of a username, as long as it selects a
+//! single player. Commands that take `<targets>` accept any selector.
+//!
+//! Built-in commands:
+//! * `help`: lists the available commands.
+//! * `kick <targets> [reason]`: disconnects players.
+//! * `tp [targets] <x> <y> <z>`: teleports entities. The targets may be
+//!   omitted if only one player is online.
+//! * `packets [player]`: lists the kinds of packets sent to and received
+//!   from a player, with their counts and when each was last seen.
+//! * `events <ticks>`: prints the events pushed during the last `ticks`
+//!   ticks, if event tracing is enabled with `VOLTZ_TRACE_EVENTS`.
+//! * `save`: saves the world.
+//! * `stop`: saves the world and stops the server.
+//!
+//! Other modules add their own commands by implementing [`Command`]
+//! and registering it, as the [`tick_rate`](crate::tick_rate) module
+//! does for `tps` and `slowmo`.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead},
+    thread,
+};
+
+use anyhow::{bail, Context};
+use common::{entity::player::Username, Pos, WorldPos};
+use flume::{Receiver, Sender};
+use glam::{vec3a, Vec3A};
+use hecs::Entity;
+use protocol::{
+    packets::{kind::Direction, server::Teleport, ServerPacket},
+    stats::PacketStats,
+};
+
+use crate::{conn::Kicked, event::SaveRequested, game::Game, selector::Selector, Mailbox};
+
+/// A command that can be run from the console.
+pub trait Command {
+    /// The name used to invoke the command, without the slash.
+    fn name(&self) -> &str;
+
+    /// Describes the command's arguments, such as `<player> [reason]`.
+    fn usage(&self) -> &str;
+
+    /// Runs the command with its whitespace-separated arguments,
+    /// returning a message describing the result.
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String>;
+}
+
+/// The commands available to the server console, by name.
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: BTreeMap<String, Box<dyn Command>>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a command.
+    ///
+    /// # Panics
+    /// Panics if a command with the same name was already registered.
+    pub fn register(&mut self, command: impl Command + 'static) {
+        let name = command.name().to_owned();
+        assert!(
+            name != "help" && !self.commands.contains_key(&name),
+            "command '{}' registered twice",
+            name
+        );
+        self.commands.insert(name, Box::new(command));
+    }
+
+    /// Parses and runs a command line, such as `/tp 0 100 0`,
+    /// returning a message describing the result.
+    pub fn run(&mut self, game: &mut Game, line: &str) -> anyhow::Result<String> {
+        let line = line.trim();
+        let line = line.strip_prefix('/').unwrap_or(line);
+        let mut words = line.split_whitespace();
+        let name = match words.next() {
+            Some(name) => name,
+            None => return Ok(String::new()),
+        };
+        let args: Vec<&str> = words.collect();
+
+        if name == "help" {
+            return Ok(self.help());
+        }
+        match self.commands.get_mut(name) {
+            Some(command) => command.run(game, &args),
+            None => bail!("unknown command '{}'; run 'help' for a list", name),
+        }
+    }
+
+    fn help(&self) -> String {
+        let mut help = "Commands:\n  /help".to_owned();
+        for command in self.commands.values() {
+            help.push_str(&format!("\n  {}", usage(command.as_ref())));
+        }
+        help
+    }
+}
+
+/// Describes how to invoke a command, e.g. for an error
+/// message when it is given the wrong arguments.
+pub fn usage(command: &dyn Command) -> String {
+    format!("/{} {}", command.name(), command.usage())
+        .trim_end()
+        .to_owned()
+}
+
+/// Adds the built-in commands.
+pub fn register(commands: &mut CommandRegistry) {
+    commands.register(KickCommand);
+    commands.register(TpCommand);
+    commands.register(PacketsCommand);
+    commands.register(EventsCommand);
+    commands.register(SaveCommand);
+    commands.register(StopCommand);
+}
+
+/// A source of command lines.
+///
+/// Clones share the same lines, so a program which hosts one server
+/// after another can give each a clone of a single stdin console.
+#[derive(Clone)]
+pub struct Console {
+    lines: Receiver<String>,
+    sender: Sender<String>,
+}
+
+impl Console {
+    /// Creates a console that reads command lines from stdin
+    /// on a separate thread.
+    pub fn stdin() -> Self {
+        let (sender, console) = Self::channel();
+        thread::Builder::new()
+            .name("console".to_owned())
+            .spawn(move || {
+                let stdin = io::stdin();
+                for line in stdin.lock().lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(e) => {
+                            log::error!("Failed to read from stdin: {}", e);
+                            return;
+                        }
+                    };
+                    if sender.send(line).is_err() {
+                        // The server stopped.
+                        return;
+                    }
+                }
+            })
+            .expect("failed to spawn console thread");
+        console
+    }
+
+    /// Creates a console that receives command lines
+    /// sent through the returned channel.
+    pub fn channel() -> (Sender<String>, Self) {
+        let (sender, lines) = flume::unbounded();
+        (sender.clone(), Self { lines, sender })
+    }
+
+    /// Sends a command line as if it had been typed, e.g. to
+    /// stop a server from the thread that launched it.
+    pub fn send(&self, line: impl Into<String>) {
+        // We hold a receiver, so sending can't fail.
+        self.sender.send(line.into()).ok();
+    }
+
+    /// Returns the lines received since the last call.
+    pub(crate) fn poll(&self) -> Vec<String> {
+        self.lines.try_iter().collect()
+    }
+}
+
+/// Finds an online player by username or by a
+/// selector that selects a single player.
+pub fn find_player(game: &Game, username: &str) -> anyhow::Result<Entity> {
+    if username.starts_with('@') {
+        return match select(game, username)?.as_slice() {
+            [player] if game.ecs().get::<Username>(*player).is_ok() => Ok(*player),
+            [_] => bail!("'{}' selects an entity that isn't a player", username),
+            _ => bail!("'{}' selects several entities; select one player", username),
+        };
+    }
+    game.ecs()
+        .query::<&Username>()
+        .iter()
+        .find(|(_, name)| name.0 == username)
+        .map(|(player, _)| player)
+        .with_context(|| format!("{} is not online", username))
+}
+
+/// Returns the entities matching a [selector](crate::selector).
+/// Fails if the selector is invalid or matches nothing.
+pub fn select(game: &Game, selector: &str) -> anyhow::Result<Vec<Entity>> {
+    let parsed: Selector = selector.parse()?;
+    let entities = parsed.select(game);
+    if entities.is_empty() {
+        if parsed.is_username() {
+            bail!("{} is not online", selector);
+        }
+        bail!("'{}' matches nothing", selector);
+    }
+    Ok(entities)
+}
+
+/// Names an entity in command output: players
+/// by username, other entities by ID.
+pub fn describe(game: &Game, entity: Entity) -> String {
+    match game.ecs().get::<Username>(entity) {
+        Ok(username) => username.0.clone(),
+        Err(_) => format!("entity {}", entity.id()),
+    }
+}
+
+/// Describes a group of entities in command output.
+fn describe_all(game: &Game, entities: &[Entity]) -> String {
+    match entities {
+        [entity] => describe(game, *entity),
+        _ => format!("{} entities", entities.len()),
+    }
+}
+
+/// Returns the only online player, for commands
+/// that may omit the player when only one is online.
+pub fn only_player(game: &Game) -> anyhow::Result<Entity> {
+    let players: Vec<Entity> = game
+        .ecs()
+        .query::<&Username>()
+        .iter()
+        .map(|(player, _)| player)
+        .collect();
+    match players.as_slice() {
+        [player] => Ok(*player),
+        [] => bail!("nobody is online"),
+        _ => bail!("several players are online; name one of them"),
+    }
+}
+
+fn parse_pos(args: &[&str]) -> anyhow::Result<Vec3A> {
+    let mut coords = [0.; 3];
+    for (coord, arg) in coords.iter_mut().zip(args) {
+        *coord = arg
+            .parse::<f32>()
+            .ok()
+            .filter(|coord| coord.is_finite())
+            .with_context(|| format!("'{}' is not a coordinate", arg))?;
+    }
+    Ok(vec3a(coords[0], coords[1], coords[2]))
+}
+
+struct KickCommand;
+
+impl Command for KickCommand {
+    fn name(&self) -> &str {
+        "kick"
+    }
+
+    fn usage(&self) -> &str {
+        "<targets> [reason]"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        let (targets, reason) = match args {
+            [targets] => (*targets, "Kicked by an operator".to_owned()),
+            [targets, reason @ ..] => (*targets, reason.join(" ")),
+            [] => bail!("usage: {}", usage(self)),
+        };
+        let players = select(game, targets)?;
+        if players
+            .iter()
+            .any(|&player| game.ecs().get::<Username>(player).is_err())
+        {
+            bail!("only players can be kicked");
+        }
+        let kicked = describe_all(game, &players);
+        for player in players {
+            game.ecs_mut()
+                .insert_one(
+                    player,
+                    Kicked {
+                        reason: reason.clone(),
+                    },
+                )
+                .unwrap();
+        }
+        Ok(format!("Kicked {}", kicked))
+    }
+}
+
+struct TpCommand;
+
+impl Command for TpCommand {
+    fn name(&self) -> &str {
+        "tp"
+    }
+
+    fn usage(&self) -> &str {
+        "[targets] <x> <y> <z>"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        let (targets, pos) = match args {
+            [targets, coords @ ..] if coords.len() == 3 => {
+                (select(game, targets)?, parse_pos(coords)?)
+            }
+            coords if coords.len() == 3 => (vec![only_player(game)?], parse_pos(coords)?),
+            _ => bail!("usage: {}", usage(self)),
+        };
+
+        // Coordinates are given in main-zone space.
+        let world_pos = WorldPos::main(pos);
+        for &entity in &targets {
+            if let Ok(mut entity_pos) = game.ecs().get_mut::<Pos>(entity) {
+                entity_pos.0 = world_pos;
+            }
+            // Other entities are replicated to clients as they move.
+            if let Ok(mailbox) = game.ecs().get::<Mailbox>(entity) {
+                mailbox.send(ServerPacket::Teleport(Teleport { pos: world_pos }));
+            }
+        }
+        Ok(format!(
+            "Teleported {} to {:.1}, {:.1}, {:.1}",
+            describe_all(game, &targets),
+            pos.x,
+            pos.y,
+            pos.z
+        ))
+    }
+}
+
+struct PacketsCommand;
+
+impl Command for PacketsCommand {
+    fn name(&self) -> &str {
+        "packets"
+    }
+
+    fn usage(&self) -> &str {
+        "[player]"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        let player = match args {
+            [] => only_player(game)?,
+            [username] => find_player(game, username)?,
+            _ => bail!("usage: {}", usage(self)),
+        };
+        let mailbox = game.ecs().get::<Mailbox>(player).unwrap();
+        Ok(format!(
+            "Packets of {}:{}",
+            describe(game, player),
+            describe_traffic(mailbox.stats())
+        ))
+    }
+}
+
+/// Lists the traffic of each kind of packet seen by
+/// a player's mailbox, most recently seen first.
+fn describe_traffic(stats: &PacketStats) -> String {
+    let seen = stats.seen();
+    if seen.is_empty() {
+        return " none".to_owned();
+    }
+    let mut traffic = String::new();
+    for (kind, kind_stats) in seen {
+        let count = match kind.direction() {
+            Direction::ToClient => format!("{} sent", kind_stats.sent),
+            Direction::ToServer => format!("{} received", kind_stats.received),
+            Direction::Both => {
+                format!("{} sent, {} received", kind_stats.sent, kind_stats.received)
+            }
+        };
+        let last_seen = kind_stats.last_seen().unwrap().elapsed();
+        traffic.push_str(&format!(
+            "\n  {}: {}, last {:.1}s ago",
+            kind,
+            count,
+            last_seen.as_secs_f32()
+        ));
+    }
+    traffic
+}
+
+/// Prints the event trace. System index 0 is connection handling;
+/// index `i + 1` is the `i`th system added in `setup`.
+struct EventsCommand;
+
+impl Command for EventsCommand {
+    fn name(&self) -> &str {
+        "events"
+    }
+
+    fn usage(&self) -> &str {
+        "<ticks>"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        let ticks = match args {
+            [ticks] => ticks
+                .parse()
+                .with_context(|| format!("invalid number of ticks '{}'", ticks))?,
+            _ => bail!("usage: {}", usage(self)),
+        };
+        let events = game.events();
+        if !events.is_tracing() {
+            bail!("event tracing is disabled; set VOLTZ_TRACE_EVENTS to enable it");
+        }
+        Ok(events.dump(ticks))
+    }
+}
+
+struct SaveCommand;
+
+impl Command for SaveCommand {
+    fn name(&self) -> &str {
+        "save"
+    }
+
+    fn usage(&self) -> &str {
+        ""
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        if !args.is_empty() {
+            bail!("usage: {}", usage(self));
+        }
+        if !game.is_saved() {
+            bail!("this world is not saved to disk");
+        }
+        game.events().push(SaveRequested);
+        Ok("Saving the world".to_owned())
+    }
+}
+
+struct StopCommand;
+
+impl Command for StopCommand {
+    fn name(&self) -> &str {
+        "stop"
+    }
+
+    fn usage(&self) -> &str {
+        ""
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        if !args.is_empty() {
+            bail!("usage: {}", usage(self));
+        }
+        game.events().push(SaveRequested);
+        game.request_stop();
+        Ok("Stopping the server".to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use protocol::bridge;
+
+    use crate::game::test_game;
+
+    use super::*;
+
+    struct Echo;
+
+    impl Command for Echo {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn usage(&self) -> &str {
+            "<words...>"
+        }
+
+        fn run(&mut self, _game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+            if args.is_empty() {
+                bail!("nothing to echo");
+            }
+            Ok(args.join(" "))
+        }
+    }
+
+    #[test]
+    fn dispatch() {
+        let mut game = test_game();
+        let mut commands = CommandRegistry::new();
+        register(&mut commands);
+        commands.register(Echo);
+
+        assert_eq!(commands.run(&mut game, "/echo  a b ").unwrap(), "a b");
+        assert_eq!(commands.run(&mut game, "echo c").unwrap(), "c");
+        assert_eq!(commands.run(&mut game, "  ").unwrap(), "");
+
+        let error = commands.run(&mut game, "/echo").unwrap_err();
+        assert_eq!(error.to_string(), "nothing to echo");
+        assert!(commands.run(&mut game, "/fly").is_err());
+        assert!(commands.run(&mut game, "/kick nobody").is_err());
+        let error = commands.run(&mut game, "/save now").unwrap_err();
+        assert_eq!(error.to_string(), "usage: /save");
+        // The world in this test isn't saved.
+        assert!(commands.run(&mut game, "/save").is_err());
+
+        let help = commands.run(&mut game, "/help").unwrap();
+        assert!(help.contains("/echo <words...>"));
+        assert!(help.contains("/tp [targets] <x> <y> <z>"));
+    }
+
+    #[test]
+    fn lists_packets() {
+        let mut game = test_game();
+        let mut commands = CommandRegistry::new();
+        register(&mut commands);
+        assert!(commands.run(&mut game, "/packets").is_err());
+
+        let (_client, mailbox) = bridge::singleplayer();
+        mailbox.send(ServerPacket::Teleport(Teleport {
+            pos: WorldPos::main(Vec3A::zero()),
+        }));
+        mailbox.send(ServerPacket::Teleport(Teleport {
+            pos: WorldPos::main(Vec3A::zero()),
+        }));
+        game.ecs_mut()
+            .spawn((Username("alice".to_owned()), mailbox));
+
+        let packets = commands.run(&mut game, "/packets alice").unwrap();
+        assert!(packets.starts_with("Packets of alice:"));
+        assert!(packets.contains("Teleport: 2 sent"));
+        assert!(!packets.contains("Ping"));
+    }
+
+    #[test]
+    fn dumps_events() {
+        let mut game = test_game();
+        let mut commands = CommandRegistry::new();
+        register(&mut commands);
+        let error = commands.run(&mut game, "/events 2").unwrap_err();
+        assert!(error.to_string().contains("VOLTZ_TRACE_EVENTS"));
+
+        game.events().enable_tracing(4);
+        for _ in 0..3 {
+            game.events().begin_tick();
+            game.events().push(SaveRequested);
+        }
+        let events = commands.run(&mut game, "/events 2").unwrap();
+        assert_eq!(events.matches("SaveRequested").count(), 2, "{}", events);
+        assert!(commands.run(&mut game, "/events").is_err());
+        assert!(commands.run(&mut game, "/events many").is_err());
+    }
+
+    #[test]
+    fn parses_positions() {
+        assert_eq!(parse_pos(&["1", "-2.5", "3"]).unwrap(), vec3a(1., -2.5, 3.));
+        assert!(parse_pos(&["1", "two", "3"]).is_err());
+        assert!(parse_pos(&["1", "NaN", "3"]).is_err());
+    }
+}
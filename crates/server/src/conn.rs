@@ -1,26 +1,49 @@
+use std::{cmp::Ordering, time::Instant};
+
 use common::{
-    entity::player::{Username, View},
-    ChunkPos, Orient, Pos,
+    block,
+    edit::BlockEdit,
+    entity::{
+        player::{Experience, GameMode, Username, View, MAX_HEALTH},
+        Health,
+    },
+    ChunkPos, Orient, Pos, WorldPos,
 };
 use glam::{Vec2, Vec3A};
 use hecs::Entity;
 use protocol::{
     bridge::ToClient,
+    dictionary::BlockDictionary,
+    features::Features,
+    keepalive::{self, Keepalive},
+    packets::server::{RegistrySync, SetBlockDictionary, WorldgenProgress},
     packets::ClientPacket,
     packets::ServerPacket,
     packets::{
         client::ClientInfo, server::JoinGame, server::ServerInfo, shared::Disconnect, SharedPacket,
     },
-    Bridge, PROTOCOL_VERSION,
+    registry, Bridge, PROTOCOL_VERSION,
 };
 
-use crate::{event::PlayerJoined, game::Game, VIEW_DISTANCE};
+use crate::{
+    chat, death,
+    dialog::{self, OpenDialogs},
+    event::{BlockEditRequested, ChatReceived, DigProgressChanged, PlayerJoined, PlayerLeft},
+    game::Game,
+    generation, health, inventory,
+    resume::{self, SessionToken},
+    view::{self, RequestedViewDistance},
+};
 
 /// A connection to a client.
 pub struct Connection {
     bridge: Bridge<ToClient>,
     state: ConnectionState,
     disconnected: bool,
+    keepalive: Keepalive,
+    /// The client's info and features while it loads
+    /// the server's [resource pack](crate::resource_pack).
+    awaiting_pack: Option<(ClientInfo, Features)>,
 }
 
 impl Connection {
@@ -29,65 +52,203 @@ impl Connection {
             bridge,
             state: ConnectionState::Login,
             disconnected: false,
+            keepalive: Keepalive::new(Instant::now()),
+            awaiting_pack: None,
         }
     }
 
+    /// Sends world generation progress to a client which
+    /// is waiting to log in.
+    pub(crate) fn send_worldgen_progress(&self, stage: &str, percent: f32) {
+        self.bridge
+            .send(ServerPacket::WorldgenProgress(WorldgenProgress {
+                stage: stage.to_owned(),
+                percent,
+            }));
+    }
+
     /// Polls for packets and invokes packet handlers.
     /// If we're in the Login state and we advance to the Game
     /// state, a new player will be added to the ECS.
     pub fn tick(&mut self, game: &mut Game) {
+        if self.disconnected {
+            return;
+        }
         if self.bridge.is_disconnected() {
-            self.disconnect(Some("bridge died".to_owned()));
+            match self.state {
+                ConnectionState::Login => self.disconnect(game, Some("bridge died".to_owned())),
+                ConnectionState::Game { player } => {
+                    // The client may have said goodbye before going away.
+                    self.handle_packets(game);
+                    if !self.disconnected {
+                        self.suspend(game, player);
+                    }
+                }
+            }
+            return;
         }
         match self.state {
             ConnectionState::Login => self.advance_login(game),
-            ConnectionState::Game { .. } => self.handle_packets(game),
+            ConnectionState::Game { player } => {
+                let kicked = game
+                    .ecs()
+                    .get::<Kicked>(player)
+                    .map(|kicked| kicked.reason.clone());
+                if let Ok(reason) = kicked {
+                    log::info!(
+                        "{} was kicked: {}",
+                        game.ecs().get::<Username>(player).unwrap().0,
+                        reason
+                    );
+                    self.disconnect(game, Some(reason));
+                    return;
+                }
+                self.handle_packets(game);
+                self.keep_alive(game);
+            }
         }
     }
 
+    /// Returns whether the connection has ended. Its player, if
+    /// any, has been despawned or [suspended](crate::resume).
+    pub(crate) fn is_disconnected(&self) -> bool {
+        self.disconnected
+    }
+
+    /// Ends a connection that was lost, keeping the player
+    /// around in case their client resumes the session.
+    fn suspend(&mut self, game: &mut Game, player: Entity) {
+        resume::suspend(game, player);
+        self.disconnected = true;
+    }
+
     fn advance_login(&mut self, game: &mut Game) {
         for packet in self.bridge.flush_received() {
             match packet {
-                ClientPacket::ClientInfo(client_info) => {
+                ClientPacket::ClientInfo(client_info) if self.awaiting_pack.is_none() => {
                     log::debug!("Received ClientInfo from client: {:?}", client_info);
+                    if let Some(reason) = version_mismatch(client_info.protocol_version) {
+                        log::info!("Rejecting {}: {}", client_info.username, reason);
+                        self.disconnect(game, Some(reason));
+                        return;
+                    }
 
+                    let features = enabled_features(game, client_info.features);
+                    let resource_pack = game.resource_pack().cloned();
+                    let awaits_pack = resource_pack.is_some();
                     let server_info = ServerInfo {
                         protocol_version: PROTOCOL_VERSION,
                         implementation: format!("voltz-server:{}", env!("CARGO_PKG_VERSION")),
+                        features,
+                        world_id: game.world_id(),
+                        resource_pack,
                     };
                     self.bridge.send(ServerPacket::ServerInfo(server_info));
 
-                    let pos = glam::vec3a(128., 240., 128.);
-                    let orient = glam::vec2(0., 0.);
-                    let vel = Vec3A::zero();
-                    let join_game = JoinGame { pos, orient, vel };
-                    self.bridge.send(ServerPacket::JoinGame(join_game));
-
-                    self.spawn_player(game, pos, orient, vel, client_info);
+                    if awaits_pack {
+                        log::debug!(
+                            "Waiting for {} to load the resource pack",
+                            client_info.username
+                        );
+                        self.awaiting_pack = Some((client_info, features));
+                    } else {
+                        self.join(game, client_info, features);
+                        return;
+                    }
+                }
+                ClientPacket::ResourcePackLoaded(_) if self.awaiting_pack.is_some() => {
+                    let (client_info, features) = self.awaiting_pack.take().unwrap();
+                    self.join(game, client_info, features);
+                    return;
                 }
                 _ => {
                     log::debug!(
                         "Received unexpected packet from client during login state. Disconnecting.",
                     );
-                    self.disconnect(Some(String::from(
-                        "received unexpected packet during the login state",
-                    )));
+                    self.disconnect(
+                        game,
+                        Some(String::from(
+                            "received unexpected packet during the login state",
+                        )),
+                    );
+                    return;
                 }
             }
         }
     }
 
+    /// Resumes the client's session or spawns its player.
+    fn join(&mut self, game: &mut Game, client_info: ClientInfo, features: Features) {
+        if client_info.registry_digest != block::registry_digest() {
+            self.bridge.send(ServerPacket::RegistrySync(RegistrySync {
+                blocks: registry::local_blocks(),
+            }));
+        }
+
+        if let Some(token) = client_info.resume_token {
+            if self.resume(game, token, &client_info, features) {
+                return;
+            }
+            log::debug!(
+                "{} has no session to resume; joining as usual",
+                client_info.username
+            );
+        }
+
+        let pos = generation::spawn_pos();
+        let orient = glam::vec2(0., 0.);
+        let vel = Vec3A::zero();
+        let session_token = rand::random();
+        let join_game = JoinGame {
+            pos,
+            orient,
+            vel,
+            session_token,
+        };
+        self.bridge.send(ServerPacket::JoinGame(join_game));
+
+        let dictionary = if client_info.registry_digest == block::registry_digest() {
+            let dictionary = BlockDictionary::from_registry();
+            self.bridge
+                .send(ServerPacket::SetBlockDictionary(SetBlockDictionary {
+                    dictionary: dictionary.clone(),
+                }));
+            Some(dictionary)
+        } else {
+            log::debug!("Client block registry differs; sending full palettes");
+            None
+        };
+
+        self.spawn_player(
+            game,
+            pos,
+            orient,
+            vel,
+            client_info,
+            features,
+            dictionary,
+            session_token,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn spawn_player(
         &mut self,
         game: &mut Game,
-        pos: Vec3A,
+        pos: WorldPos,
         orient: Vec2,
         vel: Vec3A,
         client_info: ClientInfo,
+        features: Features,
+        dictionary: Option<BlockDictionary>,
+        session_token: u64,
     ) {
         log::info!("{} joined the game.", client_info.username);
+        let view_distance = view::clamp_distance(game, client_info.view_distance);
+        let view = View::new(ChunkPos::from_pos(game.world().to_main(pos)), view_distance);
         let pos = Pos(pos);
         let orient = Orient(orient);
+        let xp = Experience::new(game.offline_player_data(&client_info.username).xp);
 
         let player = game.ecs_mut().spawn((
             pos,
@@ -95,11 +256,74 @@ impl Connection {
             vel,
             Username(client_info.username),
             self.bridge.clone(),
-            View::new(ChunkPos::from_pos(pos), VIEW_DISTANCE),
+            view,
+            RequestedViewDistance(client_info.view_distance),
+            OpenDialogs::default(),
+            features,
+            xp,
+            Health::full(MAX_HEALTH),
+            GameMode::default(),
+            inventory::starting_inventory(),
         ));
+        if let Some(dictionary) = dictionary {
+            game.ecs_mut().insert_one(player, dictionary).unwrap();
+        }
+        game.ecs_mut()
+            .insert_one(player, SessionToken(session_token))
+            .unwrap();
         game.events().push(PlayerJoined { player });
 
         self.state = ConnectionState::Game { player };
+        self.keepalive = Keepalive::new(Instant::now());
+    }
+
+    /// Resumes the session of the suspended player with `token`
+    /// and the client's username. Returns `false` if there is none.
+    fn resume(
+        &mut self,
+        game: &mut Game,
+        token: u64,
+        client_info: &ClientInfo,
+        features: Features,
+    ) -> bool {
+        let player = match game.suspended().find(game, token, &client_info.username) {
+            Some(player) => player,
+            None => return false,
+        };
+        let entity = game.ecs().entity(player).unwrap();
+        let join_game = JoinGame {
+            pos: entity.get::<Pos>().unwrap().0,
+            orient: entity.get::<Orient>().unwrap().0,
+            vel: Vec3A::zero(),
+            session_token: token,
+        };
+        *entity.get_mut::<Features>().unwrap() = features;
+        // Resumed players are sent their whole view again, so
+        // the new distance needs no chunks loaded or unloaded.
+        let view_distance = view::clamp_distance(game, client_info.view_distance);
+        {
+            let mut view = entity.get_mut::<View>().unwrap();
+            *view = View::new(view.center(), view_distance);
+        }
+        entity.get_mut::<RequestedViewDistance>().unwrap().0 = client_info.view_distance;
+        self.bridge.send(ServerPacket::JoinGame(join_game));
+
+        // The client may have restarted with different blocks.
+        if client_info.registry_digest == block::registry_digest() {
+            let dictionary = BlockDictionary::from_registry();
+            self.bridge
+                .send(ServerPacket::SetBlockDictionary(SetBlockDictionary {
+                    dictionary: dictionary.clone(),
+                }));
+            game.ecs_mut().insert_one(player, dictionary).unwrap();
+        } else {
+            game.ecs_mut().remove_one::<BlockDictionary>(player).ok();
+        }
+
+        resume::resume(game, token, &self.bridge);
+        self.state = ConnectionState::Game { player };
+        self.keepalive = Keepalive::new(Instant::now());
+        true
     }
 
     fn handle_packets(&mut self, game: &mut Game) {
@@ -117,34 +341,172 @@ impl Connection {
                         if let Some(reason) = disconnect.reason {
                             log::debug!("Reason for disconnect: {}", reason);
                         }
-                        game.ecs_mut().despawn(player).unwrap();
+                        despawn_player(game, player);
+                        self.disconnected = true;
                         return;
                     }
+                    SharedPacket::Ping(ping) => {
+                        self.bridge.send(ServerPacket::Shared(SharedPacket::Pong(
+                            keepalive::answer(ping),
+                        )));
+                    }
+                    SharedPacket::Pong(pong) => self.keepalive.on_pong(pong, Instant::now()),
                 },
-                ClientPacket::ClientInfo(_) => {
+                ClientPacket::ClientInfo(_) | ClientPacket::ResourcePackLoaded(_) => {
                     log::debug!(
-                        "Received ClientInfo during game state from {}.",
+                        "Received a login packet during game state from {}.",
                         entity.get::<Username>().unwrap().0
                     );
-                    self.disconnect(Some("received ClientInfo during game state".to_owned()));
+                    self.disconnect(
+                        game,
+                        Some("received a login packet during game state".to_owned()),
+                    );
+                    return;
                 }
                 ClientPacket::UpdatePosition(pos) => {
+                    if game.world().zone(pos.new_pos.zone).is_none() {
+                        log::debug!(
+                            "{} moved to an unknown zone; ignoring",
+                            entity.get::<Username>().unwrap().0
+                        );
+                        continue;
+                    }
                     entity.get_mut::<Pos>().unwrap().0 = pos.new_pos;
                     entity.get_mut::<Orient>().unwrap().0 = pos.new_orient;
                 }
+                ClientPacket::DialogResponse(response) => {
+                    dialog::handle_response(game, player, response);
+                }
+                ClientPacket::PlaceBlock(place) => {
+                    game.events().push(BlockEditRequested {
+                        player,
+                        pos: place.pos,
+                        edit: BlockEdit::Place(place.block),
+                    });
+                }
+                ClientPacket::BreakBlock(break_block) => {
+                    game.events().push(BlockEditRequested {
+                        player,
+                        pos: break_block.pos,
+                        edit: BlockEdit::Break,
+                    });
+                }
+                ClientPacket::UseBlock(use_block) => {
+                    game.events().push(BlockEditRequested {
+                        player,
+                        pos: use_block.pos,
+                        edit: BlockEdit::Use,
+                    });
+                }
+                ClientPacket::DigProgress(progress) => {
+                    game.events().push(DigProgressChanged {
+                        player,
+                        digging: progress.digging,
+                    });
+                }
+                ClientPacket::ChatMessage(chat) => {
+                    if let Some(message) = chat::sanitize(&chat.message) {
+                        game.events().push(ChatReceived { player, message });
+                    }
+                }
+                ClientPacket::Respawn(_) => death::respawn(game, player),
+                ClientPacket::UpdateSettings(settings) => {
+                    view::set_distance(game, player, settings.view_distance);
+                }
+                ClientPacket::SelectSlot(select) => inventory::select(game, player, select.slot),
+                ClientPacket::Landed(landed) => health::land(game, player, landed.distance),
+                ClientPacket::RequestChunks(request) => {
+                    view::send_requested_chunks(game, player, &request.positions);
+                }
             }
         }
     }
 
-    fn disconnect(&mut self, reason: Option<String>) {
+    /// Pings the client, and disconnects it if
+    /// it has stopped answering pings.
+    fn keep_alive(&mut self, game: &mut Game) {
+        if self.disconnected {
+            return;
+        }
+        let now = Instant::now();
+        if self.keepalive.is_timed_out(now) {
+            match self.state {
+                ConnectionState::Game { player } => {
+                    let username = game.ecs().get::<Username>(player).unwrap();
+                    log::info!("{} timed out.", username.0);
+                    drop(username);
+                    self.suspend(game, player);
+                }
+                ConnectionState::Login => self.disconnect(game, Some("timed out".to_owned())),
+            }
+        } else if let Some(ping) = self.keepalive.poll(now) {
+            self.bridge
+                .send(ServerPacket::Shared(SharedPacket::Ping(ping)));
+        }
+    }
+
+    /// Ends the connection, despawning the player if they joined.
+    pub(crate) fn disconnect(&mut self, game: &mut Game, reason: Option<String>) {
         self.bridge
             .send(ServerPacket::Shared(SharedPacket::Disconnect(Disconnect {
                 reason,
             })));
+        if let ConnectionState::Game { player } = self.state {
+            despawn_player(game, player);
+        }
         self.disconnected = true;
     }
 }
 
+/// Despawns a player who left, remembering their data.
+pub(crate) fn despawn_player(game: &mut Game, player: Entity) {
+    game.remember_player(player);
+    if let Ok(username) = game
+        .ecs()
+        .get::<Username>(player)
+        .map(|name| name.0.clone())
+    {
+        game.events().push(PlayerLeft { username });
+    }
+    game.ecs_mut().despawn(player).ok();
+}
+
+/// Describes why a client implementing `version` of the
+/// protocol can't connect, or returns `None` if it can.
+fn version_mismatch(version: u32) -> Option<String> {
+    match version.cmp(&PROTOCOL_VERSION) {
+        Ordering::Equal => None,
+        Ordering::Less => Some(format!(
+            "outdated client: the server implements protocol version {}, but the client implements version {}",
+            PROTOCOL_VERSION, version
+        )),
+        Ordering::Greater => Some(format!(
+            "outdated server: the server implements protocol version {}, but the client implements version {}",
+            PROTOCOL_VERSION, version
+        )),
+    }
+}
+
+/// Returns the optional features to enable for a client
+/// which supports `supported`.
+fn enabled_features(game: &Game, supported: Features) -> Features {
+    let mut turned_on = Features::NONE;
+    if game.compresses_chunks() {
+        turned_on = turned_on | Features::COMPRESSED_CHUNKS;
+    }
+    if game.caches_chunks() {
+        turned_on = turned_on | Features::CHUNK_CACHE;
+    }
+    supported & turned_on
+}
+
+/// Component marking a player to be disconnected with `reason` when their
+/// connection next ticks. Added by the `kick` command and to players who
+/// don't accept the [server rules](crate::server_rules).
+pub(crate) struct Kicked {
+    pub reason: String,
+}
+
 enum ConnectionState {
     /// We're in the login phase, still performing the handshake.
     Login,
@@ -154,3 +516,15 @@ enum ConnectionState {
         player: Entity,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_other_versions() {
+        assert_eq!(version_mismatch(PROTOCOL_VERSION), None);
+        let reason = version_mismatch(PROTOCOL_VERSION + 1).unwrap();
+        assert!(reason.starts_with("outdated server"), "{}", reason);
+    }
+}
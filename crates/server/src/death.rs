@@ -0,0 +1,104 @@
+//! Player death and respawning.
+//!
+//! [`kill`] marks a player as dead and sends them `PlayerDied` with the
+//! cause of death. Dead players stay where they died and can't edit
+//! blocks until they ask to respawn. The server then moves them back to
+//! spawn and confirms with `Respawn`.
+//!
+//! Players die when their [health](crate::health) runs out, or when
+//! killed with the `kill [player] [cause]` console command. Respawned
+//! players have full health again.
+
+use anyhow::bail;
+use common::{entity::player::Username, Pos};
+use hecs::Entity;
+use protocol::packets::{
+    server::{PlayerDied, Respawn},
+    ServerPacket,
+};
+
+use crate::{
+    command::{self, Command, CommandRegistry},
+    event,
+    game::Game,
+    generation, health, Mailbox,
+};
+
+/// The cause of death of players killed with the `kill` command.
+const KILL_COMMAND_CAUSE: &str = "Killed by an operator";
+
+/// Component marking a player who died and hasn't respawned.
+#[derive(Debug)]
+struct Dead;
+
+/// Returns whether `player` is dead.
+pub fn is_dead(game: &Game, player: Entity) -> bool {
+    game.ecs().get::<Dead>(player).is_ok()
+}
+
+/// Kills a player. Does nothing if they are already dead.
+pub fn kill(game: &mut Game, player: Entity, cause: impl Into<String>) {
+    if is_dead(game, player) {
+        return;
+    }
+    let cause = cause.into();
+    if let Ok(username) = game.ecs().get::<Username>(player) {
+        log::info!("{} died: {}", username.0, cause);
+    }
+    if let Ok(mailbox) = game.ecs().get::<Mailbox>(player) {
+        mailbox.send(ServerPacket::PlayerDied(PlayerDied {
+            cause: cause.clone(),
+        }));
+    }
+    game.ecs_mut().insert_one(player, Dead).ok();
+    game.events().push(event::PlayerDied { player, cause });
+}
+
+/// Respawns a dead player at spawn. Does nothing if they are alive.
+pub(crate) fn respawn(game: &mut Game, player: Entity) {
+    if game.ecs_mut().remove_one::<Dead>(player).is_err() {
+        return;
+    }
+    let pos = generation::spawn_pos();
+    if let Ok(mut player_pos) = game.ecs().get_mut::<Pos>(player) {
+        player_pos.0 = pos;
+    }
+    if let Ok(mailbox) = game.ecs().get::<Mailbox>(player) {
+        mailbox.send(ServerPacket::Respawn(Respawn { pos }));
+    }
+    health::heal_fully(game, player);
+}
+
+/// Registers the `kill` console command.
+pub fn register_commands(commands: &mut CommandRegistry) {
+    commands.register(KillCommand);
+}
+
+struct KillCommand;
+
+impl Command for KillCommand {
+    fn name(&self) -> &str {
+        "kill"
+    }
+
+    fn usage(&self) -> &str {
+        "[player] [cause]"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        let (player, cause) = match args {
+            [] => (command::only_player(game)?, KILL_COMMAND_CAUSE.to_owned()),
+            [username] => (
+                command::find_player(game, username)?,
+                KILL_COMMAND_CAUSE.to_owned(),
+            ),
+            [username, cause @ ..] => (command::find_player(game, username)?, cause.join(" ")),
+        };
+        if is_dead(game, player) {
+            bail!("the player is already dead");
+        }
+        kill(game, player, cause);
+        let username = game.ecs().get::<Username>(player).unwrap().0.clone();
+        Ok(format!("Killed {}", username))
+    }
+}
@@ -0,0 +1,133 @@
+//! Modal dialogs presented to players.
+//!
+//! A dialog has a title, body text, and a set of buttons.
+//! The player's choice is reported as a [`DialogResponded`] event.
+//! The [server rules](crate::server_rules) are shown in a dialog.
+
+use hecs::Entity;
+use protocol::packets::{
+    client::DialogResponse,
+    server::{CloseDialog, DialogButton, OpenDialog},
+    ServerPacket,
+};
+
+use crate::{event::DialogResponded, game::Game, Mailbox};
+
+/// A dialog to present to a player.
+#[derive(Debug, Clone)]
+pub struct Dialog {
+    title: String,
+    text: String,
+    buttons: Vec<DialogButton>,
+}
+
+impl Dialog {
+    pub fn new(title: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            text: text.into(),
+            buttons: Vec::new(),
+        }
+    }
+
+    /// Adds a button. `id` is reported in [`DialogResponded`]
+    /// if the player chooses this button.
+    pub fn button(mut self, id: u32, label: impl Into<String>) -> Self {
+        self.buttons.push(DialogButton {
+            id,
+            label: label.into(),
+        });
+        self
+    }
+}
+
+/// Component storing the dialogs a player has open.
+#[derive(Debug, Default)]
+pub struct OpenDialogs {
+    /// Open dialog IDs with the IDs of their buttons.
+    dialogs: Vec<(u32, Vec<u32>)>,
+    next_id: u32,
+}
+
+impl OpenDialogs {
+    /// Returns whether the dialog with the given ID is open.
+    pub fn is_open(&self, dialog: u32) -> bool {
+        self.dialogs.iter().any(|(id, _)| *id == dialog)
+    }
+
+    fn insert(&mut self, buttons: Vec<u32>) -> u32 {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.dialogs.push((id, buttons));
+        id
+    }
+
+    fn remove(&mut self, dialog: u32) -> Option<Vec<u32>> {
+        let index = self.dialogs.iter().position(|(id, _)| *id == dialog)?;
+        Some(self.dialogs.remove(index).1)
+    }
+}
+
+/// Opens a dialog for a player. Returns the ID of the dialog,
+/// which identifies it in the [`DialogResponded`] event.
+///
+/// Returns `None` if `player` is not a player entity.
+pub fn open_dialog(game: &Game, player: Entity, dialog: Dialog) -> Option<u32> {
+    let mailbox = game.ecs().get::<Mailbox>(player).ok()?;
+    let mut open_dialogs = game.ecs().get_mut::<OpenDialogs>(player).ok()?;
+
+    let id = open_dialogs.insert(dialog.buttons.iter().map(|button| button.id).collect());
+    mailbox.send(ServerPacket::OpenDialog(OpenDialog {
+        id,
+        title: dialog.title,
+        text: dialog.text,
+        buttons: dialog.buttons,
+    }));
+    Some(id)
+}
+
+/// Closes a dialog without waiting for the player's response.
+/// No [`DialogResponded`] event is emitted.
+pub fn close_dialog(game: &Game, player: Entity, dialog: u32) {
+    let mailbox = match game.ecs().get::<Mailbox>(player) {
+        Ok(m) => m,
+        Err(_) => return,
+    };
+    if let Ok(mut open_dialogs) = game.ecs().get_mut::<OpenDialogs>(player) {
+        if open_dialogs.remove(dialog).is_some() {
+            mailbox.send(ServerPacket::CloseDialog(CloseDialog { id: dialog }));
+        }
+    }
+}
+
+/// Handles a `DialogResponse` packet, emitting a [`DialogResponded`]
+/// event if it responds to an open dialog with one of its buttons.
+pub(crate) fn handle_response(game: &Game, player: Entity, response: DialogResponse) {
+    let mut open_dialogs = match game.ecs().get_mut::<OpenDialogs>(player) {
+        Ok(o) => o,
+        Err(_) => return,
+    };
+    let buttons = match open_dialogs.remove(response.dialog) {
+        Some(b) => b,
+        None => {
+            log::debug!("Received response to unknown dialog {}", response.dialog);
+            return;
+        }
+    };
+    if let Some(button) = response.button {
+        if !buttons.contains(&button) {
+            log::debug!(
+                "Received response to dialog {} with unknown button {}",
+                response.dialog,
+                button
+            );
+            return;
+        }
+    }
+
+    game.events().push(DialogResponded {
+        player,
+        dialog: response.dialog,
+        button: response.button,
+    });
+}
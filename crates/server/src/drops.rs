@@ -0,0 +1,269 @@
+//! Dropped items.
+//!
+//! A block broken by a player in survival mode drops an [`ItemDrop`] of
+//! the item that places it, unless the `doTileDrops` game rule is off.
+//! Players who die drop their [inventory](crate::inventory) too.
+//! Drops fall like other entities, and a player whose bounding box
+//! overlaps a drop picks it up into their [inventory](crate::inventory).
+//! Items that don't fit stay on the ground. Uncollected drops disappear
+//! after [`DROP_LIFETIME`] seconds.
+//!
+//! Players see drops through the `SpawnEntity`, `MoveEntity`, and
+//! `DespawnEntity` packets. Drops already on the ground are sent with
+//! their chunk when it enters a player's [view](crate::view).
+
+use common::{
+    blocks::{Air, Door},
+    entity::{
+        player::{GameMode, Username},
+        ItemDrop, Vel,
+    },
+    game_rules::GameRule,
+    item::{ItemId, ItemStack},
+    BlockId, BlockPos, Pos, System, SystemExecutor, WorldPos,
+};
+use glam::{vec3a, Vec2, Vec3A};
+use hecs::Entity;
+use physics::Aabb;
+use protocol::packets::{
+    server::{DespawnEntity, EntityKind, MoveEntity, SpawnEntity},
+    ServerPacket,
+};
+use rand::Rng;
+
+use crate::{
+    death, event::BlockEdited, falling::send_to_viewers, game::Game, game_mode, inventory, Mailbox,
+};
+
+/// The bounding box of a drop.
+const DROP_BOUNDS: Aabb = Aabb {
+    min: Vec3A::zero(),
+    max: glam::const_vec3a!([0.25, 0.25, 0.25]),
+};
+/// The bounding box of a player, the same as the client's.
+const PLAYER_BOUNDS: Aabb = Aabb {
+    min: Vec3A::zero(),
+    max: glam::const_vec3a!([0.5, 2., 0.5]),
+};
+/// The number of seconds until an uncollected drop disappears.
+const DROP_LIFETIME: u64 = 5 * 60;
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(DropSystem);
+}
+
+/// The tick at which a drop disappears.
+#[derive(Copy, Clone, Debug)]
+struct Expiry(u64);
+
+/// System to drop, move, and pick up items.
+struct DropSystem;
+
+impl System<Game> for DropSystem {
+    fn run(&mut self, game: &mut Game) {
+        drop_broken_blocks(game);
+        let (moved, touched) = move_drops(game);
+        for (drop, player) in touched {
+            pick_up(game, drop, player);
+        }
+        remove_expired(game);
+        send_positions(game, &moved);
+    }
+}
+
+/// Returns the item dropped when a player breaks `block`, if any.
+fn item_for_broken(block: BlockId) -> Option<ItemId> {
+    // Breaking either half of a door removes both, but drops one door.
+    if block.cast::<Door>().map_or(false, |door| door.upper) {
+        return None;
+    }
+    ItemId::for_block(block)
+}
+
+/// Drops the blocks broken by players in survival mode.
+fn drop_broken_blocks(game: &mut Game) {
+    if !game.rules().get_bool(GameRule::DoTileDrops) {
+        return;
+    }
+    let broken: Vec<(BlockPos, ItemId)> = game
+        .events()
+        .iter::<BlockEdited>()
+        .filter(|edit| edit.new.is::<Air>())
+        .filter(|edit| game_mode::game_mode(game, edit.player) == GameMode::Survival)
+        .filter_map(|edit| Some((edit.pos, item_for_broken(edit.old)?)))
+        .collect();
+
+    for (pos, item) in broken {
+        spawn(game, pos, ItemStack::new(item, 1));
+    }
+}
+
+/// Spawns a drop in the block at `pos`, thrown up slightly.
+pub fn spawn(game: &mut Game, pos: BlockPos, stack: ItemStack) -> Entity {
+    let vel = {
+        let mut rng = game.rng();
+        vec3a(rng.gen_range(-1., 1.), 3., rng.gen_range(-1., 1.))
+    };
+    let drop_pos = WorldPos::main(vec3a(
+        pos.x as f32 + 0.375,
+        pos.y as f32,
+        pos.z as f32 + 0.375,
+    ));
+    let expiry = Expiry(game.tick() + DROP_LIFETIME * game.tps() as u64);
+    let drop = game.ecs_mut().spawn((
+        Pos(drop_pos),
+        Vel(vel),
+        DROP_BOUNDS,
+        ItemDrop(stack),
+        expiry,
+    ));
+    send_to_viewers(game, drop_pos, || {
+        ServerPacket::SpawnEntity(SpawnEntity {
+            entity: drop.to_bits(),
+            pos: drop_pos,
+            orient: Vec2::zero(),
+            kind: EntityKind::ItemDrop { stack },
+        })
+    });
+    drop
+}
+
+/// Moves drops by one tick. Returns the drops that moved, and
+/// the drops touched by players along with one of those players.
+fn move_drops(game: &Game) -> (Vec<Entity>, Vec<(Entity, Entity)>) {
+    let dt = game.tick_length().as_secs_f32();
+    // Blocks are only broken in the main zone, so drops' local
+    // positions are in main-zone space.
+    let zone = game.main_zone();
+    let players: Vec<(Entity, Aabb)> = game
+        .ecs()
+        .query::<(&Pos, &Username)>()
+        .iter()
+        .filter(|&(player, _)| !death::is_dead(game, player))
+        .map(|(player, (pos, _))| (player, PLAYER_BOUNDS + game.world().to_main(pos.0)))
+        .collect();
+    let mut moved = Vec::new();
+    let mut touched = Vec::new();
+
+    let mut query = game.ecs().query::<(&mut Pos, &mut Vel, &Aabb, &ItemDrop)>();
+    for (drop, (pos, vel, &bounds, _)) in query.iter() {
+        let old_pos = pos.0;
+        physics::do_tick(bounds, &mut pos.0, &mut vel.0, dt, |block_pos| {
+            zone.block(block_pos)
+        });
+        if pos.0 != old_pos {
+            moved.push(drop);
+        }
+        if let Some(player) = toucher(bounds + pos.0.local, &players) {
+            touched.push((drop, player));
+        }
+    }
+
+    (moved, touched)
+}
+
+/// Returns the first player whose bounds overlap `bounds`,
+/// given the bounds of all players.
+fn toucher(bounds: Aabb, players: &[(Entity, Aabb)]) -> Option<Entity> {
+    players
+        .iter()
+        .find(|(_, player_bounds)| player_bounds.intersects(bounds))
+        .map(|&(player, _)| player)
+}
+
+/// Moves as many items as fit from a drop into a player's
+/// inventory, removing the drop if it becomes empty.
+fn pick_up(game: &mut Game, drop: Entity, player: Entity) {
+    let stack = match game.ecs().get::<ItemDrop>(drop) {
+        Ok(drop) => drop.0,
+        Err(_) => return,
+    };
+    let remaining = inventory::give(game, player, stack);
+    if remaining == 0 {
+        despawn(game, drop);
+    } else if let Ok(mut drop) = game.ecs().get_mut::<ItemDrop>(drop) {
+        drop.0.count = remaining;
+    }
+}
+
+fn remove_expired(game: &mut Game) {
+    let tick = game.tick();
+    let expired: Vec<Entity> = game
+        .ecs()
+        .query::<(&Expiry, &ItemDrop)>()
+        .iter()
+        .filter(|(_, (expiry, _))| expiry.0 <= tick)
+        .map(|(drop, _)| drop)
+        .collect();
+    for drop in expired {
+        despawn(game, drop);
+    }
+}
+
+fn despawn(game: &mut Game, drop: Entity) {
+    game.ecs_mut().despawn(drop).ok();
+    for (_, mailbox) in game.ecs().query::<&Mailbox>().iter() {
+        mailbox.send(ServerPacket::DespawnEntity(DespawnEntity {
+            entity: drop.to_bits(),
+        }));
+    }
+}
+
+/// Sends the new positions of drops that moved.
+fn send_positions(game: &Game, moved: &[Entity]) {
+    for &drop in moved {
+        if let Ok(pos) = game.ecs().get::<Pos>(drop) {
+            send_to_viewers(game, pos.0, || {
+                ServerPacket::MoveEntity(MoveEntity {
+                    entity: drop.to_bits(),
+                    pos: pos.0,
+                })
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::blocks::Stone;
+
+    use crate::game::test_game;
+
+    use super::*;
+
+    #[test]
+    fn doors_drop_once() {
+        let door = |upper| {
+            BlockId::new(Door {
+                open: false,
+                upper,
+                powered: false,
+            })
+        };
+        assert_eq!(item_for_broken(door(false)), ItemId::from_slug("door"));
+        assert_eq!(item_for_broken(door(true)), None);
+        assert_eq!(
+            item_for_broken(BlockId::new(Stone)),
+            ItemId::from_slug("stone")
+        );
+    }
+
+    #[test]
+    fn players_pick_up_drops_they_touch() {
+        let mut game = test_game();
+        let stone = ItemStack::new(ItemId::from_slug("stone").unwrap(), 1);
+        let drop = spawn(&mut game, BlockPos { x: 4, y: 0, z: 4 }, stone);
+        let player = game.ecs_mut().spawn((
+            Pos(WorldPos::main(vec3a(4., 0., 4.))),
+            Username("picker".to_owned()),
+            common::item::Inventory::new(),
+        ));
+
+        let (_, touched) = move_drops(&game);
+        assert_eq!(touched, vec![(drop, player)]);
+        pick_up(&mut game, drop, player);
+        assert!(game.ecs().get::<ItemDrop>(drop).is_err());
+        let inventory = game.ecs().get::<common::item::Inventory>(player).unwrap();
+        assert_eq!(inventory.slot(0), Some(stone));
+    }
+}
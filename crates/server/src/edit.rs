@@ -0,0 +1,186 @@
+//! Block edits made by players.
+//!
+//! Connections turn `PlaceBlock`, `BreakBlock`, and `UseBlock` packets
+//! into [`BlockEditRequested`] events. [`EditSystem`] validates the
+//! edits following the rules in [`common::edit`] and applies them to
+//! the main zone, and [`BroadcastSystem`] sends every changed block to
+//! the players who can see it. Players can only edit blocks within
+//! their [reach](crate::game_mode), [dead](crate::death) players
+//! can't edit blocks, and edits that would change blocks in a
+//! [protected region](crate::protection) need its permission.
+//!
+//! While players dig, they report the crack stage of the block with
+//! `DigProgress`, and [`DigSystem`] shares it with the other players
+//! who can see the block so they can draw the cracks.
+
+use common::{
+    edit::{BlockEdit, Digging, DIG_STAGES},
+    entity::player::View,
+    BlockId, BlockPos, System, SystemExecutor,
+};
+use hecs::Entity;
+use protocol::packets::{
+    server::{BlockUpdate, EntityDigProgress},
+    ServerPacket,
+};
+use worldgen::ColumnPos;
+
+use crate::{
+    death,
+    event::{BlockChanged, BlockEditRequested, BlockEdited, DigProgressChanged},
+    game::Game,
+    game_mode,
+    protection::Permission,
+    Mailbox,
+};
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(EditSystem);
+    systems.add(BroadcastSystem);
+    systems.add(DigSystem);
+}
+
+/// System to apply edits requested by players.
+///
+/// A rejected edit is answered with the unchanged blocks
+/// so the player can undo any prediction it made.
+struct EditSystem;
+
+impl System<Game> for EditSystem {
+    fn run(&mut self, game: &mut Game) {
+        let requests: Vec<_> = game
+            .events()
+            .iter::<BlockEditRequested>()
+            .map(|request| (request.player, request.pos, request.edit))
+            .collect();
+
+        for (player, pos, edit) in requests {
+            let allowed = game.is_column_generated(ColumnPos::from_chunk(pos.chunk()))
+                && !death::is_dead(game, player)
+                && game_mode::can_reach(game, player, pos);
+            let changes = if allowed {
+                edit.apply(pos, |pos| game.main_zone().block(pos))
+            } else {
+                None
+            };
+            // Doors span two blocks, so check every block the edit changes.
+            let permission = Permission::required_by(edit);
+            let changes = changes.filter(|changes| {
+                changes
+                    .iter()
+                    .all(|&(pos, _)| game.protected_regions().allows(pos, permission))
+            });
+
+            match changes {
+                Some(changes) => {
+                    for (pos, block) in changes {
+                        let old = match game.main_zone().block(pos) {
+                            Some(old) => old,
+                            None => continue,
+                        };
+                        if game.set_block(pos, block).is_ok() {
+                            game.events().push(BlockEdited {
+                                player,
+                                pos,
+                                old,
+                                new: block,
+                            });
+                        }
+                    }
+                }
+                None => {
+                    log::debug!("Rejecting {:?} at {:?}", edit, pos);
+                    for &pos in &BlockEdit::affected_positions(pos) {
+                        if let Some(current) = game.main_zone().block(pos) {
+                            send_block_update(game, player, pos, current);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// System to send changed blocks to players
+/// whose view contains them.
+struct BroadcastSystem;
+
+impl System<Game> for BroadcastSystem {
+    fn run(&mut self, game: &mut Game) {
+        let mut changes = Vec::new_in(game.bump());
+        changes.extend(game.events().iter::<BlockChanged>().copied());
+        if changes.is_empty() {
+            return;
+        }
+
+        for (_, (view, mailbox)) in game.ecs().query::<(&View, &Mailbox)>().iter() {
+            for change in &changes {
+                if view.contains(change.pos.chunk()) {
+                    mailbox.send(ServerPacket::BlockUpdate(BlockUpdate {
+                        pos: change.pos,
+                        block: change.new,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+/// System to share the blocks players dig with the other
+/// players whose view contains them.
+///
+/// The block each player digs is kept in their [`Digging`]
+/// component, so players who saw them start digging are
+/// told when they stop.
+struct DigSystem;
+
+impl System<Game> for DigSystem {
+    fn run(&mut self, game: &mut Game) {
+        let changes: Vec<_> = game
+            .events()
+            .iter::<DigProgressChanged>()
+            .map(|change| (change.player, change.digging))
+            .collect();
+
+        for (player, digging) in changes {
+            // Players can only dig blocks they could break.
+            let digging = digging.filter(|digging| {
+                digging.stage < DIG_STAGES
+                    && !death::is_dead(game, player)
+                    && game_mode::can_reach(game, player, digging.pos)
+            });
+            let previous = game.ecs().get::<Digging>(player).ok().map(|d| *d);
+            if digging == previous {
+                continue;
+            }
+            match digging {
+                Some(digging) => {
+                    game.ecs_mut().insert_one(player, digging).ok();
+                }
+                None => {
+                    game.ecs_mut().remove_one::<Digging>(player).ok();
+                }
+            }
+
+            let chunks: Vec<_> = previous
+                .iter()
+                .chain(&digging)
+                .map(|digging| digging.pos.chunk())
+                .collect();
+            for (other, (view, mailbox)) in game.ecs().query::<(&View, &Mailbox)>().iter() {
+                if other != player && chunks.iter().any(|&chunk| view.contains(chunk)) {
+                    mailbox.send(ServerPacket::EntityDigProgress(EntityDigProgress {
+                        entity: player.to_bits(),
+                        digging,
+                    }));
+                }
+            }
+        }
+    }
+}
+
+fn send_block_update(game: &Game, player: Entity, pos: BlockPos, block: BlockId) {
+    if let Ok(mailbox) = game.ecs().get::<Mailbox>(player) {
+        mailbox.send(ServerPacket::BlockUpdate(BlockUpdate { pos, block }));
+    }
+}
@@ -1,5 +1,153 @@
+use common::{
+    edit::{BlockEdit, Digging},
+    entity::player::View,
+    world::BlockPos,
+    BlockId,
+};
 use hecs::Entity;
+use worldgen::ColumnPos;
+
+use crate::schedule::ScheduledEvent;
 
 pub struct PlayerJoined {
     pub player: Entity,
 }
+
+/// A player whose connection was lost came back and
+/// [resumed](crate::resume) their session.
+pub struct PlayerResumed {
+    pub player: Entity,
+}
+
+/// A player changed their view distance with `UpdateSettings`.
+/// Their [`View`] already has the new distance.
+pub struct ViewDistanceChanged {
+    pub player: Entity,
+    /// The view before the change.
+    pub old_view: View,
+}
+
+/// A player left the game. Their entity has already been despawned.
+pub struct PlayerLeft {
+    pub username: String,
+}
+
+/// A player responded to a dialog opened
+/// with [`open_dialog`](crate::dialog::open_dialog).
+pub struct DialogResponded {
+    pub player: Entity,
+    /// The ID of the dialog.
+    pub dialog: u32,
+    /// The ID of the chosen button, or `None`
+    /// if the player dismissed the dialog.
+    pub button: Option<u32>,
+}
+
+/// A chunk column finished generating
+/// and was added to the main zone.
+pub struct ColumnGenerated {
+    pub pos: ColumnPos,
+}
+
+/// A block in the main zone changed.
+/// Pushed by [`Game::set_block`](crate::game::Game::set_block).
+#[derive(Copy, Clone, Debug)]
+pub struct BlockChanged {
+    pub pos: BlockPos,
+    pub old: BlockId,
+    pub new: BlockId,
+}
+
+/// A player changed a block with an edit applied by the [`edit`](crate::edit)
+/// module. Pushed in addition to [`BlockChanged`] for each changed block.
+#[derive(Copy, Clone, Debug)]
+pub struct BlockEdited {
+    pub player: Entity,
+    pub pos: BlockPos,
+    pub old: BlockId,
+    pub new: BlockId,
+}
+
+/// A scheduled [block update](crate::block_update)
+/// is due at `pos`.
+#[derive(Copy, Clone, Debug)]
+pub struct BlockUpdateDue {
+    pub pos: BlockPos,
+}
+
+/// A block tick scheduled with
+/// [`Game::schedule_block_tick`](crate::game::Game::schedule_block_tick)
+/// is due at `pos`.
+#[derive(Copy, Clone, Debug)]
+pub struct BlockTickDue {
+    pub pos: BlockPos,
+}
+
+/// A block was chosen for a [random tick](crate::random_tick).
+#[derive(Copy, Clone, Debug)]
+pub struct RandomTick {
+    pub pos: BlockPos,
+    pub block: BlockId,
+}
+
+/// A player requested a block edit with
+/// `PlaceBlock` or `BreakBlock`. Applied by the
+/// [`edit`](crate::edit) module if allowed.
+pub struct BlockEditRequested {
+    pub player: Entity,
+    pub pos: BlockPos,
+    pub edit: BlockEdit,
+}
+
+/// A player reported with `DigProgress` that they started or
+/// stopped digging a block, or that its crack stage changed.
+/// Shared with other players by the [`edit`](crate::edit) module.
+pub struct DigProgressChanged {
+    pub player: Entity,
+    pub digging: Option<Digging>,
+}
+
+/// An occurrence of a [scheduled event](crate::schedule) started.
+pub struct ScheduledEventStarted {
+    pub event: ScheduledEvent,
+}
+
+/// An occurrence of a [scheduled event](crate::schedule)
+/// ended after lasting its duration.
+pub struct ScheduledEventEnded {
+    pub event: ScheduledEvent,
+}
+
+/// A player sent a chat message. The message is trimmed,
+/// non-empty, and at most `MAX_CHAT_MESSAGE_LENGTH` characters long.
+pub struct ChatReceived {
+    pub player: Entity,
+    pub message: String,
+}
+
+/// A player died. Pushed by [`death::kill`](crate::death::kill).
+pub struct PlayerDied {
+    pub player: Entity,
+    /// Describes how the player died, e.g. "Killed by an operator".
+    pub cause: String,
+}
+
+/// The world should be saved at the end of the tick, regardless
+/// of when it was last autosaved. Ignored if the world is not
+/// [saved](crate::game::Game::is_saved).
+#[derive(Copy, Clone, Debug)]
+pub struct SaveRequested;
+
+/// The world should be saved at the end of the tick and then
+/// [backed up](crate::backup). Ignored if the world is not saved.
+#[derive(Copy, Clone, Debug)]
+pub struct BackupRequested;
+
+/// Something hurt an entity. Applied by the [`health`](crate::health)
+/// module to entities with a `Health` component.
+pub struct Damage {
+    pub entity: Entity,
+    pub amount: u32,
+    /// The cause of death if the damage is fatal.
+    pub cause: String,
+}
@@ -0,0 +1,204 @@
+//! Blocks affected by gravity.
+//!
+//! When a [block update](crate::block_update) finds sand or gravel with
+//! nothing solid below it, the block turns into a [`FallingBlock`]
+//! entity. [`FallingSystem`] simulates these entities with the `physics`
+//! crate and turns them back into blocks when they land.
+//!
+//! Players see falling blocks through the `SpawnFallingBlock`,
+//! `MoveEntity`, and `DespawnEntity` packets.
+
+use common::{
+    blocks::{Air, Gravel, Sand},
+    entity::{player::View, FallingBlock, Vel},
+    BlockId, BlockPos, ChunkPos, Pos, System, SystemExecutor, WorldPos, Zone,
+};
+use glam::{vec3a, Vec3A};
+use hecs::Entity;
+use physics::Aabb;
+use protocol::packets::{
+    server::{DespawnEntity, MoveEntity, SpawnFallingBlock},
+    ServerPacket,
+};
+
+use crate::{event::BlockUpdateDue, game::Game, Mailbox};
+
+/// The bounding box of a falling block. Slightly narrower
+/// than a block so it does not catch on its neighbors.
+const BOUNDS: Aabb = Aabb {
+    min: Vec3A::zero(),
+    max: glam::const_vec3a!([0.98, 1., 0.98]),
+};
+
+/// The number of physics steps per tick. Collisions are only
+/// detected at the end of each step, so falling blocks would pass
+/// through floors at high speeds if they moved a full tick at once.
+const SUBSTEPS: u32 = 4;
+
+/// How far above the position it came to rest a falling block may
+/// settle if that position is occupied, e.g. by a block placed
+/// while it was falling.
+const MAX_LANDING_OFFSET: i32 = 2;
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(FallingSystem);
+}
+
+/// System to turn unsupported blocks into falling blocks, simulate
+/// them, and place them again when they land.
+struct FallingSystem;
+
+impl System<Game> for FallingSystem {
+    fn run(&mut self, game: &mut Game) {
+        start_falling(game);
+        for (entity, pos, block) in simulate(game) {
+            land(game, entity, pos, block);
+        }
+        send_positions(game);
+    }
+}
+
+/// Returns whether a block falls when nothing supports it.
+fn has_gravity(block: BlockId) -> bool {
+    block.is::<Sand>() || block.is::<Gravel>()
+}
+
+/// Replaces blocks that lost their support with falling blocks.
+fn start_falling(game: &mut Game) {
+    let due: Vec<BlockPos> = game
+        .events()
+        .iter::<BlockUpdateDue>()
+        .map(|event| event.pos)
+        .collect();
+
+    for pos in due {
+        let block = match game.main_zone().block(pos) {
+            Some(block) if has_gravity(block) => block,
+            _ => continue,
+        };
+        let below = game.main_zone().block(pos.offset(0, -1, 0));
+        if below.map_or(true, BlockId::is_solid) {
+            continue;
+        }
+
+        game.set_block(pos, BlockId::new(Air)).ok();
+        let entity_pos = WorldPos::main(pos.bottom_center());
+        let entity =
+            game.ecs_mut()
+                .spawn((Pos(entity_pos), Vel::default(), BOUNDS, FallingBlock(block)));
+        send_to_viewers(game, entity_pos, || {
+            ServerPacket::SpawnFallingBlock(SpawnFallingBlock {
+                entity: entity.to_bits(),
+                pos: entity_pos,
+                block,
+            })
+        });
+        log::trace!("Block at {:?} started falling as {:?}", pos, entity);
+    }
+}
+
+/// Advances falling blocks by one tick, returning
+/// those that landed and their positions.
+fn simulate(game: &Game) -> Vec<(Entity, Vec3A, BlockId)> {
+    let dt = game.tick_length().as_secs_f32() / SUBSTEPS as f32;
+    // Blocks only fall in the main zone.
+    let zone = game.main_zone();
+    let mut landed = Vec::new();
+
+    let mut query = game
+        .ecs()
+        .query::<(&mut Pos, &mut Vel, &Aabb, &FallingBlock)>();
+    for (entity, (pos, vel, &bounds, &FallingBlock(block))) in query.iter() {
+        for _ in 0..SUBSTEPS {
+            // Physics treats blocks outside the zone as solid,
+            // so falling blocks come to rest at its bottom.
+            physics::do_tick(bounds, &mut pos.0, &mut vel.0, dt, |block_pos| {
+                zone.block(block_pos)
+            });
+            if physics::is_on_ground(pos.0, |block_pos| zone.block(block_pos)) {
+                landed.push((entity, pos.0.local, block));
+                break;
+            }
+        }
+    }
+
+    landed
+}
+
+/// Removes a falling block that landed at `pos`
+/// and places its block there.
+fn land(game: &mut Game, entity: Entity, pos: Vec3A, block: BlockId) {
+    game.ecs_mut().despawn(entity).ok();
+    for (_, mailbox) in game.ecs().query::<&Mailbox>().iter() {
+        mailbox.send(ServerPacket::DespawnEntity(DespawnEntity {
+            entity: entity.to_bits(),
+        }));
+    }
+
+    let rest_pos = BlockPos::from_pos(pos + vec3a(0., 0.5, 0.));
+    match landing_pos(game.main_zone(), rest_pos) {
+        Some(landing_pos) => {
+            game.set_block(landing_pos, block).ok();
+        }
+        None => log::debug!(
+            "Falling {} at {:?} has nowhere to land; discarding it",
+            block.descriptor().slug(),
+            rest_pos
+        ),
+    }
+}
+
+/// Returns where to place a falling block that came to rest
+/// at `pos`: the first position at or slightly above `pos`
+/// that contains air.
+fn landing_pos(zone: &Zone, pos: BlockPos) -> Option<BlockPos> {
+    (0..=MAX_LANDING_OFFSET)
+        .map(|dy| pos.offset(0, dy, 0))
+        .find(|&pos| zone.block(pos).map_or(false, |block| block.is::<Air>()))
+}
+
+fn send_positions(game: &Game) {
+    for (entity, (pos, _)) in game.ecs().query::<(&Pos, &FallingBlock)>().iter() {
+        send_to_viewers(game, pos.0, || {
+            ServerPacket::MoveEntity(MoveEntity {
+                entity: entity.to_bits(),
+                pos: pos.0,
+            })
+        });
+    }
+}
+
+/// Sends a packet to each player whose view contains `pos`.
+pub(crate) fn send_to_viewers(game: &Game, pos: WorldPos, packet: impl Fn() -> ServerPacket) {
+    let chunk = ChunkPos::from_pos(game.world().to_main(pos));
+    for (_, (view, mailbox)) in game.ecs().query::<(&View, &Mailbox)>().iter() {
+        if view.contains(chunk) {
+            mailbox.send(packet());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::blocks::Stone;
+
+    use crate::game::test_zone;
+
+    use super::*;
+
+    #[test]
+    fn lands_on_first_free_block() {
+        let mut zone = test_zone();
+
+        let pos = |y| BlockPos { x: 3, y, z: 3 };
+        assert_eq!(landing_pos(&zone, pos(1)), Some(pos(1)));
+
+        for y in 1..=2 {
+            zone.set_block(pos(y), BlockId::new(Stone)).unwrap();
+        }
+        assert_eq!(landing_pos(&zone, pos(1)), Some(pos(3)));
+
+        zone.set_block(pos(3), BlockId::new(Stone)).unwrap();
+        assert_eq!(landing_pos(&zone, pos(1)), None);
+    }
+}
@@ -1,9 +1,35 @@
-use std::cell::{RefCell, RefMut};
+use std::{
+    cell::{RefCell, RefMut},
+    time::Duration,
+};
 
 use bumpalo::Bump;
-use common::{event::EventBus, World, Zone};
+use common::{
+    edit::Reach,
+    entity::player::{Experience, Username},
+    event::EventBus,
+    game_rules::GameRules,
+    weather::Weather,
+    world::{BlockOutOfBounds, BlockPos},
+    BlockId, World, Zone,
+};
+use hashbrown::{HashMap, HashSet};
+use hecs::Entity;
+use protocol::resource_pack::ResourcePack;
 use rand::{Rng, SeedableRng};
 use rand_pcg::Pcg64Mcg;
+use worldgen::ColumnPos;
+
+use crate::{
+    block_update::{BlockTickQueue, BlockUpdateQueue},
+    event::BlockChanged,
+    health::FallSettings,
+    protection::ProtectedRegions,
+    resume::Suspended,
+    save::PlayerData,
+    tag::TagIndex,
+    DEFAULT_MAX_VIEW_DISTANCE, SLOW_MOTION_TPS, TPS,
+};
 
 /// Uberstruct containing the entire game state.
 ///
@@ -17,12 +43,84 @@ pub struct Game {
     /// The world containing all zones.
     world: World<Zone>,
 
+    /// The [rules](crate::server_rules) players accept before playing.
+    server_rules: Option<String>,
+
+    /// Columns of the main zone that have been generated.
+    /// Other columns are empty until world generation reaches them.
+    generated_columns: HashSet<ColumnPos>,
+
+    /// Positions awaiting a block update.
+    block_updates: BlockUpdateQueue,
+    /// Block ticks scheduled for later game ticks.
+    block_ticks: BlockTickQueue,
+
+    /// The current weather.
+    weather: Weather,
+    /// The world's time in seconds of game time.
+    time: f64,
+    /// The world's game rules.
+    rules: GameRules,
+    /// Regions where players may not edit some blocks.
+    protected_regions: ProtectedRegions,
+
+    /// Whether chunk data is compressed for players who support it.
+    compress_chunks: bool,
+    /// Whether players who support it may load chunks from their cache.
+    cache_chunks: bool,
+    /// Identifies the world to clients caching its chunks.
+    world_id: u64,
+    /// The resource pack players must load to join.
+    resource_pack: Option<ResourcePack>,
+
+    /// How far players can reach in each game mode.
+    reach: Reach,
+    /// How falls hurt players.
+    fall_settings: FallSettings,
+    /// The largest view distance players may choose.
+    max_view_distance: u32,
+    /// Lowers the largest view distance while the server
+    /// is under load. See [`view_scaling`](crate::view_scaling).
+    view_distance_limit: Option<u32>,
+
+    /// Whether the world is saved to disk.
+    saved: bool,
+    /// Whether the server should stop after the current tick.
+    stop_requested: bool,
+
+    /// The data of players as of when they last left,
+    /// by username. Includes players loaded from the save.
+    offline_players: HashMap<String, PlayerData>,
+    /// Players whose connections were lost, who
+    /// may still [resume](crate::resume) their sessions.
+    suspended: Suspended,
+
+    /// Maps [tags](crate::tag) to the entities that have them.
+    tags: TagIndex,
+
+    /// Multiplies resources gained by players. Raised
+    /// during scheduled double-resource periods.
+    resource_multiplier: u32,
+
     /// The event bus.
     events: RefCell<EventBus>,
 
     /// The bump allocator.
     bump: Bump,
 
+    /// The number of ticks run so far.
+    tick: u64,
+
+    /// The real time the last tick took to run.
+    last_tick_duration: Duration,
+
+    /// The number of ticks per second of game time.
+    tps: u32,
+
+    /// Whether ticks run at [`SLOW_MOTION_TPS`] in real time
+    /// for debugging, regardless of `tps`.
+    slow_motion: bool,
+
     /// The non-cryptographic RNG used for game operations.
     rng: RefCell<Pcg64Mcg>,
 }
@@ -39,8 +137,34 @@ impl Game {
         Self {
             ecs,
             world,
+            server_rules: None,
+            generated_columns: HashSet::new(),
+            block_updates: BlockUpdateQueue::new(),
+            block_ticks: BlockTickQueue::new(),
+            weather: Weather::Clear,
+            time: 0.,
+            rules: GameRules::new(),
+            protected_regions: ProtectedRegions::new(),
+            compress_chunks: false,
+            cache_chunks: false,
+            world_id: 0,
+            resource_pack: None,
+            reach: Reach::default(),
+            fall_settings: FallSettings::default(),
+            max_view_distance: DEFAULT_MAX_VIEW_DISTANCE,
+            view_distance_limit: None,
+            saved: false,
+            stop_requested: false,
+            offline_players: HashMap::new(),
+            suspended: Suspended::default(),
+            tags: TagIndex::default(),
+            resource_multiplier: 1,
             events,
             bump,
+            tick: 0,
+            last_tick_duration: Duration::default(),
+            tps: TPS,
+            slow_motion: false,
             rng,
         }
     }
@@ -75,11 +199,348 @@ impl Game {
         self.world_mut().main_zone_mut()
     }
 
+    /// Sets a block in the main zone, pushing a [`BlockChanged`] event
+    /// and scheduling updates for the block and its neighbors.
+    /// Returns an error if `pos` is outside the main zone.
+    pub fn set_block(&mut self, pos: BlockPos, block: BlockId) -> Result<(), BlockOutOfBounds> {
+        let zone = self.main_zone_mut();
+        let old = zone.block(pos);
+        zone.set_block(pos, block)?;
+        if let Some(old) = old {
+            self.events().push(BlockChanged {
+                pos,
+                old,
+                new: block,
+            });
+        }
+
+        self.schedule_block_update(pos);
+        for &adjacent in &pos.adjacent() {
+            self.schedule_block_update(adjacent);
+        }
+        Ok(())
+    }
+
+    /// Schedules a [block update](crate::block_update) at `pos`.
+    pub fn schedule_block_update(&mut self, pos: BlockPos) {
+        self.block_updates.schedule(pos);
+    }
+
+    pub(crate) fn block_updates_mut(&mut self) -> &mut BlockUpdateQueue {
+        &mut self.block_updates
+    }
+
+    /// Schedules a [`BlockTickDue`](crate::event::BlockTickDue) event at
+    /// `pos` after `delay` ticks. Does nothing if a tick is already
+    /// scheduled there.
+    pub fn schedule_block_tick(&mut self, pos: BlockPos, delay: u64) {
+        let due = self.tick() + delay;
+        self.block_ticks.schedule(pos, due);
+    }
+
+    /// Returns whether a block tick is scheduled at `pos`.
+    pub fn is_block_tick_scheduled(&self, pos: BlockPos) -> bool {
+        self.block_ticks.is_scheduled(pos)
+    }
+
+    pub(crate) fn block_ticks_mut(&mut self) -> &mut BlockTickQueue {
+        &mut self.block_ticks
+    }
+
+    /// Returns the [rules](crate::server_rules) players
+    /// accept before playing, if the server has any.
+    pub fn server_rules(&self) -> Option<&str> {
+        self.server_rules.as_deref()
+    }
+
+    pub(crate) fn set_server_rules(&mut self, rules: Option<String>) {
+        self.server_rules = rules;
+    }
+
+    /// Returns whether the chunks in the given column
+    /// of the main zone have been generated.
+    pub fn is_column_generated(&self, pos: ColumnPos) -> bool {
+        self.generated_columns.contains(&pos)
+    }
+
+    /// Iterates over the generated columns of the main zone.
+    pub fn generated_columns(&self) -> impl Iterator<Item = ColumnPos> + '_ {
+        self.generated_columns.iter().copied()
+    }
+
+    /// Marks a column of the main zone as generated.
+    pub fn mark_column_generated(&mut self, pos: ColumnPos) {
+        self.generated_columns.insert(pos);
+    }
+
+    /// Returns whether chunk data is compressed for players who support it.
+    pub fn compresses_chunks(&self) -> bool {
+        self.compress_chunks
+    }
+
+    pub(crate) fn set_compress_chunks(&mut self, compress: bool) {
+        self.compress_chunks = compress;
+    }
+
+    /// Returns whether players who support it
+    /// may load chunks from their cache.
+    pub fn caches_chunks(&self) -> bool {
+        self.cache_chunks
+    }
+
+    pub(crate) fn set_cache_chunks(&mut self, cache: bool) {
+        self.cache_chunks = cache;
+    }
+
+    /// Returns the ID identifying the world to clients caching its chunks.
+    pub fn world_id(&self) -> u64 {
+        self.world_id
+    }
+
+    pub(crate) fn set_world_id(&mut self, world_id: u64) {
+        self.world_id = world_id;
+    }
+
+    /// Returns the largest view distance players may choose.
+    pub fn max_view_distance(&self) -> u32 {
+        self.max_view_distance
+    }
+
+    pub(crate) fn set_max_view_distance(&mut self, distance: u32) {
+        self.max_view_distance = distance;
+    }
+
+    /// Returns the largest view distance players get right now: the
+    /// maximum, unless it is [limited](crate::view_scaling) under load.
+    pub fn effective_max_view_distance(&self) -> u32 {
+        match self.view_distance_limit {
+            Some(limit) => limit.min(self.max_view_distance),
+            None => self.max_view_distance,
+        }
+    }
+
+    /// Returns the view distance limit set while
+    /// the server is under load, if any.
+    pub fn view_distance_limit(&self) -> Option<u32> {
+        self.view_distance_limit
+    }
+
+    pub(crate) fn set_view_distance_limit(&mut self, limit: Option<u32>) {
+        self.view_distance_limit = limit;
+    }
+
+    /// Returns the [resource pack](crate::resource_pack)
+    /// players must load to join, if any.
+    pub fn resource_pack(&self) -> Option<&ResourcePack> {
+        self.resource_pack.as_ref()
+    }
+
+    pub(crate) fn set_resource_pack(&mut self, pack: Option<ResourcePack>) {
+        self.resource_pack = pack;
+    }
+
+    /// Returns how far players can reach in each game mode.
+    /// See the [`game_mode`](crate::game_mode) module.
+    pub fn reach(&self) -> Reach {
+        self.reach
+    }
+
+    pub(crate) fn set_reach(&mut self, reach: Reach) {
+        self.reach = reach;
+    }
+
+    /// Returns how falls hurt players.
+    /// See the [`health`](crate::health) module.
+    pub fn fall_settings(&self) -> FallSettings {
+        self.fall_settings
+    }
+
+    pub(crate) fn set_fall_settings(&mut self, settings: FallSettings) {
+        self.fall_settings = settings;
+    }
+
+    /// Returns whether the world is saved to disk. If it is,
+    /// pushing [`SaveRequested`](crate::event::SaveRequested)
+    /// saves it at the end of the tick.
+    pub fn is_saved(&self) -> bool {
+        self.saved
+    }
+
+    pub(crate) fn set_saved(&mut self, saved: bool) {
+        self.saved = saved;
+    }
+
+    /// Returns whether the server stops after the current tick.
+    pub fn is_stop_requested(&self) -> bool {
+        self.stop_requested
+    }
+
+    /// Makes the server stop after the current tick. Players
+    /// still connected then are disconnected.
+    pub fn request_stop(&mut self) {
+        self.stop_requested = true;
+    }
+
+    /// Gets the current weather.
+    pub fn weather(&self) -> Weather {
+        self.weather
+    }
+
+    /// Sets the weather. Players are told about the
+    /// change by the [weather system](crate::weather).
+    pub fn set_weather(&mut self, weather: Weather) {
+        self.weather = weather;
+    }
+
+    /// Gets the world's time in seconds of game time.
+    /// See [`common::time`].
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Sets the world's time. Players are told about the
+    /// change by the [time system](crate::time).
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    /// Gets the world's game rules.
+    pub fn rules(&self) -> &GameRules {
+        &self.rules
+    }
+
+    /// Gets the game rules for changing them. Players are told
+    /// about changes by the [game rules system](crate::game_rules).
+    pub fn rules_mut(&mut self) -> &mut GameRules {
+        &mut self.rules
+    }
+
+    /// Gets the world's [protected regions](crate::protection).
+    pub fn protected_regions(&self) -> &ProtectedRegions {
+        &self.protected_regions
+    }
+
+    pub fn protected_regions_mut(&mut self) -> &mut ProtectedRegions {
+        &mut self.protected_regions
+    }
+
+    /// Gets the factor by which resources gained by
+    /// players are multiplied. Usually 1.
+    pub fn resource_multiplier(&self) -> u32 {
+        self.resource_multiplier
+    }
+
+    pub(crate) fn set_resource_multiplier(&mut self, multiplier: u32) {
+        self.resource_multiplier = multiplier;
+    }
+
+    /// Gets the data a player had when they last left,
+    /// or the default data if they never joined.
+    pub fn offline_player_data(&self, username: &str) -> PlayerData {
+        self.offline_players
+            .get(username)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn set_offline_players(&mut self, players: HashMap<String, PlayerData>) {
+        self.offline_players = players;
+    }
+
+    /// Records the current data of a player who is leaving.
+    pub(crate) fn remember_player(&mut self, player: Entity) {
+        if let Some((username, data)) = player_data(&self.ecs, player) {
+            self.offline_players.insert(username, data);
+        }
+    }
+
+    /// Gets the players whose connections were lost.
+    pub fn suspended(&self) -> &Suspended {
+        &self.suspended
+    }
+
+    pub(crate) fn suspended_mut(&mut self) -> &mut Suspended {
+        &mut self.suspended
+    }
+
+    /// Gets the index of entities by tag.
+    pub fn tags(&self) -> &TagIndex {
+        &self.tags
+    }
+
+    pub(crate) fn tags_mut(&mut self) -> &mut TagIndex {
+        &mut self.tags
+    }
+
+    /// Returns the data of all players, online or not.
+    pub fn all_player_data(&self) -> HashMap<String, PlayerData> {
+        let mut players = self.offline_players.clone();
+        for (player, _) in self.ecs.query::<&Username>().iter() {
+            players.extend(player_data(&self.ecs, player));
+        }
+        players
+    }
+
     /// Gets the event bus, used to process or enqueue events.
     pub fn events(&self) -> RefMut<EventBus> {
         self.events.borrow_mut()
     }
 
+    /// Gets the number of ticks run so far.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Advances the tick counter. Called at the start of each tick.
+    pub(crate) fn advance_tick(&mut self) {
+        self.tick += 1;
+    }
+
+    /// Gets the real time the last tick took to run, not
+    /// counting the time the server slept between ticks.
+    pub fn last_tick_duration(&self) -> Duration {
+        self.last_tick_duration
+    }
+
+    pub(crate) fn set_last_tick_duration(&mut self, duration: Duration) {
+        self.last_tick_duration = duration;
+    }
+
+    /// Gets the number of ticks per second of game time.
+    pub fn tps(&self) -> u32 {
+        self.tps
+    }
+
+    /// Gets the game time simulated by each tick. Systems
+    /// should use this instead of assuming [`TPS`].
+    pub fn tick_length(&self) -> Duration {
+        Duration::from_secs(1) / self.tps
+    }
+
+    pub(crate) fn set_tps(&mut self, tps: u32) {
+        assert!(tps > 0, "TPS must be positive");
+        self.tps = tps;
+    }
+
+    /// Gets the real time between ticks. This is the
+    /// [tick length](Game::tick_length) unless slow motion is on.
+    pub fn real_tick_length(&self) -> Duration {
+        if self.slow_motion {
+            Duration::from_secs(1) / SLOW_MOTION_TPS
+        } else {
+            self.tick_length()
+        }
+    }
+
+    /// Returns whether slow motion is on.
+    pub fn is_slow_motion(&self) -> bool {
+        self.slow_motion
+    }
+
+    pub(crate) fn set_slow_motion(&mut self, slow_motion: bool) {
+        self.slow_motion = slow_motion;
+    }
+
     /// Gets the _non-cryptocraphic_ RNG for game logic.
     pub fn rng(&self) -> RefMut<impl Rng> {
         self.rng.borrow_mut()
@@ -94,3 +555,25 @@ impl Game {
         &mut self.bump
     }
 }
+
+/// Gets the username and current data of a player.
+fn player_data(ecs: &hecs::World, player: Entity) -> Option<(String, PlayerData)> {
+    let username = ecs.get::<Username>(player).ok()?;
+    let xp = ecs.get::<Experience>(player).ok()?;
+    Some((username.0.clone(), PlayerData { xp: xp.total }))
+}
+
+/// Creates a zone of a single empty chunk at the origin.
+#[cfg(test)]
+pub(crate) fn test_zone() -> Zone {
+    let chunk_pos = common::ChunkPos { x: 0, y: 0, z: 0 };
+    let mut builder = Zone::builder(chunk_pos, chunk_pos);
+    builder.add_chunk(chunk_pos, common::Chunk::new()).unwrap();
+    builder.build().ok().unwrap()
+}
+
+/// Creates a game whose main zone is a [`test_zone`].
+#[cfg(test)]
+pub(crate) fn test_game() -> Game {
+    Game::new(test_zone())
+}
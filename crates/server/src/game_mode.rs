@@ -0,0 +1,195 @@
+//! Game modes and how far players can reach.
+//!
+//! Players join in survival mode. Each game mode has a reach, the
+//! distance from a player's eyes within which they can edit blocks,
+//! configured with `VOLTZ_SURVIVAL_REACH` and `VOLTZ_CREATIVE_REACH`.
+//! Players are sent their game mode and reach with `SetGameMode` when
+//! they join and whenever it changes, so their targeting agrees with
+//! the [edit](crate::edit) validation done here.
+//!
+//! The `gamemode <survival|creative> [player]` console
+//! [command](crate::command) changes a player's game mode.
+
+use std::env;
+
+use anyhow::{bail, Context};
+use common::{
+    edit::{self, Reach},
+    entity::player::{self, GameMode, Username},
+    BlockPos, Pos, System, SystemExecutor,
+};
+use hecs::Entity;
+use protocol::packets::{server::SetGameMode, ServerPacket};
+
+use crate::{
+    command::{self, Command, CommandRegistry},
+    event::PlayerJoined,
+    game::Game,
+    Mailbox,
+};
+
+/// Extra reach allowed by the server, in blocks. The server checks
+/// edits against the player's latest known position, which may lag
+/// behind the position the client targeted the block from. Placed
+/// blocks may also be slightly further than the block they are
+/// placed against, which is the one the client checked.
+const REACH_TOLERANCE: f32 = 1.;
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(GameModeSystem);
+}
+
+/// Reads the reach of each game mode from `VOLTZ_SURVIVAL_REACH` and
+/// `VOLTZ_CREATIVE_REACH`, keeping the default for any that is not
+/// set or invalid.
+pub fn reach_from_env() -> Reach {
+    let default = Reach::default();
+    Reach {
+        survival: reach_var("VOLTZ_SURVIVAL_REACH", default.survival),
+        creative: reach_var("VOLTZ_CREATIVE_REACH", default.creative),
+    }
+}
+
+fn reach_var(var: &str, default: f32) -> f32 {
+    let value = match env::var(var) {
+        Ok(value) => value,
+        Err(_) => return default,
+    };
+    match parse_reach(&value) {
+        Ok(reach) => reach,
+        Err(e) => {
+            log::error!("Ignoring invalid {} '{}': {:#}", var, value, e);
+            default
+        }
+    }
+}
+
+fn parse_reach(value: &str) -> anyhow::Result<f32> {
+    let reach: f32 = value
+        .trim()
+        .parse()
+        .context("the reach must be a number of blocks")?;
+    if !reach.is_finite() || reach < 0. {
+        bail!("the reach must be a non-negative number of blocks");
+    }
+    Ok(reach)
+}
+
+/// Returns a player's game mode.
+pub fn game_mode(game: &Game, player: Entity) -> GameMode {
+    game.ecs()
+        .get::<GameMode>(player)
+        .map(|mode| *mode)
+        .unwrap_or_default()
+}
+
+/// Returns how far a player can reach.
+pub fn reach(game: &Game, player: Entity) -> f32 {
+    game.reach().for_mode(game_mode(game, player))
+}
+
+/// Returns whether a player is close enough to the block at `pos`
+/// to edit it, allowing for the latency of their position.
+pub fn can_reach(game: &Game, player: Entity, pos: BlockPos) -> bool {
+    let eye = match game.ecs().get::<Pos>(player) {
+        // Blocks are edited in the main zone.
+        Ok(pos) => player::eye_pos(game.world().to_main(pos.0)),
+        Err(_) => return false,
+    };
+    edit::is_in_reach(eye, pos, reach(game, player) + REACH_TOLERANCE)
+}
+
+/// Changes a player's game mode and tells them about it.
+pub fn set_game_mode(game: &mut Game, player: Entity, mode: GameMode) {
+    game.ecs_mut().insert_one(player, mode).ok();
+    send_game_mode(game, player);
+}
+
+fn send_game_mode(game: &Game, player: Entity) {
+    if let Ok(mailbox) = game.ecs().get::<Mailbox>(player) {
+        mailbox.send(ServerPacket::SetGameMode(SetGameMode {
+            mode: game_mode(game, player),
+            reach: reach(game, player),
+        }));
+    }
+}
+
+/// System to tell joining players their game mode.
+struct GameModeSystem;
+
+impl System<Game> for GameModeSystem {
+    fn run(&mut self, game: &mut Game) {
+        for event in game.events().iter::<PlayerJoined>() {
+            send_game_mode(game, event.player);
+        }
+    }
+}
+
+/// Registers the `gamemode` console command.
+pub fn register_commands(commands: &mut CommandRegistry) {
+    commands.register(GameModeCommand);
+}
+
+struct GameModeCommand;
+
+impl Command for GameModeCommand {
+    fn name(&self) -> &str {
+        "gamemode"
+    }
+
+    fn usage(&self) -> &str {
+        "<survival|creative> [player]"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        let (mode, player) = match args {
+            [mode] => (mode.parse::<GameMode>()?, command::only_player(game)?),
+            [mode, username] => (
+                mode.parse::<GameMode>()?,
+                command::find_player(game, username)?,
+            ),
+            _ => bail!("usage: {}", command::usage(self)),
+        };
+        set_game_mode(game, player, mode);
+        let username = game.ecs().get::<Username>(player).unwrap().0.clone();
+        Ok(format!("Set the game mode of {} to {}", username, mode))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::WorldPos;
+    use glam::vec3a;
+
+    use crate::game::test_game;
+
+    use super::*;
+
+    #[test]
+    fn parses_reach() {
+        assert_eq!(parse_reach(" 6.5 ").unwrap(), 6.5);
+        assert!(parse_reach("-1").is_err());
+        assert!(parse_reach("inf").is_err());
+        assert!(parse_reach("far").is_err());
+    }
+
+    #[test]
+    fn reach_depends_on_game_mode() {
+        let mut game = test_game();
+        game.set_reach(Reach {
+            survival: 4.,
+            creative: 10.,
+        });
+
+        let player = game
+            .ecs_mut()
+            .spawn((Pos(WorldPos::main(vec3a(0.5, 0., 0.5))),));
+        // The nearest face of this block is 7.5 blocks from the player's eyes.
+        let block = BlockPos { x: 8, y: 1, z: 0 };
+        assert_eq!(game_mode(&game, player), GameMode::Survival);
+        assert!(!can_reach(&game, player, block));
+        set_game_mode(&mut game, player, GameMode::Creative);
+        assert_eq!(reach(&game, player), 10.);
+        assert!(can_reach(&game, player, block));
+    }
+}
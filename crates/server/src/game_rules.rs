@@ -0,0 +1,150 @@
+//! Per-world [game rules](common::game_rules).
+//!
+//! The rules are stored in the [`Game`] and saved with the world. Systems
+//! query them where they apply; for example, the [weather](crate::weather)
+//! only changes while `doWeatherCycle` is on. Rules which clients use to
+//! predict the game are sent to players with `UpdateGameRules` when they
+//! join and whenever one of them changes.
+//!
+//! The `gamerule [rule] [value]` console [command](crate::command) lists
+//! the rules, prints the value of one, or changes it.
+
+use anyhow::{bail, Context};
+use common::{
+    game_rules::{GameRule, RuleValue},
+    System, SystemExecutor,
+};
+use protocol::packets::{server::UpdateGameRules, ServerPacket};
+
+use crate::{
+    command::{self, Command, CommandRegistry},
+    event::PlayerJoined,
+    game::Game,
+    Mailbox,
+};
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(GameRulesSystem { announced: None });
+}
+
+/// Returns the values of the rules sent to clients.
+fn synced_rules(game: &Game) -> Vec<(GameRule, RuleValue)> {
+    game.rules()
+        .iter()
+        .filter(|(rule, _)| rule.is_synced())
+        .collect()
+}
+
+fn send_rules(mailbox: &Mailbox, rules: &[(GameRule, RuleValue)]) {
+    mailbox.send(ServerPacket::UpdateGameRules(UpdateGameRules {
+        rules: rules.to_vec(),
+    }));
+}
+
+/// System to tell players the synced game rules.
+struct GameRulesSystem {
+    /// The synced rules players were last told about.
+    announced: Option<Vec<(GameRule, RuleValue)>>,
+}
+
+impl System<Game> for GameRulesSystem {
+    fn run(&mut self, game: &mut Game) {
+        let rules = synced_rules(game);
+        // Rules may have been loaded or changed before the first tick.
+        if self.announced.as_ref() != Some(&rules) {
+            if self.announced.is_some() {
+                for (_, mailbox) in game.ecs().query::<&Mailbox>().iter() {
+                    send_rules(mailbox, &rules);
+                }
+            }
+            self.announced = Some(rules.clone());
+        }
+        for event in game.events().iter::<PlayerJoined>() {
+            if let Ok(mailbox) = game.ecs().get::<Mailbox>(event.player) {
+                send_rules(&mailbox, &rules);
+            }
+        }
+    }
+}
+
+/// Registers the `gamerule` console command.
+pub fn register_commands(commands: &mut CommandRegistry) {
+    commands.register(GameRuleCommand);
+}
+
+struct GameRuleCommand;
+
+impl Command for GameRuleCommand {
+    fn name(&self) -> &str {
+        "gamerule"
+    }
+
+    fn usage(&self) -> &str {
+        "[rule] [value]"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        match args {
+            [] => {
+                let mut list = "Game rules:".to_owned();
+                for (rule, value) in game.rules().iter() {
+                    list.push_str(&format!("\n  {} = {}", rule, value));
+                }
+                Ok(list)
+            }
+            [name] => {
+                let rule = parse_rule(name)?;
+                Ok(format!("{} = {}", rule, game.rules().get(rule)))
+            }
+            [name, value] => {
+                let rule = parse_rule(name)?;
+                let value = game
+                    .rules()
+                    .get(rule)
+                    .parse_same_type(value)
+                    .with_context(|| format!("invalid value for {}", rule))?;
+                game.rules_mut().set(rule, value)?;
+                Ok(format!("Set {} to {}", rule, value))
+            }
+            _ => bail!("usage: {}", command::usage(self)),
+        }
+    }
+}
+
+fn parse_rule(name: &str) -> anyhow::Result<GameRule> {
+    GameRule::from_name(name)
+        .with_context(|| format!("unknown game rule '{}'; run 'gamerule' for a list", name))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::game::test_game;
+
+    use super::*;
+
+    #[test]
+    fn gamerule_command() {
+        let mut game = test_game();
+        let mut commands = CommandRegistry::new();
+        register_commands(&mut commands);
+
+        let list = commands.run(&mut game, "/gamerule").unwrap();
+        assert!(list.contains("randomTickSpeed = 3"), "{}", list);
+
+        commands
+            .run(&mut game, "/gamerule randomTickSpeed 10")
+            .unwrap();
+        assert_eq!(game.rules().get_int(GameRule::RandomTickSpeed), 10);
+        assert_eq!(
+            commands
+                .run(&mut game, "/gamerule randomTickSpeed")
+                .unwrap(),
+            "randomTickSpeed = 10"
+        );
+
+        assert!(commands.run(&mut game, "/gamerule fallDamage 1").is_err());
+        assert!(commands.run(&mut game, "/gamerule mobGriefing").is_err());
+        assert!(game.rules().get_bool(GameRule::FallDamage));
+    }
+}
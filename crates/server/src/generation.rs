@@ -0,0 +1,198 @@
+//! Generates the main zone while the game runs.
+//!
+//! Only a small area around spawn is generated before the server
+//! starts, so players can join without waiting for the whole world.
+//! The remaining columns are generated on a background thread, nearest
+//! to spawn first, and moved into the main zone as they finish.
+//! Columns that have not been generated yet contain only air
+//! and are not sent to clients. Columns loaded from a save
+//! count as generated. Generated columns store their biomes in the
+//! main zone; those of columns saved without biomes are looked up
+//! in the world generator's biome grid when the save is loaded.
+
+use std::{sync::Arc, thread, time::Instant};
+
+use common::{
+    chunk::CHUNK_DIM, world::ZoneBuilder, Chunk, ChunkPos, System, SystemExecutor, WorldPos, Zone,
+};
+use flume::Receiver;
+use glam::vec3a;
+use hashbrown::HashSet;
+use worldgen::{ChunkColumn, ColumnPos, WorldGenerator};
+
+use crate::{event::ColumnGenerated, game::Game, save::SavedColumn, WORLD_SIZE};
+
+/// The column containing the spawn point.
+pub const SPAWN_COLUMN: ColumnPos = ColumnPos { x: 8, z: 8 };
+/// The height at which players spawn. Players fall
+/// from there to the ground.
+const SPAWN_HEIGHT: f32 = 240.;
+/// Columns within this many chunks of [`SPAWN_COLUMN`]
+/// are generated before players can join.
+const SPAWN_RADIUS: i32 = 2;
+/// The number of chunks in each column of the main zone.
+pub(crate) const COLUMN_HEIGHT: i32 = 16;
+
+/// Adds the system that generates columns not yet
+/// marked as generated in `game`.
+pub fn setup(
+    systems: &mut SystemExecutor<Game>,
+    game: &Game,
+    world_generator: Arc<WorldGenerator>,
+    seed: u64,
+) {
+    let remaining: Vec<ColumnPos> = columns_by_distance()
+        .into_iter()
+        .filter(|&pos| !game.is_column_generated(pos))
+        .collect();
+
+    let num_remaining = remaining.len();
+    let generated = generate_in_background(world_generator, seed, remaining);
+    systems.add(GenerationSystem {
+        generated,
+        remaining: num_remaining,
+    });
+}
+
+/// Creates the main zone from the columns loaded from a save,
+/// generating any missing columns around spawn. The rest are left
+/// empty. `progress` is invoked with the current stage and percent
+/// complete.
+///
+/// Returns the zone and the positions of all loaded
+/// and generated columns.
+pub fn generate_spawn_area(
+    world_generator: &WorldGenerator,
+    seed: u64,
+    loaded: Vec<SavedColumn>,
+    mut progress: impl FnMut(&str, f32),
+) -> (Zone, Vec<ColumnPos>) {
+    let mut builder = ZoneBuilder::new(
+        ChunkPos { x: 0, y: 0, z: 0 },
+        ChunkPos {
+            x: WORLD_SIZE - 1,
+            y: COLUMN_HEIGHT - 1,
+            z: WORLD_SIZE - 1,
+        },
+    );
+
+    let mut available = HashSet::new();
+    for column in loaded {
+        if column.chunks.len() != COLUMN_HEIGHT as usize {
+            log::warn!("Discarding saved column {:?} of wrong height", column.pos);
+            continue;
+        }
+        for (y, chunk) in column.chunks.into_iter().enumerate() {
+            let _ = builder.add_chunk(column.pos.chunk(y as i32), chunk);
+        }
+        let biomes = column
+            .biomes
+            .unwrap_or_else(|| world_generator.column_biomes(seed, column.pos));
+        let _ = builder.set_column_biomes(column.pos.x, column.pos.z, biomes);
+        available.insert(column.pos);
+    }
+
+    let spawn_area: Vec<ColumnPos> = columns_by_distance()
+        .into_iter()
+        .filter(|&pos| is_in_spawn_area(pos) && !available.contains(&pos))
+        .collect();
+    if !spawn_area.is_empty() {
+        progress("Generating biomes", 0.);
+        world_generator.prepare_biome_grid(seed);
+    }
+    for (i, &pos) in spawn_area.iter().enumerate() {
+        world_generator.generate_column_into_zone(&mut builder, seed, pos);
+        available.insert(pos);
+        let percent = (i + 1) as f32 / spawn_area.len() as f32 * 100.;
+        progress("Generating spawn area", percent);
+    }
+
+    for pos in columns_by_distance() {
+        if !available.contains(&pos) {
+            for y in 0..COLUMN_HEIGHT {
+                let _ = builder.add_chunk(pos.chunk(y), Chunk::default());
+            }
+        }
+    }
+
+    let zone = builder.build().ok().expect("failed to create all chunks");
+    (zone, available.into_iter().collect())
+}
+
+/// Returns the position at which players join and respawn.
+pub fn spawn_pos() -> WorldPos {
+    WorldPos::main(vec3a(
+        (SPAWN_COLUMN.x * CHUNK_DIM as i32) as f32,
+        SPAWN_HEIGHT,
+        (SPAWN_COLUMN.z * CHUNK_DIM as i32) as f32,
+    ))
+}
+
+fn is_in_spawn_area(pos: ColumnPos) -> bool {
+    (pos.x - SPAWN_COLUMN.x).abs() <= SPAWN_RADIUS && (pos.z - SPAWN_COLUMN.z).abs() <= SPAWN_RADIUS
+}
+
+/// Returns every column in the main zone, nearest to spawn first.
+fn columns_by_distance() -> Vec<ColumnPos> {
+    let mut columns = Vec::with_capacity((WORLD_SIZE * WORLD_SIZE) as usize);
+    for x in 0..WORLD_SIZE {
+        for z in 0..WORLD_SIZE {
+            columns.push(ColumnPos { x, z });
+        }
+    }
+    columns.sort_unstable_by_key(|pos| {
+        (pos.x - SPAWN_COLUMN.x).abs() + (pos.z - SPAWN_COLUMN.z).abs()
+    });
+    columns
+}
+
+/// Spawns a thread to generate `columns` in order.
+fn generate_in_background(
+    world_generator: Arc<WorldGenerator>,
+    seed: u64,
+    columns: Vec<ColumnPos>,
+) -> Receiver<(ColumnPos, ChunkColumn)> {
+    let (sender, receiver) = flume::unbounded();
+    thread::Builder::new()
+        .name("worldgen".to_owned())
+        .spawn(move || {
+            let start = Instant::now();
+            for pos in columns {
+                let column = world_generator.generate_chunk_column(seed, pos);
+                if sender.send((pos, column)).is_err() {
+                    // The server shut down.
+                    return;
+                }
+            }
+            log::info!("Finished generating the world in {:?}", start.elapsed());
+        })
+        .expect("failed to spawn world generation thread");
+    receiver
+}
+
+/// System to move columns generated in the background
+/// into the main zone.
+struct GenerationSystem {
+    generated: Receiver<(ColumnPos, ChunkColumn)>,
+    /// The number of columns not yet received.
+    remaining: usize,
+}
+
+impl System<Game> for GenerationSystem {
+    fn run(&mut self, game: &mut Game) {
+        for (pos, column) in self.generated.try_iter() {
+            let chunks: Box<[Chunk]> = column.chunks;
+            for (y, chunk) in chunks.into_vec().into_iter().enumerate() {
+                let _ = game.main_zone_mut().set_chunk(pos.chunk(y as i32), chunk);
+            }
+            let _ = game
+                .main_zone_mut()
+                .set_column_biomes(pos.x, pos.z, column.biomes);
+
+            game.mark_column_generated(pos);
+            game.events().push(ColumnGenerated { pos });
+            self.remaining -= 1;
+            log::trace!("Generated {:?}; {} remaining", pos, self.remaining);
+        }
+    }
+}
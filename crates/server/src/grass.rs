@@ -0,0 +1,124 @@
+//! Grass spreading and decay.
+//!
+//! On a [random tick](crate::random_tick), grass covered by an opaque
+//! block dies and turns into dirt. Uncovered grass instead spreads to
+//! a random nearby dirt block, if that block is uncovered too.
+//!
+//! A block is uncovered when the block above it lets light through.
+
+use common::{
+    blocks::{Dirt, Grass},
+    BlockId, BlockPos, System, SystemExecutor, Zone,
+};
+use rand::Rng;
+
+use crate::{event::RandomTick, game::Game};
+
+/// The number of random nearby blocks grass
+/// tries to spread to on each random tick.
+const SPREAD_ATTEMPTS: u32 = 4;
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(GrassSystem);
+}
+
+/// System to spread and decay grass on random ticks.
+struct GrassSystem;
+
+impl System<Game> for GrassSystem {
+    fn run(&mut self, game: &mut Game) {
+        let mut changes = Vec::new();
+        for tick in game.events().iter::<RandomTick>() {
+            if tick.block.is::<Grass>() {
+                changes.extend(tick_grass(game.main_zone(), tick.pos, &mut *game.rng()));
+            }
+        }
+
+        for (pos, block) in changes {
+            game.set_block(pos, block).ok();
+        }
+    }
+}
+
+/// Determines the block changes caused by a random tick
+/// of the grass at `pos`.
+fn tick_grass(zone: &Zone, pos: BlockPos, rng: &mut impl Rng) -> Vec<(BlockPos, BlockId)> {
+    if !is_uncovered(zone, pos) {
+        return vec![(pos, BlockId::new(Dirt))];
+    }
+
+    let mut changes = Vec::new();
+    for _ in 0..SPREAD_ATTEMPTS {
+        // Grass can spread one block sideways, and
+        // up to one block up or three blocks down.
+        let target = pos.offset(
+            rng.gen_range(-1, 2),
+            rng.gen_range(-3, 2),
+            rng.gen_range(-1, 2),
+        );
+        let is_dirt = zone.block(target).map_or(false, |block| block.is::<Dirt>());
+        if is_dirt
+            && is_uncovered(zone, target)
+            && !changes.contains(&(target, BlockId::new(Grass)))
+        {
+            changes.push((target, BlockId::new(Grass)));
+        }
+    }
+    changes
+}
+
+/// Returns whether the block above `pos` is loaded and lets light through.
+fn is_uncovered(zone: &Zone, pos: BlockPos) -> bool {
+    zone.block(pos.offset(0, 1, 0))
+        .map_or(false, |above| !above.is_opaque())
+}
+
+#[cfg(test)]
+mod tests {
+    use common::blocks::Stone;
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    use crate::game::test_zone;
+
+    use super::*;
+
+    #[test]
+    fn covered_grass_dies() {
+        let mut zone = test_zone();
+        let pos = BlockPos { x: 4, y: 4, z: 4 };
+        zone.set_block(pos, BlockId::new(Grass)).unwrap();
+        zone.set_block(pos.offset(0, 1, 0), BlockId::new(Stone))
+            .unwrap();
+
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        assert_eq!(
+            tick_grass(&zone, pos, &mut rng),
+            vec![(pos, BlockId::new(Dirt))]
+        );
+    }
+
+    #[test]
+    fn grass_spreads_to_uncovered_dirt() {
+        let mut zone = test_zone();
+        let pos = BlockPos { x: 4, y: 4, z: 4 };
+        let uncovered = pos.offset(1, 0, 0);
+        let covered = pos.offset(-1, 0, 0);
+        zone.set_block(pos, BlockId::new(Grass)).unwrap();
+        zone.set_block(uncovered, BlockId::new(Dirt)).unwrap();
+        zone.set_block(covered, BlockId::new(Dirt)).unwrap();
+        zone.set_block(covered.offset(0, 1, 0), BlockId::new(Stone))
+            .unwrap();
+
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        let mut spread = false;
+        for _ in 0..100 {
+            for (target, block) in tick_grass(&zone, pos, &mut rng) {
+                assert_eq!(target, uncovered);
+                assert_eq!(block, BlockId::new(Grass));
+                spread = true;
+            }
+        }
+        assert!(spread);
+    }
+}
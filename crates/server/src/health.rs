@@ -0,0 +1,251 @@
+//! Player health and damage.
+//!
+//! Players have a [`Health`] component, full at [`MAX_HEALTH`] when they
+//! join or respawn, and are sent their health with `UpdateHealth`. Anything
+//! that hurts an entity pushes a [`Damage`] event, which the health system
+//! applies unless the entity is a dead player or a player in creative mode.
+//! Players whose health runs out [die](crate::death) of the damage's cause.
+//!
+//! Clients move their own players, so they track how far their players
+//! fall and report landings with `Landed`. The server turns the distance
+//! into fall damage according to the [`FallSettings`], configured with
+//! `VOLTZ_SAFE_FALL_DISTANCE` and `VOLTZ_FALL_DAMAGE_PER_BLOCK`, unless the
+//! `fallDamage` [game rule](GameRule) is off. Health is not saved yet.
+
+use std::env;
+
+use anyhow::{bail, Context};
+use common::{
+    entity::{
+        player::{GameMode, MAX_HEALTH},
+        Health,
+    },
+    game_rules::GameRule,
+    System, SystemExecutor,
+};
+use hecs::Entity;
+use protocol::packets::{server::UpdateHealth, ServerPacket};
+
+use crate::{
+    death,
+    event::{Damage, PlayerJoined},
+    game::Game,
+    game_mode, Mailbox,
+};
+
+/// Falls at least this many blocks long are
+/// [from a high place](HIGH_FALL_CAUSE).
+const HIGH_FALL_DISTANCE: f32 = 8.;
+/// The causes of death of players killed by fall damage.
+const FALL_CAUSE: &str = "Hit the ground too hard";
+const HIGH_FALL_CAUSE: &str = "Fell from a high place";
+
+/// How falls hurt players.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FallSettings {
+    /// The farthest a player can fall without being hurt, in blocks.
+    pub safe_distance: f32,
+    /// The damage taken per block fallen beyond the safe distance.
+    pub damage_per_block: f32,
+}
+
+impl Default for FallSettings {
+    fn default() -> Self {
+        Self {
+            safe_distance: 3.,
+            damage_per_block: 1.,
+        }
+    }
+}
+
+impl FallSettings {
+    /// Reads the settings from `VOLTZ_SAFE_FALL_DISTANCE` and
+    /// `VOLTZ_FALL_DAMAGE_PER_BLOCK`, keeping the default for
+    /// any that is not set or invalid.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            safe_distance: setting_var("VOLTZ_SAFE_FALL_DISTANCE", default.safe_distance),
+            damage_per_block: setting_var("VOLTZ_FALL_DAMAGE_PER_BLOCK", default.damage_per_block),
+        }
+    }
+
+    /// Returns the damage taken when landing after falling `distance` blocks.
+    pub fn damage(&self, distance: f32) -> u32 {
+        // Also rejects NaN distances reported by clients.
+        if !(distance > self.safe_distance) {
+            return 0;
+        }
+        ((distance - self.safe_distance) * self.damage_per_block).ceil() as u32
+    }
+}
+
+fn setting_var(var: &str, default: f32) -> f32 {
+    let value = match env::var(var) {
+        Ok(value) => value,
+        Err(_) => return default,
+    };
+    match parse_setting(&value) {
+        Ok(setting) => setting,
+        Err(e) => {
+            log::error!("Ignoring invalid {} '{}': {:#}", var, value, e);
+            default
+        }
+    }
+}
+
+fn parse_setting(value: &str) -> anyhow::Result<f32> {
+    let setting: f32 = value.trim().parse().context("not a number")?;
+    if !setting.is_finite() || setting < 0. {
+        bail!("the setting must be a non-negative number");
+    }
+    Ok(setting)
+}
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(HealthSystem);
+}
+
+/// Hurts a player who landed after falling `distance` blocks.
+pub(crate) fn land(game: &Game, player: Entity, distance: f32) {
+    if !game.rules().get_bool(GameRule::FallDamage) {
+        return;
+    }
+    let amount = game.fall_settings().damage(distance);
+    if amount > 0 {
+        let cause = if distance >= HIGH_FALL_DISTANCE {
+            HIGH_FALL_CAUSE
+        } else {
+            FALL_CAUSE
+        };
+        game.events().push(Damage {
+            entity: player,
+            amount,
+            cause: cause.to_owned(),
+        });
+    }
+}
+
+/// Restores a player's health to the maximum.
+pub(crate) fn heal_fully(game: &Game, player: Entity) {
+    if let Ok(mut health) = game.ecs().get_mut::<Health>(player) {
+        *health = Health::full(MAX_HEALTH);
+    }
+    send(game, player);
+}
+
+/// Sends a player their health.
+fn send(game: &Game, player: Entity) {
+    let health = match game.ecs().get::<Health>(player) {
+        Ok(health) => *health,
+        Err(_) => return,
+    };
+    if let Ok(mailbox) = game.ecs().get::<Mailbox>(player) {
+        mailbox.send(ServerPacket::UpdateHealth(UpdateHealth {
+            health: health.current,
+            max_health: health.max,
+        }));
+    }
+}
+
+/// System to apply damage and send players their health when they join.
+struct HealthSystem;
+
+impl System<Game> for HealthSystem {
+    fn run(&mut self, game: &mut Game) {
+        for event in game.events().iter::<PlayerJoined>() {
+            send(game, event.player);
+        }
+
+        let damages: Vec<(Entity, u32, String)> = game
+            .events()
+            .iter::<Damage>()
+            .map(|damage| (damage.entity, damage.amount, damage.cause.clone()))
+            .collect();
+        for (entity, amount, cause) in damages {
+            apply(game, entity, amount, cause);
+        }
+    }
+}
+
+fn apply(game: &mut Game, entity: Entity, amount: u32, cause: String) {
+    if death::is_dead(game, entity) || game_mode::game_mode(game, entity) == GameMode::Creative {
+        return;
+    }
+    let died = match game.ecs().get_mut::<Health>(entity) {
+        Ok(mut health) => health.damage(amount),
+        Err(_) => return,
+    };
+    send(game, entity);
+    if died {
+        death::kill(game, entity, cause);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::game_rules::RuleValue;
+
+    use crate::game::test_game;
+
+    use super::*;
+
+    #[test]
+    fn fall_damage_grows_with_distance() {
+        let settings = FallSettings::default();
+        assert_eq!(settings.damage(settings.safe_distance), 0);
+        assert_eq!(settings.damage(f32::NAN), 0);
+        assert_eq!(settings.damage(settings.safe_distance + 0.5), 1);
+        assert!(settings.damage(10.) > settings.damage(5.));
+
+        assert!(parse_setting(" 2.5 ").is_ok());
+        assert!(parse_setting("-1").is_err());
+        assert!(parse_setting("far").is_err());
+    }
+
+    #[test]
+    fn falls_have_causes() {
+        let mut game = test_game();
+        let player = game.ecs_mut().spawn((Health::full(MAX_HEALTH),));
+        land(&game, player, 1.);
+        land(&game, player, 5.);
+        land(&game, player, 50.);
+        game.rules_mut()
+            .set(GameRule::FallDamage, RuleValue::Bool(false))
+            .unwrap();
+        land(&game, player, 50.);
+
+        let causes: Vec<String> = game
+            .events()
+            .iter::<Damage>()
+            .map(|damage| damage.cause.clone())
+            .collect();
+        assert_eq!(causes, vec![FALL_CAUSE, HIGH_FALL_CAUSE]);
+    }
+
+    #[test]
+    fn damage_kills_at_zero_health() {
+        let mut game = test_game();
+        let player = game.ecs_mut().spawn((Health::full(MAX_HEALTH),));
+        apply(&mut game, player, MAX_HEALTH - 1, "test".to_owned());
+        assert_eq!(game.ecs().get::<Health>(player).unwrap().current, 1);
+        assert!(!death::is_dead(&game, player));
+
+        apply(&mut game, player, 5, "test".to_owned());
+        assert!(death::is_dead(&game, player));
+        // Dead players take no more damage.
+        apply(&mut game, player, 5, "test".to_owned());
+        assert_eq!(game.ecs().get::<Health>(player).unwrap().current, 0);
+
+        // Respawned players have full health, and creative players take no damage.
+        death::respawn(&mut game, player);
+        game.ecs_mut()
+            .insert_one(player, GameMode::Creative)
+            .unwrap();
+        apply(&mut game, player, 5, "test".to_owned());
+        assert_eq!(
+            *game.ecs().get::<Health>(player).unwrap(),
+            Health::full(MAX_HEALTH)
+        );
+    }
+}
@@ -0,0 +1,528 @@
+//! The world history: an append-only log of block edits, joins,
+//! leaves, and deaths, for finding out who griefed what.
+//!
+//! The log is kept in the `history` directory of the [save](crate::save),
+//! one entry per line, so it can also be searched with ordinary tools.
+//! When `events.log` grows past [`MAX_LOG_LEN`], it is renamed to
+//! `events.1.log`, older logs move up a number, and the oldest of the
+//! [`KEPT_LOGS`] rotated logs is deleted. Worlds that are not saved have
+//! no history.
+//!
+//! The `history <x> <y> <z>` console [command](crate::command) lists the
+//! latest edits of a block. The logs are written and searched on a
+//! separate [thread](History), which logs the edits once it has read
+//! through them, so that the tick thread never waits for the disk.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    str::{self, FromStr},
+    thread,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context};
+use common::{entity::player::Username, BlockId, BlockPos, System, SystemExecutor};
+use flume::Sender;
+use hecs::Entity;
+
+use crate::{
+    command::{self, Command, CommandRegistry},
+    event::{BlockEdited, PlayerDied, PlayerJoined, PlayerLeft},
+    game::Game,
+};
+
+/// The length in bytes past which the current log is rotated.
+pub const MAX_LOG_LEN: u64 = 16 * 1024 * 1024;
+/// The number of rotated logs kept in addition to the current one.
+pub const KEPT_LOGS: u32 = 4;
+/// The number of edits listed by the `history` command.
+const MAX_LISTED_EDITS: usize = 10;
+
+/// Something that happened in the world.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+    /// The game tick.
+    pub tick: u64,
+    pub kind: EntryKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntryKind {
+    /// A player changed a block. Blocks are identified by their slugs.
+    Edit {
+        player: String,
+        pos: BlockPos,
+        old: String,
+        new: String,
+    },
+    Join {
+        player: String,
+    },
+    Leave {
+        player: String,
+    },
+    Death {
+        player: String,
+        cause: String,
+    },
+}
+
+/// Replaces the characters which separate fields and entries.
+fn sanitize(field: &str) -> String {
+    field.replace(|c| c == '\t' || c == '\n' || c == '\r', " ")
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}\t{}\t", self.time, self.tick)?;
+        match &self.kind {
+            EntryKind::Edit {
+                player,
+                pos,
+                old,
+                new,
+            } => write!(
+                f,
+                "edit\t{}\t{} {} {}\t{}\t{}",
+                sanitize(player),
+                pos.x,
+                pos.y,
+                pos.z,
+                old,
+                new
+            ),
+            EntryKind::Join { player } => write!(f, "join\t{}", sanitize(player)),
+            EntryKind::Leave { player } => write!(f, "leave\t{}", sanitize(player)),
+            EntryKind::Death { player, cause } => {
+                write!(f, "death\t{}\t{}", sanitize(player), sanitize(cause))
+            }
+        }
+    }
+}
+
+impl FromStr for Entry {
+    type Err = anyhow::Error;
+
+    fn from_str(line: &str) -> anyhow::Result<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let (time, tick, kind) = match fields.as_slice() {
+            [time, tick, kind @ ..] => (time.parse()?, tick.parse()?, kind),
+            _ => bail!("missing time or tick"),
+        };
+        let kind = match kind {
+            ["edit", player, pos, old, new] => EntryKind::Edit {
+                player: player.to_string(),
+                pos: parse_block_pos(&pos.split(' ').collect::<Vec<_>>())?,
+                old: old.to_string(),
+                new: new.to_string(),
+            },
+            ["join", player] => EntryKind::Join {
+                player: player.to_string(),
+            },
+            ["leave", player] => EntryKind::Leave {
+                player: player.to_string(),
+            },
+            ["death", player, cause] => EntryKind::Death {
+                player: player.to_string(),
+                cause: cause.to_string(),
+            },
+            _ => bail!("unknown entry"),
+        };
+        Ok(Self { time, tick, kind })
+    }
+}
+
+fn parse_block_pos(coords: &[&str]) -> anyhow::Result<BlockPos> {
+    match *coords {
+        [x, y, z] => {
+            let coord = |coord: &str| {
+                coord
+                    .parse::<i32>()
+                    .with_context(|| format!("'{}' is not a block coordinate", coord))
+            };
+            Ok(BlockPos {
+                x: coord(x)?,
+                y: coord(y)?,
+                z: coord(z)?,
+            })
+        }
+        _ => bail!("expected three block coordinates"),
+    }
+}
+
+/// The history log of a save.
+#[derive(Clone, Debug)]
+pub struct HistoryLog {
+    dir: PathBuf,
+}
+
+impl HistoryLog {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Appends entries to the current log, rotating it first
+    /// if it has grown past [`MAX_LOG_LEN`].
+    pub fn append(&self, entries: &[Entry]) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(0);
+        if fs::metadata(&path).map_or(false, |metadata| metadata.len() >= MAX_LOG_LEN) {
+            self.rotate()?;
+        }
+
+        let mut text = String::new();
+        for entry in entries {
+            text.push_str(&entry.to_string());
+            text.push('\n');
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        file.write_all(text.as_bytes())?;
+        Ok(())
+    }
+
+    fn rotate(&self) -> anyhow::Result<()> {
+        let oldest = self.path(KEPT_LOGS);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for number in (0..KEPT_LOGS).rev() {
+            let path = self.path(number);
+            if path.exists() {
+                fs::rename(&path, self.path(number + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls `f` with every entry from oldest to newest, reading the
+    /// logs a line at a time. Lines which are not valid entries, e.g.
+    /// from a crash while writing, are skipped.
+    pub fn for_each(&self, mut f: impl FnMut(Entry)) -> anyhow::Result<()> {
+        for number in (0..=KEPT_LOGS).rev() {
+            let path = self.path(number);
+            if !path.exists() {
+                continue;
+            }
+            let context = || format!("failed to read {}", path.display());
+            let file = File::open(&path).with_context(context)?;
+            for line in BufReader::new(file).split(b'\n') {
+                let line = line.with_context(context)?;
+                if let Some(entry) = str::from_utf8(&line)
+                    .ok()
+                    .and_then(|line| line.parse().ok())
+                {
+                    f(entry);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the path of the current log if `number` is 0,
+    /// or of the `number`th rotated log otherwise.
+    fn path(&self, number: u32) -> PathBuf {
+        if number == 0 {
+            self.dir.join("events.log")
+        } else {
+            self.dir.join(format!("events.{}.log", number))
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+/// A request to the history thread.
+enum Request {
+    /// Appends entries to the log.
+    Append(Vec<Entry>),
+    /// Logs the latest edits of a block.
+    ListEdits(BlockPos),
+}
+
+/// Handle to the thread which writes and searches a [`HistoryLog`].
+#[derive(Clone)]
+pub struct History {
+    requests: Sender<Request>,
+}
+
+impl History {
+    /// Spawns the thread which handles the requests for `log`.
+    pub fn spawn(log: HistoryLog) -> Self {
+        let (requests, receiver) = flume::unbounded();
+        thread::Builder::new()
+            .name("history".to_owned())
+            .spawn(move || {
+                for request in receiver {
+                    match request {
+                        Request::Append(entries) => {
+                            if let Err(e) = log.append(&entries) {
+                                log::error!("Failed to write the world history: {:#}", e);
+                            }
+                        }
+                        Request::ListEdits(pos) => match list_edits(&log, pos) {
+                            Ok(message) => log::info!("{}", message),
+                            Err(e) => log::warn!("Failed to read the world history: {:#}", e),
+                        },
+                    }
+                }
+            })
+            .expect("failed to spawn history thread");
+        Self { requests }
+    }
+
+    /// Sends a request to the thread. Returns `false`
+    /// if the thread exited.
+    fn send(&self, request: Request) -> bool {
+        self.requests.send(request).is_ok()
+    }
+}
+
+/// Adds the system which records entries to the `history`.
+pub fn setup(systems: &mut SystemExecutor<Game>, history: History) {
+    systems.add(HistorySystem { history });
+}
+
+/// System to record the events of each tick in the history.
+struct HistorySystem {
+    history: History,
+}
+
+impl System<Game> for HistorySystem {
+    fn run(&mut self, game: &mut Game) {
+        let mut kinds = Vec::new();
+        {
+            let events = game.events();
+            let username = |player: Entity| {
+                game.ecs()
+                    .get::<Username>(player)
+                    .map(|name| name.0.clone())
+                    .unwrap_or_else(|_| "?".to_owned())
+            };
+            for event in events.iter::<PlayerJoined>() {
+                kinds.push(EntryKind::Join {
+                    player: username(event.player),
+                });
+            }
+            for event in events.iter::<BlockEdited>() {
+                kinds.push(EntryKind::Edit {
+                    player: username(event.player),
+                    pos: event.pos,
+                    old: slug(event.old),
+                    new: slug(event.new),
+                });
+            }
+            for event in events.iter::<PlayerDied>() {
+                kinds.push(EntryKind::Death {
+                    player: username(event.player),
+                    cause: event.cause.clone(),
+                });
+            }
+            for event in events.iter::<PlayerLeft>() {
+                kinds.push(EntryKind::Leave {
+                    player: event.username.clone(),
+                });
+            }
+        }
+        if kinds.is_empty() {
+            return;
+        }
+
+        let time = unix_time();
+        let entries = kinds
+            .into_iter()
+            .map(|kind| Entry {
+                time,
+                tick: game.tick(),
+                kind,
+            })
+            .collect();
+        if !self.history.send(Request::Append(entries)) {
+            log::error!("The history thread exited. Events will not be recorded.");
+        }
+    }
+}
+
+fn slug(block: BlockId) -> String {
+    block.descriptor().slug().to_owned()
+}
+
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Describes how long ago something happened, given its age in seconds.
+pub(crate) fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{}s ago", seconds),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86_399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86_400),
+    }
+}
+
+/// Describes the latest edits of the block at `pos`, reading
+/// through the whole log but keeping only the edits listed.
+fn list_edits(log: &HistoryLog, pos: BlockPos) -> anyhow::Result<String> {
+    let mut edits = VecDeque::with_capacity(MAX_LISTED_EDITS);
+    let mut total = 0;
+    log.for_each(|entry| {
+        if matches!(&entry.kind, EntryKind::Edit { pos: edited, .. } if *edited == pos) {
+            if edits.len() == MAX_LISTED_EDITS {
+                edits.pop_front();
+            }
+            edits.push_back(entry);
+            total += 1;
+        }
+    })?;
+    if edits.is_empty() {
+        return Ok(format!(
+            "No edits of {} {} {} are recorded",
+            pos.x, pos.y, pos.z
+        ));
+    }
+
+    let now = unix_time();
+    let mut message = format!(
+        "Edits of {} {} {} (showing the latest {} of {}):",
+        pos.x,
+        pos.y,
+        pos.z,
+        edits.len(),
+        total
+    );
+    for entry in &edits {
+        if let EntryKind::Edit {
+            player, old, new, ..
+        } = &entry.kind
+        {
+            message.push_str(&format!(
+                "\n  {} (tick {}): {} replaced {} with {}",
+                format_age(now.saturating_sub(entry.time)),
+                entry.tick,
+                player,
+                old,
+                new
+            ));
+        }
+    }
+    Ok(message)
+}
+
+/// Registers the `history` console command. It
+/// fails unless the world has a `history`.
+pub fn register_commands(commands: &mut CommandRegistry, history: Option<History>) {
+    commands.register(HistoryCommand { history });
+}
+
+struct HistoryCommand {
+    history: Option<History>,
+}
+
+impl Command for HistoryCommand {
+    fn name(&self) -> &str {
+        "history"
+    }
+
+    fn usage(&self) -> &str {
+        "<x> <y> <z>"
+    }
+
+    fn run(&mut self, _game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        if args.len() != 3 {
+            bail!("usage: {}", command::usage(self));
+        }
+        let pos = parse_block_pos(args)?;
+        let history = match &self.history {
+            Some(history) => history,
+            None => bail!("this world is not saved, so it has no history"),
+        };
+        if !history.send(Request::ListEdits(pos)) {
+            bail!("the history thread exited");
+        }
+        Ok(format!(
+            "Searching the history for edits of {} {} {}",
+            pos.x, pos.y, pos.z
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(tick: u64) -> Entry {
+        Entry {
+            time: 1_600_000_000,
+            tick,
+            kind: EntryKind::Edit {
+                player: "alice".to_owned(),
+                pos: BlockPos {
+                    x: -4,
+                    y: 70,
+                    z: 12,
+                },
+                old: "dirt".to_owned(),
+                new: "air".to_owned(),
+            },
+        }
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let death = Entry {
+            time: 1_600_000_000,
+            tick: 5,
+            kind: EntryKind::Death {
+                player: "bob".to_owned(),
+                cause: "Killed by an operator".to_owned(),
+            },
+        };
+        for entry in &[edit(3), death] {
+            assert_eq!(entry.to_string().parse::<Entry>().unwrap(), *entry);
+        }
+
+        let sneaky = Entry {
+            time: 0,
+            tick: 0,
+            kind: EntryKind::Join {
+                player: "eve\tleave\nx".to_owned(),
+            },
+        };
+        assert_eq!(sneaky.to_string(), "0\t0\tjoin\teve leave x");
+        assert!("0\t0\tteleport\tbob".parse::<Entry>().is_err());
+    }
+
+    #[test]
+    fn rotates_logs() {
+        let dir = std::env::temp_dir().join(format!("voltz-history-test-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        let log = HistoryLog::new(&dir);
+
+        log.append(&[edit(0)]).unwrap();
+        for number in 0..KEPT_LOGS + 2 {
+            // Make the current log full so that the next append rotates it.
+            let path = log.path(0);
+            let file = OpenOptions::new().append(true).open(&path).unwrap();
+            file.set_len(MAX_LOG_LEN).unwrap();
+            log.append(&[edit(number as u64 + 1)]).unwrap();
+        }
+
+        assert!(log.path(KEPT_LOGS).exists());
+        assert!(!log.path(KEPT_LOGS + 1).exists());
+        let mut ticks = Vec::new();
+        log.for_each(|entry| ticks.push(entry.tick)).unwrap();
+        // The padding isn't valid entries, and the oldest logs were deleted.
+        assert_eq!(ticks, vec![2, 3, 4, 5, 6]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
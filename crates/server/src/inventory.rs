@@ -0,0 +1,238 @@
+//! Player inventories.
+//!
+//! Each player has an [`Inventory`] component. New players start with a
+//! hotbar of [`STARTING_ITEMS`]. Players are sent their whole inventory
+//! with `SetInventory` when they join and whenever it changes, and tell
+//! the server which hotbar slot they selected with `SelectSlot`.
+//!
+//! Players fill their inventories by picking up [dropped items](crate::drops).
+//! Unless the `keepInventory` game rule is on, players who die drop
+//! everything in their inventory where they died.
+//! Inventories are not saved yet, and placing blocks
+//! doesn't take items from them.
+//!
+//! The `give <targets> <item> [count]` console
+//! [command](crate::command) adds items to inventories.
+
+use anyhow::bail;
+use common::{
+    game_rules::GameRule,
+    item::{Inventory, ItemId, ItemStack, INVENTORY_SIZE, MAX_STACK},
+    BlockPos, Pos, System, SystemExecutor,
+};
+use hecs::Entity;
+use protocol::packets::{server::SetInventory, ServerPacket};
+
+use crate::{
+    command::{self, Command, CommandRegistry},
+    drops,
+    event::{PlayerDied, PlayerJoined},
+    game::Game,
+    Mailbox,
+};
+
+/// The slugs of the items in the hotbar of new players,
+/// who get a full stack of each.
+pub const STARTING_ITEMS: [&str; 9] = [
+    "stone", "dirt", "grass", "sand", "gravel", "log", "leaves", "lamp", "door",
+];
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(InventorySystem);
+}
+
+/// Registers the `give` console command.
+pub fn register_commands(commands: &mut CommandRegistry) {
+    commands.register(GiveCommand);
+}
+
+/// Creates the inventory of a new player.
+pub fn starting_inventory() -> Inventory {
+    let mut inventory = Inventory::new();
+    for (slot, slug) in STARTING_ITEMS.iter().enumerate() {
+        let item = ItemId::from_slug(slug).expect("starting item is registered");
+        inventory.set_slot(slot, Some(ItemStack::new(item, MAX_STACK)));
+    }
+    inventory
+}
+
+/// Selects a hotbar slot of a player's inventory. Invalid
+/// slots are ignored, and the client is sent its inventory
+/// again so that it shows the slot actually selected.
+pub fn select(game: &Game, player: Entity, slot: u32) {
+    let selected = match game.ecs().get_mut::<Inventory>(player) {
+        Ok(mut inventory) => inventory.select(slot as usize),
+        Err(_) => return,
+    };
+    if !selected {
+        log::debug!("Ignoring selection of invalid slot {}", slot);
+        send(game, player);
+    }
+}
+
+/// Adds items to a player's inventory and sends it to them.
+/// Returns the number of items that didn't fit.
+pub fn give(game: &Game, player: Entity, stack: ItemStack) -> u32 {
+    let remaining = match game.ecs().get_mut::<Inventory>(player) {
+        Ok(mut inventory) => inventory.add(stack),
+        Err(_) => return stack.count,
+    };
+    if remaining != stack.count {
+        send(game, player);
+    }
+    remaining
+}
+
+/// Sends a player their inventory.
+pub fn send(game: &Game, player: Entity) {
+    let inventory = match game.ecs().get::<Inventory>(player) {
+        Ok(inventory) => inventory,
+        Err(_) => return,
+    };
+    if let Ok(mailbox) = game.ecs().get::<Mailbox>(player) {
+        mailbox.send(ServerPacket::SetInventory(SetInventory {
+            slots: inventory.slots().to_vec(),
+            selected: inventory.selected() as u32,
+        }));
+    }
+}
+
+/// Empties a player's inventory, dropping its items where they stand.
+fn drop_all(game: &mut Game, player: Entity) {
+    let pos = match game.ecs().get::<Pos>(player) {
+        Ok(pos) => BlockPos::from_pos(game.world().to_main(pos.0)),
+        Err(_) => return,
+    };
+    let mut stacks = Vec::new();
+    if let Ok(mut inventory) = game.ecs().get_mut::<Inventory>(player) {
+        for slot in 0..INVENTORY_SIZE {
+            if let Some(stack) = inventory.slot(slot) {
+                stacks.push(stack);
+                inventory.set_slot(slot, None);
+            }
+        }
+    }
+    if stacks.is_empty() {
+        return;
+    }
+    for stack in stacks {
+        drops::spawn(game, pos, stack);
+    }
+    send(game, player);
+}
+
+/// System to send players their inventories when they join
+/// and to drop the inventories of players who die.
+struct InventorySystem;
+
+impl System<Game> for InventorySystem {
+    fn run(&mut self, game: &mut Game) {
+        for event in game.events().iter::<PlayerJoined>() {
+            send(game, event.player);
+        }
+
+        if game.rules().get_bool(GameRule::KeepInventory) {
+            return;
+        }
+        let died: Vec<Entity> = game
+            .events()
+            .iter::<PlayerDied>()
+            .map(|event| event.player)
+            .collect();
+        for player in died {
+            drop_all(game, player);
+        }
+    }
+}
+
+struct GiveCommand;
+
+impl Command for GiveCommand {
+    fn name(&self) -> &str {
+        "give"
+    }
+
+    fn usage(&self) -> &str {
+        "<targets> <item> [count]"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        let (targets, slug, count) = match args {
+            [targets, item] => (*targets, *item, 1),
+            [targets, item, count] => match count.parse::<u32>() {
+                Ok(count) if count > 0 => (*targets, *item, count),
+                _ => bail!("the count must be a positive number"),
+            },
+            _ => bail!("usage: {}", command::usage(self)),
+        };
+        let item = match ItemId::from_slug(slug) {
+            Some(item) => item,
+            None => bail!("unknown item '{}'", slug),
+        };
+
+        let mut lines = Vec::new();
+        for player in command::select(game, targets)? {
+            if game.ecs().get::<Inventory>(player).is_err() {
+                continue;
+            }
+            let given = count - give(game, player, ItemStack::new(item, count));
+            lines.push(format!(
+                "Gave {} {} to {}",
+                given,
+                item.descriptor().display_name(),
+                command::describe(game, player)
+            ));
+        }
+        if lines.is_empty() {
+            bail!("no targets have an inventory");
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{entity::ItemDrop, WorldPos};
+    use glam::vec3a;
+
+    use crate::game::test_game;
+
+    use super::*;
+
+    #[test]
+    fn starting_hotbar_is_full() {
+        let inventory = starting_inventory();
+        assert!(inventory.hotbar().iter().all(Option::is_some));
+        assert_eq!(
+            inventory.selected_stack().unwrap().item,
+            ItemId::from_slug("stone").unwrap()
+        );
+    }
+
+    #[test]
+    fn give_and_select() {
+        let mut game = test_game();
+        let player = game.ecs_mut().spawn((starting_inventory(),));
+        let melium = ItemId::from_slug("melium").unwrap();
+        assert_eq!(give(&game, player, ItemStack::new(melium, 10)), 0);
+        select(&game, player, 4);
+        select(&game, player, 100);
+
+        let inventory = game.ecs().get::<Inventory>(player).unwrap();
+        assert_eq!(inventory.slot(9), Some(ItemStack::new(melium, 10)));
+        assert_eq!(inventory.selected(), 4);
+    }
+
+    #[test]
+    fn drop_all_empties_the_inventory() {
+        let mut game = test_game();
+        let pos = Pos(WorldPos::main(vec3a(0., 64., 0.)));
+        let player = game.ecs_mut().spawn((starting_inventory(), pos));
+        drop_all(&mut game, player);
+
+        let inventory = game.ecs().get::<Inventory>(player).unwrap();
+        assert!(inventory.slots().iter().all(Option::is_none));
+        let drops = game.ecs().query::<&ItemDrop>().iter().count();
+        assert_eq!(drops, STARTING_ITEMS.len());
+    }
+}
@@ -1,73 +1,246 @@
 #![feature(allocator_api)]
 
-use std::{
-    panic,
-    sync::Arc,
-    thread,
-    time::{Duration, Instant},
-};
-
-use common::{world::ZoneBuilder, ChunkPos, SystemExecutor, Zone};
+use std::{cell::RefCell, env, panic, path::PathBuf, rc::Rc, sync::Arc, thread, time::Instant};
+
+use backup::{BackupSettings, Backups};
+use command::{CommandRegistry, Console};
+use common::SystemExecutor;
 pub use conn::Connection;
-use game::Game;
+use event::SaveRequested;
+pub use game::Game;
+use hashbrown::HashSet;
+use health::FallSettings;
+use history::{History, HistoryLog};
 use panic::AssertUnwindSafe;
-use protocol::{bridge::ToClient, Bridge};
-use worldgen::WorldGenerator;
+use protection::ProtectedRegions;
+use protocol::{bridge::ToClient, chunk_cache, resource_pack::ResourcePack, Bridge};
+use save::{LevelData, RegionPos, SavedColumn, WorldSave};
+use schedule::Schedule;
+use snapshot::{SnapshotSettings, Snapshots};
+use watchdog::{Watchdog, WatchdogSettings};
+pub use worldgen::{Backend, BiomeSequence};
+use worldgen::{ColumnPos, CompositionTable, WorldGenerator};
 
+pub mod backup;
+pub mod block_update;
+pub mod chat;
+pub mod command;
 mod conn;
-mod event;
+pub mod death;
+pub mod dialog;
+pub mod drops;
+pub mod edit;
+pub mod event;
+pub mod falling;
 mod game;
+pub mod game_mode;
+pub mod game_rules;
+mod generation;
+pub mod grass;
+pub mod health;
+pub mod history;
+pub mod inventory;
+pub mod protection;
+pub mod random_tick;
+mod replication;
+pub mod resource_pack;
+pub mod resume;
+pub mod sapling;
+pub mod save;
+pub mod schedule;
+pub mod selector;
+pub mod server_rules;
+pub mod signal;
+pub mod snapshot;
+pub mod tag;
+pub mod tick_rate;
+pub mod time;
 mod view;
+pub mod view_scaling;
+pub mod watchdog;
+pub mod weather;
+pub mod xp;
 
 pub type Mailbox = Bridge<ToClient>;
 
-/// The number of ticks executed per second.
+/// The default number of ticks executed per second.
+/// See the [`tick_rate`] module.
 pub const TPS: u32 = 20;
-/// The number of milliseconds in between each tick.
-pub const TICK_LENGTH: u32 = 1000 / TPS;
+/// The number of ticks executed per second of real time in slow motion.
+pub const SLOW_MOTION_TPS: u32 = 2;
 
-/// The number of chunks visible from a player's current
-/// position. Fixed for now.
-pub const VIEW_DISTANCE: u32 = 8;
+/// The most chunks players can see in each direction,
+/// unless `VOLTZ_MAX_VIEW_DISTANCE` is set. Players
+/// choose their view distance up to this maximum.
+pub const DEFAULT_MAX_VIEW_DISTANCE: u32 = 16;
+/// The fewest chunks players see in each direction,
+/// whatever view distance they ask for.
+pub const MIN_VIEW_DISTANCE: u32 = 2;
 pub const WORLD_SIZE: i32 = 16;
+/// The number of traced ticks logged when a tick panics.
+const PANIC_TRACE_TICKS: usize = 5;
+/// The seed used to generate the world. Fixed for now.
+const WORLD_SEED: u64 = 6256;
 
 /// The top-level server state.
 pub struct Server {
     clients: Vec<Connection>,
     game: Game,
     systems: SystemExecutor<Game>,
+    commands: CommandRegistry,
+    /// Present if the server accepts console commands.
+    console: Option<Console>,
 
     world_generator: Arc<WorldGenerator>,
+
+    /// Present if time-travel debugging is enabled. Shared
+    /// with the `snapshot` [command](snapshot).
+    snapshots: Option<Rc<RefCell<Snapshots>>>,
+    /// Present while [`Server::run`] runs, unless the watchdog is disabled.
+    watchdog: Option<Watchdog>,
 }
 
 impl Server {
     /// Creates a new `Server` with the given set of initial clients.
     ///
-    /// This is an expensive operation: we have to generate the world.
-    pub fn new(
-        clients: Vec<Connection>,
-        device: &Arc<wgpu::Device>,
-        queue: &Arc<wgpu::Queue>,
-    ) -> Self {
-        let world_generator = Arc::new(WorldGenerator::new(device, queue));
-        log::info!("Generating world...");
+    /// Only the area around spawn is generated before this returns;
+    /// the rest of the world generates in the background while
+    /// the server runs.
+    /// `backend` determines where world generation runs; headless
+    /// servers can use [`Backend::detect`] to fall back to the CPU.
+    ///
+    /// If `save_dir` is set, the world is loaded from that directory if
+    /// it contains a save and is periodically saved there.
+    pub fn new(clients: Vec<Connection>, backend: Backend, save_dir: Option<PathBuf>) -> Self {
+        let biome_sequence = BiomeSequence::from_env();
+        let world_generator = Arc::new(
+            WorldGenerator::new(backend)
+                .with_biome_sequence(biome_sequence.clone())
+                .with_composition(CompositionTable::from_env()),
+        );
+        let mut save = save_dir.map(WorldSave::new);
+        let new_world = save.as_ref().map_or(true, |save| !save.exists());
+        let (seed, loaded) = load_world(&mut save, &biome_sequence);
+        let history = save
+            .as_ref()
+            .map(|save| History::spawn(HistoryLog::new(save.history_dir())));
+        let backups = save
+            .as_ref()
+            .map(|save| Backups::new(save, BackupSettings::from_env()));
+        let loaded_columns: HashSet<ColumnPos> = loaded.iter().map(|column| column.pos).collect();
+
+        if world_generator.is_cpu() {
+            log::info!("Preparing spawn area on the CPU...");
+        } else {
+            log::info!("Preparing spawn area...");
+        }
         let start = Instant::now();
-        let main_zone = generate_world(&world_generator);
-        log::info!("World generated in {:?}", start.elapsed());
+        let (main_zone, available_columns) =
+            generation::generate_spawn_area(&world_generator, seed, loaded, |stage, percent| {
+                for conn in &clients {
+                    conn.send_worldgen_progress(stage, percent);
+                }
+            });
+        log::info!("Spawn area prepared in {:?}", start.elapsed());
+
+        let mut game = Game::new(main_zone);
+        game.set_tps(tick_rate::tps_from_env());
+        game.set_reach(game_mode::reach_from_env());
+        game.set_fall_settings(FallSettings::from_env());
+        game.events().enable_tracing_from_env();
+        // Compression only pays off over a real network, so it is opt-in.
+        game.set_compress_chunks(env::var("VOLTZ_COMPRESS_CHUNKS").is_ok());
+        // Clients only load cached chunks with matching hashes, so caching is safe.
+        game.set_cache_chunks(env::var("VOLTZ_NO_CHUNK_CACHE").is_err());
+        game.set_world_id(chunk_cache::world_id(seed));
+        game.set_resource_pack(resource_pack::from_env());
+        game.set_server_rules(server_rules::from_env());
+        game.set_max_view_distance(view::max_distance_from_env());
+        game.set_saved(save.is_some());
+        // Saves without protected regions predate them,
+        // so only new worlds get spawn protection.
+        if new_world {
+            *game.protected_regions_mut() =
+                ProtectedRegions::with_spawn_protection(protection::spawn_radius_from_env());
+        }
+        if let Some(save) = &save {
+            match save.read_players() {
+                Ok(players) => game.set_offline_players(players),
+                Err(e) => log::error!("Failed to load player data: {:?}", e),
+            }
+            match save.read_game_rules() {
+                Ok(rules) => *game.rules_mut() = rules,
+                Err(e) => log::error!("Failed to load game rules: {:?}", e),
+            }
+            match save.read_protected_regions() {
+                Ok(Some(regions)) => *game.protected_regions_mut() = regions,
+                Ok(None) => {}
+                Err(e) => log::error!("Failed to load protected regions: {:?}", e),
+            }
+        }
+        let mut unsaved_regions = HashSet::new();
+        for pos in available_columns {
+            game.mark_column_generated(pos);
+            if !loaded_columns.contains(&pos) {
+                unsaved_regions.insert(RegionPos::of_column(pos));
+            }
+        }
+        let save = save
+            .zip(backups.clone())
+            .map(|(save, backups)| (save, unsaved_regions, backups));
+        let systems = setup(
+            &game,
+            Arc::clone(&world_generator),
+            seed,
+            save,
+            history.clone(),
+            Schedule::from_env(),
+        );
 
-        let game = Game::new(main_zone);
-        let systems = setup();
+        let snapshots = SnapshotSettings::from_env().map(|settings| {
+            log::info!(
+                "Recording snapshots every {} ticks for debugging",
+                settings.interval
+            );
+            Rc::new(RefCell::new(Snapshots::new(settings)))
+        });
 
         Self {
             clients,
             game,
             systems,
+            commands: register_commands(history, backups, snapshots.clone()),
+            console: None,
             world_generator,
+            snapshots,
+            watchdog: None,
         }
     }
 
-    /// Runs the server.
+    /// Creates the integrated server of a singleplayer world saved in
+    /// `save_dir`, with `conn` as its only client. Spawn is not protected,
+    /// since the player owns the world.
+    pub fn singleplayer(conn: Connection, backend: Backend, save_dir: PathBuf) -> Self {
+        let mut server = Self::new(vec![conn], backend, Some(save_dir));
+        server.disable_spawn_protection();
+        server
+    }
+
+    /// Shows players `rules` when they join, replacing the
+    /// [server rules](server_rules) read from the environment.
+    pub fn set_server_rules(&mut self, rules: Option<String>) {
+        self.game.set_server_rules(rules);
+    }
+
+    /// Runs the server until it is stopped
+    /// with the `stop` [command](command).
+    ///
+    /// A [watchdog](watchdog) reports ticks that stall.
     pub fn run(&mut self) {
+        if let Some(settings) = WatchdogSettings::from_env() {
+            let system_names = self.systems.timings().map(|(name, _)| name).collect();
+            self.watchdog = Some(Watchdog::start(settings, system_names));
+        }
         loop {
             let start = Instant::now();
 
@@ -75,56 +248,255 @@ impl Server {
                 self.tick();
             })) {
                 log::error!("The server panicked while ticking: {:?}", e);
+                if self.game.events().is_tracing() {
+                    log::error!(
+                        "Events of the last ticks:\n{}",
+                        self.game.events().dump(PANIC_TRACE_TICKS)
+                    );
+                }
                 log::error!("This is a bug. Please report it.");
                 log::error!("We will try to recover, but the game state may have become corrupted. We advise that you restart the server.");
             }
 
-            let elapsed = start.elapsed().as_millis() as u32;
-            if elapsed > TICK_LENGTH {
-                log::warn!("Tick took too long! ({}ms)", elapsed);
+            if self.game.is_stop_requested() {
+                self.stop();
+                return;
+            }
+
+            let elapsed = start.elapsed();
+            let tick_length = self.game.real_tick_length();
+            if elapsed > tick_length {
+                log::warn!("Tick took too long! ({}ms)", elapsed.as_millis());
                 continue;
             } else {
-                let remaining = TICK_LENGTH - elapsed;
-                thread::sleep(Duration::from_millis(remaining as u64));
+                thread::sleep(tick_length - elapsed);
             }
         }
     }
 
-    fn tick(&mut self) {
+    /// Disconnects all clients. The world was saved
+    /// during the tick in which the stop was requested.
+    fn stop(&mut self) {
+        for conn in &mut self.clients {
+            conn.disconnect(&mut self.game, Some("The server stopped".to_owned()));
+        }
+        self.clients.clear();
+        resume::despawn_all(&mut self.game);
+        self.watchdog = None;
+        log::info!("Server stopped");
+    }
+
+    /// Adds a client that connected after the server started,
+    /// such as one [resuming](resume) a lost connection.
+    pub fn add_client(&mut self, conn: Connection) {
+        self.clients.push(conn);
+    }
+
+    /// Gets the game state.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Requires clients to load `pack` before joining, replacing
+    /// the [resource pack](resource_pack) read from the environment.
+    /// Applies to clients that haven't yet received `ServerInfo`.
+    pub fn set_resource_pack(&mut self, pack: Option<ResourcePack>) {
+        self.game.set_resource_pack(pack);
+    }
+
+    /// Removes the [spawn protection](protection) region, if
+    /// the world has one. Other protected regions are kept.
+    pub fn disable_spawn_protection(&mut self) {
+        self.game
+            .protected_regions_mut()
+            .remove(protection::SPAWN_REGION);
+    }
+
+    /// Starts accepting [commands](command) from `console`.
+    pub fn set_console(&mut self, console: Console) {
+        self.console = Some(console);
+    }
+
+    /// Gets the commands available to the console, so
+    /// that more can be registered.
+    pub fn commands_mut(&mut self) -> &mut CommandRegistry {
+        &mut self.commands
+    }
+
+    /// Runs a single tick. [`Server::run`] calls this
+    /// at a fixed rate; tests may call it directly.
+    pub fn tick(&mut self) {
+        let start = Instant::now();
+        self.game.advance_tick();
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.begin_tick(self.game.tick());
+        }
+        self.game.events().begin_tick();
         self.game.events().set_system(0);
         self.poll_connections();
+        self.run_commands();
+        if let Some(watchdog) = &self.watchdog {
+            if watchdog.take_recovered() && watchdog.settings().save {
+                log::info!("Saving the world after a stalled tick");
+                self.game.events().push(SaveRequested);
+            }
+        }
 
+        let watchdog = &self.watchdog;
         self.systems.run(&mut self.game, |game, system| {
             game.events().set_system(system + 1);
+            if let Some(watchdog) = watchdog {
+                watchdog.begin_system(system + 1);
+            }
         });
 
+        if let Some(snapshots) = &self.snapshots {
+            snapshots.borrow_mut().update(&self.game);
+        }
+
         self.game.bump_mut().reset();
+        self.game.set_last_tick_duration(start.elapsed());
+        if let Some(watchdog) = &self.watchdog {
+            watchdog.end_tick();
+        }
+    }
+
+    /// Runs the commands received from the console since the last tick.
+    fn run_commands(&mut self) {
+        let lines = match &self.console {
+            Some(console) => console.poll(),
+            None => return,
+        };
+        for line in lines {
+            log::info!("Running command '{}'", line.trim());
+            match self.commands.run(&mut self.game, &line) {
+                Ok(message) if message.is_empty() => {}
+                Ok(message) => log::info!("{}", message),
+                Err(e) => log::warn!("Command failed: {:#}", e),
+            }
+        }
     }
 
     fn poll_connections(&mut self) {
         for conn in &mut self.clients {
             conn.tick(&mut self.game);
         }
+        self.clients.retain(|conn| !conn.is_disconnected());
     }
 }
 
-fn generate_world(world_generator: &WorldGenerator) -> Zone {
-    let mut builder = ZoneBuilder::new(
-        ChunkPos { x: 0, y: 0, z: 0 },
-        ChunkPos {
-            x: WORLD_SIZE - 1,
-            y: 15,
-            z: WORLD_SIZE - 1,
-        },
-    );
-    world_generator.generate_into_zone(&mut builder, 6256);
-    builder.build().ok().expect("failed to create all chunks")
+/// Loads the world from `save` if it contains one, returning the seed and
+/// the saved columns. If the save cannot be loaded, saving is disabled
+/// so that it is not overwritten.
+fn load_world(
+    save: &mut Option<WorldSave>,
+    biome_sequence: &BiomeSequence,
+) -> (u64, Vec<SavedColumn>) {
+    let world_save = match save {
+        Some(world_save) => world_save,
+        None => return (WORLD_SEED, Vec::new()),
+    };
+    let dir = world_save.dir().display().to_string();
+
+    if !world_save.exists() {
+        log::info!("Creating a new world in {}", dir);
+        if let Err(e) = world_save.write_level(&LevelData::new(WORLD_SEED, biome_sequence)) {
+            log::error!(
+                "Failed to create a save in {}: {:#}. The world will not be saved.",
+                dir,
+                e
+            );
+            *save = None;
+        }
+        return (WORLD_SEED, Vec::new());
+    }
+
+    match world_save.load() {
+        Ok((level, columns)) => {
+            log::info!("Loaded {} chunk columns from {}", columns.len(), dir);
+            if level.has_other_biome_sequence(biome_sequence) {
+                log::warn!(
+                    "The world in {} was generated with a different biome sequence. \
+                     Newly generated columns won't line up with the saved ones.",
+                    dir
+                );
+            }
+            (level.seed, columns)
+        }
+        Err(e) => {
+            log::error!(
+                "Failed to load the world from {}: {:#}. Generating a new world, which will not be saved.",
+                dir,
+                e
+            );
+            *save = None;
+            (WORLD_SEED, Vec::new())
+        }
+    }
+}
+
+fn register_commands(
+    history: Option<History>,
+    backups: Option<Backups>,
+    snapshots: Option<Rc<RefCell<Snapshots>>>,
+) -> CommandRegistry {
+    let mut commands = CommandRegistry::new();
+    command::register(&mut commands);
+    tick_rate::register_commands(&mut commands);
+    time::register_commands(&mut commands);
+    death::register_commands(&mut commands);
+    game_mode::register_commands(&mut commands);
+    game_rules::register_commands(&mut commands);
+    history::register_commands(&mut commands, history);
+    backup::register_commands(&mut commands, backups);
+    tag::register_commands(&mut commands);
+    inventory::register_commands(&mut commands);
+    protection::register_commands(&mut commands);
+    snapshot::register_commands(&mut commands, snapshots);
+    commands
 }
 
-fn setup() -> SystemExecutor<Game> {
+fn setup(
+    game: &Game,
+    world_generator: Arc<WorldGenerator>,
+    seed: u64,
+    save: Option<(WorldSave, HashSet<RegionPos>, Backups)>,
+    history: Option<History>,
+    schedule: Option<Schedule>,
+) -> SystemExecutor<Game> {
     let mut systems = SystemExecutor::new();
 
+    generation::setup(&mut systems, game, world_generator, seed);
+    view_scaling::setup(&mut systems);
     view::setup(&mut systems);
+    server_rules::setup(&mut systems);
+    resume::setup(&mut systems);
+    tag::setup(&mut systems);
+    replication::setup(&mut systems);
+    game_mode::setup(&mut systems);
+    health::setup(&mut systems);
+    game_rules::setup(&mut systems);
+    edit::setup(&mut systems);
+    chat::setup(&mut systems);
+    block_update::setup(&mut systems);
+    random_tick::setup(&mut systems);
+    grass::setup(&mut systems);
+    sapling::setup(&mut systems);
+    signal::setup(&mut systems);
+    falling::setup(&mut systems);
+    xp::setup(&mut systems);
+    inventory::setup(&mut systems);
+    drops::setup(&mut systems);
+    weather::setup(&mut systems, game);
+    time::setup(&mut systems, game);
+    tick_rate::setup(&mut systems, game);
+    schedule::setup(&mut systems, schedule);
+    if let Some(history) = history {
+        history::setup(&mut systems, history);
+    }
+    if let Some((save, unsaved_regions, backups)) = save {
+        save::setup(&mut systems, save, unsaved_regions, backups);
+    }
 
     systems
 }
@@ -0,0 +1,424 @@
+//! Protected regions of the world.
+//!
+//! A protected region is a named cuboid of blocks with permissions which
+//! restrict what players may do inside it: `build` allows placing and
+//! breaking blocks, and `interact` allows using blocks such as doors.
+//! The [edit system](crate::edit) rejects edits that would change a block
+//! in a region without the permission the edit needs. Where regions
+//! overlap, an edit needs the permission in all of them.
+//!
+//! Newly created worlds start with a `spawn` region, which allows
+//! interacting but not building within `VOLTZ_SPAWN_PROTECTION` blocks
+//! (16 by default) horizontally of the spawn point, at any height. Setting
+//! it to 0 leaves spawn unprotected. Worlds loaded from a save only have
+//! the regions saved with them. Singleplayer worlds are never protected,
+//! since their only player owns them; see [`Server::singleplayer`].
+//!
+//! [`Server::singleplayer`]: crate::Server::singleplayer
+//!
+//! The `protect` console [command](crate::command) manages the regions:
+//! * `protect list`: lists the regions and their permissions.
+//! * `protect add <name> <x1> <y1> <z1> <x2> <y2> <z2>`: protects the
+//!   cuboid with the given corners, allowing nothing inside it.
+//! * `protect remove <name>`: removes a region.
+//! * `protect set <name> <build|interact> <true|false>`: changes
+//!   one of a region's permissions.
+
+use std::{collections::BTreeMap, env, fmt};
+
+use anyhow::{bail, Context};
+use common::{chunk::CHUNK_DIM, edit::BlockEdit, BlockPos};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    command::{self, Command, CommandRegistry},
+    game::Game,
+    generation::{COLUMN_HEIGHT, SPAWN_COLUMN},
+};
+
+/// The name of the region protecting spawn.
+pub const SPAWN_REGION: &str = "spawn";
+/// How far spawn protection extends from the spawn point
+/// in blocks, unless `VOLTZ_SPAWN_PROTECTION` is set.
+pub const DEFAULT_SPAWN_RADIUS: u32 = 16;
+
+/// Something a protected region may allow players to do.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Permission {
+    /// Placing and breaking blocks.
+    Build,
+    /// Using blocks, e.g. opening doors.
+    Interact,
+}
+
+impl Permission {
+    /// Returns the permission needed to make `edit`.
+    pub fn required_by(edit: BlockEdit) -> Self {
+        match edit {
+            BlockEdit::Place(_) | BlockEdit::Break => Permission::Build,
+            BlockEdit::Use => Permission::Interact,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Permission::Build => "build",
+            Permission::Interact => "interact",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "build" => Some(Permission::Build),
+            "interact" => Some(Permission::Interact),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A cuboid of blocks and what players may do inside it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectedRegion {
+    /// The corner with the lowest coordinates.
+    min: BlockPos,
+    /// The corner with the highest coordinates. Inclusive.
+    max: BlockPos,
+    build: bool,
+    interact: bool,
+}
+
+impl ProtectedRegion {
+    /// Creates a region spanning the blocks between two opposite
+    /// corners, inclusive. The region allows nothing.
+    pub fn new(a: BlockPos, b: BlockPos) -> Self {
+        Self {
+            min: BlockPos {
+                x: a.x.min(b.x),
+                y: a.y.min(b.y),
+                z: a.z.min(b.z),
+            },
+            max: BlockPos {
+                x: a.x.max(b.x),
+                y: a.y.max(b.y),
+                z: a.z.max(b.z),
+            },
+            build: false,
+            interact: false,
+        }
+    }
+
+    /// Gets the corner with the lowest coordinates.
+    pub fn min(&self) -> BlockPos {
+        self.min
+    }
+
+    /// Gets the corner with the highest coordinates.
+    pub fn max(&self) -> BlockPos {
+        self.max
+    }
+
+    /// Returns whether the block at `pos` is inside the region.
+    pub fn contains(&self, pos: BlockPos) -> bool {
+        (self.min.x..=self.max.x).contains(&pos.x)
+            && (self.min.y..=self.max.y).contains(&pos.y)
+            && (self.min.z..=self.max.z).contains(&pos.z)
+    }
+
+    /// Returns whether players have `permission` inside the region.
+    pub fn allows(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::Build => self.build,
+            Permission::Interact => self.interact,
+        }
+    }
+
+    pub fn set_allowed(&mut self, permission: Permission, allowed: bool) {
+        match permission {
+            Permission::Build => self.build = allowed,
+            Permission::Interact => self.interact = allowed,
+        }
+    }
+
+    /// Returns the region with `permission` allowed.
+    pub fn allowing(mut self, permission: Permission) -> Self {
+        self.set_allowed(permission, true);
+        self
+    }
+}
+
+/// The protected regions of the world, by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtectedRegions {
+    regions: BTreeMap<String, ProtectedRegion>,
+}
+
+impl ProtectedRegions {
+    /// Creates an empty set of regions, which protects nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the regions of a new world: a [`SPAWN_REGION`] extending
+    /// `radius` blocks from the spawn point, or none if `radius` is zero.
+    pub fn with_spawn_protection(radius: u32) -> Self {
+        let mut regions = Self::new();
+        if radius > 0 {
+            let radius = radius as i32;
+            let spawn_x = SPAWN_COLUMN.x * CHUNK_DIM as i32;
+            let spawn_z = SPAWN_COLUMN.z * CHUNK_DIM as i32;
+            let region = ProtectedRegion::new(
+                BlockPos {
+                    x: spawn_x - radius,
+                    y: 0,
+                    z: spawn_z - radius,
+                },
+                BlockPos {
+                    x: spawn_x + radius,
+                    y: COLUMN_HEIGHT * CHUNK_DIM as i32 - 1,
+                    z: spawn_z + radius,
+                },
+            );
+            regions.insert(SPAWN_REGION, region.allowing(Permission::Interact));
+        }
+        regions
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ProtectedRegion> {
+        self.regions.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut ProtectedRegion> {
+        self.regions.get_mut(name)
+    }
+
+    /// Adds a region, returning the region it
+    /// replaced if one had the same name.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        region: ProtectedRegion,
+    ) -> Option<ProtectedRegion> {
+        self.regions.insert(name.into(), region)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<ProtectedRegion> {
+        self.regions.remove(name)
+    }
+
+    /// Iterates over the regions, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ProtectedRegion)> + '_ {
+        self.regions
+            .iter()
+            .map(|(name, region)| (name.as_str(), region))
+    }
+
+    /// Returns whether players have `permission` at `pos`,
+    /// i.e. whether every region containing it allows it.
+    pub fn allows(&self, pos: BlockPos, permission: Permission) -> bool {
+        self.regions
+            .values()
+            .filter(|region| region.contains(pos))
+            .all(|region| region.allows(permission))
+    }
+}
+
+/// Reads the radius of spawn protection from `VOLTZ_SPAWN_PROTECTION`,
+/// falling back to [`DEFAULT_SPAWN_RADIUS`].
+pub fn spawn_radius_from_env() -> u32 {
+    match env::var("VOLTZ_SPAWN_PROTECTION") {
+        Ok(value) => match value.parse() {
+            Ok(radius) => radius,
+            Err(_) => {
+                log::warn!("Ignoring invalid VOLTZ_SPAWN_PROTECTION '{}'", value);
+                DEFAULT_SPAWN_RADIUS
+            }
+        },
+        Err(_) => DEFAULT_SPAWN_RADIUS,
+    }
+}
+
+/// Registers the `protect` console command.
+pub fn register_commands(commands: &mut CommandRegistry) {
+    commands.register(ProtectCommand);
+}
+
+struct ProtectCommand;
+
+impl Command for ProtectCommand {
+    fn name(&self) -> &str {
+        "protect"
+    }
+
+    fn usage(&self) -> &str {
+        "list | add <name> <x1> <y1> <z1> <x2> <y2> <z2> | remove <name> | set <name> <build|interact> <true|false>"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        match args {
+            ["list"] => {
+                let mut list = "Protected regions:".to_owned();
+                for (name, region) in game.protected_regions().iter() {
+                    list.push_str(&format!("\n  {}", describe(name, region)));
+                }
+                if game.protected_regions().iter().next().is_none() {
+                    list.push_str(" none");
+                }
+                Ok(list)
+            }
+            ["add", name, x1, y1, z1, x2, y2, z2] => {
+                let a = parse_block_pos(x1, y1, z1)?;
+                let b = parse_block_pos(x2, y2, z2)?;
+                let regions = game.protected_regions_mut();
+                if regions.get(name).is_some() {
+                    bail!("region '{}' already exists", name);
+                }
+                let region = ProtectedRegion::new(a, b);
+                regions.insert(*name, region);
+                Ok(format!("Protected {}", describe(name, &region)))
+            }
+            ["remove", name] => {
+                game.protected_regions_mut()
+                    .remove(name)
+                    .with_context(|| unknown_region(name))?;
+                Ok(format!("Removed region '{}'", name))
+            }
+            ["set", name, permission, allowed] => {
+                let permission = Permission::from_name(permission).with_context(|| {
+                    format!(
+                        "unknown permission '{}'; expected build or interact",
+                        permission
+                    )
+                })?;
+                let allowed: bool = allowed
+                    .parse()
+                    .context("expected true or false for the permission")?;
+                let region = game
+                    .protected_regions_mut()
+                    .get_mut(name)
+                    .with_context(|| unknown_region(name))?;
+                region.set_allowed(permission, allowed);
+                Ok(format!("Set {} to {} in '{}'", permission, allowed, name))
+            }
+            _ => bail!("usage: {}", command::usage(self)),
+        }
+    }
+}
+
+fn describe(name: &str, region: &ProtectedRegion) -> String {
+    let (min, max) = (region.min(), region.max());
+    format!(
+        "'{}' from {} {} {} to {} {} {} (build: {}, interact: {})",
+        name,
+        min.x,
+        min.y,
+        min.z,
+        max.x,
+        max.y,
+        max.z,
+        region.allows(Permission::Build),
+        region.allows(Permission::Interact)
+    )
+}
+
+fn unknown_region(name: &str) -> String {
+    format!("unknown region '{}'; run 'protect list' for a list", name)
+}
+
+fn parse_block_pos(x: &str, y: &str, z: &str) -> anyhow::Result<BlockPos> {
+    let parse = |coordinate: &str| {
+        coordinate
+            .parse::<i32>()
+            .with_context(|| format!("invalid coordinate '{}'", coordinate))
+    };
+    Ok(BlockPos {
+        x: parse(x)?,
+        y: parse(y)?,
+        z: parse(z)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::test_game;
+
+    use super::*;
+
+    fn pos(x: i32, y: i32, z: i32) -> BlockPos {
+        BlockPos { x, y, z }
+    }
+
+    #[test]
+    fn overlapping_regions_all_need_to_allow() {
+        let mut regions = ProtectedRegions::new();
+        regions.insert(
+            "house",
+            ProtectedRegion::new(pos(10, 0, 10), pos(0, 20, 0)).allowing(Permission::Interact),
+        );
+        regions.insert("vault", ProtectedRegion::new(pos(2, 2, 2), pos(4, 4, 4)));
+
+        assert!(regions.allows(pos(11, 5, 5), Permission::Build));
+        assert!(!regions.allows(pos(10, 5, 0), Permission::Build));
+        assert!(regions.allows(pos(10, 5, 0), Permission::Interact));
+        assert!(!regions.allows(pos(3, 3, 3), Permission::Interact));
+    }
+
+    #[test]
+    fn spawn_protection() {
+        let regions = ProtectedRegions::with_spawn_protection(4);
+        let spawn = regions.get(SPAWN_REGION).unwrap();
+        let x = SPAWN_COLUMN.x * CHUNK_DIM as i32;
+        let z = SPAWN_COLUMN.z * CHUNK_DIM as i32;
+        assert!(spawn.contains(pos(x + 4, 0, z - 4)));
+        assert!(!spawn.contains(pos(x + 5, 0, z)));
+        assert!(!spawn.allows(Permission::Build));
+        assert!(spawn.allows(Permission::Interact));
+
+        assert_eq!(
+            ProtectedRegions::with_spawn_protection(0),
+            ProtectedRegions::new()
+        );
+    }
+
+    #[test]
+    fn protect_command() {
+        let mut game = test_game();
+        let mut commands = CommandRegistry::new();
+        register_commands(&mut commands);
+
+        commands
+            .run(&mut game, "/protect add shop 5 0 5 -5 10 -5")
+            .unwrap();
+        assert!(commands
+            .run(&mut game, "/protect add shop 0 0 0 1 1 1")
+            .is_err());
+        assert!(!game
+            .protected_regions()
+            .allows(pos(0, 0, 0), Permission::Build));
+
+        commands
+            .run(&mut game, "/protect set shop build true")
+            .unwrap();
+        assert!(game
+            .protected_regions()
+            .allows(pos(0, 0, 0), Permission::Build));
+        assert!(!game
+            .protected_regions()
+            .allows(pos(0, 0, 0), Permission::Interact));
+        assert!(commands
+            .run(&mut game, "/protect set shop fly true")
+            .is_err());
+
+        let list = commands.run(&mut game, "/protect list").unwrap();
+        assert!(list.contains("'shop' from -5 0 -5 to 5 10 5"), "{}", list);
+
+        commands.run(&mut game, "/protect remove shop").unwrap();
+        assert!(commands.run(&mut game, "/protect remove shop").is_err());
+        assert_eq!(game.protected_regions(), &ProtectedRegions::new());
+    }
+}
@@ -0,0 +1,55 @@
+//! Random ticks.
+//!
+//! Each tick, random blocks in every generated, non-empty chunk are pushed
+//! as [`RandomTick`] events. The `randomTickSpeed` [game rule](GameRule)
+//! sets how many blocks are chosen in each chunk. Systems use random
+//! ticks for slow processes spread over the whole world, like
+//! [grass](crate::grass) spreading, without scanning every block. Air
+//! never receives random ticks.
+
+use common::{blocks::Air, chunk::CHUNK_DIM, game_rules::GameRule, System, SystemExecutor};
+use rand::Rng;
+
+use crate::{event::RandomTick, game::Game, generation::COLUMN_HEIGHT};
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(RandomTickSystem);
+}
+
+/// System to emit [`RandomTick`] events.
+struct RandomTickSystem;
+
+impl System<Game> for RandomTickSystem {
+    fn run(&mut self, game: &mut Game) {
+        let ticks_per_chunk = game.rules().get_int(GameRule::RandomTickSpeed);
+        let mut ticks = Vec::new_in(game.bump());
+        {
+            let mut rng = game.rng();
+            for column in game.generated_columns() {
+                for y in 0..COLUMN_HEIGHT {
+                    let chunk_pos = column.chunk(y);
+                    let chunk = match game.main_zone().chunk(chunk_pos) {
+                        Some(chunk) if !chunk.is_empty() => chunk,
+                        _ => continue,
+                    };
+                    for _ in 0..ticks_per_chunk {
+                        let x = rng.gen_range(0, CHUNK_DIM);
+                        let y = rng.gen_range(0, CHUNK_DIM);
+                        let z = rng.gen_range(0, CHUNK_DIM);
+                        let block = chunk.get(x, y, z);
+                        if block.is::<Air>() {
+                            continue;
+                        }
+                        let pos = chunk_pos.block_min().offset(x as i32, y as i32, z as i32);
+                        ticks.push(RandomTick { pos, block });
+                    }
+                }
+            }
+        }
+
+        let mut events = game.events();
+        for tick in ticks {
+            events.push(tick);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! Replication of players to each other.
+//!
+//! Each player is sent every other player whose position is in their
+//! [`View`] with `SpawnEntity`, kept up to date with `EntityPosition`,
+//! and told to forget them with `DespawnEntity` when they leave the
+//! view or the game.
+//!
+//! Falling blocks are replicated separately by [`falling`](crate::falling),
+//! and dropped items by [`drops`](crate::drops).
+
+use common::{
+    entity::player::{Username, View},
+    ChunkPos, Orient, Pos, System, SystemExecutor, WorldPos,
+};
+use glam::Vec2;
+use hashbrown::{HashMap, HashSet};
+use hecs::Entity;
+use protocol::packets::{
+    server::{DespawnEntity, EntityKind, EntityPosition, SpawnEntity},
+    ServerPacket,
+};
+
+use crate::{game::Game, Mailbox};
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(ReplicationSystem::default());
+}
+
+/// System to send players the other players they can see.
+#[derive(Default)]
+struct ReplicationSystem {
+    /// Maps each player to the players they have been sent.
+    known: HashMap<Entity, HashSet<Entity>>,
+    /// The position and orientation of each player as of the
+    /// previous tick, and whether the player moved in that tick.
+    last_positions: HashMap<Entity, (WorldPos, Vec2, bool)>,
+}
+
+impl System<Game> for ReplicationSystem {
+    fn run(&mut self, game: &mut Game) {
+        let mut players = Vec::new_in(game.bump());
+        players.extend(game.ecs().query::<(&Pos, &Orient, &Username)>().iter().map(
+            |(player, (pos, orient, _))| {
+                let (moved, was_moving) = match self.last_positions.get(&player) {
+                    Some(&(last_pos, last_orient, was_moving)) => {
+                        (last_pos != pos.0 || last_orient != orient.0, was_moving)
+                    }
+                    None => (true, false),
+                };
+                // The position is also sent on the tick a player stops, so
+                // clients stop extrapolating their movement.
+                (player, pos.0, orient.0, moved, moved || was_moving)
+            },
+        ));
+
+        // Forget players who left the game.
+        self.known
+            .retain(|&viewer, _| players.iter().any(|&(player, ..)| player == viewer));
+
+        for (viewer, (view, mailbox)) in game.ecs().query::<(&View, &Mailbox)>().iter() {
+            let known = self.known.entry(viewer).or_default();
+
+            for &(player, pos, orient, _, send_position) in &players {
+                if player == viewer {
+                    continue;
+                }
+                let visible = view.contains(ChunkPos::from_pos(game.world().to_main(pos)));
+                let entity = player.to_bits();
+                if visible && known.insert(player) {
+                    let username = game.ecs().get::<Username>(player).unwrap().0.clone();
+                    mailbox.send(ServerPacket::SpawnEntity(SpawnEntity {
+                        entity,
+                        pos,
+                        orient,
+                        kind: EntityKind::Player { username },
+                    }));
+                } else if visible && send_position {
+                    mailbox.send(ServerPacket::EntityPosition(EntityPosition {
+                        entity,
+                        pos,
+                        orient,
+                    }));
+                } else if !visible && known.remove(&player) {
+                    mailbox.send(ServerPacket::DespawnEntity(DespawnEntity { entity }));
+                }
+            }
+
+            // Despawn players who left the game.
+            known.retain(|&known| {
+                let exists = players.iter().any(|&(player, ..)| player == known);
+                if !exists {
+                    mailbox.send(ServerPacket::DespawnEntity(DespawnEntity {
+                        entity: known.to_bits(),
+                    }));
+                }
+                exists
+            });
+        }
+
+        self.last_positions.clear();
+        self.last_positions.extend(
+            players
+                .iter()
+                .map(|&(player, pos, orient, moved, _)| (player, (pos, orient, moved))),
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! The resource pack clients must load to join.
+//!
+//! Set `VOLTZ_RESOURCE_PACK` to the path of the pack's archive and
+//! `VOLTZ_RESOURCE_PACK_URL` to where clients download it. The server
+//! hashes its own copy at startup and advertises the hash, so the two
+//! must be the same file: clients refuse archives with another hash.
+//! See [`protocol::resource_pack`] for the archive format.
+
+use std::{env, fs, path::Path};
+
+use anyhow::{bail, Context};
+use protocol::resource_pack::{PackHash, ResourcePack};
+
+/// Reads the required resource pack from the environment.
+/// Returns `None` if there is none or it can't be read.
+pub fn from_env() -> Option<ResourcePack> {
+    let path = env::var_os("VOLTZ_RESOURCE_PACK")?;
+    let url = match env::var("VOLTZ_RESOURCE_PACK_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            log::error!("Ignoring VOLTZ_RESOURCE_PACK since VOLTZ_RESOURCE_PACK_URL is not set");
+            return None;
+        }
+    };
+    match load(Path::new(&path), url) {
+        Ok(pack) => {
+            log::info!("Requiring resource pack {} ({})", pack.url, pack.hash);
+            Some(pack)
+        }
+        Err(e) => {
+            log::error!("Failed to load the resource pack: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Describes the archive at `path`, to be downloaded from `url`.
+pub fn load(path: &Path, url: String) -> anyhow::Result<ResourcePack> {
+    if !["http://", "https://", "file://"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+    {
+        bail!(
+            "resource pack URL '{}' is not an http, https, or file URL",
+            url
+        );
+    }
+    let data = fs::read(path).with_context(|| format!("failed to read '{}'", path.display()))?;
+    Ok(ResourcePack {
+        url,
+        hash: PackHash::of(&data),
+    })
+}
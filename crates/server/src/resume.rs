@@ -0,0 +1,179 @@
+//! Resuming connections that were lost.
+//!
+//! Each player gets a session token in `JoinGame`. If their connection
+//! dies without a `Disconnect`, e.g. on a flaky network, the player is
+//! suspended rather than despawned: their entity stays in the world, and
+//! packets sent to them are held back. A client that logs in again within
+//! [`GRACE_PERIOD`] with the token as its `resume_token` takes over the
+//! same entity. The held-back packets are then replayed, except stale
+//! pings and chunk loads; the [view](crate::view) system sends chunks
+//! again for the player's current view instead. Players who don't
+//! return in time are despawned as if they had left.
+
+use std::time::Duration;
+
+use common::{entity::player::Username, System, SystemExecutor};
+use hashbrown::HashMap;
+use hecs::Entity;
+use protocol::{
+    bridge::{self, ToClient, ToServer},
+    packets::{ServerPacket, SharedPacket},
+    Bridge,
+};
+
+use crate::{conn, event::PlayerResumed, game::Game, Mailbox};
+
+/// How long a suspended player waits for their client to return.
+pub const GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Component holding the token a player's client
+/// can use to resume their session.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SessionToken(pub u64);
+
+/// The players whose connections were lost, by session token.
+#[derive(Default)]
+pub struct Suspended {
+    players: HashMap<u64, SuspendedPlayer>,
+}
+
+struct SuspendedPlayer {
+    player: Entity,
+    /// Receives the packets sent to the player while suspended.
+    held: Bridge<ToServer>,
+    /// The tick the player was suspended.
+    since: u64,
+}
+
+impl Suspended {
+    /// Returns the suspended player with the session
+    /// `token` and `username`, if there is one.
+    pub fn find(&self, game: &Game, token: u64, username: &str) -> Option<Entity> {
+        let suspended = self.players.get(&token)?;
+        let name = game.ecs().get::<Username>(suspended.player).ok()?;
+        if name.0 == username {
+            Some(suspended.player)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the number of suspended players.
+    pub fn len(&self) -> usize {
+        self.players.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.players.is_empty()
+    }
+}
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(ExpirySystem);
+}
+
+/// Suspends a player whose connection was lost. Packets sent
+/// to their [`Mailbox`] are held back until they resume.
+pub(crate) fn suspend(game: &mut Game, player: Entity) {
+    let token = match game.ecs().get::<SessionToken>(player) {
+        Ok(token) => token.0,
+        Err(_) => {
+            conn::despawn_player(game, player);
+            return;
+        }
+    };
+    let (held, mailbox) = bridge::singleplayer();
+    if let Ok(mut old) = game.ecs().get_mut::<Mailbox>(player) {
+        *old = mailbox;
+    }
+    if let Ok(username) = game.ecs().get::<Username>(player) {
+        log::info!(
+            "Lost the connection to {}. Holding their player for {:?}.",
+            username.0,
+            GRACE_PERIOD
+        );
+    }
+    let since = game.tick();
+    game.suspended_mut().players.insert(
+        token,
+        SuspendedPlayer {
+            player,
+            held,
+            since,
+        },
+    );
+}
+
+/// Resumes the suspended player with the session `token` on
+/// `bridge`, replaying the packets held back for them.
+///
+/// Call after sending `JoinGame`, so that the client is
+/// in the game state when the packets arrive.
+pub(crate) fn resume(game: &mut Game, token: u64, bridge: &Bridge<ToClient>) -> Option<Entity> {
+    let suspended = game.suspended_mut().players.remove(&token)?;
+    let player = suspended.player;
+
+    let mut replayed = 0;
+    for packet in suspended.held.flush_received() {
+        match packet {
+            // Chunks are sent again for the current view.
+            ServerPacket::LoadChunk(_) | ServerPacket::LoadCachedChunk(_) => continue,
+            // The new connection has its own keepalive.
+            ServerPacket::Shared(SharedPacket::Ping(_)) => continue,
+            _ => {}
+        }
+        bridge.send(packet);
+        replayed += 1;
+    }
+    if let Ok(mut mailbox) = game.ecs().get_mut::<Mailbox>(player) {
+        *mailbox = bridge.clone();
+    }
+    if let Ok(username) = game.ecs().get::<Username>(player) {
+        log::info!(
+            "{} resumed their session. Replayed {} packets.",
+            username.0,
+            replayed
+        );
+    }
+    game.events().push(PlayerResumed { player });
+    Some(player)
+}
+
+/// Despawns all suspended players, e.g. when the server stops.
+pub(crate) fn despawn_all(game: &mut Game) {
+    let players: Vec<_> = game
+        .suspended_mut()
+        .players
+        .drain()
+        .map(|(_, suspended)| suspended.player)
+        .collect();
+    for player in players {
+        conn::despawn_player(game, player);
+    }
+}
+
+/// System to despawn suspended players whose
+/// clients did not return within the grace period.
+struct ExpirySystem;
+
+impl System<Game> for ExpirySystem {
+    fn run(&mut self, game: &mut Game) {
+        let grace_ticks = GRACE_PERIOD.as_secs() * game.tps() as u64;
+        let tick = game.tick();
+        let mut expired = Vec::new();
+        game.suspended_mut().players.retain(|_, suspended| {
+            let keep = tick - suspended.since < grace_ticks;
+            if !keep {
+                expired.push(suspended.player);
+            }
+            keep
+        });
+
+        for player in expired {
+            if let Ok(username) = game.ecs().get::<Username>(player) {
+                log::info!("{} did not return in time.", username.0);
+            }
+            conn::despawn_player(game, player);
+        }
+    }
+}
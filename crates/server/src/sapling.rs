@@ -0,0 +1,141 @@
+//! Saplings growing into trees.
+//!
+//! When a [`Sapling`] is placed, a [block tick](crate::block_update)
+//! is scheduled for a random time within [`GROWTH_SECONDS`]. When it is
+//! due, the sapling is replaced with a [`Tree`] from worldgen's
+//! templates, provided the tree fits: every block of the tree must be
+//! loaded and free. A sapling that doesn't fit tries again later.
+//!
+//! Saplings loaded from a save have no scheduled tick, so a
+//! [random tick](crate::random_tick) on a sapling schedules
+//! one if it is missing.
+
+use std::ops::Range;
+
+use common::{
+    blocks::{Air, Dirt, Grass, Leaves, Sapling, Snow},
+    BlockId, BlockPos, System, SystemExecutor, Zone,
+};
+use rand::Rng;
+use worldgen::tree::Tree;
+
+use crate::{
+    event::{BlockChanged, BlockTickDue, RandomTick},
+    game::Game,
+};
+
+/// The range of seconds it takes a sapling to grow.
+const GROWTH_SECONDS: Range<u64> = 30..90;
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(SaplingSystem);
+}
+
+/// System to schedule and perform sapling growth.
+struct SaplingSystem;
+
+impl System<Game> for SaplingSystem {
+    fn run(&mut self, game: &mut Game) {
+        let mut to_schedule = Vec::new();
+        to_schedule.extend(
+            game.events()
+                .iter::<BlockChanged>()
+                .filter(|change| change.new.is::<Sapling>())
+                .map(|change| change.pos),
+        );
+        to_schedule.extend(
+            game.events()
+                .iter::<RandomTick>()
+                .filter(|tick| {
+                    tick.block.is::<Sapling>() && !game.is_block_tick_scheduled(tick.pos)
+                })
+                .map(|tick| tick.pos),
+        );
+
+        let mut due = Vec::new();
+        due.extend(game.events().iter::<BlockTickDue>().map(|tick| tick.pos));
+
+        for pos in due {
+            let is_sapling = game
+                .main_zone()
+                .block(pos)
+                .map_or(false, |block| block.is::<Sapling>());
+            if !is_sapling {
+                continue;
+            }
+            let tree = Tree::random(&mut *game.rng());
+            if can_grow(game.main_zone(), pos, tree) {
+                log::debug!("Sapling at {:?} grew into a tree", pos);
+                for (offset, block) in tree.blocks() {
+                    let block_pos = pos.offset(offset.x, offset.y, offset.z);
+                    game.set_block(block_pos, block).ok();
+                }
+            } else {
+                to_schedule.push(pos);
+            }
+        }
+
+        for pos in to_schedule {
+            let delay = game
+                .rng()
+                .gen_range(GROWTH_SECONDS.start, GROWTH_SECONDS.end)
+                * game.tps() as u64;
+            game.schedule_block_tick(pos, delay);
+        }
+    }
+}
+
+/// Returns whether `tree` can grow from the sapling at `pos`. The
+/// sapling must be planted in dirt or grass, and every other block
+/// of the tree must be loaded and free.
+fn can_grow(zone: &Zone, pos: BlockPos, tree: Tree) -> bool {
+    let planted = zone
+        .block(pos.offset(0, -1, 0))
+        .map_or(false, |soil| soil.is::<Dirt>() || soil.is::<Grass>());
+    planted
+        && tree.blocks().into_iter().all(|(offset, _)| {
+            let block_pos = pos.offset(offset.x, offset.y, offset.z);
+            block_pos == pos || zone.block(block_pos).map_or(false, is_free)
+        })
+}
+
+/// Returns whether a tree may grow into `block`.
+fn is_free(block: BlockId) -> bool {
+    block.is::<Air>() || block.is::<Snow>() || block.is::<Leaves>()
+}
+
+#[cfg(test)]
+mod tests {
+    use common::blocks::Stone;
+
+    use crate::game::test_zone;
+
+    use super::*;
+
+    #[test]
+    fn grows_only_when_planted_with_space() {
+        let mut zone = test_zone();
+        let pos = BlockPos { x: 8, y: 2, z: 8 };
+        let tree = Tree::new(4);
+        zone.set_block(pos, BlockId::new(Sapling)).unwrap();
+        assert!(!can_grow(&zone, pos, tree));
+
+        zone.set_block(pos.offset(0, -1, 0), BlockId::new(Grass))
+            .unwrap();
+        assert!(can_grow(&zone, pos, tree));
+
+        zone.set_block(pos.offset(1, 4, 0), BlockId::new(Stone))
+            .unwrap();
+        assert!(!can_grow(&zone, pos, tree));
+    }
+
+    #[test]
+    fn needs_loaded_space() {
+        let mut zone = test_zone();
+        // The leaves would extend past the top of the zone.
+        let pos = BlockPos { x: 8, y: 12, z: 8 };
+        zone.set_block(pos.offset(0, -1, 0), BlockId::new(Dirt))
+            .unwrap();
+        assert!(!can_grow(&zone, pos, Tree::new(4)));
+    }
+}
@@ -0,0 +1,825 @@
+//! World persistence.
+//!
+//! A save is a directory containing `level.bin`, which stores the world seed
+//! and the biome sequence it was generated with, `players.bin`, which stores
+//! the [`PlayerData`] of each player by username, `gamerules.bin`, which
+//! stores the [game rules](common::game_rules) that differ from their
+//! defaults by name, `protection.bin`, which stores the [protected
+//! regions](crate::protection), a `history` directory with the [world
+//! history](crate::history), and a `regions` directory of region files.
+//! Each region file holds the generated chunk columns in a 16x16 area of
+//! columns. Only generated columns are saved; the rest are generated as
+//! usual when the world is loaded.
+//!
+//! The autosave system periodically rewrites the regions that changed
+//! since the last save, along with the player data, game rules, and protected
+//! regions. A [`SaveRequested`]
+//! event, e.g. from the `save` [command](crate::command), saves them
+//! immediately. Writing happens on a separate thread, which also
+//! takes [backups](crate::backup) after saves.
+//!
+//! # Region file format
+//! All integers are little-endian.
+//! * The magic bytes `VZRG`, then the format version as a `u32`.
+//! * A table with one entry per column in the region, ordered by X then Z.
+//!   Each entry is the `u32` offset of the column's data from the start of
+//!   the file, followed by its `u32` length. Absent columns have length zero.
+//! * Column data: a zstd-compressed, bincode-encoded list of the column's
+//!   chunks from bottom to top, followed by the column's optional
+//!   [biomes](common::biome::ColumnBiomes). Each chunk is stored as its
+//!   palette followed by its packed palette indexes. Each column is
+//!   compressed as a separate zstd frame with a content checksum, which
+//!   is verified when decoding.
+//!
+//! Version 1 files lack the biomes. They are still loaded, and the
+//! server fills in the missing biomes from the world generator.
+//!
+//! The `voltz-world-tool` binary verifies, compacts,
+//! and prints statistics about saves.
+
+use std::{
+    convert::TryInto,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{anyhow, bail, ensure, Context};
+use common::{biome::ColumnBiomes, block, game_rules::GameRules, Chunk, System, SystemExecutor};
+use flume::Sender;
+use hashbrown::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use worldgen::{BiomeSequence, ColumnPos};
+
+use crate::{
+    backup::Backups,
+    event::{BackupRequested, BlockChanged, ColumnGenerated, SaveRequested},
+    game::Game,
+    generation::COLUMN_HEIGHT,
+    protection::ProtectedRegions,
+};
+
+const REGION_MAGIC: &[u8; 4] = b"VZRG";
+const FORMAT_VERSION: u32 = 2;
+/// The oldest format version that can still be loaded.
+const OLDEST_FORMAT_VERSION: u32 = 1;
+
+/// Width of a region in chunk columns.
+const REGION_WIDTH: i32 = 16;
+const REGION_COLUMNS: usize = (REGION_WIDTH * REGION_WIDTH) as usize;
+const HEADER_LEN: usize = 8 + REGION_COLUMNS * 8;
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// The number of seconds of game time between autosaves.
+const AUTOSAVE_INTERVAL: u64 = 5 * 60;
+
+/// The position of a region file, measured in regions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RegionPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl RegionPos {
+    /// Gets the region containing the given column.
+    pub fn of_column(pos: ColumnPos) -> Self {
+        Self {
+            x: pos.x.div_euclid(REGION_WIDTH),
+            z: pos.z.div_euclid(REGION_WIDTH),
+        }
+    }
+
+    /// Returns the columns in this region, ordered as in the region file.
+    pub fn columns(self) -> impl Iterator<Item = ColumnPos> {
+        (0..REGION_COLUMNS).map(move |index| self.column(index))
+    }
+
+    fn column(self, index: usize) -> ColumnPos {
+        ColumnPos {
+            x: self.x * REGION_WIDTH + index as i32 / REGION_WIDTH,
+            z: self.z * REGION_WIDTH + index as i32 % REGION_WIDTH,
+        }
+    }
+
+    pub fn file_name(self) -> String {
+        format!("r.{}.{}.vzr", self.x, self.z)
+    }
+}
+
+fn column_index(pos: ColumnPos) -> usize {
+    (pos.x.rem_euclid(REGION_WIDTH) * REGION_WIDTH + pos.z.rem_euclid(REGION_WIDTH)) as usize
+}
+
+/// A saved chunk column.
+pub struct SavedColumn {
+    pub pos: ColumnPos,
+    /// The column's chunks from bottom to top.
+    pub chunks: Vec<Chunk>,
+    /// The column's biomes, or `None` if the
+    /// column was saved before biomes were.
+    pub biomes: Option<ColumnBiomes>,
+}
+
+/// Encodes columns into a region file.
+pub fn encode_region(region: RegionPos, columns: &[SavedColumn]) -> anyhow::Result<Vec<u8>> {
+    let mut table = vec![(0u32, 0u32); REGION_COLUMNS];
+    let mut data = Vec::new();
+    for column in columns {
+        ensure!(
+            RegionPos::of_column(column.pos) == region,
+            "column {:?} is not in region {:?}",
+            column.pos,
+            region
+        );
+        let encoded = bincode::serialize(&(&column.chunks, column.biomes))?;
+        let compressed = compress(&encoded)?;
+        let offset = (HEADER_LEN + data.len()).try_into()?;
+        table[column_index(column.pos)] = (offset, compressed.len().try_into()?);
+        data.extend_from_slice(&compressed);
+    }
+
+    let mut file = Vec::with_capacity(HEADER_LEN + data.len());
+    file.extend_from_slice(REGION_MAGIC);
+    file.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    for (offset, len) in table {
+        file.extend_from_slice(&offset.to_le_bytes());
+        file.extend_from_slice(&len.to_le_bytes());
+    }
+    file.extend_from_slice(&data);
+    Ok(file)
+}
+
+/// Decodes the columns stored in a region file.
+///
+/// Fails if any column is corrupt. Use [`inspect_region`]
+/// to decode the intact columns of a damaged file.
+pub fn decode_region(region: RegionPos, file: &[u8]) -> anyhow::Result<Vec<SavedColumn>> {
+    let report = inspect_region(region, file)?;
+    if let Some(problem) = report.problems.into_iter().next() {
+        bail!(problem);
+    }
+    Ok(report.columns)
+}
+
+/// The contents of a region file, as returned by [`inspect_region`].
+pub struct RegionReport {
+    /// The columns that were decoded successfully.
+    pub columns: Vec<SavedColumn>,
+    /// Problems with the file. Columns with
+    /// problems are not included in `columns`.
+    pub problems: Vec<String>,
+    /// The number of bytes after the header
+    /// that do not belong to any column.
+    pub unused_bytes: usize,
+}
+
+/// Decodes a region file, collecting problems with its columns instead
+/// of failing on the first one. Verifies the checksum and chunks of each
+/// column. Fails only if the file is not a region file at all.
+pub fn inspect_region(region: RegionPos, file: &[u8]) -> anyhow::Result<RegionReport> {
+    ensure!(
+        file.len() >= HEADER_LEN && &file[..4] == REGION_MAGIC,
+        "not a region file"
+    );
+    let version = read_u32(file, 4);
+    ensure!(
+        (OLDEST_FORMAT_VERSION..=FORMAT_VERSION).contains(&version),
+        "unsupported region format version {}",
+        version
+    );
+
+    let mut report = RegionReport {
+        columns: Vec::new(),
+        problems: Vec::new(),
+        unused_bytes: file.len() - HEADER_LEN,
+    };
+    let mut ranges = Vec::new();
+    for index in 0..REGION_COLUMNS {
+        let offset = read_u32(file, 8 + index * 8) as usize;
+        let len = read_u32(file, 12 + index * 8) as usize;
+        if len == 0 {
+            continue;
+        }
+
+        let pos = region.column(index);
+        let data = match file.get(offset..offset + len) {
+            Some(data) if offset >= HEADER_LEN => data,
+            _ => {
+                report
+                    .problems
+                    .push(format!("data for column {:?} is out of bounds", pos));
+                continue;
+            }
+        };
+        ranges.push((offset, offset + len, pos));
+        report.unused_bytes = report.unused_bytes.saturating_sub(len);
+
+        match decode_column(data, version) {
+            Ok((chunks, biomes)) => report.columns.push(SavedColumn {
+                pos,
+                chunks,
+                biomes,
+            }),
+            Err(e) => report
+                .problems
+                .push(format!("column {:?} is corrupt: {:#}", pos, e)),
+        }
+    }
+
+    ranges.sort_unstable_by_key(|&(start, _, _)| start);
+    for pair in ranges.windows(2) {
+        let ((_, end, first), (start, _, second)) = (pair[0], pair[1]);
+        if start < end {
+            report.problems.push(format!(
+                "data for columns {:?} and {:?} overlaps",
+                first, second
+            ));
+        }
+    }
+
+    Ok(report)
+}
+
+fn decode_column(data: &[u8], version: u32) -> anyhow::Result<(Vec<Chunk>, Option<ColumnBiomes>)> {
+    let encoded = zstd::decode_all(data)?;
+    let (chunks, biomes): (Vec<Chunk>, Option<ColumnBiomes>) = if version == 1 {
+        (
+            bincode::deserialize(&encoded).context("malformed chunks")?,
+            None,
+        )
+    } else {
+        bincode::deserialize(&encoded).context("malformed column")?
+    };
+    ensure!(
+        chunks.len() == COLUMN_HEIGHT as usize,
+        "expected {} chunks, found {}",
+        COLUMN_HEIGHT,
+        chunks.len()
+    );
+    // Deserialized chunks are not validated, so check
+    // that their indexes and blocks are in bounds.
+    let chunks = chunks
+        .into_iter()
+        .enumerate()
+        .map(|(y, chunk)| {
+            let (indexes, palette) = chunk.into_parts();
+            if let Some(block) = palette.iter().find(|block| !block.is_valid()) {
+                bail!("chunk {} contains unknown block {:?}", y, block);
+            }
+            Chunk::from_parts(indexes, palette)
+                .ok_or_else(|| anyhow!("chunk {} has indexes out of bounds", y))
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok((chunks, biomes))
+}
+
+/// Compresses column data as a zstd frame with a checksum.
+fn compress(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut encoder = zstd::stream::Encoder::new(Vec::new(), COMPRESSION_LEVEL)?;
+    encoder.include_checksum(true)?;
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Data about the world as a whole.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LevelData {
+    pub format_version: u32,
+    /// The seed used to generate the world.
+    pub seed: u64,
+    /// The [`registry_digest`](block::registry_digest) of the server
+    /// that wrote the save. Block IDs are only meaningful
+    /// with the same registry.
+    pub registry_digest: u64,
+    /// The [`digest`](BiomeSequence::digest) of the biome sequence
+    /// the world was generated with. Columns generated with another
+    /// sequence don't line up with the saved ones. `None` for saves
+    /// written before the sequence was recorded.
+    pub biome_sequence: Option<u64>,
+}
+
+/// [`LevelData`] as written before it recorded the biome sequence.
+#[derive(Deserialize)]
+struct LevelDataV1 {
+    format_version: u32,
+    seed: u64,
+    registry_digest: u64,
+}
+
+impl LevelData {
+    pub fn new(seed: u64, biome_sequence: &BiomeSequence) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            seed,
+            registry_digest: block::registry_digest(),
+            biome_sequence: Some(biome_sequence.digest()),
+        }
+    }
+
+    /// Returns whether the world may have been generated
+    /// with a different biome sequence than `sequence`.
+    pub fn has_other_biome_sequence(&self, sequence: &BiomeSequence) -> bool {
+        self.biome_sequence
+            .map_or(false, |digest| digest != sequence.digest())
+    }
+}
+
+fn decode_level(bytes: &[u8]) -> anyhow::Result<LevelData> {
+    if let Ok(level) = bincode::deserialize(bytes) {
+        return Ok(level);
+    }
+    let level: LevelDataV1 = bincode::deserialize(bytes).context("malformed level data")?;
+    Ok(LevelData {
+        format_version: level.format_version,
+        seed: level.seed,
+        registry_digest: level.registry_digest,
+        biome_sequence: None,
+    })
+}
+
+/// Data about a player which persists while they are offline.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerData {
+    /// The player's total experience points.
+    pub xp: u32,
+}
+
+/// A save directory.
+#[derive(Clone)]
+pub struct WorldSave {
+    dir: PathBuf,
+}
+
+impl WorldSave {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns whether a world has been saved here.
+    pub fn exists(&self) -> bool {
+        self.level_path().exists()
+    }
+
+    /// Loads the level data and all saved columns.
+    pub fn load(&self) -> anyhow::Result<(LevelData, Vec<SavedColumn>)> {
+        let level = self.read_level()?;
+        if !(OLDEST_FORMAT_VERSION..=FORMAT_VERSION).contains(&level.format_version) {
+            bail!("unsupported save format version {}", level.format_version);
+        }
+        if level.registry_digest != block::registry_digest() {
+            bail!("the save was written with a different block registry");
+        }
+
+        let mut columns = Vec::new();
+        for (region, path) in self.region_files()? {
+            let file = fs::read(&path)?;
+            columns.extend(
+                decode_region(region, &file)
+                    .with_context(|| format!("failed to load {}", path.display()))?,
+            );
+        }
+        Ok((level, columns))
+    }
+
+    /// Reads the level data without checking that
+    /// this server can load the save.
+    pub fn read_level(&self) -> anyhow::Result<LevelData> {
+        decode_level(&fs::read(self.level_path())?)
+    }
+
+    /// Lists the region files in the save and their positions.
+    /// Other files in the regions directory are ignored.
+    pub fn region_files(&self) -> anyhow::Result<Vec<(RegionPos, PathBuf)>> {
+        let regions_dir = self.regions_dir();
+        if !regions_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&regions_dir)? {
+            let path = entry?.path();
+            if let Some(region) = parse_region_file_name(&path) {
+                files.push((region, path));
+            }
+        }
+        Ok(files)
+    }
+
+    /// Reads the data of all players who have joined the world.
+    pub fn read_players(&self) -> anyhow::Result<HashMap<String, PlayerData>> {
+        let path = self.players_path();
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        bincode::deserialize(&fs::read(path)?).context("malformed player data")
+    }
+
+    pub fn write_players(&self, players: &HashMap<String, PlayerData>) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        write_atomically(&self.players_path(), &bincode::serialize(players)?)
+    }
+
+    /// Reads the world's game rules. Unknown rules, e.g. ones
+    /// removed since the save was written, are ignored.
+    pub fn read_game_rules(&self) -> anyhow::Result<GameRules> {
+        let path = self.game_rules_path();
+        if !path.exists() {
+            return Ok(GameRules::new());
+        }
+        let named = bincode::deserialize(&fs::read(path)?).context("malformed game rules")?;
+        let (rules, invalid) = GameRules::from_named(named);
+        for name in invalid {
+            log::warn!("Ignoring unknown or invalid game rule '{}'", name);
+        }
+        Ok(rules)
+    }
+
+    pub fn write_game_rules(&self, rules: &GameRules) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        write_atomically(
+            &self.game_rules_path(),
+            &bincode::serialize(&rules.to_named())?,
+        )
+    }
+
+    /// Reads the world's protected regions, or `None`
+    /// if they were never saved, e.g. in a new world.
+    pub fn read_protected_regions(&self) -> anyhow::Result<Option<ProtectedRegions>> {
+        let path = self.protected_regions_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        bincode::deserialize(&fs::read(path)?)
+            .map(Some)
+            .context("malformed protected regions")
+    }
+
+    pub fn write_protected_regions(&self, regions: &ProtectedRegions) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        write_atomically(
+            &self.protected_regions_path(),
+            &bincode::serialize(regions)?,
+        )
+    }
+
+    pub fn write_level(&self, level: &LevelData) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        write_atomically(&self.level_path(), &bincode::serialize(level)?)
+    }
+
+    /// Writes a region file, replacing any existing one.
+    pub fn write_region(&self, region: RegionPos, columns: &[SavedColumn]) -> anyhow::Result<()> {
+        let file = encode_region(region, columns)?;
+        fs::create_dir_all(self.regions_dir())?;
+        write_atomically(&self.regions_dir().join(region.file_name()), &file)
+    }
+
+    /// Deletes a region file. Its columns are
+    /// generated again when the world is loaded.
+    pub fn remove_region(&self, region: RegionPos) -> anyhow::Result<()> {
+        fs::remove_file(self.regions_dir().join(region.file_name()))?;
+        Ok(())
+    }
+
+    fn level_path(&self) -> PathBuf {
+        self.dir.join("level.bin")
+    }
+
+    fn players_path(&self) -> PathBuf {
+        self.dir.join("players.bin")
+    }
+
+    fn game_rules_path(&self) -> PathBuf {
+        self.dir.join("gamerules.bin")
+    }
+
+    fn protected_regions_path(&self) -> PathBuf {
+        self.dir.join("protection.bin")
+    }
+
+    /// Returns the directory containing the [world history](crate::history).
+    pub fn history_dir(&self) -> PathBuf {
+        self.dir.join("history")
+    }
+
+    /// Returns the directory containing [backups](crate::backup) of the
+    /// save: `world-backups` next to a save in `world`.
+    pub fn backups_dir(&self) -> PathBuf {
+        match self.dir.file_name() {
+            Some(name) => self
+                .dir
+                .with_file_name(format!("{}-backups", name.to_string_lossy())),
+            None => self.dir.join("backups"),
+        }
+    }
+
+    fn regions_dir(&self) -> PathBuf {
+        self.dir.join("regions")
+    }
+}
+
+fn parse_region_file_name(path: &Path) -> Option<RegionPos> {
+    let name = path.file_name()?.to_str()?;
+    let mut parts = name.strip_prefix("r.")?.strip_suffix(".vzr")?.split('.');
+    let x = parts.next()?.parse().ok()?;
+    let z = parts.next()?.parse().ok()?;
+    Some(RegionPos { x, z })
+}
+
+/// Writes to a temporary file and renames it over `path`, so
+/// that a crash while saving does not corrupt the old file.
+fn write_atomically(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, data)?;
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Adds the autosave system. `dirty` contains regions
+/// with changes that have not been saved yet.
+pub fn setup(
+    systems: &mut SystemExecutor<Game>,
+    save: WorldSave,
+    dirty: HashSet<RegionPos>,
+    backups: Backups,
+) {
+    let (writer, jobs) = flume::unbounded::<SaveJob>();
+    let thread_backups = backups.clone();
+    let thread = thread::Builder::new()
+        .name("world-save".to_owned())
+        .spawn(move || {
+            for job in jobs {
+                match job {
+                    SaveJob::Region(region, columns) => {
+                        if let Err(e) = save.write_region(region, &columns) {
+                            log::error!("Failed to save region {:?}: {:#}", region, e);
+                        }
+                    }
+                    SaveJob::Players(players) => {
+                        if let Err(e) = save.write_players(&players) {
+                            log::error!("Failed to save player data: {:#}", e);
+                        }
+                    }
+                    SaveJob::GameRules(rules) => {
+                        if let Err(e) = save.write_game_rules(&rules) {
+                            log::error!("Failed to save game rules: {:#}", e);
+                        }
+                    }
+                    SaveJob::ProtectedRegions(regions) => {
+                        if let Err(e) = save.write_protected_regions(&regions) {
+                            log::error!("Failed to save protected regions: {:#}", e);
+                        }
+                    }
+                    SaveJob::Backup => thread_backups.create_and_prune(),
+                }
+            }
+        })
+        .expect("failed to spawn world save thread");
+
+    systems.add(AutosaveSystem {
+        writer: Some(writer),
+        thread: Some(thread),
+        dirty,
+        last_save: 0,
+        backups,
+        last_backup: 0,
+    });
+}
+
+/// Data to write on the world save thread.
+enum SaveJob {
+    Region(RegionPos, Vec<SavedColumn>),
+    Players(HashMap<String, PlayerData>),
+    GameRules(GameRules),
+    ProtectedRegions(ProtectedRegions),
+    /// Sent after the jobs of a save.
+    Backup,
+}
+
+/// System to periodically save regions that changed,
+/// the data of all players, the game rules, and the
+/// protected regions, and to back up the world.
+///
+/// Dropping the system waits for the world save thread to write
+/// everything sent to it, so that the save is complete once the
+/// server is gone, e.g. before an integrated server is restarted.
+struct AutosaveSystem {
+    /// Always present until the system is dropped.
+    writer: Option<Sender<SaveJob>>,
+    thread: Option<JoinHandle<()>>,
+    dirty: HashSet<RegionPos>,
+    /// The tick of the last autosave.
+    last_save: u64,
+    backups: Backups,
+    /// The tick of the last backup.
+    last_backup: u64,
+}
+
+impl System<Game> for AutosaveSystem {
+    fn run(&mut self, game: &mut Game) {
+        for event in game.events().iter::<ColumnGenerated>() {
+            self.dirty.insert(RegionPos::of_column(event.pos));
+        }
+        for event in game.events().iter::<BlockChanged>() {
+            let column = ColumnPos::from_chunk(event.pos.chunk());
+            self.dirty.insert(RegionPos::of_column(column));
+        }
+
+        let backup_due = match self.backups.settings().interval {
+            Some(interval) => game.tick() - self.last_backup >= interval * game.tps() as u64,
+            None => false,
+        };
+        let backup = backup_due || game.events().iter::<BackupRequested>().next().is_some();
+        let requested = backup || game.events().iter::<SaveRequested>().next().is_some();
+        let interval = AUTOSAVE_INTERVAL * game.tps() as u64;
+        if !requested && game.tick() - self.last_save < interval {
+            return;
+        }
+        self.last_save = game.tick();
+
+        if requested {
+            log::info!("Saving {} regions", self.dirty.len());
+        } else {
+            log::info!("Autosaving {} regions", self.dirty.len());
+        }
+        let mut jobs = vec![
+            SaveJob::Players(game.all_player_data()),
+            SaveJob::GameRules(game.rules().clone()),
+            SaveJob::ProtectedRegions(game.protected_regions().clone()),
+        ];
+        jobs.extend(
+            self.dirty
+                .drain()
+                .map(|region| SaveJob::Region(region, collect_region(game, region))),
+        );
+        if backup {
+            self.last_backup = game.tick();
+            jobs.push(SaveJob::Backup);
+        }
+        let writer = self.writer.as_ref().unwrap();
+        for job in jobs {
+            if writer.send(job).is_err() {
+                log::error!("The world save thread exited. Changes will not be saved.");
+                return;
+            }
+        }
+    }
+}
+
+impl Drop for AutosaveSystem {
+    fn drop(&mut self) {
+        // Closing the channel ends the thread once it has written all jobs.
+        self.writer = None;
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("The world save thread panicked");
+            }
+        }
+    }
+}
+
+/// Copies the generated columns in a region out of the main zone.
+fn collect_region(game: &Game, region: RegionPos) -> Vec<SavedColumn> {
+    let zone = game.main_zone();
+    region
+        .columns()
+        .filter(|&pos| game.is_column_generated(pos))
+        .map(|pos| SavedColumn {
+            pos,
+            chunks: (zone.min().y..=zone.max().y)
+                .filter_map(|y| zone.chunk(pos.chunk(y)).cloned())
+                .collect(),
+            biomes: zone.column_biomes(pos.x, pos.z),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{biome::Biome, blocks, BlockId};
+
+    /// Returns a column of tundra with a block of dirt in its top chunk.
+    fn column(pos: ColumnPos) -> SavedColumn {
+        let mut chunks = vec![Chunk::new(); COLUMN_HEIGHT as usize];
+        chunks
+            .last_mut()
+            .unwrap()
+            .set(1, 2, 3, BlockId::new(blocks::Dirt));
+        SavedColumn {
+            pos,
+            chunks,
+            biomes: Some(ColumnBiomes::from_fn(|_, _| Biome::Tundra)),
+        }
+    }
+
+    #[test]
+    fn region_round_trip() {
+        let columns = vec![
+            column(ColumnPos { x: -1, z: 17 }),
+            column(ColumnPos { x: -16, z: 31 }),
+        ];
+        let region = RegionPos { x: -1, z: 1 };
+
+        let file = encode_region(region, &columns).unwrap();
+        let decoded = decode_region(region, &file).unwrap();
+
+        assert_eq!(decoded.len(), 2);
+        let mut positions: Vec<_> = decoded.iter().map(|column| column.pos).collect();
+        positions.sort_by_key(|pos| (pos.x, pos.z));
+        assert_eq!(
+            positions,
+            vec![ColumnPos { x: -16, z: 31 }, ColumnPos { x: -1, z: 17 }]
+        );
+        for column in &decoded {
+            let chunk = column.chunks.last().unwrap();
+            assert_eq!(chunk.get(1, 2, 3), BlockId::new(blocks::Dirt));
+            assert_eq!(chunk.get(0, 0, 0), BlockId::new(blocks::Air));
+            assert_eq!(column.biomes.unwrap().get(4, 9), Biome::Tundra);
+        }
+    }
+
+    #[test]
+    fn loads_version_1_regions() {
+        let region = RegionPos { x: 0, z: 0 };
+        let pos = ColumnPos { x: 3, z: 5 };
+        let mut file = encode_region(region, &[column(pos)]).unwrap();
+
+        // Replace the column's data with a version 1 encoding.
+        let chunks = column(pos).chunks;
+        let data = compress(&bincode::serialize(&chunks).unwrap()).unwrap();
+        file.truncate(HEADER_LEN);
+        file[4..8].copy_from_slice(&1u32.to_le_bytes());
+        let len_offset = 12 + column_index(pos) * 8;
+        file[len_offset..len_offset + 4].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        file.extend_from_slice(&data);
+
+        let decoded = decode_region(region, &file).unwrap();
+        assert_eq!(decoded.len(), 1);
+        assert_eq!(decoded[0].chunks.len(), COLUMN_HEIGHT as usize);
+        assert_eq!(decoded[0].biomes, None);
+    }
+
+    #[test]
+    fn reads_levels_without_biome_sequence() {
+        let mut level = LevelData::new(7, &BiomeSequence::default());
+        let decoded = decode_level(&bincode::serialize(&level).unwrap()).unwrap();
+        assert_eq!(decoded.seed, 7);
+        assert!(!decoded.has_other_biome_sequence(&BiomeSequence::default()));
+
+        level.biome_sequence = None;
+        let mut bytes = bincode::serialize(&level).unwrap();
+        // Drop the `None` tag, as written before the field existed.
+        bytes.pop();
+        let decoded = decode_level(&bytes).unwrap();
+        assert_eq!(decoded.seed, 7);
+        assert_eq!(decoded.biome_sequence, None);
+        assert!(!decoded.has_other_biome_sequence(&BiomeSequence::default()));
+    }
+
+    #[test]
+    fn reports_corrupt_columns() {
+        let region = RegionPos { x: 0, z: 0 };
+        let columns = vec![
+            column(ColumnPos { x: 0, z: 0 }),
+            column(ColumnPos { x: 0, z: 1 }),
+        ];
+        let mut file = encode_region(region, &columns).unwrap();
+        // Flip a bit in the last byte of the second column's data.
+        *file.last_mut().unwrap() ^= 1;
+
+        let report = inspect_region(region, &file).unwrap();
+        assert_eq!(report.columns.len(), 1);
+        assert_eq!(report.columns[0].pos, ColumnPos { x: 0, z: 0 });
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.unused_bytes, 0);
+        assert!(decode_region(region, &file).is_err());
+    }
+
+    #[test]
+    fn rejects_columns_outside_region() {
+        let columns = vec![SavedColumn {
+            pos: ColumnPos { x: 16, z: 0 },
+            chunks: vec![Chunk::new()],
+            biomes: None,
+        }];
+        assert!(encode_region(RegionPos { x: 0, z: 0 }, &columns).is_err());
+    }
+
+    #[test]
+    fn parses_region_file_names() {
+        let region = RegionPos { x: -3, z: 12 };
+        let path = PathBuf::from("world/regions").join(region.file_name());
+        assert_eq!(parse_region_file_name(&path), Some(region));
+        assert_eq!(parse_region_file_name(Path::new("r.1.vzr")), None);
+    }
+}
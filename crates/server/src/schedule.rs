@@ -0,0 +1,285 @@
+//! Scheduled world events.
+//!
+//! A schedule lists events that recur at fixed tick intervals, like
+//! cron jobs. It is read from the YAML file named by
+//! `VOLTZ_EVENT_SCHEDULE`:
+//!
+//! ```yaml
+//! # A meteor shower lasting five minutes each hour,
+//! # starting half an hour after the server starts.
+//! - every: 72000
+//!   offset: 36000
+//!   duration: 6000
+//!   event:
+//!     type: meteor_shower
+//! - every: 36000
+//!   event:
+//!     type: broadcast
+//!     message: Remember to take breaks!
+//! ```
+//!
+//! `offset` and `duration` default to zero. Ticks are counted
+//! from when the server started.
+//!
+//! [`ScheduleSystem`] pushes a [`ScheduledEventStarted`] event when an
+//! occurrence starts and a [`ScheduledEventEnded`] event when its duration
+//! has passed. Other systems hook into scheduled events by handling these.
+//! [`BuiltinEventSystem`] implements the built-in events:
+//! * `meteor_shower`: players see meteors streak across the sky.
+//! * `double_resources`: [`Game::resource_multiplier`] is 2.
+//! * `broadcast`: sends `message` to every player.
+
+use std::{env, fs};
+
+use anyhow::{bail, Context};
+use common::{System, SystemExecutor};
+use protocol::packets::{
+    server::{MeteorShower, SystemMessage},
+    ServerPacket,
+};
+use serde::Deserialize;
+
+use crate::{
+    event::{PlayerJoined, ScheduledEventEnded, ScheduledEventStarted},
+    game::Game,
+    Mailbox,
+};
+
+/// An event that can be scheduled.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScheduledEvent {
+    MeteorShower,
+    DoubleResources,
+    Broadcast { message: String },
+}
+
+/// An entry in a [`Schedule`].
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleEntry {
+    /// The number of ticks between occurrences.
+    pub every: u64,
+    /// The tick of the first occurrence.
+    #[serde(default)]
+    pub offset: u64,
+    /// The number of ticks each occurrence lasts.
+    #[serde(default)]
+    pub duration: u64,
+    pub event: ScheduledEvent,
+}
+
+impl ScheduleEntry {
+    /// Returns whether an occurrence of this entry starts at `tick`.
+    fn starts_at(&self, tick: u64) -> bool {
+        tick >= self.offset && (tick - self.offset) % self.every == 0
+    }
+
+    /// Returns whether an occurrence of this entry ends at `tick`.
+    fn ends_at(&self, tick: u64) -> bool {
+        self.duration > 0 && tick >= self.offset + self.duration && {
+            (tick - self.offset - self.duration) % self.every == 0
+        }
+    }
+}
+
+/// A list of recurring events.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    entries: Vec<ScheduleEntry>,
+}
+
+impl Schedule {
+    /// Parses a schedule from YAML.
+    pub fn parse(yaml: &str) -> anyhow::Result<Self> {
+        let entries: Vec<ScheduleEntry> = serde_yaml::from_str(yaml)?;
+        for (i, entry) in entries.iter().enumerate() {
+            if entry.every == 0 {
+                bail!("entry {}: 'every' must be positive", i);
+            }
+            if entry.duration > entry.every {
+                bail!(
+                    "entry {}: 'duration' ({}) is longer than 'every' ({}), so occurrences would overlap",
+                    i,
+                    entry.duration,
+                    entry.every
+                );
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    /// Reads the schedule from the file named by `VOLTZ_EVENT_SCHEDULE`.
+    /// Returns `None` if the variable is not set or the schedule is invalid.
+    pub fn from_env() -> Option<Self> {
+        let path = env::var("VOLTZ_EVENT_SCHEDULE").ok()?;
+        let schedule = fs::read_to_string(&path)
+            .context("failed to read file")
+            .and_then(|yaml| Self::parse(&yaml));
+        match schedule {
+            Ok(schedule) => Some(schedule),
+            Err(e) => {
+                log::error!("Ignoring invalid event schedule '{}': {:#}", path, e);
+                None
+            }
+        }
+    }
+
+    pub fn entries(&self) -> &[ScheduleEntry] {
+        &self.entries
+    }
+}
+
+pub fn setup(systems: &mut SystemExecutor<Game>, schedule: Option<Schedule>) {
+    if let Some(schedule) = schedule {
+        log::info!(
+            "Using an event schedule with {} entries",
+            schedule.entries().len()
+        );
+        systems.add(ScheduleSystem { schedule });
+    }
+    systems.add(BuiltinEventSystem::default());
+}
+
+/// System to start and end scheduled events.
+struct ScheduleSystem {
+    schedule: Schedule,
+}
+
+impl System<Game> for ScheduleSystem {
+    fn run(&mut self, game: &mut Game) {
+        let tick = game.tick();
+        let mut events = game.events();
+        // Ends are pushed first so an occurrence can end
+        // on the same tick that the next one starts.
+        for entry in &self.schedule.entries {
+            if entry.ends_at(tick) {
+                events.push(ScheduledEventEnded {
+                    event: entry.event.clone(),
+                });
+            }
+        }
+        for entry in &self.schedule.entries {
+            if entry.starts_at(tick) {
+                log::info!("Starting scheduled event {:?}", entry.event);
+                events.push(ScheduledEventStarted {
+                    event: entry.event.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// System to handle the built-in scheduled events.
+#[derive(Default)]
+struct BuiltinEventSystem {
+    /// The number of meteor showers in progress. Showers from
+    /// several schedule entries may overlap.
+    meteor_showers: u32,
+    /// The number of double-resource periods in progress.
+    double_resources: u32,
+}
+
+impl System<Game> for BuiltinEventSystem {
+    fn run(&mut self, game: &mut Game) {
+        let (was_showering, was_doubled) = (self.meteor_showers > 0, self.double_resources > 0);
+
+        for event in game.events().iter::<ScheduledEventEnded>() {
+            match event.event {
+                ScheduledEvent::MeteorShower => {
+                    self.meteor_showers = self.meteor_showers.saturating_sub(1)
+                }
+                ScheduledEvent::DoubleResources => {
+                    self.double_resources = self.double_resources.saturating_sub(1)
+                }
+                ScheduledEvent::Broadcast { .. } => {}
+            }
+        }
+        for event in game.events().iter::<ScheduledEventStarted>() {
+            match &event.event {
+                ScheduledEvent::MeteorShower => self.meteor_showers += 1,
+                ScheduledEvent::DoubleResources => self.double_resources += 1,
+                ScheduledEvent::Broadcast { message } => {
+                    broadcast(game, || {
+                        ServerPacket::SystemMessage(SystemMessage {
+                            message: message.clone(),
+                        })
+                    });
+                }
+            }
+        }
+
+        let showering = self.meteor_showers > 0;
+        if showering != was_showering {
+            broadcast(game, || {
+                ServerPacket::MeteorShower(MeteorShower { active: showering })
+            });
+        }
+        for event in game.events().iter::<PlayerJoined>() {
+            if let Ok(mailbox) = game.ecs().get::<Mailbox>(event.player) {
+                mailbox.send(ServerPacket::MeteorShower(MeteorShower {
+                    active: showering,
+                }));
+            }
+        }
+
+        let doubled = self.double_resources > 0;
+        if doubled != was_doubled {
+            game.set_resource_multiplier(if doubled { 2 } else { 1 });
+        }
+    }
+}
+
+fn broadcast(game: &Game, packet: impl Fn() -> ServerPacket) {
+    for (_, mailbox) in game.ecs().query::<&Mailbox>().iter() {
+        mailbox.send(packet());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_recur() {
+        let schedule = Schedule::parse(
+            "
+- every: 100
+  offset: 50
+  duration: 10
+  event:
+    type: meteor_shower
+- every: 20
+  event:
+    type: broadcast
+    message: hello
+",
+        )
+        .unwrap();
+        let entries = schedule.entries();
+        assert_eq!(entries.len(), 2);
+        let (shower, message) = (&entries[0], &entries[1]);
+        assert_eq!(
+            message.event,
+            ScheduledEvent::Broadcast {
+                message: "hello".to_owned()
+            }
+        );
+
+        let starts: Vec<u64> = (0..300).filter(|&tick| shower.starts_at(tick)).collect();
+        assert_eq!(starts, vec![50, 150, 250]);
+        let ends: Vec<u64> = (0..300).filter(|&tick| shower.ends_at(tick)).collect();
+        assert_eq!(ends, vec![60, 160, 260]);
+        assert!(!(0..300).any(|tick| message.ends_at(tick)));
+    }
+
+    #[test]
+    fn rejects_invalid_entries() {
+        assert!(Schedule::parse("- every: 0\n  event:\n    type: meteor_shower").is_err());
+        assert!(Schedule::parse(
+            "- every: 10\n  duration: 20\n  event:\n    type: double_resources"
+        )
+        .is_err());
+        assert!(Schedule::parse("- every: 10\n  event:\n    type: earthquake").is_err());
+    }
+}
@@ -0,0 +1,281 @@
+//! Selectors name the entities a command acts on.
+//!
+//! * A username selects that player.
+//! * `@a` selects all players and `@e` all entities.
+//! * `@p` selects the player nearest to a position,
+//!   the world spawn unless one is given.
+//!
+//! Filters in brackets narrow the selection, e.g.
+//! `@e[tag=boss]`, `@a[tag=!afk]`, `@p[x=10,y=64,z=-3]`, or
+//! `@e[tag=boss,limit=2]`. Tags are looked up through the
+//! [`TagIndex`](crate::tag::TagIndex), so tagged entities are found
+//! without scanning the rest. When a position is given, the closest
+//! entities come first; otherwise the order is arbitrary but stable.
+
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use common::{entity::player::Username, Pos};
+use glam::{vec3a, Vec3A};
+use hecs::Entity;
+
+use crate::{game::Game, generation, tag};
+
+/// A parsed selector.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Selector {
+    target: Target,
+    tags: Vec<TagFilter>,
+    near: Option<Vec3A>,
+    limit: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Target {
+    Player(String),
+    AllPlayers,
+    AllEntities,
+    NearestPlayer,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct TagFilter {
+    name: String,
+    /// Selects entities without the tag instead.
+    negated: bool,
+}
+
+impl FromStr for Selector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (target, filters) = match s.strip_prefix('@') {
+            None => {
+                if s.contains(|c| c == '[' || c == ']') {
+                    bail!("'{}' is not a username or selector", s);
+                }
+                (Target::Player(s.to_owned()), None)
+            }
+            Some(rest) => {
+                let (kind, filters) = match rest.find('[') {
+                    Some(start) => {
+                        let filters = rest[start + 1..]
+                            .strip_suffix(']')
+                            .with_context(|| format!("'{}' is missing a closing ']'", s))?;
+                        (&rest[..start], Some(filters))
+                    }
+                    None => (rest, None),
+                };
+                let target = match kind {
+                    "a" => Target::AllPlayers,
+                    "e" => Target::AllEntities,
+                    "p" => Target::NearestPlayer,
+                    _ => bail!("unknown selector '@{}'; expected @a, @e, or @p", kind),
+                };
+                (target, filters)
+            }
+        };
+
+        let mut selector = Selector {
+            target,
+            tags: Vec::new(),
+            near: None,
+            limit: None,
+        };
+        let mut coords = [None; 3];
+        for filter in filters.into_iter().flat_map(|filters| filters.split(',')) {
+            let equals = filter
+                .find('=')
+                .with_context(|| format!("filter '{}' should look like 'key=value'", filter))?;
+            let (key, value) = (filter[..equals].trim(), filter[equals + 1..].trim());
+            match key {
+                "tag" => {
+                    let (name, negated) = match value.strip_prefix('!') {
+                        Some(name) => (name, true),
+                        None => (value, false),
+                    };
+                    selector.tags.push(TagFilter {
+                        name: name.to_owned(),
+                        negated,
+                    });
+                }
+                "x" | "y" | "z" => {
+                    let axis = (key.as_bytes()[0] - b'x') as usize;
+                    let coord = value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|coord| coord.is_finite())
+                        .with_context(|| format!("'{}' is not a coordinate", value))?;
+                    coords[axis] = Some(coord);
+                }
+                "limit" => {
+                    let limit = value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&limit| limit > 0)
+                        .with_context(|| format!("'{}' is not a positive limit", value))?;
+                    selector.limit = Some(limit);
+                }
+                _ => bail!("unknown filter '{}'; expected tag, x, y, z, or limit", key),
+            }
+        }
+        selector.near = match coords {
+            [Some(x), Some(y), Some(z)] => Some(vec3a(x, y, z)),
+            [None, None, None] => None,
+            _ => bail!("a position needs all of x, y, and z"),
+        };
+        Ok(selector)
+    }
+}
+
+impl Selector {
+    /// Returns whether the selector names a single player by username.
+    pub fn is_username(&self) -> bool {
+        matches!(self.target, Target::Player(_))
+    }
+
+    /// Returns the entities selected in `game`.
+    pub fn select(&self, game: &Game) -> Vec<Entity> {
+        let players_only = self.target != Target::AllEntities;
+        let candidates: Vec<Entity> = match (&self.target, self.required_tag()) {
+            (Target::Player(username), _) => game
+                .ecs()
+                .query::<&Username>()
+                .iter()
+                .filter(|(_, name)| &name.0 == username)
+                .map(|(entity, _)| entity)
+                .collect(),
+            (_, Some(tag)) => game.tags().tagged(tag).collect(),
+            (Target::AllEntities, None) => game
+                .ecs()
+                .query::<&Pos>()
+                .iter()
+                .map(|(entity, _)| entity)
+                .collect(),
+            (_, None) => game
+                .ecs()
+                .query::<&Username>()
+                .iter()
+                .map(|(entity, _)| entity)
+                .collect(),
+        };
+
+        let origin = match self.target {
+            Target::NearestPlayer => Some(
+                self.near
+                    .unwrap_or_else(|| game.world().to_main(generation::spawn_pos())),
+            ),
+            _ => self.near,
+        };
+        let mut selected: Vec<(Entity, Option<f32>)> = candidates
+            .into_iter()
+            .filter(|&entity| game.ecs().entity(entity).is_ok())
+            .filter(|&entity| !players_only || game.ecs().get::<Username>(entity).is_ok())
+            .filter(|&entity| {
+                self.tags
+                    .iter()
+                    .all(|filter| tag::has(game, entity, &filter.name) != filter.negated)
+            })
+            .map(|entity| {
+                let distance = origin.and_then(|origin| {
+                    let pos = game.ecs().get::<Pos>(entity).ok()?;
+                    Some(game.world().to_main(pos.0).distance_squared(origin))
+                });
+                (entity, distance)
+            })
+            .collect();
+        // Entities without a position come last.
+        selected.sort_by(|(a, a_distance), (b, b_distance)| {
+            let a_distance = a_distance.unwrap_or(f32::INFINITY);
+            let b_distance = b_distance.unwrap_or(f32::INFINITY);
+            a_distance
+                .partial_cmp(&b_distance)
+                .unwrap()
+                .then(a.id().cmp(&b.id()))
+        });
+
+        let limit = match self.target {
+            Target::NearestPlayer => self.limit.unwrap_or(1),
+            _ => self.limit.unwrap_or(usize::MAX),
+        };
+        selected
+            .into_iter()
+            .take(limit)
+            .map(|(entity, _)| entity)
+            .collect()
+    }
+
+    /// Returns a tag every selected entity must have, if any.
+    fn required_tag(&self) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|filter| !filter.negated)
+            .map(|filter| filter.name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::WorldPos;
+
+    use crate::game::test_game;
+
+    use super::*;
+
+    fn select(game: &Game, selector: &str) -> Vec<Entity> {
+        selector.parse::<Selector>().unwrap().select(game)
+    }
+
+    #[test]
+    fn parses_selectors() {
+        let selector: Selector = "@e[tag=boss, tag=!asleep,limit=2]".parse().unwrap();
+        assert_eq!(selector.target, Target::AllEntities);
+        assert_eq!(selector.tags.len(), 2);
+        assert!(selector.tags[1].negated);
+        assert_eq!(selector.limit, Some(2));
+
+        let selector: Selector = "@p[x=1,y=2,z=-3.5]".parse().unwrap();
+        assert_eq!(selector.near, Some(vec3a(1., 2., -3.5)));
+        assert!("alice".parse::<Selector>().unwrap().is_username());
+
+        for invalid in &[
+            "@q",
+            "@e[tag=boss",
+            "@e[colour=red]",
+            "@p[x=1]",
+            "@a[limit=0]",
+        ] {
+            assert!(invalid.parse::<Selector>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn selects_by_tag_and_distance() {
+        let mut game = test_game();
+        let player = |game: &mut Game, name: &str, x: f32| {
+            game.ecs_mut().spawn((
+                Pos(WorldPos::main(vec3a(x, 0., 0.))),
+                Username(name.to_owned()),
+            ))
+        };
+        let alice = player(&mut game, "alice", 10.);
+        let bob = player(&mut game, "bob", 100.);
+        let boss = game
+            .ecs_mut()
+            .spawn((Pos(WorldPos::main(vec3a(50., 0., 0.))),));
+        tag::add(&mut game, boss, "boss").unwrap();
+        tag::add(&mut game, bob, "boss").unwrap();
+
+        assert_eq!(select(&game, "bob"), vec![bob]);
+        assert_eq!(select(&game, "carol"), vec![]);
+        assert_eq!(select(&game, "@a").len(), 2);
+        assert_eq!(select(&game, "@e").len(), 3);
+        assert_eq!(select(&game, "@a[tag=boss]"), vec![bob]);
+        assert_eq!(select(&game, "@e[tag=!boss]"), vec![alice]);
+        assert_eq!(select(&game, "@e[tag=boss,x=60,y=0,z=0]"), vec![boss, bob]);
+        assert_eq!(select(&game, "@p[x=90,y=0,z=0]"), vec![bob]);
+
+        game.ecs_mut().despawn(boss).unwrap();
+        assert_eq!(select(&game, "@e[tag=boss]"), vec![bob]);
+    }
+}
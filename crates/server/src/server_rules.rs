@@ -0,0 +1,127 @@
+//! Rules players accept before playing.
+//!
+//! If the server has rules, read from the text file named by
+//! `VOLTZ_SERVER_RULES`, they are shown to players in a [dialog](crate::dialog)
+//! when they join. Players who decline or dismiss the rules, or don't
+//! answer within [`ANSWER_TIMEOUT`], are kicked.
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    time::{Duration, Instant},
+};
+
+use common::{System, SystemExecutor};
+use hecs::Entity;
+
+use crate::{
+    conn::Kicked,
+    dialog::{self, Dialog},
+    event::{DialogResponded, PlayerJoined},
+    game::Game,
+};
+
+/// How long players have to accept the rules.
+pub const ANSWER_TIMEOUT: Duration = Duration::from_secs(300);
+
+const ACCEPT: u32 = 0;
+const DECLINE: u32 = 1;
+
+/// Reads the server rules from the environment.
+/// Returns `None` if there are none or they can't be read.
+pub fn from_env() -> Option<String> {
+    let path = env::var_os("VOLTZ_SERVER_RULES")?;
+    match fs::read_to_string(&path) {
+        Ok(rules) => Some(rules),
+        Err(e) => {
+            log::error!("Failed to read the server rules: {}", e);
+            None
+        }
+    }
+}
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(RulesSystem::default());
+}
+
+/// A rules dialog a player hasn't answered yet.
+struct Pending {
+    dialog: u32,
+    opened: Instant,
+}
+
+/// System to show the rules to joining players
+/// and kick those who don't accept them.
+#[derive(Default)]
+struct RulesSystem {
+    pending: HashMap<Entity, Pending>,
+}
+
+impl System<Game> for RulesSystem {
+    fn run(&mut self, game: &mut Game) {
+        if let Some(rules) = game.server_rules() {
+            let joined: Vec<Entity> = game
+                .events()
+                .iter::<PlayerJoined>()
+                .map(|event| event.player)
+                .collect();
+            for player in joined {
+                let dialog = Dialog::new("Server rules", rules)
+                    .button(ACCEPT, "Accept")
+                    .button(DECLINE, "Decline");
+                if let Some(dialog) = dialog::open_dialog(game, player, dialog) {
+                    self.pending.insert(
+                        player,
+                        Pending {
+                            dialog,
+                            opened: Instant::now(),
+                        },
+                    );
+                }
+            }
+        }
+
+        let mut declined = Vec::new();
+        for event in game.events().iter::<DialogResponded>() {
+            match self.pending.get(&event.player) {
+                Some(pending) if pending.dialog == event.dialog => {}
+                _ => continue,
+            }
+            self.pending.remove(&event.player);
+            if event.button != Some(ACCEPT) {
+                declined.push(event.player);
+            }
+        }
+        for player in declined {
+            kick(game, player, "You must accept the server rules to play");
+        }
+
+        let timed_out: Vec<(Entity, u32)> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.opened.elapsed() >= ANSWER_TIMEOUT)
+            .map(|(&player, pending)| (player, pending.dialog))
+            .collect();
+        for (player, dialog) in timed_out {
+            self.pending.remove(&player);
+            dialog::close_dialog(game, player, dialog);
+            kick(game, player, "You did not accept the server rules in time");
+        }
+
+        // Players who left can't answer.
+        self.pending
+            .retain(|&player, _| game.ecs().contains(player));
+    }
+}
+
+fn kick(game: &mut Game, player: Entity, reason: &str) {
+    // The player may have left in the meantime.
+    game.ecs_mut()
+        .insert_one(
+            player,
+            Kicked {
+                reason: reason.to_owned(),
+            },
+        )
+        .ok();
+}
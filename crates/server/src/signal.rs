@@ -0,0 +1,268 @@
+//! Signal blocks.
+//!
+//! A [`SignalSource`] powers the blocks next to it. [`Wire`] conducts
+//! power, losing one level per block, and actuators react to being
+//! powered: [`Lamp`]s light up, and [`Door`]s and [`Trapdoor`]s open
+//! when they become powered and close when they lose power.
+//!
+//! Signals propagate through [block updates](crate::block_update).
+//! When a signal block's state changes, its neighbors are updated in
+//! turn, so a signal travels one block per update and is bounded by the
+//! per-tick update budget.
+
+use common::{
+    blocks::{Door, Lamp, SignalSource, Trapdoor, Wire},
+    BlockId, BlockPos, System, SystemExecutor, Zone,
+};
+
+use crate::{event::BlockUpdateDue, game::Game};
+
+/// The power level of a signal source.
+pub const MAX_POWER: u32 = 15;
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(SignalSystem);
+}
+
+/// System to update signal blocks when a block update is due.
+struct SignalSystem;
+
+impl System<Game> for SignalSystem {
+    fn run(&mut self, game: &mut Game) {
+        let due: Vec<BlockPos> = game
+            .events()
+            .iter::<BlockUpdateDue>()
+            .map(|event| event.pos)
+            .collect();
+
+        for pos in due {
+            if let Some(new) = updated_state(game.main_zone(), pos) {
+                game.set_block(pos, new).ok();
+            }
+        }
+    }
+}
+
+/// Returns the new state of the block at `pos` given the power
+/// it receives, or `None` if the block should not change.
+fn updated_state(zone: &Zone, pos: BlockPos) -> Option<BlockId> {
+    let block = zone.block(pos)?;
+    let new = if block.is::<Wire>() {
+        BlockId::new(Wire {
+            power: wire_power(zone, pos),
+        })
+    } else if block.is::<Lamp>() {
+        BlockId::new(Lamp {
+            lit: is_powered(zone, pos),
+        })
+    } else if let Some(door) = block.cast::<Door>() {
+        // Both halves of a door share its power.
+        let powered = is_powered(zone, pos) || is_powered(zone, door.other_half(pos));
+        if powered == door.powered {
+            return None;
+        }
+        BlockId::new(Door {
+            open: powered,
+            powered,
+            ..door
+        })
+    } else if let Some(trapdoor) = block.cast::<Trapdoor>() {
+        let powered = is_powered(zone, pos);
+        if powered == trapdoor.powered {
+            return None;
+        }
+        BlockId::new(Trapdoor {
+            open: powered,
+            powered,
+        })
+    } else {
+        return None;
+    };
+
+    if new == block {
+        None
+    } else {
+        Some(new)
+    }
+}
+
+/// Returns the power emitted by a block to its neighbors.
+fn emitted_power(block: BlockId) -> u32 {
+    if block.is::<SignalSource>() {
+        MAX_POWER
+    } else if let Some(wire) = block.cast::<Wire>() {
+        wire.power
+    } else {
+        0
+    }
+}
+
+/// Returns the power level of a wire at `pos`.
+fn wire_power(zone: &Zone, pos: BlockPos) -> u32 {
+    pos.adjacent()
+        .iter()
+        .filter_map(|&adjacent| zone.block(adjacent))
+        .map(|block| {
+            if block.is::<SignalSource>() {
+                MAX_POWER
+            } else {
+                emitted_power(block).saturating_sub(1)
+            }
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Returns whether an actuator at `pos` is powered.
+fn is_powered(zone: &Zone, pos: BlockPos) -> bool {
+    pos.adjacent()
+        .iter()
+        .filter_map(|&adjacent| zone.block(adjacent))
+        .any(|block| emitted_power(block) > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use common::blocks::Air;
+
+    use crate::{block_update::BlockUpdateQueue, game::test_zone};
+
+    use super::*;
+
+    /// Sets a block and runs block updates until nothing changes,
+    /// like `Game::set_block` followed by the signal system.
+    fn set_block(zone: &mut Zone, pos: BlockPos, block: BlockId) {
+        fn set(zone: &mut Zone, queue: &mut BlockUpdateQueue, pos: BlockPos, block: BlockId) {
+            zone.set_block(pos, block).unwrap();
+            queue.schedule(pos);
+            for &adjacent in &pos.adjacent() {
+                queue.schedule(adjacent);
+            }
+        }
+
+        let mut queue = BlockUpdateQueue::new();
+        set(zone, &mut queue, pos, block);
+        while let Some(pos) = queue.pop() {
+            if let Some(new) = updated_state(zone, pos) {
+                set(zone, &mut queue, pos, new);
+            }
+        }
+    }
+
+    fn wire_at(x: i32) -> BlockPos {
+        BlockPos { x, y: 0, z: 0 }
+    }
+
+    fn power(zone: &Zone, pos: BlockPos) -> Option<u32> {
+        zone.block(pos)?.cast::<Wire>().map(|wire| wire.power)
+    }
+
+    #[test]
+    fn signal_propagates_and_decays() {
+        let mut zone = test_zone();
+        for x in 1..=5 {
+            set_block(&mut zone, wire_at(x), BlockId::new(Wire { power: 0 }));
+        }
+        set_block(&mut zone, wire_at(6), BlockId::new(Lamp { lit: false }));
+        set_block(&mut zone, wire_at(0), BlockId::new(SignalSource));
+
+        for x in 1..=5 {
+            assert_eq!(power(&zone, wire_at(x)), Some(16 - x as u32));
+        }
+        assert_eq!(
+            zone.block(wire_at(6)),
+            Some(BlockId::new(Lamp { lit: true }))
+        );
+
+        set_block(&mut zone, wire_at(0), BlockId::new(Air));
+        for x in 1..=5 {
+            assert_eq!(power(&zone, wire_at(x)), Some(0));
+        }
+        assert_eq!(
+            zone.block(wire_at(6)),
+            Some(BlockId::new(Lamp { lit: false }))
+        );
+    }
+
+    #[test]
+    fn signal_reaches_max_power_blocks() {
+        let mut zone = test_zone();
+        set_block(&mut zone, wire_at(0), BlockId::new(SignalSource));
+        for x in 1..=15 {
+            set_block(&mut zone, wire_at(x), BlockId::new(Wire { power: 0 }));
+        }
+        set_block(
+            &mut zone,
+            BlockPos { x: 15, y: 1, z: 0 },
+            BlockId::new(Trapdoor {
+                open: false,
+                powered: false,
+            }),
+        );
+
+        assert_eq!(power(&zone, wire_at(15)), Some(1));
+        assert_eq!(
+            zone.block(BlockPos { x: 15, y: 1, z: 0 }),
+            Some(BlockId::new(Trapdoor {
+                open: true,
+                powered: true,
+            }))
+        );
+    }
+
+    #[test]
+    fn both_door_halves_open_when_powered() {
+        let mut zone = test_zone();
+        let door = |upper| Door {
+            open: false,
+            upper,
+            powered: false,
+        };
+        set_block(
+            &mut zone,
+            BlockPos { x: 1, y: 1, z: 0 },
+            BlockId::new(door(false)),
+        );
+        set_block(
+            &mut zone,
+            BlockPos { x: 1, y: 2, z: 0 },
+            BlockId::new(door(true)),
+        );
+        set_block(
+            &mut zone,
+            BlockPos { x: 0, y: 1, z: 0 },
+            BlockId::new(SignalSource),
+        );
+
+        for &(y, upper) in &[(1, false), (2, true)] {
+            assert_eq!(
+                zone.block(BlockPos { x: 1, y, z: 0 }),
+                Some(BlockId::new(Door {
+                    open: true,
+                    upper,
+                    powered: true,
+                }))
+            );
+        }
+    }
+
+    #[test]
+    fn loops_lose_power_when_source_is_removed() {
+        let mut zone = test_zone();
+        let ring = [(1, 0), (2, 0), (2, 1), (1, 1)];
+        for &(x, z) in &ring {
+            set_block(
+                &mut zone,
+                BlockPos { x, y: 0, z },
+                BlockId::new(Wire { power: 0 }),
+            );
+        }
+        set_block(&mut zone, wire_at(0), BlockId::new(SignalSource));
+        assert_eq!(power(&zone, wire_at(1)), Some(MAX_POWER));
+
+        set_block(&mut zone, wire_at(0), BlockId::new(Air));
+        for &(x, z) in &ring {
+            assert_eq!(power(&zone, BlockPos { x, y: 0, z }), Some(0));
+        }
+    }
+}
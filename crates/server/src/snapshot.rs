@@ -0,0 +1,507 @@
+//! Time-travel debugging.
+//!
+//! When enabled, the server records a lightweight [`Snapshot`] of the game
+//! every few ticks into a ring buffer: the position, orientation, and velocity
+//! of each entity, plus the blocks changed since the previous snapshot.
+//! Commands then dump a snapshot, diff two snapshots, or rewind the game
+//! to an earlier snapshot, which makes it possible to inspect the state
+//! around the tick at which a bug was reported.
+//!
+//! Snapshots are enabled by setting `VOLTZ_SNAPSHOT_INTERVAL`
+//! to the number of ticks between snapshots.
+//!
+//! # Commands
+//! The `snapshot` console command takes one of:
+//! * `list`: lists the ticks of recorded snapshots.
+//! * `dump <tick>`: prints the latest snapshot taken at or before `tick`.
+//! * `diff <from> <to>`: prints what changed between two snapshots.
+//! * `rewind <tick>`: restores entities and blocks to a snapshot and
+//!   discards newer snapshots. Clients are not notified of the changes.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    env,
+    fmt::Write,
+    rc::Rc,
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context};
+use common::{
+    entity::{player::Username, Vel},
+    BlockId, BlockPos, Orient, Pos, WorldPos,
+};
+use glam::{Vec2, Vec3A};
+use hecs::Entity;
+
+use crate::{
+    command::{Command, CommandRegistry},
+    event::BlockChanged,
+    game::Game,
+};
+
+/// The number of snapshots kept when not configured otherwise.
+const DEFAULT_CAPACITY: usize = 256;
+
+/// Configures snapshot recording.
+#[derive(Copy, Clone, Debug)]
+pub struct SnapshotSettings {
+    /// The number of ticks between snapshots.
+    pub interval: u64,
+    /// The maximum number of snapshots kept. Older
+    /// snapshots are dropped first.
+    pub capacity: usize,
+}
+
+impl SnapshotSettings {
+    /// Reads settings from the environment. Returns
+    /// `None` if snapshots are disabled.
+    pub fn from_env() -> Option<Self> {
+        let interval = env::var("VOLTZ_SNAPSHOT_INTERVAL").ok()?;
+        match interval.parse() {
+            Ok(interval) if interval > 0 => Some(Self {
+                interval,
+                capacity: DEFAULT_CAPACITY,
+            }),
+            _ => {
+                log::warn!("Ignoring invalid VOLTZ_SNAPSHOT_INTERVAL '{}'", interval);
+                None
+            }
+        }
+    }
+}
+
+/// The recorded state of an entity.
+#[derive(Clone, Debug, PartialEq)]
+struct EntityState {
+    username: Option<String>,
+    pos: Option<WorldPos>,
+    orient: Option<Vec2>,
+    vel: Option<Vec3A>,
+}
+
+impl EntityState {
+    fn capture(game: &Game, entity: Entity) -> Self {
+        let ecs = game.ecs();
+        Self {
+            username: ecs.get::<Username>(entity).ok().map(|name| name.0.clone()),
+            pos: ecs.get::<Pos>(entity).ok().map(|pos| pos.0),
+            orient: ecs.get::<Orient>(entity).ok().map(|orient| orient.0),
+            vel: ecs.get::<Vel>(entity).ok().map(|vel| vel.0),
+        }
+    }
+
+    fn restore(&self, game: &mut Game, entity: Entity) {
+        let ecs = game.ecs_mut();
+        if let (Some(pos), Ok(mut current)) = (self.pos, ecs.get_mut::<Pos>(entity)) {
+            current.0 = pos;
+        }
+        if let (Some(orient), Ok(mut current)) = (self.orient, ecs.get_mut::<Orient>(entity)) {
+            current.0 = orient;
+        }
+        if let (Some(vel), Ok(mut current)) = (self.vel, ecs.get_mut::<Vel>(entity)) {
+            current.0 = vel;
+        }
+    }
+}
+
+/// The state of the game at a given tick.
+pub struct Snapshot {
+    tick: u64,
+    /// Entities keyed by [`Entity::to_bits`].
+    entities: BTreeMap<u64, EntityState>,
+    /// Blocks changed since the previous snapshot, in order.
+    block_changes: Vec<BlockChanged>,
+}
+
+impl Snapshot {
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+}
+
+/// A ring buffer of snapshots.
+pub struct Snapshots {
+    settings: SnapshotSettings,
+    snapshots: VecDeque<Snapshot>,
+    /// Blocks changed since the last snapshot.
+    pending_block_changes: Vec<BlockChanged>,
+}
+
+impl Snapshots {
+    pub fn new(settings: SnapshotSettings) -> Self {
+        Self {
+            settings,
+            snapshots: VecDeque::with_capacity(settings.capacity),
+            pending_block_changes: Vec::new(),
+        }
+    }
+
+    /// Records block changes from the current tick and takes a
+    /// snapshot if one is due. Call at the end of each tick.
+    pub fn update(&mut self, game: &Game) {
+        self.pending_block_changes
+            .extend(game.events().iter::<BlockChanged>().copied());
+
+        if game.tick() % self.settings.interval == 0 {
+            self.take_snapshot(game);
+        }
+    }
+
+    fn take_snapshot(&mut self, game: &Game) {
+        let entities = game
+            .ecs()
+            .iter()
+            .map(|(entity, _)| (entity.to_bits(), EntityState::capture(game, entity)))
+            .collect();
+        let snapshot = Snapshot {
+            tick: game.tick(),
+            entities,
+            block_changes: std::mem::take(&mut self.pending_block_changes),
+        };
+
+        if self.snapshots.len() == self.settings.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Gets the latest snapshot taken at or before `tick`.
+    pub fn at(&self, tick: u64) -> Option<&Snapshot> {
+        self.snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.tick <= tick)
+    }
+
+    /// Runs a command. Returns the text to display.
+    pub fn run_command(&mut self, game: &mut Game, command: &str) -> anyhow::Result<String> {
+        let mut args = command.split_whitespace();
+        let name = args.next().unwrap_or("list");
+        let mut next_tick = || -> anyhow::Result<u64> {
+            let arg = args.next().ok_or_else(|| anyhow!("missing tick"))?;
+            u64::from_str(arg).with_context(|| format!("invalid tick '{}'", arg))
+        };
+
+        match name {
+            "list" => Ok(self.list()),
+            "dump" => {
+                let tick = next_tick()?;
+                Ok(dump(self.find(tick)?))
+            }
+            "diff" => {
+                let from = next_tick()?;
+                let to = next_tick()?;
+                self.diff(from, to)
+            }
+            "rewind" => {
+                let tick = next_tick()?;
+                self.rewind(game, tick)
+            }
+            _ => bail!("unknown snapshot command '{}'", name),
+        }
+    }
+
+    fn find(&self, tick: u64) -> anyhow::Result<&Snapshot> {
+        self.at(tick)
+            .ok_or_else(|| anyhow!("no snapshot at or before tick {}", tick))
+    }
+
+    fn list(&self) -> String {
+        let ticks: Vec<String> = self
+            .snapshots
+            .iter()
+            .map(|snapshot| snapshot.tick.to_string())
+            .collect();
+        format!("{} snapshots: {}", ticks.len(), ticks.join(", "))
+    }
+
+    /// Returns the block changes made after `from` up to and including `to`.
+    fn block_changes_between(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> impl Iterator<Item = &BlockChanged> + '_ {
+        self.snapshots
+            .iter()
+            .filter(move |snapshot| snapshot.tick > from && snapshot.tick <= to)
+            .flat_map(|snapshot| snapshot.block_changes.iter())
+    }
+
+    fn diff(&self, from: u64, to: u64) -> anyhow::Result<String> {
+        let from = self.find(from)?;
+        let to = self.find(to)?;
+        let mut output = format!("Changes from tick {} to tick {}:\n", from.tick, to.tick);
+
+        for (bits, old) in &from.entities {
+            match to.entities.get(bits) {
+                Some(new) if new == old => {}
+                Some(new) => {
+                    writeln!(output, "  changed {}:", describe(*bits, old)).ok();
+                    write_field_change(&mut output, "pos", old.pos, new.pos);
+                    write_field_change(&mut output, "orient", old.orient, new.orient);
+                    write_field_change(&mut output, "vel", old.vel, new.vel);
+                }
+                None => {
+                    writeln!(output, "  removed {}", describe(*bits, old)).ok();
+                }
+            }
+        }
+        for (bits, new) in &to.entities {
+            if !from.entities.contains_key(bits) {
+                writeln!(output, "  added {}", describe(*bits, new)).ok();
+            }
+        }
+
+        // Collapse repeated changes to the same block.
+        let mut blocks: BTreeMap<BlockPos, (BlockId, BlockId)> = BTreeMap::new();
+        for change in self.block_changes_between(from.tick, to.tick) {
+            blocks
+                .entry(change.pos)
+                .and_modify(|(_, new)| *new = change.new)
+                .or_insert((change.old, change.new));
+        }
+        for (pos, (old, new)) in blocks {
+            if old != new {
+                writeln!(output, "  block {:?}: {:?} -> {:?}", pos, old, new).ok();
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn rewind(&mut self, game: &mut Game, tick: u64) -> anyhow::Result<String> {
+        let target = self.find(tick)?.tick;
+
+        // Undo block changes newest first.
+        let mut undone = 0;
+        let newer_changes = self
+            .snapshots
+            .iter()
+            .filter(|snapshot| snapshot.tick > target)
+            .flat_map(|snapshot| snapshot.block_changes.iter())
+            .chain(self.pending_block_changes.iter());
+        let newer_changes: Vec<BlockChanged> = newer_changes.copied().collect();
+        for change in newer_changes.iter().rev() {
+            if game
+                .main_zone_mut()
+                .set_block(change.pos, change.old)
+                .is_ok()
+            {
+                undone += 1;
+            }
+        }
+
+        let snapshot = self.find(target)?;
+        let mut restored = 0;
+        let mut missing = 0;
+        for (&bits, state) in &snapshot.entities {
+            match Entity::from_bits(bits) {
+                entity if game.ecs().contains(entity) => {
+                    state.restore(game, entity);
+                    restored += 1;
+                }
+                _ => missing += 1,
+            }
+        }
+
+        while self
+            .snapshots
+            .back()
+            .map_or(false, |snapshot| snapshot.tick > target)
+        {
+            self.snapshots.pop_back();
+        }
+        self.pending_block_changes.clear();
+
+        Ok(format!(
+            "Rewound to tick {}: restored {} entities ({} no longer exist) and undid {} block changes",
+            target, restored, missing, undone
+        ))
+    }
+}
+
+/// Registers the `snapshot` command, which runs the commands
+/// above on `snapshots`. Fails if snapshots are disabled.
+pub fn register_commands(
+    commands: &mut CommandRegistry,
+    snapshots: Option<Rc<RefCell<Snapshots>>>,
+) {
+    commands.register(SnapshotCommand { snapshots });
+}
+
+struct SnapshotCommand {
+    snapshots: Option<Rc<RefCell<Snapshots>>>,
+}
+
+impl Command for SnapshotCommand {
+    fn name(&self) -> &str {
+        "snapshot"
+    }
+
+    fn usage(&self) -> &str {
+        "list | dump <tick> | diff <from> <to> | rewind <tick>"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        match &self.snapshots {
+            Some(snapshots) => snapshots.borrow_mut().run_command(game, &args.join(" ")),
+            None => bail!("snapshots are disabled; set VOLTZ_SNAPSHOT_INTERVAL to enable them"),
+        }
+    }
+}
+
+fn describe(bits: u64, state: &EntityState) -> String {
+    match &state.username {
+        Some(username) => format!("{:?} ({})", Entity::from_bits(bits), username),
+        None => format!("{:?}", Entity::from_bits(bits)),
+    }
+}
+
+fn write_field_change<T: std::fmt::Debug + PartialEq>(
+    output: &mut String,
+    name: &str,
+    old: Option<T>,
+    new: Option<T>,
+) {
+    if old != new {
+        writeln!(output, "    {}: {:?} -> {:?}", name, old, new).ok();
+    }
+}
+
+fn dump(snapshot: &Snapshot) -> String {
+    let mut output = format!(
+        "Snapshot at tick {} ({} entities, {} block changes since previous snapshot):\n",
+        snapshot.tick,
+        snapshot.entities.len(),
+        snapshot.block_changes.len()
+    );
+    for (&bits, state) in &snapshot.entities {
+        writeln!(
+            output,
+            "  {}: pos {:?}, orient {:?}, vel {:?}",
+            describe(bits, state),
+            state.pos,
+            state.orient,
+            state.vel
+        )
+        .ok();
+    }
+    for change in &snapshot.block_changes {
+        writeln!(
+            output,
+            "  block {:?}: {:?} -> {:?}",
+            change.pos, change.old, change.new
+        )
+        .ok();
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use common::blocks::Stone;
+    use glam::vec3a;
+
+    use crate::game::test_game;
+
+    use super::*;
+
+    /// Runs a tick in which `changes` are made, then records it.
+    fn tick(game: &mut Game, snapshots: &mut Snapshots, changes: &[(BlockPos, BlockId)]) {
+        game.advance_tick();
+        game.events().begin_tick();
+        for &(pos, block) in changes {
+            game.set_block(pos, block).unwrap();
+        }
+        snapshots.update(game);
+    }
+
+    fn snapshots(interval: u64, capacity: usize) -> Snapshots {
+        Snapshots::new(SnapshotSettings { interval, capacity })
+    }
+
+    #[test]
+    fn drops_oldest_snapshots() {
+        let mut game = test_game();
+        let mut snapshots = snapshots(2, 3);
+        for _ in 0..10 {
+            tick(&mut game, &mut snapshots, &[]);
+        }
+
+        assert_eq!(snapshots.list(), "3 snapshots: 6, 8, 10");
+        assert!(snapshots.at(5).is_none());
+        assert_eq!(snapshots.at(9).unwrap().tick(), 8);
+        assert_eq!(snapshots.at(100).unwrap().tick(), 10);
+    }
+
+    #[test]
+    fn diff_lists_changes() {
+        let mut game = test_game();
+        let mut snapshots = snapshots(1, 16);
+        let moved = game
+            .ecs_mut()
+            .spawn((Pos(WorldPos::main(vec3a(0., 0., 0.))),));
+        let still = game
+            .ecs_mut()
+            .spawn((Pos(WorldPos::main(vec3a(5., 5., 5.))),));
+        tick(&mut game, &mut snapshots, &[]);
+
+        game.ecs_mut().get_mut::<Pos>(moved).unwrap().0 = WorldPos::main(vec3a(1., 2., 3.));
+        let added = game
+            .ecs_mut()
+            .spawn((Pos(WorldPos::main(vec3a(0., 0., 0.))),));
+        let placed = BlockPos { x: 1, y: 1, z: 1 };
+        let reverted = BlockPos { x: 2, y: 2, z: 2 };
+        let air = game.main_zone().block(reverted).unwrap();
+        tick(
+            &mut game,
+            &mut snapshots,
+            &[
+                (placed, BlockId::new(Stone)),
+                (reverted, BlockId::new(Stone)),
+            ],
+        );
+        tick(&mut game, &mut snapshots, &[(reverted, air)]);
+
+        let diff = snapshots.run_command(&mut game, "diff 1 3").unwrap();
+        assert!(diff.contains(&format!("changed {:?}", moved)), "{}", diff);
+        assert!(diff.contains("pos:"), "{}", diff);
+        assert!(!diff.contains(&format!("{:?}", still)), "{}", diff);
+        assert!(diff.contains(&format!("added {:?}", added)), "{}", diff);
+        assert!(diff.contains(&format!("block {:?}", placed)), "{}", diff);
+        assert!(!diff.contains(&format!("block {:?}", reverted)), "{}", diff);
+
+        assert!(snapshots.run_command(&mut game, "diff 0 3").is_err());
+        assert!(snapshots.run_command(&mut game, "diff 1").is_err());
+    }
+
+    #[test]
+    fn rewind_restores_entities_and_blocks() {
+        let mut game = test_game();
+        let mut snapshots = snapshots(2, 16);
+        let start = WorldPos::main(vec3a(0., 0., 0.));
+        let entity = game.ecs_mut().spawn((Pos(start),));
+        let a = BlockPos { x: 1, y: 1, z: 1 };
+        let b = BlockPos { x: 3, y: 1, z: 1 };
+        let air = game.main_zone().block(a).unwrap();
+        tick(&mut game, &mut snapshots, &[]);
+        tick(&mut game, &mut snapshots, &[]);
+
+        game.ecs_mut().get_mut::<Pos>(entity).unwrap().0 = WorldPos::main(vec3a(4., 4., 4.));
+        tick(&mut game, &mut snapshots, &[(a, BlockId::new(Stone))]);
+        tick(&mut game, &mut snapshots, &[]);
+        // Not yet part of a snapshot.
+        tick(&mut game, &mut snapshots, &[(b, BlockId::new(Stone))]);
+        assert_eq!(snapshots.list(), "2 snapshots: 2, 4");
+
+        let message = snapshots.run_command(&mut game, "rewind 3").unwrap();
+        assert!(message.starts_with("Rewound to tick 2"), "{}", message);
+        assert_eq!(game.ecs().get::<Pos>(entity).unwrap().0, start);
+        assert_eq!(game.main_zone().block(a), Some(air));
+        assert_eq!(game.main_zone().block(b), Some(air));
+        assert_eq!(snapshots.list(), "1 snapshots: 2");
+        assert!(snapshots.run_command(&mut game, "rewind 1").is_err());
+    }
+}
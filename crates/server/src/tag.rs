@@ -0,0 +1,321 @@
+//! Names attached to entities so that commands can find them.
+//!
+//! An entity's tags are kept in its [`Tags`] component. The [`TagIndex`]
+//! held by the [`Game`] maps each tag to the entities that have it, so that
+//! [selectors](crate::selector) like `@e[tag=boss]` don't scan every entity.
+//! hecs can't notify us of changes, so tags must be changed through [`add`]
+//! and [`remove`], which keep the two in sync. Despawned entities are
+//! dropped from the index by a system shortly afterwards; until then,
+//! lookups skip them.
+//!
+//! Tag names are interned: every [`Tag`] with the same name
+//! shares one allocation.
+
+use std::{borrow::Borrow, fmt, sync::Arc};
+
+use anyhow::bail;
+use common::{System, SystemExecutor};
+use hashbrown::{HashMap, HashSet};
+use hecs::Entity;
+
+use crate::{
+    command::{self, Command, CommandRegistry},
+    game::Game,
+};
+
+/// The longest allowed tag name, in bytes.
+pub const MAX_TAG_LEN: usize = 32;
+
+/// An interned tag name.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Tag(Arc<str>);
+
+impl Tag {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Tag {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Component holding the tags of an entity. Entities
+/// without tags may lack the component.
+#[derive(Clone, Debug, Default)]
+pub struct Tags(Vec<Tag>);
+
+impl Tags {
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.iter().any(|tag| tag.as_str() == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Tag> + '_ {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Maps tags to the entities that have them.
+#[derive(Default)]
+pub struct TagIndex {
+    /// Includes tags that no entity has any more,
+    /// so that their names stay interned.
+    entities: HashMap<Tag, HashSet<Entity>>,
+}
+
+impl TagIndex {
+    /// Gets the tag with the given name, interning it if needed.
+    fn intern(&mut self, name: &str) -> Tag {
+        if let Some((tag, _)) = self.entities.get_key_value(name) {
+            return tag.clone();
+        }
+        let tag = Tag(Arc::from(name));
+        self.entities.insert(tag.clone(), HashSet::new());
+        tag
+    }
+
+    /// Returns the entities that had the tag `name` when last
+    /// indexed. Some of them may have been despawned since.
+    pub fn tagged<'a>(&'a self, name: &str) -> impl Iterator<Item = Entity> + 'a {
+        self.entities
+            .get(name)
+            .into_iter()
+            .flat_map(|entities| entities.iter().copied())
+    }
+
+    /// Returns the number of entities with the tag `name`,
+    /// possibly including some that were despawned.
+    pub fn count(&self, name: &str) -> usize {
+        self.entities.get(name).map_or(0, HashSet::len)
+    }
+
+    fn forget(&mut self, despawned: &[Entity]) {
+        for entities in self.entities.values_mut() {
+            for entity in despawned {
+                entities.remove(entity);
+            }
+        }
+    }
+}
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(PruneSystem);
+}
+
+pub fn register_commands(commands: &mut CommandRegistry) {
+    commands.register(TagCommand);
+}
+
+/// Adds the tag `name` to `entity`. Returns `false`
+/// if the entity already had it.
+pub fn add(game: &mut Game, entity: Entity, name: &str) -> anyhow::Result<bool> {
+    validate(name)?;
+    if game.ecs().entity(entity).is_err() {
+        bail!("the entity no longer exists");
+    }
+    let tag = game.tags_mut().intern(name);
+    let existing = game.ecs().get_mut::<Tags>(entity).ok().map(|mut tags| {
+        if tags.contains(name) {
+            false
+        } else {
+            tags.0.push(tag.clone());
+            true
+        }
+    });
+    let added = match existing {
+        Some(added) => added,
+        None => {
+            game.ecs_mut()
+                .insert_one(entity, Tags(vec![tag.clone()]))
+                .unwrap();
+            true
+        }
+    };
+    if added {
+        game.tags_mut()
+            .entities
+            .get_mut(&tag)
+            .expect("interned")
+            .insert(entity);
+    }
+    Ok(added)
+}
+
+/// Removes the tag `name` from `entity`. Returns
+/// `false` if the entity didn't have it.
+pub fn remove(game: &mut Game, entity: Entity, name: &str) -> bool {
+    let removed = match game.ecs().get_mut::<Tags>(entity) {
+        Ok(mut tags) => {
+            let len = tags.0.len();
+            tags.0.retain(|tag| tag.as_str() != name);
+            tags.0.len() != len
+        }
+        Err(_) => false,
+    };
+    if removed {
+        if let Some(entities) = game.tags_mut().entities.get_mut(name) {
+            entities.remove(&entity);
+        }
+    }
+    removed
+}
+
+/// Returns whether `entity` has the tag `name`.
+pub fn has(game: &Game, entity: Entity, name: &str) -> bool {
+    game.ecs()
+        .get::<Tags>(entity)
+        .map_or(false, |tags| tags.contains(name))
+}
+
+fn validate(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() || name.len() > MAX_TAG_LEN {
+        bail!("tags must be 1 to {} characters long", MAX_TAG_LEN);
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|&c| !c.is_ascii_alphanumeric() && !"_-.".contains(c))
+    {
+        bail!("tags may not contain '{}'", c);
+    }
+    Ok(())
+}
+
+/// System to drop despawned entities from the [`TagIndex`].
+struct PruneSystem;
+
+impl System<Game> for PruneSystem {
+    fn run(&mut self, game: &mut Game) {
+        // Lookups skip despawned entities, so this needn't run every tick.
+        if game.tick() % game.tps() as u64 != 0 {
+            return;
+        }
+        let despawned: Vec<Entity> = game
+            .tags()
+            .entities
+            .values()
+            .flatten()
+            .copied()
+            .filter(|&entity| game.ecs().entity(entity).is_err())
+            .collect();
+        if !despawned.is_empty() {
+            game.tags_mut().forget(&despawned);
+        }
+    }
+}
+
+struct TagCommand;
+
+impl Command for TagCommand {
+    fn name(&self) -> &str {
+        "tag"
+    }
+
+    fn usage(&self) -> &str {
+        "<targets> add|remove <tag> | <targets> list"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        let (targets, action, name) = match args {
+            [targets, action, name] if *action == "add" || *action == "remove" => {
+                (*targets, *action, *name)
+            }
+            [targets, "list"] => {
+                let mut lines = Vec::new();
+                for entity in command::select(game, targets)? {
+                    let tags = game
+                        .ecs()
+                        .get::<Tags>(entity)
+                        .map(|tags| tags.iter().map(Tag::as_str).collect::<Vec<_>>().join(", "))
+                        .unwrap_or_default();
+                    let tags = if tags.is_empty() { "none" } else { &tags };
+                    lines.push(format!("{}: {}", command::describe(game, entity), tags));
+                }
+                return Ok(lines.join("\n"));
+            }
+            _ => bail!("usage: {}", command::usage(self)),
+        };
+
+        let mut changed = 0;
+        let targets = command::select(game, targets)?;
+        for &entity in &targets {
+            let did_change = if action == "add" {
+                add(game, entity, name)?
+            } else {
+                remove(game, entity, name)
+            };
+            changed += did_change as usize;
+        }
+        let verb = if action == "add" {
+            "Tagged"
+        } else {
+            "Untagged"
+        };
+        Ok(format!(
+            "{} {} of {} entities as '{}'",
+            verb,
+            changed,
+            targets.len(),
+            name
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{Pos, WorldPos};
+    use glam::Vec3A;
+
+    use crate::game::test_game;
+
+    use super::*;
+
+    #[test]
+    fn index_follows_tags() {
+        let mut game = test_game();
+        let a = game.ecs_mut().spawn((Pos(WorldPos::main(Vec3A::zero())),));
+        let b = game.ecs_mut().spawn((Pos(WorldPos::main(Vec3A::zero())),));
+
+        assert!(add(&mut game, a, "boss").unwrap());
+        assert!(!add(&mut game, a, "boss").unwrap());
+        assert!(add(&mut game, b, "boss").unwrap());
+        assert!(add(&mut game, b, "minion").unwrap());
+        assert!(add(&mut game, b, "two words").is_err());
+        assert!(has(&game, a, "boss"));
+        assert_eq!(game.tags().count("boss"), 2);
+
+        assert!(remove(&mut game, a, "boss"));
+        assert!(!remove(&mut game, a, "boss"));
+        assert_eq!(game.tags().tagged("boss").collect::<Vec<_>>(), vec![b]);
+
+        game.ecs_mut().despawn(b).unwrap();
+        game.tags_mut().forget(&[b]);
+        assert_eq!(game.tags().count("boss"), 0);
+        assert_eq!(game.tags().count("minion"), 0);
+    }
+
+    #[test]
+    fn tags_are_interned() {
+        let mut index = TagIndex::default();
+        let a = index.intern("boss");
+        let b = index.intern("boss");
+        assert!(Arc::ptr_eq(&a.0, &b.0));
+    }
+}
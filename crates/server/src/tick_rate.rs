@@ -0,0 +1,177 @@
+//! The tick rate.
+//!
+//! The server runs [`TPS`] ticks per second unless `VOLTZ_TPS` is set.
+//! Time-based systems read the length of a tick from
+//! [`Game::tick_length`], so changing the rate changes how often
+//! the game is simulated, not how fast it runs.
+//!
+//! Slow motion instead runs ticks at [`SLOW_MOTION_TPS`] in real time
+//! while each still simulates a full tick of game time, making it
+//! possible to watch gameplay logic tick by tick. Players are told
+//! the real time between ticks with the `TickRate` packet so they can
+//! keep interpolating smoothly.
+//!
+//! Both are changed at runtime through [`run_command`], which also
+//! backs the console [commands](crate::command) of the same names:
+//! * `tps`: prints the tick rate.
+//! * `tps <ticks per second>`: sets the tick rate.
+//! * `slowmo [on|off]`: toggles or sets slow motion.
+
+use std::env;
+
+use anyhow::{bail, Context};
+use common::{System, SystemExecutor};
+use protocol::packets::{server::TickRate, ServerPacket};
+
+use crate::{
+    command::{Command, CommandRegistry},
+    event::PlayerJoined,
+    game::Game,
+    Mailbox, SLOW_MOTION_TPS, TPS,
+};
+
+/// The highest supported tick rate.
+const MAX_TPS: u32 = 1000;
+
+/// Reads the tick rate from `VOLTZ_TPS`, falling back
+/// to [`TPS`] if it is not set or invalid.
+pub fn tps_from_env() -> u32 {
+    let value = match env::var("VOLTZ_TPS") {
+        Ok(value) => value,
+        Err(_) => return TPS,
+    };
+    match parse_tps(&value) {
+        Ok(tps) => tps,
+        Err(e) => {
+            log::error!("Ignoring invalid VOLTZ_TPS '{}': {:#}", value, e);
+            TPS
+        }
+    }
+}
+
+fn parse_tps(value: &str) -> anyhow::Result<u32> {
+    let tps: u32 = value.trim().parse().context("not a number")?;
+    if tps == 0 || tps > MAX_TPS {
+        bail!("the tick rate must be between 1 and {}", MAX_TPS);
+    }
+    Ok(tps)
+}
+
+/// Runs a tick rate command, such as `tps 10`, returning
+/// a description of the result.
+pub fn run_command(game: &mut Game, command: &str) -> anyhow::Result<String> {
+    let args: Vec<&str> = command.split_whitespace().collect();
+    match args.as_slice() {
+        ["tps"] => Ok(describe(game)),
+        ["tps", tps] => {
+            game.set_tps(parse_tps(tps)?);
+            Ok(describe(game))
+        }
+        ["slowmo"] => {
+            game.set_slow_motion(!game.is_slow_motion());
+            Ok(describe(game))
+        }
+        ["slowmo", "on"] => {
+            game.set_slow_motion(true);
+            Ok(describe(game))
+        }
+        ["slowmo", "off"] => {
+            game.set_slow_motion(false);
+            Ok(describe(game))
+        }
+        _ => bail!("unknown command; expected 'tps [ticks per second]' or 'slowmo [on|off]'"),
+    }
+}
+
+/// Registers the `tps` and `slowmo` console commands.
+pub fn register_commands(commands: &mut CommandRegistry) {
+    commands.register(TickRateCommand {
+        name: "tps",
+        usage: "[ticks per second]",
+    });
+    commands.register(TickRateCommand {
+        name: "slowmo",
+        usage: "[on|off]",
+    });
+}
+
+/// A console command handled by [`run_command`].
+struct TickRateCommand {
+    name: &'static str,
+    usage: &'static str,
+}
+
+impl Command for TickRateCommand {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn usage(&self) -> &str {
+        self.usage
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        let mut command = vec![self.name];
+        command.extend_from_slice(args);
+        run_command(game, &command.join(" "))
+    }
+}
+
+fn describe(game: &Game) -> String {
+    if game.is_slow_motion() {
+        format!(
+            "Running at {} TPS in slow motion ({} TPS in real time)",
+            game.tps(),
+            SLOW_MOTION_TPS
+        )
+    } else {
+        format!("Running at {} TPS", game.tps())
+    }
+}
+
+pub fn setup(systems: &mut SystemExecutor<Game>, game: &Game) {
+    systems.add(TickRateSystem {
+        announced: game.real_tick_length().as_secs_f32(),
+    });
+}
+
+/// System to tell players the real time between ticks.
+struct TickRateSystem {
+    /// The tick length players were last told about, in seconds.
+    announced: f32,
+}
+
+impl System<Game> for TickRateSystem {
+    fn run(&mut self, game: &mut Game) {
+        let tick_length = game.real_tick_length().as_secs_f32();
+        if tick_length != self.announced {
+            self.announced = tick_length;
+            log::info!("{}", describe(game));
+            for (_, mailbox) in game.ecs().query::<&Mailbox>().iter() {
+                send_tick_rate(mailbox, tick_length);
+            }
+        }
+        for event in game.events().iter::<PlayerJoined>() {
+            if let Ok(mailbox) = game.ecs().get::<Mailbox>(event.player) {
+                send_tick_rate(&mailbox, tick_length);
+            }
+        }
+    }
+}
+
+fn send_tick_rate(mailbox: &Mailbox, tick_length: f32) {
+    mailbox.send(ServerPacket::TickRate(TickRate { tick_length }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tick_rates() {
+        assert_eq!(parse_tps(" 10 ").unwrap(), 10);
+        assert!(parse_tps("0").is_err());
+        assert!(parse_tps("1001").is_err());
+        assert!(parse_tps("fast").is_err());
+    }
+}
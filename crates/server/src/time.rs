@@ -0,0 +1,159 @@
+//! The world's time and the day/night cycle.
+//!
+//! [`TimeSystem`] advances the [time](Game::time) by a tick of game time
+//! each tick while the `doDaylightCycle` [game rule](GameRule) is on.
+//! Players are sent the time with the `TimeUpdate` packet when they
+//! join and every [`UPDATE_INTERVAL`] seconds, and advance it themselves
+//! in between. Updates are also sent right away when the time is set or
+//! starts passing at a different rate, e.g. in slow motion.
+//!
+//! The `time` console [command](crate::command) prints the time,
+//! `time set <day|noon|night|midnight|seconds>` sets it, and
+//! `time add <seconds>` skips ahead.
+
+use anyhow::{bail, Context};
+use common::{
+    game_rules::GameRule,
+    time::{self, DAY_LENGTH, MIDNIGHT, NOON, SUNRISE, SUNSET},
+    System, SystemExecutor,
+};
+use protocol::packets::{server::TimeUpdate, ServerPacket};
+
+use crate::{
+    command::{self, Command, CommandRegistry},
+    event::PlayerJoined,
+    game::Game,
+    Mailbox,
+};
+
+/// The number of seconds of real time between time updates.
+const UPDATE_INTERVAL: u64 = 5;
+
+pub fn setup(systems: &mut SystemExecutor<Game>, game: &Game) {
+    systems.add(TimeSystem {
+        expected: game.time(),
+        announced_rate: rate(game),
+        last_update: game.tick(),
+    });
+}
+
+/// Registers the `time` console command.
+pub fn register_commands(commands: &mut CommandRegistry) {
+    commands.register(TimeCommand);
+}
+
+/// System to advance the time and tell players about it.
+struct TimeSystem {
+    /// The time as of the end of the last tick. If it differs
+    /// at the start of a tick, the time was set.
+    expected: f64,
+    /// The rate players were last told about.
+    announced_rate: f32,
+    /// The tick of the last periodic update.
+    last_update: u64,
+}
+
+impl System<Game> for TimeSystem {
+    fn run(&mut self, game: &mut Game) {
+        let set = game.time() != self.expected;
+        let rate = rate(game);
+        if rate > 0. {
+            game.set_time(game.time() + game.tick_length().as_secs_f64());
+        }
+        self.expected = game.time();
+
+        // Periodic updates correct the drift of players' clocks.
+        let real_tps = (1. / game.real_tick_length().as_secs_f64()).round() as u64;
+        let due = game.tick() - self.last_update >= UPDATE_INTERVAL * real_tps.max(1);
+        if set || rate != self.announced_rate || due {
+            self.announced_rate = rate;
+            self.last_update = game.tick();
+            for (_, mailbox) in game.ecs().query::<&Mailbox>().iter() {
+                send_time(mailbox, game.time(), rate);
+            }
+        }
+        for event in game.events().iter::<PlayerJoined>() {
+            if let Ok(mailbox) = game.ecs().get::<Mailbox>(event.player) {
+                send_time(&mailbox, game.time(), rate);
+            }
+        }
+    }
+}
+
+/// Returns the seconds of game time that pass per real second.
+fn rate(game: &Game) -> f32 {
+    if game.rules().get_bool(GameRule::DoDaylightCycle) {
+        (game.tick_length().as_secs_f64() / game.real_tick_length().as_secs_f64()) as f32
+    } else {
+        0.
+    }
+}
+
+fn send_time(mailbox: &Mailbox, time: f64, rate: f32) {
+    mailbox.send(ServerPacket::TimeUpdate(TimeUpdate { time, rate }));
+}
+
+/// Describes `time` as the day and the time on a 24-hour
+/// clock, on which the sun rises at 6:00.
+fn clock(time: f64) -> String {
+    let day = (time / DAY_LENGTH).floor() as i64 + 1;
+    let hours = (time::time_of_day(time) as f64 * 24. + 6.) % 24.;
+    let minutes = (hours.fract() * 60.) as u32;
+    format!("day {}, {:02}:{:02}", day, hours as u32, minutes)
+}
+
+/// Returns the time at which the current day reaches `time_of_day`.
+fn time_today(time: f64, time_of_day: f32) -> f64 {
+    (time / DAY_LENGTH).floor() * DAY_LENGTH + time_of_day as f64 * DAY_LENGTH
+}
+
+/// The `time` console command.
+struct TimeCommand;
+
+impl Command for TimeCommand {
+    fn name(&self) -> &str {
+        "time"
+    }
+
+    fn usage(&self) -> &str {
+        "[set <day|noon|night|midnight|seconds>|add <seconds>]"
+    }
+
+    fn run(&mut self, game: &mut Game, args: &[&str]) -> anyhow::Result<String> {
+        let time = match args {
+            [] => return Ok(format!("It is {}", clock(game.time()))),
+            ["set", "day"] => time_today(game.time(), SUNRISE),
+            ["set", "noon"] => time_today(game.time(), NOON),
+            ["set", "night"] => time_today(game.time(), SUNSET),
+            ["set", "midnight"] => time_today(game.time(), MIDNIGHT),
+            ["set", seconds] => parse_seconds(seconds)?,
+            ["add", seconds] => game.time() + parse_seconds(seconds)?,
+            _ => bail!("usage: {}", command::usage(self)),
+        };
+        game.set_time(time);
+        Ok(format!("Set the time to {}", clock(time)))
+    }
+}
+
+fn parse_seconds(value: &str) -> anyhow::Result<f64> {
+    let seconds: f64 = value.parse().context("not a number of seconds")?;
+    if !seconds.is_finite() {
+        bail!("the time must be finite");
+    }
+    Ok(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_clock() {
+        assert_eq!(clock(0.), "day 1, 06:00");
+        assert_eq!(clock(DAY_LENGTH * 1.25), "day 2, 12:00");
+        assert_eq!(clock(DAY_LENGTH * 0.875), "day 1, 03:00");
+        assert_eq!(time_today(DAY_LENGTH * 3.6, NOON), DAY_LENGTH * 3.25);
+        assert!(parse_seconds("inf").is_err());
+        assert!(parse_seconds("dawn").is_err());
+    }
+}
@@ -1,58 +1,220 @@
+//! Players' views: the chunks they are sent.
+//!
+//! Each player sees the chunks within their view distance of the chunk
+//! they are in. Clients choose the distance in `ClientInfo` and may change
+//! it with `UpdateSettings`; it is clamped between [`MIN_VIEW_DISTANCE`]
+//! and the server's maximum, set with `VOLTZ_MAX_VIEW_DISTANCE`. The
+//! maximum is lowered temporarily while the server is under load; see the
+//! [`view_scaling`](crate::view_scaling) module. Players are told the
+//! distance they get with `ViewDistanceChanged` when they join and
+//! whenever it changes.
+//!
+//! Item drops and experience orbs are sent along with the chunk they are
+//! in when it enters a player's view, and despawned when it leaves.
+//!
+//! Players whose clients [cache chunks](protocol::chunk_cache) are sent
+//! the hash of each chunk, and the chunk itself only when they ask for
+//! it. Setting `VOLTZ_NO_CHUNK_CACHE` turns chunk caching off.
+
+use std::env;
+
 use bumpalo::Bump;
 use common::{
-    entity::player::{Username, View},
+    entity::{
+        player::{Username, View},
+        ItemDrop, XpOrb,
+    },
     ChunkPos, Pos, System, SystemExecutor,
 };
-use hashbrown::HashSet;
+use glam::Vec2;
+use hashbrown::{hash_map::DefaultHashBuilder, HashSet};
 use hecs::Entity;
-use protocol::packets::{
-    server::{LoadChunk, UnloadChunk},
-    ServerPacket,
+use protocol::{
+    chunk_cache,
+    dictionary::{BlockDictionary, ChunkData},
+    features::Features,
+    packets::{
+        server::{
+            self, DespawnEntity, EntityKind, LoadCachedChunk, LoadChunk, SpawnEntity, UnloadChunk,
+        },
+        ServerPacket,
+    },
 };
 
-use crate::{event::PlayerJoined, game::Game, Mailbox};
+use worldgen::ColumnPos;
+
+use crate::{
+    event::{ColumnGenerated, PlayerJoined, PlayerResumed, ViewDistanceChanged},
+    game::Game,
+    Mailbox, DEFAULT_MAX_VIEW_DISTANCE, MIN_VIEW_DISTANCE,
+};
 
 pub fn setup(systems: &mut SystemExecutor<Game>) {
     systems.add(ViewSystem::default());
 }
 
+/// Reads the maximum view distance from `VOLTZ_MAX_VIEW_DISTANCE`,
+/// falling back to [`DEFAULT_MAX_VIEW_DISTANCE`].
+pub(crate) fn max_distance_from_env() -> u32 {
+    match env::var("VOLTZ_MAX_VIEW_DISTANCE") {
+        Ok(value) => match value.parse() {
+            Ok(distance) if distance >= MIN_VIEW_DISTANCE => distance,
+            _ => {
+                log::warn!("Ignoring invalid VOLTZ_MAX_VIEW_DISTANCE '{}'", value);
+                DEFAULT_MAX_VIEW_DISTANCE
+            }
+        },
+        Err(_) => DEFAULT_MAX_VIEW_DISTANCE,
+    }
+}
+
+/// The view distance a player's client asked for, before clamping.
+/// Kept so the distance can be restored after being
+/// [limited](crate::view_scaling) under load.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct RequestedViewDistance(pub u32);
+
+/// Clamps a view distance asked for by a client to the allowed range.
+pub(crate) fn clamp_distance(game: &Game, requested: u32) -> u32 {
+    requested
+        .min(game.effective_max_view_distance())
+        .max(MIN_VIEW_DISTANCE)
+}
+
+/// Changes the view distance of `player` to `requested`, clamped.
+/// The chunks are loaded and unloaded to match when the
+/// view system next runs.
+pub(crate) fn set_distance(game: &Game, player: Entity, requested: u32) {
+    if let Ok(mut requested_distance) = game.ecs().get_mut::<RequestedViewDistance>(player) {
+        requested_distance.0 = requested;
+    }
+    apply_distance(game, player, requested);
+}
+
+/// Clamps the view distance of every player again, after the
+/// largest allowed distance changed.
+pub(crate) fn reclamp_distances(game: &Game) {
+    let mut players = Vec::new_in(game.bump());
+    players.extend(
+        game.ecs()
+            .query::<&RequestedViewDistance>()
+            .iter()
+            .map(|(player, requested)| (player, requested.0)),
+    );
+    for (player, requested) in players {
+        apply_distance(game, player, requested);
+    }
+}
+
+fn apply_distance(game: &Game, player: Entity, requested: u32) {
+    let distance = clamp_distance(game, requested);
+    let old_view = match game.ecs().get_mut::<View>(player) {
+        Ok(mut view) => {
+            let old_view = *view;
+            *view = View::new(old_view.center(), distance);
+            old_view
+        }
+        Err(_) => return,
+    };
+    if old_view.distance() != distance {
+        game.events().push(ViewDistanceChanged { player, old_view });
+    }
+}
+
 /// System to
 /// 1) update player's view when they move into a new chunk
 /// 2) send new chunks when the view changes
 /// 3) unload all chunks when the view changes
+/// 4) send chunks in players' views as they are generated
 #[derive(Default)]
 struct ViewSystem;
 
 impl System<Game> for ViewSystem {
     fn run(&mut self, game: &mut Game) {
+        send_distances(game);
         let players = update_views(game);
         for (player, _, _) in &players {
             let username = game.ecs().get::<Username>(*player).unwrap();
             log::debug!("Updating view for {}", username.0);
         }
         update_chunks(&players, game);
+        send_generated_columns(&players, game);
     }
 }
 
 type UpdatedView = (Entity, View, View);
 
+/// Tells players who joined or whose view distance changed their distance.
+fn send_distances(game: &Game) {
+    let mut players = Vec::new_in(game.bump());
+    {
+        let mut events = game.events();
+        players.extend(events.iter::<PlayerJoined>().map(|event| event.player));
+        players.extend(events.iter::<PlayerResumed>().map(|event| event.player));
+        players.extend(
+            events
+                .iter::<ViewDistanceChanged>()
+                .map(|event| event.player),
+        );
+    }
+    players.sort_unstable();
+    players.dedup();
+
+    for player in players {
+        if let (Ok(view), Ok(mailbox)) = (
+            game.ecs().get::<View>(player),
+            game.ecs().get::<Mailbox>(player),
+        ) {
+            mailbox.send(ServerPacket::ViewDistanceChanged(
+                server::ViewDistanceChanged {
+                    view_distance: view.distance(),
+                },
+            ));
+        }
+    }
+}
+
 fn update_views<'g>(game: &'g Game) -> Vec<UpdatedView, &'g Bump> {
     let mut updated = Vec::new_in(game.bump());
 
+    // Views whose distance changed, as they were before.
+    let mut resized = Vec::new_in(game.bump());
+    resized.extend(
+        game.events()
+            .iter::<ViewDistanceChanged>()
+            .map(|event| (event.player, event.old_view)),
+    );
+
     for (player, (&pos, view)) in game.ecs().query::<(&Pos, &mut View)>().iter() {
-        let chunk = ChunkPos::from_pos(pos);
-        if chunk != view.center() {
+        // Views are made of main-zone chunks.
+        let chunk = ChunkPos::from_pos(game.world().to_main(pos.0));
+        let resized_from = resized
+            .iter()
+            .find(|(resized, _)| *resized == player)
+            .map(|&(_, old_view)| old_view);
+        if chunk != view.center() || resized_from.is_some() {
             // View should be updated.
-            let old_view = *view;
+            let old_view = resized_from.unwrap_or(*view);
             *view = View::new(chunk, view.distance());
             updated.push((player, old_view, *view));
         }
     }
 
     // Process newly joined players, whose views also need updating.
-    for event in game.events().iter::<PlayerJoined>() {
-        let player = event.player;
-        if let Ok(view) = game.ecs().get::<View>(player) {
+    // Resumed players may have missed chunks, so they get all of theirs again.
+    let mut joined = Vec::new_in(game.bump());
+    {
+        let mut events = game.events();
+        joined.extend(events.iter::<PlayerJoined>().map(|event| event.player));
+        joined.extend(events.iter::<PlayerResumed>().map(|event| event.player));
+    }
+    for player in joined {
+        if let Some(update) = updated
+            .iter_mut()
+            .find(|(updated, _, _)| *updated == player)
+        {
+            update.1 = View::empty();
+        } else if let Ok(view) = game.ecs().get::<View>(player) {
             updated.push((player, View::empty(), *view));
         }
     }
@@ -78,29 +240,161 @@ fn update_chunks(players: &[UpdatedView], game: &Game) {
         let mailbox = game.ecs().get::<Mailbox>(player).unwrap();
         let username = game.ecs().get::<Username>(player).unwrap();
 
-        let mut loaded = 0;
+        let mut loaded = HashSet::new_in(game.bump());
         for chunk_to_load in chunks_to_load {
-            if let Some(chunk) = game.main_zone().chunk(chunk_to_load) {
-                let packet = ServerPacket::LoadChunk(LoadChunk {
-                    pos: chunk_to_load,
-                    chunk: chunk.clone(),
-                });
-                log::trace!("Loading {:?} for {}", chunk_to_load, username.0);
-                mailbox.send(packet);
-                loaded += 1;
+            if game.is_column_generated(ColumnPos::from_chunk(chunk_to_load)) {
+                send_chunk(game, player, chunk_to_load);
+                loaded.insert(chunk_to_load);
             }
         }
-        log::debug!("Sent {} chunks to {}", loaded, username.0);
+        log::debug!("Sent {} chunks to {}", loaded.len(), username.0);
 
-        let mut unloaded = 0;
+        let mut unloaded = HashSet::new_in(game.bump());
         for &chunk_to_unload in old_chunks.difference(&new_chunks) {
             let packet = ServerPacket::UnloadChunk(UnloadChunk {
                 pos: chunk_to_unload,
             });
             log::trace!("Unloading {:?} for {}", chunk_to_unload, username.0);
             mailbox.send(packet);
-            unloaded += 1;
+            unloaded.insert(chunk_to_unload);
+        }
+        log::debug!("Unloaded {} chunks for {}", unloaded.len(), username.0);
+
+        update_entities(game, &mailbox, &loaded, &unloaded);
+    }
+}
+
+/// Sends a player the item drops and experience orbs in the chunks
+/// `loaded` into their view, and despawns those in the chunks `unloaded`.
+fn update_entities(
+    game: &Game,
+    mailbox: &Mailbox,
+    loaded: &HashSet<ChunkPos, DefaultHashBuilder, &Bump>,
+    unloaded: &HashSet<ChunkPos, DefaultHashBuilder, &Bump>,
+) {
+    let mut entities = Vec::new_in(game.bump());
+    entities.extend(
+        game.ecs()
+            .query::<(&Pos, &ItemDrop)>()
+            .iter()
+            .map(|(entity, (pos, drop))| (entity, pos.0, EntityKind::ItemDrop { stack: drop.0 })),
+    );
+    entities.extend(
+        game.ecs()
+            .query::<(&Pos, &XpOrb)>()
+            .iter()
+            .map(|(entity, (pos, orb))| (entity, pos.0, EntityKind::XpOrb { value: orb.value })),
+    );
+
+    for (entity, pos, kind) in entities {
+        let chunk = ChunkPos::from_pos(game.world().to_main(pos));
+        if loaded.contains(&chunk) {
+            mailbox.send(ServerPacket::SpawnEntity(SpawnEntity {
+                entity: entity.to_bits(),
+                pos,
+                orient: Vec2::zero(),
+                kind,
+            }));
+        } else if unloaded.contains(&chunk) {
+            mailbox.send(ServerPacket::DespawnEntity(DespawnEntity {
+                entity: entity.to_bits(),
+            }));
+        }
+    }
+}
+
+/// Sends newly generated columns to players who can see them.
+fn send_generated_columns(updated: &[UpdatedView], game: &Game) {
+    let mut generated = Vec::new_in(game.bump());
+    generated.extend(
+        game.events()
+            .iter::<ColumnGenerated>()
+            .map(|event| event.pos),
+    );
+    if generated.is_empty() {
+        return;
+    }
+
+    for (player, view) in game.ecs().query::<&View>().iter() {
+        // Players whose view changed this tick were already sent
+        // the generated chunks that entered their view.
+        let old_view = updated
+            .iter()
+            .find(|&&(updated, _, _)| updated == player)
+            .map(|&(_, old_view, _)| old_view);
+
+        for &column in &generated {
+            for y in view.min_y()..=view.max_y() {
+                let pos = column.chunk(y);
+                let already_sent = old_view.map_or(false, |old_view| !old_view.contains(pos));
+                if view.contains(pos) && !already_sent {
+                    send_chunk(game, player, pos);
+                }
+            }
         }
-        log::debug!("Unloaded {} chunks for {}", unloaded, username.0);
     }
 }
+
+/// Sends the chunks a player asked for with `RequestChunks`,
+/// skipping those no longer in their view.
+pub(crate) fn send_requested_chunks(game: &Game, player: Entity, positions: &[ChunkPos]) {
+    let view = match game.ecs().get::<View>(player) {
+        Ok(view) => *view,
+        Err(_) => return,
+    };
+    for &pos in positions {
+        if view.contains(pos) && game.is_column_generated(ColumnPos::from_chunk(pos)) {
+            send_full_chunk(game, player, pos);
+        }
+    }
+}
+
+/// Sends a chunk, or only its hash if the player caches chunks.
+fn send_chunk(game: &Game, player: Entity, pos: ChunkPos) {
+    let features = *game.ecs().get::<Features>(player).unwrap();
+    if !features.contains(Features::CHUNK_CACHE) {
+        send_full_chunk(game, player, pos);
+        return;
+    }
+    let zone = game.main_zone();
+    let (chunk, biomes) = match (zone.chunk(pos), zone.column_biomes(pos.x, pos.z)) {
+        (Some(chunk), Some(biomes)) => (chunk, biomes),
+        _ => return,
+    };
+    let mailbox = game.ecs().get::<Mailbox>(player).unwrap();
+    let hash = chunk_cache::chunk_hash(chunk);
+    mailbox.send(ServerPacket::LoadCachedChunk(LoadCachedChunk {
+        pos,
+        hash,
+        biomes,
+    }));
+}
+
+fn send_full_chunk(game: &Game, player: Entity, pos: ChunkPos) {
+    let zone = game.main_zone();
+    let (chunk, biomes) = match (zone.chunk(pos), zone.column_biomes(pos.x, pos.z)) {
+        (Some(chunk), Some(biomes)) => (chunk, biomes),
+        _ => return,
+    };
+    let mailbox = game.ecs().get::<Mailbox>(player).unwrap();
+    let username = game.ecs().get::<Username>(player).unwrap();
+    // Only present if the player's registry matches ours.
+    let dictionary = game.ecs().get::<BlockDictionary>(player).ok();
+
+    let mut data = match &dictionary {
+        Some(dictionary) => dictionary.encode(chunk.clone()),
+        None => ChunkData::Full(chunk.clone()),
+    };
+    let features = *game.ecs().get::<Features>(player).unwrap();
+    if features.contains(Features::COMPRESSED_CHUNKS) {
+        data = data.compress();
+    }
+
+    let packet = ServerPacket::LoadChunk(LoadChunk {
+        pos,
+        chunk: data,
+        biomes,
+    });
+    log::trace!("Loading {:?} for {}", pos, username.0);
+    mailbox.send(packet);
+}
@@ -0,0 +1,193 @@
+//! Scaling view distances with the server's load.
+//!
+//! Sending and tracking chunks is much of the work of a busy server, so
+//! when ticks run long, [`ViewScalingSystem`] lowers the largest view
+//! distance players get one chunk at a time until they fit in the tick
+//! length again, down to [`MIN_VIEW_DISTANCE`]. Once the load drops, the
+//! limit is raised one chunk at a time back to the maximum. Players keep
+//! the distance they asked for and get it back as the limit is raised;
+//! they are told each change with `ViewDistanceChanged`.
+//!
+//! The load is the time a tick takes as a fraction of the real time
+//! between ticks, averaged over roughly a second of ticks. Setting
+//! `VOLTZ_NO_VIEW_SCALING` keeps the view distance fixed.
+
+use std::env;
+
+use common::{System, SystemExecutor};
+
+use crate::{game::Game, view, MIN_VIEW_DISTANCE};
+
+/// The load above which the view distance is lowered.
+const HIGH_LOAD: f32 = 0.8;
+/// The load below which the view distance is raised again.
+const LOW_LOAD: f32 = 0.4;
+/// How much each tick's load counts towards the average.
+const SMOOTHING: f32 = 0.05;
+/// The seconds to wait after a change before lowering the view distance
+/// again, giving players' views time to shrink and the load to settle.
+const LOWER_DELAY_SECS: u32 = 5;
+/// The seconds to wait after a change before raising the view distance
+/// again. Longer than [`LOWER_DELAY_SECS`] so a load that hovers around
+/// the threshold doesn't make views grow and shrink constantly.
+const RAISE_DELAY_SECS: u32 = 15;
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    if env::var("VOLTZ_NO_VIEW_SCALING").is_ok() {
+        log::info!("View distance scaling is disabled");
+        return;
+    }
+    systems.add(ViewScalingSystem {
+        monitor: LoadMonitor::default(),
+    });
+}
+
+/// A change to the view distance limit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Adjustment {
+    Lower,
+    Raise,
+}
+
+/// Averages the load of ticks and decides when to change the limit.
+#[derive(Default)]
+struct LoadMonitor {
+    /// The average load of recent ticks.
+    average: f32,
+    /// The number of ticks since the limit last changed.
+    ticks_since_change: u32,
+}
+
+impl LoadMonitor {
+    /// Records the load of a tick and returns the adjustment to
+    /// make, if any. `tps` converts the delays to ticks.
+    fn update(
+        &mut self,
+        load: f32,
+        tps: u32,
+        can_lower: bool,
+        can_raise: bool,
+    ) -> Option<Adjustment> {
+        self.average += (load - self.average) * SMOOTHING;
+        self.ticks_since_change = self.ticks_since_change.saturating_add(1);
+
+        let adjustment = if can_lower
+            && self.average > HIGH_LOAD
+            && self.ticks_since_change >= LOWER_DELAY_SECS * tps
+        {
+            Adjustment::Lower
+        } else if can_raise
+            && self.average < LOW_LOAD
+            && self.ticks_since_change >= RAISE_DELAY_SECS * tps
+        {
+            Adjustment::Raise
+        } else {
+            return None;
+        };
+        self.ticks_since_change = 0;
+        Some(adjustment)
+    }
+}
+
+/// System to lower the view distance limit while ticks run
+/// long, and to raise it again once the load drops.
+struct ViewScalingSystem {
+    monitor: LoadMonitor,
+}
+
+impl System<Game> for ViewScalingSystem {
+    fn run(&mut self, game: &mut Game) {
+        let load = game.last_tick_duration().as_secs_f32() / game.real_tick_length().as_secs_f32();
+        let distance = game.effective_max_view_distance();
+        let max = game.max_view_distance();
+        let adjustment = self.monitor.update(
+            load,
+            game.tps(),
+            distance > MIN_VIEW_DISTANCE,
+            distance < max,
+        );
+
+        let limit = match adjustment {
+            Some(Adjustment::Lower) => {
+                log::warn!(
+                    "Ticks are running long; lowering the view distance to {} chunks",
+                    distance - 1
+                );
+                Some(distance - 1)
+            }
+            Some(Adjustment::Raise) if distance + 1 >= max => {
+                log::info!(
+                    "The load dropped; restoring the view distance to {} chunks",
+                    max
+                );
+                None
+            }
+            Some(Adjustment::Raise) => {
+                log::info!(
+                    "The load dropped; raising the view distance to {} chunks",
+                    distance + 1
+                );
+                Some(distance + 1)
+            }
+            None => return,
+        };
+        game.set_view_distance_limit(limit);
+        view::reclamp_distances(game);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TPS: u32 = 20;
+
+    /// Runs ticks with the given load until the monitor adjusts
+    /// the limit, returning the adjustment and the number of ticks.
+    fn run_until_adjusted(monitor: &mut LoadMonitor, load: f32) -> (Adjustment, u32) {
+        for tick in 1..=1000 {
+            if let Some(adjustment) = monitor.update(load, TPS, true, true) {
+                return (adjustment, tick);
+            }
+        }
+        panic!("no adjustment at load {}", load);
+    }
+
+    #[test]
+    fn lowers_under_load_and_raises_after_it_drops() {
+        let mut monitor = LoadMonitor::default();
+        let (adjustment, ticks) = run_until_adjusted(&mut monitor, 1.5);
+        assert_eq!(adjustment, Adjustment::Lower);
+        assert_eq!(ticks, LOWER_DELAY_SECS * TPS);
+
+        // Lowering again waits for the delay.
+        let (adjustment, ticks) = run_until_adjusted(&mut monitor, 1.5);
+        assert_eq!(adjustment, Adjustment::Lower);
+        assert_eq!(ticks, LOWER_DELAY_SECS * TPS);
+
+        let (adjustment, ticks) = run_until_adjusted(&mut monitor, 0.1);
+        assert_eq!(adjustment, Adjustment::Raise);
+        assert_eq!(ticks, RAISE_DELAY_SECS * TPS);
+    }
+
+    #[test]
+    fn ignores_short_spikes() {
+        let mut monitor = LoadMonitor::default();
+        for tick in 0..LOWER_DELAY_SECS * TPS * 2 {
+            let load = if tick % 20 == 0 { 3. } else { 0.5 };
+            assert_eq!(monitor.update(load, TPS, true, false), None);
+        }
+    }
+
+    #[test]
+    fn respects_bounds() {
+        let mut monitor = LoadMonitor::default();
+        for _ in 0..RAISE_DELAY_SECS * TPS * 2 {
+            assert_eq!(monitor.update(2., TPS, false, true), None);
+        }
+        let mut monitor = LoadMonitor::default();
+        for _ in 0..RAISE_DELAY_SECS * TPS * 2 {
+            assert_eq!(monitor.update(0., TPS, true, false), None);
+        }
+    }
+}
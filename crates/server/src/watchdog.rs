@@ -0,0 +1,292 @@
+//! Detecting stalled ticks.
+//!
+//! A tick that never finishes, because of a deadlock or a GPU wait that
+//! doesn't return, leaves a dedicated server running but silent. The
+//! watchdog thread follows the progress of each tick, and if one runs
+//! longer than `VOLTZ_WATCHDOG_SECS` seconds (10 by default, 0 disables
+//! the watchdog), it logs the tick, the system running it and the state
+//! of every thread of the process.
+//!
+//! Rust can't capture the backtrace of another thread, so the thread dump
+//! only shows what each thread is waiting on, as reported by the kernel.
+//! If `VOLTZ_WATCHDOG_ABORT` is set, the watchdog aborts the process after
+//! the dump, so that a core dump or an attached debugger has the full
+//! backtraces.
+//!
+//! The world can't be saved while the stalled tick holds it. If
+//! `VOLTZ_WATCHDOG_SAVE` is set, the world is saved as soon as the
+//! stalled tick finishes, in case the server is about to hang again.
+
+use std::{
+    env,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use flume::{RecvTimeoutError, Sender};
+
+/// How long a tick may run unless `VOLTZ_WATCHDOG_SECS` is set.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+/// The longest the watchdog sleeps between checks.
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WatchdogSettings {
+    /// How long a tick may run before it is reported.
+    pub timeout: Duration,
+    /// Whether to abort the process after reporting a stall.
+    pub abort: bool,
+    /// Whether to save the world once a stalled tick finishes.
+    pub save: bool,
+}
+
+impl Default for WatchdogSettings {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            abort: false,
+            save: false,
+        }
+    }
+}
+
+impl WatchdogSettings {
+    /// Reads the settings from the environment. Returns
+    /// `None` if the watchdog is disabled.
+    pub fn from_env() -> Option<Self> {
+        let mut settings = Self::default();
+        if let Ok(secs) = env::var("VOLTZ_WATCHDOG_SECS") {
+            match secs.parse::<u64>() {
+                Ok(0) => return None,
+                Ok(secs) => settings.timeout = Duration::from_secs(secs),
+                Err(_) => log::warn!("Ignoring invalid VOLTZ_WATCHDOG_SECS '{}'", secs),
+            }
+        }
+        settings.abort = env::var("VOLTZ_WATCHDOG_ABORT").is_ok();
+        settings.save = env::var("VOLTZ_WATCHDOG_SAVE").is_ok();
+        Some(settings)
+    }
+}
+
+/// The progress of the current tick, shared with the watchdog thread.
+#[derive(Debug, Default)]
+struct Progress {
+    tick: u64,
+    /// When the current tick started, or `None` between ticks.
+    started: Option<Instant>,
+    /// The index of the running system. 0 is connection handling;
+    /// `i + 1` is the `i`th system added in `setup`.
+    system: usize,
+    /// Whether the current tick has been reported as stalled.
+    reported: bool,
+    /// Whether a stalled tick finished since the last call
+    /// to [`Watchdog::take_recovered`].
+    recovered: bool,
+}
+
+/// Handle to the watchdog thread, which stops when this is dropped.
+pub struct Watchdog {
+    settings: WatchdogSettings,
+    progress: Arc<Mutex<Progress>>,
+    /// Dropped to stop the thread.
+    _stop: Sender<()>,
+}
+
+impl Watchdog {
+    /// Starts the watchdog thread. `system_names` are the
+    /// names of the systems in the order they run.
+    pub fn start(settings: WatchdogSettings, system_names: Vec<&'static str>) -> Self {
+        let progress = Arc::new(Mutex::new(Progress::default()));
+        let (stop, stopped) = flume::bounded(0);
+        let thread_progress = Arc::clone(&progress);
+        thread::Builder::new()
+            .name("watchdog".to_owned())
+            .spawn(move || {
+                let interval = (settings.timeout / 4).min(MAX_CHECK_INTERVAL);
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    check(&settings, &thread_progress, &system_names);
+                }
+            })
+            .expect("failed to spawn the watchdog thread");
+        log::info!(
+            "Watchdog enabled; ticks longer than {:?} will be reported",
+            settings.timeout
+        );
+        Self {
+            settings,
+            progress,
+            _stop: stop,
+        }
+    }
+
+    pub fn settings(&self) -> WatchdogSettings {
+        self.settings
+    }
+
+    /// Called when a tick starts.
+    pub fn begin_tick(&self, tick: u64) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.tick = tick;
+        progress.started = Some(Instant::now());
+        progress.system = 0;
+        progress.reported = false;
+    }
+
+    /// Called before system `system` runs, numbered like in [`Progress`].
+    pub fn begin_system(&self, system: usize) {
+        self.progress.lock().unwrap().system = system;
+    }
+
+    /// Called when a tick finishes.
+    pub fn end_tick(&self) {
+        let mut progress = self.progress.lock().unwrap();
+        if progress.reported {
+            log::warn!(
+                "Stalled tick {} finished after {:?}",
+                progress.tick,
+                progress
+                    .started
+                    .map(|started| started.elapsed())
+                    .unwrap_or_default()
+            );
+            progress.recovered = true;
+        }
+        progress.started = None;
+    }
+
+    /// Returns whether a stalled tick has finished
+    /// since the last call, then resets the flag.
+    pub fn take_recovered(&self) -> bool {
+        let mut progress = self.progress.lock().unwrap();
+        let recovered = progress.recovered;
+        progress.recovered = false;
+        recovered
+    }
+}
+
+/// Reports the current tick if it has stalled.
+fn check(settings: &WatchdogSettings, progress: &Mutex<Progress>, system_names: &[&str]) {
+    let mut progress = progress.lock().unwrap();
+    let elapsed = match progress.started {
+        Some(started) => started.elapsed(),
+        None => return,
+    };
+    if progress.reported || elapsed < settings.timeout {
+        return;
+    }
+    progress.reported = true;
+
+    log::error!(
+        "Tick {} has been running for {:?} and is stuck in {}",
+        progress.tick,
+        elapsed,
+        system_name(system_names, progress.system)
+    );
+    log::error!("Threads:\n{}", thread_dump());
+    if settings.abort {
+        log::error!("Aborting because VOLTZ_WATCHDOG_ABORT is set");
+        log::logger().flush();
+        std::process::abort();
+    }
+    if settings.save {
+        log::error!("The world will be saved if the tick finishes");
+    }
+}
+
+/// Names a system, numbered like in [`Progress`].
+fn system_name(system_names: &[&str], system: usize) -> String {
+    match system.checked_sub(1) {
+        None => "connection handling".to_owned(),
+        Some(i) => match system_names.get(i) {
+            Some(name) => (*name).to_owned(),
+            None => format!("system {}", i),
+        },
+    }
+}
+
+/// Describes every thread of the process: its name, its state
+/// and the kernel function it is waiting in, if any.
+#[cfg(target_os = "linux")]
+fn thread_dump() -> String {
+    use std::fs;
+
+    let tasks = match fs::read_dir("/proc/self/task") {
+        Ok(tasks) => tasks,
+        Err(e) => return format!("failed to list threads: {}", e),
+    };
+    let mut lines = Vec::new();
+    for task in tasks.flatten() {
+        let dir = task.path();
+        let read = |file: &str| {
+            fs::read_to_string(dir.join(file))
+                .map(|contents| contents.trim().to_owned())
+                .unwrap_or_default()
+        };
+        let name = read("comm");
+        // The state follows the parenthesized name, which may contain spaces.
+        let stat = read("stat");
+        let state = stat
+            .rfind(')')
+            .and_then(|end| stat[end + 1..].split_whitespace().next())
+            .unwrap_or("?")
+            .to_owned();
+        let wchan = read("wchan");
+        lines.push(format!(
+            "  {} '{}' state {} waiting in {}",
+            task.file_name().to_string_lossy(),
+            name,
+            state,
+            if wchan.is_empty() || wchan == "0" {
+                "nothing"
+            } else {
+                wchan.as_str()
+            }
+        ));
+    }
+    lines.sort();
+    lines.join("\n")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_dump() -> String {
+    "  thread dumps are only supported on Linux".to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_systems() {
+        let names = ["generation", "view"];
+        assert_eq!(system_name(&names, 0), "connection handling");
+        assert_eq!(system_name(&names, 2), "view");
+        assert_eq!(system_name(&names, 5), "system 4");
+    }
+
+    #[test]
+    fn reports_stalled_ticks_once() {
+        let settings = WatchdogSettings {
+            timeout: Duration::from_millis(0),
+            ..Default::default()
+        };
+        let progress = Mutex::new(Progress::default());
+        check(&settings, &progress, &[]);
+        assert!(!progress.lock().unwrap().reported);
+
+        progress.lock().unwrap().started = Some(Instant::now());
+        check(&settings, &progress, &[]);
+        assert!(progress.lock().unwrap().reported);
+    }
+
+    #[test]
+    fn recovers_after_stalled_ticks() {
+        let watchdog = Watchdog::start(WatchdogSettings::default(), Vec::new());
+        watchdog.begin_tick(1);
+        watchdog.progress.lock().unwrap().reported = true;
+        watchdog.end_tick();
+        assert!(watchdog.take_recovered());
+        assert!(!watchdog.take_recovered());
+    }
+}
@@ -0,0 +1,167 @@
+//! The weather.
+//!
+//! [`WeatherSystem`] moves the weather between clear skies, rain,
+//! and thunder, with each lasting a random duration. The cycle pauses
+//! while the `doWeatherCycle` [game rule](GameRule) is off. Players learn
+//! the weather through the `WeatherChange` packet.
+//!
+//! While it snows, layers of [`Snow`] slowly accumulate on
+//! exposed surfaces.
+
+use common::{
+    blocks::{Snow, Water},
+    chunk::CHUNK_DIM,
+    game_rules::GameRule,
+    weather::{self, Precipitation, Weather},
+    BlockId, BlockPos, System, SystemExecutor,
+};
+use protocol::packets::{server::WeatherChange, ServerPacket};
+use rand::Rng;
+use worldgen::ColumnPos;
+
+use crate::{event::PlayerJoined, game::Game, Mailbox, WORLD_SIZE};
+
+/// The number of random columns checked for snow accumulation
+/// each tick while it snows. Snow covers the world gradually, so
+/// this is low.
+const SNOW_ATTEMPTS_PER_TICK: u32 = 16;
+
+pub fn setup(systems: &mut SystemExecutor<Game>, game: &Game) {
+    let first_change = duration(Weather::Clear, game.tps(), &mut *game.rng());
+    systems.add(WeatherSystem {
+        next_change: game.tick() + first_change,
+        announced: game.weather(),
+    });
+}
+
+/// System to change the weather, tell players about it,
+/// and accumulate snow.
+struct WeatherSystem {
+    /// The tick at which the weather next changes.
+    next_change: u64,
+    /// The weather players were last told about.
+    announced: Weather,
+}
+
+impl System<Game> for WeatherSystem {
+    fn run(&mut self, game: &mut Game) {
+        if !game.rules().get_bool(GameRule::DoWeatherCycle) {
+            // Pause the current weather's remaining duration.
+            self.next_change += 1;
+        } else if game.tick() >= self.next_change {
+            let weather = next_weather(game.weather(), &mut *game.rng());
+            self.next_change = game.tick() + duration(weather, game.tps(), &mut *game.rng());
+            game.set_weather(weather);
+        }
+
+        // The weather may also have been set by something else.
+        if game.weather() != self.announced {
+            self.announced = game.weather();
+            log::info!("Weather changed to {:?}", self.announced);
+            for (_, mailbox) in game.ecs().query::<&Mailbox>().iter() {
+                send_weather(mailbox, self.announced);
+            }
+        }
+        for event in game.events().iter::<PlayerJoined>() {
+            if let Ok(mailbox) = game.ecs().get::<Mailbox>(event.player) {
+                send_weather(&mailbox, self.announced);
+            }
+        }
+
+        accumulate_snow(game);
+    }
+}
+
+fn send_weather(mailbox: &Mailbox, weather: Weather) {
+    mailbox.send(ServerPacket::WeatherChange(WeatherChange { weather }));
+}
+
+/// Picks the weather following `current`.
+fn next_weather(current: Weather, rng: &mut impl Rng) -> Weather {
+    match current {
+        Weather::Clear if rng.gen_bool(0.2) => Weather::Thunder,
+        Weather::Clear => Weather::Rain,
+        Weather::Rain if rng.gen_bool(0.3) => Weather::Thunder,
+        Weather::Rain => Weather::Clear,
+        // Storms calm down to rain before clearing up.
+        Weather::Thunder => Weather::Rain,
+    }
+}
+
+/// Picks how many ticks `weather` lasts at `tps` ticks per second.
+fn duration(weather: Weather, tps: u32, rng: &mut impl Rng) -> u64 {
+    let (min_minutes, max_minutes) = match weather {
+        Weather::Clear => (5, 15),
+        Weather::Rain => (3, 8),
+        Weather::Thunder => (2, 5),
+    };
+    let ticks_per_minute = 60 * tps as u64;
+    rng.gen_range(
+        min_minutes * ticks_per_minute,
+        max_minutes * ticks_per_minute,
+    )
+}
+
+/// Places snow on top of random generated columns where it snows.
+fn accumulate_snow(game: &mut Game) {
+    if game.weather() == Weather::Clear {
+        return;
+    }
+
+    let size = WORLD_SIZE * CHUNK_DIM as i32;
+    for _ in 0..SNOW_ATTEMPTS_PER_TICK {
+        let (x, z) = {
+            let mut rng = game.rng();
+            (rng.gen_range(0, size), rng.gen_range(0, size))
+        };
+        let column = ColumnPos::from_chunk(BlockPos { x, y: 0, z }.chunk());
+        if !game.is_column_generated(column) {
+            continue;
+        }
+
+        let (surface_pos, surface) = match game.main_zone().column_summary(x, z) {
+            Some(summary) => (
+                BlockPos {
+                    x,
+                    y: summary.height,
+                    z,
+                },
+                summary.top,
+            ),
+            None => continue,
+        };
+        let covered = surface.is_solid() && !surface.is::<Water>();
+        if covered
+            && weather::precipitation(
+                game.weather(),
+                game.main_zone().biome_at(surface_pos),
+                surface_pos.y,
+            ) == Precipitation::Snow
+        {
+            game.set_block(surface_pos.offset(0, 1, 0), BlockId::new(Snow))
+                .ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_pcg::Pcg64Mcg;
+
+    use super::*;
+    use crate::TPS;
+
+    #[test]
+    fn weather_always_changes() {
+        let mut rng = Pcg64Mcg::seed_from_u64(0);
+        let mut weather = Weather::Clear;
+        for _ in 0..100 {
+            let next = next_weather(weather, &mut rng);
+            assert_ne!(next, weather);
+            let ticks = duration(next, TPS, &mut rng);
+            assert!(ticks >= 2 * 60 * TPS as u64 && ticks < 15 * 60 * TPS as u64);
+            weather = next;
+        }
+    }
+}
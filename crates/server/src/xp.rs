@@ -0,0 +1,245 @@
+//! Experience orbs.
+//!
+//! Mining Melium drops an [`XpOrb`] entity worth a few experience
+//! points, multiplied by the [resource multiplier](Game::resource_multiplier).
+//! Orbs fall like other entities and fly toward the nearest player within
+//! [`ATTRACTION_RADIUS`]. A player who touches an orb collects it, adding
+//! its value to their [`Experience`]. Uncollected orbs disappear after
+//! [`ORB_LIFETIME`] seconds.
+//!
+//! Players see orbs through the `SpawnEntity`, `MoveEntity`, and
+//! `DespawnEntity` packets, and their own total through `UpdateXp`.
+//! Orbs already on the ground are sent with their chunk when it enters
+//! a player's [view](crate::view).
+
+use common::{
+    blocks::{Air, Melium},
+    entity::{
+        player::{Experience, Username},
+        Vel, XpOrb,
+    },
+    game_rules::GameRule,
+    BlockPos, Pos, System, SystemExecutor, WorldPos,
+};
+use glam::{vec3a, Vec2, Vec3A};
+use hecs::Entity;
+use physics::Aabb;
+use protocol::packets::{
+    server::{DespawnEntity, EntityKind, MoveEntity, SpawnEntity, UpdateXp},
+    ServerPacket,
+};
+use rand::Rng;
+
+use crate::{
+    event::{BlockChanged, PlayerJoined},
+    falling::send_to_viewers,
+    game::Game,
+    Mailbox,
+};
+
+/// The bounding box of an orb.
+const ORB_BOUNDS: Aabb = Aabb {
+    min: Vec3A::zero(),
+    max: glam::const_vec3a!([0.25, 0.25, 0.25]),
+};
+/// The offset from a player's position to the center of their body.
+const PLAYER_CENTER: Vec3A = glam::const_vec3a!([0.25, 0.9, 0.25]);
+
+/// The highest value of an orb dropped by Melium,
+/// before the resource multiplier.
+const MAX_MELIUM_XP: u32 = 3;
+/// Orbs fly toward players within this distance.
+const ATTRACTION_RADIUS: f32 = 6.;
+/// The speed in blocks per second at which orbs fly toward players.
+const ATTRACTION_SPEED: f32 = 8.;
+/// Players collect orbs within this distance.
+const PICKUP_RADIUS: f32 = 1.;
+/// The number of seconds until an uncollected orb disappears.
+const ORB_LIFETIME: u64 = 5 * 60;
+
+pub fn setup(systems: &mut SystemExecutor<Game>) {
+    systems.add(XpSystem);
+}
+
+/// The tick at which an orb disappears.
+#[derive(Copy, Clone, Debug)]
+struct Expiry(u64);
+
+/// System to drop, move, and collect experience orbs.
+struct XpSystem;
+
+impl System<Game> for XpSystem {
+    fn run(&mut self, game: &mut Game) {
+        for event in game.events().iter::<PlayerJoined>() {
+            send_xp(game, event.player);
+        }
+
+        drop_orbs(game);
+        let collected = move_orbs(game);
+        for (orb, player) in collected {
+            collect(game, orb, player);
+        }
+        remove_expired(game);
+        send_positions(game);
+    }
+}
+
+/// Drops orbs where Melium was mined, unless
+/// the `doXpDrops` game rule is off.
+fn drop_orbs(game: &mut Game) {
+    if !game.rules().get_bool(GameRule::DoXpDrops) {
+        return;
+    }
+    let mined: Vec<BlockPos> = game
+        .events()
+        .iter::<BlockChanged>()
+        .filter(|change| change.old.is::<Melium>() && change.new.is::<Air>())
+        .map(|change| change.pos)
+        .collect();
+
+    for pos in mined {
+        let (value, vel) = {
+            let mut rng = game.rng();
+            let value = rng.gen_range(1, MAX_MELIUM_XP + 1) * game.resource_multiplier();
+            let vel = vec3a(rng.gen_range(-1., 1.), 4., rng.gen_range(-1., 1.));
+            (value, vel)
+        };
+        let orb_pos = WorldPos::main(vec3a(
+            pos.x as f32 + 0.375,
+            pos.y as f32,
+            pos.z as f32 + 0.375,
+        ));
+        let expiry = Expiry(game.tick() + ORB_LIFETIME * game.tps() as u64);
+        let orb =
+            game.ecs_mut()
+                .spawn((Pos(orb_pos), Vel(vel), ORB_BOUNDS, XpOrb { value }, expiry));
+        send_to_viewers(game, orb_pos, || {
+            ServerPacket::SpawnEntity(SpawnEntity {
+                entity: orb.to_bits(),
+                pos: orb_pos,
+                orient: Vec2::zero(),
+                kind: EntityKind::XpOrb { value },
+            })
+        });
+    }
+}
+
+/// Moves orbs by one tick, returning those
+/// collected and the players who collected them.
+fn move_orbs(game: &Game) -> Vec<(Entity, Entity)> {
+    let dt = game.tick_length().as_secs_f32();
+    // Orbs are only dropped in the main zone, so their
+    // local positions are in main-zone space.
+    let zone = game.main_zone();
+    let players: Vec<(Entity, Vec3A)> = game
+        .ecs()
+        .query::<(&Pos, &Username)>()
+        .iter()
+        .map(|(player, (pos, _))| (player, game.world().to_main(pos.0) + PLAYER_CENTER))
+        .collect();
+    let mut collected = Vec::new();
+
+    let mut query = game.ecs().query::<(&mut Pos, &mut Vel, &Aabb, &XpOrb)>();
+    for (orb, (pos, vel, &bounds, _)) in query.iter() {
+        let center = pos.0.local + (bounds.min + bounds.max) / 2.;
+        if let Some((player, target, distance)) = nearest_player(center, &players) {
+            if distance <= PICKUP_RADIUS {
+                collected.push((orb, player));
+                continue;
+            }
+            vel.0 = (target - center).normalize() * ATTRACTION_SPEED;
+        }
+        physics::do_tick(bounds, &mut pos.0, &mut vel.0, dt, |block_pos| {
+            zone.block(block_pos)
+        });
+    }
+
+    collected
+}
+
+fn send_positions(game: &Game) {
+    for (orb, (pos, _)) in game.ecs().query::<(&Pos, &XpOrb)>().iter() {
+        send_to_viewers(game, pos.0, || {
+            ServerPacket::MoveEntity(MoveEntity {
+                entity: orb.to_bits(),
+                pos: pos.0,
+            })
+        });
+    }
+}
+
+/// Returns the player nearest to `pos` within [`ATTRACTION_RADIUS`],
+/// their center, and their distance, given the centers of all players.
+fn nearest_player(pos: Vec3A, players: &[(Entity, Vec3A)]) -> Option<(Entity, Vec3A, f32)> {
+    players
+        .iter()
+        .map(|&(player, center)| (player, center, center.distance(pos)))
+        .filter(|&(_, _, distance)| distance <= ATTRACTION_RADIUS)
+        .min_by(|(_, _, a), (_, _, b)| a.partial_cmp(b).unwrap())
+}
+
+/// Gives the value of an orb to a player and removes the orb.
+fn collect(game: &mut Game, orb: Entity, player: Entity) {
+    let value = match game.ecs().get::<XpOrb>(orb) {
+        Ok(orb) => orb.value,
+        Err(_) => return,
+    };
+    if let Ok(mut xp) = game.ecs().get_mut::<Experience>(player) {
+        xp.total = xp.total.saturating_add(value);
+    }
+    despawn(game, orb);
+    send_xp(game, player);
+}
+
+fn remove_expired(game: &mut Game) {
+    let tick = game.tick();
+    let expired: Vec<Entity> = game
+        .ecs()
+        .query::<&Expiry>()
+        .iter()
+        .filter(|(_, expiry)| expiry.0 <= tick)
+        .map(|(orb, _)| orb)
+        .collect();
+    for orb in expired {
+        despawn(game, orb);
+    }
+}
+
+fn despawn(game: &mut Game, orb: Entity) {
+    game.ecs_mut().despawn(orb).ok();
+    for (_, mailbox) in game.ecs().query::<&Mailbox>().iter() {
+        mailbox.send(ServerPacket::DespawnEntity(DespawnEntity {
+            entity: orb.to_bits(),
+        }));
+    }
+}
+
+/// Sends a player their total experience points.
+fn send_xp(game: &Game, player: Entity) {
+    let xp = match game.ecs().get::<Experience>(player) {
+        Ok(xp) => *xp,
+        Err(_) => return,
+    };
+    if let Ok(mailbox) = game.ecs().get::<Mailbox>(player) {
+        mailbox.send(ServerPacket::UpdateXp(UpdateXp { total: xp.total }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attracted_to_nearest_player_in_range() {
+        let mut world = hecs::World::new();
+        let near = world.spawn(());
+        let far = world.spawn(());
+        let players = [(far, vec3a(4., 0., 0.)), (near, vec3a(0., 2., 0.))];
+
+        assert_eq!(
+            nearest_player(Vec3A::zero(), &players),
+            Some((near, vec3a(0., 2., 0.), 2.))
+        );
+        assert_eq!(nearest_player(vec3a(20., 0., 0.), &players), None);
+    }
+}
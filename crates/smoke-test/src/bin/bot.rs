@@ -0,0 +1,282 @@
+//! Puts a server under load with scripted headless clients.
+//!
+//! # Usage
+//! `voltz-bot [--bots N] [--seconds S] [--cpu]`
+//!
+//! Starts a server and connects `N` bots (10 by default) to it, each
+//! over its own bridge. Bots log in, walk around randomly, and now and
+//! then place or break a block near them. The server ticks at its normal
+//! rate on the tool's thread for `S` seconds (60 by default), so the tick
+//! times reported show whether it keeps up. Pass `--cpu` to generate the
+//! world on the CPU.
+//!
+//! Every few seconds and once at the end, the tool prints aggregate
+//! statistics: ping round-trip times, the share of pings left unanswered
+//! for longer than the [keepalive timeout](keepalive::TIMEOUT), packet
+//! counts, and tick times.
+//!
+//! The server runs in-process because the singleplayer bridge is the
+//! only transport; the tool therefore lives here rather than next to
+//! the protocol's other code.
+
+use std::{
+    env, process, thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context};
+use common::{blocks, BlockId, BlockPos};
+use glam::vec3a;
+use protocol::{bridge, keepalive, packets::PacketKind};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+use server::{Backend, Connection, Server};
+use simple_logger::SimpleLogger;
+use smoke_test::HeadlessClient;
+
+const USAGE: &str = "usage: voltz-bot [--bots N] [--seconds S] [--cpu]";
+
+/// The time between reports.
+const REPORT_INTERVAL: Duration = Duration::from_secs(5);
+/// The chance each tick that a bot takes a step.
+const STEP_CHANCE: f64 = 0.5;
+/// The chance each tick that a bot edits a block.
+const EDIT_CHANCE: f64 = 0.01;
+
+struct Options {
+    bots: usize,
+    duration: Duration,
+    cpu: bool,
+}
+
+fn main() {
+    if let Err(e) = SimpleLogger::new()
+        .with_level(log::LevelFilter::Warn)
+        .init()
+    {
+        eprintln!("failed to initialize logging: {}", e);
+    }
+
+    let result = parse_options(env::args().skip(1)).and_then(run);
+    if let Err(e) = result {
+        eprintln!("error: {:#}", e);
+        process::exit(1);
+    }
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> anyhow::Result<Options> {
+    let mut options = Options {
+        bots: 10,
+        duration: Duration::from_secs(60),
+        cpu: false,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow!("missing value for {}\n{}", arg, USAGE))
+        };
+        match arg.as_str() {
+            "--bots" => options.bots = value()?.parse().context("invalid number of bots")?,
+            "--seconds" => {
+                options.duration =
+                    Duration::from_secs(value()?.parse().context("invalid number of seconds")?)
+            }
+            "--cpu" => options.cpu = true,
+            _ => bail!("unknown argument '{}'\n{}", arg, USAGE),
+        }
+    }
+    Ok(options)
+}
+
+/// A headless client and the state of its script.
+struct Bot {
+    client: HeadlessClient,
+    rng: Pcg64Mcg,
+    last_ping: Instant,
+    /// The error that stopped the bot, if any.
+    error: Option<anyhow::Error>,
+}
+
+impl Bot {
+    fn tick(&mut self) -> anyhow::Result<()> {
+        self.client.poll()?;
+        let pos = match self.client.pos() {
+            Some(pos) if self.client.disconnect_reason().is_none() => pos,
+            _ => return Ok(()),
+        };
+
+        if self.last_ping.elapsed() >= keepalive::PING_INTERVAL {
+            self.client.ping()?;
+            self.last_ping = Instant::now();
+        }
+
+        if self.rng.gen_bool(STEP_CHANCE) {
+            let step = vec3a(self.rng.gen_range(-1., 1.), 0., self.rng.gen_range(-1., 1.));
+            self.client.move_to(pos.offset(step))?;
+        }
+
+        if self.rng.gen_bool(EDIT_CHANCE) {
+            let block = pos.block();
+            let target = BlockPos {
+                x: block.x + self.rng.gen_range(-3, 4),
+                y: block.y + self.rng.gen_range(-2, 3),
+                z: block.z + self.rng.gen_range(-3, 4),
+            };
+            match self.client.block(target) {
+                Some(current) if current.is::<blocks::Air>() => self
+                    .client
+                    .place_block(target, BlockId::new(blocks::Stone))?,
+                Some(_) => self.client.break_block(target)?,
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Statistics gathered between two reports.
+#[derive(Default)]
+struct Period {
+    rtts: Vec<Duration>,
+    pings_lost: usize,
+    tick_times: Vec<Duration>,
+}
+
+fn run(options: Options) -> anyhow::Result<()> {
+    let backend = if options.cpu {
+        Backend::Cpu
+    } else {
+        Backend::detect()
+    };
+    println!("Starting the server...");
+    let mut server = Server::new(Vec::new(), backend, None);
+    // Bots edit blocks around spawn.
+    server.disable_spawn_protection();
+
+    let mut bots: Vec<Bot> = (0..options.bots)
+        .map(|index| {
+            let (client_bridge, server_bridge) = bridge::singleplayer();
+            server.add_client(Connection::new(server_bridge));
+            Bot {
+                client: HeadlessClient::connect(client_bridge, &format!("bot-{}", index)),
+                rng: Pcg64Mcg::seed_from_u64(index as u64),
+                last_ping: Instant::now(),
+                error: None,
+            }
+        })
+        .collect();
+    println!("Connected {} bots", bots.len());
+
+    let start = Instant::now();
+    let mut last_report = start;
+    let mut period = Period::default();
+    let mut total = Period::default();
+    while start.elapsed() < options.duration {
+        let tick_start = Instant::now();
+        server.tick();
+        period.tick_times.push(tick_start.elapsed());
+
+        for bot in bots.iter_mut().filter(|bot| bot.error.is_none()) {
+            if let Err(e) = bot.tick() {
+                log::error!("A bot stopped: {:#}", e);
+                bot.error = Some(e);
+            }
+            period.rtts.extend(bot.client.take_rtts());
+            period.pings_lost += bot.client.drop_unanswered_pings(keepalive::TIMEOUT);
+        }
+
+        if last_report.elapsed() >= REPORT_INTERVAL {
+            report(
+                &format!("{:>4}s", start.elapsed().as_secs()),
+                &bots,
+                &period,
+            );
+            total.rtts.append(&mut period.rtts);
+            total.pings_lost += period.pings_lost;
+            total.tick_times.append(&mut period.tick_times);
+            period = Period::default();
+            last_report = Instant::now();
+        }
+
+        let elapsed = tick_start.elapsed();
+        let tick_length = server.game().real_tick_length();
+        if elapsed < tick_length {
+            thread::sleep(tick_length - elapsed);
+        }
+    }
+
+    // Give the server a last tick to answer pings in flight. Pings
+    // still awaiting an answer after it count as lost.
+    server.tick();
+    for bot in bots.iter_mut().filter(|bot| bot.error.is_none()) {
+        bot.client.poll().ok();
+        period.rtts.extend(bot.client.take_rtts());
+        period.pings_lost += bot.client.drop_unanswered_pings(Duration::from_secs(0));
+    }
+    total.rtts.append(&mut period.rtts);
+    total.pings_lost += period.pings_lost;
+    total.tick_times.append(&mut period.tick_times);
+    report("total", &bots, &total);
+
+    let failed = bots.iter().filter(|bot| bot.error.is_some()).count();
+    if failed > 0 {
+        bail!("{} of {} bots failed", failed, bots.len());
+    }
+    Ok(())
+}
+
+fn report(label: &str, bots: &[Bot], period: &Period) {
+    let in_game = bots
+        .iter()
+        .filter(|bot| bot.client.is_in_game() && bot.error.is_none())
+        .count();
+    let disconnected = bots
+        .iter()
+        .filter(|bot| bot.client.disconnect_reason().is_some())
+        .count();
+    let (sent, received) = bots.iter().fold((0, 0), |(sent, received), bot| {
+        PacketKind::all().fold((sent, received), |(sent, received), kind| {
+            let stats = bot.client.stats().get(kind);
+            (sent + stats.sent, received + stats.received)
+        })
+    });
+
+    let answered = period.rtts.len();
+    let loss = if answered + period.pings_lost == 0 {
+        0.
+    } else {
+        period.pings_lost as f64 / (answered + period.pings_lost) as f64 * 100.
+    };
+
+    println!(
+        "[{}] bots: {} in game, {} disconnected | ping: {} | loss: {:.1}% | packets: {} sent, {} received | tick: {}",
+        label,
+        in_game,
+        disconnected,
+        summarize(&period.rtts),
+        loss,
+        sent,
+        received,
+        summarize(&period.tick_times),
+    );
+}
+
+/// Formats the median, 95th percentile and maximum of some durations.
+fn summarize(durations: &[Duration]) -> String {
+    if durations.is_empty() {
+        return "n/a".to_owned();
+    }
+    let mut sorted = durations.to_vec();
+    sorted.sort();
+    let percentile = |p: usize| sorted[(sorted.len() - 1) * p / 100];
+    format!(
+        "p50 {:.1}ms, p95 {:.1}ms, max {:.1}ms",
+        millis(percentile(50)),
+        millis(percentile(95)),
+        millis(sorted[sorted.len() - 1]),
+    )
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.
+}
@@ -0,0 +1,809 @@
+//! Harness for end-to-end smoke tests of the protocol and game loop.
+//!
+//! A [`Harness`] runs a [`Server`] connected to a [`HeadlessClient`]
+//! over a singleplayer bridge. The headless client implements the
+//! client side of the protocol without a window or renderer and keeps
+//! only the state that tests need to check what the server sent.
+//! Both sides are ticked on the test's thread, so tests are deterministic
+//! apart from world generation running in the background.
+//!
+//! The headless client [caches](protocol::chunk_cache) chunks in memory
+//! for as long as it lives, so reconnecting exercises the cache.
+//!
+//! The `voltz-bot` tool uses headless clients to put a server under load.
+
+use std::{
+    collections::HashMap,
+    iter,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use common::{
+    biome::{Biome, ColumnBiomes},
+    block,
+    chunk::CHUNK_DIM,
+    edit::Digging,
+    entity::Health,
+    item::Inventory,
+    world::{BlockPos, SparseZone},
+    BlockId, Chunk, ChunkPos, WorldPos,
+};
+use glam::Vec2;
+use protocol::{
+    bridge::{self, ToServer},
+    chunk_cache,
+    dictionary::BlockDictionary,
+    features::Features,
+    keepalive,
+    packets::{
+        client::{
+            self, BreakBlock, ClientInfo, DialogResponse, DigProgress, Landed, PlaceBlock,
+            RequestChunks, ResourcePackLoaded, SelectSlot, UpdatePosition, UpdateSettings,
+            UseBlock,
+        },
+        server::{
+            BlockUpdate, ChatMessage, CloseDialog, DespawnEntity, EntityDigProgress, EntityKind,
+            EntityPosition, JoinGame, LoadCachedChunk, LoadChunk, MoveEntity, OpenDialog,
+            PlayerDied, RegistrySync, Respawn, ServerInfo, SetGameMode, SetInventory, SpawnEntity,
+            Teleport, TimeUpdate, UpdateHealth, ViewDistanceChanged,
+        },
+        shared::{Disconnect, Ping, Pong},
+        ClientPacket, ServerPacket, SharedPacket,
+    },
+    registry::RegistryMap,
+    resource_pack::ResourcePack,
+    stats::PacketStats,
+    Bridge, PROTOCOL_VERSION,
+};
+use server::{Backend, Connection, Server};
+
+/// The view distance the headless client asks for when logging in.
+pub const VIEW_DISTANCE: u32 = 8;
+
+/// A server and the headless clients connected to it.
+pub struct Harness {
+    pub server: Server,
+    pub client: HeadlessClient,
+    /// Clients of other players, for tests of what players see of each other.
+    pub others: Vec<HeadlessClient>,
+}
+
+impl Harness {
+    /// Starts a server and connects a client with the given username.
+    ///
+    /// World generation runs on the CPU so tests do not need a GPU.
+    /// Spawn is not protected, so tests can edit blocks around it.
+    pub fn new(username: &str) -> Self {
+        Self::with_others(username, &[])
+    }
+
+    /// Starts a server and connects a client with the given username,
+    /// and one of [`others`](Self::others) for each of `other_usernames`.
+    pub fn with_others(username: &str, other_usernames: &[&str]) -> Self {
+        let mut connections = Vec::new();
+        let mut clients = Vec::new();
+        for username in iter::once(username).chain(other_usernames.iter().copied()) {
+            let (client_bridge, server_bridge) = bridge::singleplayer();
+            connections.push(Connection::new(server_bridge));
+            clients.push(HeadlessClient::connect(client_bridge, username));
+        }
+        let mut server = Server::new(connections, Backend::Cpu, None);
+        server.disable_spawn_protection();
+        let client = clients.remove(0);
+        Self {
+            server,
+            client,
+            others: clients,
+        }
+    }
+
+    /// Starts the integrated server of a singleplayer world saved
+    /// in `save_dir`, as the client does, and connects a client.
+    pub fn singleplayer(username: &str, save_dir: PathBuf) -> Self {
+        let (client_bridge, server_bridge) = bridge::singleplayer();
+        let server = Server::singleplayer(Connection::new(server_bridge), Backend::Cpu, save_dir);
+        let client = HeadlessClient::connect(client_bridge, username);
+        Self {
+            server,
+            client,
+            others: Vec::new(),
+        }
+    }
+
+    /// Connects the client to the server again over a new bridge,
+    /// resuming its session. Call after [`HeadlessClient::lose_connection`].
+    pub fn reconnect(&mut self) -> anyhow::Result<()> {
+        let (client_bridge, server_bridge) = bridge::singleplayer();
+        self.server.add_client(Connection::new(server_bridge));
+        self.client.resume(client_bridge)
+    }
+
+    /// Ticks the server, then handles the packets it sent.
+    pub fn tick(&mut self) -> anyhow::Result<()> {
+        self.server.tick();
+        self.client.poll()?;
+        for other in &mut self.others {
+            other.poll()?;
+        }
+        Ok(())
+    }
+
+    /// Ticks until `condition` returns `true`. Fails if it
+    /// does not within `max_ticks` ticks.
+    pub fn tick_until(
+        &mut self,
+        max_ticks: u32,
+        mut condition: impl FnMut(&Self) -> bool,
+    ) -> anyhow::Result<()> {
+        for _ in 0..max_ticks {
+            if condition(self) {
+                return Ok(());
+            }
+            self.tick()?;
+        }
+        if condition(self) {
+            Ok(())
+        } else {
+            Err(anyhow!("condition not met after {} ticks", max_ticks))
+        }
+    }
+}
+
+/// An entity the server told us about with `SpawnEntity`.
+#[derive(Debug, Clone)]
+pub struct RemoteEntity {
+    pub kind: EntityKind,
+    pub pos: WorldPos,
+    /// The block a player is digging, as told with `EntityDigProgress`.
+    pub digging: Option<Digging>,
+}
+
+enum State {
+    /// Waiting for `ServerInfo` and `JoinGame`.
+    Login { received_server_info: bool },
+    /// The player has joined.
+    Game { pos: WorldPos, orient: Vec2 },
+    /// The server disconnected us.
+    Disconnected { reason: Option<String> },
+}
+
+/// The client side of the protocol, without a window.
+pub struct HeadlessClient {
+    bridge: Bridge<ToServer>,
+    username: String,
+    state: State,
+    /// The token to resume our session with, once we have joined.
+    session_token: Option<u64>,
+    /// The resource pack the server requires, if any.
+    resource_pack: Option<ResourcePack>,
+    dictionary: Option<BlockDictionary>,
+    /// Translates the server's block IDs, if its registry differs from ours.
+    registry: Option<RegistryMap>,
+    chunks: SparseZone,
+    /// Every chunk received in full, with the server's block IDs.
+    cache: HashMap<ChunkPos, Chunk>,
+    /// The number of chunks loaded from `cache`.
+    cache_hits: usize,
+    /// Chunks missing from `cache`, to request once
+    /// the current packets are handled.
+    uncached: Vec<ChunkPos>,
+    /// Entities the server spawned and hasn't despawned,
+    /// by the server's IDs for them.
+    entities: HashMap<u64, RemoteEntity>,
+    /// Dialogs the server opened which we haven't
+    /// answered and it hasn't closed.
+    dialogs: Vec<OpenDialog>,
+    /// Chat messages received, with their senders.
+    chat: Vec<(String, String)>,
+    /// The cause of our death, if we died and haven't respawned.
+    death: Option<String>,
+    /// How far we can reach, once the server has told us.
+    reach: Option<f32>,
+    /// Our inventory, once the server has sent it.
+    inventory: Option<Inventory>,
+    /// Our health, once the server has sent it.
+    health: Option<Health>,
+    /// The world's time, as last sent by the server.
+    time: Option<f64>,
+    /// The view distance last sent by the server.
+    view_distance: Option<u32>,
+    /// Pings we sent and haven't had answered, with their send times.
+    pending_pings: HashMap<u64, Instant>,
+    next_ping: u64,
+    /// Round-trip times of answered pings not yet taken.
+    rtts: Vec<Duration>,
+}
+
+impl HeadlessClient {
+    /// Starts logging in to the server on the other side of `bridge`.
+    pub fn connect(bridge: Bridge<ToServer>, username: &str) -> Self {
+        bridge.send(client_info(username, None));
+        Self {
+            bridge,
+            username: username.to_owned(),
+            state: State::Login {
+                received_server_info: false,
+            },
+            session_token: None,
+            resource_pack: None,
+            dictionary: None,
+            registry: None,
+            chunks: SparseZone::new(),
+            cache: HashMap::new(),
+            cache_hits: 0,
+            uncached: Vec::new(),
+            entities: HashMap::new(),
+            dialogs: Vec::new(),
+            chat: Vec::new(),
+            death: None,
+            reach: None,
+            inventory: None,
+            health: None,
+            time: None,
+            view_distance: None,
+            pending_pings: HashMap::new(),
+            next_ping: 0,
+            rtts: Vec::new(),
+        }
+    }
+
+    /// Handles all packets received since the last call.
+    /// Fails if the server violated the protocol.
+    pub fn poll(&mut self) -> anyhow::Result<()> {
+        for packet in self.bridge.flush_received() {
+            match self.state {
+                State::Login {
+                    received_server_info,
+                } => self.handle_login_packet(packet, received_server_info)?,
+                State::Game { .. } => self.handle_game_packet(packet)?,
+                State::Disconnected { .. } => bail!("received {:?} after disconnecting", packet),
+            }
+        }
+        if !self.uncached.is_empty() {
+            let positions = std::mem::take(&mut self.uncached);
+            self.bridge
+                .send(ClientPacket::RequestChunks(RequestChunks { positions }));
+        }
+        Ok(())
+    }
+
+    fn handle_login_packet(
+        &mut self,
+        packet: ServerPacket,
+        received_server_info: bool,
+    ) -> anyhow::Result<()> {
+        match packet {
+            ServerPacket::WorldgenProgress(_) => {}
+            ServerPacket::ServerInfo(ServerInfo { resource_pack, .. }) if !received_server_info => {
+                self.resource_pack = resource_pack;
+                self.state = State::Login {
+                    received_server_info: true,
+                };
+            }
+            ServerPacket::RegistrySync(RegistrySync { blocks }) if received_server_info => {
+                self.registry = Some(RegistryMap::new(&blocks)?);
+            }
+            ServerPacket::JoinGame(JoinGame {
+                pos,
+                orient,
+                session_token,
+                ..
+            }) if received_server_info => {
+                self.state = State::Game { pos, orient };
+                self.session_token = Some(session_token);
+            }
+            ServerPacket::Shared(SharedPacket::Disconnect(Disconnect { reason })) => {
+                self.state = State::Disconnected { reason };
+            }
+            packet => bail!("unexpected packet during login state: {:?}", packet),
+        }
+        Ok(())
+    }
+
+    fn handle_game_packet(&mut self, packet: ServerPacket) -> anyhow::Result<()> {
+        match packet {
+            ServerPacket::Shared(SharedPacket::Disconnect(Disconnect { reason })) => {
+                self.state = State::Disconnected { reason };
+            }
+            ServerPacket::Shared(SharedPacket::Ping(ping)) => {
+                self.bridge
+                    .send(ClientPacket::Shared(SharedPacket::Pong(keepalive::answer(
+                        ping,
+                    ))));
+            }
+            ServerPacket::Shared(SharedPacket::Pong(Pong { id })) => {
+                if let Some(sent) = self.pending_pings.remove(&id) {
+                    self.rtts.push(sent.elapsed());
+                }
+            }
+            ServerPacket::SetBlockDictionary(packet) => self.dictionary = Some(packet.dictionary),
+            ServerPacket::LoadChunk(LoadChunk { pos, chunk, biomes }) => {
+                let chunk = match &self.dictionary {
+                    Some(dictionary) => dictionary.decode(chunk),
+                    None => chunk.into_full(),
+                }
+                .ok_or_else(|| anyhow!("received malformed chunk {:?}", pos))?;
+                self.cache.insert(pos, chunk.clone());
+                self.load_chunk(pos, chunk, biomes)?;
+            }
+            ServerPacket::LoadCachedChunk(LoadCachedChunk { pos, hash, biomes }) => {
+                match self.cache.get(&pos) {
+                    Some(chunk) if chunk_cache::chunk_hash(chunk) == hash => {
+                        let chunk = chunk.clone();
+                        self.cache_hits += 1;
+                        self.load_chunk(pos, chunk, biomes)?;
+                    }
+                    _ => self.uncached.push(pos),
+                }
+            }
+            ServerPacket::UnloadChunk(packet) => {
+                self.chunks.remove(packet.pos);
+            }
+            ServerPacket::Teleport(Teleport { pos: new_pos }) => {
+                if let State::Game { pos, .. } = &mut self.state {
+                    *pos = new_pos;
+                }
+            }
+            ServerPacket::PlayerDied(PlayerDied { cause }) => self.death = Some(cause),
+            ServerPacket::Respawn(Respawn { pos: new_pos }) => {
+                if self.death.take().is_none() {
+                    bail!("respawned without dying");
+                }
+                if let State::Game { pos, .. } = &mut self.state {
+                    *pos = new_pos;
+                }
+            }
+            ServerPacket::SetGameMode(SetGameMode { reach, .. }) => self.reach = Some(reach),
+            ServerPacket::ChatMessage(ChatMessage { sender, message }) => {
+                self.chat.push((sender, message));
+            }
+            ServerPacket::BlockUpdate(BlockUpdate { pos, block }) => {
+                let local = match &self.registry {
+                    Some(registry) => registry.to_local(block),
+                    None => Some(block).filter(|block| block.is_valid()),
+                };
+                let block = local
+                    .ok_or_else(|| anyhow!("received invalid block {:?} at {:?}", block, pos))?;
+                // Updates to unloaded chunks are allowed and ignored.
+                self.chunks.set_block(pos, block).ok();
+            }
+            ServerPacket::SpawnEntity(SpawnEntity {
+                entity, pos, kind, ..
+            }) => {
+                self.entities.insert(
+                    entity,
+                    RemoteEntity {
+                        kind,
+                        pos,
+                        digging: None,
+                    },
+                );
+            }
+            ServerPacket::EntityPosition(EntityPosition { entity, pos, .. })
+            | ServerPacket::MoveEntity(MoveEntity { entity, pos }) => {
+                if let Some(remote) = self.entities.get_mut(&entity) {
+                    remote.pos = pos;
+                }
+            }
+            ServerPacket::DespawnEntity(DespawnEntity { entity }) => {
+                self.entities.remove(&entity);
+            }
+            ServerPacket::EntityDigProgress(EntityDigProgress { entity, digging }) => {
+                if let Some(remote) = self.entities.get_mut(&entity) {
+                    remote.digging = digging;
+                }
+            }
+            ServerPacket::OpenDialog(dialog) => self.dialogs.push(dialog),
+            ServerPacket::CloseDialog(CloseDialog { id }) => {
+                self.dialogs.retain(|dialog| dialog.id != id)
+            }
+            ServerPacket::UpdateHealth(UpdateHealth { health, max_health }) => {
+                if health > max_health {
+                    bail!(
+                        "received health {} above the maximum {}",
+                        health,
+                        max_health
+                    );
+                }
+                self.health = Some(Health {
+                    current: health,
+                    max: max_health,
+                });
+            }
+            ServerPacket::SetInventory(SetInventory { slots, selected }) => {
+                let inventory = Inventory::from_slots(slots, selected as usize)
+                    .ok_or_else(|| anyhow!("received invalid inventory"))?;
+                self.inventory = Some(inventory);
+            }
+            ServerPacket::TimeUpdate(TimeUpdate { time, .. }) => self.time = Some(time),
+            ServerPacket::ViewDistanceChanged(ViewDistanceChanged { view_distance }) => {
+                self.view_distance = Some(view_distance)
+            }
+            ServerPacket::WorldgenProgress(_)
+            | ServerPacket::SpawnFallingBlock(_)
+            | ServerPacket::WeatherChange(_)
+            | ServerPacket::MeteorShower(_)
+            | ServerPacket::SystemMessage(_)
+            | ServerPacket::TickRate(_)
+            | ServerPacket::UpdateGameRules(_)
+            | ServerPacket::UpdateXp(_) => {}
+            ServerPacket::ServerInfo(_)
+            | ServerPacket::RegistrySync(_)
+            | ServerPacket::JoinGame(_) => {
+                bail!("unexpected packet during game state: {:?}", packet)
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads a chunk with the server's block IDs.
+    fn load_chunk(
+        &mut self,
+        pos: ChunkPos,
+        chunk: Chunk,
+        biomes: ColumnBiomes,
+    ) -> anyhow::Result<()> {
+        let chunk = match &self.registry {
+            Some(registry) => registry.chunk_to_local(chunk),
+            None => Some(chunk),
+        }
+        .ok_or_else(|| anyhow!("received malformed chunk {:?}", pos))?;
+        self.chunks.insert(pos, chunk);
+        self.chunks.set_column_biomes(pos.x, pos.z, biomes);
+        Ok(())
+    }
+
+    /// Says goodbye and leaves the game.
+    pub fn leave(&mut self) {
+        self.bridge
+            .send(ClientPacket::Shared(SharedPacket::Disconnect(Disconnect {
+                reason: None,
+            })));
+        self.state = State::Disconnected { reason: None };
+    }
+
+    /// Drops our end of the bridge without saying goodbye,
+    /// as if the network had failed.
+    pub fn lose_connection(&mut self) {
+        let (dead, _) = bridge::singleplayer();
+        self.bridge = dead;
+    }
+
+    /// Logs in again over `bridge`, asking to resume our session.
+    /// Chunks received before are kept.
+    pub fn resume(&mut self, bridge: Bridge<ToServer>) -> anyhow::Result<()> {
+        let token = self
+            .session_token
+            .ok_or_else(|| anyhow!("cannot resume before joining the game"))?;
+        bridge.send(client_info(&self.username, Some(token)));
+        self.bridge = bridge;
+        self.state = State::Login {
+            received_server_info: false,
+        };
+        Ok(())
+    }
+
+    /// Gets the token to resume our session with,
+    /// or `None` if we never joined.
+    pub fn session_token(&self) -> Option<u64> {
+        self.session_token
+    }
+
+    /// Gets the resource pack the server requires, or `None`
+    /// if it requires none or hasn't sent `ServerInfo` yet.
+    pub fn resource_pack(&self) -> Option<&ResourcePack> {
+        self.resource_pack.as_ref()
+    }
+
+    /// Tells the server we loaded its resource pack, which it
+    /// waits for before letting us join. There are no assets to
+    /// load it into, so the pack isn't actually downloaded.
+    pub fn load_resource_pack(&mut self) -> anyhow::Result<()> {
+        if self.resource_pack.is_none() {
+            bail!("the server requires no resource pack");
+        }
+        self.bridge
+            .send(ClientPacket::ResourcePackLoaded(ResourcePackLoaded));
+        Ok(())
+    }
+
+    /// Returns whether the player has joined the game.
+    pub fn is_in_game(&self) -> bool {
+        matches!(self.state, State::Game { .. })
+    }
+
+    /// Returns the reason the server disconnected us,
+    /// or `None` if we are still connected.
+    pub fn disconnect_reason(&self) -> Option<&str> {
+        match &self.state {
+            State::Disconnected { reason } => Some(reason.as_deref().unwrap_or("no reason given")),
+            _ => None,
+        }
+    }
+
+    /// Gets the player's position, or `None` if not in game.
+    pub fn pos(&self) -> Option<WorldPos> {
+        match self.state {
+            State::Game { pos, .. } => Some(pos),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the server sent a block dictionary.
+    pub fn has_dictionary(&self) -> bool {
+        self.dictionary.is_some()
+    }
+
+    /// Gets the chunks the server has sent and not unloaded.
+    pub fn chunks(&self) -> &SparseZone {
+        &self.chunks
+    }
+
+    /// Returns the number of chunks the server let us load from our cache.
+    pub fn cache_hits(&self) -> usize {
+        self.cache_hits
+    }
+
+    /// Moves the player and reports the new position to the server.
+    pub fn move_to(&mut self, new_pos: WorldPos) -> anyhow::Result<()> {
+        match &mut self.state {
+            State::Game { pos, orient } => {
+                *pos = new_pos;
+                self.bridge
+                    .send(ClientPacket::UpdatePosition(UpdatePosition {
+                        new_pos,
+                        new_orient: *orient,
+                    }));
+                Ok(())
+            }
+            _ => bail!("cannot move before joining the game"),
+        }
+    }
+
+    /// Asks the server to change our view distance.
+    pub fn set_view_distance(&mut self, view_distance: u32) -> anyhow::Result<()> {
+        self.ensure_in_game()?;
+        self.bridge
+            .send(ClientPacket::UpdateSettings(UpdateSettings {
+                view_distance,
+            }));
+        Ok(())
+    }
+
+    /// Asks the server to place a block. The local copy
+    /// changes only once the server confirms.
+    pub fn place_block(&mut self, pos: BlockPos, block: BlockId) -> anyhow::Result<()> {
+        self.ensure_in_game()?;
+        self.bridge
+            .send(ClientPacket::PlaceBlock(PlaceBlock { pos, block }));
+        Ok(())
+    }
+
+    /// Asks the server to break a block. The local copy
+    /// changes only once the server confirms.
+    pub fn break_block(&mut self, pos: BlockPos) -> anyhow::Result<()> {
+        self.ensure_in_game()?;
+        self.bridge
+            .send(ClientPacket::BreakBlock(BreakBlock { pos }));
+        Ok(())
+    }
+
+    /// Asks the server to use a block, e.g. to open a door.
+    /// The local copy changes only once the server confirms.
+    pub fn use_block(&mut self, pos: BlockPos) -> anyhow::Result<()> {
+        self.ensure_in_game()?;
+        self.bridge.send(ClientPacket::UseBlock(UseBlock { pos }));
+        Ok(())
+    }
+
+    /// Tells the server which block we dig, or that we stopped digging.
+    pub fn dig(&mut self, digging: Option<Digging>) -> anyhow::Result<()> {
+        self.ensure_in_game()?;
+        self.bridge
+            .send(ClientPacket::DigProgress(DigProgress { digging }));
+        Ok(())
+    }
+
+    /// Sends a chat message.
+    pub fn chat(&mut self, message: &str) -> anyhow::Result<()> {
+        self.ensure_in_game()?;
+        self.bridge
+            .send(ClientPacket::ChatMessage(client::ChatMessage {
+                message: message.to_owned(),
+            }));
+        Ok(())
+    }
+
+    /// Gets the entities the server spawned and hasn't
+    /// despawned, by the server's IDs for them.
+    pub fn entities(&self) -> &HashMap<u64, RemoteEntity> {
+        &self.entities
+    }
+
+    /// Gets another player the server spawned, by username.
+    pub fn player(&self, username: &str) -> Option<&RemoteEntity> {
+        self.entities.values().find(|remote| {
+            matches!(&remote.kind, EntityKind::Player { username: name } if name == username)
+        })
+    }
+
+    /// Gets the dialogs the server opened which we
+    /// haven't answered and it hasn't closed.
+    pub fn dialogs(&self) -> &[OpenDialog] {
+        &self.dialogs
+    }
+
+    /// Answers an open dialog with one of its buttons,
+    /// or dismisses it if `button` is `None`.
+    pub fn respond_to_dialog(&mut self, dialog: u32, button: Option<u32>) -> anyhow::Result<()> {
+        self.ensure_in_game()?;
+        let index = self
+            .dialogs
+            .iter()
+            .position(|open| open.id == dialog)
+            .ok_or_else(|| anyhow!("dialog {} is not open", dialog))?;
+        self.dialogs.remove(index);
+        self.bridge
+            .send(ClientPacket::DialogResponse(DialogResponse {
+                dialog,
+                button,
+            }));
+        Ok(())
+    }
+
+    /// Gets the chat messages received so far and their senders.
+    pub fn received_chat(&self) -> &[(String, String)] {
+        &self.chat
+    }
+
+    /// Gets the cause of our death, or `None` if we are alive.
+    pub fn death_cause(&self) -> Option<&str> {
+        self.death.as_deref()
+    }
+
+    /// Asks the server to respawn after dying.
+    pub fn respawn(&mut self) -> anyhow::Result<()> {
+        self.ensure_in_game()?;
+        self.bridge.send(ClientPacket::Respawn(client::Respawn));
+        Ok(())
+    }
+
+    /// Gets how far we can reach, or `None` if
+    /// the server hasn't sent our game mode yet.
+    pub fn reach(&self) -> Option<f32> {
+        self.reach
+    }
+
+    /// Gets our inventory, or `None` if the server hasn't sent it yet.
+    pub fn inventory(&self) -> Option<&Inventory> {
+        self.inventory.as_ref()
+    }
+
+    /// Selects a hotbar slot. Like the real client,
+    /// we select it locally without waiting for the server.
+    pub fn select_slot(&mut self, slot: usize) -> anyhow::Result<()> {
+        self.ensure_in_game()?;
+        if let Some(inventory) = &mut self.inventory {
+            inventory.select(slot);
+        }
+        self.bridge
+            .send(ClientPacket::SelectSlot(SelectSlot { slot: slot as u32 }));
+        Ok(())
+    }
+
+    /// Gets our health, or `None` if the server hasn't sent it yet.
+    pub fn health(&self) -> Option<Health> {
+        self.health
+    }
+
+    /// Gets the world's time as of the last `TimeUpdate`,
+    /// or `None` if the server hasn't sent it yet.
+    pub fn time(&self) -> Option<f64> {
+        self.time
+    }
+
+    /// Returns the view distance the server last said it
+    /// sends us chunks within, if it has said so yet.
+    pub fn view_distance(&self) -> Option<u32> {
+        self.view_distance
+    }
+
+    /// Tells the server we landed on the ground after falling `distance` blocks.
+    pub fn land(&mut self, distance: f32) -> anyhow::Result<()> {
+        self.ensure_in_game()?;
+        self.bridge.send(ClientPacket::Landed(Landed { distance }));
+        Ok(())
+    }
+
+    /// Sends a ping. Its round-trip time is
+    /// available from `take_rtts` once answered.
+    pub fn ping(&mut self) -> anyhow::Result<()> {
+        self.ensure_in_game()?;
+        let id = self.next_ping;
+        self.next_ping += 1;
+        self.pending_pings.insert(id, Instant::now());
+        self.bridge
+            .send(ClientPacket::Shared(SharedPacket::Ping(Ping { id })));
+        Ok(())
+    }
+
+    /// Takes the round-trip times of the pings answered since the last call.
+    pub fn take_rtts(&mut self) -> Vec<Duration> {
+        std::mem::take(&mut self.rtts)
+    }
+
+    /// Forgets the pings that have waited longer than `timeout`
+    /// for an answer and returns how many there were.
+    pub fn drop_unanswered_pings(&mut self, timeout: Duration) -> usize {
+        let before = self.pending_pings.len();
+        self.pending_pings
+            .retain(|_, sent| sent.elapsed() < timeout);
+        before - self.pending_pings.len()
+    }
+
+    /// Gets the statistics of the packets we sent and received.
+    pub fn stats(&self) -> &PacketStats {
+        self.bridge.stats()
+    }
+
+    fn ensure_in_game(&self) -> anyhow::Result<()> {
+        if self.is_in_game() {
+            Ok(())
+        } else {
+            bail!("cannot interact before joining the game")
+        }
+    }
+
+    /// Gets a block from a loaded chunk.
+    pub fn block(&self, pos: BlockPos) -> Option<BlockId> {
+        self.chunks.block(pos)
+    }
+
+    /// Gets the biome at a block in a loaded chunk column.
+    pub fn biome(&self, pos: BlockPos) -> Option<&'static Biome> {
+        self.chunks.biome_at(pos)
+    }
+}
+
+fn client_info(username: &str, resume_token: Option<u64>) -> ClientPacket {
+    ClientPacket::ClientInfo(ClientInfo {
+        protocol_version: PROTOCOL_VERSION,
+        implementation: format!("voltz-smoke-test:{}", env!("CARGO_PKG_VERSION")),
+        username: username.to_owned(),
+        features: Features::SUPPORTED,
+        view_distance: VIEW_DISTANCE,
+        registry_digest: block::registry_digest(),
+        resume_token,
+    })
+}
+
+/// Returns the position of the first block that differs
+/// between two chunks, or `None` if they are identical.
+pub fn first_difference(a: &Chunk, b: &Chunk) -> Option<(usize, usize, usize)> {
+    for x in 0..CHUNK_DIM {
+        for y in 0..CHUNK_DIM {
+            for z in 0..CHUNK_DIM {
+                if a.get(x, y, z) != b.get(x, y, z) {
+                    return Some((x, y, z));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Returns the chunks known to `client` that differ
+/// from the server's main zone.
+pub fn mismatched_chunks(client: &HeadlessClient, server: &Server) -> Vec<ChunkPos> {
+    let zone = server.game().main_zone();
+    let mut mismatched = Vec::new();
+    for (pos, chunk) in client.chunks().chunks() {
+        match zone.chunk(pos) {
+            Some(server_chunk) if first_difference(chunk, server_chunk).is_none() => {}
+            _ => mismatched.push(pos),
+        }
+    }
+    mismatched
+}
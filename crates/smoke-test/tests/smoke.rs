@@ -0,0 +1,850 @@
+use std::{fs, time::Duration};
+
+use common::{
+    block::Facing,
+    blocks,
+    chunk::CHUNK_DIM,
+    edit::Digging,
+    entity::{
+        player::{Username, View, MAX_HEALTH},
+        FallingBlock, Health, ItemDrop,
+    },
+    item::{Inventory, ItemId, ItemStack},
+    time::{DAY_LENGTH, NOON},
+    BlockId, BlockPos, ChunkPos, Pos, WorldPos,
+};
+use glam::vec3a;
+use hecs::Entity;
+use protocol::{
+    packets::server::{EntityKind, OpenDialog},
+    resource_pack::{PackHash, ResourcePack},
+};
+use server::{
+    command::Console,
+    dialog::{self, Dialog},
+    protection::ProtectedRegions,
+    save::{LevelData, WorldSave},
+    BiomeSequence,
+};
+use smoke_test::{mismatched_chunks, Harness};
+
+const USERNAME: &str = "smoke-test";
+
+#[test]
+fn login_chunks_and_movement() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+
+    // Login
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    assert_eq!(harness.client.disconnect_reason(), None);
+    let spawn = harness.client.pos().unwrap();
+
+    let server_pos = player_pos(&harness).expect("player entity not spawned");
+    assert_eq!(server_pos, spawn);
+
+    // Chunks around spawn
+    harness.tick_until(20, |h| h.client.chunks().len() > 0)?;
+    assert!(harness.client.has_dictionary());
+    let spawn_chunk = ChunkPos::from_pos(spawn.local);
+    assert!(harness.client.chunks().chunk(spawn_chunk).is_some());
+    assert_eq!(mismatched_chunks(&harness.client, &harness.server), vec![]);
+
+    // Movement
+    let new_pos = spawn.offset(vec3a(64., 0., 0.));
+    harness.client.move_to(new_pos)?;
+    harness.tick_until(20, |h| player_pos(h) == Some(new_pos))?;
+    harness.tick()?;
+
+    let new_chunk = ChunkPos::from_pos(new_pos.local);
+    let view_distance = smoke_test::VIEW_DISTANCE as i32;
+    for (pos, _) in harness.client.chunks().chunks() {
+        assert!(
+            (pos.x - new_chunk.x).abs() <= view_distance,
+            "{:?} should have been unloaded",
+            pos
+        );
+    }
+    assert_eq!(mismatched_chunks(&harness.client, &harness.server), vec![]);
+    assert_eq!(harness.client.disconnect_reason(), None);
+
+    Ok(())
+}
+
+#[test]
+fn players_see_each_other() -> anyhow::Result<()> {
+    let mut harness = Harness::with_others(USERNAME, &["other"]);
+    harness.tick_until(20, |h| h.client.is_in_game() && h.others[0].is_in_game())?;
+
+    // Players spawn in each other's view.
+    harness.tick_until(5, |h| h.client.player("other").is_some())?;
+    assert!(harness.others[0].player(USERNAME).is_some());
+
+    // Movement
+    let spawn = harness.others[0].pos().unwrap();
+    let moved = spawn.offset(vec3a(3., 0., 2.));
+    harness.others[0].move_to(moved)?;
+    harness.tick_until(5, |h| {
+        h.client.player("other").map(|other| other.pos) == Some(moved)
+    })?;
+
+    // Leaving the view despawns the player, and coming back spawns them again.
+    let far = (smoke_test::VIEW_DISTANCE + 2) as usize * CHUNK_DIM;
+    harness.others[0].move_to(spawn.offset(vec3a(far as f32, 0., 0.)))?;
+    harness.tick_until(5, |h| h.client.player("other").is_none())?;
+    harness.others[0].move_to(moved)?;
+    harness.tick_until(5, |h| {
+        h.client.player("other").map(|other| other.pos) == Some(moved)
+    })?;
+
+    // Leaving the game despawns the player.
+    harness.others[0].leave();
+    harness.tick_until(5, |h| h.client.player("other").is_none())?;
+    assert_eq!(harness.client.entities().len(), 0);
+    Ok(())
+}
+
+#[test]
+fn players_see_digging() -> anyhow::Result<()> {
+    let mut harness = Harness::with_others(USERNAME, &["other"]);
+    let (commands, console) = Console::channel();
+    harness.server.set_console(console);
+    harness.tick_until(20, |h| h.client.is_in_game() && h.others[0].is_in_game())?;
+    harness.tick_until(5, |h| h.client.player("other").is_some())?;
+    let digging = |h: &Harness| h.client.player("other").and_then(|other| other.digging);
+
+    // Digging and its crack stages are shared
+    let pos = harness.others[0].pos().unwrap().block().offset(0, -1, 0);
+    let started = Digging { pos, stage: 0 };
+    let cracked = Digging { pos, stage: 5 };
+    harness.others[0].dig(Some(started))?;
+    harness.tick_until(5, |h| digging(h) == Some(started))?;
+    harness.others[0].dig(Some(cracked))?;
+    harness.tick_until(5, |h| digging(h) == Some(cracked))?;
+
+    // So is stopping
+    harness.others[0].dig(None)?;
+    harness.tick_until(5, |h| digging(h).is_none())?;
+
+    // Blocks out of reach can't be dug, so digging one stops digging
+    harness.others[0].dig(Some(started))?;
+    harness.tick_until(5, |h| digging(h) == Some(started))?;
+    let reach = harness.server.game().reach().survival;
+    let far = pos.offset(reach as i32 + 3, 0, 0);
+    harness.others[0].dig(Some(Digging { pos: far, stage: 0 }))?;
+    harness.tick_until(5, |h| digging(h).is_none())?;
+
+    // Nor can dead players dig
+    harness.others[0].dig(Some(started))?;
+    harness.tick_until(5, |h| digging(h) == Some(started))?;
+    commands.send("/kill other Fell out of the test".to_owned())?;
+    harness.tick_until(5, |h| h.others[0].death_cause().is_some())?;
+    harness.others[0].dig(Some(cracked))?;
+    harness.tick_until(5, |h| digging(h).is_none())?;
+    assert!(harness.client.player("other").is_some());
+    Ok(())
+}
+
+#[test]
+fn server_rules() -> anyhow::Result<()> {
+    let rules = "No griefing.";
+    let join = |harness: &mut Harness| -> anyhow::Result<OpenDialog> {
+        harness.server.set_server_rules(Some(rules.to_owned()));
+        harness.tick_until(20, |h| !h.client.dialogs().is_empty())?;
+        let dialog = harness.client.dialogs()[0].clone();
+        assert_eq!(dialog.text, rules);
+        Ok(dialog)
+    };
+
+    // Accepting the rules
+    let mut harness = Harness::new(USERNAME);
+    let dialog = join(&mut harness)?;
+    let accept = dialog.buttons[0].id;
+    harness.client.respond_to_dialog(dialog.id, Some(accept))?;
+    for _ in 0..5 {
+        harness.tick()?;
+    }
+    assert_eq!(harness.client.disconnect_reason(), None);
+
+    // Declining them gets the player kicked
+    let mut harness = Harness::new(USERNAME);
+    let dialog = join(&mut harness)?;
+    let decline = dialog.buttons[1].id;
+    harness.client.respond_to_dialog(dialog.id, Some(decline))?;
+    harness.tick_until(5, |h| h.client.disconnect_reason().is_some())?;
+    assert!(harness
+        .client
+        .disconnect_reason()
+        .unwrap()
+        .contains("rules"));
+    Ok(())
+}
+
+#[test]
+fn dialogs_close() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    let player = player_entity(&harness).expect("player entity not spawned");
+
+    let id = dialog::open_dialog(
+        harness.server.game(),
+        player,
+        Dialog::new("Title", "Text").button(0, "OK"),
+    )
+    .unwrap();
+    harness.tick_until(5, |h| h.client.dialogs().len() == 1)?;
+    assert_eq!(harness.client.dialogs()[0].id, id);
+
+    dialog::close_dialog(harness.server.game(), player, id);
+    harness.tick_until(5, |h| h.client.dialogs().is_empty())?;
+    Ok(())
+}
+
+#[test]
+fn block_edits() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    let spawn = harness.client.pos().unwrap();
+
+    // The spawn point is in the air above the terrain.
+    let pos = spawn.block();
+    harness.tick_until(20, |h| h.client.block(pos).is_some())?;
+    let air = BlockId::new(blocks::Air);
+    let stone = BlockId::new(blocks::Stone);
+    assert_eq!(harness.client.block(pos), Some(air));
+
+    // Placing
+    harness.client.place_block(pos, stone)?;
+    harness.tick_until(5, |h| h.client.block(pos) == Some(stone))?;
+    assert_eq!(server_block(&harness, pos), Some(stone));
+
+    // Placing into a solid block is rejected
+    harness
+        .client
+        .place_block(pos, BlockId::new(blocks::Dirt))?;
+    harness.tick()?;
+    harness.tick()?;
+    assert_eq!(harness.client.block(pos), Some(stone));
+    assert_eq!(server_block(&harness, pos), Some(stone));
+
+    // Breaking
+    harness.client.break_block(pos)?;
+    harness.tick_until(5, |h| h.client.block(pos) == Some(air))?;
+    assert_eq!(server_block(&harness, pos), Some(air));
+
+    assert_eq!(mismatched_chunks(&harness.client, &harness.server), vec![]);
+    assert_eq!(harness.client.disconnect_reason(), None);
+
+    Ok(())
+}
+
+#[test]
+fn block_drops() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    harness.tick_until(5, |h| h.client.inventory().is_some())?;
+    let pos = harness.client.pos().unwrap().block();
+    harness.tick_until(20, |h| h.client.block(pos).is_some())?;
+    let stone = BlockId::new(blocks::Stone);
+    harness.client.place_block(pos, stone)?;
+    harness.tick_until(5, |h| h.client.block(pos) == Some(stone))?;
+
+    // Standing where the block drops picks it up
+    let drop = ItemStack::new(ItemId::from_slug("stone").unwrap(), 1);
+    harness.client.move_to(WorldPos::main(vec3a(
+        pos.x as f32 + 0.25,
+        pos.y as f32,
+        pos.z as f32 + 0.25,
+    )))?;
+    harness.client.break_block(pos)?;
+    harness.tick_until(5, |h| {
+        h.client
+            .inventory()
+            .map_or(false, |inventory| inventory.slots().contains(&Some(drop)))
+    })?;
+    let game = harness.server.game();
+    assert_eq!(game.ecs().query::<&ItemDrop>().iter().count(), 0);
+    Ok(())
+}
+
+#[test]
+fn drops_are_sent_with_their_chunk() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    let spawn = harness.client.pos().unwrap();
+    // Far enough away that the player doesn't pick up the drop
+    let pos = spawn.block().offset(3, 0, 0);
+    harness.tick_until(20, |h| h.client.block(pos).is_some())?;
+    let stone = BlockId::new(blocks::Stone);
+    harness.client.place_block(pos, stone)?;
+    harness.tick_until(5, |h| h.client.block(pos) == Some(stone))?;
+    harness.client.break_block(pos)?;
+    let has_drop = |h: &Harness| {
+        h.client
+            .entities()
+            .values()
+            .any(|entity| matches!(entity.kind, EntityKind::ItemDrop { .. }))
+    };
+    harness.tick_until(5, has_drop)?;
+
+    // Leaving the drop's chunk despawns it, and coming back sends it again.
+    let far = (smoke_test::VIEW_DISTANCE + 2) as usize * CHUNK_DIM;
+    harness
+        .client
+        .move_to(spawn.offset(vec3a(far as f32, 0., 0.)))?;
+    harness.tick_until(5, |h| !has_drop(h))?;
+    harness.client.move_to(spawn)?;
+    harness.tick_until(5, has_drop)?;
+    Ok(())
+}
+
+#[test]
+fn doors() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    let ground = harness.client.pos().unwrap().block();
+    harness.tick_until(20, |h| h.client.block(ground).is_some())?;
+    let lower = ground.offset(0, 1, 0);
+    let upper = ground.offset(0, 2, 0);
+    let door = |open, upper_half| {
+        BlockId::new(blocks::Door {
+            open,
+            upper: upper_half,
+            powered: false,
+        })
+    };
+
+    // Doors need a solid block below
+    harness.client.place_block(lower, door(false, false))?;
+    harness.tick()?;
+    harness.tick()?;
+    assert_eq!(
+        server_block(&harness, lower),
+        Some(BlockId::new(blocks::Air))
+    );
+
+    // Placing the lower half places the upper half too
+    harness
+        .client
+        .place_block(ground, BlockId::new(blocks::Stone))?;
+    harness.client.place_block(lower, door(false, false))?;
+    harness.tick_until(5, |h| h.client.block(upper) == Some(door(false, true)))?;
+    assert_eq!(harness.client.block(lower), Some(door(false, false)));
+
+    // Using either half opens both
+    harness.client.use_block(upper)?;
+    harness.tick_until(5, |h| h.client.block(lower) == Some(door(true, false)))?;
+    assert_eq!(harness.client.block(upper), Some(door(true, true)));
+
+    // Breaking either half breaks both
+    harness.client.break_block(upper)?;
+    harness.tick_until(5, |h| {
+        h.client.block(lower) == Some(BlockId::new(blocks::Air))
+    })?;
+    assert_eq!(
+        server_block(&harness, upper),
+        Some(BlockId::new(blocks::Air))
+    );
+
+    assert_eq!(mismatched_chunks(&harness.client, &harness.server), vec![]);
+    Ok(())
+}
+
+#[test]
+fn protected_regions() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    let (commands, console) = Console::channel();
+    harness.server.set_console(console);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    let pos = harness.client.pos().unwrap().block();
+    harness.tick_until(20, |h| h.client.block(pos).is_some())?;
+    let air = BlockId::new(blocks::Air);
+    let stone = BlockId::new(blocks::Stone);
+
+    // Building is rejected in a region that doesn't allow it
+    commands.send(format!(
+        "/protect add home {} {} {} {} {} {}",
+        pos.x - 2,
+        pos.y - 2,
+        pos.z - 2,
+        pos.x + 2,
+        pos.y + 2,
+        pos.z + 2
+    ))?;
+    harness.tick()?;
+    harness.client.place_block(pos, stone)?;
+    harness.tick()?;
+    harness.tick()?;
+    assert_eq!(server_block(&harness, pos), Some(air));
+    assert_eq!(harness.client.block(pos), Some(air));
+
+    // ...and allowed once the region permits it
+    commands.send("/protect set home build true".to_owned())?;
+    harness.tick()?;
+    harness.client.place_block(pos, stone)?;
+    harness.tick_until(5, |h| h.client.block(pos) == Some(stone))?;
+    assert_eq!(server_block(&harness, pos), Some(stone));
+
+    assert_eq!(mismatched_chunks(&harness.client, &harness.server), vec![]);
+    Ok(())
+}
+
+#[test]
+fn singleplayer_spawn_is_not_protected() -> anyhow::Result<()> {
+    let dir = std::env::temp_dir().join(format!("voltz-smoke-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let stone = BlockId::new(blocks::Stone);
+    let build_at_spawn = |harness: &mut Harness| -> anyhow::Result<()> {
+        harness.tick_until(20, |h| h.client.is_in_game())?;
+        let pos = harness.client.pos().unwrap().block();
+        harness.tick_until(20, |h| h.client.block(pos).is_some())?;
+        harness.client.place_block(pos, stone)?;
+        harness.tick_until(5, |h| h.client.block(pos) == Some(stone))?;
+        assert_eq!(server_block(harness, pos), Some(stone));
+        Ok(())
+    };
+
+    // A new world
+    build_at_spawn(&mut Harness::singleplayer(USERNAME, dir.join("new")))?;
+
+    // A world saved with spawn protection
+    let save = WorldSave::new(dir.join("protected"));
+    save.write_level(&LevelData::new(0, &BiomeSequence::from_env()))?;
+    save.write_protected_regions(&ProtectedRegions::with_spawn_protection(16))?;
+    build_at_spawn(&mut Harness::singleplayer(USERNAME, save.dir().to_owned()))?;
+
+    let _ = fs::remove_dir_all(&dir);
+    Ok(())
+}
+
+#[test]
+fn ladders() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    let ground = harness.client.pos().unwrap().block();
+    harness.tick_until(20, |h| h.client.block(ground).is_some())?;
+    let pos = ground.offset(0, 2, 0);
+    let wall = ground.offset(-1, 2, 0);
+    let air = BlockId::new(blocks::Air);
+    let ladder = BlockId::new(blocks::Ladder {
+        facing: Facing::PosX,
+    });
+
+    // Ladders need a wall to hang on
+    harness.client.break_block(wall)?;
+    harness.tick_until(5, |h| h.client.block(wall) == Some(air))?;
+    harness.client.place_block(pos, ladder)?;
+    harness.tick()?;
+    harness.tick()?;
+    assert_eq!(server_block(&harness, pos), Some(air));
+
+    harness
+        .client
+        .place_block(wall, BlockId::new(blocks::Stone))?;
+    harness.client.place_block(pos, ladder)?;
+    harness.tick_until(5, |h| h.client.block(pos) == Some(ladder))?;
+    assert_eq!(server_block(&harness, pos), Some(ladder));
+
+    assert_eq!(mismatched_chunks(&harness.client, &harness.server), vec![]);
+    Ok(())
+}
+
+#[test]
+fn sand_falls() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    let ground = harness.client.pos().unwrap().block();
+    harness.tick_until(20, |h| h.client.block(ground).is_some())?;
+    let sand = BlockId::new(blocks::Sand);
+    let air = BlockId::new(blocks::Air);
+
+    // Sand placed in the air falls until it lands on the ground
+    harness
+        .client
+        .place_block(ground, BlockId::new(blocks::Stone))?;
+    let above = ground.offset(0, 4, 0);
+    harness.client.place_block(above, sand)?;
+    harness.tick_until(60, |h| h.client.block(ground.offset(0, 1, 0)) == Some(sand))?;
+    assert_eq!(harness.client.block(above), Some(air));
+    assert_eq!(server_block(&harness, above), Some(air));
+
+    let game = harness.server.game();
+    let falling = game.ecs().query::<&FallingBlock>().iter().count();
+    assert_eq!(falling, 0);
+
+    assert_eq!(mismatched_chunks(&harness.client, &harness.server), vec![]);
+    Ok(())
+}
+
+#[test]
+fn chat() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+
+    // Blank messages are dropped; others are trimmed and echoed back
+    harness.client.chat("   ")?;
+    harness.client.chat("  hello  ")?;
+    harness.tick_until(5, |h| !h.client.received_chat().is_empty())?;
+    harness.tick()?;
+    assert_eq!(
+        harness.client.received_chat(),
+        &[(USERNAME.to_owned(), "hello".to_owned())]
+    );
+    Ok(())
+}
+
+#[test]
+fn console_commands() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    let (commands, console) = Console::channel();
+    harness.server.set_console(console);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+
+    // With one player online, tp may omit the player
+    let target = WorldPos::main(vec3a(40., 200., 40.));
+    commands.send("/tp 40 200 40".to_owned())?;
+    harness.tick_until(5, |h| h.client.pos() == Some(target))?;
+    assert_eq!(player_pos(&harness), Some(target));
+
+    // Failed commands leave the server running
+    commands.send("/kick nobody".to_owned())?;
+    commands.send("/fly".to_owned())?;
+    harness.tick()?;
+    assert_eq!(harness.client.disconnect_reason(), None);
+
+    commands.send(format!("/kick {} Testing kicks", USERNAME))?;
+    harness.tick_until(5, |h| h.client.disconnect_reason().is_some())?;
+    assert_eq!(harness.client.disconnect_reason(), Some("Testing kicks"));
+    assert_eq!(player_pos(&harness), None);
+    Ok(())
+}
+
+#[test]
+fn death_and_respawn() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    let (commands, console) = Console::channel();
+    harness.server.set_console(console);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    let spawn = harness.client.pos().unwrap();
+
+    let elsewhere = spawn.offset(vec3a(20., 0., 0.));
+    harness.client.move_to(elsewhere)?;
+    commands.send(format!("/kill {} Fell out of the test", USERNAME))?;
+    harness.tick_until(5, |h| h.client.death_cause().is_some())?;
+    assert_eq!(harness.client.death_cause(), Some("Fell out of the test"));
+
+    harness.client.respawn()?;
+    harness.tick_until(5, |h| h.client.death_cause().is_none())?;
+    assert_eq!(harness.client.pos(), Some(spawn));
+    assert_eq!(player_pos(&harness), Some(spawn));
+    assert_eq!(harness.client.disconnect_reason(), None);
+    Ok(())
+}
+
+#[test]
+fn fall_damage() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    let full = Health::full(MAX_HEALTH);
+    harness.tick_until(5, |h| h.client.health() == Some(full))?;
+
+    // Short falls don't hurt
+    harness.client.land(2.)?;
+    harness.tick()?;
+    harness.tick()?;
+    assert_eq!(harness.client.health(), Some(full));
+
+    harness.client.land(6.)?;
+    harness.tick_until(5, |h| h.client.health() != Some(full))?;
+    let health = harness.client.health().unwrap();
+    assert!(health.current > 0 && health.current < MAX_HEALTH);
+
+    // Fatal falls kill, and respawning heals
+    harness.client.land(1000.)?;
+    harness.tick_until(5, |h| h.client.death_cause().is_some())?;
+    assert_eq!(harness.client.death_cause(), Some("Fell from a high place"));
+    assert_eq!(harness.client.health().map(|h| h.current), Some(0));
+    harness.client.respawn()?;
+    harness.tick_until(5, |h| h.client.health() == Some(full))?;
+    assert_eq!(harness.client.death_cause(), None);
+    Ok(())
+}
+
+#[test]
+fn time_sync() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    let (commands, console) = Console::channel();
+    harness.server.set_console(console);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    harness.tick_until(5, |h| h.client.time().is_some())?;
+
+    // Setting the time is sent right away
+    let noon = DAY_LENGTH * NOON as f64;
+    commands.send("/time set noon".to_owned())?;
+    harness.tick_until(5, |h| {
+        h.client
+            .time()
+            .map_or(false, |time| (time - noon).abs() < 1.)
+    })?;
+    Ok(())
+}
+
+#[test]
+fn reach() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    let (commands, console) = Console::channel();
+    harness.server.set_console(console);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    harness.tick_until(5, |h| h.client.reach().is_some())?;
+    let reach = harness.server.game().reach();
+    assert_eq!(harness.client.reach(), Some(reach.survival));
+
+    // Blocks out of reach can't be edited
+    let spawn = harness.client.pos().unwrap().block();
+    let far = spawn.offset(reach.survival as i32 + 3, 0, 0);
+    harness.tick_until(20, |h| h.client.block(far).is_some())?;
+    let stone = BlockId::new(blocks::Stone);
+    harness.client.place_block(far, stone)?;
+    harness.tick()?;
+    harness.tick()?;
+    assert_eq!(server_block(&harness, far), Some(BlockId::new(blocks::Air)));
+
+    // Creative mode reaches further
+    commands.send("/gamemode creative".to_owned())?;
+    harness.tick_until(5, |h| h.client.reach() == Some(reach.creative))?;
+    harness.client.place_block(far, stone)?;
+    harness.tick_until(5, |h| h.client.block(far) == Some(stone))?;
+    assert_eq!(server_block(&harness, far), Some(stone));
+    Ok(())
+}
+
+#[test]
+fn inventory() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    let (commands, console) = Console::channel();
+    harness.server.set_console(console);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    harness.tick_until(5, |h| h.client.inventory().is_some())?;
+    let stone = ItemId::from_slug("stone").unwrap();
+    let inventory = harness.client.inventory().unwrap();
+    assert_eq!(
+        inventory.selected_stack().map(|stack| stack.item),
+        Some(stone)
+    );
+
+    harness.client.select_slot(3)?;
+    harness.tick()?;
+    assert_eq!(server_inventory(&harness).unwrap().selected(), 3);
+
+    // Invalid slots are refused and the inventory is sent again
+    harness.client.select_slot(50)?;
+    harness.tick()?;
+    assert_eq!(harness.client.inventory().unwrap().selected(), 3);
+
+    let melium = ItemId::from_slug("melium").unwrap();
+    commands.send(format!("/give {} melium 5", USERNAME))?;
+    harness.tick_until(5, |h| {
+        h.client.inventory().map_or(false, |inventory| {
+            inventory.slots().contains(&Some(ItemStack::new(melium, 5)))
+        })
+    })?;
+    assert_eq!(
+        harness.client.inventory(),
+        server_inventory(&harness).as_ref()
+    );
+    Ok(())
+}
+
+#[test]
+fn resume_after_connection_loss() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    let (commands, console) = Console::channel();
+    harness.server.set_console(console);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    harness.tick_until(20, |h| h.client.chunks().len() > 0)?;
+    let reach = harness.server.game().reach();
+
+    // The player stays while the connection is down,
+    // and packets sent to them are held back
+    harness.client.lose_connection();
+    commands.send("/gamemode creative".to_owned())?;
+    harness.tick()?;
+    harness.tick()?;
+    assert!(player_pos(&harness).is_some());
+    assert_eq!(harness.server.game().suspended().len(), 1);
+
+    harness.reconnect()?;
+    harness.tick_until(5, |h| h.client.is_in_game())?;
+    harness.tick_until(5, |h| h.client.reach() == Some(reach.creative))?;
+    assert!(harness.server.game().suspended().is_empty());
+    harness.tick()?;
+    assert_eq!(mismatched_chunks(&harness.client, &harness.server), vec![]);
+
+    let game = harness.server.game();
+    let mut query = game.ecs().query::<&Username>();
+    assert_eq!(query.iter().count(), 1);
+    Ok(())
+}
+
+#[test]
+fn pings_are_answered() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+
+    harness.client.ping()?;
+    harness.client.ping()?;
+    harness.tick()?;
+    assert_eq!(harness.client.take_rtts().len(), 2);
+    assert!(harness.client.take_rtts().is_empty());
+    assert_eq!(
+        harness.client.drop_unanswered_pings(Duration::from_secs(0)),
+        0
+    );
+    Ok(())
+}
+
+#[test]
+fn chunk_cache() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    let pos = harness.client.pos().unwrap().block();
+    harness.tick_until(20, |h| h.client.block(pos).is_some())?;
+    assert_eq!(harness.client.cache_hits(), 0);
+
+    // Edited after it was cached, so it will be sent again
+    let stone = BlockId::new(blocks::Stone);
+    harness.client.place_block(pos, stone)?;
+    harness.tick_until(5, |h| h.client.block(pos) == Some(stone))?;
+    let loaded = harness.client.chunks().len();
+
+    // Rejoining loads the unchanged chunks from the cache
+    harness.client.lose_connection();
+    harness.tick()?;
+    harness.reconnect()?;
+    harness.tick_until(5, |h| h.client.is_in_game())?;
+    harness.tick_until(5, |h| h.client.cache_hits() > 0)?;
+    harness.tick()?;
+    harness.tick()?;
+    assert!(harness.client.cache_hits() < loaded);
+    assert_eq!(harness.client.block(pos), Some(stone));
+    assert_eq!(mismatched_chunks(&harness.client, &harness.server), vec![]);
+    Ok(())
+}
+
+#[test]
+fn biomes_are_synced() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    let spawn = harness.client.pos().unwrap().block();
+    harness.tick_until(20, |h| h.client.block(spawn).is_some())?;
+
+    let zone = harness.server.game().main_zone();
+    for dx in (-16..16).step_by(4) {
+        for dz in (-16..16).step_by(4) {
+            let pos = spawn.offset(dx, 0, dz);
+            let biome = zone.biome_at(pos);
+            assert!(biome.is_some(), "no biome stored at {:?}", pos);
+            if harness.client.block(pos).is_some() {
+                assert_eq!(harness.client.biome(pos), biome);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn view_distance() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    harness.tick_until(20, |h| h.client.is_in_game())?;
+    let spawn_chunk = ChunkPos::from_pos(harness.client.pos().unwrap().local);
+    let within = |h: &Harness, distance: i32| {
+        h.client.chunks().chunks().all(|(pos, _)| {
+            (pos.x - spawn_chunk.x).abs() <= distance
+                && (pos.y - spawn_chunk.y).abs() <= distance
+                && (pos.z - spawn_chunk.z).abs() <= distance
+        })
+    };
+    harness.tick_until(20, |h| h.client.chunks().len() > 0)?;
+    let view_distance = smoke_test::VIEW_DISTANCE as i32;
+    assert!(within(&harness, view_distance));
+    assert_eq!(
+        harness.client.view_distance(),
+        Some(smoke_test::VIEW_DISTANCE)
+    );
+
+    // Shrinking the view unloads the chunks outside it
+    harness.client.set_view_distance(3)?;
+    harness.tick()?;
+    harness.tick()?;
+    assert!(within(&harness, 3));
+    assert_eq!(harness.client.view_distance(), Some(3));
+    assert_eq!(mismatched_chunks(&harness.client, &harness.server), vec![]);
+
+    // Distances are clamped to the server's maximum
+    harness.client.set_view_distance(1000)?;
+    harness.tick()?;
+    harness.tick()?;
+    let game = harness.server.game();
+    let mut query = game.ecs().query::<&View>();
+    let (_, view) = query.iter().next().unwrap();
+    assert_eq!(view.distance(), game.max_view_distance());
+    assert_eq!(harness.client.view_distance(), Some(view.distance()));
+    Ok(())
+}
+
+#[test]
+fn required_resource_pack() -> anyhow::Result<()> {
+    let mut harness = Harness::new(USERNAME);
+    let pack = ResourcePack {
+        url: "https://example.com/pack.tar".to_owned(),
+        hash: PackHash::of(b"pack"),
+    };
+    harness.server.set_resource_pack(Some(pack.clone()));
+
+    // The server waits until the pack is loaded
+    harness.tick_until(20, |h| h.client.resource_pack().is_some())?;
+    assert_eq!(harness.client.resource_pack(), Some(&pack));
+    for _ in 0..5 {
+        harness.tick()?;
+    }
+    assert!(!harness.client.is_in_game());
+    assert!(player_pos(&harness).is_none());
+
+    harness.client.load_resource_pack()?;
+    harness.tick_until(5, |h| h.client.is_in_game())?;
+    assert!(player_pos(&harness).is_some());
+    Ok(())
+}
+
+fn server_block(harness: &Harness, pos: BlockPos) -> Option<BlockId> {
+    harness.server.game().main_zone().block(pos)
+}
+
+/// Gets the inventory of the test player on the server.
+fn server_inventory(harness: &Harness) -> Option<Inventory> {
+    let game = harness.server.game();
+    let mut query = game.ecs().query::<&Inventory>();
+    let inventory = query.iter().next().map(|(_, inventory)| inventory.clone());
+    inventory
+}
+
+/// Gets the entity of the test player on the server.
+fn player_entity(harness: &Harness) -> Option<Entity> {
+    let game = harness.server.game();
+    let mut query = game.ecs().query::<&Username>();
+    let player = query
+        .iter()
+        .find(|(_, username)| username.0 == USERNAME)
+        .map(|(player, _)| player);
+    player
+}
+
+/// Gets the position of the test player on the server.
+fn player_pos(harness: &Harness) -> Option<WorldPos> {
+    let game = harness.server.game();
+    let mut query = game.ecs().query::<(&Username, &Pos)>();
+    let pos = query
+        .iter()
+        .find(|(_, (username, _))| username.0 == USERNAME)
+        .map(|(_, (_, pos))| pos.0);
+    pos
+}
@@ -0,0 +1,306 @@
+//! Time-driven animations of widget properties.
+//!
+//! A [`Tween`] interpolates a value from a start to a target
+//! over some duration using an [`Easing`] function. Tweens
+//! stored in a [`Ui`](crate::Ui) persist across calls to `build()`
+//! and are advanced by [`Ui::advance`](crate::Ui::advance).
+
+use std::{
+    any::Any,
+    hash::{Hash, Hasher},
+    panic::Location,
+};
+
+use ahash::{AHashMap, AHasher};
+use glam::Vec2;
+use utils::Color;
+
+/// A value which can be linearly interpolated.
+pub trait Lerp: Copy + PartialEq + 'static {
+    /// Interpolates between `self` and `other`. `t` is in `[0, 1]`.
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Vec2 {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Color {
+    fn lerp(self, other: Self, t: f32) -> Self {
+        Color::rgba(
+            self.r.lerp(other.r, t),
+            self.g.lerp(other.g, t),
+            self.b.lerp(other.b, t),
+            self.a.lerp(other.a, t),
+        )
+    }
+}
+
+/// An easing function, mapping linear progress in `[0, 1]`
+/// to eased progress in `[0, 1]`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    /// Quadratic ease in: starts slow.
+    EaseIn,
+    /// Quadratic ease out: ends slow.
+    EaseOut,
+    /// Cubic ease in and out: starts and ends slow.
+    EaseInOut,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::EaseInOut
+    }
+}
+
+impl Easing {
+    /// Applies the easing function to `t`. The input is
+    /// clamped to `[0, 1]`.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.).min(1.);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2. - t),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4. * t * t * t
+                } else {
+                    let u = -2. * t + 2.;
+                    1. - u * u * u / 2.
+                }
+            }
+        }
+    }
+}
+
+/// A value animated from a start value to a target value.
+#[derive(Copy, Clone, Debug)]
+pub struct Tween<T> {
+    from: T,
+    to: T,
+    duration: f32,
+    elapsed: f32,
+    easing: Easing,
+}
+
+impl<T: Lerp> Tween<T> {
+    /// Creates a tween which animates from `from` to `to`
+    /// over `duration` seconds.
+    pub fn new(from: T, to: T, duration: f32, easing: Easing) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            elapsed: 0.,
+            easing,
+        }
+    }
+
+    /// Creates a tween which is already finished at `value`.
+    pub fn fixed(value: T) -> Self {
+        Self::new(value, value, 0., Easing::Linear)
+    }
+
+    /// Advances the animation by `dt` seconds.
+    pub fn advance(&mut self, dt: f32) {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+    }
+
+    /// Returns the animation progress in `[0, 1]` before easing.
+    pub fn progress(&self) -> f32 {
+        if self.duration <= 0. {
+            1.
+        } else {
+            self.elapsed / self.duration
+        }
+    }
+
+    /// Gets the current value of the animation.
+    pub fn value(&self) -> T {
+        self.from.lerp(self.to, self.easing.apply(self.progress()))
+    }
+
+    /// Returns the value this tween is animating toward.
+    pub fn target(&self) -> T {
+        self.to
+    }
+
+    /// Returns whether the animation has reached its target.
+    pub fn is_finished(&self) -> bool {
+        self.progress() >= 1.
+    }
+
+    /// Starts animating toward a new target from the current value.
+    /// Does nothing if `to` is already the target.
+    pub fn retarget(&mut self, to: T, duration: f32, easing: Easing) {
+        if to == self.to {
+            return;
+        }
+        *self = Self::new(self.value(), to, duration, easing);
+    }
+}
+
+/// Identifies an animation in an [`Animations`] store.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AnimationKey {
+    location: &'static Location<'static>,
+    id: u64,
+}
+
+impl AnimationKey {
+    /// Creates a key from the caller's source location and
+    /// an additional ID, which disambiguates animations
+    /// created at the same location (e.g. in a loop).
+    #[track_caller]
+    pub fn new(id: impl Hash) -> Self {
+        let mut hasher = AHasher::default();
+        id.hash(&mut hasher);
+        Self {
+            location: Location::caller(),
+            id: hasher.finish(),
+        }
+    }
+}
+
+trait ErasedTween {
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn advance(&mut self, dt: f32);
+    fn is_finished(&self) -> bool;
+}
+
+impl<T: Lerp> ErasedTween for Tween<T> {
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn advance(&mut self, dt: f32) {
+        Tween::advance(self, dt);
+    }
+
+    fn is_finished(&self) -> bool {
+        Tween::is_finished(self)
+    }
+}
+
+struct AnimationSlot {
+    tween: Box<dyn ErasedTween>,
+    /// Whether the animation was accessed since the last call to `retain_accessed()`.
+    accessed: bool,
+}
+
+/// Stores the tweens of a UI across rebuilds.
+///
+/// An animation is dropped if it is not accessed
+/// for an entire build of the UI.
+#[derive(Default)]
+pub struct Animations {
+    slots: AHashMap<AnimationKey, AnimationSlot>,
+}
+
+impl Animations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the current value of the animation with the given key,
+    /// animating toward `target`. If the animation does not exist yet,
+    /// it starts at `target`; if `target` has changed since the last call,
+    /// the value is animated from its current value to the new target.
+    ///
+    /// # Panics
+    /// Panics if the animation with this key was created with a different type.
+    pub fn animate<T: Lerp>(
+        &mut self,
+        key: AnimationKey,
+        target: T,
+        duration: f32,
+        easing: Easing,
+    ) -> T {
+        let slot = self.slots.entry(key).or_insert_with(|| AnimationSlot {
+            tween: Box::new(Tween::fixed(target)),
+            accessed: true,
+        });
+        slot.accessed = true;
+        let tween = slot
+            .tween
+            .as_any_mut()
+            .downcast_mut::<Tween<T>>()
+            .expect("mismatched animation types");
+        tween.retarget(target, duration, easing);
+        tween.value()
+    }
+
+    /// Advances all animations by `dt` seconds.
+    ///
+    /// Returns whether any animation was still running,
+    /// i.e. whether any animated value may have changed.
+    pub fn advance(&mut self, dt: f32) -> bool {
+        let mut running = false;
+        for slot in self.slots.values_mut() {
+            running |= !slot.tween.is_finished();
+            slot.tween.advance(dt);
+        }
+        running
+    }
+
+    /// Drops animations which were not accessed since the
+    /// previous call to this function.
+    pub(crate) fn retain_accessed(&mut self) {
+        self.slots.retain(|_, slot| {
+            let keep = slot.accessed;
+            slot.accessed = false;
+            keep
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn easing_endpoints() {
+        for &easing in &[
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_eq!(easing.apply(0.), 0.);
+            assert_eq!(easing.apply(1.), 1.);
+            assert_eq!(easing.apply(2.), 1.);
+        }
+    }
+
+    #[test]
+    fn tween_reaches_target() {
+        let mut tween = Tween::new(0.0f32, 10., 1., Easing::Linear);
+        assert_eq!(tween.value(), 0.);
+        tween.advance(0.5);
+        assert_eq!(tween.value(), 5.);
+        tween.advance(1.);
+        assert!(tween.is_finished());
+        assert_eq!(tween.value(), 10.);
+    }
+
+    #[test]
+    fn animations_retarget() {
+        let mut animations = Animations::new();
+        let key = AnimationKey::new(0);
+        assert_eq!(animations.animate(key, 1.0f32, 1., Easing::Linear), 1.);
+        assert_eq!(animations.animate(key, 3.0f32, 1., Easing::Linear), 1.);
+        animations.advance(0.5);
+        assert_eq!(animations.animate(key, 3.0f32, 1., Easing::Linear), 2.);
+    }
+}
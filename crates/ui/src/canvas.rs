@@ -9,10 +9,12 @@ use fontdue::{
     layout::{GlyphRasterConfig, Layout, LayoutSettings, TextStyle, WrapStyle},
     Font,
 };
-use glam::Vec2;
+use glam::{vec2, Vec2};
 use tiny_skia::{ColorU8, Pixmap, PixmapPaint};
 use utils::{Color, Rect};
 
+use crate::theme::Insets;
+
 #[doc(inline)]
 pub use tiny_skia::{BlendMode, FillRule, FilterQuality, LineCap, LineJoin};
 
@@ -53,6 +55,11 @@ impl PathBuilder {
         self
     }
 
+    pub fn close(mut self) -> Self {
+        self.0.close();
+        self
+    }
+
     pub fn finish(self) -> Path {
         Path(self.0.finish().expect("invalid path"))
     }
@@ -60,6 +67,10 @@ impl PathBuilder {
 
 pub struct Path(tiny_skia::Path);
 
+/// The ratio of a circle's radius at which the control points
+/// of a cubic Bézier curve approximating a quarter circle lie.
+const CIRCLE_KAPPA: f32 = 0.552_284_8;
+
 impl Path {
     pub fn builder() -> PathBuilder {
         PathBuilder::new()
@@ -75,6 +86,48 @@ impl Path {
     pub fn rect(rect: Rect) -> Self {
         Self(tiny_skia::PathBuilder::from_rect(tsk_rect(rect)))
     }
+
+    /// A rectangle with its corners rounded to `radius`, which
+    /// is clamped to half the length of the shorter side.
+    pub fn rounded_rect(rect: Rect, radius: f32) -> Self {
+        let radius = radius.min(rect.size.x / 2.).min(rect.size.y / 2.);
+        if radius <= 0. {
+            return Self::rect(rect);
+        }
+        let min = rect.pos;
+        let max = rect.pos + rect.size;
+        // Distance from each corner to the control points of the
+        // cubic curve approximating its quarter circle.
+        let k = radius * (1. - CIRCLE_KAPPA);
+        Self::builder()
+            .move_to(vec2(min.x + radius, min.y))
+            .line_to(vec2(max.x - radius, min.y))
+            .cubic_to(
+                vec2(max.x - k, min.y),
+                vec2(max.x, min.y + k),
+                vec2(max.x, min.y + radius),
+            )
+            .line_to(vec2(max.x, max.y - radius))
+            .cubic_to(
+                vec2(max.x, max.y - k),
+                vec2(max.x - k, max.y),
+                vec2(max.x - radius, max.y),
+            )
+            .line_to(vec2(min.x + radius, max.y))
+            .cubic_to(
+                vec2(min.x + k, max.y),
+                vec2(min.x, max.y - k),
+                vec2(min.x, max.y - radius),
+            )
+            .line_to(vec2(min.x, min.y + radius))
+            .cubic_to(
+                vec2(min.x, min.y + k),
+                vec2(min.x + k, min.y),
+                vec2(min.x + radius, min.y),
+            )
+            .close()
+            .finish()
+    }
 }
 
 pub struct Paint<'a>(tiny_skia::Paint<'a>);
@@ -145,6 +198,7 @@ pub struct TextSettings {
     pub align_h: HorizontalAlign,
     pub align_v: VerticalAlign,
     pub size: f32,
+    pub color: Color,
     pub pos: Vec2,
     pub max_width: Option<f32>,
     pub max_height: Option<f32>,
@@ -154,6 +208,7 @@ impl Debug for TextSettings {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("TextSettings")
             .field("size", &self.size)
+            .field("color", &self.color)
             .field("pos", &self.pos)
             .field("max_width", &self.max_width)
             .field("max_height", &self.max_height)
@@ -250,7 +305,7 @@ impl Canvas {
             .entry(settings.font.deref() as *const Font)
             .or_default();
         for glyph in self.layout_engine.glyphs() {
-            let pixmap = glyph_cache.glyph(&settings.font, glyph.key);
+            let pixmap = glyph_cache.glyph(&settings.font, glyph.key, settings.color);
             if let Some(pixmap) = pixmap {
                 self.target.draw_pixmap(
                     glyph.x as i32,
@@ -265,6 +320,45 @@ impl Canvas {
         }
     }
 
+    /// Draws a nine-patch image stretched to fill `bounds`.
+    ///
+    /// If `bounds` is smaller than the image's insets,
+    /// the corners are shrunk to fit.
+    pub fn draw_nine_patch(&mut self, nine_patch: &NinePatch, bounds: Rect) {
+        let insets = nine_patch.insets;
+        let shrink_x = (bounds.size.x / (insets.left + insets.right).max(1) as f32).min(1.);
+        let shrink_y = (bounds.size.y / (insets.top + insets.bottom).max(1) as f32).min(1.);
+        let xs = [
+            bounds.pos.x,
+            bounds.pos.x + insets.left as f32 * shrink_x,
+            bounds.pos.x + bounds.size.x - insets.right as f32 * shrink_x,
+            bounds.pos.x + bounds.size.x,
+        ];
+        let ys = [
+            bounds.pos.y,
+            bounds.pos.y + insets.top as f32 * shrink_y,
+            bounds.pos.y + bounds.size.y - insets.bottom as f32 * shrink_y,
+            bounds.pos.y + bounds.size.y,
+        ];
+
+        for (i, slice) in nine_patch.slices.iter().enumerate() {
+            let (row, col) = (i / 3, i % 3);
+            if let Some(slice) = slice {
+                let dest = Rect {
+                    pos: Vec2::new(xs[col], ys[row]),
+                    size: Vec2::new(xs[col + 1] - xs[col], ys[row + 1] - ys[row]),
+                };
+                self.draw_pixmap_scaled(slice, dest, FilterQuality::Bilinear);
+            }
+        }
+    }
+
+    /// Draws a texture scaled to fill `dest`, sampling it with `quality`.
+    /// [`FilterQuality::Nearest`] keeps pixel art sharp.
+    pub fn draw_image(&mut self, texture: &Texture, dest: Rect, quality: FilterQuality) {
+        self.draw_pixmap_scaled(&texture.pixmap, dest, quality);
+    }
+
     pub fn data(&self) -> &[u8] {
         self.target.pixmap.data()
     }
@@ -276,6 +370,27 @@ impl Canvas {
             .expect("failed to save PNG")
     }
 
+    fn draw_pixmap_scaled(&mut self, pixmap: &Pixmap, dest: Rect, quality: FilterQuality) {
+        if dest.size.x <= 0. || dest.size.y <= 0. {
+            return;
+        }
+        self.target.translate(dest.pos.x, dest.pos.y);
+        self.target.scale(
+            dest.size.x / pixmap.width() as f32,
+            dest.size.y / pixmap.height() as f32,
+        );
+        self.target.draw_pixmap(
+            0,
+            0,
+            pixmap,
+            &PixmapPaint {
+                quality,
+                ..Default::default()
+            },
+        );
+        self.apply_scale();
+    }
+
     fn set_scale(&mut self, new_scale: f32) {
         self.scale = new_scale;
         self.apply_scale();
@@ -291,15 +406,138 @@ impl Canvas {
     }
 }
 
+/// An RGBA image which can be drawn with [`Canvas::draw_image`].
+pub struct Texture {
+    pixmap: Pixmap,
+}
+
+impl Texture {
+    /// Creates a texture from RGBA8 pixel data which
+    /// is not premultiplied.
+    ///
+    /// # Panics
+    /// Panics if `data` is not `width * height * 4` bytes long
+    /// or if the texture is empty.
+    pub fn from_rgba(data: &[u8], width: u32, height: u32) -> Self {
+        assert_eq!(
+            data.len(),
+            width as usize * height as usize * 4,
+            "data length does not match dimensions"
+        );
+        let pixmap = pixmap_from_rgba(data, width, (0, width), (0, height)).expect("empty texture");
+        Self { pixmap }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.pixmap.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.pixmap.height()
+    }
+}
+
+impl Debug for Texture {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Texture")
+            .field("width", &self.width())
+            .field("height", &self.height())
+            .finish()
+    }
+}
+
+/// An image divided into nine slices by its [`Insets`].
+///
+/// When drawn, the corners keep their size, the edges
+/// stretch along one axis, and the center stretches along both.
+pub struct NinePatch {
+    /// Slices in row-major order starting at the top left.
+    /// Slices with zero area are `None`.
+    slices: [Option<Pixmap>; 9],
+    insets: Insets,
+}
+
+impl NinePatch {
+    /// Creates a nine-patch from RGBA8 pixel data which
+    /// is not premultiplied.
+    ///
+    /// # Panics
+    /// Panics if `data` is not `width * height * 4` bytes long
+    /// or if the insets are larger than the image.
+    pub fn from_rgba(data: &[u8], width: u32, height: u32, insets: Insets) -> Self {
+        assert_eq!(
+            data.len(),
+            width as usize * height as usize * 4,
+            "data length does not match dimensions"
+        );
+        assert!(
+            insets.left + insets.right <= width && insets.top + insets.bottom <= height,
+            "insets larger than image"
+        );
+
+        let xs = [0, insets.left, width - insets.right, width];
+        let ys = [0, insets.top, height - insets.bottom, height];
+
+        let mut slices: [Option<Pixmap>; 9] = Default::default();
+        for (i, slice) in slices.iter_mut().enumerate() {
+            let (row, col) = (i / 3, i % 3);
+            *slice = pixmap_from_rgba(data, width, (xs[col], xs[col + 1]), (ys[row], ys[row + 1]));
+        }
+
+        Self { slices, insets }
+    }
+
+    pub fn insets(&self) -> Insets {
+        self.insets
+    }
+}
+
+impl Debug for NinePatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NinePatch")
+            .field("insets", &self.insets)
+            .finish()
+    }
+}
+
+/// Copies the pixels of an RGBA8 image of the given width within
+/// `x0..x1` and `y0..y1` into a pixmap, premultiplying them.
+/// Returns `None` if the area is empty.
+fn pixmap_from_rgba(
+    data: &[u8],
+    width: u32,
+    (x0, x1): (u32, u32),
+    (y0, y1): (u32, u32),
+) -> Option<Pixmap> {
+    let mut pixmap = Pixmap::new(x1 - x0, y1 - y0)?;
+    let area_width = (x1 - x0) as usize;
+    for (j, pixel) in pixmap.pixels_mut().iter_mut().enumerate() {
+        let x = x0 as usize + j % area_width;
+        let y = y0 as usize + j / area_width;
+        let index = (y * width as usize + x) * 4;
+        let [r, g, b, a] = [
+            data[index],
+            data[index + 1],
+            data[index + 2],
+            data[index + 3],
+        ];
+        *pixel = ColorU8::from_rgba(r, g, b, a).premultiply();
+    }
+    Some(pixmap)
+}
+
+/// Caches rasterized glyphs, keyed by
+/// raster config and RGBA8 color.
 #[derive(Default)]
 struct FontGlyphCache {
-    glyphs: AHashMap<GlyphRasterConfig, Option<Pixmap>>,
+    glyphs: AHashMap<(GlyphRasterConfig, [u8; 4]), Option<Pixmap>>,
 }
 
 impl FontGlyphCache {
-    pub fn glyph(&mut self, font: &Font, key: GlyphRasterConfig) -> Option<&Pixmap> {
+    pub fn glyph(&mut self, font: &Font, key: GlyphRasterConfig, color: Color) -> Option<&Pixmap> {
+        let color = color_to_u8(color);
         self.glyphs
-            .entry(key)
+            .entry((key, color))
             .or_insert_with(|| {
                 let (metrics, bitmap) = font.rasterize_config(key);
                 if metrics.width == 0 || metrics.height == 0 {
@@ -309,6 +547,7 @@ impl FontGlyphCache {
                         &bitmap,
                         metrics.width as u32,
                         metrics.height as u32,
+                        color,
                     ))
                 }
             })
@@ -316,14 +555,75 @@ impl FontGlyphCache {
     }
 }
 
-fn coverage_to_pixmap(coverage: &[u8], width: u32, height: u32) -> Pixmap {
+fn color_to_u8(color: Color) -> [u8; 4] {
+    let channel = |c: f32| (c.max(0.).min(1.) * 255.).round() as u8;
+    [
+        channel(color.r),
+        channel(color.g),
+        channel(color.b),
+        channel(color.a),
+    ]
+}
+
+fn coverage_to_pixmap(coverage: &[u8], width: u32, height: u32, color: [u8; 4]) -> Pixmap {
+    let [r, g, b, a] = color;
     let mut pixmap = Pixmap::new(width, height).expect("pixmap of size 0");
     pixmap
         .pixels_mut()
         .iter_mut()
         .zip(coverage.iter().copied())
         .for_each(|(pixel, coverage)| {
-            *pixel = ColorU8::from_rgba(u8::MAX, u8::MAX, u8::MAX, coverage).premultiply();
+            let alpha = (coverage as u16 * a as u16 / u8::MAX as u16) as u8;
+            *pixel = ColorU8::from_rgba(r, g, b, alpha).premultiply();
         });
     pixmap
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_scaled_images() {
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        let texture = Texture::from_rgba(&[red, blue].concat(), 2, 1);
+        let mut canvas = Canvas::new(4, 2, 1.);
+        canvas.clear(Color::rgba(0., 0., 0., 0.));
+        canvas.draw_image(
+            &texture,
+            Rect {
+                pos: Vec2::zero(),
+                size: Vec2::new(4., 2.),
+            },
+            FilterQuality::Nearest,
+        );
+
+        let pixel = |x: usize, y: usize| &canvas.data()[(y * 4 + x) * 4..][..4];
+        assert_eq!(pixel(0, 0), red);
+        assert_eq!(pixel(1, 1), red);
+        assert_eq!(pixel(2, 0), blue);
+        assert_eq!(pixel(3, 1), blue);
+    }
+
+    #[test]
+    fn rounds_corners() {
+        let mut canvas = Canvas::new(20, 20, 1.);
+        canvas.clear(Color::rgba(0., 0., 0., 0.));
+        let rect = Rect {
+            pos: Vec2::zero(),
+            size: Vec2::splat(20.),
+        };
+        canvas.fill_path(
+            &Path::rounded_rect(rect, 8.),
+            &Paint::new().shade_solid(Color::rgb(1., 1., 1.)),
+        );
+
+        let pixel = |x: usize, y: usize| &canvas.data()[(y * 20 + x) * 4..][..4];
+        let white = [255, 255, 255, 255];
+        assert_eq!(pixel(0, 0)[3], 0);
+        assert_eq!(pixel(19, 19)[3], 0);
+        assert_eq!(pixel(10, 0), white);
+        assert_eq!(pixel(10, 10), white);
+    }
+}
@@ -0,0 +1,65 @@
+//! Pointer and keyboard input.
+//!
+//! Applications feed [`Event`]s to a [`Ui`](crate::Ui) with
+//! [`Ui::handle_event`](crate::Ui::handle_event). The UI hit-tests the
+//! pointer against the layout computed by its last render and delivers
+//! [`PointerEvent`]s to the widgets under it through
+//! [`WidgetState::on_event`](crate::WidgetState::on_event). Keyboard
+//! events move focus between widgets and activate the focused one.
+//! Widgets which the user can activate, like buttons, answer with
+//! [`Message`]s, which the application takes with
+//! [`Ui::take_messages`](crate::Ui::take_messages).
+
+use glam::Vec2;
+
+/// A button on a pointing device.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PointerButton {
+    Left,
+    Right,
+    Middle,
+}
+
+/// An input event fed to a UI.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The pointer moved to `pos`, in logical pixels
+    /// relative to the top-left corner of the UI.
+    PointerMoved { pos: Vec2 },
+    /// The pointer left the UI's window.
+    PointerLeft,
+    /// A button was pressed at the pointer's position.
+    PointerPressed { button: PointerButton },
+    /// A button was released at the pointer's position.
+    PointerReleased { button: PointerButton },
+    /// Focus should move to the next focusable widget, e.g. on Tab.
+    FocusNext,
+    /// Focus should move to the previous focusable
+    /// widget, e.g. on Shift-Tab.
+    FocusPrevious,
+    /// The focused widget should be activated, e.g. on Enter.
+    Activate,
+}
+
+/// A pointer event delivered to a widget.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PointerEvent {
+    /// The pointer moved onto the widget.
+    Enter,
+    /// The pointer moved off the widget.
+    Leave,
+    /// A button was pressed over the widget.
+    Press(PointerButton),
+    /// A button was released over the widget.
+    Release(PointerButton),
+}
+
+/// A message sent by a widget when the user activates it.
+/// Each names the widget with the ID the application gave it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// A [`Button`](crate::widgets::Button) was clicked.
+    Clicked(&'static str),
+    /// A [`Checkbox`](crate::widgets::Checkbox) was checked or unchecked.
+    Toggled(&'static str, bool),
+}
@@ -6,12 +6,18 @@
 //! * `stretch` for node layout
 //! * `std::panic::Location` for node stable identity
 
+pub mod animation;
 pub mod canvas;
+pub mod event;
+pub mod theme;
 pub mod ui;
 pub mod widget;
 pub mod widgets;
 
-pub use canvas::{Canvas, Path};
+pub use animation::{AnimationKey, Easing, Tween};
+pub use canvas::{Canvas, NinePatch, Path, Texture};
+pub use event::{Event, Message, PointerButton, PointerEvent};
+pub use theme::Theme;
 pub use ui::Ui;
 pub use widget::{WidgetData, WidgetState};
 
@@ -0,0 +1,118 @@
+//! Themes control the appearance of built-in widgets.
+//!
+//! A [`Theme`] is deserializable so that applications
+//! can load it from an asset file. It can be replaced
+//! at runtime with [`Ui::set_theme`](crate::Ui::set_theme).
+
+use std::{
+    fmt::{self, Debug, Formatter},
+    sync::Arc,
+};
+
+use fontdue::Font;
+use serde::{Deserialize, Serialize};
+use utils::Color;
+
+use crate::canvas::NinePatch;
+
+/// Insets of a nine-patch image, in pixels.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Insets {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+/// Colors, fonts, spacing, shapes, and images
+/// used by built-in widgets.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// Default color of text.
+    pub text_color: Color,
+    /// Default font size of text.
+    pub font_size: f32,
+    /// Font size of headings.
+    pub heading_font_size: f32,
+    /// Path to the font of text which doesn't set one.
+    /// Resolved by the application into `default_font`.
+    pub font: Option<String>,
+    /// The loaded default font.
+    #[serde(skip)]
+    pub default_font: Option<Arc<Font>>,
+    /// Padding between a panel's border and its children.
+    pub padding: f32,
+    /// Radius of the rounded corners of panels, buttons,
+    /// and checkboxes. Zero gives square corners.
+    pub corner_radius: f32,
+    /// Width of the border around panels. Zero disables borders.
+    pub border_width: f32,
+    /// Color of the border around panels.
+    pub border_color: Color,
+    /// Fill color of panels without a nine-patch image.
+    pub panel_color: Color,
+    /// Color of the ring drawn around the focused widget.
+    pub focus_ring_color: Color,
+    /// Fill color of buttons and checkboxes.
+    pub button_color: Color,
+    /// Fill color of buttons and checkboxes under the pointer.
+    pub button_hover_color: Color,
+    /// Fill color of buttons and checkboxes while pressed.
+    pub button_pressed_color: Color,
+    /// Path to the image drawn as a panel background.
+    /// Resolved by the application into `panel_nine_patch`.
+    pub panel_image: Option<String>,
+    /// Nine-slice insets of `panel_image`.
+    pub panel_insets: Insets,
+    /// The loaded panel background. Takes precedence
+    /// over `panel_color` if set.
+    #[serde(skip)]
+    pub panel_nine_patch: Option<Arc<NinePatch>>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            text_color: Color::rgb(1., 1., 1.),
+            font_size: 14.,
+            heading_font_size: 24.,
+            font: None,
+            default_font: None,
+            padding: 8.,
+            corner_radius: 0.,
+            border_width: 0.,
+            border_color: Color::rgba(1., 1., 1., 0.2),
+            panel_color: Color::rgba(0., 0., 0., 0.6),
+            focus_ring_color: Color::rgb(1., 1., 1.),
+            button_color: Color::rgba(0.2, 0.2, 0.2, 0.8),
+            button_hover_color: Color::rgba(0.35, 0.35, 0.35, 0.8),
+            button_pressed_color: Color::rgba(0.1, 0.1, 0.1, 0.8),
+            panel_image: None,
+            panel_insets: Insets::default(),
+            panel_nine_patch: None,
+        }
+    }
+}
+
+impl Debug for Theme {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Theme")
+            .field("text_color", &self.text_color)
+            .field("font_size", &self.font_size)
+            .field("heading_font_size", &self.heading_font_size)
+            .field("font", &self.font)
+            .field("padding", &self.padding)
+            .field("corner_radius", &self.corner_radius)
+            .field("border_width", &self.border_width)
+            .field("border_color", &self.border_color)
+            .field("panel_color", &self.panel_color)
+            .field("focus_ring_color", &self.focus_ring_color)
+            .field("button_color", &self.button_color)
+            .field("button_hover_color", &self.button_hover_color)
+            .field("button_pressed_color", &self.button_pressed_color)
+            .field("panel_image", &self.panel_image)
+            .field("panel_insets", &self.panel_insets)
+            .finish()
+    }
+}
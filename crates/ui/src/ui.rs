@@ -1,6 +1,17 @@
-use std::{cell::RefCell, rc::Rc, sync::atomic::AtomicU64};
+use std::{
+    any::Any,
+    cell::RefCell,
+    panic::Location,
+    rc::Rc,
+    sync::{atomic::AtomicU64, Arc},
+};
 
-use crate::{Canvas, WidgetData, WidgetState};
+use crate::{
+    animation::{AnimationKey, Animations, Easing, Lerp},
+    canvas::{Paint, Stroke},
+    widget::ChangeList,
+    Canvas, Event, Message, Path, PointerEvent, Theme, WidgetData, WidgetState,
+};
 use ahash::AHashMap;
 use glam::{vec2, Vec2};
 use stretch::{
@@ -23,12 +34,56 @@ impl NodeId {
     }
 }
 
+/// Width of the ring drawn around the focused widget.
+const FOCUS_RING_WIDTH: f32 = 2.;
+
+/// Identifies a node across rebuilds of the UI.
+///
+/// Node IDs change each time the UI is built, so focus and hover
+/// are tracked by the source location which created the widget,
+/// disambiguated by the number of preceding widgets created
+/// at the same location.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct WidgetKey {
+    location: &'static Location<'static>,
+    occurrence: usize,
+}
+
 /// Stores the persistent node tree.
 pub struct Ui {
     stretch: Stretch,
     root_stretch_node: Node,
 
     tree: Tree,
+
+    /// Animated values which persist across builds.
+    animations: Animations,
+
+    /// The widget with keyboard focus.
+    focused: Option<WidgetKey>,
+
+    /// The pointer's position, if it is over the window.
+    pointer: Option<Vec2>,
+    /// The widgets under the pointer as of the last hit test.
+    hovered: Vec<WidgetKey>,
+    /// The size the layout was last computed for
+    /// by `render()`, used for hit testing.
+    layout_size: Option<Vec2>,
+    /// The number of widgets created at each source
+    /// location since the UI was last built.
+    occurrences: AHashMap<&'static Location<'static>, usize>,
+    /// The widget states of the previous build, which
+    /// persistent widgets of the current build reuse.
+    previous: AHashMap<WidgetKey, Rc<dyn Any>>,
+
+    /// Messages sent by widgets and not yet taken.
+    messages: Vec<Message>,
+
+    theme: Arc<Theme>,
+
+    /// Whether the node tree has changed since
+    /// the last call to `render()`.
+    dirty: bool,
 }
 
 impl Ui {
@@ -54,18 +109,61 @@ impl Ui {
             stretch,
             root_stretch_node,
             tree,
+            animations: Animations::new(),
+            focused: None,
+            pointer: None,
+            hovered: Vec::new(),
+            layout_size: None,
+            occurrences: AHashMap::new(),
+            previous: AHashMap::new(),
+            messages: Vec::new(),
+            theme: Arc::new(Theme::default()),
+            dirty: true,
+        }
+    }
+
+    /// Gets the theme used to draw widgets.
+    pub fn theme(&self) -> &Arc<Theme> {
+        &self.theme
+    }
+
+    /// Sets the theme used to draw widgets. Takes
+    /// effect the next time the UI is built.
+    pub fn set_theme(&mut self, theme: Arc<Theme>) {
+        self.theme = theme;
+        self.dirty = true;
+    }
+
+    /// Advances all animations by `dt` seconds.
+    /// Should be called once per frame.
+    pub fn advance(&mut self, dt: f32) {
+        if self.animations.advance(dt) {
+            self.dirty = true;
         }
     }
 
+    /// Returns whether the UI has been rebuilt, or had running
+    /// animations, since the last call to `render()`. If this returns
+    /// `false`, then the previously rendered canvas is still up to date.
+    pub fn needs_redraw(&self) -> bool {
+        self.dirty
+    }
+
     /// Returns a `UiBuilder` to build the UI. New widgets
-    /// are added to the UI, widgets from the previous
-    /// `build()` call are persited, and missing widgets are removed.
+    /// are added to the UI, [persistent](WidgetData::is_persistent)
+    /// widgets from the previous `build()` call are updated, and
+    /// other widgets are replaced or removed.
     pub fn build(&mut self) -> UiBuilder {
         self.tree.children.clear();
         self.tree.roots.clear();
+        self.previous.clear();
         for (_, slot) in self.tree.nodes.drain() {
             self.stretch.remove(slot.stretch_node);
+            self.previous.insert(slot.key, slot.state);
         }
+        self.occurrences.clear();
+        self.animations.retain_accessed();
+        self.dirty = true;
         UiBuilder {
             ui: self,
             parent_stack: Vec::new(),
@@ -74,18 +172,247 @@ impl Ui {
 
     /// Renders to the canvas. Does not clear.
     pub fn render(&mut self, canvas: &mut Canvas) {
-        self.compute_layout(canvas.width(), canvas.height());
-        let Self { stretch, .. } = self;
+        let size = vec2(canvas.width(), canvas.height());
+        self.compute_layout(size.x, size.y);
+        self.layout_size = Some(size);
+        // The widgets under the pointer change when the UI is rebuilt.
+        self.update_hover();
+
+        let focused_node = self.focused_node();
+        let mut focused_bounds = None;
+        let Self {
+            stretch,
+            tree,
+            theme,
+            ..
+        } = self;
+        tree.fold_traverse(Vec2::zero(), |parent_pos, id, slot| {
+            let layout = stretch.layout(slot.stretch_node).unwrap();
+            let bounds = Rect {
+                pos: vec2(layout.location.x, layout.location.y) + parent_pos,
+                size: vec2(layout.size.width, layout.size.height),
+            };
+            slot.node.borrow_mut().draw(bounds, canvas, theme);
+            if focused_node == Some(id) {
+                focused_bounds = Some(bounds);
+            }
+            bounds.pos
+        });
+        if let Some(bounds) = focused_bounds {
+            draw_focus_ring(bounds, canvas, &self.theme);
+        }
+        self.dirty = false;
+    }
+
+    /// Moves focus to the next focusable widget in tab order,
+    /// wrapping around at the end.
+    ///
+    /// Tab order is a depth-first traversal of the tree.
+    pub fn focus_next(&mut self) {
+        self.move_focus(|index, len| match index {
+            Some(i) => (i + 1) % len,
+            None => 0,
+        });
+    }
+
+    /// Moves focus to the previous focusable widget in tab order,
+    /// wrapping around at the start.
+    pub fn focus_previous(&mut self) {
+        self.move_focus(|index, len| match index {
+            Some(0) | None => len - 1,
+            Some(i) => i - 1,
+        });
+    }
+
+    /// Removes focus from the focused widget.
+    pub fn clear_focus(&mut self) {
+        if self.focused.take().is_some() {
+            self.dirty = true;
+        }
+    }
+
+    /// Returns whether any widget has keyboard focus.
+    pub fn has_focus(&self) -> bool {
+        self.focused_node().is_some()
+    }
+
+    /// Activates the focused widget, if any. Returns
+    /// whether a widget was activated.
+    pub fn activate_focused(&mut self) -> bool {
+        match self.focused_node() {
+            Some(id) => {
+                let mut node = self.tree.nodes[&id].node.borrow_mut();
+                node.activate();
+                self.messages.extend(node.take_message());
+                self.dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Takes the messages widgets have sent since the last call,
+    /// in the order they were sent.
+    pub fn take_messages(&mut self) -> Vec<Message> {
+        std::mem::take(&mut self.messages)
+    }
+
+    /// Handles input, delivering pointer events to the widgets
+    /// under the pointer and keyboard events to the focused widget.
+    /// Returns whether a widget handled it, in which case the
+    /// application should not act on it.
+    ///
+    /// Hit testing uses the size of the canvas passed to the
+    /// last call to [`render`](Self::render); before the first
+    /// render, no widget is under the pointer.
+    pub fn handle_event(&mut self, event: Event) -> bool {
+        if let Some(size) = self.layout_size {
+            // The UI may have been rebuilt since it was last rendered.
+            self.compute_layout(size.x, size.y);
+        }
+        match event {
+            Event::PointerMoved { pos } => {
+                self.pointer = Some(pos);
+                self.update_hover()
+            }
+            Event::PointerLeft => {
+                self.pointer = None;
+                self.update_hover()
+            }
+            Event::PointerPressed { button } => self.dispatch(PointerEvent::Press(button)),
+            Event::PointerReleased { button } => self.dispatch(PointerEvent::Release(button)),
+            Event::FocusNext => {
+                self.focus_next();
+                self.has_focus()
+            }
+            Event::FocusPrevious => {
+                self.focus_previous();
+                self.has_focus()
+            }
+            Event::Activate => self.activate_focused(),
+        }
+    }
+
+    /// Sends `event` to the topmost widget under the pointer,
+    /// then to its ancestors until one handles it.
+    fn dispatch(&mut self, event: PointerEvent) -> bool {
+        for id in self.hit_path().into_iter().rev() {
+            let mut node = self.tree.nodes[&id].node.borrow_mut();
+            if node.on_event(event) {
+                self.messages.extend(node.take_message());
+                self.dirty = true;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Sends `Enter` and `Leave` to the widgets the pointer
+    /// has moved onto or off of since the last hit test.
+    fn update_hover(&mut self) -> bool {
+        let hovered: Vec<WidgetKey> = self
+            .hit_path()
+            .into_iter()
+            .map(|id| self.tree.nodes[&id].key)
+            .collect();
+        let mut handled = false;
+        for slot in self.tree.nodes.values() {
+            let event = match (
+                self.hovered.contains(&slot.key),
+                hovered.contains(&slot.key),
+            ) {
+                (true, false) => PointerEvent::Leave,
+                (false, true) => PointerEvent::Enter,
+                _ => continue,
+            };
+            handled |= slot.node.borrow_mut().on_event(event);
+        }
+        self.hovered = hovered;
+        if handled {
+            self.dirty = true;
+        }
+        handled
+    }
+
+    /// Returns the nodes under the pointer, from a root down
+    /// to the topmost node. Later siblings are above earlier ones.
+    fn hit_path(&self) -> Vec<NodeId> {
+        let mut path = Vec::new();
+        let pointer = match self.pointer {
+            Some(pointer) if self.layout_size.is_some() => pointer,
+            _ => return path,
+        };
+        let mut candidates = self.tree.roots.as_slice();
+        let mut origin = Vec2::zero();
+        while let Some((id, bounds)) = candidates.iter().rev().find_map(|&id| {
+            let bounds = self.bounds(id, origin);
+            if bounds.contains(pointer) {
+                Some((id, bounds))
+            } else {
+                None
+            }
+        }) {
+            path.push(id);
+            origin = bounds.pos;
+            candidates = self
+                .tree
+                .children
+                .get(&id)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+        }
+        path
+    }
+
+    /// Returns the bounds of a node whose parent's
+    /// top-left corner is at `origin`.
+    fn bounds(&self, id: NodeId, origin: Vec2) -> Rect {
+        let layout = self
+            .stretch
+            .layout(self.tree.nodes[&id].stretch_node)
+            .unwrap();
+        Rect {
+            pos: vec2(layout.location.x, layout.location.y) + origin,
+            size: vec2(layout.size.width, layout.size.height),
+        }
+    }
+
+    fn move_focus(&mut self, next_index: impl FnOnce(Option<usize>, usize) -> usize) {
+        let order = self.focus_order();
+        if order.is_empty() {
+            self.focused = None;
+            return;
+        }
+        let current = self
+            .focused
+            .and_then(|focused| order.iter().position(|(_, key)| *key == focused));
+        let (_, key) = order[next_index(current, order.len())];
+        self.focused = Some(key);
+        self.dirty = true;
+    }
+
+    fn focused_node(&self) -> Option<NodeId> {
+        let focused = self.focused?;
+        self.focus_order()
+            .into_iter()
+            .find(|(_, key)| *key == focused)
+            .map(|(id, _)| id)
+    }
+
+    /// Returns the focusable nodes in tab order.
+    fn focus_order(&self) -> Vec<(NodeId, WidgetKey)> {
         self.tree
-            .fold_traverse(Vec2::zero(), |parent_pos, _id, slot| {
-                let layout = stretch.layout(slot.stretch_node).unwrap();
-                let bounds = Rect {
-                    pos: vec2(layout.location.x, layout.location.y) + parent_pos,
-                    size: vec2(layout.size.width, layout.size.height),
-                };
-                slot.node.borrow_mut().draw(bounds, canvas);
-                parent_pos + bounds.pos
-            });
+            .preorder()
+            .into_iter()
+            .filter_map(|id| {
+                let slot = &self.tree.nodes[&id];
+                if slot.node.borrow().is_focusable() {
+                    Some((id, slot.key))
+                } else {
+                    None
+                }
+            })
+            .collect()
     }
 
     fn compute_layout(&mut self, width: f32, height: f32) {
@@ -100,13 +427,49 @@ impl Ui {
             .unwrap();
     }
 
-    fn insert_node(
-        &mut self,
-        parent: Option<NodeId>,
-        node: Rc<RefCell<dyn WidgetState>>,
-    ) -> NodeId {
+    fn insert_node<D>(&mut self, parent: Option<NodeId>, data: D) -> NodeId
+    where
+        D: WidgetData,
+        D::State: WidgetState + 'static,
+    {
+        let location = data.location();
+        let occurrence = self.occurrences.entry(location).or_default();
+        let key = WidgetKey {
+            location,
+            occurrence: *occurrence,
+        };
+        *occurrence += 1;
+
+        let previous = self
+            .previous
+            .remove(&key)
+            .filter(|_| data.is_persistent())
+            .and_then(|state| state.downcast::<RefCell<D::State>>().ok());
+        let state = match previous {
+            Some(state) => {
+                let mut changes = ChangeList::new();
+                data.apply_changes(&state.borrow(), &mut changes);
+                changes.apply_to(&mut state.borrow_mut());
+                state
+            }
+            None => {
+                let state = Rc::new(RefCell::new(data.into_state()));
+                if self.hovered.contains(&key) {
+                    // The widget replaces one that was under the pointer.
+                    state.borrow_mut().on_event(PointerEvent::Enter);
+                }
+                state
+            }
+        };
+        let node: Rc<RefCell<dyn WidgetState>> = Rc::clone(&state) as _;
+
         let stretch_node = self.create_stretch_node(&node);
-        let slot = NodeSlot { node, stretch_node };
+        let slot = NodeSlot {
+            node,
+            state,
+            stretch_node,
+            key,
+        };
         let id = NodeId::next();
         self.tree.nodes.insert(id, slot);
         if let Some(parent) = parent {
@@ -128,8 +491,10 @@ impl Ui {
 
     fn create_stretch_node(&mut self, node_rc: &Rc<RefCell<dyn WidgetState>>) -> Node {
         let node = node_rc.borrow();
+        let style = node.style(&self.theme);
         if node.is_leaf() {
             let node_rc = Rc::clone(node_rc);
+            let theme = Arc::clone(&self.theme);
             let measure = Box::new(move |max_size: stretch::geometry::Size<Number>| {
                 let max_width = match max_size.width {
                     Number::Defined(x) => Some(x),
@@ -139,15 +504,17 @@ impl Ui {
                     Number::Defined(x) => Some(x),
                     Number::Undefined => None,
                 };
-                let size = node_rc.borrow_mut().compute_size(max_width, max_height);
+                let size = node_rc
+                    .borrow_mut()
+                    .compute_size(max_width, max_height, &theme);
                 Ok(Size {
                     width: size.x,
                     height: size.y,
                 })
             });
-            self.stretch.new_leaf(node.style(), measure).unwrap()
+            self.stretch.new_leaf(style, measure).unwrap()
         } else {
-            self.stretch.new_node(node.style(), Vec::new()).unwrap()
+            self.stretch.new_node(style, Vec::new()).unwrap()
         }
     }
 }
@@ -165,11 +532,7 @@ impl<'a> UiBuilder<'a> {
         D: WidgetData,
         D::State: WidgetState + 'static,
     {
-        let node = data.into_state();
-        self.ui.insert_node(
-            self.parent_stack.last().copied(),
-            Rc::new(RefCell::new(node)),
-        );
+        self.ui.insert_node(self.parent_stack.last().copied(), data);
         self
     }
 
@@ -180,11 +543,7 @@ impl<'a> UiBuilder<'a> {
         D: WidgetData,
         D::State: WidgetState + 'static,
     {
-        let node = data.into_state();
-        let id = self.ui.insert_node(
-            self.parent_stack.last().copied(),
-            Rc::new(RefCell::new(node)),
-        );
+        let id = self.ui.insert_node(self.parent_stack.last().copied(), data);
         self.parent_stack.push(id);
         self
     }
@@ -195,11 +554,49 @@ impl<'a> UiBuilder<'a> {
         self.parent_stack.pop();
         self
     }
+
+    /// Animates a value toward `target`, returning the current value.
+    /// The animation is identified by the caller's source location.
+    ///
+    /// See [`Animations::animate`].
+    #[track_caller]
+    pub fn animate<T: Lerp>(&mut self, target: T, duration: f32, easing: Easing) -> T {
+        self.animate_keyed(AnimationKey::new(()), target, duration, easing)
+    }
+
+    /// Animates a value toward `target`, returning the current value.
+    /// Use this instead of [`animate`](Self::animate) when multiple
+    /// animations are created at the same source location.
+    pub fn animate_keyed<T: Lerp>(
+        &mut self,
+        key: AnimationKey,
+        target: T,
+        duration: f32,
+        easing: Easing,
+    ) -> T {
+        self.ui.animations.animate(key, target, duration, easing)
+    }
 }
 
 struct NodeSlot {
     node: Rc<RefCell<dyn WidgetState>>,
+    /// The same state as `node`, for persistent
+    /// widgets of the next build to downcast.
+    state: Rc<dyn Any>,
     stretch_node: Node,
+    key: WidgetKey,
+}
+
+fn draw_focus_ring(bounds: Rect, canvas: &mut Canvas, theme: &Theme) {
+    let ring = Rect {
+        pos: bounds.pos - Vec2::splat(FOCUS_RING_WIDTH),
+        size: bounds.size + Vec2::splat(FOCUS_RING_WIDTH * 2.),
+    };
+    canvas.stroke_path(
+        &Path::rounded_rect(ring, theme.corner_radius + FOCUS_RING_WIDTH),
+        &Paint::new().shade_solid(theme.focus_ring_color),
+        &Stroke::new().width(FOCUS_RING_WIDTH),
+    );
 }
 
 #[derive(Default)]
@@ -210,6 +607,20 @@ struct Tree {
 }
 
 impl Tree {
+    /// Returns all nodes in depth-first order,
+    /// with siblings in the order they were added.
+    pub fn preorder(&self) -> Vec<NodeId> {
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut stack: Vec<_> = self.roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            order.push(id);
+            if let Some(children) = self.children.get(&id) {
+                stack.extend(children.iter().rev().copied());
+            }
+        }
+        order
+    }
+
     /// Performs a depth-first traversal of the node tree.
     pub fn fold_traverse<S: Copy>(
         &mut self,
@@ -231,3 +642,233 @@ impl Tree {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use fontdue::Font;
+    use stretch::geometry;
+
+    use super::*;
+    use crate::{
+        widgets::{Button, Checkbox, Container},
+        PointerButton,
+    };
+
+    type Log = Rc<RefCell<Vec<(&'static str, PointerEvent)>>>;
+
+    /// A fixed-size widget with padding which records its events.
+    #[derive(Debug)]
+    struct Probe {
+        name: &'static str,
+        size: f32,
+        /// Whether the probe handles presses and releases.
+        handles: bool,
+        log: Log,
+        location: &'static Location<'static>,
+    }
+
+    impl Probe {
+        #[track_caller]
+        fn new(name: &'static str, size: f32, handles: bool, log: &Log) -> Self {
+            Self {
+                name,
+                size,
+                handles,
+                log: Rc::clone(log),
+                location: Location::caller(),
+            }
+        }
+    }
+
+    impl WidgetData for Probe {
+        type State = Self;
+
+        fn location(&self) -> &'static Location<'static> {
+            self.location
+        }
+
+        fn into_state(self) -> Self::State {
+            self
+        }
+
+        fn apply_changes(&self, _state: &Self, _changes: &mut crate::widget::ChangeList<Self>) {}
+    }
+
+    impl WidgetState for Probe {
+        fn style(&self, _theme: &Theme) -> Style {
+            Style {
+                size: Size {
+                    width: Dimension::Points(self.size),
+                    height: Dimension::Points(self.size),
+                },
+                padding: padding(),
+                ..Default::default()
+            }
+        }
+
+        fn draw(&mut self, _bounds: Rect, _cv: &mut Canvas, _theme: &Theme) {}
+
+        fn on_event(&mut self, event: PointerEvent) -> bool {
+            self.log.borrow_mut().push((self.name, event));
+            match event {
+                PointerEvent::Press(_) | PointerEvent::Release(_) => self.handles,
+                _ => false,
+            }
+        }
+    }
+
+    fn padding() -> geometry::Rect<Dimension> {
+        geometry::Rect {
+            start: Dimension::Points(10.),
+            end: Dimension::Points(10.),
+            top: Dimension::Points(10.),
+            bottom: Dimension::Points(10.),
+        }
+    }
+
+    /// Takes the logged events, sorted since enter and
+    /// leave events are sent in no particular order.
+    fn take(log: &Log) -> Vec<(&'static str, PointerEvent)> {
+        let mut events = log.borrow_mut().split_off(0);
+        events.sort_by_key(|(name, _)| *name);
+        events
+    }
+
+    #[test]
+    fn hit_testing() {
+        let log = Log::default();
+        let mut ui = Ui::new();
+        // The panel is at (10, 10) and the button at (20, 20).
+        let build = |ui: &mut Ui| {
+            ui.build()
+                .begin(Container::row().with_style(|style| style.padding = padding()))
+                .begin(Probe::new("panel", 100., true, &log))
+                .push(Probe::new("button", 20., false, &log))
+                .end()
+                .end();
+        };
+        build(&mut ui);
+
+        // Nothing is hit before the layout is computed.
+        assert!(!ui.handle_event(Event::PointerMoved {
+            pos: vec2(25., 25.)
+        }));
+        assert_eq!(take(&log), vec![]);
+
+        ui.render(&mut Canvas::new(200, 200, 1.));
+        assert_eq!(
+            take(&log),
+            vec![
+                ("button", PointerEvent::Enter),
+                ("panel", PointerEvent::Enter)
+            ]
+        );
+
+        // Presses bubble up to the panel, which handles them.
+        let press = Event::PointerPressed {
+            button: PointerButton::Left,
+        };
+        assert!(ui.handle_event(press));
+        assert_eq!(
+            take(&log),
+            vec![
+                ("button", PointerEvent::Press(PointerButton::Left)),
+                ("panel", PointerEvent::Press(PointerButton::Left))
+            ]
+        );
+
+        // Rebuilt widgets under the pointer are entered again.
+        build(&mut ui);
+        assert_eq!(
+            take(&log),
+            vec![
+                ("button", PointerEvent::Enter),
+                ("panel", PointerEvent::Enter)
+            ]
+        );
+
+        ui.handle_event(Event::PointerMoved {
+            pos: vec2(15., 15.),
+        });
+        assert_eq!(take(&log), vec![("button", PointerEvent::Leave)]);
+        ui.handle_event(Event::PointerLeft);
+        assert_eq!(take(&log), vec![("panel", PointerEvent::Leave)]);
+        assert!(!ui.handle_event(press));
+        assert_eq!(take(&log), vec![]);
+    }
+
+    #[test]
+    fn buttons_and_checkboxes() {
+        let font = Arc::new(
+            Font::from_bytes(
+                &include_bytes!("../../../assets/font/Play-Regular.ttf")[..],
+                Default::default(),
+            )
+            .unwrap(),
+        );
+        let mut ui = Ui::new();
+        let build = |ui: &mut Ui| {
+            ui.build()
+                .begin(Container::column())
+                .push(Checkbox::new("vsync", "VSync", &font).initially_checked(true))
+                .push(Button::new("apply", "Apply", &font))
+                .end();
+            ui.render(&mut Canvas::new(200, 200, 1.));
+        };
+        let click = |ui: &mut Ui, pos| {
+            ui.handle_event(Event::PointerMoved { pos });
+            ui.handle_event(Event::PointerPressed {
+                button: PointerButton::Left,
+            });
+            ui.handle_event(Event::PointerReleased {
+                button: PointerButton::Left,
+            })
+        };
+        build(&mut ui);
+
+        // The checkbox is in the top-left corner.
+        assert!(click(&mut ui, vec2(2., 2.)));
+        assert_eq!(ui.take_messages(), vec![Message::Toggled("vsync", false)]);
+
+        // Rebuilding doesn't reset the checkbox to its initial state.
+        build(&mut ui);
+        click(&mut ui, vec2(2., 2.));
+        assert_eq!(ui.take_messages(), vec![Message::Toggled("vsync", true)]);
+        assert_eq!(ui.take_messages(), vec![]);
+
+        ui.focus_next();
+        ui.focus_next();
+        assert!(ui.activate_focused());
+        assert_eq!(ui.take_messages(), vec![Message::Clicked("apply")]);
+
+        // Keyboard events move focus and activate the focused widget.
+        assert!(ui.handle_event(Event::FocusPrevious));
+        assert!(ui.handle_event(Event::Activate));
+        assert_eq!(ui.take_messages(), vec![Message::Toggled("vsync", false)]);
+    }
+
+    #[test]
+    fn running_animations_need_redraw() {
+        let mut ui = Ui::new();
+        let mut canvas = Canvas::new(100, 100, 1.);
+        let build = |ui: &mut Ui, target: f32| {
+            ui.build().animate(target, 1., Easing::Linear);
+        };
+        build(&mut ui, 0.);
+        ui.render(&mut canvas);
+        ui.advance(0.5);
+        assert!(!ui.needs_redraw());
+
+        build(&mut ui, 1.);
+        for _ in 0..2 {
+            ui.render(&mut canvas);
+            ui.advance(0.5);
+            assert!(ui.needs_redraw());
+        }
+
+        // The animation finished during the last frame.
+        ui.render(&mut canvas);
+        ui.advance(0.5);
+        assert!(!ui.needs_redraw());
+    }
+}
@@ -1,6 +1,6 @@
 use std::{fmt::Debug, panic::Location};
 
-use crate::Canvas;
+use crate::{Canvas, Message, PointerEvent, Theme};
 use glam::Vec2;
 use stretch::style::Style;
 use utils::Rect;
@@ -10,9 +10,21 @@ pub struct ChangeList<W> {
 }
 
 impl<W> ChangeList<W> {
+    pub(crate) fn new() -> Self {
+        Self {
+            changes: Vec::new(),
+        }
+    }
+
     pub fn apply(&mut self, change: impl FnOnce(&mut W) + 'static) {
         self.changes.push(Box::new(change));
     }
+
+    pub(crate) fn apply_to(self, state: &mut W) {
+        for change in self.changes {
+            change(state);
+        }
+    }
 }
 
 pub trait WidgetData {
@@ -23,19 +35,64 @@ pub trait WidgetData {
     fn into_state(self) -> Self::State;
 
     fn apply_changes(&self, state: &Self::State, changes: &mut ChangeList<Self::State>);
+
+    /// Returns whether the widget's state persists when the UI is
+    /// rebuilt. If so, a widget created at the same location as one
+    /// in the previous build updates its state with
+    /// [`apply_changes`](Self::apply_changes) instead of replacing it.
+    fn is_persistent(&self) -> bool {
+        false
+    }
 }
 
+/// The persistent state of a widget.
+///
+/// Methods which affect appearance receive the UI's
+/// current [`Theme`], which built-in widgets consult
+/// for defaults not overridden by the widget itself.
 pub trait WidgetState: Debug {
-    fn style(&self) -> Style;
+    fn style(&self, theme: &Theme) -> Style;
 
     fn is_leaf(&self) -> bool {
         false
     }
 
-    fn compute_size(&mut self, max_width: Option<f32>, max_height: Option<f32>) -> Vec2 {
-        let _ = (max_width, max_height);
+    fn compute_size(
+        &mut self,
+        max_width: Option<f32>,
+        max_height: Option<f32>,
+        theme: &Theme,
+    ) -> Vec2 {
+        let _ = (max_width, max_height, theme);
         Vec2::zero()
     }
 
-    fn draw(&mut self, bounds: Rect, cv: &mut Canvas);
+    fn draw(&mut self, bounds: Rect, cv: &mut Canvas, theme: &Theme);
+
+    /// Returns whether this widget can receive keyboard focus.
+    fn is_focusable(&self) -> bool {
+        false
+    }
+
+    /// Called when this widget has focus and the user
+    /// activates it, e.g. by pressing Enter.
+    fn activate(&mut self) {}
+
+    /// Handles a pointer event, returning whether the widget handled
+    /// it. Handling an event redraws the UI.
+    ///
+    /// `Enter` and `Leave` are sent to every widget the pointer moves
+    /// onto or off of, including when the UI is rebuilt under the
+    /// pointer. Presses and releases are sent to the topmost widget
+    /// under the pointer, then to its ancestors until one handles them.
+    fn on_event(&mut self, event: PointerEvent) -> bool {
+        let _ = event;
+        false
+    }
+
+    /// Takes the message the widget sent, if any. Called after the
+    /// widget handles an event or is [activated](Self::activate).
+    fn take_message(&mut self) -> Option<Message> {
+        None
+    }
 }
@@ -1,7 +1,17 @@
+pub mod button;
+pub mod checkbox;
 pub mod container;
+pub mod image;
+pub mod panel;
 pub mod rectangle;
 pub mod text;
+pub mod text_input;
 
+pub use button::Button;
+pub use checkbox::Checkbox;
 pub use container::Container;
+pub use image::Image;
+pub use panel::Panel;
 pub use rectangle::Rectangle;
 pub use text::Text;
+pub use text_input::TextInput;
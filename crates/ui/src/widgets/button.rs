@@ -0,0 +1,206 @@
+use std::{panic::Location, sync::Arc};
+
+use fontdue::{
+    layout::{HorizontalAlign, VerticalAlign},
+    Font,
+};
+use glam::{vec2, Vec2};
+use stretch::style::Style;
+use utils::{Color, Rect};
+
+use crate::{
+    canvas::{Paint, TextSettings},
+    widget::ChangeList,
+    Canvas, Message, Path, PointerButton, PointerEvent, Theme, WidgetData, WidgetState,
+};
+
+/// A button with a text label.
+///
+/// Clicking the button with the left mouse button, or activating it
+/// while it has focus, sends [`Message::Clicked`] with its ID. The
+/// button is filled with the theme's button colors, which depend on
+/// whether it is under the pointer or pressed.
+pub struct Button<'a> {
+    id: &'static str,
+    label: &'a str,
+    font: Arc<Font>,
+    location: &'static Location<'static>,
+}
+
+impl<'a> Button<'a> {
+    #[track_caller]
+    pub fn new(id: &'static str, label: &'a str, font: &Arc<Font>) -> Self {
+        Self {
+            id,
+            label,
+            font: Arc::clone(font),
+            location: Location::caller(),
+        }
+    }
+}
+
+impl WidgetData for Button<'_> {
+    type State = State;
+
+    fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    fn into_state(self) -> Self::State {
+        State {
+            id: self.id,
+            label: self.label.to_owned(),
+            font: self.font,
+            pointer: PointerState::default(),
+            clicked: false,
+        }
+    }
+
+    fn apply_changes(&self, state: &Self::State, changes: &mut ChangeList<Self::State>) {
+        if state.id != self.id || state.label != self.label || !Arc::ptr_eq(&state.font, &self.font)
+        {
+            let (id, label, font) = (self.id, self.label.to_owned(), Arc::clone(&self.font));
+            changes.apply(move |state| {
+                state.id = id;
+                state.label = label;
+                state.font = font;
+            });
+        }
+    }
+
+    fn is_persistent(&self) -> bool {
+        true
+    }
+}
+
+pub struct State {
+    id: &'static str,
+    label: String,
+    font: Arc<Font>,
+    pointer: PointerState,
+    /// Whether the button was clicked since
+    /// its message was last taken.
+    clicked: bool,
+}
+
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("id", &self.id)
+            .field("label", &self.label)
+            .field("pointer", &self.pointer)
+            .field("clicked", &self.clicked)
+            .finish()
+    }
+}
+
+impl WidgetState for State {
+    fn style(&self, _theme: &Theme) -> Style {
+        Style::default()
+    }
+
+    fn is_leaf(&self) -> bool {
+        true
+    }
+
+    fn compute_size(
+        &mut self,
+        _max_width: Option<f32>,
+        _max_height: Option<f32>,
+        theme: &Theme,
+    ) -> Vec2 {
+        let settings = label_settings(&self.font, theme);
+        super::text::compute_size(&settings, &self.label) + inset(theme) * 2.
+    }
+
+    fn draw(&mut self, bounds: Rect, cv: &mut Canvas, theme: &Theme) {
+        cv.fill_path(
+            &Path::rounded_rect(bounds, theme.corner_radius),
+            &Paint::new().shade_solid(self.pointer.fill_color(theme)),
+        );
+        let mut settings = label_settings(&self.font, theme);
+        settings.pos = bounds.pos + inset(theme);
+        cv.fill_text(&self.label, &settings);
+    }
+
+    fn is_focusable(&self) -> bool {
+        true
+    }
+
+    fn activate(&mut self) {
+        self.clicked = true;
+    }
+
+    fn on_event(&mut self, event: PointerEvent) -> bool {
+        let handled = self.pointer.on_event(event);
+        if handled == Some(true) {
+            self.clicked = true;
+        }
+        handled.is_some()
+    }
+
+    fn take_message(&mut self) -> Option<Message> {
+        if self.clicked {
+            self.clicked = false;
+            Some(Message::Clicked(self.id))
+        } else {
+            None
+        }
+    }
+}
+
+/// Tracks the pointer over a clickable widget.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct PointerState {
+    hovered: bool,
+    pressed: bool,
+}
+
+impl PointerState {
+    /// Updates the state with an event. Returns `None` if the event
+    /// is not handled, or whether it completed a click: a press
+    /// and release of the left button over the widget.
+    pub fn on_event(&mut self, event: PointerEvent) -> Option<bool> {
+        match event {
+            PointerEvent::Enter => self.hovered = true,
+            PointerEvent::Leave => *self = Self::default(),
+            PointerEvent::Press(PointerButton::Left) => self.pressed = true,
+            PointerEvent::Release(PointerButton::Left) => {
+                let clicked = self.pressed;
+                self.pressed = false;
+                return Some(clicked);
+            }
+            _ => return None,
+        }
+        Some(false)
+    }
+
+    /// Returns the theme color to fill the widget with.
+    pub fn fill_color(self, theme: &Theme) -> Color {
+        if self.pressed {
+            theme.button_pressed_color
+        } else if self.hovered {
+            theme.button_hover_color
+        } else {
+            theme.button_color
+        }
+    }
+}
+
+pub(crate) fn label_settings(font: &Arc<Font>, theme: &Theme) -> TextSettings {
+    TextSettings {
+        font: Arc::clone(font),
+        align_h: HorizontalAlign::Left,
+        align_v: VerticalAlign::Top,
+        size: theme.font_size,
+        color: theme.text_color,
+        pos: Vec2::zero(),
+        max_width: None,
+        max_height: None,
+    }
+}
+
+/// Returns the space between a button's border and its label.
+fn inset(theme: &Theme) -> Vec2 {
+    vec2(theme.padding, theme.padding / 2.)
+}
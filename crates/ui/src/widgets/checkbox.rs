@@ -0,0 +1,192 @@
+use std::{panic::Location, sync::Arc};
+
+use fontdue::Font;
+use glam::{vec2, Vec2};
+use stretch::style::Style;
+use utils::Rect;
+
+use super::button::{label_settings, PointerState};
+use crate::{
+    canvas::Paint, widget::ChangeList, Canvas, Message, Path, PointerEvent, Theme, WidgetData,
+    WidgetState,
+};
+
+/// Fraction of the box's size left empty around the check mark.
+const CHECK_INSET: f32 = 0.25;
+
+/// A box which can be checked, followed by a text label.
+///
+/// Clicking the checkbox or activating it while it has focus toggles
+/// it and sends [`Message::Toggled`] with its ID and new state. Whether
+/// it is checked persists across rebuilds of the UI, so the application
+/// only sets the initial state.
+pub struct Checkbox<'a> {
+    id: &'static str,
+    label: &'a str,
+    font: Arc<Font>,
+    checked: bool,
+    location: &'static Location<'static>,
+}
+
+impl<'a> Checkbox<'a> {
+    #[track_caller]
+    pub fn new(id: &'static str, label: &'a str, font: &Arc<Font>) -> Self {
+        Self {
+            id,
+            label,
+            font: Arc::clone(font),
+            checked: false,
+            location: Location::caller(),
+        }
+    }
+
+    /// Sets whether the checkbox is checked when it is first built.
+    pub fn initially_checked(mut self, checked: bool) -> Self {
+        self.checked = checked;
+        self
+    }
+}
+
+impl WidgetData for Checkbox<'_> {
+    type State = State;
+
+    fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    fn into_state(self) -> Self::State {
+        State {
+            id: self.id,
+            label: self.label.to_owned(),
+            font: self.font,
+            checked: self.checked,
+            pointer: PointerState::default(),
+            toggled: false,
+        }
+    }
+
+    fn apply_changes(&self, state: &Self::State, changes: &mut ChangeList<Self::State>) {
+        if state.id != self.id || state.label != self.label || !Arc::ptr_eq(&state.font, &self.font)
+        {
+            let (id, label, font) = (self.id, self.label.to_owned(), Arc::clone(&self.font));
+            changes.apply(move |state| {
+                state.id = id;
+                state.label = label;
+                state.font = font;
+            });
+        }
+    }
+
+    fn is_persistent(&self) -> bool {
+        true
+    }
+}
+
+pub struct State {
+    id: &'static str,
+    label: String,
+    font: Arc<Font>,
+    checked: bool,
+    pointer: PointerState,
+    /// Whether the checkbox was toggled since
+    /// its message was last taken.
+    toggled: bool,
+}
+
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("id", &self.id)
+            .field("label", &self.label)
+            .field("checked", &self.checked)
+            .field("pointer", &self.pointer)
+            .finish()
+    }
+}
+
+impl State {
+    fn toggle(&mut self) {
+        self.checked = !self.checked;
+        self.toggled = true;
+    }
+}
+
+impl WidgetState for State {
+    fn style(&self, _theme: &Theme) -> Style {
+        Style::default()
+    }
+
+    fn is_leaf(&self) -> bool {
+        true
+    }
+
+    fn compute_size(
+        &mut self,
+        _max_width: Option<f32>,
+        _max_height: Option<f32>,
+        theme: &Theme,
+    ) -> Vec2 {
+        let settings = label_settings(&self.font, theme);
+        let label = super::text::compute_size(&settings, &self.label);
+        vec2(
+            box_size(theme) + theme.padding / 2. + label.x,
+            label.y.max(box_size(theme)),
+        )
+    }
+
+    fn draw(&mut self, bounds: Rect, cv: &mut Canvas, theme: &Theme) {
+        let size = box_size(theme);
+        let check_box = Rect {
+            pos: bounds.pos,
+            size: Vec2::splat(size),
+        };
+        cv.fill_path(
+            &Path::rounded_rect(check_box, theme.corner_radius),
+            &Paint::new().shade_solid(self.pointer.fill_color(theme)),
+        );
+        if self.checked {
+            let mark = Rect {
+                pos: check_box.pos + Vec2::splat(size * CHECK_INSET),
+                size: Vec2::splat(size * (1. - CHECK_INSET * 2.)),
+            };
+            cv.fill_path(
+                &Path::rect(mark),
+                &Paint::new().shade_solid(theme.text_color),
+            );
+        }
+
+        let mut settings = label_settings(&self.font, theme);
+        settings.pos = bounds.pos + vec2(size + theme.padding / 2., 0.);
+        cv.fill_text(&self.label, &settings);
+    }
+
+    fn is_focusable(&self) -> bool {
+        true
+    }
+
+    fn activate(&mut self) {
+        self.toggle();
+    }
+
+    fn on_event(&mut self, event: PointerEvent) -> bool {
+        let handled = self.pointer.on_event(event);
+        if handled == Some(true) {
+            self.toggle();
+        }
+        handled.is_some()
+    }
+
+    fn take_message(&mut self) -> Option<Message> {
+        if self.toggled {
+            self.toggled = false;
+            Some(Message::Toggled(self.id, self.checked))
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the side length of the box.
+fn box_size(theme: &Theme) -> f32 {
+    theme.font_size
+}
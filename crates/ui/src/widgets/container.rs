@@ -2,7 +2,7 @@ use std::panic::Location;
 
 use stretch::style::Style;
 
-use crate::{WidgetData, WidgetState};
+use crate::{Theme, WidgetData, WidgetState};
 
 /// A container widget, used to lay out its
 /// children.
@@ -66,11 +66,11 @@ impl WidgetData for Container {
 }
 
 impl WidgetState for Container {
-    fn style(&self) -> Style {
+    fn style(&self, _theme: &Theme) -> Style {
         self.style
     }
 
-    fn draw(&mut self, bounds: utils::Rect, cv: &mut crate::Canvas) {
+    fn draw(&mut self, bounds: utils::Rect, cv: &mut crate::Canvas, _theme: &Theme) {
         let _ = (bounds, cv);
     }
 }
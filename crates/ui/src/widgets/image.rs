@@ -0,0 +1,110 @@
+use std::{panic::Location, sync::Arc};
+
+use glam::{vec2, Vec2};
+use stretch::{
+    geometry::Size,
+    style::{Dimension, Style},
+};
+
+use crate::{
+    canvas::{FilterQuality, Texture},
+    Theme, WidgetData, WidgetState,
+};
+
+/// Draws a [`Texture`], such as an icon or a logo.
+///
+/// The image is the size of the texture in logical pixels
+/// unless given a [`size`](Self::size), in which case the texture
+/// is scaled to fit. Scaled textures are filtered bilinearly;
+/// use [`FilterQuality::Nearest`] for pixel art.
+pub struct Image {
+    texture: Arc<Texture>,
+    size: Option<Vec2>,
+    quality: FilterQuality,
+    location: &'static Location<'static>,
+}
+
+impl Image {
+    #[track_caller]
+    pub fn new(texture: &Arc<Texture>) -> Self {
+        Self {
+            texture: Arc::clone(texture),
+            size: None,
+            quality: FilterQuality::Bilinear,
+            location: Location::caller(),
+        }
+    }
+
+    pub fn size(mut self, size: Vec2) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn filter_quality(mut self, quality: FilterQuality) -> Self {
+        self.quality = quality;
+        self
+    }
+}
+
+impl WidgetData for Image {
+    type State = State;
+
+    fn location(&self) -> &'static std::panic::Location<'static> {
+        self.location
+    }
+
+    fn into_state(self) -> Self::State {
+        let size = self
+            .size
+            .unwrap_or_else(|| vec2(self.texture.width() as f32, self.texture.height() as f32));
+        State {
+            texture: self.texture,
+            size,
+            quality: self.quality,
+        }
+    }
+
+    fn apply_changes(
+        &self,
+        state: &Self::State,
+        changes: &mut crate::widget::ChangeList<Self::State>,
+    ) {
+        let _ = (state, changes);
+    }
+}
+
+#[derive(Debug)]
+pub struct State {
+    texture: Arc<Texture>,
+    size: Vec2,
+    quality: FilterQuality,
+}
+
+impl WidgetState for State {
+    fn style(&self, _theme: &Theme) -> Style {
+        Style {
+            size: Size {
+                width: Dimension::Points(self.size.x),
+                height: Dimension::Points(self.size.y),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        true
+    }
+
+    fn compute_size(
+        &mut self,
+        _max_width: Option<f32>,
+        _max_height: Option<f32>,
+        _theme: &Theme,
+    ) -> Vec2 {
+        self.size
+    }
+
+    fn draw(&mut self, bounds: utils::Rect, cv: &mut crate::Canvas, _theme: &Theme) {
+        cv.draw_image(&self.texture, bounds, self.quality);
+    }
+}
@@ -0,0 +1,118 @@
+use std::panic::Location;
+
+use glam::Vec2;
+use stretch::{
+    geometry::Rect,
+    style::{Dimension, FlexDirection, Style},
+};
+
+use crate::{
+    canvas::{Paint, Stroke},
+    Canvas, Path, Theme, WidgetData, WidgetState,
+};
+
+/// A container with a themed background, laying
+/// out its children in a column.
+///
+/// The background is the theme's panel nine-patch if it has
+/// one, or else a rectangle of the panel color with the theme's
+/// corner radius and border. Children are inset by the theme's
+/// padding.
+#[derive(Debug)]
+pub struct Panel {
+    style: Style,
+    location: &'static Location<'static>,
+}
+
+impl Panel {
+    #[track_caller]
+    pub fn new() -> Self {
+        Self {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                ..Default::default()
+            },
+            location: Location::caller(),
+        }
+    }
+
+    /// Updates the style. Padding is overridden by the theme.
+    pub fn with_style(mut self, style: impl FnOnce(&mut Style)) -> Self {
+        style(&mut self.style);
+        self
+    }
+}
+
+impl Default for Panel {
+    #[track_caller]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WidgetData for Panel {
+    type State = Self;
+
+    fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    fn into_state(self) -> Self::State {
+        self
+    }
+
+    fn apply_changes(
+        &self,
+        state: &Self::State,
+        changes: &mut crate::widget::ChangeList<Self::State>,
+    ) {
+        let _ = (state, changes);
+    }
+}
+
+impl WidgetState for Panel {
+    fn style(&self, theme: &Theme) -> Style {
+        let padding = Dimension::Points(theme.padding);
+        Style {
+            padding: Rect {
+                start: padding,
+                end: padding,
+                top: padding,
+                bottom: padding,
+            },
+            ..self.style
+        }
+    }
+
+    fn draw(&mut self, bounds: utils::Rect, cv: &mut Canvas, theme: &Theme) {
+        match &theme.panel_nine_patch {
+            Some(nine_patch) => cv.draw_nine_patch(nine_patch, bounds),
+            None => {
+                cv.fill_path(
+                    &Path::rounded_rect(bounds, theme.corner_radius),
+                    &Paint::new().shade_solid(theme.panel_color),
+                );
+                if theme.border_width > 0. {
+                    draw_border(bounds, cv, theme);
+                }
+            }
+        }
+    }
+}
+
+/// Strokes the theme's border just inside `bounds`.
+fn draw_border(bounds: utils::Rect, cv: &mut Canvas, theme: &Theme) {
+    let half_width = theme.border_width / 2.;
+    let border = utils::Rect {
+        pos: bounds.pos + Vec2::splat(half_width),
+        size: bounds.size - Vec2::splat(theme.border_width),
+    };
+    if border.size.x <= 0. || border.size.y <= 0. {
+        return;
+    }
+    cv.stroke_path(
+        &Path::rounded_rect(border, theme.corner_radius - half_width),
+        &Paint::new().shade_solid(theme.border_color),
+        &Stroke::new().width(theme.border_width),
+    );
+}
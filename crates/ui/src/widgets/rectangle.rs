@@ -7,7 +7,7 @@ use stretch::{
 };
 use utils::Color;
 
-use crate::{canvas::Paint, Path, WidgetData, WidgetState};
+use crate::{canvas::Paint, Path, Theme, WidgetData, WidgetState};
 
 /// A bare, solid-color rectangle.
 #[derive(Debug)]
@@ -58,7 +58,7 @@ pub struct State {
 }
 
 impl WidgetState for State {
-    fn style(&self) -> Style {
+    fn style(&self, _theme: &Theme) -> Style {
         Style {
             size: Size {
                 width: Dimension::Points(self.size.x),
@@ -72,11 +72,16 @@ impl WidgetState for State {
         true
     }
 
-    fn compute_size(&mut self, _max_width: Option<f32>, _max_height: Option<f32>) -> Vec2 {
+    fn compute_size(
+        &mut self,
+        _max_width: Option<f32>,
+        _max_height: Option<f32>,
+        _theme: &Theme,
+    ) -> Vec2 {
         self.size
     }
 
-    fn draw(&mut self, bounds: utils::Rect, cv: &mut crate::Canvas) {
+    fn draw(&mut self, bounds: utils::Rect, cv: &mut crate::Canvas, _theme: &Theme) {
         cv.fill_path(&Path::rect(bounds), &Paint::new().shade_solid(self.color));
     }
 }
@@ -1,52 +1,73 @@
-use std::{panic::Location, sync::Arc};
+use std::{
+    fmt::{self, Debug, Formatter},
+    panic::Location,
+    sync::Arc,
+};
 
 use fontdue::{
     layout::{HorizontalAlign, Layout, VerticalAlign},
     Font,
 };
 use glam::{vec2, Vec2};
+use utils::Color;
 
-use crate::{canvas::TextSettings, WidgetData, WidgetState};
-
-const DEFAULT_SIZE: f32 = 14.;
+use crate::{canvas::TextSettings, Theme, WidgetData, WidgetState};
 
 /// Render some text.
+///
+/// Font, size, and color default to those of the [`Theme`].
+/// Text without a font of its own is not drawn if the theme
+/// has no default font.
 pub struct Text<'a> {
     text: &'a str,
-    settings: TextSettings,
+    font: Option<Arc<Font>>,
+    align_h: HorizontalAlign,
+    align_v: VerticalAlign,
+    size: Option<f32>,
+    color: Option<Color>,
     location: &'static Location<'static>,
 }
 
 impl<'a> Text<'a> {
     #[track_caller]
     pub fn new(text: &'a str, font: &Arc<Font>) -> Self {
+        Self {
+            font: Some(Arc::clone(font)),
+            ..Self::themed(text)
+        }
+    }
+
+    /// Creates text in the theme's default font.
+    #[track_caller]
+    pub fn themed(text: &'a str) -> Self {
         Self {
             text,
-            settings: TextSettings {
-                font: Arc::clone(font),
-                align_h: HorizontalAlign::Left,
-                align_v: VerticalAlign::Top,
-                size: DEFAULT_SIZE,
-                pos: Vec2::zero(),
-                max_width: None,
-                max_height: None,
-            },
+            font: None,
+            align_h: HorizontalAlign::Left,
+            align_v: VerticalAlign::Top,
+            size: None,
+            color: None,
             location: Location::caller(),
         }
     }
 
     pub fn size(mut self, size: f32) -> Self {
-        self.settings.size = size;
+        self.size = Some(size);
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = Some(color);
         self
     }
 
     pub fn align_h(mut self, align: HorizontalAlign) -> Self {
-        self.settings.align_h = align;
+        self.align_h = align;
         self
     }
 
     pub fn aligh_v(mut self, align: VerticalAlign) -> Self {
-        self.settings.align_v = align;
+        self.align_v = align;
         self
     }
 }
@@ -61,7 +82,11 @@ impl WidgetData for Text<'_> {
     fn into_state(self) -> Self::State {
         State {
             text: self.text.to_owned(),
-            settings: self.settings,
+            font: self.font,
+            align_h: self.align_h,
+            align_v: self.align_v,
+            size: self.size,
+            color: self.color,
         }
     }
 
@@ -74,14 +99,46 @@ impl WidgetData for Text<'_> {
     }
 }
 
-#[derive(Debug)]
 pub struct State {
     text: String,
-    settings: TextSettings,
+    /// `None` to use the theme's default font.
+    font: Option<Arc<Font>>,
+    align_h: HorizontalAlign,
+    align_v: VerticalAlign,
+    size: Option<f32>,
+    color: Option<Color>,
+}
+
+impl Debug for State {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("State")
+            .field("text", &self.text)
+            .field("size", &self.size)
+            .field("color", &self.color)
+            .finish()
+    }
+}
+
+impl State {
+    /// Returns the settings to draw the text with, or `None`
+    /// if neither the text nor the theme has a font.
+    fn settings(&self, theme: &Theme) -> Option<TextSettings> {
+        let font = self.font.as_ref().or_else(|| theme.default_font.as_ref())?;
+        Some(TextSettings {
+            font: Arc::clone(font),
+            align_h: self.align_h,
+            align_v: self.align_v,
+            size: self.size.unwrap_or(theme.font_size),
+            color: self.color.unwrap_or(theme.text_color),
+            pos: Vec2::zero(),
+            max_width: None,
+            max_height: None,
+        })
+    }
 }
 
 impl WidgetState for State {
-    fn style(&self) -> stretch::style::Style {
+    fn style(&self, _theme: &Theme) -> stretch::style::Style {
         stretch::style::Style::default()
     }
 
@@ -89,22 +146,33 @@ impl WidgetState for State {
         true
     }
 
-    fn compute_size(&mut self, max_width: Option<f32>, max_height: Option<f32>) -> Vec2 {
-        self.settings.max_width = max_width;
-        self.settings.max_height = max_height;
-        compute_size(&self.settings, &self.text)
+    fn compute_size(
+        &mut self,
+        max_width: Option<f32>,
+        max_height: Option<f32>,
+        theme: &Theme,
+    ) -> Vec2 {
+        match self.settings(theme) {
+            Some(mut settings) => {
+                settings.max_width = max_width;
+                settings.max_height = max_height;
+                compute_size(&settings, &self.text)
+            }
+            None => Vec2::zero(),
+        }
     }
 
-    fn draw(&mut self, bounds: utils::Rect, cv: &mut crate::Canvas) {
-        self.settings.max_width = Some(bounds.size.x);
-        self.settings.max_height = Some(bounds.size.y);
-        self.settings.pos = bounds.pos;
-
-        cv.fill_text(&self.text, &self.settings);
+    fn draw(&mut self, bounds: utils::Rect, cv: &mut crate::Canvas, theme: &Theme) {
+        if let Some(mut settings) = self.settings(theme) {
+            settings.max_width = Some(bounds.size.x);
+            settings.max_height = Some(bounds.size.y);
+            settings.pos = bounds.pos;
+            cv.fill_text(&self.text, &settings);
+        }
     }
 }
 
-fn compute_size(settings: &TextSettings, text: &str) -> Vec2 {
+pub(crate) fn compute_size(settings: &TextSettings, text: &str) -> Vec2 {
     let mut layout_engine = Layout::new(fontdue::layout::CoordinateSystem::PositiveYDown);
     settings.layout(text, &mut layout_engine);
     let width = layout_engine
@@ -0,0 +1,211 @@
+use std::{panic::Location, sync::Arc};
+
+use fontdue::{
+    layout::{HorizontalAlign, VerticalAlign},
+    Font,
+};
+use glam::{vec2, Vec2};
+use stretch::{
+    geometry::Size,
+    style::{Dimension, Style},
+};
+use utils::Color;
+
+use crate::{
+    canvas::{Paint, TextSettings},
+    Path, Theme, WidgetData, WidgetState,
+};
+
+/// The character drawn after the text to mark the caret.
+const CARET: char = '|';
+/// Opacity of the placeholder relative to the text color.
+const PLACEHOLDER_ALPHA: f32 = 0.5;
+
+/// A single-line text field.
+///
+/// The widget only displays the text; the application owns it
+/// and edits it with [`type_char`] as characters are typed. The
+/// field fills the width of its parent and is drawn on the theme's
+/// panel color.
+pub struct TextInput<'a> {
+    text: &'a str,
+    placeholder: &'a str,
+    font: Arc<Font>,
+    show_caret: bool,
+    location: &'static Location<'static>,
+}
+
+impl<'a> TextInput<'a> {
+    #[track_caller]
+    pub fn new(text: &'a str, font: &Arc<Font>) -> Self {
+        Self {
+            text,
+            placeholder: "",
+            font: Arc::clone(font),
+            show_caret: true,
+            location: Location::caller(),
+        }
+    }
+
+    /// Sets the text displayed while the field is empty.
+    pub fn placeholder(mut self, placeholder: &'a str) -> Self {
+        self.placeholder = placeholder;
+        self
+    }
+
+    /// Sets whether the caret is drawn after the text.
+    pub fn show_caret(mut self, show_caret: bool) -> Self {
+        self.show_caret = show_caret;
+        self
+    }
+}
+
+impl WidgetData for TextInput<'_> {
+    type State = State;
+
+    fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    fn into_state(self) -> Self::State {
+        State {
+            text: self.text.to_owned(),
+            placeholder: self.placeholder.to_owned(),
+            font: self.font,
+            show_caret: self.show_caret,
+        }
+    }
+
+    fn apply_changes(
+        &self,
+        state: &Self::State,
+        changes: &mut crate::widget::ChangeList<Self::State>,
+    ) {
+        let _ = (state, changes);
+    }
+}
+
+pub struct State {
+    text: String,
+    placeholder: String,
+    font: Arc<Font>,
+    show_caret: bool,
+}
+
+impl std::fmt::Debug for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("State")
+            .field("text", &self.text)
+            .field("placeholder", &self.placeholder)
+            .field("show_caret", &self.show_caret)
+            .finish()
+    }
+}
+
+impl State {
+    fn settings(&self, theme: &Theme, color: Color) -> TextSettings {
+        TextSettings {
+            font: Arc::clone(&self.font),
+            align_h: HorizontalAlign::Left,
+            align_v: VerticalAlign::Top,
+            size: theme.font_size,
+            color,
+            pos: Vec2::zero(),
+            max_width: None,
+            max_height: None,
+        }
+    }
+}
+
+impl WidgetState for State {
+    fn style(&self, _theme: &Theme) -> Style {
+        Style {
+            size: Size {
+                width: Dimension::Percent(1.),
+                height: Dimension::Auto,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        true
+    }
+
+    fn compute_size(
+        &mut self,
+        max_width: Option<f32>,
+        _max_height: Option<f32>,
+        theme: &Theme,
+    ) -> Vec2 {
+        let settings = self.settings(theme, theme.text_color);
+        let line = super::text::compute_size(&settings, &CARET.to_string());
+        vec2(max_width.unwrap_or(line.x), line.y + theme.padding)
+    }
+
+    fn draw(&mut self, bounds: utils::Rect, cv: &mut crate::Canvas, theme: &Theme) {
+        cv.fill_path(
+            &Path::rect(bounds),
+            &Paint::new().shade_solid(theme.panel_color),
+        );
+
+        let inset = vec2(theme.padding, theme.padding / 2.);
+        let (text, color) = if self.text.is_empty() && !self.placeholder.is_empty() {
+            let mut color = theme.text_color;
+            color.a *= PLACEHOLDER_ALPHA;
+            (self.placeholder.clone(), color)
+        } else if self.show_caret {
+            (format!("{}{}", self.text, CARET), theme.text_color)
+        } else {
+            (self.text.clone(), theme.text_color)
+        };
+        let mut settings = self.settings(theme, color);
+        settings.pos = bounds.pos + inset;
+        settings.max_width = Some((bounds.size.x - inset.x * 2.).max(0.));
+        settings.max_height = Some((bounds.size.y - inset.y * 2.).max(0.));
+        cv.fill_text(&text, &settings);
+    }
+}
+
+/// Applies a typed character to the text of a [`TextInput`].
+///
+/// Backspace removes the last character. Other control characters,
+/// such as Enter and Escape, are ignored so the application can
+/// handle them as keys. Printable characters are appended
+/// unless the text already has `max_chars` characters.
+pub fn type_char(text: &mut String, c: char, max_chars: usize) {
+    match c {
+        '\u{8}' => {
+            text.pop();
+        }
+        c if c.is_control() => {}
+        c => {
+            if text.chars().count() < max_chars {
+                text.push(c);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typing() {
+        let mut text = String::new();
+        for c in "hé\u{1b}llo\r".chars() {
+            type_char(&mut text, c, 4);
+        }
+        assert_eq!(text, "héll");
+
+        type_char(&mut text, '\u{8}', 4);
+        type_char(&mut text, 'y', 4);
+        assert_eq!(text, "hély");
+
+        for _ in 0..5 {
+            type_char(&mut text, '\u{8}', 4);
+        }
+        assert_eq!(text, "");
+    }
+}
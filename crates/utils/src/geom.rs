@@ -9,6 +9,15 @@ pub struct Rect {
     pub size: Vec2,
 }
 
+impl Rect {
+    /// Returns whether `point` lies inside the rectangle,
+    /// including its top and left edges.
+    pub fn contains(self, point: Vec2) -> bool {
+        let max = self.pos + self.size;
+        point.x >= self.pos.x && point.y >= self.pos.y && point.x < max.x && point.y < max.y
+    }
+}
+
 /// A color in linear RGBA space.
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 #[repr(C)]
@@ -3,10 +3,12 @@ use std::{
     sync::atomic::{AtomicUsize, Ordering},
 };
 
-/// A global allocator which tracks the amount of allocated memory.
+/// A global allocator which tracks the amount of allocated memory
+/// and the number of allocations.
 pub struct TrackAllocator<A> {
     wrapped: A,
     allocated: AtomicUsize,
+    allocations: AtomicUsize,
 }
 
 impl<A> TrackAllocator<A> {
@@ -14,6 +16,7 @@ impl<A> TrackAllocator<A> {
         Self {
             wrapped,
             allocated: AtomicUsize::new(0),
+            allocations: AtomicUsize::new(0),
         }
     }
 
@@ -21,6 +24,13 @@ impl<A> TrackAllocator<A> {
     pub fn allocated(&self) -> usize {
         self.allocated.load(Ordering::Relaxed)
     }
+
+    /// Returns the number of allocations made so far.
+    /// The difference between two calls is the number
+    /// of allocations made in between.
+    pub fn allocations(&self) -> usize {
+        self.allocations.load(Ordering::Relaxed)
+    }
 }
 
 unsafe impl<A> GlobalAlloc for TrackAllocator<A>
@@ -29,6 +39,7 @@ where
 {
     unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
         self.allocated.fetch_add(layout.size(), Ordering::Relaxed);
+        self.allocations.fetch_add(1, Ordering::Relaxed);
         self.wrapped.alloc(layout)
     }
 
@@ -39,6 +50,7 @@ where
 
     unsafe fn alloc_zeroed(&self, layout: std::alloc::Layout) -> *mut u8 {
         self.allocated.fetch_add(layout.size(), Ordering::Relaxed);
+        self.allocations.fetch_add(1, Ordering::Relaxed);
         self.wrapped.alloc_zeroed(layout)
     }
 
@@ -127,6 +127,12 @@ fn main() -> anyhow::Result<()> {
                 Rgba([40, 140, 20, u8::MAX])
             } else if src == 5 {
                 Rgba([40, 40, 160, u8::MAX])
+            } else if src == 6 {
+                Rgba([150, 150, 150, u8::MAX])
+            } else if src == 7 {
+                Rgba([70, 90, 50, u8::MAX])
+            } else if src == 8 {
+                Rgba([220, 230, 240, u8::MAX])
             } else {
                 panic!("unexpected biome value {}", src)
             };
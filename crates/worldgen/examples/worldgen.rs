@@ -0,0 +1,267 @@
+//! Benchmarks the world generator.
+//!
+//! # Usage
+//! `cargo run --release --example worldgen -- [--regions N] [--seed S] [--cpu] [--heightmap PATH]`
+//!
+//! Generates `N` regions (4 by default), each with its own seed counting
+//! up from `S` (1 by default), and prints how long each stage took for
+//! every region, followed by the average over all regions. The stages
+//! run one at a time so they can be timed separately:
+//! * `biomes`: generating the region's biome grid.
+//! * `terrain`: the region shader, which composes the blocks.
+//! * `readback`: copying the blocks and the biome grid to the CPU.
+//! * `convert`: converting the blocks into chunks.
+//! * `total`: the whole pipeline through
+//!   [`WorldGenerator::generate_into_zone`], including post-processing.
+//!
+//! wgpu 0.6 has no timestamp queries, so GPU stages are timed on the
+//! CPU: each is submitted on its own and timed until the device is idle.
+//! The times therefore include the overhead of a submission. Pass `--cpu`
+//! to time the CPU backend instead, which has no readback.
+//!
+//! The biome grid is generated with the sequence named by
+//! `VOLTZ_BIOME_SEQUENCE` and composed with the table named by
+//! `VOLTZ_COMPOSITION_TABLE`, like on the server. `--heightmap PATH` writes
+//! a heightmap of the first region to a PNG file: higher terrain is
+//! brighter, and water is blue.
+
+use std::{
+    env, iter,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context};
+use common::{blocks, world::ZoneBuilder, ChunkPos, Zone};
+use futures_executor::block_on;
+use image::{ImageBuffer, Rgb};
+use worldgen::{
+    biomes::{self, BiomeGenerator, BiomeSequence},
+    cpu,
+    region::{Region, RegionGenerator, REGION_CHUNKS, REGION_DIM},
+    Backend, CompositionTable, WorldGenerator,
+};
+
+const USAGE: &str = "usage: worldgen [--regions N] [--seed S] [--cpu] [--heightmap PATH]";
+
+const STAGES: [&str; 5] = ["biomes", "terrain", "readback", "convert", "total"];
+
+/// The time each stage took, or `None` for stages the backend skips.
+type Timings = [Option<Duration>; STAGES.len()];
+
+struct Options {
+    regions: u64,
+    seed: u64,
+    cpu: bool,
+    heightmap: Option<String>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let options = parse_options(env::args().skip(1))?;
+    let sequence = BiomeSequence::from_env();
+    let composition = CompositionTable::from_env();
+
+    let (stages, backend) = if options.cpu {
+        (Stages::Cpu, Backend::Cpu)
+    } else {
+        let (device, queue, _) =
+            common::gpu::init(wgpu::Instance::new(wgpu::BackendBit::PRIMARY), None)?;
+        let device = Arc::new(device);
+        let queue = Arc::new(queue);
+        common::gpu::launch_poll_thread(&device);
+
+        let mut biome_generator = BiomeGenerator::new(&device);
+        biome_generator.set_sequence(&sequence);
+        let region_generator = RegionGenerator::new(&device);
+        let stages = Stages::Gpu {
+            device: Arc::clone(&device),
+            queue: Arc::clone(&queue),
+            biome_generator,
+            region_generator,
+        };
+        (stages, Backend::Gpu { device, queue })
+    };
+    let generator = WorldGenerator::new(backend)
+        .with_biome_sequence(sequence.clone())
+        .with_composition(composition.clone());
+
+    let mut all = Vec::new();
+    for seed in options.seed..options.seed + options.regions {
+        let mut timings = stages.run(seed, &sequence, &composition);
+
+        let mut builder = ZoneBuilder::new(
+            ChunkPos { x: 0, y: 0, z: 0 },
+            ChunkPos {
+                x: REGION_CHUNKS as i32 - 1,
+                y: REGION_CHUNKS as i32 - 1,
+                z: REGION_CHUNKS as i32 - 1,
+            },
+        );
+        let start = Instant::now();
+        generator.generate_into_zone(&mut builder, seed);
+        timings[4] = Some(start.elapsed());
+
+        println!("seed {:>6}: {}", seed, format_timings(&timings));
+        if seed == options.seed {
+            if let Some(path) = &options.heightmap {
+                let zone = builder.build().ok().expect("missing chunks");
+                write_heightmap(&zone, seed, path)?;
+            }
+        }
+        all.push(timings);
+    }
+
+    println!("{:>12}: {}", "average", format_timings(&average(&all)));
+    Ok(())
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> anyhow::Result<Options> {
+    let mut options = Options {
+        regions: 4,
+        seed: 1,
+        cpu: false,
+        heightmap: None,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow!("missing value for {}\n{}", arg, USAGE))
+        };
+        match arg.as_str() {
+            "--regions" => {
+                options.regions = value()?.parse().context("invalid number of regions")?
+            }
+            "--seed" => options.seed = value()?.parse().context("invalid seed")?,
+            "--cpu" => options.cpu = true,
+            "--heightmap" => options.heightmap = Some(value()?),
+            _ => bail!("unknown argument '{}'\n{}", arg, USAGE),
+        }
+    }
+    Ok(options)
+}
+
+/// Runs the stages of the pipeline before post-processing one at a time.
+enum Stages {
+    Gpu {
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        biome_generator: BiomeGenerator,
+        region_generator: RegionGenerator,
+    },
+    Cpu,
+}
+
+impl Stages {
+    fn run(&self, seed: u64, sequence: &BiomeSequence, composition: &CompositionTable) -> Timings {
+        let mut timings = Timings::default();
+        let blocks = match self {
+            Stages::Gpu {
+                device,
+                queue,
+                biome_generator,
+                region_generator,
+            } => {
+                let bundle = biome_generator.prepare(device, seed, REGION_DIM as u32);
+                timings[0] = Some(time_gpu(device, queue, |encoder| {
+                    let mut pass = encoder.begin_compute_pass();
+                    biome_generator.execute(&bundle, &mut pass, queue);
+                }));
+
+                let payload =
+                    region_generator.prepare(device, bundle.output_texture(), composition);
+                timings[1] = Some(time_gpu(device, queue, |encoder| {
+                    let mut pass = encoder.begin_compute_pass();
+                    region_generator.execute(&payload, &mut pass);
+                }));
+
+                let start = Instant::now();
+                let encoder =
+                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+                let blocks = block_on(
+                    region_generator.load_blocks_from_gpu(&payload, device, queue, encoder),
+                );
+                block_on(biomes::read_grid(
+                    device,
+                    queue,
+                    bundle.output_texture(),
+                    bundle.output_size(),
+                ));
+                timings[2] = Some(start.elapsed());
+                blocks
+            }
+            Stages::Cpu => {
+                let start = Instant::now();
+                let grid = biomes::generate_on_cpu(seed, REGION_DIM as u32, sequence);
+                timings[0] = Some(start.elapsed());
+
+                let start = Instant::now();
+                let blocks = cpu::generate_area(&grid, composition, [0, 0], REGION_DIM as u32);
+                timings[1] = Some(start.elapsed());
+                blocks
+            }
+        };
+
+        let start = Instant::now();
+        let region = Region::from_gpu_data(&blocks);
+        timings[3] = Some(start.elapsed());
+        drop(region);
+        timings
+    }
+}
+
+/// Submits the commands recorded by `record` and
+/// returns how long the device took to finish them.
+fn time_gpu(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    record: impl FnOnce(&mut wgpu::CommandEncoder),
+) -> Duration {
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    record(&mut encoder);
+    let start = Instant::now();
+    queue.submit(iter::once(encoder.finish()));
+    device.poll(wgpu::Maintain::Wait);
+    start.elapsed()
+}
+
+fn average(all: &[Timings]) -> Timings {
+    let mut average = Timings::default();
+    for (stage, average) in average.iter_mut().enumerate() {
+        let times: Option<Vec<Duration>> = all.iter().map(|timings| timings[stage]).collect();
+        *average = times
+            .filter(|times| !times.is_empty())
+            .map(|times| times.iter().sum::<Duration>() / times.len() as u32);
+    }
+    average
+}
+
+fn format_timings(timings: &Timings) -> String {
+    STAGES
+        .iter()
+        .zip(timings)
+        .map(|(stage, time)| match time {
+            Some(time) => format!("{} {:>8.1}ms", stage, time.as_secs_f64() * 1000.),
+            None => format!("{} {:>10}", stage, "n/a"),
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn write_heightmap(zone: &Zone, seed: u64, path: &str) -> anyhow::Result<()> {
+    let dim = REGION_DIM as u32;
+    let image = ImageBuffer::from_fn(dim, dim, |x, z| {
+        match zone.column_summary(x as i32, z as i32) {
+            Some(summary) if summary.top.is::<blocks::Water>() => Rgb([40, 80, 200]),
+            Some(summary) => {
+                let brightness = summary.height.max(0).min(u8::MAX as i32) as u8;
+                Rgb([brightness, brightness, brightness])
+            }
+            None => Rgb([0, 0, 0]),
+        }
+    });
+    image
+        .save(path)
+        .with_context(|| format!("failed to write {}", path))?;
+    println!("Wrote the heightmap of seed {} to {}", seed, path);
+    Ok(())
+}
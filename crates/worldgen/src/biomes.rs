@@ -5,11 +5,22 @@
 //! project for generating Minecraft biomes. We operate on an array of integers, which
 //! we can "zoom" to add detail, "smooth" to remove noise, and apply other operations
 //! to map integers to biomes. The final result is an array of biomes.
+//!
+//! The order of these operations is a [`BiomeSequence`], loaded from
+//! `assets/worldgen/biome_sequence.yml` or a file named by the
+//! `VOLTZ_BIOME_SEQUENCE` environment variable.
 
+use anyhow::{bail, Context};
 use bytemuck::{Pod, Zeroable};
 use rand::{Rng, SeedableRng};
 use rand_pcg::Pcg64Mcg;
-use std::{mem::size_of, sync::Arc};
+use serde::Deserialize;
+use std::{env, fs, iter, mem::size_of, sync::Arc};
+
+use crate::{
+    cpu::{self, BiomeGrid},
+    WORLD_DIM,
+};
 
 pub const BIOME_GRID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Uint;
 const INITIAL_GRID_SIZE: u32 = 16;
@@ -17,12 +28,14 @@ const INITIAL_GRID_SIZE: u32 = 16;
 #[derive(Copy, Clone, Pod, Zeroable)]
 #[repr(C)]
 struct PushConstants {
-    seed: u32,
+    /// The seed as (low bits, high bits).
+    seed: [u32; 2],
     offset: [u32; 2],
 }
 
 pub struct BiomeBundle {
     bundle: SequenceBundle,
+    seed: u64,
     push_constants: PushConstants,
 }
 
@@ -35,6 +48,12 @@ impl BiomeBundle {
         &self.last_stage().output_texture
     }
 
+    /// Takes the output texture, dropping the textures
+    /// of intermediate stages.
+    pub fn into_output_texture(mut self) -> wgpu::Texture {
+        self.bundle.stages.pop().unwrap().output_texture
+    }
+
     fn last_stage(&self) -> &PreparedStage {
         self.bundle.stages.last().unwrap()
     }
@@ -48,7 +67,7 @@ pub struct BiomeGenerator {
 impl BiomeGenerator {
     pub fn new(device: &wgpu::Device) -> Self {
         let pipelines = Pipelines::new(device);
-        let sequence = Self::create_sequence(&pipelines);
+        let sequence = Self::create_sequence(&pipelines, &BiomeSequence::default());
 
         Self {
             sequence,
@@ -56,21 +75,27 @@ impl BiomeGenerator {
         }
     }
 
+    /// Replaces the stages run to generate biome grids.
+    pub fn set_sequence(&mut self, sequence: &BiomeSequence) {
+        self.sequence = Self::create_sequence(&self.pipelines, sequence);
+    }
+
     pub fn prepare<'a>(
         &'a self,
         device: &'a wgpu::Device,
-        seed: u32,
+        seed: u64,
         max_output_size: u32,
     ) -> BiomeBundle {
         let bundle =
             self.sequence
                 .create_bundle(device, &self.pipelines.bg_layout, max_output_size);
         let push_constants = PushConstants {
-            seed,
+            seed: [seed as u32, (seed >> 32) as u32],
             offset: [0, 0],
         };
         BiomeBundle {
             bundle,
+            seed,
             push_constants,
         }
     }
@@ -81,11 +106,7 @@ impl BiomeGenerator {
         pass: &mut wgpu::ComputePass<'a>,
         queue: &wgpu::Queue,
     ) {
-        self.upload_initial_grid(
-            bundle.push_constants.seed,
-            queue,
-            &bundle.bundle.input_texture,
-        );
+        self.upload_initial_grid(bundle.seed, queue, &bundle.bundle.input_texture);
         for stage in &bundle.bundle.stages {
             pass.set_pipeline(&stage.pipeline);
             pass.set_push_constants(0, bytemuck::cast_slice(&[bundle.push_constants]));
@@ -102,38 +123,16 @@ impl BiomeGenerator {
         ]
     }
 
-    fn create_sequence(pipelines: &Pipelines) -> Sequence {
+    fn create_sequence(pipelines: &Pipelines, sequence: &BiomeSequence) -> Sequence {
         let mut encoder = SequenceEncoder::new(pipelines);
-
-        encoder
-            .push(Zoom)
-            .push(Smooth)
-            .push(Zoom)
-            .push(Smooth)
-            .push(Land)
-            .push(Zoom)
-            .push(Smooth)
-            .push(Zoom)
-            .push(Smooth)
-            .push(Zoom)
-            .push(Smooth)
-            .push(Zoom)
-            .push(Smooth)
-            .push(Rivers)
-            .push(Zoom)
-            .push(Smooth)
-            .push(Zoom)
-            .push(Smooth)
-            .push(Zoom)
-            .push(Smooth)
-            .push(Zoom)
-            .push(Smooth);
-
+        for &stage in &sequence.stages {
+            encoder.push(stage);
+        }
         encoder.finish()
     }
 
-    fn upload_initial_grid(&self, seed: u32, queue: &wgpu::Queue, texture: &wgpu::Texture) {
-        let grid = self.generate_initial_grid(seed);
+    fn upload_initial_grid(&self, seed: u64, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+        let grid = generate_initial_grid(seed);
         queue.write_texture(
             wgpu::TextureCopyView {
                 texture,
@@ -153,25 +152,96 @@ impl BiomeGenerator {
             },
         );
     }
+}
 
-    fn generate_initial_grid<'a>(&self, seed: u32) -> Vec<u8> {
-        let mut grid = vec![0u8; (INITIAL_GRID_SIZE * INITIAL_GRID_SIZE) as usize];
-        let mut rng = Pcg64Mcg::seed_from_u64(seed as u64);
-        for x in 0..INITIAL_GRID_SIZE {
-            for y in 0..INITIAL_GRID_SIZE {
-                let value = rng.gen::<bool>() as u8;
-                grid[(y * INITIAL_GRID_SIZE + x) as usize] = value;
-            }
+fn generate_initial_grid(seed: u64) -> Vec<u8> {
+    let mut grid = vec![0u8; (INITIAL_GRID_SIZE * INITIAL_GRID_SIZE) as usize];
+    let mut rng = Pcg64Mcg::seed_from_u64(seed);
+    for x in 0..INITIAL_GRID_SIZE {
+        for y in 0..INITIAL_GRID_SIZE {
+            let value = rng.gen::<bool>() as u8;
+            grid[(y * INITIAL_GRID_SIZE + x) as usize] = value;
         }
-
-        grid
     }
+
+    grid
+}
+
+/// Generates a biome grid on the CPU. Produces the same grid as
+/// [`BiomeGenerator`] for a given seed and sequence, up to
+/// floating-point differences.
+pub fn generate_on_cpu(seed: u64, max_output_size: u32, sequence: &BiomeSequence) -> BiomeGrid {
+    let mut grid = BiomeGrid::new(INITIAL_GRID_SIZE, generate_initial_grid(seed));
+    // Stage sizes are computed from the unclamped size
+    // of the previous stage, like in `Sequence`.
+    let mut dimensions = INITIAL_GRID_SIZE;
+    for stage in &sequence.stages {
+        dimensions = stage.output_dimensions(dimensions);
+        grid = stage.run_on_cpu(&grid, max_output_size.min(dimensions), seed);
+    }
+    grid
+}
+
+/// Copies a biome grid texture `size` texels wide back to the CPU.
+pub async fn read_grid(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    size: u32,
+) -> BiomeGrid {
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let bytes_per_row = (size + alignment - 1) / alignment * alignment;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (bytes_per_row * size) as u64,
+        usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    encoder.copy_texture_to_buffer(
+        wgpu::TextureCopyView {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        wgpu::BufferCopyView {
+            buffer: &buffer,
+            layout: wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row,
+                rows_per_image: size,
+            },
+        },
+        wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth: 1,
+        },
+    );
+    queue.submit(iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice
+        .map_async(wgpu::MapMode::Read)
+        .await
+        .expect("failed to map biome grid buffer");
+    let data = slice.get_mapped_range();
+    // Drop the padding at the end of each row.
+    let biomes = data
+        .chunks_exact(bytes_per_row as usize)
+        .flat_map(|row| &row[..size as usize])
+        .copied()
+        .collect();
+    BiomeGrid::new(size, biomes)
 }
 
 struct Pipelines {
     zoom: Arc<wgpu::ComputePipeline>,
     smooth: Arc<wgpu::ComputePipeline>,
     land: Arc<wgpu::ComputePipeline>,
+    temperature: Arc<wgpu::ComputePipeline>,
+    humidity: Arc<wgpu::ComputePipeline>,
     rivers: Arc<wgpu::ComputePipeline>,
     bg_layout: wgpu::BindGroupLayout,
 }
@@ -182,12 +252,16 @@ impl Pipelines {
         let zoom = Self::create_zoom_pipeline(device, &bg_layout);
         let smooth = Self::create_smooth_pipeline(device, &bg_layout);
         let land = Self::create_land_pipeline(device, &bg_layout);
+        let temperature = Self::create_temperature_pipeline(device, &bg_layout);
+        let humidity = Self::create_humidity_pipeline(device, &bg_layout);
         let rivers = Self::create_rivers_pipeline(device, &bg_layout);
 
         Self {
             zoom,
             smooth,
             land,
+            temperature,
+            humidity,
             rivers,
             bg_layout,
         }
@@ -254,6 +328,28 @@ impl Pipelines {
         )
     }
 
+    fn create_temperature_pipeline(
+        device: &wgpu::Device,
+        bg_layout: &wgpu::BindGroupLayout,
+    ) -> Arc<wgpu::ComputePipeline> {
+        Self::create_pipeline(
+            device,
+            bg_layout,
+            wgpu::include_spirv!("../../../assets/shader/worldgen/biomegrid/temperature.spv"),
+        )
+    }
+
+    fn create_humidity_pipeline(
+        device: &wgpu::Device,
+        bg_layout: &wgpu::BindGroupLayout,
+    ) -> Arc<wgpu::ComputePipeline> {
+        Self::create_pipeline(
+            device,
+            bg_layout,
+            wgpu::include_spirv!("../../../assets/shader/worldgen/biomegrid/humidity.spv"),
+        )
+    }
+
     fn create_rivers_pipeline(
         device: &wgpu::Device,
         bg_layout: &wgpu::BindGroupLayout,
@@ -320,12 +416,179 @@ struct EncodedStage {
     work_group_size: [u32; 2],
 }
 
-trait Stage {
+trait Stage: Sync {
     fn output_dimensions(&self, input_dimensions: u32) -> u32;
 
+    /// The smallest grid the stage can take as input.
+    fn min_input_dimensions(&self) -> u32 {
+        1
+    }
+
     fn work_group_size(&self) -> [u32; 2];
 
     fn pipeline<'a>(&self, pipelines: &'a Pipelines) -> &'a Arc<wgpu::ComputePipeline>;
+
+    /// Runs the stage on the CPU, producing a grid of the given size.
+    fn run_on_cpu(&self, input: &BiomeGrid, output_dimensions: u32, seed: u64) -> BiomeGrid;
+}
+
+/// The built-in biome sequence.
+const DEFAULT_SEQUENCE: &str = include_str!("../../../assets/worldgen/biome_sequence.yml");
+
+/// An entry of the YAML biome sequence: either the
+/// name of a stage or a group of entries run repeatedly.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Stage(String),
+    Repeat { repeat: u32, stages: Vec<Entry> },
+}
+
+/// Gets a stage by the name used in biome sequence files.
+fn stage_by_name(name: &str) -> Option<&'static dyn Stage> {
+    match name {
+        "zoom" => Some(&Zoom),
+        "smooth" => Some(&Smooth),
+        "land" => Some(&Land),
+        "temperature" => Some(&Temperature),
+        "humidity" => Some(&Humidity),
+        "rivers" => Some(&Rivers),
+        _ => None,
+    }
+}
+
+/// The stages run to generate a biome grid, in order.
+#[derive(Clone)]
+pub struct BiomeSequence {
+    stages: Vec<&'static dyn Stage>,
+    digest: u64,
+}
+
+impl BiomeSequence {
+    /// Parses a biome sequence: a list of stage names (`zoom`, `smooth`,
+    /// `land`, `temperature`, `humidity` or `rivers`) and groups like
+    /// `{ repeat: 4, stages: [zoom, smooth] }`.
+    ///
+    /// The sequence must produce a grid covering [`WORLD_DIM`] blocks.
+    pub fn from_yaml(yaml: &str) -> anyhow::Result<Self> {
+        let entries: Vec<Entry> = serde_yaml::from_str(yaml).context("malformed biome sequence")?;
+        let mut names = Vec::new();
+        Self::flatten(&entries, &mut names)?;
+        let stages: Vec<_> = names
+            .iter()
+            .map(|name| stage_by_name(name).expect("flattened an unknown stage"))
+            .collect();
+
+        let mut dimensions = INITIAL_GRID_SIZE;
+        for (i, stage) in stages.iter().enumerate() {
+            if dimensions < stage.min_input_dimensions() {
+                bail!(
+                    "stage {} of the biome sequence gets a {}x{} grid, which is too small",
+                    i + 1,
+                    dimensions,
+                    dimensions
+                );
+            }
+            dimensions = stage.output_dimensions(dimensions);
+        }
+        if dimensions < WORLD_DIM {
+            bail!(
+                "the biome sequence produces a {}x{} grid, but the world is {} blocks across",
+                dimensions,
+                dimensions,
+                WORLD_DIM
+            );
+        }
+
+        Ok(Self {
+            stages,
+            digest: digest(&names),
+        })
+    }
+
+    /// Reads the sequence from the file named by `VOLTZ_BIOME_SEQUENCE`,
+    /// or uses the built-in one if the variable isn't set or the file is invalid.
+    pub fn from_env() -> Self {
+        let path = match env::var_os("VOLTZ_BIOME_SEQUENCE") {
+            Some(path) => path,
+            None => return Self::default(),
+        };
+        let sequence = fs::read_to_string(&path)
+            .context("failed to read the file")
+            .and_then(|yaml| Self::from_yaml(&yaml));
+        match sequence {
+            Ok(sequence) => {
+                log::info!("Using the biome sequence in {:?}", path);
+                sequence
+            }
+            Err(e) => {
+                log::error!("Ignoring the biome sequence in {:?}: {:#}", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Returns the number of stages.
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Returns a digest of the stages. Sequences with equal
+    /// digests generate the same biomes for every seed.
+    ///
+    /// The digest is stable across processes and platforms.
+    pub fn digest(&self) -> u64 {
+        self.digest
+    }
+
+    /// Expands repeated groups into the names of the stages they run.
+    fn flatten<'a>(entries: &'a [Entry], names: &mut Vec<&'a str>) -> anyhow::Result<()> {
+        for entry in entries {
+            match entry {
+                Entry::Stage(name) => {
+                    if stage_by_name(name).is_none() {
+                        bail!("unknown biome stage '{}'", name);
+                    }
+                    names.push(name);
+                }
+                Entry::Repeat {
+                    repeat,
+                    stages: group,
+                } => {
+                    for _ in 0..*repeat {
+                        Self::flatten(group, names)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Computes the digest of a sequence of stages from their names.
+fn digest(names: &[&str]) -> u64 {
+    // FNV-1a, like the block registry digest.
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+    let mut hash = OFFSET_BASIS;
+    for name in names {
+        for &byte in name.as_bytes().iter().chain(&[0]) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    hash
+}
+
+impl Default for BiomeSequence {
+    /// Loads the built-in biome sequence.
+    fn default() -> Self {
+        Self::from_yaml(DEFAULT_SEQUENCE).expect("invalid built-in biome sequence")
+    }
 }
 
 struct Zoom;
@@ -342,6 +605,10 @@ impl Stage for Zoom {
     fn pipeline<'a>(&self, pipelines: &'a Pipelines) -> &'a Arc<wgpu::ComputePipeline> {
         &pipelines.zoom
     }
+
+    fn run_on_cpu(&self, input: &BiomeGrid, output_dimensions: u32, seed: u64) -> BiomeGrid {
+        cpu::zoom(input, output_dimensions, seed)
+    }
 }
 
 struct Smooth;
@@ -351,6 +618,10 @@ impl Stage for Smooth {
         input_dimensions - 2
     }
 
+    fn min_input_dimensions(&self) -> u32 {
+        3
+    }
+
     fn work_group_size(&self) -> [u32; 2] {
         [16; 2]
     }
@@ -358,6 +629,10 @@ impl Stage for Smooth {
     fn pipeline<'a>(&self, pipelines: &'a Pipelines) -> &'a Arc<wgpu::ComputePipeline> {
         &pipelines.smooth
     }
+
+    fn run_on_cpu(&self, input: &BiomeGrid, output_dimensions: u32, seed: u64) -> BiomeGrid {
+        cpu::smooth(input, output_dimensions, seed)
+    }
 }
 
 struct Land;
@@ -374,6 +649,50 @@ impl Stage for Land {
     fn pipeline<'a>(&self, pipelines: &'a Pipelines) -> &'a Arc<wgpu::ComputePipeline> {
         &pipelines.land
     }
+
+    fn run_on_cpu(&self, input: &BiomeGrid, output_dimensions: u32, seed: u64) -> BiomeGrid {
+        cpu::land(input, output_dimensions, seed)
+    }
+}
+
+struct Temperature;
+
+impl Stage for Temperature {
+    fn output_dimensions(&self, input_dimensions: u32) -> u32 {
+        input_dimensions
+    }
+
+    fn work_group_size(&self) -> [u32; 2] {
+        [32; 2]
+    }
+
+    fn pipeline<'a>(&self, pipelines: &'a Pipelines) -> &'a Arc<wgpu::ComputePipeline> {
+        &pipelines.temperature
+    }
+
+    fn run_on_cpu(&self, input: &BiomeGrid, output_dimensions: u32, seed: u64) -> BiomeGrid {
+        cpu::temperature(input, output_dimensions, seed)
+    }
+}
+
+struct Humidity;
+
+impl Stage for Humidity {
+    fn output_dimensions(&self, input_dimensions: u32) -> u32 {
+        input_dimensions
+    }
+
+    fn work_group_size(&self) -> [u32; 2] {
+        [32; 2]
+    }
+
+    fn pipeline<'a>(&self, pipelines: &'a Pipelines) -> &'a Arc<wgpu::ComputePipeline> {
+        &pipelines.humidity
+    }
+
+    fn run_on_cpu(&self, input: &BiomeGrid, output_dimensions: u32, seed: u64) -> BiomeGrid {
+        cpu::humidity(input, output_dimensions, seed)
+    }
 }
 
 struct Rivers;
@@ -383,6 +702,10 @@ impl Stage for Rivers {
         input_dimensions - 2
     }
 
+    fn min_input_dimensions(&self) -> u32 {
+        3
+    }
+
     fn work_group_size(&self) -> [u32; 2] {
         [16; 2]
     }
@@ -390,6 +713,10 @@ impl Stage for Rivers {
     fn pipeline<'a>(&self, pipelines: &'a Pipelines) -> &'a Arc<wgpu::ComputePipeline> {
         &pipelines.rivers
     }
+
+    fn run_on_cpu(&self, input: &BiomeGrid, output_dimensions: u32, seed: u64) -> BiomeGrid {
+        cpu::rivers(input, output_dimensions, seed)
+    }
 }
 
 struct SequenceEncoder<'a> {
@@ -405,7 +732,7 @@ impl<'a> SequenceEncoder<'a> {
         }
     }
 
-    pub fn push(&mut self, stage: impl Stage) -> &mut Self {
+    pub fn push(&mut self, stage: &dyn Stage) -> &mut Self {
         let input_dimensions = self.sequence.output_dimensions();
         let output_dimensions = stage.output_dimensions(input_dimensions);
         let pipeline = Arc::clone(stage.pipeline(&self.pipelines));
@@ -549,3 +876,120 @@ fn texture_descriptor(size: u32) -> wgpu::TextureDescriptor<'static> {
 fn default_view(texture: &wgpu::Texture) -> wgpu::TextureView {
     texture.create_view(&Default::default())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_repeated_stages() {
+        let sequence = BiomeSequence::default();
+        assert_eq!(sequence.len(), 24);
+
+        let flat = "[zoom, smooth, zoom, smooth, zoom, smooth, zoom, smooth, zoom, smooth, \
+                    zoom, smooth, zoom, smooth, zoom, smooth, zoom, smooth]";
+        let grouped = "[{ repeat: 9, stages: [zoom, smooth] }]";
+        let (flat, grouped) = (
+            BiomeSequence::from_yaml(flat).unwrap(),
+            BiomeSequence::from_yaml(grouped).unwrap(),
+        );
+        let seed = 5;
+        assert_eq!(
+            generate_on_cpu(seed, 64, &flat),
+            generate_on_cpu(seed, 64, &grouped)
+        );
+        assert_eq!(flat.digest(), grouped.digest());
+        assert_ne!(flat.digest(), sequence.digest());
+    }
+
+    #[test]
+    fn rejects_invalid_sequences() {
+        // Unknown stage.
+        assert!(BiomeSequence::from_yaml("[zoom, grow]").is_err());
+        // Smooths the grid away.
+        assert!(BiomeSequence::from_yaml("[{ repeat: 8, stages: [smooth] }]").is_err());
+        // Too small for the world.
+        assert!(BiomeSequence::from_yaml("[zoom, smooth, land]").is_err());
+    }
+
+    /// Builds a sequence without checking that it covers the world,
+    /// so tests can run a few stages.
+    fn unchecked(names: &[&str]) -> BiomeSequence {
+        BiomeSequence {
+            stages: names
+                .iter()
+                .map(|name| stage_by_name(name).unwrap())
+                .collect(),
+            digest: digest(names),
+        }
+    }
+
+    /// Runs `sequence` in compute shaders, or returns `None`
+    /// if there is no adapter to run them on.
+    fn generate_on_gpu(
+        seed: u64,
+        max_output_size: u32,
+        sequence: &BiomeSequence,
+    ) -> Option<BiomeGrid> {
+        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+        let (device, queue, _) = match common::gpu::init(instance, None) {
+            Ok(gpu) => gpu,
+            Err(e) => {
+                eprintln!("skipping GPU biome test: {:#}", e);
+                return None;
+            }
+        };
+        let device = Arc::new(device);
+        common::gpu::launch_poll_thread(&device);
+
+        let mut generator = BiomeGenerator::new(&device);
+        generator.set_sequence(sequence);
+        let bundle = generator.prepare(&device, seed, max_output_size);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass();
+            generator.execute(&bundle, &mut pass, &queue);
+        }
+        queue.submit(iter::once(encoder.finish()));
+
+        Some(futures_executor::block_on(read_grid(
+            &device,
+            &queue,
+            bundle.output_texture(),
+            bundle.output_size(),
+        )))
+    }
+
+    #[test]
+    fn gpu_stages_match_cpu() {
+        // Larger than `u32::MAX` so the high seed bits matter.
+        let seed = 0x1234_5678_9abc_def0;
+
+        // Integer-only stages match exactly.
+        let sequence = unchecked(&["zoom", "smooth", "zoom", "smooth", "zoom", "rivers"]);
+        let gpu = match generate_on_gpu(seed, 64, &sequence) {
+            Some(grid) => grid,
+            None => return,
+        };
+        assert_eq!(gpu, generate_on_cpu(seed, 64, &sequence));
+
+        // `land` samples noise, so allow a few cells on
+        // biome boundaries to round differently.
+        let sequence = unchecked(&["zoom", "smooth", "zoom", "land", "smooth", "rivers"]);
+        let gpu = generate_on_gpu(seed, 64, &sequence).unwrap();
+        let cpu = generate_on_cpu(seed, 64, &sequence);
+        assert_eq!(gpu.size(), cpu.size());
+        let differing = gpu
+            .as_bytes()
+            .iter()
+            .zip(cpu.as_bytes())
+            .filter(|(a, b)| a != b)
+            .count();
+        assert!(
+            differing * 100 <= cpu.as_bytes().len(),
+            "{} of {} cells differ",
+            differing,
+            cpu.as_bytes().len()
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Carves caves into generated terrain.
+//!
+//! Tunnels follow the intersection of the zero isosurfaces of
+//! two 3D noise fields. Whether a block is carved depends only
+//! on its position and the seed, so separately generated chunk
+//! columns line up.
+
+use rayon::prelude::*;
+
+use crate::{
+    noise::{derive_seed, random, simplex_3d, SALT_CAVES},
+    region::{BLOCK_AIR, BLOCK_WATER, REGION_DIM},
+};
+
+/// Minimum number of blocks between a cave and water above it.
+const WATER_CLEARANCE: usize = 4;
+
+/// Parameters of the cave carving pass.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CaveSettings {
+    /// Frequency of the noise defining tunnels. Higher values
+    /// produce more tunnels with tighter turns.
+    pub frequency: f32,
+    /// Thickness of tunnels, as a noise threshold between 0 and 1.
+    /// A size of zero disables caves.
+    pub size: f32,
+    /// Scale applied to the vertical frequency. Values above
+    /// one produce flatter, more horizontal tunnels.
+    pub vertical_scale: f32,
+    /// The lowest Y coordinate at which blocks are carved.
+    pub min_y: usize,
+    /// Blocks at or above this Y coordinate are never carved.
+    pub max_y: usize,
+}
+
+impl Default for CaveSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 0.015,
+            size: 0.06,
+            vertical_scale: 2.,
+            min_y: 1,
+            max_y: 64,
+        }
+    }
+}
+
+/// Carves caves into block data laid out as output by the
+/// region shader. `offset` is the position in blocks of the
+/// area's minimum corner, and `output_dim` is its width.
+pub(crate) fn carve(
+    blocks: &mut [u8],
+    offset: [i32; 2],
+    output_dim: usize,
+    seed: u64,
+    settings: &CaveSettings,
+) {
+    if settings.size <= 0. {
+        return;
+    }
+
+    let seed = derive_seed(seed, SALT_CAVES);
+    let noise_offsets = [noise_offset(seed, 0), noise_offset(seed, 1)];
+    blocks
+        .par_chunks_mut(REGION_DIM)
+        .enumerate()
+        .for_each(|(index, column)| {
+            let x = offset[0] + (index / output_dim) as i32;
+            let z = offset[1] + (index % output_dim) as i32;
+            carve_column(column, x, z, noise_offsets, settings);
+        });
+}
+
+/// Offsets noise coordinates so that each
+/// seed and noise field produces different caves.
+fn noise_offset(seed: u32, field: u32) -> f32 {
+    (random(seed, field) % 4096) as f32
+}
+
+fn carve_column(
+    column: &mut [u8],
+    x: i32,
+    z: i32,
+    noise_offsets: [f32; 2],
+    settings: &CaveSettings,
+) {
+    // Keep caves from opening up beneath water.
+    let max_y = column
+        .iter()
+        .position(|&block| block == BLOCK_WATER)
+        .map(|y| y.saturating_sub(WATER_CLEARANCE))
+        .unwrap_or(REGION_DIM)
+        .min(settings.max_y);
+
+    let frequency = settings.frequency;
+    for y in settings.min_y..max_y {
+        if column[y] == BLOCK_AIR {
+            continue;
+        }
+
+        let pos = [
+            x as f32 * frequency,
+            y as f32 * frequency * settings.vertical_scale,
+            z as f32 * frequency,
+        ];
+        let in_tunnel = noise_offsets.iter().all(|&offset| {
+            simplex_3d([pos[0] + offset, pos[1] + offset, pos[2] + offset]).abs() < settings.size
+        });
+        if in_tunnel {
+            column[y] = BLOCK_AIR;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region::BLOCK_STONE;
+
+    const DIM: usize = 16;
+
+    fn solid_area() -> Vec<u8> {
+        vec![BLOCK_STONE; DIM * DIM * REGION_DIM]
+    }
+
+    fn carved(seed: u64, settings: &CaveSettings) -> Vec<u8> {
+        let mut blocks = solid_area();
+        carve(&mut blocks, [64, -32], DIM, seed, settings);
+        blocks
+    }
+
+    #[test]
+    fn carves_within_bounds() {
+        let settings = CaveSettings {
+            size: 0.2,
+            ..Default::default()
+        };
+        let blocks = carved(5, &settings);
+        assert_eq!(blocks, carved(5, &settings));
+
+        let mut num_carved = 0;
+        for column in blocks.chunks_exact(REGION_DIM) {
+            for (y, &block) in column.iter().enumerate() {
+                if block == BLOCK_AIR {
+                    assert!(y >= settings.min_y && y < settings.max_y);
+                    num_carved += 1;
+                }
+            }
+        }
+        assert!(num_carved > 0);
+    }
+
+    #[test]
+    fn zero_size_disables_caves() {
+        let settings = CaveSettings {
+            size: 0.,
+            ..Default::default()
+        };
+        assert_eq!(carved(5, &settings), solid_area());
+    }
+
+    #[test]
+    fn water_is_not_undermined() {
+        let settings = CaveSettings {
+            size: 1.,
+            ..Default::default()
+        };
+        let mut blocks = solid_area();
+        for column in blocks.chunks_exact_mut(REGION_DIM) {
+            column[40] = BLOCK_WATER;
+        }
+        carve(&mut blocks, [0, 0], DIM, 5, &settings);
+        for column in blocks.chunks_exact(REGION_DIM) {
+            assert!(column[40 - WATER_CLEARANCE..]
+                .iter()
+                .all(|&block| block != BLOCK_AIR));
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! The blocks making up the terrain of each biome.
+//!
+//! Composition turns the solid parts of the terrain into blocks: a surface
+//! block on top, a few filler blocks below it, and stone beneath those.
+//! Terrain whose top is below sea level gets the biome's underwater block
+//! instead. The blocks of each biome come from a [`CompositionTable`]
+//! loaded from `assets/worldgen/composition.yml` or a file named by the
+//! `VOLTZ_COMPOSITION_TABLE` environment variable. The region shader
+//! reads it from a storage buffer, so biomes change their blocks without
+//! shader edits.
+
+use anyhow::{bail, Context};
+use bytemuck::{Pod, Zeroable};
+use serde::Deserialize;
+use std::{env, fs};
+
+use crate::region::{self, BLOCK_STONE, SEA_LEVEL};
+
+/// The slugs of the biomes, ordered by ID.
+/// Needs to match shader/include/biomes.glsl.
+pub const BIOMES: [&str; 9] = [
+    "ocean",
+    "plains",
+    "hills",
+    "desert",
+    "forest",
+    "river",
+    "mountains",
+    "swamp",
+    "tundra",
+];
+
+/// The built-in composition table.
+const DEFAULT_TABLE: &str = include_str!("../../../assets/worldgen/composition.yml");
+
+/// An entry of the YAML composition table.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    biome: String,
+    surface: String,
+    filler: String,
+    depth: u32,
+    underwater: String,
+}
+
+/// The blocks making up the terrain of a biome, as block indexes output
+/// by the region shader. Matches the `Composition` struct in region.glsl.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub struct Composition {
+    pub surface: u32,
+    pub filler: u32,
+    /// The number of filler blocks below the surface.
+    pub depth: u32,
+    pub underwater: u32,
+}
+
+impl Composition {
+    /// Returns the block `depth` blocks below the top of terrain whose
+    /// top block is at `surface_y`. Mirrors `composeBlock` in region.glsl.
+    pub fn block(&self, depth: usize, surface_y: usize) -> u8 {
+        let block = if depth > self.depth as usize {
+            BLOCK_STONE as u32
+        } else if surface_y < SEA_LEVEL {
+            self.underwater
+        } else if depth == 0 {
+            self.surface
+        } else {
+            self.filler
+        };
+        block as u8
+    }
+}
+
+/// The [`Composition`] of each biome, indexed by biome ID.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompositionTable {
+    biomes: Vec<Composition>,
+}
+
+impl CompositionTable {
+    /// Parses a composition table, which lists each biome
+    /// in [`BIOMES`] once in order of their IDs.
+    pub fn from_yaml(yaml: &str) -> anyhow::Result<Self> {
+        let entries: Vec<Entry> =
+            serde_yaml::from_str(yaml).context("malformed composition table")?;
+        if entries.len() != BIOMES.len() {
+            bail!(
+                "composition table has {} biomes, but there are {}",
+                entries.len(),
+                BIOMES.len()
+            );
+        }
+
+        let mut biomes = Vec::with_capacity(entries.len());
+        for (entry, &biome) in entries.iter().zip(&BIOMES) {
+            if entry.biome != biome {
+                bail!(
+                    "expected biome '{}' in composition table, found '{}'",
+                    biome,
+                    entry.biome
+                );
+            }
+            let block = |slug: &str| {
+                region::block_index(slug).map(u32::from).with_context(|| {
+                    format!(
+                        "biome '{}' has block '{}', which terrain can't contain",
+                        biome, slug
+                    )
+                })
+            };
+            biomes.push(Composition {
+                surface: block(&entry.surface)?,
+                filler: block(&entry.filler)?,
+                depth: entry.depth,
+                underwater: block(&entry.underwater)?,
+            });
+        }
+        Ok(Self { biomes })
+    }
+
+    /// Reads the table from the file named by `VOLTZ_COMPOSITION_TABLE`,
+    /// or uses the built-in one if the variable isn't set or the file is invalid.
+    pub fn from_env() -> Self {
+        let path = match env::var_os("VOLTZ_COMPOSITION_TABLE") {
+            Some(path) => path,
+            None => return Self::default(),
+        };
+        let table = fs::read_to_string(&path)
+            .context("failed to read the file")
+            .and_then(|yaml| Self::from_yaml(&yaml));
+        match table {
+            Ok(table) => {
+                log::info!("Using the composition table in {:?}", path);
+                table
+            }
+            Err(e) => {
+                log::error!("Ignoring the composition table in {:?}: {:#}", path, e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Gets the composition of a biome by its ID.
+    pub fn get(&self, biome: u8) -> &Composition {
+        &self.biomes[biome as usize]
+    }
+
+    /// Returns the table as uploaded to the region shader.
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.biomes)
+    }
+}
+
+impl Default for CompositionTable {
+    /// Loads the built-in composition table.
+    fn default() -> Self {
+        Self::from_yaml(DEFAULT_TABLE).expect("invalid built-in composition table")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn biomes_match_shader() {
+        let source = include_str!("../../../assets/shader/include/biomes.glsl");
+        let mut biomes: Vec<_> = region::shader_defines(source, "BIOME_").collect();
+        biomes.sort_by_key(|&(_, id)| id);
+        let slugs: Vec<_> = biomes.iter().map(|(slug, _)| slug.as_str()).collect();
+        assert_eq!(slugs, BIOMES);
+    }
+
+    #[test]
+    fn loads_default_table() {
+        let table = CompositionTable::default();
+        assert_eq!(table.as_bytes().len(), BIOMES.len() * 16);
+        let index = |slug| region::block_index(slug).unwrap() as u32;
+        assert_eq!(table.get(1).surface, index("grass"));
+        assert_eq!(table.get(1).filler, index("dirt"));
+        assert_eq!(table.get(3).surface, index("sand"));
+    }
+
+    #[test]
+    fn rejects_invalid_tables() {
+        let table = DEFAULT_TABLE.replace("surface: grass", "surface: ladder");
+        assert!(CompositionTable::from_yaml(&table).is_err());
+        let table = DEFAULT_TABLE.replace("biome: hills", "biome: mountains");
+        assert!(CompositionTable::from_yaml(&table).is_err());
+        let (first_half, _) =
+            DEFAULT_TABLE.split_at(DEFAULT_TABLE.find("- biome: desert").unwrap());
+        assert!(CompositionTable::from_yaml(first_half).is_err());
+    }
+
+    #[test]
+    fn layers_terrain() {
+        let plains = *CompositionTable::default().get(1);
+        let above_sea = SEA_LEVEL + 10;
+        assert_eq!(plains.block(0, above_sea), plains.surface as u8);
+        assert_eq!(plains.block(3, above_sea), plains.filler as u8);
+        assert_eq!(plains.block(4, above_sea), BLOCK_STONE);
+        // Lake beds are underwater blocks all the way down to the stone.
+        let lake_bed = SEA_LEVEL - 5;
+        assert_eq!(plains.block(0, lake_bed), plains.underwater as u8);
+        assert_eq!(plains.block(3, lake_bed), plains.underwater as u8);
+        assert_eq!(plains.block(4, lake_bed), BLOCK_STONE);
+    }
+}
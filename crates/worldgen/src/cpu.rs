@@ -0,0 +1,424 @@
+//! The CPU backend, for machines without a usable GPU.
+//!
+//! Each function here is a port of the corresponding compute shader
+//! in `shader/worldgen`. Output is deterministic for a given seed and
+//! matches the GPU backend up to floating-point differences.
+
+use std::sync::{Arc, Mutex};
+
+use rayon::prelude::*;
+
+use crate::{
+    biomes::{self, BiomeSequence},
+    composition::{CompositionTable, BIOMES},
+    noise::{
+        derive_seed, fbm_3d, random, simplex_2d, SALT_HUMIDITY, SALT_LAND, SALT_SMOOTH,
+        SALT_TEMPERATURE, SALT_ZOOM,
+    },
+    region::{BLOCK_AIR, REGION_DIM},
+    WORLD_DIM,
+};
+use common::chunk::CHUNK_DIM;
+
+// Needs to match biome definitions in shader/include/biomes.glsl
+const BIOME_OCEAN: u8 = 0;
+const BIOME_PLAINS: u8 = 1;
+const BIOME_HILLS: u8 = 2;
+const BIOME_DESERT: u8 = 3;
+const BIOME_FOREST: u8 = 4;
+const BIOME_RIVER: u8 = 5;
+const BIOME_MOUNTAINS: u8 = 6;
+const BIOME_SWAMP: u8 = 7;
+const BIOME_TUNDRA: u8 = 8;
+const NUM_BIOMES: usize = BIOMES.len();
+
+// Per-biome terrain parameters from region.glsl.
+const BIOME_FREQUENCIES: [f32; NUM_BIOMES] =
+    [0.0, 0.005, 0.012, 0.01, 0.011, 0.0, 0.008, 0.006, 0.007];
+const BIOME_AMPLITUDES: [f32; NUM_BIOMES] = [1.0, 0.07, 0.025, 0.2, 0.15, 1.0, 0.01, 0.25, 0.06];
+const BIOME_MIDPOINTS: [f32; NUM_BIOMES] = [50.0, 64.0, 75.0, 65.0, 66.0, 58.0, 90.0, 63.0, 68.0];
+
+/// Radius of the area sampled around each column
+/// to blend terrain between biomes.
+const BLEND_RADIUS: i32 = 7;
+
+/// A square 2D grid of biome IDs, the CPU counterpart
+/// of the biome grid texture.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BiomeGrid {
+    size: u32,
+    biomes: Vec<u8>,
+}
+
+impl BiomeGrid {
+    pub(crate) fn new(size: u32, biomes: Vec<u8>) -> Self {
+        assert_eq!(biomes.len(), (size * size) as usize);
+        Self { size, biomes }
+    }
+
+    /// Creates a grid by evaluating `f` for each cell in parallel.
+    fn from_fn(size: u32, f: impl Fn(i32, i32) -> u8 + Sync) -> Self {
+        let mut biomes = vec![0; (size * size) as usize];
+        biomes
+            .par_chunks_mut(size as usize)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, biome) in row.iter_mut().enumerate() {
+                    *biome = f(x as i32, y as i32);
+                }
+            });
+        Self { size, biomes }
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Gets the biome at the given position. Positions
+    /// outside the grid are ocean, like out-of-bounds
+    /// image loads on the GPU.
+    pub fn get(&self, x: i32, y: i32) -> u8 {
+        if x < 0 || y < 0 || x >= self.size as i32 || y >= self.size as i32 {
+            return BIOME_OCEAN;
+        }
+        self.biomes[y as usize * self.size as usize + x as usize]
+    }
+
+    /// Gets the grid's data in row-major order.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.biomes
+    }
+}
+
+/// Port of `zoom.glsl`.
+pub(crate) fn zoom(input: &BiomeGrid, size: u32, seed: u64) -> BiomeGrid {
+    let seed = derive_seed(seed, SALT_ZOOM);
+    BiomeGrid::from_fn(size, |x, y| {
+        let top_left = input.get(x / 2, y / 2);
+        let top_right = input.get((x + 1) / 2, y / 2);
+        let bottom_left = input.get(x / 2, (y + 1) / 2);
+        let bottom_right = input.get((x + 1) / 2, (y + 1) / 2);
+
+        let rand = random((x as u32).wrapping_add(seed), (y as u32).wrapping_add(seed)) % 4;
+        [top_left, top_right, bottom_left, bottom_right][rand as usize]
+    })
+}
+
+/// Port of `smooth.glsl`.
+pub(crate) fn smooth(input: &BiomeGrid, size: u32, seed: u64) -> BiomeGrid {
+    let seed = derive_seed(seed, SALT_SMOOTH);
+    BiomeGrid::from_fn(size, |x, y| {
+        let (x, y) = (x + 1, y + 1);
+        let left = input.get(x - 1, y);
+        let right = input.get(x + 1, y);
+        let top = input.get(x, y - 1);
+        let bottom = input.get(x, y + 1);
+
+        let horizontal = left == right;
+        let vertical = top == bottom;
+
+        if horizontal && vertical {
+            let rand = random((x as u32).wrapping_add(seed), (y as u32).wrapping_add(seed));
+            [left, top][rand as usize % 2]
+        } else if horizontal {
+            left
+        } else if vertical {
+            top
+        } else {
+            input.get(x, y)
+        }
+    })
+}
+
+/// Port of `land.glsl`.
+pub(crate) fn land(input: &BiomeGrid, size: u32, seed: u64) -> BiomeGrid {
+    let noise_offset = (derive_seed(seed, SALT_LAND) % 4096) as f32;
+    BiomeGrid::from_fn(size, |x, y| {
+        if input.get(x, y) == 0 {
+            // Ocean: leave unchanged.
+            return BIOME_OCEAN;
+        }
+
+        let noise_x = (x as f32 + noise_offset) * 0.05;
+        let noise_y = (y as f32 + noise_offset) * 0.05;
+        let noise = simplex_2d(noise_x, noise_y);
+        if noise < -0.5 {
+            BIOME_FOREST
+        } else if noise < -0.2 {
+            BIOME_HILLS
+        } else if noise < 0.4 {
+            BIOME_PLAINS
+        } else {
+            BIOME_DESERT
+        }
+    })
+}
+
+/// Port of `temperature.glsl`.
+pub(crate) fn temperature(input: &BiomeGrid, size: u32, seed: u64) -> BiomeGrid {
+    let noise_offset = (derive_seed(seed, SALT_TEMPERATURE) % 4096) as f32;
+    BiomeGrid::from_fn(size, |x, y| {
+        let biome = input.get(x, y);
+        if biome == BIOME_OCEAN {
+            return biome;
+        }
+
+        let noise_x = (x as f32 + noise_offset) * 0.03;
+        let noise_y = (y as f32 + noise_offset) * 0.03;
+        let temperature = simplex_2d(noise_x, noise_y);
+        if temperature < -0.45 {
+            BIOME_TUNDRA
+        } else if temperature < -0.1 && biome == BIOME_HILLS {
+            BIOME_MOUNTAINS
+        } else {
+            biome
+        }
+    })
+}
+
+/// Port of `humidity.glsl`.
+pub(crate) fn humidity(input: &BiomeGrid, size: u32, seed: u64) -> BiomeGrid {
+    let noise_offset = (derive_seed(seed, SALT_HUMIDITY) % 4096) as f32;
+    BiomeGrid::from_fn(size, |x, y| {
+        let biome = input.get(x, y);
+        if biome != BIOME_PLAINS && biome != BIOME_FOREST {
+            return biome;
+        }
+
+        let noise_x = (x as f32 + noise_offset) * 0.04;
+        let noise_y = (y as f32 + noise_offset) * 0.04;
+        if simplex_2d(noise_x, noise_y) > 0.45 {
+            BIOME_SWAMP
+        } else {
+            biome
+        }
+    })
+}
+
+/// Port of `rivers.glsl`.
+pub(crate) fn rivers(input: &BiomeGrid, size: u32, _seed: u64) -> BiomeGrid {
+    BiomeGrid::from_fn(size, |x, y| {
+        let (x, y) = (x + 1, y + 1);
+        let left = input.get(x - 1, y);
+        let right = input.get(x + 1, y);
+        let top = input.get(x, y - 1);
+        let bottom = input.get(x, y + 1);
+
+        let river = (left != right && left != BIOME_OCEAN && right != BIOME_OCEAN)
+            || (top != bottom && top != BIOME_OCEAN && bottom != BIOME_OCEAN);
+        if river {
+            BIOME_RIVER
+        } else {
+            input.get(x, y)
+        }
+    })
+}
+
+/// Port of `region.glsl`. Generates the blocks of an area
+/// `output_dim` blocks wide and deep whose minimum corner
+/// is at `offset` in the biome grid.
+///
+/// The returned data has the same layout as the GPU block buffer.
+pub fn generate_area(
+    biome_grid: &BiomeGrid,
+    composition: &CompositionTable,
+    offset: [i32; 2],
+    output_dim: u32,
+) -> Vec<u8> {
+    let mut blocks = vec![BLOCK_AIR; output_dim as usize * output_dim as usize * REGION_DIM];
+    blocks
+        .par_chunks_mut(REGION_DIM)
+        .enumerate()
+        .for_each(|(index, column)| {
+            let local_x = (index / output_dim as usize) as i32;
+            let local_z = (index % output_dim as usize) as i32;
+            generate_column(
+                biome_grid,
+                composition,
+                offset[0] + local_x,
+                offset[1] + local_z,
+                column,
+            );
+        });
+    blocks
+}
+
+fn generate_column(
+    biome_grid: &BiomeGrid,
+    composition: &CompositionTable,
+    x: i32,
+    z: i32,
+    column: &mut [u8],
+) {
+    let biome = biome_grid.get(x, z);
+    let frequency = BIOME_FREQUENCIES[biome as usize];
+
+    // Blend terrain parameters with those of nearby biomes.
+    let mut amplitude = 0.0;
+    let mut midpoint = 0.0;
+    let mut weight_sum = 0.0;
+    for dx in -BLEND_RADIUS..=BLEND_RADIUS {
+        for dz in -BLEND_RADIUS..=BLEND_RADIUS {
+            let weight = 10.0 / (((dx * dx + dz * dz) as f32).sqrt() + 1.0);
+            let sample = biome_grid.get(x + dx, z + dz) as usize;
+
+            amplitude += BIOME_AMPLITUDES[sample] * weight;
+            midpoint += BIOME_MIDPOINTS[sample] * weight;
+            weight_sum += weight;
+        }
+    }
+    let amplitude = amplitude / weight_sum;
+    let midpoint = midpoint / weight_sum;
+
+    let mut solid = [false; REGION_DIM];
+    for (y, solid) in solid.iter_mut().enumerate() {
+        let pos = [x as f32, y as f32, z as f32];
+        let scaled = [pos[0] * frequency, pos[1] * frequency, pos[2] * frequency];
+        let noise1 = fbm_3d(scaled, 2, 2.0, 0.5);
+        let noise2 = fbm_3d([scaled[0], scaled[1] + 1000.0, scaled[2]], 2, 2.0, 0.5);
+        let choice = fbm_3d(
+            [pos[0] * 0.005, pos[1] * 0.005, pos[2] * 0.005],
+            2,
+            2.0,
+            0.5,
+        );
+        let noise = noise1 * (1.0 - choice) + noise2 * choice;
+
+        let mut gradient = (pos[1] - midpoint + 1.0) * amplitude;
+        if gradient < 0.0 {
+            gradient *= 4.0;
+        }
+
+        let density = -noise.abs() + gradient;
+        *solid = density < 0.0;
+    }
+
+    // Layer the biome's blocks from the top of each stretch of solid blocks down.
+    let composition = composition.get(biome);
+    let mut surface_y = None;
+    for y in (0..REGION_DIM).rev() {
+        column[y] = if solid[y] {
+            let surface_y = *surface_y.get_or_insert(y);
+            composition.block(surface_y - y, surface_y)
+        } else {
+            surface_y = None;
+            BLOCK_AIR
+        };
+    }
+}
+
+/// A biome grid generated for a given seed.
+struct CachedBiomeGrid {
+    seed: u64,
+    grid: Arc<BiomeGrid>,
+}
+
+#[derive(Default)]
+pub(crate) struct CpuGenerator {
+    biome_sequence: BiomeSequence,
+    biome_grid: Mutex<Option<CachedBiomeGrid>>,
+}
+
+impl CpuGenerator {
+    /// Replaces the stages run to generate biome grids.
+    pub fn set_biome_sequence(&mut self, sequence: BiomeSequence) {
+        self.biome_sequence = sequence;
+        *self.biome_grid.get_mut().unwrap() = None;
+    }
+
+    /// Generates the blocks of a region, returning
+    /// them along with the region's biome grid.
+    pub fn generate_region_blocks(
+        &self,
+        seed: u64,
+        composition: &CompositionTable,
+    ) -> (Vec<u8>, BiomeGrid) {
+        let biome_grid = biomes::generate_on_cpu(seed, REGION_DIM as u32, &self.biome_sequence);
+        let blocks = generate_area(&biome_grid, composition, [0, 0], REGION_DIM as u32);
+        (blocks, biome_grid)
+    }
+
+    pub fn generate_column_blocks(
+        &self,
+        seed: u64,
+        composition: &CompositionTable,
+        offset: [i32; 2],
+    ) -> Vec<u8> {
+        let biome_grid = self.biome_grid(seed);
+        generate_area(&biome_grid, composition, offset, CHUNK_DIM as u32)
+    }
+
+    pub fn biome_grid(&self, seed: u64) -> Arc<BiomeGrid> {
+        let mut cached = self.biome_grid.lock().unwrap();
+        match &*cached {
+            Some(cached) if cached.seed == seed => Arc::clone(&cached.grid),
+            _ => {
+                let grid = Arc::new(biomes::generate_on_cpu(
+                    seed,
+                    WORLD_DIM,
+                    &self.biome_sequence,
+                ));
+                *cached = Some(CachedBiomeGrid {
+                    seed,
+                    grid: Arc::clone(&grid),
+                });
+                grid
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn biome_grid_is_deterministic() {
+        let sequence = BiomeSequence::default();
+        let a = biomes::generate_on_cpu(10, 256, &sequence);
+        let b = biomes::generate_on_cpu(10, 256, &sequence);
+        let c = biomes::generate_on_cpu(11, 256, &sequence);
+        assert_eq!(a.size(), 256);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert!(a
+            .as_bytes()
+            .iter()
+            .all(|&biome| (biome as usize) < NUM_BIOMES));
+    }
+
+    #[test]
+    fn climate_stages_keep_oceans() {
+        let ocean = BiomeGrid::from_fn(32, |_, _| BIOME_OCEAN);
+        assert_eq!(temperature(&ocean, 32, 3), ocean);
+        assert_eq!(humidity(&ocean, 32, 3), ocean);
+
+        let plains = BiomeGrid::from_fn(64, |_, _| BIOME_PLAINS);
+        let cooled = temperature(&plains, 64, 3);
+        assert!(cooled
+            .as_bytes()
+            .iter()
+            .all(|&biome| biome == BIOME_PLAINS || biome == BIOME_TUNDRA));
+        let humid = humidity(&plains, 64, 3);
+        assert!(humid
+            .as_bytes()
+            .iter()
+            .all(|&biome| biome == BIOME_PLAINS || biome == BIOME_SWAMP));
+    }
+
+    #[test]
+    fn area_is_deterministic() {
+        let grid = biomes::generate_on_cpu(10, 64, &BiomeSequence::default());
+        let composition = CompositionTable::default();
+        let a = generate_area(&grid, &composition, [16, 32], CHUNK_DIM as u32);
+        let b = generate_area(&grid, &composition, [16, 32], CHUNK_DIM as u32);
+        assert_eq!(a.len(), CHUNK_DIM * CHUNK_DIM * REGION_DIM);
+        assert_eq!(a, b);
+
+        // The bottom of the world is solid and the top is air.
+        for column in a.chunks_exact(REGION_DIM) {
+            assert_ne!(column[0], BLOCK_AIR);
+            assert_eq!(column[REGION_DIM - 1], BLOCK_AIR);
+        }
+    }
+}
@@ -1,8 +1,9 @@
 //! Voxel world generator for Voltz.
 //!
-//! This world generator currently generates the entire world in one go,
-//! in comparison to Minecraft's worldgen which does one chunk column at a time.
-//! Most algorithms, however, are parallelized.
+//! The world can be generated either a region at a time with
+//! [`WorldGenerator::generate_into_zone`], or one chunk column at a time
+//! with [`WorldGenerator::generate_chunk_column`]. The latter allows
+//! terrain to be streamed in as players explore. Most algorithms are parallelized.
 //!
 //! # Pipeline
 //! Data is fed through a number of stages before
@@ -10,32 +11,471 @@
 //!
 //! `Biome grid => density grid => composition => post-processing`
 //!
-//! The biome grid generates a 2D grid of biomes, one for each block column. The density
+//! The biome grid generates a 2D grid of biomes, one for each block column, by running
+//! the stages of a [`BiomeSequence`]. The density
 //! grid generates a 3D bitset where bits are set for non-air blocks. Composition takes
-//! the density and biome grids and generates chunks with actual blocks. Finally, post-processing
-//! adds features, such as trees and caves.
+//! the density and biome grids and generates chunks with actual blocks, taking the blocks
+//! of each biome from a [`CompositionTable`]. Finally, post-processing
+//! fills the sea up to [`SEA_LEVEL`] and adds features, such as ores, trees and caves.
+//! See [`OreSettings`] and [`CaveSettings`] for the parameters of the ore placement
+//! and cave carving passes. Last, the structures
+//! of a [`StructureSet`] are stamped onto the terrain.
+//!
+//! The biome grid is generated once per seed and covers [`WORLD_DIM`] blocks
+//! along the X and Z axes. Terrain outside this area is ocean. Generated
+//! chunk columns carry their biomes as [`ColumnBiomes`], so gameplay can
+//! look biomes up without running the generator again.
+//!
+//! # Backends
+//! Stages run in compute shaders by default. [`Backend::Cpu`] runs ports
+//! of the same shaders on the CPU for machines without a usable GPU.
 
-use std::{mem::take, sync::Arc};
+use std::{
+    collections::{HashMap, VecDeque},
+    iter,
+    mem::take,
+    sync::{Arc, Mutex, MutexGuard},
+};
 
 use biomes::BiomeGenerator;
-use common::{world::ZoneBuilder, ChunkPos};
+use common::{
+    biome::{Biome, ColumnBiomes},
+    chunk::CHUNK_DIM,
+    world::ZoneBuilder,
+    Chunk, ChunkPos,
+};
+use cpu::CpuGenerator;
 use futures_executor::block_on;
 use region::{Region, RegionGenerator, REGION_CHUNKS, REGION_DIM};
 
+pub use biomes::BiomeSequence;
+pub use caves::CaveSettings;
+pub use composition::CompositionTable;
+pub use cpu::BiomeGrid;
+pub use ores::{Ore, OreSettings};
+pub use region::{ChunkColumn, SEA_LEVEL};
+pub use structures::{Structure, StructureSet, Template};
+
 pub mod biomes;
+mod caves;
+pub mod composition;
+pub mod cpu;
+mod noise;
+mod ores;
 pub mod region;
+pub mod structures;
+pub mod tree;
+
+/// Length in blocks of the X and Z sides of the area
+/// covered by the biome grid used for chunk columns.
+pub const WORLD_DIM: u32 = 4096;
+
+/// The number of structure origins whose terrain surface is cached.
+const MAX_STRUCTURE_SURFACES: usize = 4096;
+
+/// The position of a chunk column, measured in chunks.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ColumnPos {
+    pub x: i32,
+    pub z: i32,
+}
+
+impl ColumnPos {
+    /// Gets the column containing the given chunk.
+    pub fn from_chunk(pos: ChunkPos) -> Self {
+        Self { x: pos.x, z: pos.z }
+    }
+
+    /// Gets the position of the chunk at index `y` in this column.
+    pub fn chunk(self, y: i32) -> ChunkPos {
+        ChunkPos {
+            x: self.x,
+            y,
+            z: self.z,
+        }
+    }
+}
+
+/// Where the world generator runs.
+pub enum Backend {
+    /// Runs generation in compute shaders.
+    Gpu {
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+    },
+    /// Runs generation on the CPU. Much slower, but works
+    /// on machines without a usable GPU adapter.
+    Cpu,
+}
+
+impl Backend {
+    /// Uses the GPU if an adapter is available and
+    /// falls back to the CPU otherwise.
+    pub fn detect() -> Self {
+        let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+        match common::gpu::init(instance, None) {
+            Ok((device, queue, _)) => {
+                let device = Arc::new(device);
+                common::gpu::launch_poll_thread(&device);
+                Backend::Gpu {
+                    device,
+                    queue: Arc::new(queue),
+                }
+            }
+            Err(e) => {
+                log::warn!(
+                    "No usable GPU for world generation ({:#}). Falling back to the CPU.",
+                    e
+                );
+                Backend::Cpu
+            }
+        }
+    }
+}
 
 pub struct WorldGenerator {
+    generator: Generator,
+    caves: CaveSettings,
+    ores: OreSettings,
+    composition: CompositionTable,
+    structures: StructureSet,
+
+    /// The terrain surface at structure origins, so chunk columns
+    /// overlapping a structure whose origin lies in another column
+    /// usually only generate that column once.
+    structure_surfaces: Mutex<StructureSurfaces>,
+}
+
+/// The terrain surface at the most recently cached structure origins,
+/// keyed by seed and origin. The oldest entries are evicted first.
+#[derive(Default)]
+struct StructureSurfaces {
+    surfaces: HashMap<(u64, [i32; 2]), Option<usize>>,
+    order: VecDeque<(u64, [i32; 2])>,
+}
+
+impl StructureSurfaces {
+    fn get(&self, seed: u64, origin: [i32; 2]) -> Option<Option<usize>> {
+        self.surfaces.get(&(seed, origin)).copied()
+    }
+
+    fn insert(&mut self, seed: u64, origin: [i32; 2], surface: Option<usize>) {
+        if self.surfaces.insert((seed, origin), surface).is_some() {
+            return;
+        }
+        self.order.push_back((seed, origin));
+        if self.order.len() > MAX_STRUCTURE_SURFACES {
+            let oldest = self.order.pop_front().unwrap();
+            self.surfaces.remove(&oldest);
+        }
+    }
+}
+
+enum Generator {
+    Gpu(GpuGenerator),
+    Cpu(CpuGenerator),
+}
+
+impl WorldGenerator {
+    pub fn new(backend: Backend) -> Self {
+        let generator = match backend {
+            Backend::Gpu { device, queue } => Generator::Gpu(GpuGenerator::new(device, queue)),
+            Backend::Cpu => Generator::Cpu(CpuGenerator::default()),
+        };
+        Self {
+            generator,
+            caves: CaveSettings::default(),
+            ores: OreSettings::default(),
+            composition: CompositionTable::default(),
+            structures: StructureSet::default(),
+            structure_surfaces: Mutex::new(StructureSurfaces::default()),
+        }
+    }
+
+    /// Sets the parameters of the cave carving pass.
+    pub fn with_caves(mut self, caves: CaveSettings) -> Self {
+        self.caves = caves;
+        self
+    }
+
+    pub fn caves(&self) -> &CaveSettings {
+        &self.caves
+    }
+
+    /// Sets the ores placed underground and their depth ranges.
+    ///
+    /// # Panics
+    /// Panics if the region shader can't output an ore's block.
+    pub fn with_ores(mut self, ores: OreSettings) -> Self {
+        for ore in &ores.ores {
+            assert!(
+                ore.is_placeable(),
+                "block '{}' can't be placed as ore",
+                ore.block.descriptor().slug()
+            );
+        }
+        self.ores = ores;
+        self
+    }
+
+    pub fn ores(&self) -> &OreSettings {
+        &self.ores
+    }
+
+    /// Sets the blocks making up the terrain of each biome.
+    pub fn with_composition(mut self, composition: CompositionTable) -> Self {
+        self.composition = composition;
+        self
+    }
+
+    pub fn composition(&self) -> &CompositionTable {
+        &self.composition
+    }
+
+    /// Sets the structures placed on the terrain.
+    pub fn with_structures(mut self, structures: StructureSet) -> Self {
+        self.structures = structures;
+        self
+    }
+
+    pub fn structures(&self) -> &StructureSet {
+        &self.structures
+    }
+
+    /// Sets the stages run to generate the biome grid.
+    pub fn with_biome_sequence(mut self, sequence: BiomeSequence) -> Self {
+        match &mut self.generator {
+            Generator::Gpu(gpu) => gpu.set_biome_sequence(&sequence),
+            Generator::Cpu(cpu) => cpu.set_biome_sequence(sequence),
+        }
+        self
+    }
+
+    /// Returns whether this generator runs on the CPU.
+    pub fn is_cpu(&self) -> bool {
+        matches!(self.generator, Generator::Cpu(_))
+    }
+
+    /// Fills a zone with generated blocks.
+    /// This function is expensive and will block on GPU operations.
+    ///
+    /// Structures are only placed if their origin lies within the
+    /// region, which holds for all of them if the structure spacing
+    /// divides [`REGION_DIM`].
+    pub fn generate_into_zone(&self, zone: &mut ZoneBuilder, seed: u64) {
+        let (mut blocks, biome_grid) = match &self.generator {
+            Generator::Gpu(gpu) => gpu.generate_region_blocks(seed, &self.composition),
+            Generator::Cpu(cpu) => cpu.generate_region_blocks(seed, &self.composition),
+        };
+        // Structures sit on the terrain as composed, before
+        // the sea and caves change the top of columns.
+        let placements = self.structures.placements(seed, [0, 0], REGION_DIM);
+        let surfaces: Vec<_> = placements
+            .iter()
+            .map(|placement| surface_in_area(&blocks, [0, 0], REGION_DIM, placement.origin))
+            .collect();
+
+        // The sea is filled first, so caves stay clear of it.
+        region::fill_sea(&mut blocks);
+        ores::place(&mut blocks, [0, 0], REGION_DIM, seed, &self.ores);
+        caves::carve(&mut blocks, [0, 0], REGION_DIM, seed, &self.caves);
+        let mut region = Region::from_gpu_data(&blocks);
+        for (placement, surface) in placements.iter().zip(surfaces) {
+            if let Some(Some(surface_y)) = surface {
+                placement.stamp(surface_y, [0, 0], REGION_DIM, |x, y, z, block| {
+                    region.set_block(x, y, z, block)
+                });
+            }
+        }
+        self.move_region_into_zone(region, zone, [0, 0, 0]);
+
+        for x in 0..REGION_CHUNKS as i32 {
+            for z in 0..REGION_CHUNKS as i32 {
+                let offset = [x * CHUNK_DIM as i32, z * CHUNK_DIM as i32];
+                let _ = zone.set_column_biomes(x, z, sample_biomes(&biome_grid, offset));
+            }
+        }
+    }
+
+    /// Generates the biome grid for `seed` ahead of time. Otherwise,
+    /// the first call to `generate_chunk_column` for the seed does so.
+    pub fn prepare_biome_grid(&self, seed: u64) {
+        match &self.generator {
+            Generator::Gpu(gpu) => {
+                gpu.biome_grid(seed);
+            }
+            Generator::Cpu(cpu) => {
+                cpu.biome_grid(seed);
+            }
+        }
+    }
+
+    /// Gets the biomes of a chunk column without generating its blocks.
+    /// Like `generate_chunk_column`, generates the biome grid on the
+    /// first call for a given seed.
+    pub fn column_biomes(&self, seed: u64, pos: ColumnPos) -> ColumnBiomes {
+        let offset = [pos.x * CHUNK_DIM as i32, pos.z * CHUNK_DIM as i32];
+        sample_biomes(&self.biome_grid(seed), offset)
+    }
+
+    fn biome_grid(&self, seed: u64) -> Arc<BiomeGrid> {
+        match &self.generator {
+            Generator::Gpu(gpu) => Arc::clone(&gpu.biome_grid(seed).as_ref().unwrap().grid),
+            Generator::Cpu(cpu) => cpu.biome_grid(seed),
+        }
+    }
+
+    /// Generates a single column of chunks.
+    ///
+    /// The first call for a given seed generates the biome grid for the
+    /// whole world, which is expensive; later calls only generate
+    /// the column's blocks. Blocks on GPU operations.
+    pub fn generate_chunk_column(&self, seed: u64, pos: ColumnPos) -> ChunkColumn {
+        let offset = [pos.x * CHUNK_DIM as i32, pos.z * CHUNK_DIM as i32];
+        let mut blocks = self.generate_column_blocks(seed, offset);
+        let placements = self.structures.placements(seed, offset, CHUNK_DIM);
+        let surfaces: Vec<_> = placements
+            .iter()
+            .map(|placement| {
+                let origin = placement.origin;
+                match surface_in_area(&blocks, offset, CHUNK_DIM, origin) {
+                    Some(surface) => {
+                        self.cache_structure_surface(seed, origin, surface);
+                        surface
+                    }
+                    None => self.structure_surface(seed, origin),
+                }
+            })
+            .collect();
+
+        region::fill_sea(&mut blocks);
+        ores::place(&mut blocks, offset, CHUNK_DIM, seed, &self.ores);
+        caves::carve(&mut blocks, offset, CHUNK_DIM, seed, &self.caves);
+        let mut column = ChunkColumn::from_gpu_data(&blocks);
+        column.biomes = self.column_biomes(seed, pos);
+        for (placement, surface) in placements.iter().zip(surfaces) {
+            if let Some(surface_y) = surface {
+                placement.stamp(surface_y, offset, CHUNK_DIM, |x, y, z, block| {
+                    column.set_block(x, y, z, block)
+                });
+            }
+        }
+        column
+    }
+
+    fn generate_column_blocks(&self, seed: u64, offset: [i32; 2]) -> Vec<u8> {
+        match &self.generator {
+            Generator::Gpu(gpu) => gpu.generate_column_blocks(seed, &self.composition, offset),
+            Generator::Cpu(cpu) => cpu.generate_column_blocks(seed, &self.composition, offset),
+        }
+    }
+
+    /// Finds the terrain surface at the origin of a structure lying in
+    /// another chunk column, generating that column's terrain if needed.
+    fn structure_surface(&self, seed: u64, origin: [i32; 2]) -> Option<usize> {
+        if let Some(surface) = self.structure_surfaces.lock().unwrap().get(seed, origin) {
+            return surface;
+        }
+
+        let offset = [
+            origin[0].div_euclid(CHUNK_DIM as i32) * CHUNK_DIM as i32,
+            origin[1].div_euclid(CHUNK_DIM as i32) * CHUNK_DIM as i32,
+        ];
+        let blocks = self.generate_column_blocks(seed, offset);
+        let surface = surface_in_area(&blocks, offset, CHUNK_DIM, origin)
+            .expect("origin lies in its own column");
+        self.cache_structure_surface(seed, origin, surface);
+        surface
+    }
+
+    fn cache_structure_surface(&self, seed: u64, origin: [i32; 2], surface: Option<usize>) {
+        self.structure_surfaces
+            .lock()
+            .unwrap()
+            .insert(seed, origin, surface);
+    }
+
+    /// Generates a chunk column and adds its chunks to a zone.
+    /// Chunks outside the zone's bounds are ignored.
+    pub fn generate_column_into_zone(&self, zone: &mut ZoneBuilder, seed: u64, pos: ColumnPos) {
+        let column = self.generate_chunk_column(seed, pos);
+        let _ = zone.set_column_biomes(pos.x, pos.z, column.biomes);
+        let chunks: Box<[Chunk]> = column.chunks;
+        for (y, chunk) in chunks.into_vec().into_iter().enumerate() {
+            let _ = zone.add_chunk(pos.chunk(y as i32), chunk);
+        }
+    }
+
+    fn move_region_into_zone(
+        &self,
+        mut region: Region,
+        zone: &mut ZoneBuilder,
+        offset_in_chunks: [i32; 3],
+    ) {
+        for chunk_x in 0..REGION_CHUNKS as i32 {
+            for chunk_y in 0..REGION_CHUNKS as i32 {
+                for chunk_z in 0..REGION_CHUNKS as i32 {
+                    let x = chunk_x + offset_in_chunks[0];
+                    let y = chunk_y + offset_in_chunks[1];
+                    let z = chunk_z + offset_in_chunks[2];
+                    let pos = ChunkPos { x, y, z };
+                    let chunk = take(
+                        &mut region.chunks[chunk_x as usize][chunk_y as usize][chunk_z as usize],
+                    );
+                    let _ = zone.add_chunk(pos, chunk);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the terrain surface at `pos` within an area of blocks laid out
+/// as output by the region shader, or `None` if `pos` is outside the area.
+fn surface_in_area(
+    blocks: &[u8],
+    offset: [i32; 2],
+    area_dim: usize,
+    pos: [i32; 2],
+) -> Option<Option<usize>> {
+    let x = pos[0] - offset[0];
+    let z = pos[1] - offset[1];
+    if x < 0 || z < 0 || x >= area_dim as i32 || z >= area_dim as i32 {
+        return None;
+    }
+    let start = (x as usize * area_dim + z as usize) * REGION_DIM;
+    Some(structures::surface_y(&blocks[start..start + REGION_DIM]))
+}
+
+/// Samples the biomes of the chunk column whose minimum
+/// corner is at `offset` in a biome grid.
+fn sample_biomes(grid: &BiomeGrid, offset: [i32; 2]) -> ColumnBiomes {
+    ColumnBiomes::from_fn(|x, z| {
+        let id = grid.get(offset[0] + x as i32, offset[1] + z as i32);
+        Biome::from_id(id).expect("unknown biome in biome grid")
+    })
+}
+
+/// A biome grid generated for a given seed.
+struct CachedBiomeGrid {
+    seed: u64,
+    texture: wgpu::Texture,
+    /// A copy of the texture on the CPU.
+    grid: Arc<BiomeGrid>,
+}
+
+struct GpuGenerator {
     biome_generator: BiomeGenerator,
     region_generator: RegionGenerator,
     device: Arc<wgpu::Device>,
     queue: Arc<wgpu::Queue>,
+
+    /// The biome grid for the most recently used seed,
+    /// reused across calls to `generate_chunk_column`.
+    biome_grid: Mutex<Option<CachedBiomeGrid>>,
 }
 
-impl WorldGenerator {
-    pub fn new(device: &Arc<wgpu::Device>, queue: &Arc<wgpu::Queue>) -> Self {
-        let device = Arc::clone(device);
-        let queue = Arc::clone(queue);
+impl GpuGenerator {
+    fn new(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> Self {
         let biome_generator = BiomeGenerator::new(&device);
         let region_generator = RegionGenerator::new(&device);
         Self {
@@ -43,12 +483,21 @@ impl WorldGenerator {
             region_generator,
             device,
             queue,
+            biome_grid: Mutex::new(None),
         }
     }
 
-    /// Fills a zone with generated blocks.
-    /// This function is expensive and will block on GPU operations.
-    pub fn generate_into_zone(&self, zone: &mut ZoneBuilder, seed: u32) {
+    /// Replaces the stages run to generate biome grids.
+    fn set_biome_sequence(&mut self, sequence: &BiomeSequence) {
+        self.biome_generator.set_sequence(sequence);
+        *self.biome_grid.get_mut().unwrap() = None;
+    }
+
+    fn generate_region_blocks(
+        &self,
+        seed: u64,
+        composition: &CompositionTable,
+    ) -> (Vec<u8>, BiomeGrid) {
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
@@ -57,7 +506,9 @@ impl WorldGenerator {
             .biome_generator
             .prepare(&self.device, seed, REGION_DIM as u32);
         let biome_grid = biome_payload.output_texture();
-        let region_payload = self.region_generator.prepare(&self.device, biome_grid);
+        let region_payload = self
+            .region_generator
+            .prepare(&self.device, biome_grid, composition);
 
         {
             let mut pass = encoder.begin_compute_pass();
@@ -66,34 +517,215 @@ impl WorldGenerator {
             self.region_generator.execute(&region_payload, &mut pass);
         }
 
-        let region = block_on(self.region_generator.load_region_from_gpu(
+        let blocks = block_on(self.region_generator.load_blocks_from_gpu(
             &region_payload,
             &self.device,
             &self.queue,
             encoder,
         ));
-        self.move_region_into_zone(region, zone, [0, 0, 0]);
+        let grid = block_on(biomes::read_grid(
+            &self.device,
+            &self.queue,
+            biome_grid,
+            biome_payload.output_size(),
+        ));
+        (blocks, grid)
     }
 
-    fn move_region_into_zone(
+    fn generate_column_blocks(
         &self,
-        mut region: Region,
-        zone: &mut ZoneBuilder,
-        offset_in_chunks: [i32; 3],
-    ) {
-        for chunk_x in 0..REGION_CHUNKS as i32 {
-            for chunk_y in 0..REGION_CHUNKS as i32 {
-                for chunk_z in 0..REGION_CHUNKS as i32 {
-                    let x = chunk_x + offset_in_chunks[0];
-                    let y = chunk_y + offset_in_chunks[1];
-                    let z = chunk_z + offset_in_chunks[2];
-                    let pos = ChunkPos { x, y, z };
-                    let chunk = take(
-                        &mut region.chunks[chunk_x as usize][chunk_y as usize][chunk_z as usize],
-                    );
-                    let _ = zone.add_chunk(pos, chunk);
+        seed: u64,
+        composition: &CompositionTable,
+        offset: [i32; 2],
+    ) -> Vec<u8> {
+        let biome_grid = self.biome_grid(seed);
+        let biome_grid = &biome_grid.as_ref().unwrap().texture;
+
+        let payload =
+            self.region_generator
+                .prepare_column(&self.device, biome_grid, composition, offset);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass();
+            self.region_generator.execute(&payload, &mut pass);
+        }
+
+        block_on(self.region_generator.load_blocks_from_gpu(
+            &payload,
+            &self.device,
+            &self.queue,
+            encoder,
+        ))
+    }
+
+    /// Gets the cached biome grid, generating it if
+    /// it was generated for a different seed.
+    fn biome_grid(&self, seed: u64) -> MutexGuard<Option<CachedBiomeGrid>> {
+        let mut biome_grid = self.biome_grid.lock().unwrap();
+        if biome_grid.as_ref().map(|grid| grid.seed) != Some(seed) {
+            let (texture, size) = self.generate_biome_grid(seed);
+            let grid = block_on(biomes::read_grid(&self.device, &self.queue, &texture, size));
+            *biome_grid = Some(CachedBiomeGrid {
+                seed,
+                texture,
+                grid: Arc::new(grid),
+            });
+        }
+        biome_grid
+    }
+
+    /// Generates the biome grid for `seed`, returning
+    /// its texture and the texture's width.
+    fn generate_biome_grid(&self, seed: u64) -> (wgpu::Texture, u32) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let biome_payload = self.biome_generator.prepare(&self.device, seed, WORLD_DIM);
+        {
+            let mut pass = encoder.begin_compute_pass();
+            self.biome_generator
+                .execute(&biome_payload, &mut pass, &self.queue);
+        }
+        self.queue.submit(iter::once(encoder.finish()));
+        let size = biome_payload.output_size();
+        (biome_payload.into_output_texture(), size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::{blocks::Log, BlockId, BlockPos, Zone};
+
+    /// Generates a few chunk columns on the CPU.
+    fn generate(seed: u64) -> Zone {
+        generate_with(&WorldGenerator::new(Backend::Cpu), seed)
+    }
+
+    fn generate_with(generator: &WorldGenerator, seed: u64) -> Zone {
+        let min = ColumnPos { x: 120, z: 130 };
+        let mut zone = ZoneBuilder::new(
+            min.chunk(0),
+            ChunkPos {
+                x: min.x + 1,
+                y: REGION_CHUNKS as i32 - 1,
+                z: min.z + 1,
+            },
+        );
+        for x in min.x..=min.x + 1 {
+            for z in min.z..=min.z + 1 {
+                generator.generate_column_into_zone(&mut zone, seed, ColumnPos { x, z });
+            }
+        }
+        zone.build().ok().expect("missing chunks")
+    }
+
+    fn assert_zones_equal(a: &Zone, b: &Zone) {
+        for ((pos_a, chunk_a), (pos_b, chunk_b)) in a.chunks().zip(b.chunks()) {
+            assert_eq!(pos_a, pos_b);
+            for x in 0..CHUNK_DIM {
+                for y in 0..CHUNK_DIM {
+                    for z in 0..CHUNK_DIM {
+                        assert_eq!(
+                            chunk_a.get(x, y, z),
+                            chunk_b.get(x, y, z),
+                            "blocks differ in {:?} at {:?}",
+                            pos_a,
+                            (x, y, z)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn regeneration_is_deterministic() {
+        let seed = 0xdead_beef_0000_0001;
+        assert_zones_equal(&generate(seed), &generate(seed));
+    }
+
+    #[test]
+    fn structures_span_columns() {
+        let size = [20, 3, 20];
+        let mut structures = StructureSet::new(32, 1.);
+        structures
+            .add(Arc::new(Template::from_blocks(
+                "slab",
+                size,
+                0,
+                &[Some(BlockId::new(Log)); 20 * 3 * 20],
+            )))
+            .unwrap();
+        let generator = WorldGenerator::new(Backend::Cpu).with_structures(structures);
+        let zone = generate_with(&generator, 0xdead_beef_0000_0001);
+
+        // The generated columns cover exactly one placement cell. Its
+        // structure is either left out for being in water or stamped
+        // whole, no matter which column its origin is in.
+        let mut num_logs = 0;
+        for (_, chunk) in zone.chunks() {
+            for x in 0..CHUNK_DIM {
+                for y in 0..CHUNK_DIM {
+                    for z in 0..CHUNK_DIM {
+                        if chunk.get(x, y, z).is::<Log>() {
+                            num_logs += 1;
+                        }
+                    }
                 }
             }
         }
+        assert!(num_logs == 0 || num_logs == 20 * 3 * 20, "{}", num_logs);
+    }
+
+    #[test]
+    fn biome_ids_match_common_biomes() {
+        assert_eq!(composition::BIOMES.len(), Biome::all().len());
+        for (id, slug) in composition::BIOMES.iter().enumerate() {
+            assert_eq!(Biome::from_id(id as u8).unwrap().slug(), *slug);
+        }
+    }
+
+    #[test]
+    fn generated_columns_record_biomes() {
+        let seed = 0xdead_beef_0000_0001;
+        let generator = WorldGenerator::new(Backend::Cpu);
+        let zone = generate_with(&generator, seed);
+        let grid = generator.biome_grid(seed);
+        for x in (120 * CHUNK_DIM as i32..122 * CHUNK_DIM as i32).step_by(4) {
+            for z in (130 * CHUNK_DIM as i32..132 * CHUNK_DIM as i32).step_by(4) {
+                let expected = Biome::from_id(grid.get(x + 2, z + 2));
+                assert_eq!(zone.biome_at(BlockPos { x, y: 70, z }), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn high_seed_bits_affect_biomes() {
+        let seed = 0xdead_beef_0000_0001;
+        let sequence = BiomeSequence::default();
+        let a = biomes::generate_on_cpu(seed, 256, &sequence);
+        let b = biomes::generate_on_cpu(seed ^ (1 << 40), 256, &sequence);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn structure_surfaces_evict_oldest() {
+        let mut surfaces = StructureSurfaces::default();
+        for x in 0..=MAX_STRUCTURE_SURFACES as i32 {
+            surfaces.insert(1, [x, 0], Some(64));
+        }
+        // Caching an origin again doesn't make it newer.
+        surfaces.insert(1, [1, 0], None);
+        assert_eq!(surfaces.surfaces.len(), MAX_STRUCTURE_SURFACES);
+        assert_eq!(surfaces.get(1, [0, 0]), None);
+        assert_eq!(surfaces.get(1, [1, 0]), Some(None));
+        assert_eq!(
+            surfaces.get(1, [MAX_STRUCTURE_SURFACES as i32, 0]),
+            Some(Some(64))
+        );
     }
 }
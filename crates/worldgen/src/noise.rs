@@ -0,0 +1,234 @@
+//! CPU ports of the noise functions in `shader/include/noise.glsl`
+//! and the RNG in `shader/include/rng.glsl`.
+//!
+//! These follow the shader code operation for operation so that
+//! the CPU backend produces the same terrain as the GPU, up to
+//! floating-point differences between the two.
+
+/// XXHash-based hash of a 2D position. Matches `random()` in `rng.glsl`.
+pub fn random(x: u32, y: u32) -> u32 {
+    const PRIME32_2: u32 = 2246822519;
+    const PRIME32_3: u32 = 3266489917;
+    const PRIME32_4: u32 = 668265263;
+    const PRIME32_5: u32 = 374761393;
+    let mut h32 = y
+        .wrapping_add(PRIME32_5)
+        .wrapping_add(x.wrapping_mul(PRIME32_3));
+    h32 = PRIME32_4.wrapping_mul(h32.rotate_left(17));
+    h32 = PRIME32_2.wrapping_mul(h32 ^ (h32 >> 15));
+    h32 = PRIME32_3.wrapping_mul(h32 ^ (h32 >> 13));
+    h32 ^ (h32 >> 16)
+}
+
+/// Salts for [`derive_seed`], one per independent random stream.
+/// Must match the `SALT_*` defines in `rng.glsl`.
+pub const SALT_ZOOM: u32 = 0;
+pub const SALT_SMOOTH: u32 = 1;
+pub const SALT_LAND: u32 = 2;
+pub const SALT_CAVES: u32 = 3;
+pub const SALT_TEMPERATURE: u32 = 4;
+pub const SALT_HUMIDITY: u32 = 5;
+pub const SALT_STRUCTURES: u32 = 6;
+pub const SALT_ORES: u32 = 7;
+
+/// Derives a 32-bit seed from the 64-bit world seed.
+/// Matches `deriveSeed()` in `rng.glsl`.
+pub fn derive_seed(seed: u64, salt: u32) -> u32 {
+    random(random(seed as u32, (seed >> 32) as u32), salt)
+}
+
+fn mod289(x: f32) -> f32 {
+    x - (x * (1.0 / 289.0)).floor() * 289.0
+}
+
+fn permute(x: f32) -> f32 {
+    mod289(((x * 34.0) + 1.0) * x)
+}
+
+fn fract(x: f32) -> f32 {
+    x - x.floor()
+}
+
+fn taylor_inv_sqrt(r: f32) -> f32 {
+    1.79284291400159 - 0.85373472095314 * r
+}
+
+/// 2D simplex noise in the range `[-1, 1]`.
+pub fn simplex_2d(x: f32, y: f32) -> f32 {
+    const C: [f32; 4] = [
+        0.211324865405187,  // (3.0-sqrt(3.0))/6.0
+        0.366025403784439,  // 0.5*(sqrt(3.0)-1.0)
+        -0.577350269189626, // -1.0 + 2.0 * C.x
+        0.024390243902439,  // 1.0 / 41.0
+    ];
+
+    // First corner
+    let s = (x + y) * C[1];
+    let ix = (x + s).floor();
+    let iy = (y + s).floor();
+    let t = (ix + iy) * C[0];
+    let x0 = [x - ix + t, y - iy + t];
+
+    // Other corners
+    let i1 = if x0[0] > x0[1] {
+        [1.0, 0.0]
+    } else {
+        [0.0, 1.0]
+    };
+    let corners = [
+        ([0.0, 0.0], x0),
+        (i1, [x0[0] + C[0] - i1[0], x0[1] + C[0] - i1[1]]),
+        ([1.0, 1.0], [x0[0] + C[2], x0[1] + C[2]]),
+    ];
+
+    // Avoid truncation effects in permutation
+    let ix = mod289(ix);
+    let iy = mod289(iy);
+
+    let mut result = 0.0;
+    for &(offset, d) in &corners {
+        let p = permute(permute(iy + offset[1]) + ix + offset[0]);
+
+        let m = (0.5 - (d[0] * d[0] + d[1] * d[1])).max(0.0);
+        let m = m * m;
+        let m = m * m;
+
+        // Gradients: 41 points uniformly over a line, mapped onto a diamond.
+        let x = 2.0 * fract(p * C[3]) - 1.0;
+        let h = x.abs() - 0.5;
+        let a0 = x - (x + 0.5).floor();
+
+        // Normalise gradients implicitly by scaling m
+        let m = m * taylor_inv_sqrt(a0 * a0 + h * h);
+
+        result += m * (a0 * d[0] + h * d[1]);
+    }
+    130.0 * result
+}
+
+fn step(edge: f32, x: f32) -> f32 {
+    if x < edge {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+fn dot3(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// 3D simplex noise in the range `[-1, 1]`.
+pub fn simplex_3d(v: [f32; 3]) -> f32 {
+    const C: [f32; 2] = [1.0 / 6.0, 1.0 / 3.0];
+
+    // First corner
+    let s = (v[0] + v[1] + v[2]) * C[1];
+    let i = [(v[0] + s).floor(), (v[1] + s).floor(), (v[2] + s).floor()];
+    let t = (i[0] + i[1] + i[2]) * C[0];
+    let x0 = [v[0] - i[0] + t, v[1] - i[1] + t, v[2] - i[2] + t];
+
+    // Other corners
+    let g = [step(x0[1], x0[0]), step(x0[2], x0[1]), step(x0[0], x0[2])];
+    let l = [1.0 - g[0], 1.0 - g[1], 1.0 - g[2]];
+    let i1 = [g[0].min(l[2]), g[1].min(l[0]), g[2].min(l[1])];
+    let i2 = [g[0].max(l[2]), g[1].max(l[0]), g[2].max(l[1])];
+
+    let corner = |offset: [f32; 3], c: f32| {
+        [
+            x0[0] - offset[0] + c,
+            x0[1] - offset[1] + c,
+            x0[2] - offset[2] + c,
+        ]
+    };
+    let corners = [
+        ([0.0; 3], x0),
+        (i1, corner(i1, C[0])),
+        (i2, corner(i2, C[1])),
+        ([1.0; 3], corner([0.0; 3], -0.5)),
+    ];
+
+    // Permutations
+    let i = [mod289(i[0]), mod289(i[1]), mod289(i[2])];
+
+    // Gradients: 7x7 points over a square, mapped onto an octahedron.
+    let n = 0.142857142857; // 1.0/7.0
+    let ns = [n * 2.0, n * 0.5 - 1.0, n];
+
+    let mut result = 0.0;
+    for &(offset, d) in &corners {
+        let p = permute(permute(permute(i[2] + offset[2]) + i[1] + offset[1]) + i[0] + offset[0]);
+
+        let j = p - 49.0 * (p * ns[2] * ns[2]).floor(); // mod(p,7*7)
+        let x_ = (j * ns[2]).floor();
+        let y_ = (j - 7.0 * x_).floor(); // mod(j,N)
+
+        let x = x_ * ns[0] + ns[1];
+        let y = y_ * ns[0] + ns[1];
+        let h = 1.0 - x.abs() - y.abs();
+
+        let sh = -step(h, 0.0);
+        let gradient = [
+            x + (x.floor() * 2.0 + 1.0) * sh,
+            y + (y.floor() * 2.0 + 1.0) * sh,
+            h,
+        ];
+
+        // Normalise gradients
+        let norm = taylor_inv_sqrt(dot3(gradient, gradient));
+        let gradient = [gradient[0] * norm, gradient[1] * norm, gradient[2] * norm];
+
+        let m = (0.5 - dot3(d, d)).max(0.0);
+        let m = m * m;
+        result += m * m * dot3(gradient, d);
+    }
+    105.0 * result
+}
+
+/// Fractal Brownian motion over 3D simplex noise.
+pub fn fbm_3d(pos: [f32; 3], octaves: u32, lacunarity: f32, gain: f32) -> f32 {
+    let mut result = 0.0;
+    let mut frequency = 1.0;
+    let mut amplitude = 0.5;
+    for _ in 0..octaves {
+        let pos = [pos[0] * frequency, pos[1] * frequency, pos[2] * frequency];
+        result += simplex_3d(pos) * amplitude;
+        frequency *= lacunarity;
+        amplitude *= gain;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_in_range() {
+        for i in 0..1000 {
+            let x = i as f32 * 0.37;
+            let y = i as f32 * -0.21;
+            let n2 = simplex_2d(x, y);
+            let n3 = simplex_3d([x, y, x * 0.5]);
+            assert!((-1.01..=1.01).contains(&n2), "{}", n2);
+            assert!((-1.01..=1.01).contains(&n3), "{}", n3);
+        }
+    }
+
+    #[test]
+    fn derived_seeds_use_all_bits() {
+        let seed = 0x1234_5678_9abc_def0;
+        assert_ne!(derive_seed(seed, SALT_ZOOM), derive_seed(seed, SALT_SMOOTH));
+        assert_ne!(
+            derive_seed(seed, SALT_ZOOM),
+            derive_seed(seed ^ (1 << 63), SALT_ZOOM)
+        );
+    }
+
+    #[test]
+    fn noise_is_continuous() {
+        let a = simplex_3d([10.3, 20.7, 30.1]);
+        let b = simplex_3d([10.301, 20.7, 30.1]);
+        assert!((a - b).abs() < 0.01);
+    }
+}
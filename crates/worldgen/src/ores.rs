@@ -0,0 +1,215 @@
+//! Places veins of ore in underground stone.
+//!
+//! Each ore has its own 3D noise field, and stone within the ore's
+//! depth range becomes ore where the field exceeds a threshold. Like
+//! caves, whether a block holds ore depends only on its position and
+//! the seed, so veins continue across chunk columns.
+
+use common::{
+    blocks::{CoalOre, GoldOre, IronOre},
+    BlockId,
+};
+use rayon::prelude::*;
+
+use crate::{
+    noise::{derive_seed, random, simplex_3d, SALT_ORES},
+    region::{self, BLOCK_STONE, REGION_DIM},
+};
+
+/// An ore placed in veins underground.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ore {
+    /// The ore's block. Needs to be one the region shader can output.
+    pub block: BlockId,
+    /// The lowest Y coordinate at which the ore is placed.
+    pub min_y: usize,
+    /// The ore is never placed at or above this Y coordinate.
+    pub max_y: usize,
+    /// Frequency of the noise defining veins. Higher
+    /// values produce smaller, more numerous veins.
+    pub frequency: f32,
+    /// How much stone becomes ore, as a noise threshold
+    /// between 0 and 1. A size of zero disables the ore.
+    pub size: f32,
+}
+
+impl Ore {
+    /// Returns whether the region shader can output the ore's block.
+    pub fn is_placeable(&self) -> bool {
+        region::block_index(self.block.descriptor().slug()).is_some()
+    }
+}
+
+/// Parameters of the ore placement pass. Where the veins of
+/// several ores overlap, the ore listed first wins.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OreSettings {
+    pub ores: Vec<Ore>,
+}
+
+impl Default for OreSettings {
+    fn default() -> Self {
+        Self {
+            ores: vec![
+                Ore {
+                    block: BlockId::new(GoldOre),
+                    min_y: 1,
+                    max_y: 30,
+                    frequency: 0.12,
+                    size: 0.08,
+                },
+                Ore {
+                    block: BlockId::new(IronOre),
+                    min_y: 1,
+                    max_y: 56,
+                    frequency: 0.1,
+                    size: 0.1,
+                },
+                Ore {
+                    block: BlockId::new(CoalOre),
+                    min_y: 8,
+                    max_y: 96,
+                    frequency: 0.08,
+                    size: 0.13,
+                },
+            ],
+        }
+    }
+}
+
+/// An ore resolved for placement.
+struct Vein {
+    block: u8,
+    min_y: usize,
+    max_y: usize,
+    frequency: f32,
+    threshold: f32,
+    noise_offset: f32,
+}
+
+/// Places ores in block data laid out as output by the
+/// region shader. `offset` is the position in blocks of the
+/// area's minimum corner, and `output_dim` is its width.
+pub(crate) fn place(
+    blocks: &mut [u8],
+    offset: [i32; 2],
+    output_dim: usize,
+    seed: u64,
+    settings: &OreSettings,
+) {
+    let seed = derive_seed(seed, SALT_ORES);
+    let veins: Vec<Vein> = settings
+        .ores
+        .iter()
+        .enumerate()
+        .filter(|(_, ore)| ore.size > 0.)
+        .filter_map(|(index, ore)| {
+            Some(Vein {
+                block: region::block_index(ore.block.descriptor().slug())?,
+                min_y: ore.min_y,
+                max_y: ore.max_y.min(REGION_DIM),
+                frequency: ore.frequency,
+                threshold: 1. - ore.size,
+                noise_offset: (random(seed, index as u32) % 4096) as f32,
+            })
+        })
+        .collect();
+    if veins.is_empty() {
+        return;
+    }
+
+    blocks
+        .par_chunks_mut(REGION_DIM)
+        .enumerate()
+        .for_each(|(index, column)| {
+            let x = offset[0] + (index / output_dim) as i32;
+            let z = offset[1] + (index % output_dim) as i32;
+            place_in_column(column, x, z, &veins);
+        });
+}
+
+fn place_in_column(column: &mut [u8], x: i32, z: i32, veins: &[Vein]) {
+    for vein in veins {
+        for y in vein.min_y..vein.max_y {
+            if column[y] != BLOCK_STONE {
+                continue;
+            }
+
+            let pos = [
+                x as f32 * vein.frequency + vein.noise_offset,
+                y as f32 * vein.frequency + vein.noise_offset,
+                z as f32 * vein.frequency + vein.noise_offset,
+            ];
+            if simplex_3d(pos) > vein.threshold {
+                column[y] = vein.block;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region::BLOCK_AIR;
+
+    const DIM: usize = 16;
+
+    fn with_ores(blocks: &[u8], seed: u64, settings: &OreSettings) -> Vec<u8> {
+        let mut blocks = blocks.to_vec();
+        place(&mut blocks, [-48, 80], DIM, seed, settings);
+        blocks
+    }
+
+    fn solid_area() -> Vec<u8> {
+        vec![BLOCK_STONE; DIM * DIM * REGION_DIM]
+    }
+
+    #[test]
+    fn default_ores_are_placeable() {
+        assert!(OreSettings::default().ores.iter().all(Ore::is_placeable));
+    }
+
+    #[test]
+    fn places_ores_within_depth_ranges() {
+        let settings = OreSettings::default();
+        let blocks = with_ores(&solid_area(), 5, &settings);
+        assert_eq!(blocks, with_ores(&solid_area(), 5, &settings));
+
+        for ore in &settings.ores {
+            let index = region::block_index(ore.block.descriptor().slug()).unwrap();
+            let mut count = 0;
+            for column in blocks.chunks_exact(REGION_DIM) {
+                for (y, &block) in column.iter().enumerate() {
+                    if block == index {
+                        assert!(y >= ore.min_y && y < ore.max_y);
+                        count += 1;
+                    }
+                }
+            }
+            assert!(count > 0, "no {:?} placed", ore.block);
+        }
+    }
+
+    #[test]
+    fn only_replaces_stone() {
+        let mut blocks = solid_area();
+        for column in blocks.chunks_exact_mut(REGION_DIM) {
+            for block in &mut column[..20] {
+                *block = BLOCK_AIR;
+            }
+        }
+        let placed = with_ores(&blocks, 5, &OreSettings::default());
+        for column in placed.chunks_exact(REGION_DIM) {
+            assert!(column[..20].iter().all(|&block| block == BLOCK_AIR));
+        }
+    }
+
+    #[test]
+    fn zero_size_disables_ores() {
+        let mut settings = OreSettings::default();
+        for ore in &mut settings.ores {
+            ore.size = 0.;
+        }
+        assert_eq!(with_ores(&solid_area(), 5, &settings), solid_area());
+    }
+}
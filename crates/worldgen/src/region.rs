@@ -1,17 +1,38 @@
 //! Generates regions of blocks on the GPU.
 //! Regions are cubs of blocks with length [`REGION_DIM`].
+//!
+//! The same pipeline generates single chunk columns,
+//! which are [`CHUNK_DIM`] wide and [`REGION_DIM`] tall.
 
-use std::iter;
+use std::{iter, mem::size_of};
 
-use common::{blocks, chunk::CHUNK_DIM, BlockId, Chunk};
+use bytemuck::{Pod, Zeroable};
+use common::{
+    biome::ColumnBiomes,
+    blocks,
+    chunk::{CHUNK_DIM, CHUNK_VOLUME},
+    BlockId, Chunk,
+};
 use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use wgpu::util::DeviceExt;
 
-use crate::biomes::BIOME_GRID_FORMAT;
+use crate::{biomes::BIOME_GRID_FORMAT, composition::CompositionTable};
 
 pub const REGION_CHUNKS: usize = 16;
 pub const REGION_DIM: usize = CHUNK_DIM * REGION_CHUNKS; // 256
+/// The height of the surface of oceans and lakes. Needs
+/// to match the water level in the region shader.
+pub const SEA_LEVEL: usize = 64;
 
-const BLOCK_BUFFER_SIZE: u64 = (REGION_DIM * REGION_DIM * REGION_DIM) as u64;
+#[derive(Copy, Clone, Pod, Zeroable)]
+#[repr(C)]
+struct PushConstants {
+    /// Position in blocks of the generated area within the world.
+    offset: [i32; 2],
+    /// Width and depth of the generated area in blocks.
+    output_dim: u32,
+}
 
 #[derive(Default)]
 pub struct Region {
@@ -19,6 +40,12 @@ pub struct Region {
     pub chunks: Box<[[[Chunk; REGION_CHUNKS]; REGION_CHUNKS]; REGION_CHUNKS]>,
 }
 
+// Block indexes output by the region shader.
+// Need to match block definitions in shader/include/blocks.glsl
+pub(crate) const BLOCK_AIR: u8 = 0;
+pub(crate) const BLOCK_STONE: u8 = 1;
+pub(crate) const BLOCK_WATER: u8 = 6;
+
 static BLOCK_LUT: Lazy<Vec<BlockId>> = Lazy::new(|| {
     // Needs to match block definitions in shader/include/blocks.glsl
     vec![
@@ -29,46 +56,153 @@ static BLOCK_LUT: Lazy<Vec<BlockId>> = Lazy::new(|| {
         BlockId::new(blocks::Sand),
         BlockId::new(blocks::Melium),
         BlockId::new(blocks::Water),
+        BlockId::new(blocks::Gravel),
+        BlockId::new(blocks::CoalOre),
+        BlockId::new(blocks::IronOre),
+        BlockId::new(blocks::GoldOre),
     ]
 });
 
+/// Returns the index of the block with the given
+/// slug, if the region shader can output it.
+pub(crate) fn block_index(slug: &str) -> Option<u8> {
+    BLOCK_LUT
+        .iter()
+        .position(|block| block.descriptor().slug() == slug)
+        .map(|index| index as u8)
+}
+
+/// A column of chunks spanning the world's height.
+#[derive(Default)]
+pub struct ChunkColumn {
+    /// Chunks from bottom to top.
+    pub chunks: Box<[Chunk; REGION_CHUNKS]>,
+    /// The biomes the column was generated with.
+    pub biomes: ColumnBiomes,
+}
+
+impl ChunkColumn {
+    pub fn from_gpu_data(data: &[u8]) -> Self {
+        let chunks: Vec<Chunk> = (0..REGION_CHUNKS)
+            .into_par_iter()
+            .map(|chunk_y| chunk_from_gpu_data(data, CHUNK_DIM, [0, chunk_y * CHUNK_DIM, 0]))
+            .collect();
+
+        let mut column = ChunkColumn::default();
+        for (slot, chunk) in column.chunks.iter_mut().zip(chunks) {
+            *slot = chunk;
+        }
+        column
+    }
+
+    /// Sets the block at a position within the column.
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block: BlockId) {
+        self.chunks[y / CHUNK_DIM].set(x, y % CHUNK_DIM, z, block);
+    }
+}
+
 impl Region {
     pub fn from_gpu_data(data: &[u8]) -> Self {
+        // Chunks are converted in parallel, ordered by X, then Y, then Z.
+        let chunks: Vec<Chunk> = (0..REGION_CHUNKS * REGION_CHUNKS * REGION_CHUNKS)
+            .into_par_iter()
+            .map(|index| {
+                let chunk_x = index / (REGION_CHUNKS * REGION_CHUNKS);
+                let chunk_y = index / REGION_CHUNKS % REGION_CHUNKS;
+                let chunk_z = index % REGION_CHUNKS;
+                let min = [
+                    chunk_x * CHUNK_DIM,
+                    chunk_y * CHUNK_DIM,
+                    chunk_z * CHUNK_DIM,
+                ];
+                chunk_from_gpu_data(data, REGION_DIM, min)
+            })
+            .collect();
+
         let mut region = Region::default();
+        let slots = region.chunks.iter_mut().flatten().flatten();
+        for (slot, chunk) in slots.zip(chunks) {
+            *slot = chunk;
+        }
+        region
+    }
 
-        let lut = BLOCK_LUT.as_slice();
-
-        for chunk_x in 0..REGION_CHUNKS {
-            for chunk_z in 0..REGION_CHUNKS {
-                for x in 0..CHUNK_DIM {
-                    for z in 0..CHUNK_DIM {
-                        for y in 0..REGION_DIM {
-                            let x = chunk_x * CHUNK_DIM + x;
-                            let z = chunk_z * CHUNK_DIM + z;
-                            let chunk_y = y / CHUNK_DIM;
-
-                            let block_index =
-                                data[x * REGION_DIM * REGION_DIM + z * REGION_DIM + y];
-                            let block = lut[block_index as usize];
-                            region.chunks[chunk_x][chunk_y][chunk_z].set(
-                                x % CHUNK_DIM,
-                                y % CHUNK_DIM,
-                                z % CHUNK_DIM,
-                                block,
-                            );
-                        }
-                    }
-                }
+    /// Sets the block at a position within the region.
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block: BlockId) {
+        self.chunks[x / CHUNK_DIM][y / CHUNK_DIM][z / CHUNK_DIM].set(
+            x % CHUNK_DIM,
+            y % CHUNK_DIM,
+            z % CHUNK_DIM,
+            block,
+        );
+    }
+}
+
+/// Converts one chunk of block data laid out as output by the region
+/// shader. `area_dim` is the width of the generated area, and `min`
+/// is the position of the chunk's minimum corner within the area.
+///
+/// Counts the blocks of each index first, so each block in the
+/// chunk's palette is looked up once rather than for every block.
+fn chunk_from_gpu_data(data: &[u8], area_dim: usize, min: [usize; 3]) -> Chunk {
+    let mut counts = [0u32; 256];
+    let mut block_indexes = [0u8; CHUNK_VOLUME];
+    for x in 0..CHUNK_DIM {
+        for z in 0..CHUNK_DIM {
+            let start = ((min[0] + x) * area_dim + min[2] + z) * REGION_DIM + min[1];
+            for (y, &block_index) in data[start..start + CHUNK_DIM].iter().enumerate() {
+                block_indexes[Chunk::ordinal(x, y, z)] = block_index;
+                counts[block_index as usize] += 1;
             }
         }
+    }
 
-        region
+    let lut = BLOCK_LUT.as_slice();
+    let mut palette = Vec::new();
+    let mut palette_indexes = [0u64; 256];
+    for (block_index, &count) in counts.iter().enumerate() {
+        if count > 0 {
+            palette_indexes[block_index] = palette.len() as u64;
+            palette.push(lut[block_index]);
+        }
     }
+
+    let indexes = block_indexes
+        .iter()
+        .map(|&block_index| palette_indexes[block_index as usize]);
+    Chunk::from_palette_indexes(palette, indexes).expect("palette covers all blocks")
+}
+
+/// Fills air below [`SEA_LEVEL`] that is open to the sky with water,
+/// turning depressions in the terrain into lakes and seas. Air beneath
+/// the highest block of a column, like a cave, stays dry.
+///
+/// `blocks` is laid out as output by the region shader.
+pub(crate) fn fill_sea(blocks: &mut [u8]) {
+    blocks.par_chunks_mut(REGION_DIM).for_each(|column| {
+        for block in column[..SEA_LEVEL].iter_mut().rev() {
+            if *block != BLOCK_AIR {
+                break;
+            }
+            *block = BLOCK_WATER;
+        }
+    });
 }
 
 pub struct ComputePayload {
     bind_group: wgpu::BindGroup,
     block_buffer: wgpu::Buffer,
+    push_constants: PushConstants,
+}
+
+impl ComputePayload {
+    fn block_buffer_size(&self) -> u64 {
+        block_buffer_size(self.push_constants.output_dim)
+    }
+}
+
+fn block_buffer_size(output_dim: u32) -> u64 {
+    output_dim as u64 * output_dim as u64 * REGION_DIM as u64
 }
 
 pub struct RegionGenerator {
@@ -87,32 +221,74 @@ impl RegionGenerator {
         }
     }
 
-    pub fn prepare(&self, device: &wgpu::Device, biome_grid: &wgpu::Texture) -> ComputePayload {
-        let block_buffer = self.create_block_buffer(device);
-        let bind_group = self.create_bind_group(device, &block_buffer, biome_grid);
+    /// Prepares to generate a region at the origin of the biome grid
+    /// with the blocks of each biome given by `composition`.
+    pub fn prepare(
+        &self,
+        device: &wgpu::Device,
+        biome_grid: &wgpu::Texture,
+        composition: &CompositionTable,
+    ) -> ComputePayload {
+        self.prepare_area(device, biome_grid, composition, [0, 0], REGION_DIM as u32)
+    }
+
+    /// Prepares to generate a single chunk column. `offset` is the
+    /// position in blocks of the column's minimum corner.
+    pub fn prepare_column(
+        &self,
+        device: &wgpu::Device,
+        biome_grid: &wgpu::Texture,
+        composition: &CompositionTable,
+        offset: [i32; 2],
+    ) -> ComputePayload {
+        self.prepare_area(device, biome_grid, composition, offset, CHUNK_DIM as u32)
+    }
+
+    fn prepare_area(
+        &self,
+        device: &wgpu::Device,
+        biome_grid: &wgpu::Texture,
+        composition: &CompositionTable,
+        offset: [i32; 2],
+        output_dim: u32,
+    ) -> ComputePayload {
+        let block_buffer = self.create_block_buffer(device, block_buffer_size(output_dim));
+        let composition_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: composition.as_bytes(),
+            usage: wgpu::BufferUsage::STORAGE,
+        });
+        let bind_group =
+            self.create_bind_group(device, &block_buffer, biome_grid, &composition_buffer);
         ComputePayload {
             block_buffer,
             bind_group,
+            push_constants: PushConstants { offset, output_dim },
         }
     }
 
     pub fn execute<'a>(&'a self, payload: &'a ComputePayload, pass: &mut wgpu::ComputePass<'a>) {
+        let output_dim = payload.push_constants.output_dim;
         pass.set_pipeline(&self.pipeline);
+        pass.set_push_constants(0, bytemuck::cast_slice(&[payload.push_constants]));
         pass.set_bind_group(0, &payload.bind_group, &[]);
-        pass.dispatch(REGION_DIM as u32, 1, REGION_DIM as u32);
+        pass.dispatch(output_dim, 1, output_dim);
     }
 
-    pub async fn load_region_from_gpu(
+    /// Reads back the generated block indexes. The block at
+    /// `(x, y, z)` is at `(x * output_dim + z) * REGION_DIM + y`.
+    pub async fn load_blocks_from_gpu(
         &self,
         payload: &ComputePayload,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         mut encoder: wgpu::CommandEncoder,
-    ) -> Region {
+    ) -> Vec<u8> {
         // We need to copy the block_buffer to a temporary buffer with
         // MAP_READ usage.
-        let temp_buffer = self.create_mappable_temp_buffer(device);
-        encoder.copy_buffer_to_buffer(&payload.block_buffer, 0, &temp_buffer, 0, BLOCK_BUFFER_SIZE);
+        let size = payload.block_buffer_size();
+        let temp_buffer = self.create_mappable_temp_buffer(device, size);
+        encoder.copy_buffer_to_buffer(&payload.block_buffer, 0, &temp_buffer, 0, size);
         queue.submit(iter::once(encoder.finish()));
 
         let block_buffer = temp_buffer.slice(..);
@@ -122,8 +298,7 @@ impl RegionGenerator {
             .expect("failed to map block buffer");
 
         let data = block_buffer.get_mapped_range();
-        let region = Region::from_gpu_data(&data);
-        region
+        data.to_vec()
     }
 
     fn create_bg_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
@@ -152,6 +327,17 @@ impl RegionGenerator {
                     },
                     count: None,
                 },
+                // uComposition
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::StorageBuffer {
+                        dynamic: false,
+                        min_binding_size: None,
+                        readonly: true,
+                    },
+                    count: None,
+                },
             ],
         })
     }
@@ -181,14 +367,17 @@ impl RegionGenerator {
         device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
             bind_group_layouts: &[bg_layout],
-            push_constant_ranges: &[],
+            push_constant_ranges: &[wgpu::PushConstantRange {
+                stages: wgpu::ShaderStage::COMPUTE,
+                range: 0..size_of::<PushConstants>() as u32,
+            }],
         })
     }
 
-    fn create_block_buffer(&self, device: &wgpu::Device) -> wgpu::Buffer {
+    fn create_block_buffer(&self, device: &wgpu::Device, size: u64) -> wgpu::Buffer {
         device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
-            size: BLOCK_BUFFER_SIZE,
+            size,
             usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_SRC,
             mapped_at_creation: false,
         })
@@ -199,6 +388,7 @@ impl RegionGenerator {
         device: &wgpu::Device,
         block_buffer: &wgpu::Buffer,
         biome_grid: &wgpu::Texture,
+        composition_buffer: &wgpu::Buffer,
     ) -> wgpu::BindGroup {
         device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: None,
@@ -214,16 +404,128 @@ impl RegionGenerator {
                         &biome_grid.create_view(&Default::default()),
                     ),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer(composition_buffer.slice(..)),
+                },
             ],
         })
     }
 
-    fn create_mappable_temp_buffer(&self, device: &wgpu::Device) -> wgpu::Buffer {
+    fn create_mappable_temp_buffer(&self, device: &wgpu::Device, size: u64) -> wgpu::Buffer {
         device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
-            size: BLOCK_BUFFER_SIZE,
+            size,
             usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
             mapped_at_creation: false,
         })
     }
 }
+
+/// Parses the `#define`s with `prefix` in a shader include,
+/// returning the lowercase name after the prefix and the value.
+#[cfg(test)]
+pub(crate) fn shader_defines<'a>(
+    source: &'a str,
+    prefix: &'a str,
+) -> impl Iterator<Item = (String, u32)> + 'a {
+    source.lines().filter_map(move |line| {
+        let mut words = line.strip_prefix("#define ")?.split_whitespace();
+        let name = words.next()?.strip_prefix(prefix)?.to_lowercase();
+        Some((name, words.next()?.parse().ok()?))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lut_matches_shader_blocks() {
+        let source = include_str!("../../../assets/shader/include/blocks.glsl");
+        let defines: Vec<_> = shader_defines(source, "BLOCK_").collect();
+        assert_eq!(defines.len(), BLOCK_LUT.len());
+        for (slug, index) in defines {
+            assert_eq!(block_index(&slug), Some(index as u8), "block '{}'", slug);
+        }
+    }
+
+    /// Returns block data for an area `dim` wide with
+    /// a different mix of blocks in each chunk.
+    fn mixed_blocks(dim: usize) -> Vec<u8> {
+        (0..dim * dim * REGION_DIM)
+            .map(|index| {
+                let (column, y) = (index / REGION_DIM, index % REGION_DIM);
+                ((column * 7 + y * 3 + y / CHUNK_DIM * column) % BLOCK_LUT.len()) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn converts_region_data() {
+        let data = mixed_blocks(REGION_DIM);
+        let region = Region::from_gpu_data(&data);
+        for x in (0..REGION_DIM).step_by(5) {
+            for z in (0..REGION_DIM).step_by(3) {
+                for y in 0..REGION_DIM {
+                    let chunk = &region.chunks[x / CHUNK_DIM][y / CHUNK_DIM][z / CHUNK_DIM];
+                    let expected = BLOCK_LUT[data[(x * REGION_DIM + z) * REGION_DIM + y] as usize];
+                    assert_eq!(
+                        chunk.get(x % CHUNK_DIM, y % CHUNK_DIM, z % CHUNK_DIM),
+                        expected
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn converts_column_data() {
+        let data = mixed_blocks(CHUNK_DIM);
+        let column = ChunkColumn::from_gpu_data(&data);
+        for x in 0..CHUNK_DIM {
+            for z in 0..CHUNK_DIM {
+                for y in 0..REGION_DIM {
+                    let chunk = &column.chunks[y / CHUNK_DIM];
+                    let expected = BLOCK_LUT[data[(x * CHUNK_DIM + z) * REGION_DIM + y] as usize];
+                    assert_eq!(chunk.get(x, y % CHUNK_DIM, z), expected);
+                }
+            }
+        }
+
+        // Chunks of a single block have a palette of one.
+        let air = ChunkColumn::from_gpu_data(&vec![BLOCK_AIR; CHUNK_DIM * CHUNK_DIM * REGION_DIM]);
+        assert!(air.chunks.iter().all(Chunk::is_empty));
+    }
+
+    #[test]
+    fn fills_depressions_below_sea_level() {
+        let mut blocks = vec![BLOCK_AIR; 3 * REGION_DIM];
+        let (low, high) = blocks.split_at_mut(REGION_DIM);
+        let (high, cave) = high.split_at_mut(REGION_DIM);
+        for block in &mut low[..40] {
+            *block = BLOCK_STONE;
+        }
+        for block in &mut high[..80] {
+            *block = BLOCK_STONE;
+        }
+        // A cave beneath the ground.
+        for block in &mut cave[..50] {
+            *block = BLOCK_STONE;
+        }
+        for block in &mut cave[20..30] {
+            *block = BLOCK_AIR;
+        }
+
+        fill_sea(&mut blocks);
+        let (low, rest) = blocks.split_at(REGION_DIM);
+        let (high, cave) = rest.split_at(REGION_DIM);
+        assert!(low[40..SEA_LEVEL].iter().all(|&block| block == BLOCK_WATER));
+        assert!(low[SEA_LEVEL..].iter().all(|&block| block == BLOCK_AIR));
+        assert!(!high.contains(&BLOCK_WATER));
+        assert!(cave[20..30].iter().all(|&block| block == BLOCK_AIR));
+        assert!(cave[50..SEA_LEVEL]
+            .iter()
+            .all(|&block| block == BLOCK_WATER));
+    }
+}
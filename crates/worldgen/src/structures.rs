@@ -0,0 +1,663 @@
+//! Structures, like ruins and huts, stamped into generated terrain.
+//!
+//! Structures are placed on a grid of square cells [`StructureSet::spacing`]
+//! blocks wide. A seeded random generator decides whether each cell holds a
+//! structure, which one, and where within the cell its footprint lies, so
+//! structures never overlap and separately generated chunk columns agree on
+//! them. The structure's base then sits on the terrain surface at its
+//! origin, and its blocks are stamped into every chunk its bounding box
+//! touches.
+//!
+//! Most structures are [`Template`]s: blocks stored as a palette of block
+//! slugs and a packed array of palette indexes, loaded from the YAML files
+//! in `assets/worldgen/structures`.
+
+use std::{fmt, sync::Arc};
+
+use anyhow::{bail, Context};
+use common::{block, BlockId, BlockPos};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+use serde::{Deserialize, Serialize};
+use utils::PackedArray;
+
+use crate::{
+    noise::{derive_seed, random, SALT_STRUCTURES},
+    region::{BLOCK_AIR, REGION_DIM, SEA_LEVEL},
+};
+
+/// The built-in structure templates.
+const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    (
+        "ruin",
+        include_str!("../../../assets/worldgen/structures/ruin.yml"),
+    ),
+    (
+        "hut",
+        include_str!("../../../assets/worldgen/structures/hut.yml"),
+    ),
+];
+
+/// A box of blocks. `min` is inclusive and `max` exclusive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub min: BlockPos,
+    pub max: BlockPos,
+}
+
+impl BoundingBox {
+    /// Returns whether the box contains `pos`.
+    pub fn contains(self, pos: BlockPos) -> bool {
+        pos.x >= self.min.x
+            && pos.y >= self.min.y
+            && pos.z >= self.min.z
+            && pos.x < self.max.x
+            && pos.y < self.max.y
+            && pos.z < self.max.z
+    }
+
+    /// Returns whether the two boxes share any blocks.
+    pub fn intersects(self, other: BoundingBox) -> bool {
+        self.min.x < other.max.x
+            && other.min.x < self.max.x
+            && self.min.y < other.max.y
+            && other.min.y < self.max.y
+            && self.min.z < other.max.z
+            && other.min.z < self.max.z
+    }
+}
+
+/// A structure that can be stamped into terrain.
+pub trait Structure: Send + Sync {
+    /// The name of the structure, used in logs and errors.
+    fn name(&self) -> &str;
+
+    /// The size in blocks of the structure's bounding box.
+    fn size(&self) -> [i32; 3];
+
+    /// The number of layers at the bottom of the structure
+    /// placed below the terrain surface, like a foundation.
+    fn buried_layers(&self) -> i32 {
+        0
+    }
+
+    /// Returns the block at `pos`, relative to the minimum corner of the
+    /// bounding box, or `None` to leave the terrain there untouched.
+    fn block(&self, pos: BlockPos) -> Option<BlockId>;
+
+    /// Returns the bounding box of the structure
+    /// when its minimum corner is at `origin`.
+    fn bounding_box(&self, origin: BlockPos) -> BoundingBox {
+        let [x, y, z] = self.size();
+        BoundingBox {
+            min: origin,
+            max: BlockPos {
+                x: origin.x + x,
+                y: origin.y + y,
+                z: origin.z + z,
+            },
+        }
+    }
+}
+
+/// A template file, as stored in YAML.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateFile {
+    size: [u32; 3],
+    #[serde(default)]
+    buried: u32,
+    /// Block slugs. `~` leaves the terrain untouched.
+    palette: Vec<Option<String>>,
+    /// Indexes into the palette, ordered by Y, then Z, then X.
+    blocks: PackedArray,
+}
+
+/// A structure whose blocks are stored as a palette and
+/// a packed array of indexes into the palette.
+///
+/// Palette entries are block slugs, placed in their default state.
+#[derive(Clone)]
+pub struct Template {
+    name: String,
+    size: [i32; 3],
+    buried: i32,
+    palette: Vec<Option<BlockId>>,
+    blocks: PackedArray,
+}
+
+impl Template {
+    /// Creates a template from the blocks of each position
+    /// within its bounding box, ordered by Y, then Z, then X.
+    ///
+    /// # Panics
+    /// Panics if the number of blocks doesn't match `size`.
+    pub fn from_blocks(
+        name: impl Into<String>,
+        size: [u32; 3],
+        buried: u32,
+        blocks: &[Option<BlockId>],
+    ) -> Self {
+        assert_eq!(blocks.len(), volume(size), "wrong number of blocks");
+
+        let mut palette = Vec::new();
+        let indexes: Vec<u64> = blocks
+            .iter()
+            .map(|block| {
+                let block = block.map(|block| BlockId::from_raw_parts(block.kind(), 0));
+                let index = palette
+                    .iter()
+                    .position(|entry| *entry == block)
+                    .unwrap_or_else(|| {
+                        palette.push(block);
+                        palette.len() - 1
+                    });
+                index as u64
+            })
+            .collect();
+        let blocks = PackedArray::from_iter(indexes, bits_for(palette.len()));
+
+        Self {
+            name: name.into(),
+            size: [size[0] as i32, size[1] as i32, size[2] as i32],
+            buried: buried as i32,
+            palette,
+            blocks,
+        }
+    }
+
+    /// Parses a template file.
+    pub fn from_yaml(name: impl Into<String>, yaml: &str) -> anyhow::Result<Self> {
+        let name = name.into();
+        let file: TemplateFile = serde_yaml::from_str(yaml)
+            .with_context(|| format!("malformed structure template '{}'", name))?;
+
+        if file
+            .size
+            .iter()
+            .any(|&side| side == 0 || side > REGION_DIM as u32)
+        {
+            bail!(
+                "structure template '{}' has invalid size {:?}",
+                name,
+                file.size
+            );
+        }
+        if file.buried >= file.size[1] {
+            bail!(
+                "structure template '{}' buries all of its {} layers",
+                name,
+                file.size[1]
+            );
+        }
+        if file.palette.is_empty() {
+            bail!("structure template '{}' has an empty palette", name);
+        }
+
+        let palette = file
+            .palette
+            .iter()
+            .map(|slug| match slug {
+                Some(slug) => block::kind_by_slug(slug)
+                    .map(|kind| Some(BlockId::from_raw_parts(kind, 0)))
+                    .with_context(|| {
+                        format!(
+                            "structure template '{}' uses unknown block '{}'",
+                            name, slug
+                        )
+                    }),
+                None => Ok(None),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // `PackedArray` doesn't check its invariants when deserialized:
+        // a width of zero would divide by zero, and hand-edited files
+        // may hold fewer bits than `length` needs.
+        let bits_per_value = file.blocks.bits_per_value();
+        if bits_per_value == 0 || bits_per_value > 16 {
+            bail!(
+                "structure template '{}' has {} bits per block",
+                name,
+                bits_per_value
+            );
+        }
+        let expected = volume(file.size);
+        if file.blocks.len() != expected || file.blocks.iter().count() != expected {
+            bail!(
+                "structure template '{}' needs {} blocks but has {}",
+                name,
+                expected,
+                file.blocks.iter().count()
+            );
+        }
+        if let Some(index) = file
+            .blocks
+            .iter()
+            .find(|&index| index as usize >= palette.len())
+        {
+            bail!(
+                "structure template '{}' uses palette index {}, but the palette has {} entries",
+                name,
+                index,
+                palette.len()
+            );
+        }
+
+        Ok(Self {
+            name,
+            size: [
+                file.size[0] as i32,
+                file.size[1] as i32,
+                file.size[2] as i32,
+            ],
+            buried: file.buried as i32,
+            palette,
+            blocks: file.blocks,
+        })
+    }
+
+    /// Serializes the template to the YAML format read by [`Template::from_yaml`].
+    pub fn to_yaml(&self) -> anyhow::Result<String> {
+        let file = TemplateFile {
+            size: [
+                self.size[0] as u32,
+                self.size[1] as u32,
+                self.size[2] as u32,
+            ],
+            buried: self.buried as u32,
+            palette: self
+                .palette
+                .iter()
+                .map(|block| block.map(|block| block.descriptor().slug().to_owned()))
+                .collect(),
+            blocks: self.blocks.clone(),
+        };
+        Ok(serde_yaml::to_string(&file)?)
+    }
+}
+
+impl Structure for Template {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn size(&self) -> [i32; 3] {
+        self.size
+    }
+
+    fn buried_layers(&self) -> i32 {
+        self.buried
+    }
+
+    fn block(&self, pos: BlockPos) -> Option<BlockId> {
+        let [size_x, _, size_z] = self.size;
+        let index = (pos.y * size_z + pos.z) * size_x + pos.x;
+        let palette_index = self.blocks.get(index as usize)?;
+        self.palette[palette_index as usize]
+    }
+}
+
+impl fmt::Debug for Template {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Template")
+            .field("name", &self.name)
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+fn volume(size: [u32; 3]) -> usize {
+    size.iter().map(|&side| side as usize).product()
+}
+
+/// Returns the number of bits needed to store indexes into a palette.
+fn bits_for(palette_len: usize) -> usize {
+    let max = palette_len.saturating_sub(1) as u64;
+    (64 - max.leading_zeros() as usize).max(1)
+}
+
+/// A structure chosen to be placed by [`StructureSet::placements`].
+/// Its Y coordinate depends on the terrain.
+#[derive(Clone)]
+pub(crate) struct Placement {
+    pub structure: Arc<dyn Structure>,
+    /// X and Z coordinates of the bounding box's minimum corner.
+    pub origin: [i32; 2],
+}
+
+impl Placement {
+    /// Returns the minimum corner of the structure's bounding box
+    /// when the top block of the terrain at its origin is at `surface_y`.
+    pub fn origin_on(&self, surface_y: usize) -> BlockPos {
+        BlockPos {
+            x: self.origin[0],
+            y: surface_y as i32 + 1 - self.structure.buried_layers(),
+            z: self.origin[1],
+        }
+    }
+
+    /// Stamps the structure into an area of generated blocks whose minimum
+    /// corner is at `offset` and whose width is `area_dim`. Blocks outside
+    /// the area are left for the areas containing them.
+    pub fn stamp(
+        &self,
+        surface_y: usize,
+        offset: [i32; 2],
+        area_dim: usize,
+        mut set_block: impl FnMut(usize, usize, usize, BlockId),
+    ) {
+        let origin = self.origin_on(surface_y);
+        let area = BoundingBox {
+            min: BlockPos {
+                x: offset[0],
+                y: 0,
+                z: offset[1],
+            },
+            max: BlockPos {
+                x: offset[0] + area_dim as i32,
+                y: REGION_DIM as i32,
+                z: offset[1] + area_dim as i32,
+            },
+        };
+
+        let [size_x, size_y, size_z] = self.structure.size();
+        for y in 0..size_y {
+            for z in 0..size_z {
+                for x in 0..size_x {
+                    let pos = BlockPos {
+                        x: origin.x + x,
+                        y: origin.y + y,
+                        z: origin.z + z,
+                    };
+                    if !area.contains(pos) {
+                        continue;
+                    }
+                    if let Some(block) = self.structure.block(BlockPos { x, y, z }) {
+                        set_block(
+                            (pos.x - offset[0]) as usize,
+                            pos.y as usize,
+                            (pos.z - offset[1]) as usize,
+                            block,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the Y coordinate of the top non-air block of a column
+/// laid out as output by the region shader, if structures may be
+/// placed on it. Structures aren't placed in water.
+pub(crate) fn surface_y(column: &[u8]) -> Option<usize> {
+    let top = column.iter().rposition(|&block| block != BLOCK_AIR)?;
+    if top < SEA_LEVEL {
+        None
+    } else {
+        Some(top)
+    }
+}
+
+/// The structures that can be placed in the world and
+/// the parameters of the placement pass.
+#[derive(Clone)]
+pub struct StructureSet {
+    structures: Vec<Arc<dyn Structure>>,
+    spacing: i32,
+    chance: f32,
+}
+
+impl StructureSet {
+    /// Creates a set without structures. Each cell of the placement
+    /// grid is `spacing` blocks wide and holds a structure with
+    /// probability `chance`.
+    pub fn new(spacing: u32, chance: f32) -> Self {
+        assert!(spacing > 0, "structure spacing must be positive");
+        Self {
+            structures: Vec::new(),
+            spacing: spacing as i32,
+            chance,
+        }
+    }
+
+    /// Adds a structure to the set.
+    ///
+    /// Fails if the structure doesn't fit in a cell of the placement grid.
+    pub fn add(&mut self, structure: Arc<dyn Structure>) -> anyhow::Result<()> {
+        let [x, y, z] = structure.size();
+        if x > self.spacing || z > self.spacing || y > REGION_DIM as i32 {
+            bail!(
+                "structure '{}' of size {:?} doesn't fit in a {} block cell",
+                structure.name(),
+                structure.size(),
+                self.spacing
+            );
+        }
+        self.structures.push(structure);
+        Ok(())
+    }
+
+    /// Side length in blocks of the cells structures are placed in.
+    pub fn spacing(&self) -> u32 {
+        self.spacing as u32
+    }
+
+    pub fn structures(&self) -> &[Arc<dyn Structure>] {
+        &self.structures
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.structures.is_empty() || self.chance <= 0.
+    }
+
+    /// Decides which structures overlap the area whose minimum corner is
+    /// at `offset` and whose width is `area_dim`. Placements depend only on
+    /// the seed and their cell, so overlapping areas agree on them.
+    pub(crate) fn placements(
+        &self,
+        seed: u64,
+        offset: [i32; 2],
+        area_dim: usize,
+    ) -> Vec<Placement> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+
+        let seed = derive_seed(seed, SALT_STRUCTURES);
+        let min = [offset[0], offset[1]];
+        let max = [offset[0] + area_dim as i32, offset[1] + area_dim as i32];
+        let min_cell = [
+            min[0].div_euclid(self.spacing),
+            min[1].div_euclid(self.spacing),
+        ];
+        let max_cell = [
+            (max[0] - 1).div_euclid(self.spacing),
+            (max[1] - 1).div_euclid(self.spacing),
+        ];
+
+        let mut placements = Vec::new();
+        for cell_x in min_cell[0]..=max_cell[0] {
+            for cell_z in min_cell[1]..=max_cell[1] {
+                let placement = match self.place_in_cell(seed, cell_x, cell_z) {
+                    Some(placement) => placement,
+                    None => continue,
+                };
+                let [size_x, _, size_z] = placement.structure.size();
+                let origin = placement.origin;
+                if origin[0] < max[0]
+                    && origin[1] < max[1]
+                    && origin[0] + size_x > min[0]
+                    && origin[1] + size_z > min[1]
+                {
+                    placements.push(placement);
+                }
+            }
+        }
+        placements
+    }
+
+    fn place_in_cell(&self, seed: u32, cell_x: i32, cell_z: i32) -> Option<Placement> {
+        let cell_seed = random(
+            random(seed.wrapping_add(cell_x as u32), cell_z as u32),
+            seed,
+        );
+        let mut rng = Pcg64Mcg::seed_from_u64(cell_seed as u64);
+        if rng.gen::<f32>() >= self.chance {
+            return None;
+        }
+
+        let structure = &self.structures[rng.gen_range(0, self.structures.len())];
+        let [size_x, _, size_z] = structure.size();
+        let x = rng.gen_range(0, self.spacing - size_x + 1);
+        let z = rng.gen_range(0, self.spacing - size_z + 1);
+        Some(Placement {
+            structure: Arc::clone(structure),
+            origin: [cell_x * self.spacing + x, cell_z * self.spacing + z],
+        })
+    }
+}
+
+impl Default for StructureSet {
+    /// The built-in templates, placed in cells 64 blocks wide.
+    fn default() -> Self {
+        let mut set = Self::new(64, 0.25);
+        for (name, yaml) in DEFAULT_TEMPLATES {
+            let template = Template::from_yaml(*name, yaml).expect("invalid built-in template");
+            set.add(Arc::new(template))
+                .expect("built-in template too large");
+        }
+        set
+    }
+}
+
+impl fmt::Debug for StructureSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<_> = self.structures.iter().map(|s| s.name()).collect();
+        f.debug_struct("StructureSet")
+            .field("structures", &names)
+            .field("spacing", &self.spacing)
+            .field("chance", &self.chance)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::blocks::{Gravel, Log, Stone};
+
+    use super::*;
+
+    fn stone() -> Option<BlockId> {
+        Some(BlockId::new(Stone))
+    }
+
+    #[test]
+    fn built_in_templates_load() {
+        let set = StructureSet::default();
+        assert_eq!(set.structures().len(), DEFAULT_TEMPLATES.len());
+    }
+
+    #[test]
+    fn templates_roundtrip_through_yaml() {
+        let blocks = [
+            stone(),
+            None,
+            Some(BlockId::new(Gravel)),
+            stone(),
+            Some(BlockId::new(Log)),
+            None,
+            stone(),
+            stone(),
+        ];
+        let template = Template::from_blocks("test", [2, 2, 2], 1, &blocks);
+        let parsed = Template::from_yaml("test", &template.to_yaml().unwrap()).unwrap();
+        assert_eq!(parsed.buried_layers(), 1);
+        for (index, &block) in blocks.iter().enumerate() {
+            let pos = BlockPos {
+                x: index as i32 % 2,
+                z: index as i32 / 2 % 2,
+                y: index as i32 / 4,
+            };
+            assert_eq!(parsed.block(pos), block, "at {:?}", pos);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        let valid = "size: [1, 2, 1]\npalette: [stone]\nblocks:\n  length: 2\n  bits_per_value: 1\n  bits: [0]\n";
+        assert!(Template::from_yaml("valid", valid).is_ok());
+
+        for yaml in &[
+            valid.replace("stone", "unobtainium"),
+            valid.replace("[1, 2, 1]", "[2, 2, 1]"),
+            valid.replace("[1, 2, 1]", "[1, 2, 1]\nburied: 2"),
+            valid.replace("bits: [0]", "bits: [2]"),
+            valid.replace("bits: [0]", "bits: []"),
+            valid.replace("[stone]", "[]"),
+        ] {
+            assert!(Template::from_yaml("invalid", yaml).is_err(), "{}", yaml);
+        }
+    }
+
+    #[test]
+    fn placements_agree_across_areas() {
+        let set = StructureSet::default();
+        let seed = 1234;
+        let origins = |offset: [i32; 2], dim: usize| -> Vec<[i32; 2]> {
+            set.placements(seed, offset, dim)
+                .into_iter()
+                .map(|placement| placement.origin)
+                .collect()
+        };
+
+        let region = origins([0, 0], 256);
+        assert!(!region.is_empty());
+        let mut from_columns: Vec<_> = Vec::new();
+        for x in (0..256).step_by(16) {
+            for z in (0..256).step_by(16) {
+                for origin in origins([x, z], 16) {
+                    if !from_columns.contains(&origin) {
+                        from_columns.push(origin);
+                    }
+                }
+            }
+        }
+        from_columns.sort();
+        let mut region = region;
+        region.sort();
+        assert_eq!(region, from_columns);
+    }
+
+    #[test]
+    fn stamps_across_area_boundaries() {
+        let mut set = StructureSet::new(16, 1.);
+        set.add(Arc::new(Template::from_blocks(
+            "pillar",
+            [4, 3, 4],
+            1,
+            &[stone(); 48],
+        )))
+        .unwrap();
+
+        let surface = 70;
+        let mut stamped = Vec::new();
+        for x in (-32..32).step_by(8) {
+            for z in (-32..32).step_by(8) {
+                for placement in set.placements(5, [x, z], 8) {
+                    placement.stamp(surface, [x, z], 8, |bx, by, bz, block| {
+                        stamped.push((x + bx as i32, by, z + bz as i32, block));
+                    });
+                }
+            }
+        }
+
+        // Every cell holds a structure, each stamped exactly once.
+        assert_eq!(stamped.len(), 16 * 48);
+        stamped.sort_by_key(|&(x, y, z, _)| (x, y, z));
+        stamped.dedup_by_key(|&mut (x, y, z, _)| (x, y, z));
+        assert_eq!(stamped.len(), 16 * 48);
+        assert!(stamped
+            .iter()
+            .all(|&(_, y, _, _)| y >= surface && y < surface + 3));
+    }
+}
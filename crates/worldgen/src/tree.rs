@@ -0,0 +1,81 @@
+//! Tree templates, which saplings grow into at runtime.
+//!
+//! World generation doesn't place trees yet. The templates live here
+//! so that it can share them with saplings once it does.
+
+use common::{
+    blocks::{Leaves, Log},
+    BlockId, BlockPos,
+};
+use rand::Rng;
+
+/// The range of trunk heights of random trees.
+pub const MIN_TRUNK_HEIGHT: i32 = 4;
+pub const MAX_TRUNK_HEIGHT: i32 = 6;
+
+/// A tree's shape: a trunk of logs topped with leaves.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tree {
+    trunk_height: i32,
+}
+
+impl Tree {
+    /// Creates a tree with a trunk of the given height.
+    pub fn new(trunk_height: i32) -> Self {
+        Self { trunk_height }
+    }
+
+    /// Creates a tree with a random trunk height.
+    pub fn random(rng: &mut impl Rng) -> Self {
+        Self::new(rng.gen_range(MIN_TRUNK_HEIGHT, MAX_TRUNK_HEIGHT + 1))
+    }
+
+    /// Returns the blocks of the tree relative to the bottom of its
+    /// trunk. Each position appears once.
+    pub fn blocks(self) -> Vec<(BlockPos, BlockId)> {
+        let mut blocks = Vec::new();
+        let top = self.trunk_height - 1;
+        for y in 0..self.trunk_height {
+            blocks.push((BlockPos { x: 0, y, z: 0 }, BlockId::new(Log)));
+        }
+
+        // Two wide layers of leaves around the top of the
+        // trunk, then two narrow layers above it.
+        for (y, radius) in [(top - 1, 2), (top, 2), (top + 1, 1), (top + 2, 1)]
+            .iter()
+            .copied()
+        {
+            for x in -radius..=radius {
+                for z in -radius..=radius {
+                    let is_trunk = x == 0 && z == 0 && y <= top;
+                    // Round off the corners of wide layers.
+                    let is_corner = radius > 1 && x.abs() == radius && z.abs() == radius;
+                    // The top layer is a plus sign.
+                    let is_top_corner = y == top + 2 && x != 0 && z != 0;
+                    if !is_trunk && !is_corner && !is_top_corner {
+                        blocks.push((BlockPos { x, y, z }, BlockId::new(Leaves)));
+                    }
+                }
+            }
+        }
+        blocks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn blocks_are_unique_and_rooted_at_trunk() {
+        for height in MIN_TRUNK_HEIGHT..=MAX_TRUNK_HEIGHT {
+            let blocks = Tree::new(height).blocks();
+            let positions: HashSet<_> = blocks.iter().map(|&(pos, _)| pos).collect();
+            assert_eq!(positions.len(), blocks.len());
+            assert!(blocks.contains(&(BlockPos::default(), BlockId::new(Log))));
+            assert!(blocks.iter().all(|&(pos, _)| pos.y >= 0));
+        }
+    }
+}
@@ -0,0 +1,20 @@
+#!/bin/bash
+# Builds the web client into web/dist. Serve that directory over
+# HTTP and open index.html in a browser supporting WebGPU.
+#
+# Requires the wasm32-unknown-unknown target and wasm-bindgen-cli.
+
+set -e
+
+dist=web/dist
+
+# wgpu's WebGPU backend uses unstable web-sys APIs.
+RUSTFLAGS=--cfg=web_sys_unstable_apis cargo build -p client --release --target wasm32-unknown-unknown
+wasm-bindgen --target web --no-typescript --out-dir ${dist} target/wasm32-unknown-unknown/release/client.wasm
+
+cp web/index.html ${dist}/
+rm -r ${dist}/assets || true
+cp -r assets ${dist}/assets
+
+# Browsers can't list directories, so the client fetches the files listed here.
+(cd ${dist}/assets && find . -type f ! -name manifest.txt | sed 's|^\./||' | sort > manifest.txt)